@@ -18,6 +18,19 @@ pub enum MathInstruction {
         /// calculated
         radicand: u64,
     },
+    /// Raise a fraction to a fractional power with PreciseNumber
+    ///
+    /// No accounts required for this instruction
+    PrecisePowFraction {
+        /// Numerator of the base
+        base_numerator: u64,
+        /// Denominator of the base
+        base_denominator: u64,
+        /// Numerator of the exponent
+        exponent_numerator: u64,
+        /// Denominator of the exponent
+        exponent_denominator: u64,
+    },
     /// Calculate the integer square root of the given u64
     ///
     /// No accounts required for this instruction
@@ -78,6 +91,27 @@ pub fn precise_sqrt(radicand: u64) -> Instruction {
     }
 }
 
+/// Create PrecisePowFraction instruction
+pub fn precise_pow_fraction(
+    base_numerator: u64,
+    base_denominator: u64,
+    exponent_numerator: u64,
+    exponent_denominator: u64,
+) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![],
+        data: MathInstruction::PrecisePowFraction {
+            base_numerator,
+            base_denominator,
+            exponent_numerator,
+            exponent_denominator,
+        }
+        .try_to_vec()
+        .unwrap(),
+    }
+}
+
 /// Create SquareRoot instruction
 pub fn sqrt_u64(radicand: u64) -> Instruction {
     Instruction {
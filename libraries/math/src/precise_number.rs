@@ -274,16 +274,24 @@ impl PreciseNumber {
 
     /// Get the power of a number, where the exponent is expressed as a fraction
     /// (numerator / denominator)
-    /// NOTE: this function is private because its accurate range and precision
-    /// have not been estbalished.
-    #[allow(dead_code)]
-    fn checked_pow_fraction(&self, exponent: &Self) -> Option<Self> {
-        assert!(self.value >= Self::min_pow_base());
-        assert!(self.value <= Self::max_pow_base());
+    /// Returns None if the base is outside of the approximation range, which
+    /// is (0, 2], since the Taylor series approximation does not converge
+    /// outside of it.
+    /// NOTE: the precision of this function has only been established for
+    /// bases between 2/3 and 3/2 with exponents up to 99, where the result is
+    /// within 2e-10 of the exact power, see `test_pow_fraction_precision`.
+    /// The approximation converges more slowly, and costs more compute, as
+    /// the base moves away from 1.
+    pub fn checked_pow_fraction(&self, exponent: &Self) -> Option<Self> {
+        if self.value < Self::min_pow_base() || self.value > Self::max_pow_base() {
+            return None;
+        }
         let whole_exponent = exponent.floor()?;
         let precise_whole = self.checked_pow(whole_exponent.to_imprecise()?)?;
         let (remainder_exponent, negative) = exponent.unsigned_sub(&whole_exponent);
-        assert!(!negative);
+        if negative {
+            return None;
+        }
         if remainder_exponent.value == InnerUint::from(0) {
             return Some(precise_whole);
         }
@@ -443,6 +451,92 @@ mod tests {
         ); // 8.629769290
     }
 
+    #[test]
+    fn test_pow_fraction_out_of_range() {
+        let exponent = PreciseNumber::new(1)
+            .unwrap()
+            .checked_div(&PreciseNumber::new(3).unwrap())
+            .unwrap();
+        assert!(PreciseNumber::new(0)
+            .unwrap()
+            .checked_pow_fraction(&exponent)
+            .is_none());
+        assert!(PreciseNumber::new(3)
+            .unwrap()
+            .checked_pow_fraction(&exponent)
+            .is_none());
+        assert!(PreciseNumber::new(2)
+            .unwrap()
+            .checked_pow_fraction(&exponent)
+            .is_some());
+    }
+
+    fn check_pow_fraction_precision(base: &PreciseNumber, exponent: &PreciseNumber) {
+        // within 2e-10 of the exact power
+        let precision = 200.0;
+        let power = base.checked_pow_fraction(exponent).unwrap();
+        let expected = (base.value.as_u128() as f64 / ONE as f64)
+            .powf(exponent.value.as_u128() as f64 / ONE as f64)
+            * ONE as f64;
+        let error = (power.value.as_u128() as f64 - expected).abs();
+        assert!(
+            error < precision,
+            "{:?} ^ {:?} is off by {}",
+            base.value,
+            exponent.value,
+            error
+        );
+    }
+
+    #[test]
+    fn test_pow_fraction_precision() {
+        let one = PreciseNumber::new(1).unwrap();
+        let two_thirds = PreciseNumber::new(2)
+            .unwrap()
+            .checked_div(&PreciseNumber::new(3).unwrap())
+            .unwrap();
+        let three_halves = PreciseNumber::new(3)
+            .unwrap()
+            .checked_div(&PreciseNumber::new(2).unwrap())
+            .unwrap();
+
+        // bases in [2/3, 1] with exponents up to 99, as used for weighted swaps
+        let step = one.checked_sub(&two_thirds).unwrap().value / 50;
+        let mut bases: Vec<PreciseNumber> = (0..50u64)
+            .map(|i| PreciseNumber {
+                value: one.value - step * i,
+            })
+            .collect();
+        bases.push(two_thirds);
+        let mut exponents: Vec<PreciseNumber> = (1..991)
+            .step_by(7)
+            .map(|i| PreciseNumber {
+                value: InnerUint::from(ONE / 10 * i),
+            })
+            .collect();
+        exponents.push(PreciseNumber::new(99).unwrap());
+        for base in bases.iter() {
+            for exponent in exponents.iter() {
+                check_pow_fraction_precision(base, exponent);
+            }
+        }
+
+        // bases in [1, 3/2] with exponents below 1, as used for weighted
+        // single-sided deposits and withdrawals
+        let step = three_halves.checked_sub(&one).unwrap().value / 50;
+        for i in 0..=50u64 {
+            let base = PreciseNumber {
+                value: one.value + step * i,
+            };
+            for j in 1..100u128 {
+                let exponent = PreciseNumber {
+                    value: InnerUint::from(ONE / 100 * j),
+                };
+                check_pow_fraction_precision(&base, &exponent);
+            }
+        }
+    }
+
     #[test]
     fn test_newtonian_approximation() {
         // square root
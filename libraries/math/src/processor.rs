@@ -21,6 +21,29 @@ pub fn process_instruction(
             msg!("{}", result);
             Ok(())
         }
+        MathInstruction::PrecisePowFraction {
+            base_numerator,
+            base_denominator,
+            exponent_numerator,
+            exponent_denominator,
+        } => {
+            msg!("Calculating fractional power using PreciseNumber");
+            let base = PreciseNumber::new(base_numerator as u128)
+                .unwrap()
+                .checked_div(&PreciseNumber::new(base_denominator as u128).unwrap())
+                .unwrap();
+            let exponent = PreciseNumber::new(exponent_numerator as u128)
+                .unwrap()
+                .checked_div(&PreciseNumber::new(exponent_denominator as u128).unwrap())
+                .unwrap();
+            let result = base
+                .checked_pow_fraction(&exponent)
+                .unwrap()
+                .value
+                .as_u128() as u64;
+            msg!("{}", result);
+            Ok(())
+        }
         MathInstruction::SquareRootU64 { radicand } => {
             msg!("Calculating u64 square root");
             let result = sqrt(radicand).unwrap();
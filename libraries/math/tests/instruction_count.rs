@@ -42,6 +42,45 @@ async fn test_precise_sqrt_u32_max() {
     banks_client.process_transaction(transaction).await.unwrap();
 }
 
+#[tokio::test]
+async fn test_precise_pow_fraction_swap() {
+    let mut pc = ProgramTest::new("spl_math", id(), processor!(process_instruction));
+
+    // Slowest weighted pool trade: the base is 2/3 when adding half of the
+    // source holdings, and the exponent is the ratio of the token weights.
+    // A trade only raises one power whatever the number of tokens in the
+    // pool, and must leave room for the token transfers within the default
+    // budget of 200_000.
+    pc.set_bpf_compute_max_units(150_000);
+
+    let (mut banks_client, payer, recent_blockhash) = pc.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::precise_pow_fraction(2, 3, 190, 3)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_precise_pow_fraction_deposit() {
+    let mut pc = ProgramTest::new("spl_math", id(), processor!(process_instruction));
+
+    // Slowest weighted pool single-sided deposit or withdrawal, adding half of
+    // the holdings of a token worth half of the pool
+    pc.set_bpf_compute_max_units(150_000);
+
+    let (mut banks_client, payer, recent_blockhash) = pc.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::precise_pow_fraction(3, 2, 1, 2)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
 #[tokio::test]
 async fn test_sqrt_u64() {
     let mut pc = ProgramTest::new("spl_math", id(), processor!(process_instruction));
@@ -1,6 +1,5 @@
 use {
     bincode::deserialize,
-    borsh::BorshDeserialize,
     solana_account_decoder::UiAccountEncoding,
     solana_client::{
         client_error::ClientError,
@@ -23,7 +22,7 @@ pub fn get_stake_pool(
     stake_pool_address: &Pubkey,
 ) -> Result<StakePool, Error> {
     let account_data = rpc_client.get_account_data(stake_pool_address)?;
-    let stake_pool = StakePool::try_from_slice(account_data.as_slice())
+    let stake_pool = try_from_slice_unchecked::<StakePool>(account_data.as_slice())
         .map_err(|err| format!("Invalid stake pool {}: {}", stake_pool_address, err))?;
     Ok(stake_pool)
 }
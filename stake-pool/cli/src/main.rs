@@ -2,9 +2,15 @@
 extern crate lazy_static;
 
 mod client;
+mod rebalance;
 
 use {
-    crate::client::*,
+    crate::{
+        client::*,
+        rebalance::{
+            compute_stake_changes, read_weights, StakeChange, StakeLimits, Strategy, ValidatorState,
+        },
+    },
     clap::{
         crate_description, crate_name, crate_version, value_t, value_t_or_exit, App, AppSettings,
         Arg, ArgGroup, SubCommand,
@@ -29,11 +35,11 @@ use {
         self,
         borsh::get_instance_packed_len,
         find_deposit_authority_program_address, find_stake_program_address,
-        find_withdraw_authority_program_address,
+        find_transient_stake_program_address, find_withdraw_authority_program_address,
         stake_program::{self, StakeAuthorize, StakeState},
         state::{StakePool, ValidatorList},
     },
-    std::process::exit,
+    std::{collections::HashMap, process::exit, str::FromStr},
 };
 
 struct Config {
@@ -99,6 +105,9 @@ fn command_create_pool(
     fee: spl_stake_pool::instruction::Fee,
     max_validators: u32,
 ) -> CommandResult {
+    let reserve_stake = Keypair::new();
+    println!("Creating reserve stake {}", reserve_stake.pubkey());
+
     let mint_account = Keypair::new();
     println!("Creating mint {}", mint_account.pubkey());
 
@@ -113,6 +122,9 @@ fn command_create_pool(
 
     let validator_list = Keypair::new();
 
+    let reserve_stake_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?;
     let mint_account_balance = config
         .rpc_client
         .get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)?;
@@ -127,7 +139,8 @@ fn command_create_pool(
     let validator_list_balance = config
         .rpc_client
         .get_minimum_balance_for_rent_exemption(validator_list_size)?;
-    let total_rent_free_balances = mint_account_balance
+    let total_rent_free_balances = reserve_stake_balance
+        + mint_account_balance
         + pool_fee_account_balance
         + stake_pool_account_lamports
         + validator_list_balance;
@@ -144,24 +157,55 @@ fn command_create_pool(
         println!("Stake pool withdraw authority {}", withdraw_authority);
     }
 
-    let mut transaction = Transaction::new_with_payer(
+    let mut setup_instructions = stake_program::create_account(
+        &config.fee_payer.pubkey(),
+        &reserve_stake.pubkey(),
+        &stake_program::Authorized {
+            staker: withdraw_authority,
+            withdrawer: withdraw_authority,
+        },
+        &stake_program::Lockup::default(),
+        reserve_stake_balance,
+    );
+    setup_instructions.extend(vec![
+        // Account for the stake pool mint
+        system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            &mint_account.pubkey(),
+            mint_account_balance,
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        // Account for the pool fee accumulation
+        system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            &pool_fee_account.pubkey(),
+            pool_fee_account_balance,
+            spl_token::state::Account::LEN as u64,
+            &spl_token::id(),
+        ),
+        // Initialize pool token mint account
+        spl_token::instruction::initialize_mint(
+            &spl_token::id(),
+            &mint_account.pubkey(),
+            &withdraw_authority,
+            None,
+            default_decimals,
+        )?,
+        // Initialize fee receiver account
+        spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            &pool_fee_account.pubkey(),
+            &mint_account.pubkey(),
+            &config.manager.pubkey(),
+        )?,
+    ]);
+    let mut setup_transaction =
+        Transaction::new_with_payer(&setup_instructions, Some(&config.fee_payer.pubkey()));
+
+    // The pool and its accounts no longer fit into a single transaction
+    let mut initialize_transaction = Transaction::new_with_payer(
         &[
-            // Account for the stake pool mint
-            system_instruction::create_account(
-                &config.fee_payer.pubkey(),
-                &mint_account.pubkey(),
-                mint_account_balance,
-                spl_token::state::Mint::LEN as u64,
-                &spl_token::id(),
-            ),
-            // Account for the pool fee accumulation
-            system_instruction::create_account(
-                &config.fee_payer.pubkey(),
-                &pool_fee_account.pubkey(),
-                pool_fee_account_balance,
-                spl_token::state::Account::LEN as u64,
-                &spl_token::id(),
-            ),
             // Account for the stake pool
             system_instruction::create_account(
                 &config.fee_payer.pubkey(),
@@ -178,21 +222,6 @@ fn command_create_pool(
                 validator_list_size as u64,
                 &spl_stake_pool::id(),
             ),
-            // Initialize pool token mint account
-            spl_token::instruction::initialize_mint(
-                &spl_token::id(),
-                &mint_account.pubkey(),
-                &withdraw_authority,
-                None,
-                default_decimals,
-            )?,
-            // Initialize fee receiver account
-            spl_token::instruction::initialize_account(
-                &spl_token::id(),
-                &pool_fee_account.pubkey(),
-                &mint_account.pubkey(),
-                &config.manager.pubkey(),
-            )?,
             // Initialize stake pool account
             spl_stake_pool::instruction::initialize(
                 &spl_stake_pool::id(),
@@ -200,6 +229,7 @@ fn command_create_pool(
                 &config.manager.pubkey(),
                 &config.staker.pubkey(),
                 &validator_list.pubkey(),
+                &reserve_stake.pubkey(),
                 &mint_account.pubkey(),
                 &pool_fee_account.pubkey(),
                 &spl_token::id(),
@@ -213,19 +243,29 @@ fn command_create_pool(
     let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
     check_fee_payer_balance(
         config,
-        total_rent_free_balances + fee_calculator.calculate_fee(&transaction.message()),
+        total_rent_free_balances
+            + fee_calculator.calculate_fee(&setup_transaction.message())
+            + fee_calculator.calculate_fee(&initialize_transaction.message()),
     )?;
-    let mut signers = vec![
+    let mut setup_signers = vec![
         config.fee_payer.as_ref(),
-        &stake_pool_keypair,
-        &validator_list,
         &mint_account,
         &pool_fee_account,
+        &reserve_stake,
+    ];
+    unique_signers!(setup_signers);
+    setup_transaction.sign(&setup_signers, recent_blockhash);
+    send_transaction(&config, setup_transaction)?;
+
+    let mut initialize_signers = vec![
+        config.fee_payer.as_ref(),
+        &stake_pool_keypair,
+        &validator_list,
         config.manager.as_ref(),
     ];
-    unique_signers!(signers);
-    transaction.sign(&signers, recent_blockhash);
-    send_transaction(&config, transaction)?;
+    unique_signers!(initialize_signers);
+    initialize_transaction.sign(&initialize_signers, recent_blockhash);
+    send_transaction(&config, initialize_transaction)?;
     Ok(())
 }
 
@@ -472,6 +512,270 @@ where
     Ok(result)
 }
 
+fn command_increase_validator_stake(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    vote_account: &Pubkey,
+    lamports: f64,
+) -> CommandResult {
+    let lamports = native_token::sol_to_lamports(lamports);
+    if !config.no_update {
+        command_update(config, stake_pool_address)?;
+    }
+
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+    let (validator_stake_address, _) =
+        find_stake_program_address(&spl_stake_pool::id(), vote_account, stake_pool_address);
+    let (transient_stake_address, _) =
+        find_transient_stake_program_address(&spl_stake_pool::id(), &validator_stake_address);
+
+    println!(
+        "Increasing stake on {} by {} through {}",
+        vote_account,
+        Sol(lamports),
+        transient_stake_address
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::increase_validator_stake(
+            &spl_stake_pool::id(),
+            stake_pool_address,
+            &config.staker.pubkey(),
+            &pool_withdraw_authority,
+            &stake_pool.validator_list,
+            &stake_pool.reserve_stake,
+            &transient_stake_address,
+            vote_account,
+            lamports,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_decrease_validator_stake(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    vote_account: &Pubkey,
+    lamports: f64,
+) -> CommandResult {
+    let lamports = native_token::sol_to_lamports(lamports);
+    if !config.no_update {
+        command_update(config, stake_pool_address)?;
+    }
+
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+    let (validator_stake_address, _) =
+        find_stake_program_address(&spl_stake_pool::id(), vote_account, stake_pool_address);
+    let (transient_stake_address, _) =
+        find_transient_stake_program_address(&spl_stake_pool::id(), &validator_stake_address);
+
+    println!(
+        "Decreasing stake on {} by {} through {}",
+        vote_account,
+        Sol(lamports),
+        transient_stake_address
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::decrease_validator_stake(
+            &spl_stake_pool::id(),
+            stake_pool_address,
+            &config.staker.pubkey(),
+            &pool_withdraw_authority,
+            &stake_pool.validator_list,
+            &validator_stake_address,
+            &transient_stake_address,
+            lamports,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_rebalance(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    strategy: &Strategy,
+) -> CommandResult {
+    if !config.no_update {
+        command_update(config, stake_pool_address)?;
+    }
+
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+
+    // Commission and delinquency of every vote account on the cluster
+    let vote_account_status = config.rpc_client.get_vote_accounts()?;
+    let mut vote_accounts: HashMap<Pubkey, (u8, bool)> = HashMap::new();
+    for (vote_account_infos, delinquent) in &[
+        (&vote_account_status.current, false),
+        (&vote_account_status.delinquent, true),
+    ] {
+        for vote_account_info in vote_account_infos.iter() {
+            let vote_account = Pubkey::from_str(&vote_account_info.vote_pubkey)?;
+            vote_accounts.insert(vote_account, (vote_account_info.commission, *delinquent));
+        }
+    }
+
+    let mut validators: Vec<ValidatorState> = vec![];
+    for validator in &validator_list.validators {
+        let (validator_stake_address, _) = find_stake_program_address(
+            &spl_stake_pool::id(),
+            &validator.vote_account,
+            stake_pool_address,
+        );
+        let (transient_stake_address, _) =
+            find_transient_stake_program_address(&spl_stake_pool::id(), &validator_stake_address);
+        let vote_account_info = vote_accounts.get(&validator.vote_account);
+        validators.push(ValidatorState {
+            vote_account: validator.vote_account,
+            stake_lamports: validator.stake_lamports,
+            stake_account_lamports: config.rpc_client.get_balance(&validator_stake_address)?,
+            commission: vote_account_info.map(|(commission, _)| *commission),
+            delinquent: vote_account_info.map_or(true, |(_, delinquent)| *delinquent),
+            has_transient_stake: config
+                .rpc_client
+                .get_account_with_commitment(
+                    &transient_stake_address,
+                    config.rpc_client.commitment(),
+                )?
+                .value
+                .is_some(),
+        });
+    }
+
+    let targets = strategy.target_stakes(&validators, stake_pool.total_stake_lamports)?;
+    for (validator, target) in validators.iter().zip(targets.iter()) {
+        println!(
+            "Validator Vote Account: {}\tBalance: {}\tTarget: {}{}",
+            validator.vote_account,
+            Sol(validator.stake_lamports),
+            Sol(*target),
+            if validator.has_transient_stake {
+                " [TRANSIENT STAKE PENDING]"
+            } else {
+                ""
+            }
+        );
+    }
+
+    let stake_rent = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?;
+    let limits = StakeLimits {
+        stake_rent,
+        min_increase_lamports: stake_rent + *MIN_STAKE_BALANCE,
+        min_validator_lamports: stake_rent + *MIN_STAKE_BALANCE,
+    };
+    let reserve_lamports = get_reserve_available_lamports(&config.rpc_client, &stake_pool)?;
+    let changes = compute_stake_changes(&validators, &targets, reserve_lamports, &limits);
+    if changes.is_empty() {
+        println!("Stake pool is balanced, no change required");
+        return Ok(());
+    }
+
+    let mut instructions: Vec<Instruction> = vec![];
+    for change in changes {
+        match change {
+            StakeChange::Increase {
+                vote_account,
+                lamports,
+            } => {
+                let (validator_stake_address, _) = find_stake_program_address(
+                    &spl_stake_pool::id(),
+                    &vote_account,
+                    stake_pool_address,
+                );
+                let (transient_stake_address, _) = find_transient_stake_program_address(
+                    &spl_stake_pool::id(),
+                    &validator_stake_address,
+                );
+                println!(
+                    "Increasing stake on {} by {} through {}",
+                    vote_account,
+                    Sol(lamports),
+                    transient_stake_address
+                );
+                instructions.push(spl_stake_pool::instruction::increase_validator_stake(
+                    &spl_stake_pool::id(),
+                    stake_pool_address,
+                    &config.staker.pubkey(),
+                    &pool_withdraw_authority,
+                    &stake_pool.validator_list,
+                    &stake_pool.reserve_stake,
+                    &transient_stake_address,
+                    &vote_account,
+                    lamports,
+                )?);
+            }
+            StakeChange::Decrease {
+                vote_account,
+                lamports,
+            } => {
+                let (validator_stake_address, _) = find_stake_program_address(
+                    &spl_stake_pool::id(),
+                    &vote_account,
+                    stake_pool_address,
+                );
+                let (transient_stake_address, _) = find_transient_stake_program_address(
+                    &spl_stake_pool::id(),
+                    &validator_stake_address,
+                );
+                println!(
+                    "Decreasing stake on {} by {} through {}",
+                    vote_account,
+                    Sol(lamports),
+                    transient_stake_address
+                );
+                instructions.push(spl_stake_pool::instruction::decrease_validator_stake(
+                    &spl_stake_pool::id(),
+                    stake_pool_address,
+                    &config.staker.pubkey(),
+                    &pool_withdraw_authority,
+                    &stake_pool.validator_list,
+                    &validator_stake_address,
+                    &transient_stake_address,
+                    lamports,
+                )?);
+            }
+        }
+    }
+
+    for instruction in instructions {
+        let mut transaction =
+            Transaction::new_with_payer(&[instruction], Some(&config.fee_payer.pubkey()));
+
+        let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+        check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+        let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+        unique_signers!(signers);
+        transaction.sign(&signers, recent_blockhash);
+        send_transaction(&config, transaction)?;
+    }
+    Ok(())
+}
+
 fn command_deposit(
     config: &Config,
     stake_pool_address: &Pubkey,
@@ -561,7 +865,6 @@ fn command_deposit(
             &stake,
             &validator_stake_account,
             &token_receiver,
-            &stake_pool.manager_fee_account,
             &stake_pool.pool_mint,
             &spl_token::id(),
         )?,
@@ -581,6 +884,95 @@ fn command_deposit(
     Ok(())
 }
 
+fn command_deposit_sol(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    amount: f64,
+    token_receiver: &Option<Pubkey>,
+    sol_deposit_authority: Option<&dyn Signer>,
+) -> CommandResult {
+    let amount = native_token::sol_to_lamports(amount);
+    if !config.no_update {
+        command_update(config, stake_pool_address)?;
+    }
+
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    if stake_pool.sol_deposit_authority.is_some() && sol_deposit_authority.is_none() {
+        return Err("Stake pool requires a SOL deposit authority to sign deposits".into());
+    }
+
+    let mut instructions: Vec<Instruction> = vec![];
+    let mut signers = vec![config.fee_payer.as_ref()];
+
+    let mut total_rent_free_balances: u64 = 0;
+
+    let token_receiver_account = Keypair::new();
+
+    // Create token account if not specified
+    let token_receiver = unwrap_create_token_account(
+        &config,
+        &token_receiver,
+        &token_receiver_account,
+        &stake_pool.pool_mint,
+        &mut instructions,
+        |balance| {
+            signers.push(&token_receiver_account);
+            total_rent_free_balances += balance;
+        },
+    )?;
+
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+
+    println!(
+        "Depositing {} into reserve stake {}",
+        Sol(amount),
+        stake_pool.reserve_stake
+    );
+
+    instructions.push(match sol_deposit_authority {
+        Some(sol_deposit_authority) => {
+            signers.push(sol_deposit_authority);
+            spl_stake_pool::instruction::deposit_sol_with_authority(
+                &spl_stake_pool::id(),
+                &stake_pool_address,
+                &pool_withdraw_authority,
+                &stake_pool.reserve_stake,
+                &config.fee_payer.pubkey(),
+                &token_receiver,
+                &stake_pool.pool_mint,
+                &spl_token::id(),
+                &sol_deposit_authority.pubkey(),
+                amount,
+            )?
+        }
+        None => spl_stake_pool::instruction::deposit_sol(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &pool_withdraw_authority,
+            &stake_pool.reserve_stake,
+            &config.fee_payer.pubkey(),
+            &token_receiver,
+            &stake_pool.pool_mint,
+            &spl_token::id(),
+            amount,
+        )?,
+    });
+
+    let mut transaction =
+        Transaction::new_with_payer(&instructions, Some(&config.fee_payer.pubkey()));
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        amount + total_rent_free_balances + fee_calculator.calculate_fee(&transaction.message()),
+    )?;
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
 fn command_list(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
     let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
@@ -614,6 +1006,16 @@ fn command_list(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
         "Total Pool Tokens: {}",
         spl_token::amount_to_ui_amount(stake_pool.pool_token_supply, pool_mint.decimals)
     );
+    println!(
+        "Epoch Fee: {}/{} of epoch rewards",
+        stake_pool.fee.numerator, stake_pool.fee.denominator
+    );
+    if let Some(next_epoch_fee) = stake_pool.next_epoch_fee {
+        println!(
+            "Next Epoch Fee: {}/{} of epoch rewards",
+            next_epoch_fee.numerator, next_epoch_fee.denominator
+        );
+    }
 
     if config.verbose {
         println!();
@@ -630,12 +1032,15 @@ fn command_list(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
         let mut total_stake_lamports: u64 = 0;
         for (pubkey, stake_lamports, stake_state) in accounts {
             total_stake_lamports += stake_lamports;
-            println!(
-                "Stake Account: {}\tVote Account: {}\t{}",
-                pubkey,
-                stake_state.delegation().expect("delegation").voter_pubkey,
-                Sol(stake_lamports)
-            );
+            match stake_state.delegation() {
+                Some(delegation) => println!(
+                    "Stake Account: {}\tVote Account: {}\t{}",
+                    pubkey,
+                    delegation.voter_pubkey,
+                    Sol(stake_lamports)
+                ),
+                None => println!("Reserve Stake Account: {}\t{}", pubkey, Sol(stake_lamports)),
+            }
         }
         println!("Total Stake Account Balance: {}", Sol(total_stake_lamports));
 
@@ -661,38 +1066,45 @@ fn command_update(config: &Config, stake_pool_address: &Pubkey) -> CommandResult
 
     let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
 
-    let accounts_to_update: Vec<Pubkey> = validator_list
+    let vote_accounts_to_update: Vec<Pubkey> = validator_list
         .validators
         .iter()
         .filter_map(|item| {
             if item.last_update_epoch >= epoch_info.epoch {
                 None
             } else {
-                let (stake_account, _) = find_stake_program_address(
-                    &spl_stake_pool::id(),
-                    &item.vote_account,
-                    &stake_pool_address,
-                );
-                Some(stake_account)
+                Some(item.vote_account)
             }
         })
         .collect();
 
+    let (withdraw_authority, _) =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), &stake_pool_address);
+
     println!("Updating stake pool...");
 
+    // Each validator brings both its stake account and its transient stake account
     let mut instructions: Vec<Instruction> = vec![];
-    for accounts_chunk in accounts_to_update.chunks(MAX_ACCOUNTS_TO_UPDATE) {
+    for vote_accounts_chunk in vote_accounts_to_update.chunks(MAX_ACCOUNTS_TO_UPDATE / 2) {
         instructions.push(spl_stake_pool::instruction::update_validator_list_balance(
             &spl_stake_pool::id(),
+            stake_pool_address,
+            &withdraw_authority,
             &stake_pool.validator_list,
-            &accounts_chunk,
+            &stake_pool.reserve_stake,
+            &vote_accounts_chunk,
         )?);
     }
 
     instructions.push(spl_stake_pool::instruction::update_stake_pool_balance(
         &spl_stake_pool::id(),
         stake_pool_address,
+        &withdraw_authority,
         &stake_pool.validator_list,
+        &stake_pool.reserve_stake,
+        &stake_pool.manager_fee_account,
+        &stake_pool.pool_mint,
+        &spl_token::id(),
     )?);
 
     // TODO: A faster solution would be to send all the `update_validator_list_balance` instructions concurrently
@@ -717,11 +1129,24 @@ struct WithdrawAccount {
 fn prepare_withdraw_accounts(
     rpc_client: &RpcClient,
     stake_pool: &StakePool,
+    stake_pool_address: &Pubkey,
     pool_withdraw_authority: &Pubkey,
     pool_amount: u64,
 ) -> Result<Vec<WithdrawAccount>, Error> {
     let mut accounts =
         get_stake_accounts_by_withdraw_authority(rpc_client, &pool_withdraw_authority)?;
+    // The reserve and transient stake accounts cannot be withdrawn from directly
+    accounts.retain(|(address, _, stake_state)| match stake_state.delegation() {
+        Some(delegation) => {
+            let (validator_stake_address, _) = find_stake_program_address(
+                &spl_stake_pool::id(),
+                &delegation.voter_pubkey,
+                stake_pool_address,
+            );
+            *address == validator_stake_address
+        }
+        None => false,
+    });
     if accounts.is_empty() {
         return Err("No accounts found.".to_string().into());
     }
@@ -769,12 +1194,87 @@ fn prepare_withdraw_accounts(
     Ok(withdraw_from)
 }
 
+/// Lamports in the reserve above its rent-exempt reserve, available for instant SOL withdrawals
+fn get_reserve_available_lamports(
+    rpc_client: &RpcClient,
+    stake_pool: &StakePool,
+) -> Result<u64, Error> {
+    let reserve_lamports = rpc_client.get_balance(&stake_pool.reserve_stake)?;
+    let rent_exempt_reserve = match get_stake_state(rpc_client, &stake_pool.reserve_stake)? {
+        StakeState::Initialized(meta) => meta.rent_exempt_reserve,
+        _ => return Ok(0),
+    };
+    Ok(reserve_lamports.saturating_sub(rent_exempt_reserve))
+}
+
+fn command_withdraw_sol(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    stake_pool: &StakePool,
+    pool_amount: u64,
+    withdraw_from: &Pubkey,
+    sol_receiver: &Pubkey,
+) -> CommandResult {
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+
+    let fee_amount = stake_pool
+        .calc_sol_withdrawal_fee_amount(pool_amount)
+        .unwrap();
+    let sol_withdraw_amount = stake_pool
+        .calc_lamports_withdraw_amount(pool_amount - fee_amount)
+        .unwrap();
+    println!(
+        "Withdrawing {} from reserve stake {} to {}",
+        Sol(sol_withdraw_amount),
+        stake_pool.reserve_stake,
+        sol_receiver
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            // Approve spending token
+            spl_token::instruction::approve(
+                &spl_token::id(),
+                &withdraw_from,
+                &pool_withdraw_authority,
+                &config.token_owner.pubkey(),
+                &[],
+                pool_amount,
+            )?,
+            spl_stake_pool::instruction::withdraw_sol(
+                &spl_stake_pool::id(),
+                &stake_pool_address,
+                &pool_withdraw_authority,
+                &withdraw_from,
+                &stake_pool.reserve_stake,
+                &sol_receiver,
+                &stake_pool.manager_fee_account,
+                &stake_pool.pool_mint,
+                &spl_token::id(),
+                pool_amount,
+            )?,
+        ],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.token_owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
 fn command_withdraw(
     config: &Config,
     stake_pool_address: &Pubkey,
     pool_amount: f64,
     withdraw_from: &Pubkey,
     stake_receiver_param: &Option<Pubkey>,
+    sol_receiver: &Option<Pubkey>,
+    use_stake: bool,
 ) -> CommandResult {
     if !config.no_update {
         command_update(config, stake_pool_address)?;
@@ -801,10 +1301,35 @@ fn command_withdraw(
         .into());
     }
 
+    // Prefer an instant SOL withdrawal when the reserve has enough liquidity
+    if !use_stake && stake_receiver_param.is_none() {
+        let fee_amount = stake_pool
+            .calc_sol_withdrawal_fee_amount(pool_amount)
+            .unwrap();
+        let sol_withdraw_amount = stake_pool
+            .calc_lamports_withdraw_amount(pool_amount - fee_amount)
+            .unwrap();
+        if sol_withdraw_amount <= get_reserve_available_lamports(&config.rpc_client, &stake_pool)? {
+            let sol_receiver = sol_receiver.unwrap_or_else(|| config.token_owner.pubkey());
+            return command_withdraw_sol(
+                config,
+                stake_pool_address,
+                &stake_pool,
+                pool_amount,
+                withdraw_from,
+                &sol_receiver,
+            );
+        }
+        if config.verbose {
+            println!("Not enough SOL in the reserve, withdrawing stake accounts instead");
+        }
+    }
+
     // Get the list of accounts to withdraw from
     let withdraw_accounts = prepare_withdraw_accounts(
         &config.rpc_client,
         &stake_pool,
+        stake_pool_address,
         &pool_withdraw_authority,
         pool_amount,
     )?;
@@ -975,6 +1500,82 @@ fn command_set_staker(
     Ok(())
 }
 
+fn command_set_sol_deposit_authority(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    new_sol_deposit_authority: &Option<Pubkey>,
+) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_sol_deposit_authority(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            new_sol_deposit_authority.as_ref(),
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_fee(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    fee: spl_stake_pool::instruction::Fee,
+) -> CommandResult {
+    if !config.no_update {
+        command_update(config, stake_pool_address)?;
+    }
+
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_fee(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            fee,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_sol_withdrawal_fee(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    fee: spl_stake_pool::instruction::Fee,
+) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_sol_withdrawal_fee(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            fee,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
 fn main() {
     solana_logger::setup_with_default("solana=info");
 
@@ -1198,6 +1799,107 @@ fn main() {
                           Defaults to the wallet owner pubkey."),
             )
         )
+        .subcommand(SubCommand::with_name("increase-validator-stake")
+            .about("Increase stake to a validator, drawing from the stake pool reserve. Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Vote account for the validator to increase stake to"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount in SOL to move from the reserve to the validator"),
+            )
+        )
+        .subcommand(SubCommand::with_name("decrease-validator-stake")
+            .about("Decrease stake from a validator, returning it to the stake pool reserve. Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Vote account for the validator to decrease stake from"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount in SOL to move from the validator back to the reserve"),
+            )
+        )
+        .subcommand(SubCommand::with_name("rebalance")
+            .about("Move stake between the reserve and the validators to follow a strategy. Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("strategy")
+                    .long("strategy")
+                    .value_name("STRATEGY")
+                    .takes_value(true)
+                    .possible_values(&["equal", "commission-cap", "weighted"])
+                    .default_value("equal")
+                    .help("How to spread the stake: equally across all validators, equally across \
+                          validators charging at most --max-commission, or following --weights-file. \
+                          Delinquent validators never receive stake."),
+            )
+            .arg(
+                Arg::with_name("max_commission")
+                    .long("max-commission")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .required_if("strategy", "commission-cap")
+                    .help("Highest commission of a validator receiving stake with the commission-cap strategy"),
+            )
+            .arg(
+                Arg::with_name("weights_file")
+                    .long("weights-file")
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .required_if("strategy", "weighted")
+                    .help("JSON file mapping vote account addresses to weights for the weighted strategy. \
+                          Validators missing from the file receive no stake."),
+            )
+        )
         .subcommand(SubCommand::with_name("deposit")
             .about("Add stake account to the stake pool")
             .arg(
@@ -1228,6 +1930,44 @@ fn main() {
                           Defaults to the new pool token account."),
             )
         )
+        .subcommand(SubCommand::with_name("deposit-sol")
+            .about("Deposit SOL from the fee payer into the stake pool reserve")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(2)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount in SOL to deposit into the stake pool reserve"),
+            )
+            .arg(
+                Arg::with_name("token_receiver")
+                    .long("token-receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Account to receive pool token. Must be initialized account of the stake pool token. \
+                          Defaults to the new pool token account."),
+            )
+            .arg(
+                Arg::with_name("sol_deposit_authority")
+                    .long("sol-deposit-authority")
+                    .value_name("KEYPAIR")
+                    .validator(is_keypair)
+                    .takes_value(true)
+                    .help("SOL deposit authority, required if the stake pool restricts SOL deposits."),
+            )
+        )
         .subcommand(SubCommand::with_name("list")
             .about("List stake accounts managed by this pool")
             .arg(
@@ -1287,7 +2027,22 @@ fn main() {
                     .validator(is_pubkey)
                     .value_name("STAKE_ACCOUNT_ADDRESS")
                     .takes_value(true)
-                    .help("Stake account to receive SOL from the stake pool. Defaults to a new stake account."),
+                    .help("Stake account to receive SOL from the stake pool. Defaults to a new stake account. \
+                          Implies --use-stake."),
+            )
+            .arg(
+                Arg::with_name("sol_receiver")
+                    .long("sol-receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Account to receive SOL when withdrawing from the reserve. Defaults to the token owner."),
+            )
+            .arg(
+                Arg::with_name("use_stake")
+                    .long("use-stake")
+                    .takes_value(false)
+                    .help("Always withdraw stake accounts, even if the reserve holds enough SOL for an instant withdrawal."),
             )
         )
         .subcommand(SubCommand::with_name("set-manager")
@@ -1344,6 +2099,87 @@ fn main() {
                     .help("Public key for the new stake pool staker."),
             )
         )
+        .subcommand(SubCommand::with_name("set-sol-deposit-authority")
+            .about("Change the SOL deposit authority for the stake pool. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("new_sol_deposit_authority")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Public key for the new SOL deposit authority. \
+                          Anyone can deposit SOL if omitted."),
+            )
+        )
+        .subcommand(SubCommand::with_name("set-fee")
+            .about("Change the fee taken on epoch rewards, effective from the next epoch. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("fee_numerator")
+                    .index(2)
+                    .validator(is_parsable::<u64>)
+                    .value_name("NUMERATOR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Fee numerator, fee amount is numerator divided by denominator."),
+            )
+            .arg(
+                Arg::with_name("fee_denominator")
+                    .index(3)
+                    .validator(is_parsable::<u64>)
+                    .value_name("DENOMINATOR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Fee denominator, fee amount is numerator divided by denominator."),
+            )
+        )
+        .subcommand(SubCommand::with_name("set-sol-withdrawal-fee")
+            .about("Change the fee charged on SOL withdrawals from the reserve. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("fee_numerator")
+                    .index(2)
+                    .validator(is_parsable::<u64>)
+                    .value_name("NUMERATOR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Fee numerator, fee amount is numerator divided by denominator."),
+            )
+            .arg(
+                Arg::with_name("fee_denominator")
+                    .index(3)
+                    .validator(is_parsable::<u64>)
+                    .value_name("DENOMINATOR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Fee denominator, fee amount is numerator divided by denominator."),
+            )
+        )
         .get_matches();
 
     let mut wallet_manager = None;
@@ -1455,6 +2291,35 @@ fn main() {
                 &new_authority,
             )
         }
+        ("increase-validator-stake", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            command_increase_validator_stake(&config, &stake_pool_address, &vote_account, amount)
+        }
+        ("decrease-validator-stake", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            command_decrease_validator_stake(&config, &stake_pool_address, &vote_account, amount)
+        }
+        ("rebalance", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let strategy = match arg_matches.value_of("strategy").unwrap() {
+                "commission-cap" => {
+                    Strategy::CommissionCapped(value_t_or_exit!(arg_matches, "max_commission", u8))
+                }
+                "weighted" => {
+                    let weights_file = arg_matches.value_of("weights_file").unwrap();
+                    Strategy::Weighted(read_weights(weights_file).unwrap_or_else(|e| {
+                        eprintln!("error: {}", e);
+                        exit(1);
+                    }))
+                }
+                _ => Strategy::Equal,
+            };
+            command_rebalance(&config, &stake_pool_address, &strategy)
+        }
         ("deposit", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             let stake_account = pubkey_of(arg_matches, "stake_account").unwrap();
@@ -1466,6 +2331,30 @@ fn main() {
                 &token_receiver,
             )
         }
+        ("deposit-sol", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let token_receiver: Option<Pubkey> = pubkey_of(arg_matches, "token_receiver");
+            let sol_deposit_authority = arg_matches.value_of("sol_deposit_authority").map(|path| {
+                signer_from_path(
+                    arg_matches,
+                    path,
+                    "sol_deposit_authority",
+                    &mut wallet_manager,
+                )
+                .unwrap_or_else(|e| {
+                    eprintln!("error: {}", e);
+                    exit(1);
+                })
+            });
+            command_deposit_sol(
+                &config,
+                &stake_pool_address,
+                amount,
+                &token_receiver,
+                sol_deposit_authority.as_deref(),
+            )
+        }
         ("list", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             command_list(&config, &stake_pool_address)
@@ -1479,12 +2368,16 @@ fn main() {
             let withdraw_from = pubkey_of(arg_matches, "withdraw_from").unwrap();
             let pool_amount = value_t_or_exit!(arg_matches, "amount", f64);
             let stake_receiver: Option<Pubkey> = pubkey_of(arg_matches, "stake_receiver");
+            let sol_receiver: Option<Pubkey> = pubkey_of(arg_matches, "sol_receiver");
+            let use_stake = arg_matches.is_present("use_stake");
             command_withdraw(
                 &config,
                 &stake_pool_address,
                 pool_amount,
                 &withdraw_from,
                 &stake_receiver,
+                &sol_receiver,
+                use_stake,
             )
         }
         ("set-manager", Some(arg_matches)) => {
@@ -1503,6 +2396,42 @@ fn main() {
             let new_staker = pubkey_of(arg_matches, "new_staker").unwrap();
             command_set_staker(&config, &stake_pool_address, &new_staker)
         }
+        ("set-sol-deposit-authority", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let new_sol_deposit_authority: Option<Pubkey> =
+                pubkey_of(arg_matches, "new_sol_deposit_authority");
+            command_set_sol_deposit_authority(
+                &config,
+                &stake_pool_address,
+                &new_sol_deposit_authority,
+            )
+        }
+        ("set-fee", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let numerator = value_t_or_exit!(arg_matches, "fee_numerator", u64);
+            let denominator = value_t_or_exit!(arg_matches, "fee_denominator", u64);
+            command_set_fee(
+                &config,
+                &stake_pool_address,
+                spl_stake_pool::instruction::Fee {
+                    denominator,
+                    numerator,
+                },
+            )
+        }
+        ("set-sol-withdrawal-fee", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let numerator = value_t_or_exit!(arg_matches, "fee_numerator", u64);
+            let denominator = value_t_or_exit!(arg_matches, "fee_denominator", u64);
+            command_set_sol_withdrawal_fee(
+                &config,
+                &stake_pool_address,
+                spl_stake_pool::instruction::Fee {
+                    denominator,
+                    numerator,
+                },
+            )
+        }
         _ => unreachable!(),
     }
     .map_err(|err| {
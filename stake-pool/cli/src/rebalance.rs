@@ -0,0 +1,255 @@
+//! Off-chain engine spreading the stake pool's lamports across its validators
+
+use {
+    solana_program::pubkey::Pubkey,
+    std::{collections::HashMap, fs::File, str::FromStr},
+};
+
+type Error = Box<dyn std::error::Error>;
+
+/// How the pool's stake is spread across its validators.
+/// Delinquent validators, and validators whose vote account cannot be found, get no stake.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Strategy {
+    /// Every validator gets the same stake
+    Equal,
+    /// Every validator charging at most the given commission gets the same stake
+    CommissionCapped(u8),
+    /// Validators get stake in proportion to their weight, and none without a weight
+    Weighted(HashMap<Pubkey, u64>),
+}
+
+impl Strategy {
+    fn weight(&self, validator: &ValidatorState) -> u64 {
+        if validator.delinquent {
+            return 0;
+        }
+        match (self, validator.commission) {
+            (_, None) => 0,
+            (Strategy::Equal, Some(_)) => 1,
+            (Strategy::CommissionCapped(max_commission), Some(commission)) => {
+                if commission <= *max_commission {
+                    1
+                } else {
+                    0
+                }
+            }
+            (Strategy::Weighted(weights), Some(_)) => {
+                weights.get(&validator.vote_account).copied().unwrap_or(0)
+            }
+        }
+    }
+
+    /// Target stake for each of the `validators`, sharing out `total_lamports`
+    pub(crate) fn target_stakes(
+        &self,
+        validators: &[ValidatorState],
+        total_lamports: u64,
+    ) -> Result<Vec<u64>, Error> {
+        let weights: Vec<u64> = validators.iter().map(|v| self.weight(v)).collect();
+        let total_weight: u128 = weights.iter().map(|w| *w as u128).sum();
+        if total_weight == 0 {
+            return Err("No validator in the pool is eligible under this strategy".into());
+        }
+        // Rounding leftovers stay in the reserve
+        Ok(weights
+            .iter()
+            .map(|weight| (total_lamports as u128 * *weight as u128 / total_weight) as u64)
+            .collect())
+    }
+}
+
+/// Read custom validator weights from a JSON file mapping vote accounts to weights, e.g.
+/// `{"8r1f8mwrUiYdg2Rx9sxTh4M3UAUcCBBrmRA3nxk3Z6Lm": 2, "HJiC8iJ4Sj846SswQuauFJK93UvV6zp3c2T6jzGqzhhz": 1}`
+pub(crate) fn read_weights(path: &str) -> Result<HashMap<Pubkey, u64>, Error> {
+    let file = File::open(path).map_err(|err| format!("Unable to open {}: {}", path, err))?;
+    let weights: HashMap<String, u64> = serde_json::from_reader(file)
+        .map_err(|err| format!("Invalid weights file {}: {}", path, err))?;
+    let mut vote_account_weights = HashMap::new();
+    for (vote_account, weight) in weights {
+        let vote_account = Pubkey::from_str(&vote_account)
+            .map_err(|err| format!("Invalid vote account {}: {}", vote_account, err))?;
+        vote_account_weights.insert(vote_account, weight);
+    }
+    Ok(vote_account_weights)
+}
+
+/// What the engine knows about one validator of the pool
+#[derive(Debug, Default)]
+pub(crate) struct ValidatorState {
+    /// Validator vote account address
+    pub vote_account: Pubkey,
+    /// Stake recorded in the validator list, including any transient stake
+    pub stake_lamports: u64,
+    /// Lamports held by the validator stake account itself
+    pub stake_account_lamports: u64,
+    /// Commission of the vote account, `None` if the vote account was not found
+    pub commission: Option<u8>,
+    /// Whether the validator stopped voting
+    pub delinquent: bool,
+    /// A validator with a transient stake account can't change until the next update merges it
+    pub has_transient_stake: bool,
+}
+
+/// Limits on a single stake change, following the checks of the stake pool program
+#[derive(Debug)]
+pub(crate) struct StakeLimits {
+    /// Rent-exempt reserve of a stake account, a decrease must move more than this
+    pub stake_rent: u64,
+    /// Smallest increase accepted by the program
+    pub min_increase_lamports: u64,
+    /// Lamports left in a validator stake account after a decrease
+    pub min_validator_lamports: u64,
+}
+
+/// A stake change needed to bring a validator closer to its target
+#[derive(Debug, PartialEq)]
+pub(crate) enum StakeChange {
+    /// Move lamports from the reserve onto the validator
+    Increase { vote_account: Pubkey, lamports: u64 },
+    /// Move lamports from the validator back to the reserve
+    Decrease { vote_account: Pubkey, lamports: u64 },
+}
+
+/// Compute the changes bringing every validator closer to its target.
+///
+/// Increases are funded from `reserve_lamports`, while decreased lamports only
+/// reach the reserve once deactivated, so full convergence can take a few epochs.
+pub(crate) fn compute_stake_changes(
+    validators: &[ValidatorState],
+    targets: &[u64],
+    reserve_lamports: u64,
+    limits: &StakeLimits,
+) -> Vec<StakeChange> {
+    let mut increases = vec![];
+    let mut decreases = vec![];
+    for (validator, target) in validators.iter().zip(targets.iter()) {
+        if validator.has_transient_stake {
+            continue;
+        }
+        if validator.stake_lamports > *target {
+            let lamports = (validator.stake_lamports - target).min(
+                validator
+                    .stake_account_lamports
+                    .saturating_sub(limits.min_validator_lamports),
+            );
+            if lamports > limits.stake_rent {
+                decreases.push((validator.vote_account, lamports));
+            }
+        } else if *target > validator.stake_lamports {
+            increases.push((validator.vote_account, target - validator.stake_lamports));
+        }
+    }
+
+    // Largest moves first, so that a short reserve goes where it matters most
+    decreases.sort_by(|a, b| b.1.cmp(&a.1));
+    increases.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let mut changes: Vec<StakeChange> = decreases
+        .into_iter()
+        .map(|(vote_account, lamports)| StakeChange::Decrease {
+            vote_account,
+            lamports,
+        })
+        .collect();
+    let mut reserve_lamports = reserve_lamports;
+    for (vote_account, lamports) in increases {
+        let lamports = lamports.min(reserve_lamports);
+        if lamports < limits.min_increase_lamports {
+            continue;
+        }
+        reserve_lamports -= lamports;
+        changes.push(StakeChange::Increase {
+            vote_account,
+            lamports,
+        });
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: StakeLimits = StakeLimits {
+        stake_rent: 10,
+        min_increase_lamports: 110,
+        min_validator_lamports: 110,
+    };
+
+    fn validator(stake_lamports: u64, commission: u8) -> ValidatorState {
+        ValidatorState {
+            vote_account: Pubkey::new_unique(),
+            stake_lamports,
+            stake_account_lamports: stake_lamports,
+            commission: Some(commission),
+            ..ValidatorState::default()
+        }
+    }
+
+    #[test]
+    fn equal_targets_skip_delinquent_validators() {
+        let mut validators = vec![validator(0, 5), validator(0, 5), validator(0, 5)];
+        validators[2].delinquent = true;
+        assert_eq!(
+            Strategy::Equal.target_stakes(&validators, 1_001).unwrap(),
+            vec![500, 500, 0]
+        );
+    }
+
+    #[test]
+    fn commission_capped_targets() {
+        let validators = vec![validator(0, 5), validator(0, 10), validator(0, 100)];
+        assert_eq!(
+            Strategy::CommissionCapped(10)
+                .target_stakes(&validators, 1_000)
+                .unwrap(),
+            vec![500, 500, 0]
+        );
+        assert!(Strategy::CommissionCapped(1)
+            .target_stakes(&validators, 1_000)
+            .is_err());
+    }
+
+    #[test]
+    fn weighted_targets() {
+        let validators = vec![validator(0, 5), validator(0, 5), validator(0, 5)];
+        let mut weights = HashMap::new();
+        weights.insert(validators[0].vote_account, 3);
+        weights.insert(validators[1].vote_account, 1);
+        assert_eq!(
+            Strategy::Weighted(weights)
+                .target_stakes(&validators, 1_000)
+                .unwrap(),
+            vec![750, 250, 0]
+        );
+    }
+
+    #[test]
+    fn changes_respect_reserve_and_limits() {
+        let mut validators = vec![
+            validator(1_000, 5),
+            validator(200, 5),
+            validator(100, 5),
+            validator(500, 5),
+        ];
+        validators[3].has_transient_stake = true;
+        let targets = vec![150, 600, 400, 0];
+        let changes = compute_stake_changes(&validators, &targets, 500, &LIMITS);
+        assert_eq!(
+            changes,
+            vec![
+                // Keeps the minimum in the validator stake account
+                StakeChange::Decrease {
+                    vote_account: validators[0].vote_account,
+                    lamports: 850,
+                },
+                StakeChange::Increase {
+                    vote_account: validators[1].vote_account,
+                    lamports: 400,
+                },
+                // Only 100 lamports left in the reserve, below the minimum increase
+            ]
+        );
+    }
+}
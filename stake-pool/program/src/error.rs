@@ -85,6 +85,23 @@ pub enum StakePoolError {
     /// Pool token supply is not zero on initialization
     #[error("NonZeroPoolTokenSupply")]
     NonZeroPoolTokenSupply,
+    /// Transient stake account for this validator already exists
+    #[error("TransientAccountInUse")]
+    TransientAccountInUse,
+    /// Invalid reserve stake account
+    #[error("InvalidReserveStakeAccount")]
+    InvalidReserveStakeAccount,
+
+    // 25.
+    /// SOL deposit authority does not match the stake pool
+    #[error("InvalidSolDepositAuthority")]
+    InvalidSolDepositAuthority,
+    /// Deposit is too small to receive any pool tokens
+    #[error("DepositTooSmall")]
+    DepositTooSmall,
+    /// Not enough inactive lamports in the reserve to cover the SOL withdrawal
+    #[error("SolWithdrawalTooLarge")]
+    SolWithdrawalTooLarge,
 }
 impl From<StakePoolError> for ProgramError {
     fn from(e: StakePoolError) -> Self {
@@ -3,7 +3,7 @@
 #![allow(clippy::too_many_arguments)]
 
 use {
-    crate::stake_program,
+    crate::{find_stake_program_address, find_transient_stake_program_address, stake_program},
     borsh::{BorshDeserialize, BorshSchema, BorshSerialize},
     solana_program::{
         instruction::{AccountMeta, Instruction},
@@ -13,7 +13,7 @@ use {
     },
 };
 
-/// Fee rate as a ratio, minted on deposit
+/// Fee rate as a ratio, minted on rewards or charged on SOL withdrawals
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
 pub struct Fee {
@@ -41,7 +41,7 @@ pub enum StakePoolInstruction {
     ///   8. `[]` Rent sysvar
     ///   9. `[]` Token program id
     Initialize {
-        /// Deposit fee assessed
+        /// Fee assessed as percentage of rewards
         #[allow(dead_code)] // but it's not
         fee: Fee,
         /// Maximum expected number of validators
@@ -118,7 +118,7 @@ pub enum StakePoolInstruction {
     ///  1. `[s]` Stake pool staker
     ///  2. `[]` Validator list
     ///  3. `[]` Stake pool withdraw authority
-    ///  5. `[w]` Canonical stake account to split from
+    ///  4. `[w]` Canonical stake account to split from
     ///  5. `[w]` Transient stake account to receive split
     ///  6. `[]` Clock sysvar
     ///  7. `[]` Rent sysvar
@@ -139,13 +139,18 @@ pub enum StakePoolInstruction {
     ///
     ///  0. `[]` Stake pool
     ///  1. `[s]` Stake pool staker
-    ///  2. `[]` Validator list
+    ///  2. `[w]` Validator list
     ///  3. `[]` Stake pool withdraw authority
     ///  4. `[w]` Stake pool reserve stake
     ///  5. `[w]` Transient stake account
-    ///  6. `[]` Canonical stake account
+    ///  6. `[]` Validator vote account to delegate to
     ///  7. '[]' Clock sysvar
-    ///  8. `[]` Stake program
+    ///  8. `[]` Rent sysvar
+    ///  9. `[]` Stake History sysvar
+    /// 10. `[]` Stake Config account
+    /// 11. `[]` System program
+    /// 12. `[]` Stake program
+    ///  userdata: amount of lamports to split
     IncreaseValidatorStake(u64),
 
     ///  Updates balances of validator and transient stake accounts in the pool
@@ -162,16 +167,22 @@ pub enum StakePoolInstruction {
     ///  2. `[w]` Reserve stake account
     ///  3. `[]` Stake pool withdraw authority
     ///  4. `[]` Sysvar clock account
-    ///  5. `[]` Stake program
-    ///  6. ..6+N ` [] N pairs of validator and transient stake accounts
+    ///  5. `[]` Sysvar stake history account
+    ///  6. `[]` Stake program
+    ///  7. ..7+2N ` [] N pairs of validator and transient stake accounts
     UpdateValidatorListBalance,
 
-    ///   Updates total pool balance based on balances in the reserve and validator list
+    ///   Updates total pool balance based on balances in the reserve and validator list,
+    ///   and mints the manager's fee on any rewards earned since the last update
     ///
     ///   0. `[w]` Stake pool
-    ///   1. `[]` Validator stake list storage account
-    ///   2. `[]` Reserve stake account
-    ///   3. `[]` Sysvar clock account
+    ///   1. `[]` Stake pool withdraw authority
+    ///   2. `[]` Validator stake list storage account
+    ///   3. `[]` Reserve stake account
+    ///   4. `[w]` Account to receive pool fee tokens
+    ///   5. `[w]` Pool token mint account
+    ///   6. `[]` Sysvar clock account
+    ///   7. `[]` Pool token program id
     UpdateStakePoolBalance,
 
     ///   Deposit some stake into the pool.  The output is a "pool" token representing ownership
@@ -184,12 +195,11 @@ pub enum StakePoolInstruction {
     ///   4. `[w]` Stake account to join the pool (withdraw should be set to stake pool deposit)
     ///   5. `[w]` Validator stake account for the stake account to be merged with
     ///   6. `[w]` User account to receive pool tokens
-    ///   7. `[w]` Account to receive pool fee tokens
-    ///   8. `[w]` Pool token mint account
-    ///   9. '[]' Sysvar clock account (required)
-    ///   10. '[]' Sysvar stake history account
-    ///   11. `[]` Pool token program id,
-    ///   12. `[]` Stake program id,
+    ///   7. `[w]` Pool token mint account
+    ///   8. '[]' Sysvar clock account (required)
+    ///   9. '[]' Sysvar stake history account
+    ///   10. `[]` Pool token program id,
+    ///   11. `[]` Stake program id,
     Deposit,
 
     ///   Withdraw the token from the pool at the current ratio.
@@ -227,6 +237,62 @@ pub enum StakePoolInstruction {
     ///  1. `[s]` Manager or current staker
     ///  2. '[]` New staker pubkey
     SetStaker,
+
+    ///   Deposit SOL directly into the pool's reserve account. The output is a "pool" token
+    ///   representing ownership into the pool. Inputs are converted to the current ratio.
+    ///
+    ///   0. `[w]` Stake pool
+    ///   1. `[]` Stake pool withdraw authority
+    ///   2. `[w]` Reserve stake account, to deposit SOL
+    ///   3. `[ws]` Account providing the lamports to be deposited into the pool
+    ///   4. `[w]` User account to receive pool tokens
+    ///   5. `[w]` Pool token mint account
+    ///   6. '[]' Sysvar clock account
+    ///   7. `[]` System program account
+    ///   8. `[]` Pool token program id
+    ///   9. `[s]` (Optional) Stake pool SOL deposit authority, required if set on the pool
+    ///   userdata: amount of lamports to deposit
+    DepositSol(u64),
+
+    ///  (Manager only) Update SOL deposit authority
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  2. '[]` (Optional) New SOL deposit authority, anyone can deposit SOL if omitted
+    SetSolDepositAuthority,
+
+    ///   Withdraw SOL directly from the pool's reserve account, burning pool
+    ///   tokens at the current ratio. Fails if the reserve does not hold enough
+    ///   inactive lamports.
+    ///
+    ///   0. `[w]` Stake pool
+    ///   1. `[]` Stake pool withdraw authority
+    ///   2. `[w]` User account with pool tokens to burn from, approved to the withdraw authority
+    ///   3. `[w]` Reserve stake account, to withdraw SOL
+    ///   4. `[w]` Account receiving the lamports from the reserve
+    ///   5. `[w]` Account to receive pool fee tokens
+    ///   6. `[w]` Pool token mint account
+    ///   7. '[]' Sysvar clock account
+    ///   8. '[]' Sysvar stake history account
+    ///   9. `[]` Stake program account
+    ///   10. `[]` Pool token program id
+    ///   userdata: amount of pool tokens to withdraw
+    WithdrawSol(u64),
+
+    ///  (Manager only) Update the fee charged on SOL withdrawals from the reserve
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    SetSolWithdrawalFee(Fee),
+
+    ///  (Manager only) Update the fee taken on rewards. The new fee only applies
+    ///  from the next epoch's `UpdateStakePoolBalance` on, so the pool must be
+    ///  up to date for the current epoch.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  2. `[]` Sysvar clock account
+    SetFee(Fee),
 }
 
 /// Creates an 'initialize' instruction.
@@ -236,6 +302,7 @@ pub fn initialize(
     manager: &Pubkey,
     staker: &Pubkey,
     validator_list: &Pubkey,
+    reserve_stake: &Pubkey,
     pool_mint: &Pubkey,
     manager_pool_account: &Pubkey,
     token_program_id: &Pubkey,
@@ -252,6 +319,7 @@ pub fn initialize(
         AccountMeta::new_readonly(*manager, true),
         AccountMeta::new_readonly(*staker, false),
         AccountMeta::new(*validator_list, false),
+        AccountMeta::new_readonly(*reserve_stake, false),
         AccountMeta::new_readonly(*pool_mint, false),
         AccountMeta::new_readonly(*manager_pool_account, false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
@@ -363,28 +431,96 @@ pub fn remove_validator_from_pool(
 
 /// Creates `DecreaseValidatorStake` instruction (rebalance from validator account to
 /// transient account)
-pub fn decrease_validator_stake() -> Result<Instruction, ProgramError> {
-    Err(ProgramError::IncorrectProgramId)
+pub fn decrease_validator_stake(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    staker: &Pubkey,
+    stake_pool_withdraw_authority: &Pubkey,
+    validator_list: &Pubkey,
+    validator_stake: &Pubkey,
+    transient_stake: &Pubkey,
+    lamports: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*staker, true),
+        AccountMeta::new_readonly(*validator_list, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
+        AccountMeta::new(*validator_stake, false),
+        AccountMeta::new(*transient_stake, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::DecreaseValidatorStake(lamports).try_to_vec()?,
+    })
 }
 
 /// Creates `IncreaseValidatorStake` instruction (rebalance from reserve account to
 /// transient account)
-pub fn increase_validator_stake() -> Result<Instruction, ProgramError> {
-    Err(ProgramError::IncorrectProgramId)
+pub fn increase_validator_stake(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    staker: &Pubkey,
+    stake_pool_withdraw_authority: &Pubkey,
+    validator_list: &Pubkey,
+    reserve_stake: &Pubkey,
+    transient_stake: &Pubkey,
+    validator: &Pubkey,
+    lamports: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*staker, true),
+        AccountMeta::new(*validator_list, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
+        AccountMeta::new(*reserve_stake, false),
+        AccountMeta::new(*transient_stake, false),
+        AccountMeta::new_readonly(*validator, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(stake_program::config_id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::IncreaseValidatorStake(lamports).try_to_vec()?,
+    })
 }
 
 /// Creates `UpdateValidatorListBalance` instruction (update validator stake account balances)
 pub fn update_validator_list_balance(
     program_id: &Pubkey,
-    validator_list_storage: &Pubkey,
-    validator_list: &[Pubkey],
+    stake_pool: &Pubkey,
+    stake_pool_withdraw_authority: &Pubkey,
+    validator_list: &Pubkey,
+    reserve_stake: &Pubkey,
+    validator_vote_accounts: &[Pubkey],
 ) -> Result<Instruction, ProgramError> {
-    let mut accounts: Vec<AccountMeta> = validator_list
-        .iter()
-        .map(|pubkey| AccountMeta::new_readonly(*pubkey, false))
-        .collect();
-    accounts.insert(0, AccountMeta::new(*validator_list_storage, false));
-    accounts.insert(1, AccountMeta::new_readonly(sysvar::clock::id(), false));
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new(*validator_list, false),
+        AccountMeta::new(*reserve_stake, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+    ];
+    for vote_account_address in validator_vote_accounts {
+        let (validator_stake_account, _) =
+            find_stake_program_address(program_id, vote_account_address, stake_pool);
+        let (transient_stake_account, _) =
+            find_transient_stake_program_address(program_id, &validator_stake_account);
+        accounts.push(AccountMeta::new(validator_stake_account, false));
+        accounts.push(AccountMeta::new(transient_stake_account, false));
+    }
     Ok(Instruction {
         program_id: *program_id,
         accounts,
@@ -396,12 +532,22 @@ pub fn update_validator_list_balance(
 pub fn update_stake_pool_balance(
     program_id: &Pubkey,
     stake_pool: &Pubkey,
+    stake_pool_withdraw: &Pubkey,
     validator_list_storage: &Pubkey,
+    reserve_stake: &Pubkey,
+    manager_fee_account: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
     let accounts = vec![
         AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw, false),
         AccountMeta::new(*validator_list_storage, false),
+        AccountMeta::new_readonly(*reserve_stake, false),
+        AccountMeta::new(*manager_fee_account, false),
+        AccountMeta::new(*pool_mint, false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(*token_program_id, false),
     ];
     Ok(Instruction {
         program_id: *program_id,
@@ -420,7 +566,6 @@ pub fn deposit(
     stake_to_join: &Pubkey,
     validator_stake_accont: &Pubkey,
     pool_tokens_to: &Pubkey,
-    pool_fee_to: &Pubkey,
     pool_mint: &Pubkey,
     token_program_id: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
@@ -432,7 +577,6 @@ pub fn deposit(
         AccountMeta::new(*stake_to_join, false),
         AccountMeta::new(*validator_stake_accont, false),
         AccountMeta::new(*pool_tokens_to, false),
-        AccountMeta::new(*pool_fee_to, false),
         AccountMeta::new(*pool_mint, false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(sysvar::stake_history::id(), false),
@@ -519,3 +663,154 @@ pub fn set_staker(
         data: StakePoolInstruction::SetStaker.try_to_vec()?,
     })
 }
+
+/// Creates a 'deposit SOL' instruction for a pool without a SOL deposit authority.
+pub fn deposit_sol(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    stake_pool_withdraw_authority: &Pubkey,
+    reserve_stake: &Pubkey,
+    lamports_from: &Pubkey,
+    pool_tokens_to: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
+        AccountMeta::new(*reserve_stake, false),
+        AccountMeta::new(*lamports_from, true),
+        AccountMeta::new(*pool_tokens_to, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::DepositSol(amount).try_to_vec()?,
+    })
+}
+
+/// Creates a 'deposit SOL' instruction signed by the pool's SOL deposit authority.
+pub fn deposit_sol_with_authority(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    stake_pool_withdraw_authority: &Pubkey,
+    reserve_stake: &Pubkey,
+    lamports_from: &Pubkey,
+    pool_tokens_to: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    sol_deposit_authority: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = deposit_sol(
+        program_id,
+        stake_pool,
+        stake_pool_withdraw_authority,
+        reserve_stake,
+        lamports_from,
+        pool_tokens_to,
+        pool_mint,
+        token_program_id,
+        amount,
+    )?;
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(*sol_deposit_authority, true));
+    Ok(instruction)
+}
+
+/// Creates a 'set SOL deposit authority' instruction.
+pub fn set_sol_deposit_authority(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    new_sol_deposit_authority: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    if let Some(new_sol_deposit_authority) = new_sol_deposit_authority {
+        accounts.push(AccountMeta::new_readonly(*new_sol_deposit_authority, false));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetSolDepositAuthority.try_to_vec()?,
+    })
+}
+
+/// Creates a 'withdraw SOL' instruction.
+pub fn withdraw_sol(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    stake_pool_withdraw_authority: &Pubkey,
+    burn_from: &Pubkey,
+    reserve_stake: &Pubkey,
+    lamports_to: &Pubkey,
+    manager_fee_account: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    pool_tokens: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
+        AccountMeta::new(*burn_from, false),
+        AccountMeta::new(*reserve_stake, false),
+        AccountMeta::new(*lamports_to, false),
+        AccountMeta::new(*manager_fee_account, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::WithdrawSol(pool_tokens).try_to_vec()?,
+    })
+}
+
+/// Creates a 'set SOL withdrawal fee' instruction.
+pub fn set_sol_withdrawal_fee(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    fee: Fee,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetSolWithdrawalFee(fee).try_to_vec()?,
+    })
+}
+
+/// Creates a 'set fee' instruction.
+pub fn set_fee(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    fee: Fee,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetFee(fee).try_to_vec()?,
+    })
+}
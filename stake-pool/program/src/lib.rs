@@ -59,4 +59,12 @@ pub fn find_stake_program_address(
     )
 }
 
+/// Generates the transient stake program address for a validator stake account
+pub fn find_transient_stake_program_address(
+    program_id: &Pubkey,
+    stake_account_address: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[&stake_account_address.to_bytes()[..32]], program_id)
+}
+
 solana_program::declare_id!("poo1B9L9nR3CrcaziKVYVpRX6A9Y1LAXYasjjfCbApj");
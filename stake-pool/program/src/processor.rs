@@ -46,6 +46,19 @@ impl Processor {
         }
     }
 
+    /// Returns the metadata of an initialized or delegated stake account
+    fn get_stake_meta(
+        stake_account_info: &AccountInfo,
+    ) -> Result<stake_program::Meta, ProgramError> {
+        let stake_state: stake_program::StakeState = deserialize(&stake_account_info.data.borrow())
+            .or(Err(ProgramError::InvalidAccountData))?;
+        match stake_state {
+            stake_program::StakeState::Initialized(meta)
+            | stake_program::StakeState::Stake(meta, _) => Ok(meta),
+            _ => Err(StakePoolError::WrongStakeState.into()),
+        }
+    }
+
     /// Checks if validator stake account is a proper program address
     fn is_validator_stake_address(
         vote_account: &Pubkey,
@@ -131,6 +144,121 @@ impl Processor {
         )
     }
 
+    /// Issue a stake_deactivate instruction.
+    fn stake_deactivate<'a>(
+        stake_pool: &Pubkey,
+        stake_account: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        authority_type: &[u8],
+        bump_seed: u8,
+        clock: AccountInfo<'a>,
+    ) -> Result<(), ProgramError> {
+        let me_bytes = stake_pool.to_bytes();
+        let authority_signature_seeds = [&me_bytes[..32], authority_type, &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = stake_program::deactivate_stake(stake_account.key, authority.key);
+
+        invoke_signed(&ix, &[stake_account, clock, authority], signers)
+    }
+
+    /// Issue a stake_delegate instruction.
+    #[allow(clippy::too_many_arguments)]
+    fn stake_delegate<'a>(
+        stake_pool: &Pubkey,
+        stake_account: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        authority_type: &[u8],
+        bump_seed: u8,
+        vote_account: AccountInfo<'a>,
+        clock: AccountInfo<'a>,
+        stake_history: AccountInfo<'a>,
+        stake_config: AccountInfo<'a>,
+    ) -> Result<(), ProgramError> {
+        let me_bytes = stake_pool.to_bytes();
+        let authority_signature_seeds = [&me_bytes[..32], authority_type, &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = stake_program::delegate_stake(stake_account.key, authority.key, vote_account.key);
+
+        invoke_signed(
+            &ix,
+            &[
+                stake_account,
+                vote_account,
+                clock,
+                stake_history,
+                stake_config,
+                authority,
+            ],
+            signers,
+        )
+    }
+
+    /// Issue a stake_withdraw instruction.
+    #[allow(clippy::too_many_arguments)]
+    fn stake_withdraw<'a>(
+        stake_pool: &Pubkey,
+        stake_account: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        authority_type: &[u8],
+        bump_seed: u8,
+        destination: AccountInfo<'a>,
+        clock: AccountInfo<'a>,
+        stake_history: AccountInfo<'a>,
+        lamports: u64,
+    ) -> Result<(), ProgramError> {
+        let me_bytes = stake_pool.to_bytes();
+        let authority_signature_seeds = [&me_bytes[..32], authority_type, &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix =
+            stake_program::withdraw(stake_account.key, authority.key, destination.key, lamports);
+
+        invoke_signed(
+            &ix,
+            &[stake_account, destination, clock, stake_history, authority],
+            signers,
+        )
+    }
+
+    /// Allocate the transient stake account of a validator stake account and
+    /// assign it to the stake program, ready to receive a split
+    fn create_transient_stake_account<'a>(
+        program_id: &Pubkey,
+        validator_stake_address: &Pubkey,
+        transient_stake_account: AccountInfo<'a>,
+        system_program_info: AccountInfo<'a>,
+    ) -> Result<(), ProgramError> {
+        let (transient_stake_address, bump_seed) =
+            crate::find_transient_stake_program_address(program_id, validator_stake_address);
+        if transient_stake_address != *transient_stake_account.key {
+            return Err(StakePoolError::InvalidStakeAccountAddress.into());
+        }
+        if *transient_stake_account.owner != solana_program::system_program::id()
+            || !transient_stake_account.data_is_empty()
+        {
+            return Err(StakePoolError::TransientAccountInUse.into());
+        }
+
+        let transient_stake_signer_seeds: &[&[_]] =
+            &[&validator_stake_address.to_bytes()[..32], &[bump_seed]];
+
+        invoke_signed(
+            &system_instruction::allocate(
+                transient_stake_account.key,
+                std::mem::size_of::<stake_program::StakeState>() as u64,
+            ),
+            &[transient_stake_account.clone(), system_program_info.clone()],
+            &[&transient_stake_signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(transient_stake_account.key, &stake_program::id()),
+            &[transient_stake_account, system_program_info],
+            &[&transient_stake_signer_seeds],
+        )
+    }
+
     /// Issue a stake_set_manager instruction.
     #[allow(clippy::too_many_arguments)]
     fn stake_authorize<'a>(
@@ -190,6 +318,38 @@ impl Processor {
         )
     }
 
+    /// Issue a spl_token `Transfer` instruction.
+    #[allow(clippy::too_many_arguments)]
+    fn token_transfer<'a>(
+        stake_pool: &Pubkey,
+        token_program: AccountInfo<'a>,
+        source: AccountInfo<'a>,
+        destination: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        authority_type: &[u8],
+        bump_seed: u8,
+        amount: u64,
+    ) -> Result<(), ProgramError> {
+        let me_bytes = stake_pool.to_bytes();
+        let authority_signature_seeds = [&me_bytes[..32], authority_type, &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = spl_token::instruction::transfer(
+            token_program.key,
+            source.key,
+            destination.key,
+            authority.key,
+            &[],
+            amount,
+        )?;
+
+        invoke_signed(
+            &ix,
+            &[source, destination, authority, token_program],
+            signers,
+        )
+    }
+
     /// Issue a spl_token `MintTo` instruction.
     #[allow(clippy::too_many_arguments)]
     fn token_mint_to<'a>(
@@ -230,6 +390,7 @@ impl Processor {
         let manager_info = next_account_info(account_info_iter)?;
         let staker_info = next_account_info(account_info_iter)?;
         let validator_list_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
         let pool_mint_info = next_account_info(account_info_iter)?;
         let manager_fee_info = next_account_info(account_info_iter)?;
         let clock_info = next_account_info(account_info_iter)?;
@@ -242,7 +403,7 @@ impl Processor {
             return Err(StakePoolError::SignatureMissing.into());
         }
 
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_uninitialized() {
             return Err(StakePoolError::AlreadyInUse.into());
         }
@@ -309,6 +470,35 @@ impl Processor {
             return Err(StakePoolError::WrongMintingAuthority.into());
         }
 
+        if *reserve_stake_info.owner != stake_program::id() {
+            msg!("Reserve stake account not owned by the stake program");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        match deserialize(&reserve_stake_info.data.borrow())
+            .or(Err(ProgramError::InvalidAccountData))?
+        {
+            stake_program::StakeState::Initialized(meta) => {
+                if meta.authorized.staker != withdraw_authority_key
+                    || meta.authorized.withdrawer != withdraw_authority_key
+                {
+                    msg!("Reserve stake authorities must be the pool withdraw authority");
+                    return Err(StakePoolError::WrongStakeState.into());
+                }
+                if meta.lockup != stake_program::Lockup::default() {
+                    msg!("Reserve stake account must not have a lockup");
+                    return Err(StakePoolError::WrongStakeState.into());
+                }
+                if reserve_stake_info.lamports() != meta.rent_exempt_reserve {
+                    msg!("Reserve stake account must only hold its rent-exempt reserve");
+                    return Err(StakePoolError::WrongStakeState.into());
+                }
+            }
+            _ => {
+                msg!("Reserve stake account must be initialized and not delegated");
+                return Err(StakePoolError::WrongStakeState.into());
+            }
+        }
+
         validator_list.serialize(&mut *validator_list_info.data.borrow_mut())?;
 
         msg!("Clock data: {:?}", clock_info.data.borrow());
@@ -320,6 +510,7 @@ impl Processor {
         stake_pool.deposit_bump_seed = deposit_bump_seed;
         stake_pool.withdraw_bump_seed = withdraw_bump_seed;
         stake_pool.validator_list = *validator_list_info.key;
+        stake_pool.reserve_stake = *reserve_stake_info.key;
         stake_pool.pool_mint = *pool_mint_info.key;
         stake_pool.manager_fee_account = *manager_fee_info.key;
         stake_pool.token_program_id = *token_program_info.key;
@@ -353,7 +544,7 @@ impl Processor {
         if stake_pool_info.owner != program_id {
             return Err(ProgramError::IncorrectProgramId);
         }
-        let stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
@@ -456,7 +647,7 @@ impl Processor {
             return Err(ProgramError::IncorrectProgramId);
         }
 
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
@@ -571,7 +762,7 @@ impl Processor {
             return Err(ProgramError::IncorrectProgramId);
         }
 
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
@@ -652,97 +843,493 @@ impl Processor {
         Ok(())
     }
 
-    /// Processes `UpdateValidatorListBalance` instruction.
-    fn process_update_validator_list_balance(
-        _program_id: &Pubkey,
+    /// Processes `DecreaseValidatorStake` instruction.
+    fn process_decrease_validator_stake(
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
+        lamports: u64,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let staker_info = next_account_info(account_info_iter)?;
         let validator_list_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let validator_stake_account_info = next_account_info(account_info_iter)?;
+        let transient_stake_account_info = next_account_info(account_info_iter)?;
         let clock_info = next_account_info(account_info_iter)?;
         let clock = &Clock::from_account_info(clock_info)?;
-        let validator_stake_accounts = account_info_iter.as_slice();
+        let rent_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
 
-        let mut validator_list =
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+        stake_pool.check_staker(staker_info)?;
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+
+        let validator_list =
             try_from_slice_unchecked::<ValidatorList>(&validator_list_info.data.borrow())?;
         if !validator_list.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
 
-        let vote_accounts: Vec<Option<Pubkey>> = validator_stake_accounts
-            .iter()
-            .map(|stake| Self::get_validator(stake).ok())
-            .collect();
+        let vote_account =
+            Self::get_validator_checked(program_id, stake_pool_info, validator_stake_account_info)?;
 
-        let mut changes = false;
-        // Do a brute iteration through the list, optimize if necessary
-        for validator_stake_record in &mut validator_list.validators {
-            if validator_stake_record.last_update_epoch >= clock.epoch {
-                continue;
-            }
-            for (validator_stake_account, vote_account) in
-                validator_stake_accounts.iter().zip(vote_accounts.iter())
-            {
-                if validator_stake_record.vote_account
-                    != vote_account.ok_or(StakePoolError::WrongStakeState)?
-                {
-                    continue;
-                }
-                validator_stake_record.last_update_epoch = clock.epoch;
-                validator_stake_record.stake_lamports = **validator_stake_account.lamports.borrow();
-                changes = true;
-            }
+        if !validator_list.contains(&vote_account) {
+            return Err(StakePoolError::ValidatorNotFound.into());
         }
 
-        if changes {
-            validator_list.serialize(&mut *validator_list_info.data.borrow_mut())?;
+        let stake_rent = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+        if lamports <= stake_rent {
+            msg!(
+                "Need more than {} lamports for the transient stake to be rent-exempt, {} provided",
+                stake_rent,
+                lamports
+            );
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+        let remaining_lamports = validator_stake_account_info
+            .lamports()
+            .checked_sub(lamports)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        if remaining_lamports <= stake_rent {
+            msg!(
+                "Validator stake account must keep more than {} lamports, {} would remain",
+                stake_rent,
+                remaining_lamports
+            );
+            return Err(ProgramError::InsufficientFunds);
         }
 
+        Self::create_transient_stake_account(
+            program_id,
+            validator_stake_account_info.key,
+            transient_stake_account_info.clone(),
+            system_program_info.clone(),
+        )?;
+
+        Self::stake_split(
+            stake_pool_info.key,
+            validator_stake_account_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            lamports,
+            transient_stake_account_info.clone(),
+        )?;
+
+        Self::stake_deactivate(
+            stake_pool_info.key,
+            transient_stake_account_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            clock_info.clone(),
+        )?;
+
         Ok(())
     }
 
-    /// Processes `UpdateStakePoolBalance` instruction.
-    fn process_update_stake_pool_balance(
-        _program_id: &Pubkey,
+    /// Processes `IncreaseValidatorStake` instruction.
+    fn process_increase_validator_stake(
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
+        lamports: u64,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let stake_pool_info = next_account_info(account_info_iter)?;
+        let staker_info = next_account_info(account_info_iter)?;
         let validator_list_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let transient_stake_account_info = next_account_info(account_info_iter)?;
+        let validator_vote_info = next_account_info(account_info_iter)?;
         let clock_info = next_account_info(account_info_iter)?;
         let clock = &Clock::from_account_info(clock_info)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let stake_config_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
 
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
 
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+        stake_pool.check_staker(staker_info)?;
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
         if *validator_list_info.key != stake_pool.validator_list {
             return Err(StakePoolError::InvalidValidatorStakeList.into());
         }
+        if *reserve_stake_info.key != stake_pool.reserve_stake {
+            return Err(StakePoolError::InvalidReserveStakeAccount.into());
+        }
 
-        let validator_list =
+        let mut validator_list =
             try_from_slice_unchecked::<ValidatorList>(&validator_list_info.data.borrow())?;
         if !validator_list.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
 
-        let mut total_stake_lamports: u64 = 0;
-        for validator_stake_record in validator_list.validators {
-            if validator_stake_record.last_update_epoch < clock.epoch {
-                return Err(StakePoolError::StakeListOutOfDate.into());
-            }
-            total_stake_lamports += validator_stake_record.stake_lamports;
+        let vote_account = *validator_vote_info.key;
+        let validator_list_item = validator_list
+            .find_mut(&vote_account)
+            .ok_or(StakePoolError::ValidatorNotFound)?;
+
+        let stake_rent = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+        let minimum_lamports = stake_rent + sol_to_lamports(1.0);
+        if lamports < minimum_lamports {
+            msg!(
+                "Need at least {} lamports to increase the stake on a validator, {} provided",
+                minimum_lamports,
+                lamports
+            );
+            return Err(ProgramError::AccountNotRentExempt);
         }
+        let reserve_meta = Self::get_stake_meta(reserve_stake_info)?;
+        let remaining_lamports = reserve_stake_info
+            .lamports()
+            .checked_sub(lamports)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        if remaining_lamports < reserve_meta.rent_exempt_reserve {
+            msg!(
+                "Reserve stake account must keep {} lamports, {} would remain",
+                reserve_meta.rent_exempt_reserve,
+                remaining_lamports
+            );
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        let (validator_stake_address, _) =
+            crate::find_stake_program_address(program_id, &vote_account, stake_pool_info.key);
+        Self::create_transient_stake_account(
+            program_id,
+            &validator_stake_address,
+            transient_stake_account_info.clone(),
+            system_program_info.clone(),
+        )?;
 
-        stake_pool.total_stake_lamports = total_stake_lamports;
-        stake_pool.last_update_epoch = clock.epoch;
-        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::stake_split(
+            stake_pool_info.key,
+            reserve_stake_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            lamports,
+            transient_stake_account_info.clone(),
+        )?;
 
-        Ok(())
-    }
+        Self::stake_delegate(
+            stake_pool_info.key,
+            transient_stake_account_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            validator_vote_info.clone(),
+            clock_info.clone(),
+            stake_history_info.clone(),
+            stake_config_info.clone(),
+        )?;
 
-    /// Check stake activation status
+        // The transient stake counts towards the validator until it is merged
+        validator_list_item.stake_lamports = validator_list_item
+            .stake_lamports
+            .checked_add(lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        validator_list.serialize(&mut *validator_list_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes `UpdateValidatorListBalance` instruction.
+    fn process_update_validator_list_balance(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let stake_history = &StakeHistory::from_account_info(stake_history_info)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+        let validator_stake_accounts = account_info_iter.as_slice();
+
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+        if *reserve_stake_info.key != stake_pool.reserve_stake {
+            return Err(StakePoolError::InvalidReserveStakeAccount.into());
+        }
+
+        let mut validator_list =
+            try_from_slice_unchecked::<ValidatorList>(&validator_list_info.data.borrow())?;
+        if !validator_list.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        let mut changes = false;
+        for validator_stakes in validator_stake_accounts.chunks_exact(2) {
+            let validator_stake_info = &validator_stakes[0];
+            let transient_stake_info = &validator_stakes[1];
+
+            let vote_account =
+                Self::get_validator_checked(program_id, stake_pool_info, validator_stake_info)?;
+            let validator_stake_record = match validator_list.find_mut(&vote_account) {
+                Some(validator_stake_record) => validator_stake_record,
+                None => continue,
+            };
+            if validator_stake_record.last_update_epoch >= clock.epoch {
+                continue;
+            }
+
+            let (transient_stake_address, _) =
+                crate::find_transient_stake_program_address(program_id, validator_stake_info.key);
+            if transient_stake_address != *transient_stake_info.key {
+                return Err(StakePoolError::InvalidStakeAccountAddress.into());
+            }
+
+            // Lamports sent to the transient address before it was allocated
+            // don't belong to the pool
+            let transient_stake_state = if *transient_stake_info.owner == stake_program::id() {
+                deserialize::<stake_program::StakeState>(&transient_stake_info.data.borrow()).ok()
+            } else {
+                None
+            };
+            match transient_stake_state {
+                Some(stake_program::StakeState::Initialized(_)) => {
+                    Self::stake_merge(
+                        stake_pool_info.key,
+                        transient_stake_info.clone(),
+                        withdraw_info.clone(),
+                        AUTHORITY_WITHDRAW,
+                        stake_pool.withdraw_bump_seed,
+                        reserve_stake_info.clone(),
+                        clock_info.clone(),
+                        stake_history_info.clone(),
+                        stake_program_info.clone(),
+                    )?;
+                }
+                Some(stake_program::StakeState::Stake(_, transient_stake)) => {
+                    let (effective, activating, deactivating) = transient_stake
+                        .delegation
+                        .stake_activating_and_deactivating(clock.epoch, Some(stake_history), true);
+                    let deactivated = transient_stake.delegation.deactivation_epoch != u64::MAX;
+                    if deactivated && effective == 0 && deactivating == 0 {
+                        // Inactive, back to the reserve
+                        Self::stake_merge(
+                            stake_pool_info.key,
+                            transient_stake_info.clone(),
+                            withdraw_info.clone(),
+                            AUTHORITY_WITHDRAW,
+                            stake_pool.withdraw_bump_seed,
+                            reserve_stake_info.clone(),
+                            clock_info.clone(),
+                            stake_history_info.clone(),
+                            stake_program_info.clone(),
+                        )?;
+                    } else if !deactivated && activating == 0 {
+                        // Active, merged into the validator stake if the stake
+                        // program allows it
+                        let validator_stake: stake_program::StakeState =
+                            deserialize(&validator_stake_info.data.borrow())
+                                .or(Err(ProgramError::InvalidAccountData))?;
+                        let can_merge = match validator_stake {
+                            stake_program::StakeState::Stake(_, validator_stake) => {
+                                validator_stake.credits_observed == transient_stake.credits_observed
+                            }
+                            _ => false,
+                        };
+                        if can_merge {
+                            Self::stake_merge(
+                                stake_pool_info.key,
+                                transient_stake_info.clone(),
+                                withdraw_info.clone(),
+                                AUTHORITY_WITHDRAW,
+                                stake_pool.withdraw_bump_seed,
+                                validator_stake_info.clone(),
+                                clock_info.clone(),
+                                stake_history_info.clone(),
+                                stake_program_info.clone(),
+                            )?;
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            // Whatever was not merged stays in the transient stake account
+            let transient_lamports = if transient_stake_state.is_some() {
+                transient_stake_info.lamports()
+            } else {
+                0
+            };
+            validator_stake_record.last_update_epoch = clock.epoch;
+            validator_stake_record.stake_lamports = validator_stake_info
+                .lamports()
+                .checked_add(transient_lamports)
+                .ok_or(StakePoolError::CalculationFailure)?;
+            changes = true;
+        }
+
+        if changes {
+            validator_list.serialize(&mut *validator_list_info.data.borrow_mut())?;
+        }
+
+        Ok(())
+    }
+
+    /// Processes `UpdateStakePoolBalance` instruction.
+    fn process_update_stake_pool_balance(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let manager_fee_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+        if *reserve_stake_info.key != stake_pool.reserve_stake {
+            return Err(StakePoolError::InvalidReserveStakeAccount.into());
+        }
+        if stake_pool.manager_fee_account != *manager_fee_info.key {
+            return Err(StakePoolError::InvalidFeeAccount.into());
+        }
+        if stake_pool.pool_mint != *pool_mint_info.key {
+            return Err(StakePoolError::WrongPoolMint.into());
+        }
+        if stake_pool.token_program_id != *token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let validator_list =
+            try_from_slice_unchecked::<ValidatorList>(&validator_list_info.data.borrow())?;
+        if !validator_list.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        // The reserve's rent-exempt reserve was never deposited into the pool
+        let reserve_meta = Self::get_stake_meta(reserve_stake_info)?;
+        let mut total_stake_lamports = reserve_stake_info
+            .lamports()
+            .saturating_sub(reserve_meta.rent_exempt_reserve);
+        for validator_stake_record in validator_list.validators {
+            if validator_stake_record.last_update_epoch < clock.epoch {
+                return Err(StakePoolError::StakeListOutOfDate.into());
+            }
+            total_stake_lamports = total_stake_lamports
+                .checked_add(validator_stake_record.stake_lamports)
+                .ok_or(StakePoolError::CalculationFailure)?;
+        }
+
+        // Deposits and withdrawals keep the value of a pool token, so any growth
+        // above its high-water mark is reward
+        let fee_pool_tokens = stake_pool
+            .update_total_stake_lamports(total_stake_lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        if fee_pool_tokens > 0 {
+            Self::token_mint_to(
+                stake_pool_info.key,
+                token_program_info.clone(),
+                pool_mint_info.clone(),
+                manager_fee_info.clone(),
+                withdraw_info.clone(),
+                AUTHORITY_WITHDRAW,
+                stake_pool.withdraw_bump_seed,
+                fee_pool_tokens,
+            )?;
+        }
+
+        // A fee change only applies once the epoch it was announced in is over
+        if stake_pool.last_update_epoch < clock.epoch {
+            if let Some(next_epoch_fee) = stake_pool.next_epoch_fee.take() {
+                stake_pool.fee = next_epoch_fee;
+            }
+        }
+
+        stake_pool.last_update_epoch = clock.epoch;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Check stake activation status
     #[allow(clippy::unnecessary_wraps)]
     fn check_stake_activation(
         _stake_info: &AccountInfo,
@@ -782,7 +1369,6 @@ impl Processor {
         let stake_info = next_account_info(account_info_iter)?;
         let validator_stake_account_info = next_account_info(account_info_iter)?;
         let dest_user_info = next_account_info(account_info_iter)?;
-        let manager_fee_info = next_account_info(account_info_iter)?;
         let pool_mint_info = next_account_info(account_info_iter)?;
         let clock_info = next_account_info(account_info_iter)?;
         let clock = &Clock::from_account_info(clock_info)?;
@@ -795,7 +1381,7 @@ impl Processor {
             return Err(ProgramError::IncorrectProgramId);
         }
 
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
@@ -805,9 +1391,6 @@ impl Processor {
         stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
         stake_pool.check_authority_deposit(deposit_info.key, program_id, stake_pool_info.key)?;
 
-        if stake_pool.manager_fee_account != *manager_fee_info.key {
-            return Err(StakePoolError::InvalidFeeAccount.into());
-        }
         if stake_pool.token_program_id != *token_program_info.key {
             return Err(ProgramError::IncorrectProgramId);
         }
@@ -838,14 +1421,6 @@ impl Processor {
             .calc_pool_tokens_for_deposit(stake_lamports)
             .ok_or(StakePoolError::CalculationFailure)?;
 
-        let fee_pool_tokens = stake_pool
-            .calc_fee_amount(new_pool_tokens)
-            .ok_or(StakePoolError::CalculationFailure)?;
-
-        let user_pool_tokens = new_pool_tokens
-            .checked_sub(fee_pool_tokens)
-            .ok_or(StakePoolError::CalculationFailure)?;
-
         Self::stake_authorize(
             stake_pool_info.key,
             stake_info.clone(),
@@ -890,25 +1465,118 @@ impl Processor {
             withdraw_info.clone(),
             AUTHORITY_WITHDRAW,
             stake_pool.withdraw_bump_seed,
-            user_pool_tokens,
+            new_pool_tokens,
+        )?;
+
+        stake_pool.pool_token_supply += new_pool_tokens;
+        stake_pool.total_stake_lamports += stake_lamports;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+
+        validator_list_item.stake_lamports = validator_list_item
+            .stake_lamports
+            .checked_add(stake_lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        validator_list.serialize(&mut *validator_list_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes [DepositSol](enum.Instruction.html).
+    fn process_deposit_sol(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        deposit_lamports: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let from_user_lamports_info = next_account_info(account_info_iter)?;
+        let dest_user_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        if let Some(sol_deposit_authority) = stake_pool.sol_deposit_authority {
+            let sol_deposit_authority_info = next_account_info(account_info_iter)?;
+            if sol_deposit_authority != *sol_deposit_authority_info.key {
+                return Err(StakePoolError::InvalidSolDepositAuthority.into());
+            }
+            if !sol_deposit_authority_info.is_signer {
+                return Err(StakePoolError::SignatureMissing.into());
+            }
+        }
+
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+
+        if *reserve_stake_info.key != stake_pool.reserve_stake {
+            return Err(StakePoolError::InvalidReserveStakeAccount.into());
+        }
+        if stake_pool.pool_mint != *pool_mint_info.key {
+            return Err(StakePoolError::WrongPoolMint.into());
+        }
+        if stake_pool.token_program_id != *token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        let new_pool_tokens = stake_pool
+            .calc_pool_tokens_for_deposit(deposit_lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        if new_pool_tokens == 0 {
+            return Err(StakePoolError::DepositTooSmall.into());
+        }
+
+        invoke(
+            &system_instruction::transfer(
+                from_user_lamports_info.key,
+                reserve_stake_info.key,
+                deposit_lamports,
+            ),
+            &[
+                from_user_lamports_info.clone(),
+                reserve_stake_info.clone(),
+                system_program_info.clone(),
+            ],
         )?;
 
         Self::token_mint_to(
             stake_pool_info.key,
             token_program_info.clone(),
             pool_mint_info.clone(),
-            manager_fee_info.clone(),
+            dest_user_info.clone(),
             withdraw_info.clone(),
             AUTHORITY_WITHDRAW,
             stake_pool.withdraw_bump_seed,
-            fee_pool_tokens,
+            new_pool_tokens,
         )?;
-        stake_pool.pool_token_supply += new_pool_tokens;
-        stake_pool.total_stake_lamports += stake_lamports;
-        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
 
-        validator_list_item.stake_lamports = **validator_stake_account_info.lamports.borrow();
-        validator_list.serialize(&mut *validator_list_info.data.borrow_mut())?;
+        stake_pool.pool_token_supply = stake_pool
+            .pool_token_supply
+            .checked_add(new_pool_tokens)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        stake_pool.total_stake_lamports = stake_pool
+            .total_stake_lamports
+            .checked_add(deposit_lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
 
         Ok(())
     }
@@ -937,7 +1605,7 @@ impl Processor {
             return Err(ProgramError::IncorrectProgramId);
         }
 
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
@@ -1022,12 +1690,140 @@ impl Processor {
         stake_pool.total_stake_lamports -= stake_lamports;
         stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
 
-        validator_list_item.stake_lamports = **stake_split_from.lamports.borrow();
+        validator_list_item.stake_lamports = validator_list_item
+            .stake_lamports
+            .checked_sub(stake_lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
         validator_list.serialize(&mut *validator_list_info.data.borrow_mut())?;
 
         Ok(())
     }
 
+    /// Processes [WithdrawSol](enum.Instruction.html).
+    fn process_withdraw_sol(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        pool_tokens: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let burn_from_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let destination_lamports_info = next_account_info(account_info_iter)?;
+        let manager_fee_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+
+        if *reserve_stake_info.key != stake_pool.reserve_stake {
+            return Err(StakePoolError::InvalidReserveStakeAccount.into());
+        }
+        if stake_pool.manager_fee_account != *manager_fee_info.key {
+            return Err(StakePoolError::InvalidFeeAccount.into());
+        }
+        if stake_pool.pool_mint != *pool_mint_info.key {
+            return Err(StakePoolError::WrongPoolMint.into());
+        }
+        if stake_pool.token_program_id != *token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        let fee_pool_tokens = stake_pool
+            .calc_sol_withdrawal_fee_amount(pool_tokens)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        let burn_pool_tokens = pool_tokens
+            .checked_sub(fee_pool_tokens)
+            .ok_or(StakePoolError::CalculationFailure)?;
+
+        let withdraw_lamports = stake_pool
+            .calc_lamports_withdraw_amount(burn_pool_tokens)
+            .ok_or(StakePoolError::CalculationFailure)?;
+
+        // Only the reserve's balance above its rent-exempt reserve can be paid out
+        let reserve_meta = Self::get_stake_meta(reserve_stake_info)?;
+        let available_lamports = reserve_stake_info
+            .lamports()
+            .saturating_sub(reserve_meta.rent_exempt_reserve);
+        if withdraw_lamports > available_lamports {
+            msg!(
+                "Reserve only has {} lamports available, {} requested",
+                available_lamports,
+                withdraw_lamports
+            );
+            return Err(StakePoolError::SolWithdrawalTooLarge.into());
+        }
+
+        if fee_pool_tokens > 0 {
+            Self::token_transfer(
+                stake_pool_info.key,
+                token_program_info.clone(),
+                burn_from_info.clone(),
+                manager_fee_info.clone(),
+                withdraw_info.clone(),
+                AUTHORITY_WITHDRAW,
+                stake_pool.withdraw_bump_seed,
+                fee_pool_tokens,
+            )?;
+        }
+
+        Self::token_burn(
+            stake_pool_info.key,
+            token_program_info.clone(),
+            burn_from_info.clone(),
+            pool_mint_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            burn_pool_tokens,
+        )?;
+
+        Self::stake_withdraw(
+            stake_pool_info.key,
+            reserve_stake_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            destination_lamports_info.clone(),
+            clock_info.clone(),
+            stake_history_info.clone(),
+            withdraw_lamports,
+        )?;
+
+        stake_pool.pool_token_supply = stake_pool
+            .pool_token_supply
+            .checked_sub(burn_pool_tokens)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        stake_pool.total_stake_lamports = stake_pool
+            .total_stake_lamports
+            .checked_sub(withdraw_lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
     /// Processes [SetManager](enum.Instruction.html).
     fn process_set_manager(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -1036,7 +1832,7 @@ impl Processor {
         let new_manager_info = next_account_info(account_info_iter)?;
         let new_manager_fee_info = next_account_info(account_info_iter)?;
 
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
@@ -1063,7 +1859,7 @@ impl Processor {
         let set_staker_authority_info = next_account_info(account_info_iter)?;
         let new_staker_info = next_account_info(account_info_iter)?;
 
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
@@ -1078,6 +1874,96 @@ impl Processor {
         Ok(())
     }
 
+    /// Processes [SetSolDepositAuthority](enum.Instruction.html).
+    fn process_set_sol_deposit_authority(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let new_sol_deposit_authority = next_account_info(account_info_iter)
+            .ok()
+            .map(|info| *info.key);
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        stake_pool.check_manager(manager_info)?;
+
+        stake_pool.sol_deposit_authority = new_sol_deposit_authority;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes [SetFee](enum.Instruction.html).
+    fn process_set_fee(program_id: &Pubkey, accounts: &[AccountInfo], fee: Fee) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        stake_pool.check_manager(manager_info)?;
+
+        // The pool must be up to date, so that the new fee waits for the next epoch's update
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        // Numerator should be smaller than or equal to denominator (fee <= 1)
+        if fee.numerator > fee.denominator {
+            return Err(StakePoolError::FeeTooHigh.into());
+        }
+
+        stake_pool.next_epoch_fee = Some(fee);
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes [SetSolWithdrawalFee](enum.Instruction.html).
+    fn process_set_sol_withdrawal_fee(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        fee: Fee,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        stake_pool.check_manager(manager_info)?;
+
+        // Numerator should be smaller than or equal to denominator (fee <= 1)
+        if fee.numerator > fee.denominator {
+            return Err(StakePoolError::FeeTooHigh.into());
+        }
+
+        stake_pool.sol_withdrawal_fee = fee;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Ok(())
+    }
+
     /// Processes [Instruction](enum.Instruction.html).
     pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
         let instruction = StakePoolInstruction::try_from_slice(input)?;
@@ -1101,13 +1987,13 @@ impl Processor {
                 msg!("Instruction: RemoveValidatorFromPool");
                 Self::process_remove_validator_from_pool(program_id, accounts)
             }
-            StakePoolInstruction::DecreaseValidatorStake(_amount) => {
+            StakePoolInstruction::DecreaseValidatorStake(amount) => {
                 msg!("Instruction: DecreaseValidatorStake");
-                Ok(())
+                Self::process_decrease_validator_stake(program_id, accounts, amount)
             }
-            StakePoolInstruction::IncreaseValidatorStake(_amount) => {
+            StakePoolInstruction::IncreaseValidatorStake(amount) => {
                 msg!("Instruction: IncreaseValidatorStake");
-                Ok(())
+                Self::process_increase_validator_stake(program_id, accounts, amount)
             }
             StakePoolInstruction::UpdateValidatorListBalance => {
                 msg!("Instruction: UpdateValidatorListBalance");
@@ -1133,6 +2019,26 @@ impl Processor {
                 msg!("Instruction: SetStaker");
                 Self::process_set_staker(program_id, accounts)
             }
+            StakePoolInstruction::DepositSol(lamports) => {
+                msg!("Instruction: DepositSol");
+                Self::process_deposit_sol(program_id, accounts, lamports)
+            }
+            StakePoolInstruction::SetSolDepositAuthority => {
+                msg!("Instruction: SetSolDepositAuthority");
+                Self::process_set_sol_deposit_authority(program_id, accounts)
+            }
+            StakePoolInstruction::WithdrawSol(pool_tokens) => {
+                msg!("Instruction: WithdrawSol");
+                Self::process_withdraw_sol(program_id, accounts, pool_tokens)
+            }
+            StakePoolInstruction::SetSolWithdrawalFee(fee) => {
+                msg!("Instruction: SetSolWithdrawalFee");
+                Self::process_set_sol_withdrawal_fee(program_id, accounts, fee)
+            }
+            StakePoolInstruction::SetFee(fee) => {
+                msg!("Instruction: SetFee");
+                Self::process_set_fee(program_id, accounts, fee)
+            }
         }
     }
 }
@@ -1168,6 +2074,11 @@ impl PrintProgramError for StakePoolError {
             StakePoolError::UnexpectedValidatorListAccountSize=> msg!("Error: The size of the given validator stake list does match the expected amount"),
             StakePoolError::WrongStaker=> msg!("Error: Wrong pool staker account"),
             StakePoolError::NonZeroPoolTokenSupply => msg!("Error: Pool token supply is not zero on initialization"),
+            StakePoolError::TransientAccountInUse => msg!("Error: Transient stake account for this validator already exists"),
+            StakePoolError::InvalidReserveStakeAccount => msg!("Error: Reserve stake account does not match the stake pool"),
+            StakePoolError::InvalidSolDepositAuthority => msg!("Error: SOL deposit authority does not match the stake pool"),
+            StakePoolError::DepositTooSmall => msg!("Error: Deposit is too small to receive any pool tokens"),
+            StakePoolError::SolWithdrawalTooLarge => msg!("Error: Not enough inactive lamports in the reserve to cover the SOL withdrawal"),
         }
     }
 }
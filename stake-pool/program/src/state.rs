@@ -73,8 +73,27 @@ pub struct StakePool {
     /// Last epoch the `total_stake_lamports` field was updated
     pub last_update_epoch: u64,
 
-    /// Fee applied to deposits
+    /// Fee taken as a proportion of rewards each epoch, minted to the manager
+    /// fee account as pool tokens
     pub fee: Fee,
+
+    /// Optional authority that must sign SOL deposits into the reserve,
+    /// anyone can deposit SOL if unset
+    pub sol_deposit_authority: Option<Pubkey>,
+
+    /// Fee applied to SOL withdrawals straight out of the reserve
+    pub sol_withdrawal_fee: Fee,
+
+    /// Fee to apply from the next epoch on, set one epoch ahead so that
+    /// depositors have time to leave the pool before it takes effect
+    pub next_epoch_fee: Option<Fee>,
+
+    /// Total stake at the high-water mark, the highest value of a pool token
+    /// on which the fee was charged
+    pub high_water_total_stake_lamports: u64,
+
+    /// Pool token supply at the high-water mark, 0 if no mark was set yet
+    pub high_water_pool_token_supply: u64,
 }
 impl StakePool {
     /// calculate the pool tokens that should be minted for a deposit of `stake_lamports`
@@ -106,15 +125,72 @@ impl StakePool {
         )
         .ok()
     }
-    /// calculate the fee in pool tokens that goes to the manager
-    pub fn calc_fee_amount(&self, pool_amount: u64) -> Option<u64> {
-        if self.fee.denominator == 0 {
+    /// Record the `total_stake_lamports` found by an epoch update, and add the fee in
+    /// pool tokens that goes to the manager to the pool token supply, returning the fee.
+    /// Only growth of the value of a pool token above its high-water mark counts as
+    /// reward, so the pool has to make up for any loss before the fee is charged again.
+    pub fn update_total_stake_lamports(&mut self, total_stake_lamports: u64) -> Option<u64> {
+        if self.high_water_pool_token_supply == 0 {
+            self.raise_high_water_mark();
+        }
+        self.total_stake_lamports = total_stake_lamports;
+        let reward_lamports = self.calc_reward_lamports()?;
+        let fee_pool_tokens = self.calc_epoch_fee_amount(reward_lamports)?;
+        self.pool_token_supply = self.pool_token_supply.checked_add(fee_pool_tokens)?;
+        if reward_lamports > 0 {
+            self.raise_high_water_mark();
+        }
+        Some(fee_pool_tokens)
+    }
+    /// calculate the lamports earned above the high-water mark
+    pub fn calc_reward_lamports(&self) -> Option<u64> {
+        if self.high_water_pool_token_supply == 0 {
+            return Some(0);
+        }
+        let high_water_pool_token_supply = self.high_water_pool_token_supply as u128;
+        let reward_lamports = (self.total_stake_lamports as u128)
+            .checked_mul(high_water_pool_token_supply)?
+            .saturating_sub(
+                (self.pool_token_supply as u128)
+                    .checked_mul(self.high_water_total_stake_lamports as u128)?,
+            )
+            .checked_div(high_water_pool_token_supply)?;
+        u64::try_from(reward_lamports).ok()
+    }
+    fn raise_high_water_mark(&mut self) {
+        self.high_water_total_stake_lamports = self.total_stake_lamports;
+        self.high_water_pool_token_supply = self.pool_token_supply;
+    }
+    /// calculate the fee in pool tokens that goes to the manager for `reward_lamports`
+    /// earned by the pool, with `total_stake_lamports` already including the reward
+    pub fn calc_epoch_fee_amount(&self, reward_lamports: u64) -> Option<u64> {
+        if reward_lamports == 0 || self.fee.denominator == 0 {
+            return Some(0);
+        }
+        let fee_lamports = (reward_lamports as u128)
+            .checked_mul(self.fee.numerator as u128)?
+            .checked_div(self.fee.denominator as u128)?;
+        if fee_lamports == 0 || self.pool_token_supply == 0 {
+            return Some(0);
+        }
+        // Mint just enough tokens for the manager to own `fee_lamports` after dilution
+        u64::try_from(
+            (self.pool_token_supply as u128)
+                .checked_mul(fee_lamports)?
+                .checked_div((self.total_stake_lamports as u128).checked_sub(fee_lamports)?)?,
+        )
+        .ok()
+    }
+
+    /// calculate the fee in pool tokens that goes to the manager on a SOL withdrawal
+    pub fn calc_sol_withdrawal_fee_amount(&self, pool_amount: u64) -> Option<u64> {
+        if self.sol_withdrawal_fee.denominator == 0 {
             return Some(0);
         }
         u64::try_from(
             (pool_amount as u128)
-                .checked_mul(self.fee.numerator as u128)?
-                .checked_div(self.fee.denominator as u128)?,
+                .checked_mul(self.sol_withdrawal_fee.numerator as u128)?
+                .checked_div(self.sol_withdrawal_fee.denominator as u128)?,
         )
         .ok()
     }
@@ -350,7 +426,94 @@ mod test {
         assert_eq!(stake_list_unpacked, stake_list);
     }
 
+    #[test]
+    fn epoch_fee_calculation() {
+        let mut stake_pool = StakePool {
+            total_stake_lamports: 1_100_000_000,
+            pool_token_supply: 1_000_000_000,
+            fee: Fee {
+                numerator: 1,
+                denominator: 10,
+            },
+            ..StakePool::default()
+        };
+        // 10% of a 100_000_000 lamport reward is worth 10_000_000 lamports
+        assert_eq!(
+            stake_pool.calc_epoch_fee_amount(100_000_000),
+            Some(9_174_311)
+        );
+        assert_eq!(stake_pool.calc_epoch_fee_amount(0), Some(0));
+
+        stake_pool.fee = Fee::default();
+        assert_eq!(stake_pool.calc_epoch_fee_amount(100_000_000), Some(0));
+    }
+
+    #[test]
+    fn epoch_fee_above_high_water_mark() {
+        let mut stake_pool = StakePool {
+            total_stake_lamports: 1_000_000_000,
+            pool_token_supply: 1_000_000_000,
+            fee: Fee {
+                numerator: 1,
+                denominator: 10,
+            },
+            ..StakePool::default()
+        };
+        assert_eq!(
+            stake_pool.update_total_stake_lamports(1_100_000_000),
+            Some(9_174_311)
+        );
+        let pool_token_supply = stake_pool.pool_token_supply;
+        assert_eq!(pool_token_supply, 1_009_174_311);
+
+        // Neither the loss nor the recovery back to the mark are charged
+        assert_eq!(
+            stake_pool.update_total_stake_lamports(1_000_000_000),
+            Some(0)
+        );
+        assert_eq!(
+            stake_pool.update_total_stake_lamports(1_100_000_000),
+            Some(0)
+        );
+        assert_eq!(stake_pool.pool_token_supply, pool_token_supply);
+        assert_eq!(stake_pool.calc_reward_lamports(), Some(0));
+
+        // Only the 110_000_000 lamports above the mark are
+        assert_eq!(
+            stake_pool.update_total_stake_lamports(1_210_000_000),
+            Some(9_258_479)
+        );
+        assert_eq!(stake_pool.high_water_total_stake_lamports, 1_210_000_000);
+        assert_eq!(
+            stake_pool.high_water_pool_token_supply,
+            pool_token_supply + 9_258_479
+        );
+    }
+
     proptest! {
+        #[test]
+        fn epoch_fee_never_exceeds_fee_share(
+            pool_token_supply in 1..u32::MAX as u64,
+            previous_lamports in 1..u32::MAX as u64,
+            reward_lamports in 0..u32::MAX as u64,
+            numerator in 0..=100_u64,
+        ) {
+            let stake_pool = StakePool {
+                total_stake_lamports: previous_lamports + reward_lamports,
+                pool_token_supply,
+                fee: Fee {
+                    numerator,
+                    denominator: 100,
+                },
+                ..StakePool::default()
+            };
+            let fee_lamports = reward_lamports * numerator / 100;
+            let fee_tokens = stake_pool.calc_epoch_fee_amount(reward_lamports).unwrap();
+            let fee_value = (fee_tokens as u128 * stake_pool.total_stake_lamports as u128)
+                / (pool_token_supply + fee_tokens) as u128;
+            assert!(fee_value <= fee_lamports as u128);
+        }
+
         #[test]
         fn stake_list_size_calculation(test_amount in 0..=100_000_u32) {
             let validators = ValidatorList::new(test_amount);
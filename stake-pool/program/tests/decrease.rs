@@ -0,0 +1,297 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    bincode::deserialize,
+    helpers::*,
+    solana_program::{hash::Hash, instruction::InstructionError, pubkey::Pubkey},
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, id, instruction, stake_program},
+};
+
+async fn setup() -> (
+    BanksClient,
+    Keypair,
+    Hash,
+    StakePoolAccounts,
+    ValidatorStakeAccount,
+    u64,
+) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    let rent = banks_client.get_rent().await.unwrap();
+    let stake_rent = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+    let decrease_lamports = stake_rent + TEST_STAKE_AMOUNT;
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake_account,
+        decrease_lamports,
+    )
+}
+
+#[tokio::test]
+async fn test_decrease_validator_stake() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake,
+        decrease_lamports,
+    ) = setup().await;
+
+    let validator_stake_before =
+        get_account(&mut banks_client, &validator_stake.stake_account).await;
+
+    let error = stake_pool_accounts
+        .decrease_validator_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake.stake_account,
+            &validator_stake.transient_stake_account,
+            decrease_lamports,
+        )
+        .await;
+    assert!(error.is_none());
+
+    // Check the validator stake account gave up the lamports
+    let validator_stake_account =
+        get_account(&mut banks_client, &validator_stake.stake_account).await;
+    assert_eq!(
+        validator_stake_account.lamports,
+        validator_stake_before.lamports - decrease_lamports
+    );
+
+    // Check the transient stake is deactivating
+    let transient_stake =
+        get_account(&mut banks_client, &validator_stake.transient_stake_account).await;
+    assert_eq!(transient_stake.lamports, decrease_lamports);
+    let stake_state = deserialize::<stake_program::StakeState>(&transient_stake.data).unwrap();
+    match stake_state {
+        stake_program::StakeState::Stake(meta, stake) => {
+            assert_eq!(
+                meta.authorized.staker,
+                stake_pool_accounts.withdraw_authority
+            );
+            assert_eq!(stake.delegation.voter_pubkey, validator_stake.vote.pubkey());
+            assert_ne!(stake.delegation.deactivation_epoch, u64::MAX);
+        }
+        _ => panic!(),
+    }
+}
+
+#[tokio::test]
+async fn test_decrease_validator_stake_with_wrong_staker() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake,
+        decrease_lamports,
+    ) = setup().await;
+
+    let wrong_staker = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::decrease_validator_stake(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_staker.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &stake_pool_accounts.validator_list.pubkey(),
+            &validator_stake.stake_account,
+            &validator_stake.transient_stake_account,
+            decrease_lamports,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &wrong_staker], recent_blockhash);
+    let transaction_error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = StakePoolError::WrongStaker as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while decreasing stake with wrong staker"),
+    }
+}
+
+#[tokio::test]
+async fn test_decrease_validator_stake_with_wrong_transient_address() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake,
+        decrease_lamports,
+    ) = setup().await;
+
+    let error = stake_pool_accounts
+        .decrease_validator_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake.stake_account,
+            &Pubkey::new_unique(),
+            decrease_lamports,
+        )
+        .await
+        .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = StakePoolError::InvalidStakeAccountAddress as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while decreasing stake with wrong transient address"),
+    }
+}
+
+#[tokio::test]
+async fn test_decrease_validator_stake_twice_in_one_epoch() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake,
+        decrease_lamports,
+    ) = setup().await;
+
+    let error = stake_pool_accounts
+        .decrease_validator_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake.stake_account,
+            &validator_stake.transient_stake_account,
+            decrease_lamports,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let error = stake_pool_accounts
+        .decrease_validator_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake.stake_account,
+            &validator_stake.transient_stake_account,
+            decrease_lamports + 1,
+        )
+        .await
+        .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = StakePoolError::TransientAccountInUse as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while decreasing stake twice in one epoch"),
+    }
+}
+
+#[tokio::test]
+async fn test_decrease_validator_stake_with_too_little_lamports() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake,
+        decrease_lamports,
+    ) = setup().await;
+
+    let error = stake_pool_accounts
+        .decrease_validator_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake.stake_account,
+            &validator_stake.transient_stake_account,
+            decrease_lamports - TEST_STAKE_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, InstructionError::AccountNotRentExempt);
+        }
+        _ => panic!("Wrong error occurs while decreasing stake by too little"),
+    }
+}
+
+#[tokio::test]
+async fn test_decrease_validator_stake_with_too_many_lamports() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake,
+        _decrease_lamports,
+    ) = setup().await;
+
+    let validator_stake_account =
+        get_account(&mut banks_client, &validator_stake.stake_account).await;
+
+    let error = stake_pool_accounts
+        .decrease_validator_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake.stake_account,
+            &validator_stake.transient_stake_account,
+            validator_stake_account.lamports,
+        )
+        .await
+        .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, InstructionError::InsufficientFunds);
+        }
+        _ => panic!("Wrong error occurs while decreasing stake by too much"),
+    }
+}
@@ -0,0 +1,299 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::hash::Hash,
+    solana_program_test::*,
+    solana_sdk::{
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{borsh::try_from_slice_unchecked, error, id, instruction, state},
+};
+
+async fn setup() -> (BanksClient, Keypair, Hash, StakePoolAccounts, Keypair) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    // make pool token account
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &payer.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        user_pool_account,
+    )
+}
+
+async fn set_sol_deposit_authority(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    new_sol_deposit_authority: Option<&Keypair>,
+) {
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_sol_deposit_authority(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            new_sol_deposit_authority.map(|a| a.pubkey()).as_ref(),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, &stake_pool_accounts.manager], *recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, user_pool_account) =
+        setup().await;
+
+    let stake_pool_before =
+        get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool_before =
+        try_from_slice_unchecked::<state::StakePool>(&stake_pool_before.data.as_slice()).unwrap();
+    let reserve_before = banks_client
+        .get_balance(stake_pool_accounts.reserve_stake.pubkey())
+        .await
+        .unwrap();
+
+    let deposit_lamports = TEST_STAKE_AMOUNT;
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account.pubkey(),
+            deposit_lamports,
+            None,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let tokens_issued = deposit_lamports; // For now tokens are 1:1 to stake
+
+    // Stake pool should add the deposit to its balance and supply
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool =
+        try_from_slice_unchecked::<state::StakePool>(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.total_stake_lamports,
+        stake_pool_before.total_stake_lamports + deposit_lamports
+    );
+    assert_eq!(
+        stake_pool.pool_token_supply,
+        stake_pool_before.pool_token_supply + tokens_issued
+    );
+
+    // Check minted tokens
+    let user_token_balance =
+        get_token_balance(&mut banks_client, &user_pool_account.pubkey()).await;
+    assert_eq!(user_token_balance, tokens_issued);
+    let pool_fee_token_balance = get_token_balance(
+        &mut banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+    assert_eq!(pool_fee_token_balance, 0);
+
+    // Check lamports landed in the reserve
+    let reserve_after = banks_client
+        .get_balance(stake_pool_accounts.reserve_stake.pubkey())
+        .await
+        .unwrap();
+    assert_eq!(reserve_after, reserve_before + deposit_lamports);
+}
+
+#[tokio::test]
+async fn success_with_sol_deposit_authority() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, user_pool_account) =
+        setup().await;
+
+    let sol_deposit_authority = Keypair::new();
+    set_sol_deposit_authority(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        Some(&sol_deposit_authority),
+    )
+    .await;
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool =
+        try_from_slice_unchecked::<state::StakePool>(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.sol_deposit_authority,
+        Some(sol_deposit_authority.pubkey())
+    );
+
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account.pubkey(),
+            TEST_STAKE_AMOUNT,
+            Some(&sol_deposit_authority),
+        )
+        .await;
+    assert!(error.is_none());
+
+    let user_token_balance =
+        get_token_balance(&mut banks_client, &user_pool_account.pubkey()).await;
+    assert_eq!(user_token_balance, TEST_STAKE_AMOUNT);
+
+    // Removing the authority opens deposits to everyone again
+    set_sol_deposit_authority(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        None,
+    )
+    .await;
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account.pubkey(),
+            TEST_STAKE_AMOUNT,
+            None,
+        )
+        .await;
+    assert!(error.is_none());
+}
+
+#[tokio::test]
+async fn fail_without_sol_deposit_authority() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, user_pool_account) =
+        setup().await;
+
+    let sol_deposit_authority = Keypair::new();
+    set_sol_deposit_authority(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        Some(&sol_deposit_authority),
+    )
+    .await;
+
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account.pubkey(),
+            TEST_STAKE_AMOUNT,
+            None,
+        )
+        .await
+        .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::NotEnoughAccountKeys,
+        )) => {}
+        _ => panic!("Wrong error occurs while depositing SOL without the authority"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_wrong_sol_deposit_authority() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, user_pool_account) =
+        setup().await;
+
+    let sol_deposit_authority = Keypair::new();
+    set_sol_deposit_authority(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        Some(&sol_deposit_authority),
+    )
+    .await;
+
+    let wrong_authority = Keypair::new();
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account.pubkey(),
+            TEST_STAKE_AMOUNT,
+            Some(&wrong_authority),
+        )
+        .await
+        .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::InvalidSolDepositAuthority as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while depositing SOL with the wrong authority"),
+    }
+}
+
+#[tokio::test]
+async fn fail_set_sol_deposit_authority_without_manager() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _) = setup().await;
+
+    let wrong_manager = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_sol_deposit_authority(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_manager.pubkey(),
+            Some(&wrong_manager.pubkey()),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &wrong_manager], recent_blockhash);
+    let error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::WrongManager as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while setting the SOL deposit authority"),
+    }
+}
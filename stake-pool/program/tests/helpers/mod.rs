@@ -13,8 +13,8 @@ use {
     },
     solana_vote_program::{self, vote_state::VoteState},
     spl_stake_pool::{
-        borsh::get_instance_packed_len, find_stake_program_address, id, instruction, processor,
-        stake_program, state,
+        borsh::get_instance_packed_len, find_stake_program_address,
+        find_transient_stake_program_address, id, instruction, processor, stake_program, state,
     },
 };
 
@@ -196,6 +196,7 @@ pub async fn create_stake_pool(
     recent_blockhash: &Hash,
     stake_pool: &Keypair,
     validator_list: &Keypair,
+    reserve_stake: &Pubkey,
     pool_mint: &Pubkey,
     pool_token_account: &Pubkey,
     manager: &Keypair,
@@ -231,6 +232,7 @@ pub async fn create_stake_pool(
                 &manager.pubkey(),
                 staker,
                 &validator_list.pubkey(),
+                reserve_stake,
                 pool_mint,
                 pool_token_account,
                 &spl_token::id(),
@@ -300,6 +302,32 @@ pub async fn create_independent_stake_account(
     lamports
 }
 
+pub async fn create_reserve_stake_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake: &Keypair,
+    authorized: &stake_program::Authorized,
+) -> u64 {
+    let rent = banks_client.get_rent().await.unwrap();
+    let lamports = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+
+    let mut transaction = Transaction::new_with_payer(
+        &stake_program::create_account(
+            &payer.pubkey(),
+            &stake.pubkey(),
+            authorized,
+            &stake_program::Lockup::default(),
+            lamports,
+        ),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, stake], *recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    lamports
+}
+
 pub async fn create_blank_stake_account(
     banks_client: &mut BanksClient,
     payer: &Keypair,
@@ -397,6 +425,7 @@ pub async fn authorize_stake_account(
 
 pub struct ValidatorStakeAccount {
     pub stake_account: Pubkey,
+    pub transient_stake_account: Pubkey,
     pub target_authority: Pubkey,
     pub vote: Keypair,
     pub stake_pool: Pubkey,
@@ -406,8 +435,11 @@ impl ValidatorStakeAccount {
     pub fn new_with_target_authority(authority: &Pubkey, stake_pool: &Pubkey) -> Self {
         let validator = Keypair::new();
         let (stake_account, _) = find_stake_program_address(&id(), &validator.pubkey(), stake_pool);
+        let (transient_stake_account, _) =
+            find_transient_stake_program_address(&id(), &stake_account);
         ValidatorStakeAccount {
             stake_account,
+            transient_stake_account,
             target_authority: *authority,
             vote: validator,
             stake_pool: *stake_pool,
@@ -461,6 +493,7 @@ impl ValidatorStakeAccount {
 pub struct StakePoolAccounts {
     pub stake_pool: Keypair,
     pub validator_list: Keypair,
+    pub reserve_stake: Keypair,
     pub pool_mint: Keypair,
     pub pool_fee_account: Keypair,
     pub manager: Keypair,
@@ -475,6 +508,7 @@ impl StakePoolAccounts {
     pub fn new() -> Self {
         let stake_pool = Keypair::new();
         let validator_list = Keypair::new();
+        let reserve_stake = Keypair::new();
         let stake_pool_address = &stake_pool.pubkey();
         let (withdraw_authority, _) = Pubkey::find_program_address(
             &[&stake_pool_address.to_bytes()[..32], b"withdraw"],
@@ -492,6 +526,7 @@ impl StakePoolAccounts {
         Self {
             stake_pool,
             validator_list,
+            reserve_stake,
             pool_mint,
             pool_fee_account,
             manager,
@@ -506,10 +541,6 @@ impl StakePoolAccounts {
         }
     }
 
-    pub fn calculate_fee(&self, amount: u64) -> u64 {
-        amount * self.fee.numerator / self.fee.denominator
-    }
-
     pub async fn initialize_stake_pool(
         &self,
         mut banks_client: &mut BanksClient,
@@ -533,12 +564,24 @@ impl StakePoolAccounts {
             &self.manager.pubkey(),
         )
         .await?;
+        create_reserve_stake_account(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &self.reserve_stake,
+            &stake_program::Authorized {
+                staker: self.withdraw_authority,
+                withdrawer: self.withdraw_authority,
+            },
+        )
+        .await;
         create_stake_pool(
             &mut banks_client,
             &payer,
             &recent_blockhash,
             &self.stake_pool,
             &self.validator_list,
+            &self.reserve_stake.pubkey(),
             &self.pool_mint.pubkey(),
             &self.pool_fee_account.pubkey(),
             &self.manager,
@@ -569,7 +612,6 @@ impl StakePoolAccounts {
                 stake,
                 validator_stake_account,
                 pool_account,
-                &self.pool_fee_account.pubkey(),
                 &self.pool_mint.pubkey(),
                 &spl_token::id(),
             )
@@ -581,6 +623,78 @@ impl StakePoolAccounts {
         Ok(())
     }
 
+    pub async fn deposit_sol(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        pool_account: &Pubkey,
+        amount: u64,
+        sol_deposit_authority: Option<&Keypair>,
+    ) -> Option<TransportError> {
+        let mut signers = vec![payer];
+        let instruction = if let Some(sol_deposit_authority) = sol_deposit_authority {
+            signers.push(sol_deposit_authority);
+            instruction::deposit_sol_with_authority(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.withdraw_authority,
+                &self.reserve_stake.pubkey(),
+                &payer.pubkey(),
+                pool_account,
+                &self.pool_mint.pubkey(),
+                &spl_token::id(),
+                &sol_deposit_authority.pubkey(),
+                amount,
+            )
+        } else {
+            instruction::deposit_sol(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.withdraw_authority,
+                &self.reserve_stake.pubkey(),
+                &payer.pubkey(),
+                pool_account,
+                &self.pool_mint.pubkey(),
+                &spl_token::id(),
+                amount,
+            )
+        }
+        .unwrap();
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.sign(&signers, *recent_blockhash);
+        banks_client.process_transaction(transaction).await.err()
+    }
+
+    pub async fn withdraw_sol(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        pool_account: &Pubkey,
+        lamports_to: &Pubkey,
+        amount: u64,
+    ) -> Option<TransportError> {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::withdraw_sol(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.withdraw_authority,
+                pool_account,
+                &self.reserve_stake.pubkey(),
+                lamports_to,
+                &self.pool_fee_account.pubkey(),
+                &self.pool_mint.pubkey(),
+                &spl_token::id(),
+                amount,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[payer], *recent_blockhash);
+        banks_client.process_transaction(transaction).await.err()
+    }
+
     pub async fn withdraw_stake(
         &self,
         banks_client: &mut BanksClient,
@@ -670,6 +784,108 @@ impl StakePoolAccounts {
         transaction.sign(&[payer, &self.staker], *recent_blockhash);
         banks_client.process_transaction(transaction).await.err()
     }
+
+    pub async fn update_validator_list_balance(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        validator_vote_accounts: &[Pubkey],
+    ) -> Option<TransportError> {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::update_validator_list_balance(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.withdraw_authority,
+                &self.validator_list.pubkey(),
+                &self.reserve_stake.pubkey(),
+                validator_vote_accounts,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[payer], *recent_blockhash);
+        banks_client.process_transaction(transaction).await.err()
+    }
+
+    pub async fn update_stake_pool_balance(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+    ) -> Option<TransportError> {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::update_stake_pool_balance(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.withdraw_authority,
+                &self.validator_list.pubkey(),
+                &self.reserve_stake.pubkey(),
+                &self.pool_fee_account.pubkey(),
+                &self.pool_mint.pubkey(),
+                &spl_token::id(),
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[payer], *recent_blockhash);
+        banks_client.process_transaction(transaction).await.err()
+    }
+
+    pub async fn decrease_validator_stake(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        validator_stake: &Pubkey,
+        transient_stake: &Pubkey,
+        lamports: u64,
+    ) -> Option<TransportError> {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::decrease_validator_stake(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.staker.pubkey(),
+                &self.withdraw_authority,
+                &self.validator_list.pubkey(),
+                validator_stake,
+                transient_stake,
+                lamports,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[payer, &self.staker], *recent_blockhash);
+        banks_client.process_transaction(transaction).await.err()
+    }
+
+    pub async fn increase_validator_stake(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        transient_stake: &Pubkey,
+        validator: &Pubkey,
+        lamports: u64,
+    ) -> Option<TransportError> {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::increase_validator_stake(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.staker.pubkey(),
+                &self.withdraw_authority,
+                &self.validator_list.pubkey(),
+                &self.reserve_stake.pubkey(),
+                transient_stake,
+                validator,
+                lamports,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[payer, &self.staker], *recent_blockhash);
+        banks_client.process_transaction(transaction).await.err()
+    }
 }
 
 pub async fn simple_add_validator_to_pool(
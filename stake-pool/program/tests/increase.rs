@@ -0,0 +1,378 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    bincode::deserialize,
+    helpers::*,
+    solana_program::{
+        hash::Hash, instruction::InstructionError, native_token::sol_to_lamports, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{
+        borsh::try_from_slice_unchecked, error::StakePoolError, id, instruction, stake_program,
+        state,
+    },
+};
+
+async fn setup() -> (
+    BanksClient,
+    Keypair,
+    Hash,
+    StakePoolAccounts,
+    ValidatorStakeAccount,
+    u64,
+) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    let reserve_lamports = sol_to_lamports(10.0);
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        reserve_lamports,
+    )
+    .await;
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake_account,
+        reserve_lamports,
+    )
+}
+
+#[tokio::test]
+async fn test_increase_validator_stake() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake,
+        reserve_lamports,
+    ) = setup().await;
+
+    let reserve_before = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await
+    .lamports;
+
+    let increase_amount = reserve_lamports / 2;
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake.transient_stake_account,
+            &validator_stake.vote.pubkey(),
+            increase_amount,
+        )
+        .await;
+    assert!(error.is_none());
+
+    // Check the reserve paid for the transient stake
+    let reserve_stake = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await;
+    assert_eq!(reserve_stake.lamports, reserve_before - increase_amount);
+
+    // Check the transient stake is delegated to the validator
+    let transient_stake =
+        get_account(&mut banks_client, &validator_stake.transient_stake_account).await;
+    assert_eq!(transient_stake.lamports, increase_amount);
+    let stake_state = deserialize::<stake_program::StakeState>(&transient_stake.data).unwrap();
+    match stake_state {
+        stake_program::StakeState::Stake(meta, stake) => {
+            assert_eq!(
+                meta.authorized.staker,
+                stake_pool_accounts.withdraw_authority
+            );
+            assert_eq!(
+                meta.authorized.withdrawer,
+                stake_pool_accounts.withdraw_authority
+            );
+            assert_eq!(stake.delegation.voter_pubkey, validator_stake.vote.pubkey());
+        }
+        _ => panic!(),
+    }
+
+    // Check the validator list counts the transient stake
+    let validator_stake_account =
+        get_account(&mut banks_client, &validator_stake.stake_account).await;
+    let validator_list = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        try_from_slice_unchecked::<state::ValidatorList>(validator_list.data.as_slice()).unwrap();
+    let validator_list_item = validator_list.find(&validator_stake.vote.pubkey()).unwrap();
+    assert_eq!(
+        validator_list_item.stake_lamports,
+        validator_stake_account.lamports + increase_amount
+    );
+}
+
+#[tokio::test]
+async fn test_increase_validator_stake_with_wrong_staker() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake,
+        reserve_lamports,
+    ) = setup().await;
+
+    let wrong_staker = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::increase_validator_stake(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_staker.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &validator_stake.transient_stake_account,
+            &validator_stake.vote.pubkey(),
+            reserve_lamports / 2,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &wrong_staker], recent_blockhash);
+    let transaction_error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = StakePoolError::WrongStaker as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while increasing stake with wrong staker"),
+    }
+}
+
+#[tokio::test]
+async fn test_increase_validator_stake_with_unknown_validator() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        _validator_stake,
+        reserve_lamports,
+    ) = setup().await;
+
+    let unknown_stake = ValidatorStakeAccount::new_with_target_authority(
+        &stake_pool_accounts.deposit_authority,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    );
+    create_vote(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &unknown_stake.vote,
+    )
+    .await;
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &unknown_stake.transient_stake_account,
+            &unknown_stake.vote.pubkey(),
+            reserve_lamports / 2,
+        )
+        .await
+        .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = StakePoolError::ValidatorNotFound as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while increasing stake on an unknown validator"),
+    }
+}
+
+#[tokio::test]
+async fn test_increase_validator_stake_with_wrong_transient_address() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake,
+        reserve_lamports,
+    ) = setup().await;
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &Pubkey::new_unique(),
+            &validator_stake.vote.pubkey(),
+            reserve_lamports / 2,
+        )
+        .await
+        .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = StakePoolError::InvalidStakeAccountAddress as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while increasing stake with wrong transient address"),
+    }
+}
+
+#[tokio::test]
+async fn test_increase_validator_stake_twice_in_one_epoch() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake,
+        reserve_lamports,
+    ) = setup().await;
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake.transient_stake_account,
+            &validator_stake.vote.pubkey(),
+            reserve_lamports / 4,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake.transient_stake_account,
+            &validator_stake.vote.pubkey(),
+            reserve_lamports / 3,
+        )
+        .await
+        .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = StakePoolError::TransientAccountInUse as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while increasing stake twice in one epoch"),
+    }
+}
+
+#[tokio::test]
+async fn test_increase_validator_stake_with_too_little_lamports() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake,
+        _reserve_lamports,
+    ) = setup().await;
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake.transient_stake_account,
+            &validator_stake.vote.pubkey(),
+            sol_to_lamports(1.0),
+        )
+        .await
+        .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, InstructionError::AccountNotRentExempt);
+        }
+        _ => panic!("Wrong error occurs while increasing stake by too little"),
+    }
+}
+
+#[tokio::test]
+async fn test_increase_validator_stake_with_insufficient_reserve() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake,
+        reserve_lamports,
+    ) = setup().await;
+
+    // The reserve must keep its own rent exemption
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake.transient_stake_account,
+            &validator_stake.vote.pubkey(),
+            reserve_lamports + 1,
+        )
+        .await
+        .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, InstructionError::InsufficientFunds);
+        }
+        _ => panic!("Wrong error occurs while increasing stake beyond the reserve"),
+    }
+}
@@ -19,7 +19,7 @@ use {
     },
     spl_stake_pool::{
         borsh::{get_instance_packed_len, try_from_slice_unchecked},
-        error, id, instruction, state,
+        error, id, instruction, stake_program, state,
     },
 };
 
@@ -207,6 +207,7 @@ async fn fail_initialize_with_wrong_max_validators() {
                 &stake_pool_accounts.manager.pubkey(),
                 &stake_pool_accounts.staker.pubkey(),
                 &stake_pool_accounts.validator_list.pubkey(),
+                &stake_pool_accounts.reserve_stake.pubkey(),
                 &stake_pool_accounts.pool_mint.pubkey(),
                 &stake_pool_accounts.pool_fee_account.pubkey(),
                 &spl_token::id(),
@@ -275,6 +276,7 @@ async fn fail_initialize_with_wrong_mint_authority() {
         &recent_blockhash,
         &stake_pool_accounts.stake_pool,
         &stake_pool_accounts.validator_list,
+        &stake_pool_accounts.reserve_stake.pubkey(),
         &wrong_mint.pubkey(),
         &stake_pool_accounts.pool_fee_account.pubkey(),
         &stake_pool_accounts.manager,
@@ -363,6 +365,7 @@ async fn fail_initialize_with_wrong_token_program_id() {
                 &stake_pool_accounts.manager.pubkey(),
                 &stake_pool_accounts.staker.pubkey(),
                 &stake_pool_accounts.validator_list.pubkey(),
+                &stake_pool_accounts.reserve_stake.pubkey(),
                 &stake_pool_accounts.pool_mint.pubkey(),
                 &stake_pool_accounts.pool_fee_account.pubkey(),
                 &wrong_token_program.pubkey(),
@@ -437,6 +440,7 @@ async fn fail_initialize_with_wrong_fee_account() {
         &recent_blockhash,
         &stake_pool_accounts.stake_pool,
         &stake_pool_accounts.validator_list,
+        &stake_pool_accounts.reserve_stake.pubkey(),
         &stake_pool_accounts.pool_mint.pubkey(),
         &stake_pool_accounts.pool_fee_account.pubkey(),
         &stake_pool_accounts.manager,
@@ -524,6 +528,7 @@ async fn fail_initialize_with_not_rent_exempt_pool() {
                 &stake_pool_accounts.manager.pubkey(),
                 &stake_pool_accounts.staker.pubkey(),
                 &stake_pool_accounts.validator_list.pubkey(),
+                &stake_pool_accounts.reserve_stake.pubkey(),
                 &stake_pool_accounts.pool_mint.pubkey(),
                 &stake_pool_accounts.pool_fee_account.pubkey(),
                 &spl_token::id(),
@@ -597,6 +602,7 @@ async fn fail_initialize_with_not_rent_exempt_validator_list() {
                 &stake_pool_accounts.manager.pubkey(),
                 &stake_pool_accounts.staker.pubkey(),
                 &stake_pool_accounts.validator_list.pubkey(),
+                &stake_pool_accounts.reserve_stake.pubkey(),
                 &stake_pool_accounts.pool_mint.pubkey(),
                 &stake_pool_accounts.pool_fee_account.pubkey(),
                 &spl_token::id(),
@@ -661,6 +667,7 @@ async fn fail_initialize_without_manager_signature() {
         AccountMeta::new_readonly(stake_pool_accounts.manager.pubkey(), false),
         AccountMeta::new_readonly(stake_pool_accounts.staker.pubkey(), false),
         AccountMeta::new(stake_pool_accounts.validator_list.pubkey(), false),
+        AccountMeta::new_readonly(stake_pool_accounts.reserve_stake.pubkey(), false),
         AccountMeta::new_readonly(stake_pool_accounts.pool_mint.pubkey(), false),
         AccountMeta::new_readonly(stake_pool_accounts.pool_fee_account.pubkey(), false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
@@ -766,6 +773,7 @@ async fn fail_initialize_with_pre_minted_pool_tokens() {
         &recent_blockhash,
         &stake_pool_accounts.stake_pool,
         &stake_pool_accounts.validator_list,
+        &stake_pool_accounts.reserve_stake.pubkey(),
         &stake_pool_accounts.pool_mint.pubkey(),
         &stake_pool_accounts.pool_fee_account.pubkey(),
         &stake_pool_accounts.manager,
@@ -788,3 +796,124 @@ async fn fail_initialize_with_pre_minted_pool_tokens() {
         _ => panic!("Wrong error occurs while try to initialize stake pool with wrong mint authority of pool fee account"),
     }
 }
+
+#[tokio::test]
+async fn fail_initialize_with_wrong_reserve_authority() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+
+    create_mint_and_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    let wrong_authority = Keypair::new();
+    create_reserve_stake_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.reserve_stake,
+        &stake_program::Authorized {
+            staker: wrong_authority.pubkey(),
+            withdrawer: wrong_authority.pubkey(),
+        },
+    )
+    .await;
+
+    let transaction_error = create_stake_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.stake_pool,
+        &stake_pool_accounts.validator_list,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.manager,
+        &stake_pool_accounts.staker.pubkey(),
+        &stake_pool_accounts.fee,
+        stake_pool_accounts.max_validators,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::WrongStakeState as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!(
+            "Wrong error occurs while try to initialize stake pool with wrong reserve authority"
+        ),
+    }
+}
+
+#[tokio::test]
+async fn fail_initialize_with_funded_reserve() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+
+    create_mint_and_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    create_reserve_stake_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.reserve_stake,
+        &stake_program::Authorized {
+            staker: stake_pool_accounts.withdraw_authority,
+            withdrawer: stake_pool_accounts.withdraw_authority,
+        },
+    )
+    .await;
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        TEST_STAKE_AMOUNT,
+    )
+    .await;
+
+    let transaction_error = create_stake_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.stake_pool,
+        &stake_pool_accounts.validator_list,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.manager,
+        &stake_pool_accounts.staker.pubkey(),
+        &stake_pool_accounts.fee,
+        stake_pool_accounts.max_validators,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::WrongStakeState as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while try to initialize stake pool with a funded reserve"),
+    }
+}
@@ -0,0 +1,219 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    bincode::deserialize,
+    helpers::*,
+    solana_program::{clock::Clock, epoch_schedule::EpochSchedule, hash::Hash, sysvar},
+    solana_program_test::*,
+    solana_sdk::{
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{borsh::try_from_slice_unchecked, error, id, instruction, state},
+};
+
+async fn setup() -> (ProgramTestContext, StakePoolAccounts) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+    (context, stake_pool_accounts)
+}
+
+async fn set_fee(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    manager: &Keypair,
+    fee: instruction::Fee,
+) -> Option<TransportError> {
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_fee(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &manager.pubkey(),
+            fee,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, manager], *recent_blockhash);
+    banks_client.process_transaction(transaction).await.err()
+}
+
+async fn warp_to_next_epoch(context: &mut ProgramTestContext) -> Hash {
+    let clock = get_account(&mut context.banks_client, &sysvar::clock::id()).await;
+    let clock = deserialize::<Clock>(&clock.data).unwrap();
+    let epoch_schedule =
+        get_account(&mut context.banks_client, &sysvar::epoch_schedule::id()).await;
+    let epoch_schedule = deserialize::<EpochSchedule>(&epoch_schedule.data).unwrap();
+    context
+        .warp_to_slot(epoch_schedule.get_first_slot_in_epoch(clock.epoch + 1))
+        .unwrap();
+    context.banks_client.get_recent_blockhash().await.unwrap()
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut context, stake_pool_accounts) = setup().await;
+    let new_fee = instruction::Fee {
+        numerator: 10,
+        denominator: 10,
+    };
+
+    let error = set_fee(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        new_fee,
+    )
+    .await;
+    assert!(error.is_none());
+
+    // The new fee is only scheduled, the current one stays in place
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = try_from_slice_unchecked::<state::StakePool>(&stake_pool.data).unwrap();
+    assert_eq!(stake_pool.fee, stake_pool_accounts.fee);
+    assert_eq!(stake_pool.next_epoch_fee, Some(new_fee));
+
+    // Updating within the same epoch does not apply it
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = try_from_slice_unchecked::<state::StakePool>(&stake_pool.data).unwrap();
+    assert_eq!(stake_pool.fee, stake_pool_accounts.fee);
+
+    // The first update of the next epoch does
+    let recent_blockhash = warp_to_next_epoch(&mut context).await;
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+    assert!(error.is_none());
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = try_from_slice_unchecked::<state::StakePool>(&stake_pool.data).unwrap();
+    assert_eq!(stake_pool.fee, new_fee);
+    assert_eq!(stake_pool.next_epoch_fee, None);
+}
+
+#[tokio::test]
+async fn fail_wrong_manager() {
+    let (mut context, stake_pool_accounts) = setup().await;
+
+    let wrong_manager = Keypair::new();
+    let error = set_fee(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+        &wrong_manager,
+        instruction::Fee {
+            numerator: 1,
+            denominator: 100,
+        },
+    )
+    .await
+    .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::WrongManager as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while setting the fee with the wrong manager"),
+    }
+}
+
+#[tokio::test]
+async fn fail_high_fee() {
+    let (mut context, stake_pool_accounts) = setup().await;
+
+    let error = set_fee(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        instruction::Fee {
+            numerator: 11,
+            denominator: 10,
+        },
+    )
+    .await
+    .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::FeeTooHigh as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while setting a fee above 100%"),
+    }
+}
+
+#[tokio::test]
+async fn fail_not_updated() {
+    let (mut context, stake_pool_accounts) = setup().await;
+
+    let recent_blockhash = warp_to_next_epoch(&mut context).await;
+    let error = set_fee(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        instruction::Fee {
+            numerator: 1,
+            denominator: 100,
+        },
+    )
+    .await
+    .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::StakeListAndPoolOutOfDate as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while setting the fee on an outdated pool"),
+    }
+}
@@ -4,6 +4,7 @@ mod helpers;
 
 use {
     helpers::*,
+    solana_program::native_token,
     solana_program_test::*,
     solana_sdk::{
         instruction::InstructionError, signature::Keypair, signature::Signer,
@@ -24,6 +25,90 @@ async fn test_update_stake_pool_balance() {
     // TODO: Waiting for the ability to advance clock (or modify account data) to finish the tests
 }
 
+#[tokio::test]
+async fn test_update_stake_pool_balance_charges_fee_on_rewards() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &payer.pubkey(),
+    )
+    .await
+    .unwrap();
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account.pubkey(),
+            native_token::sol_to_lamports(10.0),
+            None,
+        )
+        .await;
+    assert!(error.is_none());
+
+    // Simulate rewards landing in the reserve
+    let reward_lamports = native_token::sol_to_lamports(1.0);
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        reward_lamports,
+    )
+    .await;
+
+    let stake_pool_before =
+        get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let mut stake_pool_before =
+        spl_stake_pool::borsh::try_from_slice_unchecked::<state::StakePool>(
+            &stake_pool_before.data.as_slice(),
+        )
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    assert!(error.is_none());
+
+    stake_pool_before.total_stake_lamports += reward_lamports;
+    let fee_tokens = stake_pool_before
+        .calc_epoch_fee_amount(reward_lamports)
+        .unwrap();
+    assert!(fee_tokens > 0);
+
+    let pool_fee_balance = get_token_balance(
+        &mut banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+    assert_eq!(pool_fee_balance, fee_tokens);
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = spl_stake_pool::borsh::try_from_slice_unchecked::<state::StakePool>(
+        &stake_pool.data.as_slice(),
+    )
+    .unwrap();
+    assert_eq!(
+        stake_pool.total_stake_lamports,
+        stake_pool_before.total_stake_lamports
+    );
+    assert_eq!(
+        stake_pool.pool_token_supply,
+        stake_pool_before.pool_token_supply + fee_tokens
+    );
+}
+
 #[tokio::test]
 async fn test_update_stake_pool_balance_with_wrong_validator_list() {
     let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
@@ -38,7 +123,12 @@ async fn test_update_stake_pool_balance_with_wrong_validator_list() {
         &[instruction::update_stake_pool_balance(
             &id(),
             &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
             &wrong_stake_list_storage.pubkey(),
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &spl_token::id(),
         )
         .unwrap()],
         Some(&payer.pubkey()),
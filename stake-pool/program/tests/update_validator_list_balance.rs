@@ -4,8 +4,11 @@ mod helpers;
 
 use {
     crate::helpers::TEST_STAKE_AMOUNT,
+    bincode::deserialize,
     helpers::*,
-    solana_program::{native_token, pubkey::Pubkey},
+    solana_program::{
+        clock::Clock, epoch_schedule::EpochSchedule, native_token, pubkey::Pubkey, sysvar,
+    },
     solana_program_test::*,
     solana_sdk::signature::Signer,
     spl_stake_pool::{borsh::try_from_slice_unchecked, stake_program, state},
@@ -82,6 +85,157 @@ async fn test_update_validator_list_balance() {
     // TODO: Execute update list with updated clock
 }
 
+#[tokio::test]
+async fn test_update_validator_list_balance_merges_transient_stakes() {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    let increased_stake = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+    let decreased_stake = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let stake_rent = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+    let increase_lamports = stake_rent + native_token::sol_to_lamports(2.0);
+    let decrease_lamports = stake_rent + TEST_STAKE_AMOUNT;
+
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        increase_lamports,
+    )
+    .await;
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &increased_stake.transient_stake_account,
+            &increased_stake.vote.pubkey(),
+            increase_lamports,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let error = stake_pool_accounts
+        .decrease_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &decreased_stake.stake_account,
+            &decreased_stake.transient_stake_account,
+            decrease_lamports,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let list_sum_before = get_list_sum(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let increased_before = get_account(&mut context.banks_client, &increased_stake.stake_account)
+        .await
+        .lamports;
+    let decreased_before = get_account(&mut context.banks_client, &decreased_stake.stake_account)
+        .await
+        .lamports;
+
+    // Move far enough that both transient stakes have finished (de)activating
+    let clock = get_account(&mut context.banks_client, &sysvar::clock::id()).await;
+    let clock = deserialize::<Clock>(&clock.data).unwrap();
+    let epoch_schedule =
+        get_account(&mut context.banks_client, &sysvar::epoch_schedule::id()).await;
+    let epoch_schedule = deserialize::<EpochSchedule>(&epoch_schedule.data).unwrap();
+    context
+        .warp_to_slot(epoch_schedule.get_first_slot_in_epoch(clock.epoch + 2))
+        .unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            &[increased_stake.vote.pubkey(), decreased_stake.vote.pubkey()],
+        )
+        .await;
+    assert!(error.is_none());
+
+    // Both transient stakes were merged away
+    for transient_stake in &[
+        increased_stake.transient_stake_account,
+        decreased_stake.transient_stake_account,
+    ] {
+        assert!(context
+            .banks_client
+            .get_account(*transient_stake)
+            .await
+            .unwrap()
+            .is_none());
+    }
+    let increased_after = get_account(&mut context.banks_client, &increased_stake.stake_account)
+        .await
+        .lamports;
+    assert_eq!(increased_after, increased_before + increase_lamports);
+    let decreased_after = get_account(&mut context.banks_client, &decreased_stake.stake_account)
+        .await
+        .lamports;
+    assert_eq!(decreased_after, decreased_before);
+
+    // The deactivated lamports went back to the reserve
+    let reserve_stake = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await;
+    assert_eq!(reserve_stake.lamports, stake_rent + decrease_lamports);
+
+    assert_eq!(
+        get_list_sum(
+            &mut context.banks_client,
+            &stake_pool_accounts.validator_list.pubkey()
+        )
+        .await,
+        list_sum_before - decrease_lamports
+    );
+
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+    assert!(error.is_none());
+
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = try_from_slice_unchecked::<state::StakePool>(&stake_pool.data).unwrap();
+    assert_eq!(stake_pool.total_stake_lamports, list_sum_before);
+}
+
 #[tokio::test]
 async fn test_update_validator_list_balance_with_uninitialized_validator_list() {} // TODO
 
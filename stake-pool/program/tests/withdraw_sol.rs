@@ -0,0 +1,305 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{hash::Hash, native_token},
+    solana_program_test::*,
+    solana_sdk::{
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{borsh::try_from_slice_unchecked, error, id, instruction, state},
+};
+
+async fn setup() -> (
+    BanksClient,
+    Keypair,
+    Hash,
+    StakePoolAccounts,
+    Keypair,
+    Keypair,
+    u64,
+) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    // make pool token account
+    let user = Keypair::new();
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    // fill up the reserve
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account.pubkey(),
+            native_token::sol_to_lamports(10.0),
+            None,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let pool_tokens = get_token_balance(&mut banks_client, &user_pool_account.pubkey()).await;
+
+    // Delegate tokens for burning
+    delegate_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account.pubkey(),
+        &user,
+        &stake_pool_accounts.withdraw_authority,
+        pool_tokens,
+    )
+    .await;
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        user,
+        user_pool_account,
+        pool_tokens,
+    )
+}
+
+async fn set_sol_withdrawal_fee(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    fee: instruction::Fee,
+) -> Option<TransportError> {
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_sol_withdrawal_fee(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            fee,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, &stake_pool_accounts.manager], *recent_blockhash);
+    banks_client.process_transaction(transaction).await.err()
+}
+
+#[tokio::test]
+async fn success() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        user,
+        user_pool_account,
+        pool_tokens,
+    ) = setup().await;
+
+    let stake_pool_before =
+        get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool_before =
+        try_from_slice_unchecked::<state::StakePool>(&stake_pool_before.data.as_slice()).unwrap();
+    let reserve_before = banks_client
+        .get_balance(stake_pool_accounts.reserve_stake.pubkey())
+        .await
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .withdraw_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account.pubkey(),
+            &user.pubkey(),
+            pool_tokens,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let withdrawn_lamports = stake_pool_before
+        .calc_lamports_withdraw_amount(pool_tokens)
+        .unwrap();
+
+    // Check pool stats
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool =
+        try_from_slice_unchecked::<state::StakePool>(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.total_stake_lamports,
+        stake_pool_before.total_stake_lamports - withdrawn_lamports
+    );
+    assert_eq!(
+        stake_pool.pool_token_supply,
+        stake_pool_before.pool_token_supply - pool_tokens
+    );
+
+    // Check tokens burned and lamports paid out of the reserve
+    let user_token_balance =
+        get_token_balance(&mut banks_client, &user_pool_account.pubkey()).await;
+    assert_eq!(user_token_balance, 0);
+    let user_lamports = banks_client.get_balance(user.pubkey()).await.unwrap();
+    assert_eq!(user_lamports, withdrawn_lamports);
+    let reserve_after = banks_client
+        .get_balance(stake_pool_accounts.reserve_stake.pubkey())
+        .await
+        .unwrap();
+    assert_eq!(reserve_after, reserve_before - withdrawn_lamports);
+}
+
+#[tokio::test]
+async fn success_with_sol_withdrawal_fee() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        user,
+        user_pool_account,
+        pool_tokens,
+    ) = setup().await;
+
+    let fee = instruction::Fee {
+        numerator: 3,
+        denominator: 1000,
+    };
+    let error = set_sol_withdrawal_fee(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        fee,
+    )
+    .await;
+    assert!(error.is_none());
+
+    let stake_pool_before =
+        get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool_before =
+        try_from_slice_unchecked::<state::StakePool>(&stake_pool_before.data.as_slice()).unwrap();
+    assert_eq!(stake_pool_before.sol_withdrawal_fee, fee);
+    let pool_fee_balance_before = get_token_balance(
+        &mut banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+
+    let error = stake_pool_accounts
+        .withdraw_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account.pubkey(),
+            &user.pubkey(),
+            pool_tokens,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let fee_tokens = pool_tokens * fee.numerator / fee.denominator;
+    let withdrawn_lamports = stake_pool_before
+        .calc_lamports_withdraw_amount(pool_tokens - fee_tokens)
+        .unwrap();
+
+    let pool_fee_balance = get_token_balance(
+        &mut banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+    assert_eq!(pool_fee_balance, pool_fee_balance_before + fee_tokens);
+    let user_lamports = banks_client.get_balance(user.pubkey()).await.unwrap();
+    assert_eq!(user_lamports, withdrawn_lamports);
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool =
+        try_from_slice_unchecked::<state::StakePool>(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.pool_token_supply,
+        stake_pool_before.pool_token_supply - pool_tokens + fee_tokens
+    );
+}
+
+#[tokio::test]
+async fn fail_with_not_enough_reserve_lamports() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        user,
+        user_pool_account,
+        pool_tokens,
+    ) = setup().await;
+
+    let error = stake_pool_accounts
+        .withdraw_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account.pubkey(),
+            &user.pubkey(),
+            pool_tokens * 2,
+        )
+        .await
+        .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::SolWithdrawalTooLarge as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while withdrawing more SOL than the reserve holds"),
+    }
+}
+
+#[tokio::test]
+async fn fail_set_sol_withdrawal_fee_too_high() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _, _, _) = setup().await;
+
+    let error = set_sol_withdrawal_fee(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        instruction::Fee {
+            numerator: 2,
+            denominator: 1,
+        },
+    )
+    .await
+    .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::FeeTooHigh as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while setting a SOL withdrawal fee above 100%"),
+    }
+}
@@ -0,0 +1,142 @@
+use {
+    solana_account_decoder::UiAccountEncoding,
+    solana_client::{
+        client_error::ClientError,
+        rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
+    },
+    solana_program::{
+        program_pack::{IsInitialized, Pack},
+        pubkey::Pubkey,
+    },
+    spl_token_lending::state::{LendingMarket, Obligation, Reserve},
+};
+
+type Error = Box<dyn std::error::Error>;
+
+pub fn get_lending_market(
+    rpc_client: &RpcClient,
+    lending_market_address: &Pubkey,
+) -> Result<LendingMarket, Error> {
+    let account_data = rpc_client.get_account_data(lending_market_address)?;
+    let lending_market = LendingMarket::unpack(account_data.as_slice())
+        .map_err(|err| format!("Invalid lending market {}: {}", lending_market_address, err))?;
+    Ok(lending_market)
+}
+
+pub fn get_reserve(rpc_client: &RpcClient, reserve_address: &Pubkey) -> Result<Reserve, Error> {
+    let account_data = rpc_client.get_account_data(reserve_address)?;
+    let reserve = Reserve::unpack(account_data.as_slice())
+        .map_err(|err| format!("Invalid reserve {}: {}", reserve_address, err))?;
+    Ok(reserve)
+}
+
+pub fn get_obligation(
+    rpc_client: &RpcClient,
+    obligation_address: &Pubkey,
+) -> Result<Obligation, Error> {
+    let account_data = rpc_client.get_account_data(obligation_address)?;
+    let obligation = Obligation::unpack(account_data.as_slice())
+        .map_err(|err| format!("Invalid obligation {}: {}", obligation_address, err))?;
+    Ok(obligation)
+}
+
+pub fn get_token_account(
+    rpc_client: &RpcClient,
+    token_account_address: &Pubkey,
+    expected_token_mint: &Pubkey,
+) -> Result<spl_token::state::Account, Error> {
+    let account_data = rpc_client.get_account_data(token_account_address)?;
+    let token_account = spl_token::state::Account::unpack_from_slice(account_data.as_slice())
+        .map_err(|err| format!("Invalid token account {}: {}", token_account_address, err))?;
+
+    if token_account.mint != *expected_token_mint {
+        Err(format!(
+            "Invalid token mint for {}, expected mint is {}",
+            token_account_address, expected_token_mint
+        )
+        .into())
+    } else {
+        Ok(token_account)
+    }
+}
+
+pub fn get_token_mint(
+    rpc_client: &RpcClient,
+    token_mint_address: &Pubkey,
+) -> Result<spl_token::state::Mint, Error> {
+    let account_data = rpc_client.get_account_data(token_mint_address)?;
+    let token_mint = spl_token::state::Mint::unpack_from_slice(account_data.as_slice())
+        .map_err(|err| format!("Invalid token mint {}: {}", token_mint_address, err))?;
+
+    Ok(token_mint)
+}
+
+pub fn get_lending_market_reserves(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    lending_market_address: &Pubkey,
+) -> Result<Vec<(Pubkey, Reserve)>, ClientError> {
+    // 9 is the lending market offset in a reserve
+    get_lending_market_accounts(rpc_client, program_id, lending_market_address, 9, "reserve")
+}
+
+pub fn get_lending_market_obligations(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    lending_market_address: &Pubkey,
+) -> Result<Vec<(Pubkey, Obligation)>, ClientError> {
+    // 10 is the lending market offset in an obligation
+    get_lending_market_accounts(
+        rpc_client,
+        program_id,
+        lending_market_address,
+        10,
+        "obligation",
+    )
+}
+
+fn get_lending_market_accounts<T: Pack + IsInitialized>(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    lending_market_address: &Pubkey,
+    lending_market_offset: usize,
+    account_name: &str,
+) -> Result<Vec<(Pubkey, T)>, ClientError> {
+    rpc_client
+        .get_program_accounts_with_config(
+            program_id,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![
+                    RpcFilterType::DataSize(T::LEN as u64),
+                    RpcFilterType::Memcmp(Memcmp {
+                        offset: lending_market_offset,
+                        bytes: MemcmpEncodedBytes::Binary(format!("{}", lending_market_address)),
+                        encoding: None,
+                    }),
+                ]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..RpcAccountInfoConfig::default()
+                },
+            },
+        )
+        .map(|accounts| {
+            accounts
+                .into_iter()
+                .filter_map(
+                    |(address, account)| match T::unpack(account.data.as_slice()) {
+                        Ok(state) => Some((address, state)),
+                        Err(err) => {
+                            eprintln!(
+                                "Invalid {} account data for {}: {}",
+                                account_name, address, err
+                            );
+                            None
+                        }
+                    },
+                )
+                .collect()
+        })
+}
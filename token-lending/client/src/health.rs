@@ -0,0 +1,144 @@
+//! Obligation health computed off-chain the same way `RefreshObligation`
+//! computes it on-chain
+
+use {
+    crate::client::{get_lending_market_obligations, get_lending_market_reserves},
+    solana_client::rpc_client::RpcClient,
+    solana_program::{clock::Slot, program_error::ProgramError, pubkey::Pubkey},
+    spl_token_lending::{
+        math::{Decimal, TryDiv},
+        state::{Obligation, Reserve},
+    },
+    std::{cmp::Ordering, collections::HashMap},
+};
+
+type Error = Box<dyn std::error::Error>;
+
+/// Obligation with values refreshed off-chain
+#[derive(Clone, Debug)]
+pub struct ObligationHealth {
+    /// Obligation address
+    pub address: Pubkey,
+    /// Obligation with deposit, borrow and total values refreshed
+    pub obligation: Obligation,
+    /// Ratio of borrowed value to deposited value, or `None` when the
+    /// obligation has borrows but no deposit value left
+    pub loan_to_value: Option<Decimal>,
+}
+
+impl ObligationHealth {
+    /// Compute the health of an obligation from reserves which accrued
+    /// interest up to the current slot
+    pub fn new(
+        address: Pubkey,
+        mut obligation: Obligation,
+        reserves: &HashMap<Pubkey, Reserve>,
+    ) -> Result<Self, Error> {
+        refresh_obligation_values(&mut obligation, reserves)?;
+        let loan_to_value = if obligation.borrowed_value == Decimal::zero() {
+            Some(Decimal::zero())
+        } else if obligation.deposited_value == Decimal::zero() {
+            None
+        } else {
+            Some(obligation.loan_to_value()?)
+        };
+        Ok(Self {
+            address,
+            obligation,
+            loan_to_value,
+        })
+    }
+
+    /// Return true if the obligation can be liquidated
+    pub fn is_unhealthy(&self) -> bool {
+        !self.obligation.borrows.is_empty()
+            && self.obligation.borrowed_value >= self.obligation.unhealthy_borrow_value
+    }
+
+    /// Ratio of the unhealthy borrow value to the borrowed value, which drops
+    /// below one when the obligation can be liquidated.  `None` when nothing
+    /// is borrowed.
+    pub fn health_factor(&self) -> Result<Option<Decimal>, ProgramError> {
+        if self.obligation.borrowed_value == Decimal::zero() {
+            return Ok(None);
+        }
+        self.obligation
+            .unhealthy_borrow_value
+            .try_div(self.obligation.borrowed_value)
+            .map(Some)
+    }
+
+    /// Order obligations by descending loan to value, with obligations that
+    /// have no deposit value left first
+    fn cmp_loan_to_value(&self, other: &Self) -> Ordering {
+        match (self.loan_to_value, other.loan_to_value) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(a), Some(b)) => b.cmp(&a),
+        }
+    }
+}
+
+/// Accrue interest on reserves up to a slot, like `RefreshReserve` does
+/// without updating market prices
+pub fn accrue_reserves_interest(
+    reserves: &mut HashMap<Pubkey, Reserve>,
+    slot: Slot,
+) -> Result<(), ProgramError> {
+    for reserve in reserves.values_mut() {
+        reserve.accrue_interest(slot.max(reserve.last_update_slot))?;
+    }
+    Ok(())
+}
+
+/// Recalculate the market values of an obligation's deposits and borrows and
+/// its total values, like `RefreshObligation` does
+pub fn refresh_obligation_values(
+    obligation: &mut Obligation,
+    reserves: &HashMap<Pubkey, Reserve>,
+) -> Result<(), Error> {
+    obligation.refresh_values(|reserve_pubkey| {
+        reserves
+            .get(reserve_pubkey)
+            .cloned()
+            .ok_or_else(|| format!("Missing reserve {}", reserve_pubkey).into())
+    })
+}
+
+/// Fetch every obligation of a lending market and compute its health in the
+/// current slot, using the market prices from the last reserve refresh.
+/// Unhealthy obligations are ranked first, each group by descending loan to
+/// value.
+pub fn scan_obligations(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    lending_market_address: &Pubkey,
+) -> Result<Vec<ObligationHealth>, Error> {
+    let slot = rpc_client.get_slot()?;
+    let mut reserves: HashMap<Pubkey, Reserve> =
+        get_lending_market_reserves(rpc_client, program_id, lending_market_address)?
+            .into_iter()
+            .collect();
+    accrue_reserves_interest(&mut reserves, slot)?;
+
+    let mut obligations: Vec<ObligationHealth> =
+        get_lending_market_obligations(rpc_client, program_id, lending_market_address)?
+            .into_iter()
+            .filter_map(|(address, obligation)| {
+                match ObligationHealth::new(address, obligation, &reserves) {
+                    Ok(health) => Some(health),
+                    Err(err) => {
+                        eprintln!("Cannot compute health of obligation {}: {}", address, err);
+                        None
+                    }
+                }
+            })
+            .collect();
+    obligations.sort_by(|a, b| {
+        b.is_unhealthy()
+            .cmp(&a.is_unhealthy())
+            .then_with(|| a.cmp_loan_to_value(b))
+    });
+    Ok(obligations)
+}
@@ -0,0 +1,5 @@
+//! Off-chain library for the token lending program, shared by the
+//! command-line client and liquidation keepers
+
+pub mod client;
+pub mod health;
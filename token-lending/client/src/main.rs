@@ -1,304 +1,1956 @@
-use solana_client::rpc_client::RpcClient;
-use solana_program::program_pack::Pack;
-use solana_sdk::{
-    pubkey::Pubkey,
-    signature::{read_keypair_file, Keypair, Signer},
-    system_instruction::create_account,
-    transaction::Transaction,
-};
-use spl_token::{
-    instruction::approve,
-    state::{Account as Token, Mint},
-};
-use spl_token_lending::{
-    instruction::{init_lending_market, init_reserve},
-    state::{LendingMarket, Reserve, ReserveConfig, ReserveFees},
+use {
+    clap::{
+        crate_description, crate_name, crate_version, value_t, value_t_or_exit, App, AppSettings,
+        Arg, ArgMatches, SubCommand,
+    },
+    solana_clap_utils::{
+        input_parsers::pubkey_of,
+        input_validators::{
+            is_amount, is_amount_or_all, is_keypair, is_parsable, is_pubkey, is_url,
+        },
+        keypair::signer_from_path,
+    },
+    solana_client::rpc_client::RpcClient,
+    solana_program::{
+        instruction::Instruction, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+    },
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        native_token::Sol,
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::Transaction,
+    },
+    spl_token::state::{Account as Token, Mint},
+    spl_token_lending::{
+        dex_market::{DexMarket, BIDS_OFFSET},
+        instruction::{
+            borrow_obligation_liquidity, deposit_obligation_collateral, deposit_reserve_liquidity,
+            init_lending_market, init_obligation, init_reserve, liquidate_obligation,
+            refresh_obligation, refresh_reserve, repay_obligation_liquidity,
+            withdraw_obligation_collateral, withdraw_reserve_liquidity, RefreshReserveOracle,
+        },
+        math::{Decimal, Rate, TryMul, TrySub, BPS_SCALER, PERCENT_SCALER, WAD},
+        state::{
+            BorrowRateCurve, BorrowRateCurvePoint, LendingMarket, Obligation, OracleConfig,
+            Reserve, ReserveConfig, ReserveFees, BORROW_RATE_CURVE_POINTS,
+        },
+    },
+    spl_token_lending_cli::{client::*, health::*},
+    std::{collections::HashMap, process::exit},
 };
-use std::str::FromStr;
-
-// -------- UPDATE START -------
-const KEYPAIR_PATH: &str = "/your/path";
-const SRM_TOKEN_ACCOUNT: &str = "BASE58_ADDRESS";
-const USDC_TOKEN_ACCOUNT: &str = "BASE58_ADDRESS";
-const WRAPPED_SOL_TOKEN_ACCOUNT: &str = "BASE58_ADDRESS";
-solana_program::declare_id!("TokenLend1ng1111111111111111111111111111111");
-// -------- UPDATE END ---------
-
-pub struct DexMarket {
-    pub name: &'static str,
-    pub pubkey: Pubkey,
+
+struct Config {
+    rpc_client: RpcClient,
+    verbose: bool,
+    program_id: Pubkey,
+    market_owner: Box<dyn Signer>,
+    owner: Box<dyn Signer>,
+    fee_payer: Box<dyn Signer>,
+    dry_run: bool,
+}
+
+type Error = Box<dyn std::error::Error>;
+type CommandResult = Result<(), Error>;
+
+/// Size of the temporary memory used to simulate trades against a dex market
+const DEX_MARKET_MEMORY_LEN: u64 = 65548;
+
+fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(), Error> {
+    let balance = config.rpc_client.get_balance(&config.fee_payer.pubkey())?;
+    if balance < required_balance {
+        Err(format!(
+            "Fee payer, {}, has insufficient balance: {} required, {} available",
+            config.fee_payer.pubkey(),
+            Sol(required_balance),
+            Sol(balance)
+        )
+        .into())
+    } else {
+        Ok(())
+    }
+}
+
+fn send_transaction(
+    config: &Config,
+    transaction: Transaction,
+) -> solana_client::client_error::Result<()> {
+    if config.dry_run {
+        let result = config.rpc_client.simulate_transaction(&transaction)?;
+        println!("Simulate result: {:?}", result);
+    } else {
+        let signature = config
+            .rpc_client
+            .send_and_confirm_transaction_with_spinner(&transaction)?;
+        println!("Signature: {}", signature);
+    }
+    Ok(())
+}
+
+fn send_instructions(
+    config: &Config,
+    instructions: &[Instruction],
+    mut signers: Vec<&dyn Signer>,
+    total_rent_free_balances: u64,
+) -> CommandResult {
+    let mut transaction =
+        Transaction::new_with_payer(instructions, Some(&config.fee_payer.pubkey()));
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        total_rent_free_balances + fee_calculator.calculate_fee(transaction.message()),
+    )?;
+    signers.sort_by_key(|signer| signer.pubkey());
+    signers.dedup_by_key(|signer| signer.pubkey());
+    transaction.sign(&signers, recent_blockhash);
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn lending_market_authority(config: &Config, lending_market_address: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[lending_market_address.as_ref()], &config.program_id).0
+}
+
+/// Build instructions which create and initialize a token account owned by
+/// the client, returning them with the rent-exempt balance of the account
+fn create_token_account(
+    config: &Config,
+    account: &Pubkey,
+    mint: &Pubkey,
+) -> Result<(Vec<Instruction>, u64), Error> {
+    let token_account_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(Token::LEN)?;
+    Ok((
+        vec![
+            system_instruction::create_account(
+                &config.fee_payer.pubkey(),
+                account,
+                token_account_balance,
+                Token::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                account,
+                mint,
+                &config.owner.pubkey(),
+            )?,
+        ],
+        token_account_balance,
+    ))
+}
+
+fn reserve_oracle(reserve_address: &Pubkey, reserve: &Reserve) -> Result<Pubkey, Error> {
+    match reserve.oracle {
+        COption::Some(oracle) => Ok(oracle),
+        COption::None => Err(format!("Reserve {} has no oracle account", reserve_address).into()),
+    }
+}
+
+/// Build instructions which refresh each reserve in the current slot.  Dex
+/// market oracles need temporary memory, which is created with the returned
+/// keypair that must also sign the transaction.
+fn refresh_reserves(
+    config: &Config,
+    reserve_addresses: &[Pubkey],
+) -> Result<(Vec<Instruction>, Option<Keypair>), Error> {
+    let mut instructions = vec![];
+    let mut memory: Option<Keypair> = None;
+    let mut refreshed: Vec<Pubkey> = vec![];
+
+    for reserve_address in reserve_addresses {
+        if refreshed.contains(reserve_address) {
+            continue;
+        }
+        refreshed.push(*reserve_address);
+
+        let reserve = get_reserve(&config.rpc_client, reserve_address)?;
+        let oracle = match reserve.config.oracle {
+            OracleConfig::QuoteCurrency => RefreshReserveOracle::QuoteCurrency,
+            OracleConfig::DexMarket => {
+                let dex_market = reserve_oracle(reserve_address, &reserve)?;
+                let dex_market_data = config.rpc_client.get_account_data(&dex_market)?;
+                let dex_market_bids = DexMarket::pubkey_at_offset(&dex_market_data, BIDS_OFFSET);
+                let memory = memory.get_or_insert_with(|| {
+                    let memory = Keypair::new();
+                    instructions.insert(
+                        0,
+                        system_instruction::create_account(
+                            &config.fee_payer.pubkey(),
+                            &memory.pubkey(),
+                            0,
+                            DEX_MARKET_MEMORY_LEN,
+                            &config.program_id,
+                        ),
+                    );
+                    memory
+                });
+                RefreshReserveOracle::DexMarket {
+                    dex_market,
+                    dex_market_bids,
+                    memory: memory.pubkey(),
+                }
+            }
+            OracleConfig::PushOracle { .. } => RefreshReserveOracle::PushOracle {
+                feed: reserve_oracle(reserve_address, &reserve)?,
+            },
+            OracleConfig::TokenSwapTwap { .. } => RefreshReserveOracle::TokenSwap {
+                swap: reserve_oracle(reserve_address, &reserve)?,
+            },
+        };
+        instructions.push(refresh_reserve(config.program_id, *reserve_address, oracle));
+    }
+
+    Ok((instructions, memory))
+}
+
+/// Reserves of an obligation in the order `RefreshObligation` expects them
+fn obligation_reserves(obligation: &Obligation) -> Vec<Pubkey> {
+    obligation
+        .deposits
+        .iter()
+        .map(|collateral| collateral.deposit_reserve)
+        .chain(
+            obligation
+                .borrows
+                .iter()
+                .map(|liquidity| liquidity.borrow_reserve),
+        )
+        .collect()
+}
+
+/// Get a reserve with interest accrued up to the current slot, priced at its
+/// last refreshed market price
+fn get_live_reserve(
+    config: &Config,
+    reserve_address: &Pubkey,
+    slot: u64,
+) -> Result<Reserve, Error> {
+    let mut reserve = get_reserve(&config.rpc_client, reserve_address)?;
+    reserve.accrue_interest(slot.max(reserve.last_update_slot))?;
+    Ok(reserve)
+}
+
+fn decimal_to_f64(value: Decimal) -> f64 {
+    value
+        .to_scaled_val()
+        .map_or(f64::INFINITY, |scaled_val| scaled_val as f64 / WAD as f64)
+}
+
+fn decimal_to_ui_amount(value: Decimal, decimals: u8) -> f64 {
+    decimal_to_f64(value) / 10f64.powi(decimals as i32)
+}
+
+fn rate_to_percent(rate: Rate) -> f64 {
+    rate.to_scaled_val() as f64 / PERCENT_SCALER as f64
+}
+
+fn cap_to_string(cap: u64, decimals: u8) -> String {
+    if cap == 0 {
+        "none".to_string()
+    } else {
+        spl_token::amount_to_ui_amount(cap, decimals).to_string()
+    }
+}
+
+fn loan_to_value_to_string(health: &ObligationHealth) -> String {
+    match health.loan_to_value {
+        Some(loan_to_value) => format!("{:.2}%", decimal_to_f64(loan_to_value) * 100.0),
+        None => "no deposits".to_string(),
+    }
+}
+
+fn health_factor_to_string(health: &ObligationHealth) -> Result<String, Error> {
+    Ok(match health.health_factor()? {
+        Some(health_factor) => format!(
+            "{:.4}{}",
+            decimal_to_f64(health_factor),
+            if health.is_unhealthy() {
+                " (liquidatable)"
+            } else {
+                ""
+            }
+        ),
+        None => "no borrows".to_string(),
+    })
+}
+
+fn borrow_rate_curve_of(value: &str) -> Result<BorrowRateCurve, String> {
+    let points = value
+        .split(',')
+        .map(|point| {
+            let parts: Vec<&str> = point.split(':').collect();
+            match parts.as_slice() {
+                [utilization_rate_bps, borrow_rate_bps] => Ok(BorrowRateCurvePoint {
+                    utilization_rate_bps: utilization_rate_bps
+                        .trim()
+                        .parse()
+                        .map_err(|err| format!("Invalid utilization rate {}: {}", point, err))?,
+                    borrow_rate_bps: borrow_rate_bps
+                        .trim()
+                        .parse()
+                        .map_err(|err| format!("Invalid borrow rate {}: {}", point, err))?,
+                }),
+                _ => Err(format!(
+                    "Invalid borrow rate curve point {}, expected UTILIZATION_BPS:RATE_BPS",
+                    point
+                )),
+            }
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let curve = BorrowRateCurve::from_points(&points).ok_or_else(|| {
+        format!(
+            "Borrow rate curve must have between 1 and {} points",
+            BORROW_RATE_CURVE_POINTS
+        )
+    })?;
+    if !curve.is_valid() {
+        return Err(
+            "Borrow rate curve must span utilization 0 to 10000 bps with increasing \
+             utilization and non-decreasing borrow rates"
+                .to_string(),
+        );
+    }
+    Ok(curve)
+}
+
+fn is_borrow_rate_curve(value: String) -> Result<(), String> {
+    borrow_rate_curve_of(&value).map(|_| ())
 }
 
-pub fn main() {
-    let mut client = RpcClient::new("https://api.mainnet-beta.solana.com".to_owned());
+fn command_create_market(config: &Config, quote_token_mint: &Pubkey) -> CommandResult {
+    let lending_market_keypair = Keypair::new();
+    println!(
+        "Creating lending market {}",
+        lending_market_keypair.pubkey()
+    );
 
-    let payer = read_keypair_file(&format!("{}/payer.json", KEYPAIR_PATH)).unwrap();
-    let usdc_mint_pubkey =
-        Pubkey::from_str("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").unwrap();
+    let lending_market_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(LendingMarket::LEN)?;
+
+    let instructions = vec![
+        // Account for the lending market
+        system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            &lending_market_keypair.pubkey(),
+            lending_market_balance,
+            LendingMarket::LEN as u64,
+            &config.program_id,
+        ),
+        // Initialize lending market account
+        init_lending_market(
+            config.program_id,
+            lending_market_keypair.pubkey(),
+            config.market_owner.pubkey(),
+            *quote_token_mint,
+        ),
+    ];
+
+    let signers = vec![config.fee_payer.as_ref(), &lending_market_keypair];
+    send_instructions(config, &instructions, signers, lending_market_balance)
+}
 
-    let sol_usdc_dex_market = DexMarket {
-        name: "sol_usdc",
-        pubkey: Pubkey::from_str("9wFFyRfZBsuAha4YcuxcXLKwMxJR43S7fPfQLusDBzvT").unwrap(),
+#[allow(clippy::too_many_arguments)]
+fn command_add_reserve(
+    config: &Config,
+    lending_market_address: &Pubkey,
+    source_liquidity: &Pubkey,
+    ui_amount: f64,
+    mut reserve_config: ReserveConfig,
+    oracle: &Option<Pubkey>,
+    supply_cap: Option<f64>,
+    borrow_cap: Option<f64>,
+) -> CommandResult {
+    let lending_market = get_lending_market(&config.rpc_client, lending_market_address)?;
+    if lending_market.owner != config.market_owner.pubkey() {
+        return Err(format!(
+            "Lending market {} is owned by {}, not {}",
+            lending_market_address,
+            lending_market.owner,
+            config.market_owner.pubkey()
+        )
+        .into());
+    }
+
+    let source_account_data = config.rpc_client.get_account_data(source_liquidity)?;
+    let source_account = Token::unpack(&source_account_data)
+        .map_err(|err| format!("Invalid token account {}: {}", source_liquidity, err))?;
+    let liquidity_mint = get_token_mint(&config.rpc_client, &source_account.mint)?;
+    let decimals = liquidity_mint.decimals;
+    let liquidity_amount = spl_token::ui_amount_to_amount(ui_amount, decimals);
+    if source_account.amount < liquidity_amount {
+        return Err(format!(
+            "Not enough tokens in {}: {} required, {} available",
+            source_liquidity,
+            ui_amount,
+            spl_token::amount_to_ui_amount(source_account.amount, decimals)
+        )
+        .into());
+    }
+
+    if reserve_config.oracle != OracleConfig::QuoteCurrency && oracle.is_none() {
+        return Err("Oracle address is required unless the reserve is the quote currency".into());
+    }
+    reserve_config.supply_cap =
+        supply_cap.map_or(0, |cap| spl_token::ui_amount_to_amount(cap, decimals));
+    reserve_config.borrow_cap =
+        borrow_cap.map_or(0, |cap| spl_token::ui_amount_to_amount(cap, decimals));
+
+    let reserve_keypair = Keypair::new();
+    println!("Creating reserve {}", reserve_keypair.pubkey());
+
+    let collateral_mint = Keypair::new();
+    let collateral_supply = Keypair::new();
+    let liquidity_supply = Keypair::new();
+    let liquidity_fee_receiver = Keypair::new();
+    let destination_collateral = Keypair::new();
+    println!(
+        "Creating collateral token account {}",
+        destination_collateral.pubkey()
+    );
+    if config.verbose {
+        println!("Collateral mint: {}", collateral_mint.pubkey());
+        println!("Collateral supply: {}", collateral_supply.pubkey());
+        println!("Liquidity supply: {}", liquidity_supply.pubkey());
+        println!(
+            "Liquidity fee receiver: {}",
+            liquidity_fee_receiver.pubkey()
+        );
+    }
+
+    let reserve_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(Reserve::LEN)?;
+    let mint_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(Mint::LEN)?;
+    let token_account_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(Token::LEN)?;
+    let total_rent_free_balances = reserve_balance + mint_balance + 4 * token_account_balance;
+
+    // The reserve initializes these accounts, so they are only allocated here
+    let mut instructions = vec![system_instruction::create_account(
+        &config.fee_payer.pubkey(),
+        &collateral_mint.pubkey(),
+        mint_balance,
+        Mint::LEN as u64,
+        &spl_token::id(),
+    )];
+    for token_account in &[
+        &collateral_supply,
+        &liquidity_supply,
+        &liquidity_fee_receiver,
+        &destination_collateral,
+    ] {
+        instructions.push(system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            &token_account.pubkey(),
+            token_account_balance,
+            Token::LEN as u64,
+            &spl_token::id(),
+        ));
+    }
+    instructions.push(system_instruction::create_account(
+        &config.fee_payer.pubkey(),
+        &reserve_keypair.pubkey(),
+        reserve_balance,
+        Reserve::LEN as u64,
+        &config.program_id,
+    ));
+
+    let signers = vec![
+        config.fee_payer.as_ref(),
+        &reserve_keypair,
+        &collateral_mint,
+        &collateral_supply,
+        &liquidity_supply,
+        &liquidity_fee_receiver,
+        &destination_collateral,
+    ];
+    send_instructions(config, &instructions, signers, total_rent_free_balances)?;
+
+    let instructions = vec![init_reserve(
+        config.program_id,
+        liquidity_amount,
+        reserve_config,
+        *source_liquidity,
+        destination_collateral.pubkey(),
+        reserve_keypair.pubkey(),
+        source_account.mint,
+        liquidity_supply.pubkey(),
+        collateral_mint.pubkey(),
+        collateral_supply.pubkey(),
+        liquidity_fee_receiver.pubkey(),
+        *lending_market_address,
+        config.market_owner.pubkey(),
+        config.owner.pubkey(),
+        *oracle,
+    )];
+
+    let signers = vec![
+        config.fee_payer.as_ref(),
+        config.market_owner.as_ref(),
+        config.owner.as_ref(),
+    ];
+    send_instructions(config, &instructions, signers, 0)
+}
+
+fn command_deposit(
+    config: &Config,
+    reserve_address: &Pubkey,
+    source_liquidity: &Pubkey,
+    ui_amount: f64,
+    destination_collateral: &Option<Pubkey>,
+    obligation_address: &Option<Pubkey>,
+) -> CommandResult {
+    let reserve = get_reserve(&config.rpc_client, reserve_address)?;
+    let decimals = reserve.liquidity.mint_decimals;
+    let source_account = get_token_account(
+        &config.rpc_client,
+        source_liquidity,
+        &reserve.liquidity.mint_pubkey,
+    )?;
+    let liquidity_amount = spl_token::ui_amount_to_amount(ui_amount, decimals);
+    if source_account.amount < liquidity_amount {
+        return Err(format!(
+            "Not enough tokens in {}: {} required, {} available",
+            source_liquidity,
+            ui_amount,
+            spl_token::amount_to_ui_amount(source_account.amount, decimals)
+        )
+        .into());
+    }
+
+    let mut instructions = vec![];
+    let mut total_rent_free_balances = 0;
+
+    let collateral_keypair = Keypair::new();
+    let (destination_collateral, initial_collateral_amount) = match destination_collateral {
+        Some(destination_collateral) => {
+            let destination_account = get_token_account(
+                &config.rpc_client,
+                destination_collateral,
+                &reserve.collateral.mint_pubkey,
+            )?;
+            (*destination_collateral, destination_account.amount)
+        }
+        None => {
+            println!(
+                "Creating collateral token account {}",
+                collateral_keypair.pubkey()
+            );
+            let (create_instructions, token_account_balance) = create_token_account(
+                config,
+                &collateral_keypair.pubkey(),
+                &reserve.collateral.mint_pubkey,
+            )?;
+            instructions.extend(create_instructions);
+            total_rent_free_balances += token_account_balance;
+            (collateral_keypair.pubkey(), 0)
+        }
     };
 
-    let srm_usdc_dex_market = DexMarket {
-        name: "srm_usdc",
-        pubkey: Pubkey::from_str("ByRys5tuUWDgL73G8JBAEfkdFf8JWBzPBDHsBVQ5vbQA").unwrap(),
+    let (refresh_instructions, memory) = refresh_reserves(config, &[*reserve_address])?;
+    instructions.extend(refresh_instructions);
+    instructions.push(deposit_reserve_liquidity(
+        config.program_id,
+        liquidity_amount,
+        *source_liquidity,
+        destination_collateral,
+        *reserve_address,
+        reserve.liquidity.supply_pubkey,
+        reserve.collateral.mint_pubkey,
+        reserve.lending_market,
+        lending_market_authority(config, &reserve.lending_market),
+        config.owner.pubkey(),
+    ));
+
+    let mut signers = vec![
+        config.fee_payer.as_ref(),
+        config.owner.as_ref(),
+        &collateral_keypair,
+    ];
+    if let Some(memory) = memory.as_ref() {
+        signers.push(memory);
+    }
+    send_instructions(config, &instructions, signers, total_rent_free_balances)?;
+
+    if let Some(obligation_address) = obligation_address {
+        if config.dry_run {
+            println!("Skipping obligation deposit, the collateral amount is unknown in a dry run");
+            return Ok(());
+        }
+
+        // Deposit whatever collateral the reserve minted for the liquidity
+        let collateral_amount = get_token_account(
+            &config.rpc_client,
+            &destination_collateral,
+            &reserve.collateral.mint_pubkey,
+        )?
+        .amount
+        .saturating_sub(initial_collateral_amount);
+        println!(
+            "Depositing {} collateral into obligation {}",
+            spl_token::amount_to_ui_amount(collateral_amount, decimals),
+            obligation_address
+        );
+
+        let instructions = vec![deposit_obligation_collateral(
+            config.program_id,
+            collateral_amount,
+            destination_collateral,
+            reserve.collateral.supply_pubkey,
+            *reserve_address,
+            *obligation_address,
+            reserve.lending_market,
+            config.owner.pubkey(),
+            config.owner.pubkey(),
+        )];
+        let signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+        send_instructions(config, &instructions, signers, 0)?;
+    }
+    Ok(())
+}
+
+fn command_withdraw(
+    config: &Config,
+    reserve_address: &Pubkey,
+    source_collateral: &Pubkey,
+    ui_amount: f64,
+    destination_liquidity: &Option<Pubkey>,
+    obligation_address: &Option<Pubkey>,
+) -> CommandResult {
+    let reserve = get_reserve(&config.rpc_client, reserve_address)?;
+    let decimals = reserve.liquidity.mint_decimals;
+    let source_account = get_token_account(
+        &config.rpc_client,
+        source_collateral,
+        &reserve.collateral.mint_pubkey,
+    )?;
+    let collateral_amount = spl_token::ui_amount_to_amount(ui_amount, decimals);
+    if obligation_address.is_none() && source_account.amount < collateral_amount {
+        return Err(format!(
+            "Not enough collateral in {}: {} required, {} available",
+            source_collateral,
+            ui_amount,
+            spl_token::amount_to_ui_amount(source_account.amount, decimals)
+        )
+        .into());
+    }
+
+    let mut instructions = vec![];
+    let mut total_rent_free_balances = 0;
+
+    let liquidity_keypair = Keypair::new();
+    let destination_liquidity = match destination_liquidity {
+        Some(destination_liquidity) => *destination_liquidity,
+        None => {
+            println!(
+                "Creating liquidity token account {}",
+                liquidity_keypair.pubkey()
+            );
+            let (create_instructions, token_account_balance) = create_token_account(
+                config,
+                &liquidity_keypair.pubkey(),
+                &reserve.liquidity.mint_pubkey,
+            )?;
+            instructions.extend(create_instructions);
+            total_rent_free_balances += token_account_balance;
+            liquidity_keypair.pubkey()
+        }
     };
 
-    let quote_token_mint = usdc_mint_pubkey;
-    let (lending_market_owner, lending_market_pubkey, _lending_market) =
-        create_lending_market(&mut client, quote_token_mint, &payer);
-
-    let usdc_liquidity_source = Pubkey::from_str(USDC_TOKEN_ACCOUNT).unwrap();
-    let usdc_reserve_config = ReserveConfig {
-        optimal_utilization_rate: 80,
-        loan_to_value_ratio: 75,
-        liquidation_bonus: 5,
-        liquidation_threshold: 80,
-        min_borrow_rate: 0,
-        optimal_borrow_rate: 4,
-        max_borrow_rate: 30,
-        fees: ReserveFees {
-            borrow_fee_wad: 100_000_000_000_000, // 1 bp
-            host_fee_percentage: 20,
-        },
+    let lending_market_authority = lending_market_authority(config, &reserve.lending_market);
+    let memory = match obligation_address {
+        Some(obligation_address) => {
+            // Withdraw the collateral from the obligation into the source
+            // account before redeeming it
+            let obligation = get_obligation(&config.rpc_client, obligation_address)?;
+            let (refresh_instructions, memory) =
+                refresh_reserves(config, &obligation_reserves(&obligation))?;
+            instructions.extend(refresh_instructions);
+            instructions.push(refresh_obligation(
+                config.program_id,
+                *obligation_address,
+                obligation_reserves(&obligation),
+            ));
+            instructions.push(withdraw_obligation_collateral(
+                config.program_id,
+                collateral_amount,
+                reserve.collateral.supply_pubkey,
+                *source_collateral,
+                *reserve_address,
+                *obligation_address,
+                reserve.lending_market,
+                lending_market_authority,
+                config.owner.pubkey(),
+            ));
+            memory
+        }
+        None => {
+            let (refresh_instructions, memory) = refresh_reserves(config, &[*reserve_address])?;
+            instructions.extend(refresh_instructions);
+            memory
+        }
     };
+    instructions.push(withdraw_reserve_liquidity(
+        config.program_id,
+        collateral_amount,
+        *source_collateral,
+        destination_liquidity,
+        *reserve_address,
+        reserve.collateral.mint_pubkey,
+        reserve.liquidity.supply_pubkey,
+        reserve.lending_market,
+        lending_market_authority,
+        config.owner.pubkey(),
+    ));
 
-    let (usdc_reserve_pubkey, _usdc_reserve) = create_reserve(
-        &mut client,
-        usdc_reserve_config,
-        lending_market_pubkey,
-        &lending_market_owner,
-        None,
-        usdc_liquidity_source,
-        &payer,
-    );
+    let mut signers = vec![
+        config.fee_payer.as_ref(),
+        config.owner.as_ref(),
+        &liquidity_keypair,
+    ];
+    if let Some(memory) = memory.as_ref() {
+        signers.push(memory);
+    }
+    send_instructions(config, &instructions, signers, total_rent_free_balances)
+}
 
-    println!("Created usdc reserve with pubkey: {}", usdc_reserve_pubkey);
-
-    let sol_liquidity_source = Pubkey::from_str(WRAPPED_SOL_TOKEN_ACCOUNT).unwrap();
-    let sol_reserve_config = ReserveConfig {
-        optimal_utilization_rate: 0,
-        loan_to_value_ratio: 75,
-        liquidation_bonus: 10,
-        liquidation_threshold: 80,
-        min_borrow_rate: 0,
-        optimal_borrow_rate: 2,
-        max_borrow_rate: 15,
-        fees: ReserveFees {
-            borrow_fee_wad: 1_000_000_000_000, // 0.01 bp
-            host_fee_percentage: 20,
-        },
-    };
+fn command_init_obligation(config: &Config, lending_market_address: &Pubkey) -> CommandResult {
+    get_lending_market(&config.rpc_client, lending_market_address)?;
 
-    let (sol_reserve_pubkey, _sol_reserve) = create_reserve(
-        &mut client,
-        sol_reserve_config,
-        lending_market_pubkey,
-        &lending_market_owner,
-        Some(sol_usdc_dex_market.pubkey),
-        sol_liquidity_source,
-        &payer,
-    );
+    let obligation_keypair = Keypair::new();
+    println!("Creating obligation {}", obligation_keypair.pubkey());
 
-    println!("Created sol reserve with pubkey: {}", sol_reserve_pubkey);
-
-    let srm_liquidity_source = Pubkey::from_str(SRM_TOKEN_ACCOUNT).unwrap();
-    let srm_reserve_config = ReserveConfig {
-        optimal_utilization_rate: 0,
-        loan_to_value_ratio: 75,
-        liquidation_bonus: 10,
-        liquidation_threshold: 80,
-        min_borrow_rate: 0,
-        optimal_borrow_rate: 2,
-        max_borrow_rate: 15,
-        fees: ReserveFees {
-            borrow_fee_wad: 10_000_000_000_000, // 0.1 bp
-            host_fee_percentage: 25,
-        },
+    let obligation_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(Obligation::LEN)?;
+
+    let instructions = vec![
+        // Account for the obligation
+        system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            &obligation_keypair.pubkey(),
+            obligation_balance,
+            Obligation::LEN as u64,
+            &config.program_id,
+        ),
+        // Initialize obligation account
+        init_obligation(
+            config.program_id,
+            obligation_keypair.pubkey(),
+            *lending_market_address,
+            config.owner.pubkey(),
+        ),
+    ];
+
+    let signers = vec![
+        config.fee_payer.as_ref(),
+        config.owner.as_ref(),
+        &obligation_keypair,
+    ];
+    send_instructions(config, &instructions, signers, obligation_balance)
+}
+
+fn command_borrow(
+    config: &Config,
+    obligation_address: &Pubkey,
+    reserve_address: &Pubkey,
+    ui_amount: f64,
+    destination_liquidity: &Option<Pubkey>,
+    host_fee_receiver: &Option<Pubkey>,
+) -> CommandResult {
+    let obligation = get_obligation(&config.rpc_client, obligation_address)?;
+    let reserve = get_reserve(&config.rpc_client, reserve_address)?;
+    let liquidity_amount =
+        spl_token::ui_amount_to_amount(ui_amount, reserve.liquidity.mint_decimals);
+
+    let mut instructions = vec![];
+    let mut total_rent_free_balances = 0;
+
+    let liquidity_keypair = Keypair::new();
+    let destination_liquidity = match destination_liquidity {
+        Some(destination_liquidity) => *destination_liquidity,
+        None => {
+            println!(
+                "Creating liquidity token account {}",
+                liquidity_keypair.pubkey()
+            );
+            let (create_instructions, token_account_balance) = create_token_account(
+                config,
+                &liquidity_keypair.pubkey(),
+                &reserve.liquidity.mint_pubkey,
+            )?;
+            instructions.extend(create_instructions);
+            total_rent_free_balances += token_account_balance;
+            liquidity_keypair.pubkey()
+        }
     };
 
-    let (srm_reserve_pubkey, _srm_reserve) = create_reserve(
-        &mut client,
-        srm_reserve_config,
-        lending_market_pubkey,
-        &lending_market_owner,
-        Some(srm_usdc_dex_market.pubkey),
-        srm_liquidity_source,
-        &payer,
-    );
+    // The borrow reserve must be refreshed even if it is not yet borrowed from
+    let mut reserve_addresses = obligation_reserves(&obligation);
+    reserve_addresses.push(*reserve_address);
+    let (refresh_instructions, memory) = refresh_reserves(config, &reserve_addresses)?;
+    instructions.extend(refresh_instructions);
+    instructions.push(refresh_obligation(
+        config.program_id,
+        *obligation_address,
+        obligation_reserves(&obligation),
+    ));
+    instructions.push(borrow_obligation_liquidity(
+        config.program_id,
+        liquidity_amount,
+        reserve.liquidity.supply_pubkey,
+        destination_liquidity,
+        *reserve_address,
+        reserve.liquidity.fee_receiver,
+        *obligation_address,
+        obligation.lending_market,
+        lending_market_authority(config, &obligation.lending_market),
+        config.owner.pubkey(),
+        *host_fee_receiver,
+    ));
 
-    println!("Created srm reserve with pubkey: {}", srm_reserve_pubkey);
+    let mut signers = vec![
+        config.fee_payer.as_ref(),
+        config.owner.as_ref(),
+        &liquidity_keypair,
+    ];
+    if let Some(memory) = memory.as_ref() {
+        signers.push(memory);
+    }
+    send_instructions(config, &instructions, signers, total_rent_free_balances)
 }
 
-pub fn create_lending_market(
-    client: &mut RpcClient,
-    quote_token_mint: Pubkey,
-    payer: &Keypair,
-) -> (Keypair, Pubkey, LendingMarket) {
-    let owner = read_keypair_file(&format!("{}/lending_market_owner.json", KEYPAIR_PATH)).unwrap();
-    let keypair = Keypair::new();
-    let pubkey = keypair.pubkey();
-
-    let mut transaction = Transaction::new_with_payer(
-        &[
-            create_account(
-                &payer.pubkey(),
-                &pubkey,
-                client
-                    .get_minimum_balance_for_rent_exemption(LendingMarket::LEN)
-                    .unwrap(),
-                LendingMarket::LEN as u64,
-                &id(),
-            ),
-            init_lending_market(id(), pubkey, owner.pubkey(), quote_token_mint),
-        ],
-        Some(&payer.pubkey()),
-    );
+fn command_repay(
+    config: &Config,
+    obligation_address: &Pubkey,
+    reserve_address: &Pubkey,
+    ui_amount: Option<f64>,
+    source_liquidity: &Pubkey,
+) -> CommandResult {
+    let obligation = get_obligation(&config.rpc_client, obligation_address)?;
+    if obligation
+        .find_liquidity_in_borrows(reserve_address)
+        .is_err()
+    {
+        return Err(format!(
+            "Obligation {} has not borrowed from reserve {}",
+            obligation_address, reserve_address
+        )
+        .into());
+    }
+    let reserve = get_reserve(&config.rpc_client, reserve_address)?;
+    get_token_account(
+        &config.rpc_client,
+        source_liquidity,
+        &reserve.liquidity.mint_pubkey,
+    )?;
+    // The program repays at most the borrowed amount
+    let liquidity_amount = ui_amount.map_or(u64::MAX, |ui_amount| {
+        spl_token::ui_amount_to_amount(ui_amount, reserve.liquidity.mint_decimals)
+    });
+
+    let (mut instructions, memory) = refresh_reserves(config, &[*reserve_address])?;
+    instructions.push(repay_obligation_liquidity(
+        config.program_id,
+        liquidity_amount,
+        *source_liquidity,
+        reserve.liquidity.supply_pubkey,
+        *reserve_address,
+        *obligation_address,
+        obligation.lending_market,
+        config.owner.pubkey(),
+    ));
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    if let Some(memory) = memory.as_ref() {
+        signers.push(memory);
+    }
+    send_instructions(config, &instructions, signers, 0)
+}
 
-    let recent_blockhash = client.get_recent_blockhash().unwrap().0;
-    transaction.sign(&[&payer, &keypair], recent_blockhash);
-    client.send_and_confirm_transaction(&transaction).unwrap();
+fn command_liquidate(
+    config: &Config,
+    obligation_address: &Pubkey,
+    repay_reserve_address: &Pubkey,
+    withdraw_reserve_address: &Pubkey,
+    ui_amount: Option<f64>,
+    source_liquidity: &Pubkey,
+    destination_collateral: &Option<Pubkey>,
+) -> CommandResult {
+    let obligation = get_obligation(&config.rpc_client, obligation_address)?;
+    let repay_reserve = get_reserve(&config.rpc_client, repay_reserve_address)?;
+    let withdraw_reserve = get_reserve(&config.rpc_client, withdraw_reserve_address)?;
+    get_token_account(
+        &config.rpc_client,
+        source_liquidity,
+        &repay_reserve.liquidity.mint_pubkey,
+    )?;
+    // The program repays at most the maximum liquidation amount
+    let liquidity_amount = ui_amount.map_or(u64::MAX, |ui_amount| {
+        spl_token::ui_amount_to_amount(ui_amount, repay_reserve.liquidity.mint_decimals)
+    });
 
-    let account = client.get_account(&pubkey).unwrap();
-    let lending_market = LendingMarket::unpack(&account.data).unwrap();
+    let mut instructions = vec![];
+    let mut total_rent_free_balances = 0;
 
-    (owner, pubkey, lending_market)
+    let collateral_keypair = Keypair::new();
+    let destination_collateral = match destination_collateral {
+        Some(destination_collateral) => *destination_collateral,
+        None => {
+            println!(
+                "Creating collateral token account {}",
+                collateral_keypair.pubkey()
+            );
+            let (create_instructions, token_account_balance) = create_token_account(
+                config,
+                &collateral_keypair.pubkey(),
+                &withdraw_reserve.collateral.mint_pubkey,
+            )?;
+            instructions.extend(create_instructions);
+            total_rent_free_balances += token_account_balance;
+            collateral_keypair.pubkey()
+        }
+    };
+
+    let (refresh_instructions, memory) =
+        refresh_reserves(config, &obligation_reserves(&obligation))?;
+    instructions.extend(refresh_instructions);
+    instructions.push(refresh_obligation(
+        config.program_id,
+        *obligation_address,
+        obligation_reserves(&obligation),
+    ));
+    instructions.push(liquidate_obligation(
+        config.program_id,
+        liquidity_amount,
+        *source_liquidity,
+        destination_collateral,
+        *repay_reserve_address,
+        repay_reserve.liquidity.supply_pubkey,
+        *withdraw_reserve_address,
+        withdraw_reserve.collateral.supply_pubkey,
+        *obligation_address,
+        obligation.lending_market,
+        lending_market_authority(config, &obligation.lending_market),
+        config.owner.pubkey(),
+    ));
+
+    let mut signers = vec![
+        config.fee_payer.as_ref(),
+        config.owner.as_ref(),
+        &collateral_keypair,
+    ];
+    if let Some(memory) = memory.as_ref() {
+        signers.push(memory);
+    }
+    send_instructions(config, &instructions, signers, total_rent_free_balances)
 }
 
-pub fn create_reserve(
-    client: &mut RpcClient,
-    config: ReserveConfig,
-    lending_market_pubkey: Pubkey,
-    lending_market_owner: &Keypair,
-    dex_market_pubkey: Option<Pubkey>,
-    liquidity_source_pubkey: Pubkey,
-    payer: &Keypair,
-) -> (Pubkey, Reserve) {
-    let reserve_keypair = Keypair::new();
-    let reserve_pubkey = reserve_keypair.pubkey();
-    let collateral_mint_keypair = Keypair::new();
-    let collateral_supply_keypair = Keypair::new();
-    let collateral_fees_receiver_keypair = Keypair::new();
-    let liquidity_supply_keypair = Keypair::new();
-    let user_collateral_token_keypair = Keypair::new();
-    let user_transfer_authority = Keypair::new();
-
-    let liquidity_source_account = client.get_account(&liquidity_source_pubkey).unwrap();
-    let liquidity_source_token = Token::unpack(&liquidity_source_account.data).unwrap();
-    let liquidity_mint_pubkey = liquidity_source_token.mint;
-
-    let recent_blockhash = client.get_recent_blockhash().unwrap().0;
-    let token_balance = client
-        .get_minimum_balance_for_rent_exemption(Token::LEN)
-        .unwrap();
-
-    let mut transaction = Transaction::new_with_payer(
-        &[
-            create_account(
-                &payer.pubkey(),
-                &collateral_mint_keypair.pubkey(),
-                client
-                    .get_minimum_balance_for_rent_exemption(Mint::LEN)
-                    .unwrap(),
-                Mint::LEN as u64,
-                &spl_token::id(),
-            ),
-            create_account(
-                &payer.pubkey(),
-                &collateral_supply_keypair.pubkey(),
-                token_balance,
-                Token::LEN as u64,
-                &spl_token::id(),
-            ),
-            create_account(
-                &payer.pubkey(),
-                &collateral_fees_receiver_keypair.pubkey(),
-                token_balance,
-                Token::LEN as u64,
-                &spl_token::id(),
-            ),
-            create_account(
-                &payer.pubkey(),
-                &liquidity_supply_keypair.pubkey(),
-                token_balance,
-                Token::LEN as u64,
-                &spl_token::id(),
-            ),
-            create_account(
-                &payer.pubkey(),
-                &user_collateral_token_keypair.pubkey(),
-                token_balance,
-                Token::LEN as u64,
-                &spl_token::id(),
-            ),
-            create_account(
-                &payer.pubkey(),
-                &reserve_pubkey,
-                client
-                    .get_minimum_balance_for_rent_exemption(Reserve::LEN)
-                    .unwrap(),
-                Reserve::LEN as u64,
-                &id(),
-            ),
-        ],
-        Some(&payer.pubkey()),
+fn command_show_reserve(config: &Config, reserve_address: &Pubkey) -> CommandResult {
+    let slot = config.rpc_client.get_slot()?;
+    let reserve = get_live_reserve(config, reserve_address, slot)?;
+    let decimals = reserve.liquidity.mint_decimals;
+
+    let utilization_rate = reserve.liquidity.utilization_rate()?;
+    let borrow_rate = reserve.current_borrow_rate()?;
+    let supply_rate = borrow_rate
+        .try_mul(utilization_rate)?
+        .try_mul(Rate::one().try_sub(Rate::from_percent(reserve.config.reserve_factor))?)?;
+
+    println!("Reserve: {}", reserve_address);
+    println!("Lending market: {}", reserve.lending_market);
+    println!("Liquidity mint: {}", reserve.liquidity.mint_pubkey);
+    println!("Collateral mint: {}", reserve.collateral.mint_pubkey);
+    match reserve.oracle {
+        COption::Some(oracle) => println!("Oracle: {:?} {}", reserve.config.oracle, oracle),
+        COption::None => println!("Oracle: {:?}", reserve.config.oracle),
+    }
+    if config.verbose {
+        println!("Liquidity supply: {}", reserve.liquidity.supply_pubkey);
+        println!("Liquidity fee receiver: {}", reserve.liquidity.fee_receiver);
+        println!("Collateral supply: {}", reserve.collateral.supply_pubkey);
+    }
+    println!(
+        "Last refreshed slot: {}{}",
+        reserve.last_update_slot,
+        if reserve.last_update_slot < slot {
+            " (stale)"
+        } else {
+            ""
+        }
     );
 
-    transaction.sign(
-        &vec![
-            payer,
-            &reserve_keypair,
-            &collateral_mint_keypair,
-            &collateral_supply_keypair,
-            &liquidity_supply_keypair,
-            &user_collateral_token_keypair,
-        ],
-        recent_blockhash,
+    println!();
+    println!(
+        "Available liquidity: {}",
+        spl_token::amount_to_ui_amount(reserve.liquidity.available_amount, decimals)
+    );
+    println!(
+        "Borrowed liquidity: {}",
+        decimal_to_ui_amount(reserve.liquidity.borrowed_amount_wads, decimals)
+    );
+    println!(
+        "Total supply: {}",
+        decimal_to_ui_amount(reserve.liquidity.total_supply()?, decimals)
+    );
+    println!(
+        "Protocol fees: {}",
+        decimal_to_ui_amount(reserve.liquidity.protocol_fees_wads, decimals)
     );
+    println!("Utilization: {:.2}%", rate_to_percent(utilization_rate));
+    println!("Borrow APY: {:.2}%", rate_to_percent(borrow_rate));
+    println!("Supply APY: {:.2}%", rate_to_percent(supply_rate));
+    println!(
+        "Collateral exchange rate: {}",
+        Rate::from(reserve.collateral_exchange_rate()?)
+    );
+    println!("Market price: {}", reserve.liquidity.market_price);
 
-    client.send_and_confirm_transaction(&transaction).unwrap();
+    println!();
+    println!("Loan to value: {}%", reserve.config.loan_to_value_ratio);
+    println!(
+        "Liquidation threshold: {}%",
+        reserve.config.liquidation_threshold
+    );
+    println!("Liquidation bonus: {}%", reserve.config.liquidation_bonus);
+    println!("Reserve factor: {}%", reserve.config.reserve_factor);
+    println!("Borrow rate curve:");
+    // Unused points repeat the last point at 100% utilization
+    let mut previous_point = None;
+    for point in reserve.config.borrow_rate_curve.points.iter() {
+        if previous_point == Some(point) {
+            break;
+        }
+        println!(
+            "  {:.2}% utilization: {:.2}% APY",
+            point.utilization_rate_bps as f64 / 100.0,
+            point.borrow_rate_bps as f64 / 100.0
+        );
+        previous_point = Some(point);
+    }
+    println!(
+        "Borrow fee: {} bps",
+        reserve.config.fees.borrow_fee_wad as f64 / BPS_SCALER as f64
+    );
+    println!(
+        "Flash loan fee: {} bps",
+        reserve.config.fees.flash_loan_fee_wad as f64 / BPS_SCALER as f64
+    );
+    println!("Host fee: {}%", reserve.config.fees.host_fee_percentage);
+    println!(
+        "Supply cap: {}",
+        cap_to_string(reserve.config.supply_cap, decimals)
+    );
+    println!(
+        "Borrow cap: {}",
+        cap_to_string(reserve.config.borrow_cap, decimals)
+    );
+    println!("Deposits disabled: {}", reserve.config.deposits_disabled);
+    println!("Borrows disabled: {}", reserve.config.borrows_disabled);
+    println!(
+        "Liquidations disabled: {}",
+        reserve.config.liquidations_disabled
+    );
+    Ok(())
+}
 
-    let mut transaction = Transaction::new_with_payer(
-        &[
-            approve(
-                &spl_token::id(),
-                &liquidity_source_pubkey,
-                &user_transfer_authority.pubkey(),
-                &payer.pubkey(),
-                &[],
-                liquidity_source_token.amount,
-            )
-            .unwrap(),
-            init_reserve(
-                id(),
-                liquidity_source_token.amount,
-                config,
-                liquidity_source_pubkey,
-                user_collateral_token_keypair.pubkey(),
-                reserve_pubkey,
-                liquidity_mint_pubkey,
-                liquidity_supply_keypair.pubkey(),
-                collateral_mint_keypair.pubkey(),
-                collateral_supply_keypair.pubkey(),
-                collateral_fees_receiver_keypair.pubkey(),
-                lending_market_pubkey,
-                lending_market_owner.pubkey(),
-                user_transfer_authority.pubkey(),
-                dex_market_pubkey,
+fn command_show_obligation(config: &Config, obligation_address: &Pubkey) -> CommandResult {
+    let slot = config.rpc_client.get_slot()?;
+    let obligation = get_obligation(&config.rpc_client, obligation_address)?;
+    let mut reserves = HashMap::new();
+    for reserve_address in obligation_reserves(&obligation) {
+        let reserve = get_reserve(&config.rpc_client, &reserve_address)?;
+        reserves.insert(reserve_address, reserve);
+    }
+    accrue_reserves_interest(&mut reserves, slot)?;
+    let health = ObligationHealth::new(*obligation_address, obligation, &reserves)?;
+    let obligation = &health.obligation;
+
+    println!("Obligation: {}", obligation_address);
+    println!("Lending market: {}", obligation.lending_market);
+    println!("Owner: {}", obligation.owner);
+
+    println!();
+    println!("Deposits:");
+    for collateral in obligation.deposits.iter() {
+        let reserve = &reserves[&collateral.deposit_reserve];
+        let decimals = reserve.liquidity.mint_decimals;
+        let liquidity_amount = reserve
+            .collateral_exchange_rate()?
+            .decimal_collateral_to_liquidity(collateral.deposited_amount.into())?;
+        println!(
+            "  Reserve: {} Collateral: {} Liquidity: {} Value: {}",
+            collateral.deposit_reserve,
+            spl_token::amount_to_ui_amount(collateral.deposited_amount, decimals),
+            decimal_to_ui_amount(liquidity_amount, decimals),
+            decimal_to_f64(collateral.market_value),
+        );
+    }
+    println!("Borrows:");
+    for liquidity in obligation.borrows.iter() {
+        let reserve = &reserves[&liquidity.borrow_reserve];
+        println!(
+            "  Reserve: {} Borrowed: {} Value: {}",
+            liquidity.borrow_reserve,
+            decimal_to_ui_amount(
+                liquidity.borrowed_amount_wads,
+                reserve.liquidity.mint_decimals
             ),
-        ],
-        Some(&payer.pubkey()),
+            decimal_to_f64(liquidity.market_value),
+        );
+    }
+
+    println!();
+    println!(
+        "Deposited value: {}",
+        decimal_to_f64(obligation.deposited_value)
     );
+    println!(
+        "Borrowed value: {}",
+        decimal_to_f64(obligation.borrowed_value)
+    );
+    println!(
+        "Allowed borrow value: {}",
+        decimal_to_f64(obligation.allowed_borrow_value)
+    );
+    println!(
+        "Unhealthy borrow value: {}",
+        decimal_to_f64(obligation.unhealthy_borrow_value)
+    );
+    println!("Loan to value: {}", loan_to_value_to_string(&health));
+    println!("Health factor: {}", health_factor_to_string(&health)?);
+    Ok(())
+}
 
-    transaction.sign(
-        &vec![payer, &lending_market_owner, &user_transfer_authority],
-        recent_blockhash,
+fn command_scan_obligations(
+    config: &Config,
+    lending_market_address: &Pubkey,
+    show_all: bool,
+) -> CommandResult {
+    get_lending_market(&config.rpc_client, lending_market_address)?;
+    let obligations = scan_obligations(
+        &config.rpc_client,
+        &config.program_id,
+        lending_market_address,
+    )?;
+
+    let unhealthy_count = obligations
+        .iter()
+        .filter(|health| health.is_unhealthy())
+        .count();
+    println!(
+        "{} obligations, {} unhealthy",
+        obligations.len(),
+        unhealthy_count
     );
 
-    client.send_and_confirm_transaction(&transaction).unwrap();
+    for health in obligations
+        .iter()
+        .filter(|health| show_all || health.is_unhealthy())
+    {
+        println!();
+        println!("Obligation: {}", health.address);
+        if config.verbose {
+            println!("Owner: {}", health.obligation.owner);
+        }
+        println!(
+            "Borrowed value: {}",
+            decimal_to_f64(health.obligation.borrowed_value)
+        );
+        println!(
+            "Unhealthy borrow value: {}",
+            decimal_to_f64(health.obligation.unhealthy_borrow_value)
+        );
+        println!("Loan to value: {}", loan_to_value_to_string(health));
+        println!("Health factor: {}", health_factor_to_string(health)?);
+    }
+    Ok(())
+}
+
+fn reserve_config_of(matches: &ArgMatches<'_>) -> Result<ReserveConfig, Error> {
+    let oracle = match matches.value_of("oracle_type").unwrap() {
+        "quote-currency" => OracleConfig::QuoteCurrency,
+        "dex-market" => OracleConfig::DexMarket,
+        "push-oracle" => OracleConfig::PushOracle {
+            max_staleness_slots: value_t_or_exit!(matches, "max_staleness_slots", u64),
+            max_confidence_bps: value_t_or_exit!(matches, "max_confidence_bps", u16),
+        },
+        "token-swap-twap" => OracleConfig::TokenSwapTwap {
+            window_slots: value_t_or_exit!(matches, "twap_window_slots", u64),
+            max_staleness_slots: value_t_or_exit!(matches, "twap_max_staleness_slots", u64),
+        },
+        _ => unreachable!(),
+    };
+    let borrow_fee_bps = value_t_or_exit!(matches, "borrow_fee_bps", f64);
+    let flash_loan_fee_bps = value_t_or_exit!(matches, "flash_loan_fee_bps", f64);
+
+    Ok(ReserveConfig {
+        loan_to_value_ratio: value_t_or_exit!(matches, "loan_to_value_ratio", u8),
+        liquidation_bonus: value_t_or_exit!(matches, "liquidation_bonus", u8),
+        liquidation_threshold: value_t_or_exit!(matches, "liquidation_threshold", u8),
+        borrow_rate_curve: borrow_rate_curve_of(matches.value_of("borrow_rate_curve").unwrap())?,
+        fees: ReserveFees {
+            borrow_fee_wad: (borrow_fee_bps * BPS_SCALER as f64) as u64,
+            flash_loan_fee_wad: (flash_loan_fee_bps * BPS_SCALER as f64) as u64,
+            host_fee_percentage: value_t_or_exit!(matches, "host_fee_percentage", u8),
+        },
+        oracle,
+        deposits_disabled: false,
+        borrows_disabled: false,
+        liquidations_disabled: false,
+        supply_cap: 0,
+        borrow_cap: 0,
+        reserve_factor: value_t_or_exit!(matches, "reserve_factor", u8),
+    })
+}
+
+fn main() {
+    solana_logger::setup_with_default("solana=info");
+
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg({
+            let arg = Arg::with_name("config_file")
+                .short("C")
+                .long("config")
+                .value_name("PATH")
+                .takes_value(true)
+                .global(true)
+                .help("Configuration file to use");
+            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
+                arg.default_value(config_file)
+            } else {
+                arg
+            }
+        })
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .takes_value(false)
+                .global(true)
+                .help("Show additional information"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .takes_value(false)
+                .global(true)
+                .help("Simulate transaction instead of executing"),
+        )
+        .arg(
+            Arg::with_name("json_rpc_url")
+                .long("url")
+                .value_name("URL")
+                .takes_value(true)
+                .global(true)
+                .validator(is_url)
+                .help("JSON RPC URL for the cluster.  Default from the configuration file."),
+        )
+        .arg(
+            Arg::with_name("program_id")
+                .long("program-id")
+                .value_name("ADDRESS")
+                .takes_value(true)
+                .global(true)
+                .validator(is_pubkey)
+                .help("Token lending program id. Defaults to the deployed program."),
+        )
+        .arg(
+            Arg::with_name("market_owner")
+                .long("market-owner")
+                .value_name("KEYPAIR")
+                .validator(is_keypair)
+                .takes_value(true)
+                .global(true)
+                .help(
+                    "Specify the lending market owner. \
+                     This may be a keypair file, the ASK keyword. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .arg(
+            Arg::with_name("owner")
+                .long("owner")
+                .value_name("KEYPAIR")
+                .validator(is_keypair)
+                .takes_value(true)
+                .global(true)
+                .help(
+                    "Specify the owner of token accounts and obligations. \
+                     This may be a keypair file, the ASK keyword. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .arg(
+            Arg::with_name("fee_payer")
+                .long("fee-payer")
+                .value_name("KEYPAIR")
+                .validator(is_keypair)
+                .takes_value(true)
+                .global(true)
+                .help(
+                    "Specify the fee-payer account. \
+                     This may be a keypair file, the ASK keyword. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .subcommand(SubCommand::with_name("create-market").about("Create a new lending market")
+            .arg(
+                Arg::with_name("quote_token_mint")
+                    .long("quote-token-mint")
+                    .validator(is_pubkey)
+                    .value_name("MINT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Mint of the currency market values are quoted in."),
+            )
+        )
+        .subcommand(SubCommand::with_name("add-reserve").about("Add a reserve to a lending market. Must be signed by the market owner.")
+            .arg(
+                Arg::with_name("market")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("MARKET_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Lending market address."),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Liquidity token account to make the initial deposit from. Must be owned by the client."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .long("amount")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity to deposit when creating the reserve."),
+            )
+            .arg(
+                Arg::with_name("oracle_type")
+                    .long("oracle-type")
+                    .value_name("TYPE")
+                    .takes_value(true)
+                    .possible_values(&["quote-currency", "dex-market", "push-oracle", "token-swap-twap"])
+                    .default_value("quote-currency")
+                    .help("Price oracle used to value the reserve liquidity."),
+            )
+            .arg(
+                Arg::with_name("oracle")
+                    .long("oracle")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required_if("oracle_type", "dex-market")
+                    .required_if("oracle_type", "push-oracle")
+                    .required_if("oracle_type", "token-swap-twap")
+                    .help("Dex market, push oracle feed or token swap address."),
+            )
+            .arg(
+                Arg::with_name("max_staleness_slots")
+                    .long("max-staleness-slots")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SLOTS")
+                    .takes_value(true)
+                    .default_value("25")
+                    .help("Maximum number of slots since a push oracle feed was published."),
+            )
+            .arg(
+                Arg::with_name("max_confidence_bps")
+                    .long("max-confidence-bps")
+                    .validator(is_parsable::<u16>)
+                    .value_name("BPS")
+                    .takes_value(true)
+                    .default_value("200")
+                    .help("Maximum width of a push oracle confidence interval, in basis points of the price."),
+            )
+            .arg(
+                Arg::with_name("twap_window_slots")
+                    .long("twap-window-slots")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SLOTS")
+                    .takes_value(true)
+                    .default_value("150")
+                    .help("Number of slots the token swap spot price is averaged over."),
+            )
+            .arg(
+                Arg::with_name("twap_max_staleness_slots")
+                    .long("twap-max-staleness-slots")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SLOTS")
+                    .takes_value(true)
+                    .default_value("600")
+                    .help("Maximum number of slots a token swap average price is used for."),
+            )
+            .arg(
+                Arg::with_name("loan_to_value_ratio")
+                    .long("loan-to-value")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("50")
+                    .help("Percentage of the collateral value that can be borrowed."),
+            )
+            .arg(
+                Arg::with_name("liquidation_threshold")
+                    .long("liquidation-threshold")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("55")
+                    .help("Loan to value at which an obligation can be liquidated."),
+            )
+            .arg(
+                Arg::with_name("liquidation_bonus")
+                    .long("liquidation-bonus")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("5")
+                    .help("Bonus a liquidator gets when repaying part of an unhealthy obligation."),
+            )
+            .arg(
+                Arg::with_name("borrow_rate_curve")
+                    .long("borrow-rate-curve")
+                    .validator(is_borrow_rate_curve)
+                    .value_name("UTILIZATION_BPS:RATE_BPS,...")
+                    .takes_value(true)
+                    .default_value("0:0,8000:400,10000:3000")
+                    .help("Borrow APY curve points by utilization, both in basis points."),
+            )
+            .arg(
+                Arg::with_name("borrow_fee_bps")
+                    .long("borrow-fee-bps")
+                    .validator(is_parsable::<f64>)
+                    .value_name("BPS")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Fee assessed on borrows, in basis points."),
+            )
+            .arg(
+                Arg::with_name("flash_loan_fee_bps")
+                    .long("flash-loan-fee-bps")
+                    .validator(is_parsable::<f64>)
+                    .value_name("BPS")
+                    .takes_value(true)
+                    .default_value("30")
+                    .help("Fee assessed on flash loans, in basis points."),
+            )
+            .arg(
+                Arg::with_name("host_fee_percentage")
+                    .long("host-fee")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("20")
+                    .help("Percentage of borrow and flash loan fees paid to the host."),
+            )
+            .arg(
+                Arg::with_name("reserve_factor")
+                    .long("reserve-factor")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("10")
+                    .help("Percentage of borrow interest kept as protocol fees."),
+            )
+            .arg(
+                Arg::with_name("supply_cap")
+                    .long("supply-cap")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Maximum total supply of the reserve. Defaults to no cap."),
+            )
+            .arg(
+                Arg::with_name("borrow_cap")
+                    .long("borrow-cap")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Maximum total borrows of the reserve. Defaults to no cap."),
+            )
+        )
+        .subcommand(SubCommand::with_name("deposit").about("Deposit liquidity into a reserve in exchange for collateral")
+            .arg(
+                Arg::with_name("reserve")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("RESERVE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve address."),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Liquidity token account to deposit from. Must be owned by the client."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .long("amount")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity to deposit."),
+            )
+            .arg(
+                Arg::with_name("destination")
+                    .long("destination")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Collateral token account to receive collateral. Defaults to a new token account."),
+            )
+            .arg(
+                Arg::with_name("obligation")
+                    .long("obligation")
+                    .validator(is_pubkey)
+                    .value_name("OBLIGATION_ADDRESS")
+                    .takes_value(true)
+                    .help("Obligation to deposit the received collateral into."),
+            )
+        )
+        .subcommand(SubCommand::with_name("withdraw").about("Redeem collateral from a reserve for liquidity")
+            .arg(
+                Arg::with_name("reserve")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("RESERVE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve address."),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Collateral token account to redeem from. Must be owned by the client."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .long("amount")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of collateral to redeem."),
+            )
+            .arg(
+                Arg::with_name("destination")
+                    .long("destination")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Liquidity token account to receive liquidity. Defaults to a new token account."),
+            )
+            .arg(
+                Arg::with_name("obligation")
+                    .long("obligation")
+                    .validator(is_pubkey)
+                    .value_name("OBLIGATION_ADDRESS")
+                    .takes_value(true)
+                    .help("Obligation to withdraw the collateral from into the source account first."),
+            )
+        )
+        .subcommand(SubCommand::with_name("init-obligation").about("Create an obligation to borrow against deposited collateral")
+            .arg(
+                Arg::with_name("market")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("MARKET_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Lending market address."),
+            )
+        )
+        .subcommand(SubCommand::with_name("borrow").about("Borrow liquidity from a reserve against obligation collateral")
+            .arg(
+                Arg::with_name("obligation")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("OBLIGATION_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Obligation address."),
+            )
+            .arg(
+                Arg::with_name("reserve")
+                    .long("reserve")
+                    .validator(is_pubkey)
+                    .value_name("RESERVE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve to borrow from."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .long("amount")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity to borrow."),
+            )
+            .arg(
+                Arg::with_name("destination")
+                    .long("destination")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Liquidity token account to receive the borrow. Defaults to a new token account."),
+            )
+            .arg(
+                Arg::with_name("host_fee_receiver")
+                    .long("host-fee-receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Liquidity token account to receive the host share of the borrow fee."),
+            )
+        )
+        .subcommand(SubCommand::with_name("repay").about("Repay borrowed liquidity of an obligation")
+            .arg(
+                Arg::with_name("obligation")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("OBLIGATION_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Obligation address."),
+            )
+            .arg(
+                Arg::with_name("reserve")
+                    .long("reserve")
+                    .validator(is_pubkey)
+                    .value_name("RESERVE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve the liquidity was borrowed from."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .long("amount")
+                    .validator(is_amount_or_all)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity to repay; accepts keyword ALL."),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Liquidity token account to repay from. Must be owned by the client."),
+            )
+        )
+        .subcommand(SubCommand::with_name("liquidate").about("Repay liquidity of an unhealthy obligation in exchange for its collateral")
+            .arg(
+                Arg::with_name("obligation")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("OBLIGATION_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Obligation address."),
+            )
+            .arg(
+                Arg::with_name("repay_reserve")
+                    .long("repay-reserve")
+                    .validator(is_pubkey)
+                    .value_name("RESERVE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve of the borrowed liquidity to repay."),
+            )
+            .arg(
+                Arg::with_name("withdraw_reserve")
+                    .long("withdraw-reserve")
+                    .validator(is_pubkey)
+                    .value_name("RESERVE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve of the deposited collateral to receive."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .long("amount")
+                    .validator(is_amount_or_all)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity to repay; accepts keyword ALL for the most that can be liquidated."),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Liquidity token account to repay from. Must be owned by the client."),
+            )
+            .arg(
+                Arg::with_name("destination")
+                    .long("destination")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Collateral token account to receive collateral. Defaults to a new token account."),
+            )
+        )
+        .subcommand(SubCommand::with_name("show-reserve").about("Show the liquidity, interest rates and configuration of a reserve")
+            .arg(
+                Arg::with_name("reserve")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("RESERVE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve address."),
+            )
+        )
+        .subcommand(SubCommand::with_name("show-obligation").about("Show the deposits, borrows and health of an obligation")
+            .arg(
+                Arg::with_name("obligation")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("OBLIGATION_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Obligation address."),
+            )
+        )
+        .subcommand(SubCommand::with_name("scan-obligations").about("Rank the obligations of a lending market by health to find liquidation candidates")
+            .arg(
+                Arg::with_name("market")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("MARKET_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Lending market address."),
+            )
+            .arg(
+                Arg::with_name("all")
+                    .long("all")
+                    .takes_value(false)
+                    .help("Show healthy obligations as well."),
+            )
+        )
+        .get_matches();
+
+    let mut wallet_manager = None;
+    let config = {
+        let cli_config = if let Some(config_file) = matches.value_of("config_file") {
+            solana_cli_config::Config::load(config_file).unwrap_or_default()
+        } else {
+            solana_cli_config::Config::default()
+        };
+        let json_rpc_url = value_t!(matches, "json_rpc_url", String)
+            .unwrap_or_else(|_| cli_config.json_rpc_url.clone());
+        let program_id = pubkey_of(&matches, "program_id").unwrap_or_else(spl_token_lending::id);
+
+        let market_owner = signer_from_path(
+            &matches,
+            &cli_config.keypair_path,
+            "market_owner",
+            &mut wallet_manager,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        });
+        let owner = signer_from_path(
+            &matches,
+            &cli_config.keypair_path,
+            "owner",
+            &mut wallet_manager,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        });
+        let fee_payer = signer_from_path(
+            &matches,
+            &cli_config.keypair_path,
+            "fee_payer",
+            &mut wallet_manager,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        });
+        let verbose = matches.is_present("verbose");
+        let dry_run = matches.is_present("dry_run");
 
-    let account = client.get_account(&reserve_pubkey).unwrap();
-    (reserve_pubkey, Reserve::unpack(&account.data).unwrap())
+        Config {
+            rpc_client: RpcClient::new_with_commitment(json_rpc_url, CommitmentConfig::confirmed()),
+            verbose,
+            program_id,
+            market_owner,
+            owner,
+            fee_payer,
+            dry_run,
+        }
+    };
+
+    let _ = match matches.subcommand() {
+        ("create-market", Some(arg_matches)) => {
+            let quote_token_mint = pubkey_of(arg_matches, "quote_token_mint").unwrap();
+            command_create_market(&config, &quote_token_mint)
+        }
+        ("add-reserve", Some(arg_matches)) => {
+            let lending_market_address = pubkey_of(arg_matches, "market").unwrap();
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let oracle: Option<Pubkey> = pubkey_of(arg_matches, "oracle");
+            let supply_cap = value_t!(arg_matches, "supply_cap", f64).ok();
+            let borrow_cap = value_t!(arg_matches, "borrow_cap", f64).ok();
+            reserve_config_of(arg_matches).and_then(|reserve_config| {
+                command_add_reserve(
+                    &config,
+                    &lending_market_address,
+                    &source,
+                    amount,
+                    reserve_config,
+                    &oracle,
+                    supply_cap,
+                    borrow_cap,
+                )
+            })
+        }
+        ("deposit", Some(arg_matches)) => {
+            let reserve_address = pubkey_of(arg_matches, "reserve").unwrap();
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let destination: Option<Pubkey> = pubkey_of(arg_matches, "destination");
+            let obligation: Option<Pubkey> = pubkey_of(arg_matches, "obligation");
+            command_deposit(
+                &config,
+                &reserve_address,
+                &source,
+                amount,
+                &destination,
+                &obligation,
+            )
+        }
+        ("withdraw", Some(arg_matches)) => {
+            let reserve_address = pubkey_of(arg_matches, "reserve").unwrap();
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let destination: Option<Pubkey> = pubkey_of(arg_matches, "destination");
+            let obligation: Option<Pubkey> = pubkey_of(arg_matches, "obligation");
+            command_withdraw(
+                &config,
+                &reserve_address,
+                &source,
+                amount,
+                &destination,
+                &obligation,
+            )
+        }
+        ("init-obligation", Some(arg_matches)) => {
+            let lending_market_address = pubkey_of(arg_matches, "market").unwrap();
+            command_init_obligation(&config, &lending_market_address)
+        }
+        ("borrow", Some(arg_matches)) => {
+            let obligation_address = pubkey_of(arg_matches, "obligation").unwrap();
+            let reserve_address = pubkey_of(arg_matches, "reserve").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let destination: Option<Pubkey> = pubkey_of(arg_matches, "destination");
+            let host_fee_receiver: Option<Pubkey> = pubkey_of(arg_matches, "host_fee_receiver");
+            command_borrow(
+                &config,
+                &obligation_address,
+                &reserve_address,
+                amount,
+                &destination,
+                &host_fee_receiver,
+            )
+        }
+        ("repay", Some(arg_matches)) => {
+            let obligation_address = pubkey_of(arg_matches, "obligation").unwrap();
+            let reserve_address = pubkey_of(arg_matches, "reserve").unwrap();
+            let amount = match arg_matches.value_of("amount").unwrap() {
+                "ALL" => None,
+                _ => Some(value_t_or_exit!(arg_matches, "amount", f64)),
+            };
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            command_repay(
+                &config,
+                &obligation_address,
+                &reserve_address,
+                amount,
+                &source,
+            )
+        }
+        ("liquidate", Some(arg_matches)) => {
+            let obligation_address = pubkey_of(arg_matches, "obligation").unwrap();
+            let repay_reserve_address = pubkey_of(arg_matches, "repay_reserve").unwrap();
+            let withdraw_reserve_address = pubkey_of(arg_matches, "withdraw_reserve").unwrap();
+            let amount = match arg_matches.value_of("amount").unwrap() {
+                "ALL" => None,
+                _ => Some(value_t_or_exit!(arg_matches, "amount", f64)),
+            };
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let destination: Option<Pubkey> = pubkey_of(arg_matches, "destination");
+            command_liquidate(
+                &config,
+                &obligation_address,
+                &repay_reserve_address,
+                &withdraw_reserve_address,
+                amount,
+                &source,
+                &destination,
+            )
+        }
+        ("show-reserve", Some(arg_matches)) => {
+            let reserve_address = pubkey_of(arg_matches, "reserve").unwrap();
+            command_show_reserve(&config, &reserve_address)
+        }
+        ("show-obligation", Some(arg_matches)) => {
+            let obligation_address = pubkey_of(arg_matches, "obligation").unwrap();
+            command_show_obligation(&config, &obligation_address)
+        }
+        ("scan-obligations", Some(arg_matches)) => {
+            let lending_market_address = pubkey_of(arg_matches, "market").unwrap();
+            let show_all = arg_matches.is_present("all");
+            command_scan_obligations(&config, &lending_market_address, show_all)
+        }
+        _ => unreachable!(),
+    }
+    .map_err(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
 }
@@ -272,7 +272,8 @@ pub const BASE_MINT_OFFSET: usize = 6;
 /// Offset for dex market quote mint
 pub const QUOTE_MINT_OFFSET: usize = 10;
 
-const BIDS_OFFSET: usize = 35;
+/// Offset for dex market bids
+pub const BIDS_OFFSET: usize = 35;
 const ASKS_OFFSET: usize = 39;
 
 /// Dex market info
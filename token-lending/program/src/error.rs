@@ -136,6 +136,81 @@ pub enum LendingError {
     /// Obligation collateral cannot be withdrawn below required amount
     #[error("Obligation collateral cannot be withdrawn below required amount")]
     ObligationCollateralWithdrawBelowRequired,
+    /// Obligation state stale
+    #[error("Obligation state needs to be refreshed for the current slot")]
+    ObligationStale,
+    /// Obligation cannot hold any more deposit or borrow reserves
+    #[error("Obligation reserve limit exceeded")]
+    ObligationReserveLimit,
+
+    // 40
+    /// Obligation has no deposits
+    #[error("Obligation has no deposits")]
+    ObligationDepositsEmpty,
+    /// Obligation has no borrows
+    #[error("Obligation has no borrows")]
+    ObligationBorrowsEmpty,
+    /// Invalid obligation liquidity amount
+    #[error("Invalid obligation liquidity amount")]
+    InvalidObligationLiquidity,
+    /// Withdraw amount too large
+    #[error("Withdraw amount too large for the remaining borrow value")]
+    WithdrawTooLarge,
+    /// Borrow amount too large
+    #[error("Borrow amount too large for deposited collateral")]
+    BorrowTooLarge,
+
+    // 45
+    /// Obligation collateral is empty
+    #[error("Obligation collateral is empty")]
+    ObligationCollateralEmpty,
+    /// Obligation liquidity is empty
+    #[error("Obligation liquidity is empty")]
+    ObligationLiquidityEmpty,
+    /// Obligation deposits have zero value
+    #[error("Obligation deposits have zero value")]
+    ObligationDepositsZero,
+    /// Flash loan was not repaid in full
+    #[error("Flash loan was not repaid with its fee")]
+    FlashLoanNotRepaid,
+    /// Flash loan receiver program is invalid
+    #[error("Invalid flash loan receiver program")]
+    InvalidFlashLoanReceiverProgram,
+
+    // 50
+    /// Expected an SPL Token account
+    #[error("Input token account is not valid")]
+    InvalidTokenAccount,
+    /// Oracle account does not match the reserve oracle config
+    #[error("Input oracle account is not valid")]
+    InvalidOracle,
+    /// Oracle price was published too many slots ago
+    #[error("Oracle price is stale")]
+    OraclePriceStale,
+    /// Oracle price confidence interval is wider than the reserve allows
+    #[error("Oracle price confidence interval is too wide")]
+    OraclePriceUncertain,
+    /// Reserve deposits are disabled by its config
+    #[error("Reserve deposits are disabled")]
+    ReserveDepositsDisabled,
+
+    // 55
+    /// Reserve borrows are disabled by its config
+    #[error("Reserve borrows are disabled")]
+    ReserveBorrowsDisabled,
+    /// Reserve liquidations are disabled by its config
+    #[error("Reserve liquidations are disabled")]
+    ReserveLiquidationsDisabled,
+    /// Deposit or borrow would exceed the reserve supply or borrow cap
+    #[error("Reserve supply or borrow cap exceeded")]
+    ReserveCapExceeded,
+    /// Deposit position does not hold enough collateral
+    #[error("Deposit position collateral is insufficient")]
+    InsufficientPositionCollateral,
+    /// Collateral deposited from a deposit position cannot leave the obligation
+    /// as collateral tokens
+    #[error("Position collateral can only be withdrawn to a deposit position")]
+    ObligationCollateralRestricted,
 }
 
 impl From<LendingError> for ProgramError {
@@ -2,10 +2,8 @@
 
 use crate::{
     error::LendingError,
-    state::{ReserveConfig, ReserveFees},
+    state::{BorrowRateCurve, BorrowRateCurvePoint, OracleConfig, ReserveConfig, ReserveFees},
 };
-use num_derive::{FromPrimitive, ToPrimitive};
-use num_traits::{FromPrimitive, ToPrimitive};
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
@@ -14,15 +12,6 @@ use solana_program::{
 };
 use std::{convert::TryInto, mem::size_of};
 
-/// Describe how the borrow input amount should be treated
-#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive, ToPrimitive)]
-pub enum BorrowAmountType {
-    /// Treat amount as amount of liquidity to borrow
-    LiquidityBorrowAmount,
-    /// Treat amount as amount of collateral tokens to deposit
-    CollateralDepositAmount,
-}
-
 /// Instructions supported by the lending program.
 #[derive(Clone, Debug, PartialEq)]
 pub enum LendingInstruction {
@@ -52,8 +41,8 @@ pub enum LendingInstruction {
     ///   4. `[writable]` Reserve liquidity supply SPL Token account - uninitialized
     ///   5. `[writable]` Reserve collateral SPL Token mint - uninitialized
     ///   6. `[writable]` Reserve collateral token supply - uninitialized
-    ///   7. `[writable]` Reserve collateral fees receiver - uninitialized.
-    ///                     Owner will be set to the lending market account.
+    ///   7. `[writable]` Reserve liquidity fee receiver - uninitialized.
+    ///                     Owner will be set to the lending market owner.
     ///   8. `[]` Lending market account.
     ///   9. `[signer]` Lending market owner.
     ///   10 `[]` Derived lending market authority.
@@ -61,7 +50,12 @@ pub enum LendingInstruction {
     ///   12 `[]` Clock sysvar
     ///   13 `[]` Rent sysvar
     ///   14 '[]` Token program id
-    ///   15 `[optional]` Serum DEX market account. Not required for quote currency reserves. Must be initialized and match quote and base currency.
+    ///   15 `[optional]` Price oracle account, as selected by the reserve oracle config.
+    ///                     Not required for quote currency reserves.
+    ///                     Serum DEX market: must be initialized and match quote and base currency.
+    ///                     Push oracle feed: must be initialized.
+    ///                     Token swap: must exchange the reserve liquidity and quote currency
+    ///                       and track cumulative prices.
     InitReserve {
         /// Initial amount of liquidity to deposit into the new reserve
         liquidity_amount: u64,
@@ -70,21 +64,16 @@ pub enum LendingInstruction {
     },
 
     // 2
-    /// Initializes a new loan obligation.
+    /// Initializes a new loan obligation which can hold collateral deposits and
+    /// liquidity borrows across the reserves of a lending market.
     ///
     /// Accounts expected by this instruction:
     ///
-    ///   0. `[]` Deposit reserve account.
-    ///   1. `[]` Borrow reserve account.
-    ///   2. `[writable]` Obligation
-    ///   3. `[writable]` Obligation token mint
-    ///   4. `[writable]` Obligation token output
-    ///   5. `[]` Obligation token owner
-    ///   6. `[]` Lending market account.
-    ///   7. `[]` Derived lending market authority.
-    ///   8. `[]` Clock sysvar
-    ///   9. `[]` Rent sysvar
-    ///   10 '[]` Token program id
+    ///   0. `[writable]` Obligation account - uninitialized.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Obligation owner.
+    ///   3. `[]` Clock sysvar
+    ///   4. `[]` Rent sysvar
     InitObligation,
 
     // 3
@@ -129,146 +118,138 @@ pub enum LendingInstruction {
     },
 
     // 5
-    /// Borrow tokens from a reserve by depositing collateral tokens. The number of borrowed tokens
-    /// is calculated by market price. The debt obligation is tokenized.
+    /// Borrow liquidity from a reserve against the collateral deposited in an
+    /// obligation. The borrow value cannot exceed the obligation's remaining
+    /// borrow value and the borrow fee is deducted from the received amount.
     ///
     /// Accounts expected by this instruction:
     ///
-    ///   0. `[writable]` Source collateral token account, minted by deposit reserve collateral mint,
-    ///                     $authority can transfer $collateral_amount
-    ///   1. `[writable]` Destination liquidity token account, minted by borrow reserve liquidity mint
-    ///   2. `[]` Deposit reserve account.
-    ///   3. `[writable]` Deposit reserve collateral supply SPL Token account
-    ///   4. `[writable]` Deposit reserve collateral fee receiver account.
+    ///   0. `[writable]` Source borrow reserve liquidity supply SPL Token account.
+    ///   1. `[writable]` Destination liquidity token account.
+    ///                     Minted by borrow reserve liquidity mint.
+    ///   2. `[writable]` Borrow reserve account - refreshed.
+    ///   3. `[writable]` Borrow reserve liquidity fee receiver account.
     ///                     Must be the fee account specified at InitReserve.
-    ///   5. `[writable]` Borrow reserve account.
-    ///   6. `[writable]` Borrow reserve liquidity supply SPL Token account
-    ///   7. `[writable]` Obligation
-    ///   8. `[writable]` Obligation token mint
-    ///   9. `[writable]` Obligation token output
-    ///   10 `[]` Lending market account.
-    ///   11 `[]` Derived lending market authority.
-    ///   12 `[signer]` User transfer authority ($authority).
-    ///   13 `[]` Dex market
-    ///   14 `[]` Dex market order book side
-    ///   15 `[]` Temporary memory
-    ///   16 `[]` Clock sysvar
-    ///   17 '[]` Token program id
-    ///   18 `[optional, writable]` Deposit reserve collateral host fee receiver account.
-    BorrowReserveLiquidity {
+    ///   4. `[writable]` Obligation account - refreshed.
+    ///   5. `[]` Lending market account.
+    ///   6. `[]` Derived lending market authority.
+    ///   7. `[signer]` Obligation owner.
+    ///   8. `[]` Clock sysvar
+    ///   9. '[]` Token program id
+    ///   10 `[optional, writable]` Host fee receiver account.
+    BorrowObligationLiquidity {
         // TODO: slippage constraint
-        /// Amount whose usage depends on `amount_type`
-        amount: u64,
-        /// Describe how the amount should be treated
-        amount_type: BorrowAmountType,
+        /// Amount of liquidity to borrow
+        liquidity_amount: u64,
     },
 
     // 6
-    /// Repay loaned tokens to a reserve and receive collateral tokens. The obligation balance
-    /// will be recalculated for interest.
+    /// Repay borrowed liquidity to a reserve. Interest accrued on the obligation
+    /// borrow is settled first, and repaying more than is owed settles the borrow.
     ///
     /// Accounts expected by this instruction:
     ///
-    ///   0. `[writable]` Source liquidity token account, minted by repay reserve liquidity mint
-    ///                     $authority can transfer $collateral_amount
-    ///   1. `[writable]` Destination collateral token account, minted by withdraw reserve collateral mint
-    ///   2. `[writable]` Repay reserve account.
-    ///   3. `[writable]` Repay reserve liquidity supply SPL Token account
-    ///   4. `[]` Withdraw reserve account.
-    ///   5. `[writable]` Withdraw reserve collateral supply SPL Token account
-    ///   6. `[writable]` Obligation - initialized
-    ///   7. `[writable]` Obligation token mint
-    ///   8. `[writable]` Obligation token input, $authority can transfer calculated amount
-    ///   9. `[]` Lending market account.
-    ///   10 `[]` Derived lending market authority.
-    ///   11 `[signer]` User transfer authority ($authority).
-    ///   12 `[]` Clock sysvar
-    ///   13 `[]` Token program id
-    RepayReserveLiquidity {
-        /// Amount of loan to repay
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     Minted by repay reserve liquidity mint.
+    ///                     $authority can transfer $liquidity_amount.
+    ///   1. `[writable]` Destination repay reserve liquidity supply SPL Token account.
+    ///   2. `[writable]` Repay reserve account - refreshed.
+    ///   3. `[writable]` Obligation account.
+    ///   4. `[]` Lending market account.
+    ///   5. `[signer]` User transfer authority ($authority).
+    ///   6. `[]` Clock sysvar
+    ///   7. '[]` Token program id
+    RepayObligationLiquidity {
+        /// Amount of liquidity to repay
         liquidity_amount: u64,
     },
 
     // 7
-    /// Purchase collateral tokens at a discount rate if the chosen obligation is unhealthy.
+    /// Repay a borrow of an unhealthy obligation and receive collateral from one
+    /// of its deposits at a discount. The liquidator chooses which borrow to
+    /// repay and which deposit to withdraw.
     ///
     /// Accounts expected by this instruction:
     ///
-    ///   0. `[writable]` Source liquidity token account, minted by repay reserve liquidity mint
-    ///                     $authority can transfer $collateral_amount
-    ///   1. `[writable]` Destination collateral token account, minted by withdraw reserve collateral mint
-    ///   2. `[writable]` Repay reserve account.
-    ///   3. `[writable]` Repay reserve liquidity supply SPL Token account
-    ///   4. `[]` Withdraw reserve account.
-    ///   5. `[writable]` Withdraw reserve collateral supply SPL Token account
-    ///   6. `[writable]` Obligation - initialized
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     Minted by repay reserve liquidity mint.
+    ///                     $authority can transfer $liquidity_amount.
+    ///   1. `[writable]` Destination collateral token account.
+    ///                     Minted by withdraw reserve collateral mint.
+    ///   2. `[writable]` Repay reserve account - refreshed.
+    ///   3. `[writable]` Repay reserve liquidity supply SPL Token account.
+    ///   4. `[]` Withdraw reserve account - refreshed.
+    ///   5. `[writable]` Withdraw reserve collateral supply SPL Token account.
+    ///   6. `[writable]` Obligation account - refreshed.
     ///   7. `[]` Lending market account.
     ///   8. `[]` Derived lending market authority.
     ///   9. `[signer]` User transfer authority ($authority).
-    ///   10 `[]` Dex market
-    ///   11 `[]` Dex market order book side
-    ///   12 `[]` Temporary memory
-    ///   13 `[]` Clock sysvar
-    ///   14 `[]` Token program id
+    ///   10 `[]` Clock sysvar
+    ///   11 `[]` Token program id
     LiquidateObligation {
-        /// Amount of loan to repay
+        /// Amount of liquidity to repay
         liquidity_amount: u64,
     },
 
     // 8
-    /// Accrue interest on reserves
+    /// Accrue interest and update the market price of a reserve. Reserves must
+    /// be refreshed in the same slot before they can be used for borrows,
+    /// withdrawals and liquidations.
     ///
     /// Accounts expected by this instruction:
     ///
-    ///   0. `[]` Clock sysvar
-    ///   1. `[writable]` Reserve account.
-    ///   .. `[writable]` Additional reserve accounts.
-    AccrueReserveInterest,
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[]` Clock sysvar
+    ///   .. `[]` Reserve oracle accounts, not required for quote currency reserves.
+    ///           Serum DEX market:
+    ///             2. `[]` Reserve dex market account.
+    ///             3. `[]` Dex market bids order book side.
+    ///             4. `[writable]` Temporary memory owned by the lending program.
+    ///           Push oracle:
+    ///             2. `[]` Reserve push oracle feed account.
+    ///           Token swap TWAP:
+    ///             2. `[]` Reserve token swap account.
+    RefreshReserve,
 
     // 9
-    /// Deposit additional collateral to an obligation.
+    /// Deposit collateral to an obligation.
     ///
     /// Accounts expected by this instruction:
     ///
-    ///   0. `[writable]` Source collateral token account, minted by deposit reserve collateral mint,
-    ///                     $authority can transfer $collateral_amount
-    ///   1. `[writable]` Destination deposit reserve collateral supply SPL Token account
+    ///   0. `[writable]` Source collateral token account.
+    ///                     Minted by deposit reserve collateral mint.
+    ///                     $authority can transfer $collateral_amount.
+    ///   1. `[writable]` Destination deposit reserve collateral supply SPL Token account.
     ///   2. `[]` Deposit reserve account.
-    ///   3. `[writable]` Obligation
-    ///   4. `[writable]` Obligation token mint
-    ///   5. `[writable]` Obligation token output
-    ///   6. `[]` Lending market account.
-    ///   7. `[]` Derived lending market authority.
-    ///   8. `[signer]` User transfer authority ($authority).
-    ///   9. '[]` Token program id
+    ///   3. `[writable]` Obligation account.
+    ///   4. `[]` Lending market account.
+    ///   5. `[signer]` Obligation owner.
+    ///   6. `[signer]` User transfer authority ($authority).
+    ///   7. '[]` Token program id
     DepositObligationCollateral {
-        /// Amount of collateral to deposit
+        /// Amount of collateral tokens to deposit
         collateral_amount: u64,
     },
 
     // 10
-    /// Withdraw excess collateral from an obligation. The loan must remain healthy.
+    /// Withdraw collateral from an obligation. The obligation must remain within
+    /// its allowed borrow value. Collateral deposited from a deposit position
+    /// can only be withdrawn with WithdrawPositionCollateral.
     ///
     /// Accounts expected by this instruction:
     ///
-    ///   0. `[writable]` Source withdraw reserve collateral supply SPL Token account
-    ///   1. `[writable]` Destination collateral token account, minted by withdraw reserve
-    ///                     collateral mint. $authority can transfer $collateral_amount
-    ///   2. `[]` Withdraw reserve account.
-    ///   3. `[]` Borrow reserve account.
-    ///   4. `[writable]` Obligation
-    ///   5. `[writable]` Obligation token mint
-    ///   6. `[writable]` Obligation token input
-    ///   7. `[]` Lending market account.
-    ///   8. `[]` Derived lending market authority.
-    ///   9. `[signer]` User transfer authority ($authority).
-    ///   10 `[]` Dex market
-    ///   11 `[]` Dex market order book side
-    ///   12 `[]` Temporary memory
-    ///   13 `[]` Clock sysvar
-    ///   14 '[]` Token program id
+    ///   0. `[writable]` Source withdraw reserve collateral supply SPL Token account.
+    ///   1. `[writable]` Destination collateral token account.
+    ///                     Minted by withdraw reserve collateral mint.
+    ///   2. `[]` Withdraw reserve account - refreshed.
+    ///   3. `[writable]` Obligation account - refreshed.
+    ///   4. `[]` Lending market account.
+    ///   5. `[]` Derived lending market authority.
+    ///   6. `[signer]` Obligation owner.
+    ///   7. `[]` Clock sysvar
+    ///   8. '[]` Token program id
     WithdrawObligationCollateral {
-        /// Amount of collateral to withdraw
+        /// Amount of collateral tokens to withdraw
         collateral_amount: u64,
     },
 
@@ -283,6 +264,176 @@ pub enum LendingInstruction {
         /// The new owner
         new_owner: Pubkey,
     },
+
+    // 12
+    /// Accrue interest on obligation borrows and update the market value of
+    /// its deposits and borrows, along with its allowed and unhealthy borrow
+    /// values. Obligations must be refreshed in the same slot before they can
+    /// be used for borrows, withdrawals and liquidations.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account.
+    ///   1. `[]` Clock sysvar
+    ///   .. `[]` Collateral deposit reserve accounts - refreshed, all, in order.
+    ///   .. `[]` Liquidity borrow reserve accounts - refreshed, all, in order.
+    RefreshObligation,
+
+    // 13
+    /// Make a flash loan of reserve liquidity. The receiver program is invoked
+    /// with instruction data `[0, amount (u64, little endian)]` and must return
+    /// the amount plus the flash loan fee to the reserve liquidity supply before
+    /// it returns.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source reserve liquidity supply SPL Token account.
+    ///   1. `[writable]` Destination liquidity token account.
+    ///                     Minted by reserve liquidity mint.
+    ///   2. `[writable]` Reserve account.
+    ///   3. `[writable]` Reserve liquidity fee receiver account.
+    ///                     Must be the fee account specified at InitReserve.
+    ///   4. `[writable]` Host fee receiver account.
+    ///   5. `[]` Lending market account.
+    ///   6. `[]` Derived lending market authority.
+    ///   7. '[]` Token program id
+    ///   8. `[]` Flash loan receiver program id.
+    ///   .. `[any]` Additional accounts passed to the flash loan receiver
+    ///                program after the destination liquidity and token
+    ///                program accounts.
+    FlashLoan {
+        /// Amount of liquidity to flash borrow
+        amount: u64,
+    },
+
+    // 14
+    /// Update the config of a reserve. The new config is validated the same
+    /// way as at InitReserve, and the reserve oracle type cannot be changed.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Lending market owner.
+    UpdateReserveConfig {
+        /// New reserve configuration values
+        config: ReserveConfig,
+    },
+
+    // 15
+    /// Withdraw the protocol fees set aside from accrued interest by the
+    /// reserve factor. Withdrawing more than the protocol fees or the available
+    /// liquidity withdraws as much as possible.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[writable]` Reserve liquidity supply SPL Token account.
+    ///   2. `[writable]` Destination liquidity token account.
+    ///                     Minted by reserve liquidity mint.
+    ///   3. `[]` Lending market account.
+    ///   4. `[signer]` Lending market owner.
+    ///   5. `[]` Derived lending market authority.
+    ///   6. '[]` Token program id
+    WithdrawProtocolFees {
+        /// Amount of liquidity to withdraw
+        liquidity_amount: u64,
+    },
+
+    // 16
+    /// Initializes a new deposit position. A deposit position records
+    /// collateral owned by a single user without minting transferable
+    /// collateral tokens, and can only be redeemed or used as obligation
+    /// collateral by its owner.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Deposit position account - uninitialized.
+    ///   1. `[]` Reserve account.
+    ///   2. `[]` Lending market account.
+    ///   3. `[signer]` Deposit position owner.
+    ///   4. `[]` Rent sysvar
+    InitDepositPosition,
+
+    // 17
+    /// Deposit liquidity into a reserve and record the collateral in a deposit
+    /// position. The collateral tokens are minted into the reserve collateral
+    /// supply instead of a user token account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account. $authority can transfer $liquidity_amount
+    ///   1. `[writable]` Deposit position account.
+    ///   2. `[writable]` Reserve account.
+    ///   3. `[writable]` Reserve liquidity supply SPL Token account.
+    ///   4. `[writable]` Reserve collateral SPL Token mint.
+    ///   5. `[writable]` Reserve collateral supply SPL Token account.
+    ///   6. `[]` Lending market account.
+    ///   7. `[]` Derived lending market authority.
+    ///   8. `[signer]` Deposit position owner.
+    ///   9. `[signer]` User transfer authority ($authority).
+    ///   10. `[]` Clock sysvar
+    ///   11. '[]` Token program id
+    DepositPositionLiquidity {
+        /// Amount to deposit into the reserve
+        liquidity_amount: u64,
+    },
+
+    // 18
+    /// Redeem collateral recorded in a deposit position for reserve liquidity.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Deposit position account.
+    ///   1. `[writable]` Destination liquidity token account.
+    ///   2. `[writable]` Reserve account - refreshed.
+    ///   3. `[writable]` Reserve collateral SPL Token mint.
+    ///   4. `[writable]` Reserve collateral supply SPL Token account.
+    ///   5. `[writable]` Reserve liquidity supply SPL Token account.
+    ///   6. `[]` Lending market account.
+    ///   7. `[]` Derived lending market authority.
+    ///   8. `[signer]` Deposit position owner.
+    ///   9. `[]` Clock sysvar
+    ///   10. '[]` Token program id
+    WithdrawPositionLiquidity {
+        /// Amount of position collateral to redeem for liquidity
+        collateral_amount: u64,
+    },
+
+    // 19
+    /// Move collateral from a deposit position to an obligation with the same
+    /// owner. The obligation records the deposited amount as position
+    /// collateral, which can only be withdrawn back into deposit positions.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Deposit position account.
+    ///   1. `[]` Deposit reserve account.
+    ///   2. `[writable]` Obligation account.
+    ///   3. `[]` Lending market account.
+    ///   4. `[signer]` Deposit position and obligation owner.
+    DepositPositionCollateral {
+        /// Amount of position collateral to deposit
+        collateral_amount: u64,
+    },
+
+    // 20
+    /// Move collateral from an obligation back to a deposit position with the
+    /// same owner, taking position collateral first. The obligation must
+    /// remain within its allowed borrow value.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Deposit position account.
+    ///   1. `[]` Withdraw reserve account - refreshed.
+    ///   2. `[writable]` Obligation account - refreshed.
+    ///   3. `[]` Lending market account.
+    ///   4. `[signer]` Deposit position and obligation owner.
+    ///   5. `[]` Clock sysvar
+    WithdrawPositionCollateral {
+        /// Amount of collateral tokens to withdraw
+        collateral_amount: u64,
+    },
 }
 
 impl LendingInstruction {
@@ -298,30 +449,10 @@ impl LendingInstruction {
             }
             1 => {
                 let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
-                let (optimal_utilization_rate, rest) = Self::unpack_u8(rest)?;
-                let (loan_to_value_ratio, rest) = Self::unpack_u8(rest)?;
-                let (liquidation_bonus, rest) = Self::unpack_u8(rest)?;
-                let (liquidation_threshold, rest) = Self::unpack_u8(rest)?;
-                let (min_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (optimal_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (max_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (borrow_fee_wad, rest) = Self::unpack_u64(rest)?;
-                let (host_fee_percentage, _rest) = Self::unpack_u8(rest)?;
+                let (config, _rest) = Self::unpack_reserve_config(rest)?;
                 Self::InitReserve {
                     liquidity_amount,
-                    config: ReserveConfig {
-                        optimal_utilization_rate,
-                        loan_to_value_ratio,
-                        liquidation_bonus,
-                        liquidation_threshold,
-                        min_borrow_rate,
-                        optimal_borrow_rate,
-                        max_borrow_rate,
-                        fees: ReserveFees {
-                            borrow_fee_wad,
-                            host_fee_percentage,
-                        },
-                    },
+                    config,
                 }
             }
             2 => Self::InitObligation,
@@ -334,24 +465,18 @@ impl LendingInstruction {
                 Self::WithdrawReserveLiquidity { collateral_amount }
             }
             5 => {
-                let (amount, rest) = Self::unpack_u64(rest)?;
-                let (amount_type, _rest) = Self::unpack_u8(rest)?;
-                let amount_type = BorrowAmountType::from_u8(amount_type)
-                    .ok_or(LendingError::InstructionUnpackError)?;
-                Self::BorrowReserveLiquidity {
-                    amount,
-                    amount_type,
-                }
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::BorrowObligationLiquidity { liquidity_amount }
             }
             6 => {
                 let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::RepayReserveLiquidity { liquidity_amount }
+                Self::RepayObligationLiquidity { liquidity_amount }
             }
             7 => {
                 let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
                 Self::LiquidateObligation { liquidity_amount }
             }
-            8 => Self::AccrueReserveInterest,
+            8 => Self::RefreshReserve,
             9 => {
                 let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
                 Self::DepositObligationCollateral { collateral_amount }
@@ -364,10 +489,91 @@ impl LendingInstruction {
                 let (new_owner, _rest) = Self::unpack_pubkey(rest)?;
                 Self::SetLendingMarketOwner { new_owner }
             }
+            12 => Self::RefreshObligation,
+            13 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::FlashLoan { amount }
+            }
+            14 => {
+                let (config, _rest) = Self::unpack_reserve_config(rest)?;
+                Self::UpdateReserveConfig { config }
+            }
+            15 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::WithdrawProtocolFees { liquidity_amount }
+            }
+            16 => Self::InitDepositPosition,
+            17 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositPositionLiquidity { liquidity_amount }
+            }
+            18 => {
+                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::WithdrawPositionLiquidity { collateral_amount }
+            }
+            19 => {
+                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositPositionCollateral { collateral_amount }
+            }
+            20 => {
+                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::WithdrawPositionCollateral { collateral_amount }
+            }
             _ => return Err(LendingError::InstructionUnpackError.into()),
         })
     }
 
+    fn unpack_reserve_config(input: &[u8]) -> Result<(ReserveConfig, &[u8]), ProgramError> {
+        let (loan_to_value_ratio, rest) = Self::unpack_u8(input)?;
+        let (liquidation_bonus, rest) = Self::unpack_u8(rest)?;
+        let (liquidation_threshold, rest) = Self::unpack_u8(rest)?;
+        let (borrow_fee_wad, rest) = Self::unpack_u64(rest)?;
+        let (flash_loan_fee_wad, rest) = Self::unpack_u64(rest)?;
+        let (host_fee_percentage, rest) = Self::unpack_u8(rest)?;
+        let (oracle_type, rest) = Self::unpack_u8(rest)?;
+        let (oracle_param_a, rest) = Self::unpack_u64(rest)?;
+        let (oracle_param_b, rest) = Self::unpack_u64(rest)?;
+        let oracle = OracleConfig::from_parts(oracle_type, oracle_param_a, oracle_param_b)
+            .ok_or(LendingError::InstructionUnpackError)?;
+        let (deposits_disabled, rest) = Self::unpack_bool(rest)?;
+        let (borrows_disabled, rest) = Self::unpack_bool(rest)?;
+        let (liquidations_disabled, rest) = Self::unpack_bool(rest)?;
+        let (supply_cap, rest) = Self::unpack_u64(rest)?;
+        let (borrow_cap, rest) = Self::unpack_u64(rest)?;
+        let (reserve_factor, mut rest) = Self::unpack_u8(rest)?;
+        let mut borrow_rate_curve = BorrowRateCurve::default();
+        for point in borrow_rate_curve.points.iter_mut() {
+            let (utilization_rate_bps, next) = Self::unpack_u16(rest)?;
+            let (borrow_rate_bps, next) = Self::unpack_u16(next)?;
+            *point = BorrowRateCurvePoint {
+                utilization_rate_bps,
+                borrow_rate_bps,
+            };
+            rest = next;
+        }
+        Ok((
+            ReserveConfig {
+                loan_to_value_ratio,
+                liquidation_bonus,
+                liquidation_threshold,
+                borrow_rate_curve,
+                fees: ReserveFees {
+                    borrow_fee_wad,
+                    flash_loan_fee_wad,
+                    host_fee_percentage,
+                },
+                oracle,
+                deposits_disabled,
+                borrows_disabled,
+                liquidations_disabled,
+                supply_cap,
+                borrow_cap,
+                reserve_factor,
+            },
+            rest,
+        ))
+    }
+
     fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
         if input.len() >= 8 {
             let (amount, rest) = input.split_at(8);
@@ -382,6 +588,20 @@ impl LendingInstruction {
         }
     }
 
+    fn unpack_u16(input: &[u8]) -> Result<(u16, &[u8]), ProgramError> {
+        if input.len() >= 2 {
+            let (amount, rest) = input.split_at(2);
+            let amount = amount
+                .get(..2)
+                .and_then(|slice| slice.try_into().ok())
+                .map(u16::from_le_bytes)
+                .ok_or(LendingError::InstructionUnpackError)?;
+            Ok((amount, rest))
+        } else {
+            Err(LendingError::InstructionUnpackError.into())
+        }
+    }
+
     fn unpack_u8(input: &[u8]) -> Result<(u8, &[u8]), ProgramError> {
         if !input.is_empty() {
             let (amount, rest) = input.split_at(1);
@@ -396,6 +616,15 @@ impl LendingInstruction {
         }
     }
 
+    fn unpack_bool(input: &[u8]) -> Result<(bool, &[u8]), ProgramError> {
+        let (value, rest) = Self::unpack_u8(input)?;
+        match value {
+            0 => Ok((false, rest)),
+            1 => Ok((true, rest)),
+            _ => Err(LendingError::InstructionUnpackError.into()),
+        }
+    }
+
     fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
         if input.len() >= 32 {
             let (key, rest) = input.split_at(32);
@@ -416,33 +645,11 @@ impl LendingInstruction {
             }
             Self::InitReserve {
                 liquidity_amount,
-                config:
-                    ReserveConfig {
-                        optimal_utilization_rate,
-                        loan_to_value_ratio,
-                        liquidation_bonus,
-                        liquidation_threshold,
-                        min_borrow_rate,
-                        optimal_borrow_rate,
-                        max_borrow_rate,
-                        fees:
-                            ReserveFees {
-                                borrow_fee_wad,
-                                host_fee_percentage,
-                            },
-                    },
+                config,
             } => {
                 buf.push(1);
                 buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-                buf.extend_from_slice(&optimal_utilization_rate.to_le_bytes());
-                buf.extend_from_slice(&loan_to_value_ratio.to_le_bytes());
-                buf.extend_from_slice(&liquidation_bonus.to_le_bytes());
-                buf.extend_from_slice(&liquidation_threshold.to_le_bytes());
-                buf.extend_from_slice(&min_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&optimal_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&max_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&borrow_fee_wad.to_le_bytes());
-                buf.extend_from_slice(&host_fee_percentage.to_le_bytes());
+                Self::pack_reserve_config(&config, &mut buf);
             }
             Self::InitObligation => {
                 buf.push(2);
@@ -455,15 +662,11 @@ impl LendingInstruction {
                 buf.push(4);
                 buf.extend_from_slice(&collateral_amount.to_le_bytes());
             }
-            Self::BorrowReserveLiquidity {
-                amount,
-                amount_type,
-            } => {
+            Self::BorrowObligationLiquidity { liquidity_amount } => {
                 buf.push(5);
-                buf.extend_from_slice(&amount.to_le_bytes());
-                buf.extend_from_slice(&amount_type.to_u8().unwrap().to_le_bytes());
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
             }
-            Self::RepayReserveLiquidity { liquidity_amount } => {
+            Self::RepayObligationLiquidity { liquidity_amount } => {
                 buf.push(6);
                 buf.extend_from_slice(&liquidity_amount.to_le_bytes());
             }
@@ -471,7 +674,7 @@ impl LendingInstruction {
                 buf.push(7);
                 buf.extend_from_slice(&liquidity_amount.to_le_bytes());
             }
-            Self::AccrueReserveInterest => {
+            Self::RefreshReserve => {
                 buf.push(8);
             }
             Self::DepositObligationCollateral { collateral_amount } => {
@@ -486,9 +689,66 @@ impl LendingInstruction {
                 buf.push(11);
                 buf.extend_from_slice(new_owner.as_ref());
             }
+            Self::RefreshObligation => {
+                buf.push(12);
+            }
+            Self::FlashLoan { amount } => {
+                buf.push(13);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::UpdateReserveConfig { config } => {
+                buf.push(14);
+                Self::pack_reserve_config(&config, &mut buf);
+            }
+            Self::WithdrawProtocolFees { liquidity_amount } => {
+                buf.push(15);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::InitDepositPosition => {
+                buf.push(16);
+            }
+            Self::DepositPositionLiquidity { liquidity_amount } => {
+                buf.push(17);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::WithdrawPositionLiquidity { collateral_amount } => {
+                buf.push(18);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+            }
+            Self::DepositPositionCollateral { collateral_amount } => {
+                buf.push(19);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+            }
+            Self::WithdrawPositionCollateral { collateral_amount } => {
+                buf.push(20);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+            }
         }
         buf
     }
+
+    fn pack_reserve_config(config: &ReserveConfig, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&config.loan_to_value_ratio.to_le_bytes());
+        buf.extend_from_slice(&config.liquidation_bonus.to_le_bytes());
+        buf.extend_from_slice(&config.liquidation_threshold.to_le_bytes());
+        buf.extend_from_slice(&config.fees.borrow_fee_wad.to_le_bytes());
+        buf.extend_from_slice(&config.fees.flash_loan_fee_wad.to_le_bytes());
+        buf.extend_from_slice(&config.fees.host_fee_percentage.to_le_bytes());
+        let (oracle_type, oracle_param_a, oracle_param_b) = config.oracle.to_parts();
+        buf.extend_from_slice(&oracle_type.to_le_bytes());
+        buf.extend_from_slice(&oracle_param_a.to_le_bytes());
+        buf.extend_from_slice(&oracle_param_b.to_le_bytes());
+        buf.push(config.deposits_disabled as u8);
+        buf.push(config.borrows_disabled as u8);
+        buf.push(config.liquidations_disabled as u8);
+        buf.extend_from_slice(&config.supply_cap.to_le_bytes());
+        buf.extend_from_slice(&config.borrow_cap.to_le_bytes());
+        buf.extend_from_slice(&config.reserve_factor.to_le_bytes());
+        for point in config.borrow_rate_curve.points.iter() {
+            buf.extend_from_slice(&point.utilization_rate_bps.to_le_bytes());
+            buf.extend_from_slice(&point.borrow_rate_bps.to_le_bytes());
+        }
+    }
 }
 
 /// Creates an 'InitLendingMarket' instruction.
@@ -526,11 +786,11 @@ pub fn init_reserve(
     reserve_liquidity_supply_pubkey: Pubkey,
     reserve_collateral_mint_pubkey: Pubkey,
     reserve_collateral_supply_pubkey: Pubkey,
-    reserve_collateral_fees_receiver_pubkey: Pubkey,
+    reserve_liquidity_fee_receiver_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     lending_market_owner_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
-    dex_market_pubkey: Option<Pubkey>,
+    oracle_pubkey: Option<Pubkey>,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) =
         Pubkey::find_program_address(&[&lending_market_pubkey.to_bytes()[..32]], &program_id);
@@ -542,7 +802,7 @@ pub fn init_reserve(
         AccountMeta::new(reserve_liquidity_supply_pubkey, false),
         AccountMeta::new(reserve_collateral_mint_pubkey, false),
         AccountMeta::new(reserve_collateral_supply_pubkey, false),
-        AccountMeta::new(reserve_collateral_fees_receiver_pubkey, false),
+        AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
         AccountMeta::new_readonly(lending_market_pubkey, false),
         AccountMeta::new_readonly(lending_market_owner_pubkey, true),
         AccountMeta::new_readonly(lending_market_authority_pubkey, false),
@@ -552,8 +812,8 @@ pub fn init_reserve(
         AccountMeta::new_readonly(spl_token::id(), false),
     ];
 
-    if let Some(dex_market_pubkey) = dex_market_pubkey {
-        accounts.push(AccountMeta::new_readonly(dex_market_pubkey, false));
+    if let Some(oracle_pubkey) = oracle_pubkey {
+        accounts.push(AccountMeta::new_readonly(oracle_pubkey, false));
     }
 
     Instruction {
@@ -568,35 +828,21 @@ pub fn init_reserve(
 }
 
 /// Creates an 'InitObligation' instruction.
-#[allow(clippy::too_many_arguments)]
 pub fn init_obligation(
     program_id: Pubkey,
-    deposit_reserve_pubkey: Pubkey,
-    borrow_reserve_pubkey: Pubkey,
-    lending_market_pubkey: Pubkey,
     obligation_pubkey: Pubkey,
-    obligation_token_mint_pubkey: Pubkey,
-    obligation_token_output_pubkey: Pubkey,
-    obligation_token_owner_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
 ) -> Instruction {
-    let (lending_market_authority_pubkey, _bump_seed) =
-        Pubkey::find_program_address(&[&lending_market_pubkey.to_bytes()[..32]], &program_id);
-    let accounts = vec![
-        AccountMeta::new_readonly(deposit_reserve_pubkey, false),
-        AccountMeta::new_readonly(borrow_reserve_pubkey, false),
-        AccountMeta::new(obligation_pubkey, false),
-        AccountMeta::new(obligation_token_mint_pubkey, false),
-        AccountMeta::new(obligation_token_output_pubkey, false),
-        AccountMeta::new_readonly(obligation_token_owner_pubkey, false),
-        AccountMeta::new_readonly(lending_market_pubkey, false),
-        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-        AccountMeta::new_readonly(sysvar::clock::id(), false),
-        AccountMeta::new_readonly(sysvar::rent::id(), false),
-        AccountMeta::new_readonly(spl_token::id(), false),
-    ];
     Instruction {
         program_id,
-        accounts,
+        accounts: vec![
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
         data: LendingInstruction::InitObligation.pack(),
     }
 }
@@ -665,104 +911,68 @@ pub fn withdraw_reserve_liquidity(
     }
 }
 
-/// Creates a 'BorrowReserveLiquidity' instruction.
+/// Creates a 'BorrowObligationLiquidity' instruction.
 #[allow(clippy::too_many_arguments)]
-pub fn borrow_reserve_liquidity(
+pub fn borrow_obligation_liquidity(
     program_id: Pubkey,
-    amount: u64,
-    amount_type: BorrowAmountType,
-    source_collateral_pubkey: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
     destination_liquidity_pubkey: Pubkey,
-    deposit_reserve_pubkey: Pubkey,
-    deposit_reserve_collateral_supply_pubkey: Pubkey,
-    deposit_reserve_collateral_fees_receiver_pubkey: Pubkey,
     borrow_reserve_pubkey: Pubkey,
-    borrow_reserve_liquidity_supply_pubkey: Pubkey,
+    borrow_reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     lending_market_authority_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
-    obligation_pubkey: Pubkey,
-    obligation_token_mint_pubkey: Pubkey,
-    obligation_token_output_pubkey: Pubkey,
-    dex_market_pubkey: Pubkey,
-    dex_market_order_book_side_pubkey: Pubkey,
-    memory_pubkey: Pubkey,
-    deposit_reserve_collateral_host_pubkey: Option<Pubkey>,
+    obligation_owner_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Option<Pubkey>,
 ) -> Instruction {
     let mut accounts = vec![
-        AccountMeta::new(source_collateral_pubkey, false),
+        AccountMeta::new(source_liquidity_pubkey, false),
         AccountMeta::new(destination_liquidity_pubkey, false),
-        AccountMeta::new_readonly(deposit_reserve_pubkey, false),
-        AccountMeta::new(deposit_reserve_collateral_supply_pubkey, false),
-        AccountMeta::new(deposit_reserve_collateral_fees_receiver_pubkey, false),
         AccountMeta::new(borrow_reserve_pubkey, false),
-        AccountMeta::new(borrow_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(borrow_reserve_liquidity_fee_receiver_pubkey, false),
         AccountMeta::new(obligation_pubkey, false),
-        AccountMeta::new(obligation_token_mint_pubkey, false),
-        AccountMeta::new(obligation_token_output_pubkey, false),
         AccountMeta::new_readonly(lending_market_pubkey, false),
         AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-        AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-        AccountMeta::new_readonly(dex_market_pubkey, false),
-        AccountMeta::new_readonly(dex_market_order_book_side_pubkey, false),
-        AccountMeta::new_readonly(memory_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
     ];
-    if let Some(deposit_reserve_collateral_host_pubkey) = deposit_reserve_collateral_host_pubkey {
-        accounts.push(AccountMeta::new(
-            deposit_reserve_collateral_host_pubkey,
-            false,
-        ));
+    if let Some(host_fee_receiver_pubkey) = host_fee_receiver_pubkey {
+        accounts.push(AccountMeta::new(host_fee_receiver_pubkey, false));
     }
     Instruction {
         program_id,
         accounts,
-        data: LendingInstruction::BorrowReserveLiquidity {
-            amount,
-            amount_type,
-        }
-        .pack(),
+        data: LendingInstruction::BorrowObligationLiquidity { liquidity_amount }.pack(),
     }
 }
 
-/// Creates a `RepayReserveLiquidity` instruction
+/// Creates a `RepayObligationLiquidity` instruction
 #[allow(clippy::too_many_arguments)]
-pub fn repay_reserve_liquidity(
+pub fn repay_obligation_liquidity(
     program_id: Pubkey,
     liquidity_amount: u64,
     source_liquidity_pubkey: Pubkey,
-    destination_collateral_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
     repay_reserve_pubkey: Pubkey,
-    repay_reserve_liquidity_supply_pubkey: Pubkey,
-    withdraw_reserve_pubkey: Pubkey,
-    withdraw_reserve_collateral_supply_pubkey: Pubkey,
     obligation_pubkey: Pubkey,
-    obligation_mint_pubkey: Pubkey,
-    obligation_output_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    lending_market_authority_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
 ) -> Instruction {
     Instruction {
         program_id,
         accounts: vec![
             AccountMeta::new(source_liquidity_pubkey, false),
-            AccountMeta::new(destination_collateral_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
             AccountMeta::new(repay_reserve_pubkey, false),
-            AccountMeta::new(repay_reserve_liquidity_supply_pubkey, false),
-            AccountMeta::new_readonly(withdraw_reserve_pubkey, false),
-            AccountMeta::new(withdraw_reserve_collateral_supply_pubkey, false),
             AccountMeta::new(obligation_pubkey, false),
-            AccountMeta::new(obligation_mint_pubkey, false),
-            AccountMeta::new(obligation_output_pubkey, false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
             AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
             AccountMeta::new_readonly(sysvar::clock::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
-        data: LendingInstruction::RepayReserveLiquidity { liquidity_amount }.pack(),
+        data: LendingInstruction::RepayObligationLiquidity { liquidity_amount }.pack(),
     }
 }
 
@@ -781,9 +991,6 @@ pub fn liquidate_obligation(
     lending_market_pubkey: Pubkey,
     lending_market_authority_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
-    dex_market_pubkey: Pubkey,
-    dex_market_order_book_side_pubkey: Pubkey,
-    memory_pubkey: Pubkey,
 ) -> Instruction {
     Instruction {
         program_id,
@@ -798,9 +1005,6 @@ pub fn liquidate_obligation(
             AccountMeta::new_readonly(lending_market_pubkey, false),
             AccountMeta::new_readonly(lending_market_authority_pubkey, false),
             AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(dex_market_pubkey, false),
-            AccountMeta::new_readonly(dex_market_order_book_side_pubkey, false),
-            AccountMeta::new_readonly(memory_pubkey, false),
             AccountMeta::new_readonly(sysvar::clock::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
@@ -808,18 +1012,65 @@ pub fn liquidate_obligation(
     }
 }
 
-/// Creates an `AccrueReserveInterest` instruction
-pub fn accrue_reserve_interest(program_id: Pubkey, reserve_pubkeys: Vec<Pubkey>) -> Instruction {
-    let mut accounts = vec![AccountMeta::new_readonly(sysvar::clock::id(), false)];
-    accounts.extend(
-        reserve_pubkeys
-            .into_iter()
-            .map(|reserve_pubkey| AccountMeta::new(reserve_pubkey, false)),
-    );
+/// Accounts a reserve oracle is read from when the reserve is refreshed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RefreshReserveOracle {
+    /// Quote currency reserves do not use an oracle
+    QuoteCurrency,
+    /// Serum dex market, its bids account and temporary memory
+    DexMarket {
+        /// Dex market account
+        dex_market: Pubkey,
+        /// Dex market bids order book side
+        dex_market_bids: Pubkey,
+        /// Temporary memory owned by the lending program
+        memory: Pubkey,
+    },
+    /// Push oracle feed account
+    PushOracle {
+        /// Push oracle feed account
+        feed: Pubkey,
+    },
+    /// Token swap account
+    TokenSwap {
+        /// Token swap account
+        swap: Pubkey,
+    },
+}
+
+/// Creates a `RefreshReserve` instruction. Reserves that are not the quote
+/// currency must provide the accounts of their oracle.
+pub fn refresh_reserve(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    oracle: RefreshReserveOracle,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    match oracle {
+        RefreshReserveOracle::QuoteCurrency => {}
+        RefreshReserveOracle::DexMarket {
+            dex_market,
+            dex_market_bids,
+            memory,
+        } => {
+            accounts.push(AccountMeta::new_readonly(dex_market, false));
+            accounts.push(AccountMeta::new_readonly(dex_market_bids, false));
+            accounts.push(AccountMeta::new(memory, false));
+        }
+        RefreshReserveOracle::PushOracle { feed } => {
+            accounts.push(AccountMeta::new_readonly(feed, false));
+        }
+        RefreshReserveOracle::TokenSwap { swap } => {
+            accounts.push(AccountMeta::new_readonly(swap, false));
+        }
+    }
     Instruction {
         program_id,
         accounts,
-        data: LendingInstruction::AccrueReserveInterest.pack(),
+        data: LendingInstruction::RefreshReserve.pack(),
     }
 }
 
@@ -832,10 +1083,8 @@ pub fn deposit_obligation_collateral(
     destination_collateral_pubkey: Pubkey,
     deposit_reserve_pubkey: Pubkey,
     obligation_pubkey: Pubkey,
-    obligation_mint_pubkey: Pubkey,
-    obligation_output_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    lending_market_authority_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
 ) -> Instruction {
     Instruction {
@@ -845,10 +1094,8 @@ pub fn deposit_obligation_collateral(
             AccountMeta::new(destination_collateral_pubkey, false),
             AccountMeta::new_readonly(deposit_reserve_pubkey, false),
             AccountMeta::new(obligation_pubkey, false),
-            AccountMeta::new(obligation_mint_pubkey, false),
-            AccountMeta::new(obligation_output_pubkey, false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
             AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
@@ -864,16 +1111,10 @@ pub fn withdraw_obligation_collateral(
     source_collateral_pubkey: Pubkey,
     destination_collateral_pubkey: Pubkey,
     withdraw_reserve_pubkey: Pubkey,
-    borrow_reserve_pubkey: Pubkey,
     obligation_pubkey: Pubkey,
-    obligation_mint_pubkey: Pubkey,
-    obligation_input_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     lending_market_authority_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
-    dex_market_pubkey: Pubkey,
-    dex_market_order_book_side_pubkey: Pubkey,
-    memory_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
 ) -> Instruction {
     Instruction {
         program_id,
@@ -881,16 +1122,10 @@ pub fn withdraw_obligation_collateral(
             AccountMeta::new(source_collateral_pubkey, false),
             AccountMeta::new(destination_collateral_pubkey, false),
             AccountMeta::new_readonly(withdraw_reserve_pubkey, false),
-            AccountMeta::new_readonly(borrow_reserve_pubkey, false),
             AccountMeta::new(obligation_pubkey, false),
-            AccountMeta::new(obligation_mint_pubkey, false),
-            AccountMeta::new(obligation_input_pubkey, false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
             AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(dex_market_pubkey, false),
-            AccountMeta::new_readonly(dex_market_order_book_side_pubkey, false),
-            AccountMeta::new_readonly(memory_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
             AccountMeta::new_readonly(sysvar::clock::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
@@ -914,3 +1149,246 @@ pub fn set_lending_market_owner(
         data: LendingInstruction::SetLendingMarketOwner { new_owner }.pack(),
     }
 }
+
+/// Creates a 'RefreshObligation' instruction.
+pub fn refresh_obligation(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    reserve_pubkeys: Vec<Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    accounts.extend(
+        reserve_pubkeys
+            .into_iter()
+            .map(|reserve_pubkey| AccountMeta::new_readonly(reserve_pubkey, false)),
+    );
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::RefreshObligation.pack(),
+    }
+}
+
+/// Creates a 'FlashLoan' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn flash_loan(
+    program_id: Pubkey,
+    amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    flash_loan_receiver_program_id: Pubkey,
+    flash_loan_receiver_program_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) =
+        Pubkey::find_program_address(&[&lending_market_pubkey.to_bytes()[..32]], &program_id);
+    let mut accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
+        AccountMeta::new(host_fee_receiver_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(flash_loan_receiver_program_id, false),
+    ];
+    accounts.extend(flash_loan_receiver_program_accounts);
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::FlashLoan { amount }.pack(),
+    }
+}
+
+/// Creates an 'UpdateReserveConfig' instruction.
+pub fn update_reserve_config(
+    program_id: Pubkey,
+    config: ReserveConfig,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::UpdateReserveConfig { config }.pack(),
+    }
+}
+
+/// Creates a 'WithdrawProtocolFees' instruction.
+pub fn withdraw_protocol_fees(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) =
+        Pubkey::find_program_address(&[&lending_market_pubkey.to_bytes()[..32]], &program_id);
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::WithdrawProtocolFees { liquidity_amount }.pack(),
+    }
+}
+
+/// Creates an 'InitDepositPosition' instruction.
+pub fn init_deposit_position(
+    program_id: Pubkey,
+    deposit_position_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    deposit_position_owner_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(deposit_position_pubkey, false),
+            AccountMeta::new_readonly(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(deposit_position_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data: LendingInstruction::InitDepositPosition.pack(),
+    }
+}
+
+/// Creates a 'DepositPositionLiquidity' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_position_liquidity(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    deposit_position_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    reserve_collateral_supply_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    deposit_position_owner_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) =
+        Pubkey::find_program_address(&[&lending_market_pubkey.to_bytes()[..32]], &program_id);
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_liquidity_pubkey, false),
+            AccountMeta::new(deposit_position_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(reserve_collateral_mint_pubkey, false),
+            AccountMeta::new(reserve_collateral_supply_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(deposit_position_owner_pubkey, true),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::DepositPositionLiquidity { liquidity_amount }.pack(),
+    }
+}
+
+/// Creates a 'WithdrawPositionLiquidity' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_position_liquidity(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    deposit_position_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    reserve_collateral_supply_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    deposit_position_owner_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) =
+        Pubkey::find_program_address(&[&lending_market_pubkey.to_bytes()[..32]], &program_id);
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(deposit_position_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_collateral_mint_pubkey, false),
+            AccountMeta::new(reserve_collateral_supply_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(deposit_position_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::WithdrawPositionLiquidity { collateral_amount }.pack(),
+    }
+}
+
+/// Creates a 'DepositPositionCollateral' instruction.
+pub fn deposit_position_collateral(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    deposit_position_pubkey: Pubkey,
+    deposit_reserve_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    owner_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(deposit_position_pubkey, false),
+            AccountMeta::new_readonly(deposit_reserve_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(owner_pubkey, true),
+        ],
+        data: LendingInstruction::DepositPositionCollateral { collateral_amount }.pack(),
+    }
+}
+
+/// Creates a 'WithdrawPositionCollateral' instruction.
+pub fn withdraw_position_collateral(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    deposit_position_pubkey: Pubkey,
+    withdraw_reserve_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    owner_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(deposit_position_pubkey, false),
+            AccountMeta::new_readonly(withdraw_reserve_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data: LendingInstruction::WithdrawPositionCollateral { collateral_amount }.pack(),
+    }
+}
@@ -7,6 +7,7 @@ pub mod entrypoint;
 pub mod error;
 pub mod instruction;
 pub mod math;
+pub mod oracle;
 pub mod processor;
 pub mod state;
 
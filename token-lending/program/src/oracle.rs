@@ -0,0 +1,501 @@
+//! Price oracles used to value reserve liquidity
+
+use crate::{
+    dex_market::TradeSimulator,
+    error::LendingError,
+    math::{Decimal, TryDiv, TryMul},
+    state::TokenConverter,
+};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Slot,
+    msg,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::Pubkey,
+};
+use spl_token_swap::{
+    oracle::PriceAccumulator,
+    state::{SwapState, SwapVersion},
+};
+
+/// Source of the market price of reserve liquidity
+pub trait PriceOracle {
+    /// Return the market price of the smallest unit of reserve liquidity in
+    /// lending market quote currency
+    fn market_price(&mut self) -> Result<Decimal, ProgramError>;
+}
+
+/// Prices reserve liquidity at the best order of a dex market order book side
+pub struct DexMarketOracle<'a> {
+    trade_simulator: TradeSimulator<'a>,
+    liquidity_mint: &'a Pubkey,
+}
+
+impl<'a> DexMarketOracle<'a> {
+    /// Create a new DexMarketOracle
+    pub fn new(
+        dex_market_info: &AccountInfo,
+        dex_market_orders_info: &AccountInfo,
+        memory: &'a AccountInfo,
+        quote_token_mint: &'a Pubkey,
+        liquidity_mint: &'a Pubkey,
+    ) -> Result<Self, ProgramError> {
+        let trade_simulator = TradeSimulator::new(
+            dex_market_info,
+            dex_market_orders_info,
+            memory,
+            quote_token_mint,
+            quote_token_mint,
+            liquidity_mint,
+        )?;
+
+        Ok(Self {
+            trade_simulator,
+            liquidity_mint,
+        })
+    }
+}
+
+impl PriceOracle for DexMarketOracle<'_> {
+    fn market_price(&mut self) -> Result<Decimal, ProgramError> {
+        self.trade_simulator.best_price(self.liquidity_mint)
+    }
+}
+
+/// Prices reserve liquidity from an aggregated push oracle feed, rejecting
+/// prices that are stale or too uncertain
+pub struct PushOracle {
+    feed: PushOracleFeed,
+    current_slot: Slot,
+    max_staleness_slots: u64,
+    max_confidence_bps: u16,
+}
+
+impl PushOracle {
+    /// Create a new PushOracle
+    pub fn new(
+        feed_info: &AccountInfo,
+        current_slot: Slot,
+        max_staleness_slots: u64,
+        max_confidence_bps: u16,
+    ) -> Result<Self, ProgramError> {
+        let feed = unpack_push_oracle_feed(feed_info)?;
+
+        Ok(Self {
+            feed,
+            current_slot,
+            max_staleness_slots,
+            max_confidence_bps,
+        })
+    }
+}
+
+impl PriceOracle for PushOracle {
+    fn market_price(&mut self) -> Result<Decimal, ProgramError> {
+        let slots_elapsed = self.current_slot.saturating_sub(self.feed.publish_slot);
+        if slots_elapsed > self.max_staleness_slots {
+            msg!(
+                "Push oracle feed was last published {} slots ago",
+                slots_elapsed
+            );
+            return Err(LendingError::OraclePriceStale.into());
+        }
+
+        if self.feed.price == 0 {
+            msg!("Push oracle feed price is zero");
+            return Err(LendingError::InvalidOracle.into());
+        }
+
+        let max_confidence = self.feed.price as u128 * self.max_confidence_bps as u128;
+        if self.feed.confidence as u128 * BPS_SCALER as u128 > max_confidence {
+            return Err(LendingError::OraclePriceUncertain.into());
+        }
+
+        self.feed.scaled_price()
+    }
+}
+
+const BPS_SCALER: u64 = 10_000;
+
+/// Largest absolute exponent a push oracle feed price can be scaled by
+pub const MAX_PUSH_ORACLE_EXPONENT: u8 = 18;
+
+/// Aggregated push oracle feed account. Publishers push prices to an
+/// aggregator off-chain, which writes the aggregated price and a confidence
+/// interval around it to the feed.
+///
+/// Prices are for the smallest unit of reserve liquidity in the smallest unit
+/// of lending market quote currency, scaled by `10^exponent`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PushOracleFeed {
+    /// Version of the feed
+    pub version: u8,
+    /// Slot the aggregated price was published in
+    pub publish_slot: Slot,
+    /// Aggregated price
+    pub price: u64,
+    /// Confidence interval around the aggregated price
+    pub confidence: u64,
+    /// Power of ten that the price and confidence are scaled by
+    pub exponent: i8,
+}
+
+impl PushOracleFeed {
+    /// Return the feed price with its exponent applied
+    pub fn scaled_price(&self) -> Result<Decimal, ProgramError> {
+        let exponent = (self.exponent as i16).abs() as u32;
+        if exponent > MAX_PUSH_ORACLE_EXPONENT as u32 {
+            msg!("Push oracle feed exponent is out of range");
+            return Err(LendingError::InvalidOracle.into());
+        }
+
+        let price = Decimal::from(self.price);
+        let scale = 10u64.pow(exponent);
+        if self.exponent < 0 {
+            price.try_div(scale)
+        } else {
+            price.try_mul(scale)
+        }
+    }
+}
+
+impl Sealed for PushOracleFeed {}
+impl IsInitialized for PushOracleFeed {
+    fn is_initialized(&self) -> bool {
+        self.version != 0
+    }
+}
+
+const PUSH_ORACLE_FEED_LEN: usize = 26;
+impl Pack for PushOracleFeed {
+    const LEN: usize = 26;
+
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, PUSH_ORACLE_FEED_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, publish_slot, price, confidence, exponent) =
+            array_refs![input, 1, 8, 8, 8, 1];
+        Ok(Self {
+            version: u8::from_le_bytes(*version),
+            publish_slot: u64::from_le_bytes(*publish_slot),
+            price: u64::from_le_bytes(*price),
+            confidence: u64::from_le_bytes(*confidence),
+            exponent: i8::from_le_bytes(*exponent),
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, PUSH_ORACLE_FEED_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, publish_slot, price, confidence, exponent) =
+            mut_array_refs![output, 1, 8, 8, 8, 1];
+        *version = self.version.to_le_bytes();
+        *publish_slot = self.publish_slot.to_le_bytes();
+        *price = self.price.to_le_bytes();
+        *confidence = self.confidence.to_le_bytes();
+        *exponent = self.exponent.to_le_bytes();
+    }
+}
+
+/// Prices reserve liquidity at the time-weighted average price of a token swap
+/// pool, taken from the cumulative prices the swap program records before each
+/// trade. The average covers the period since the observation stored in the
+/// reserve, which only moves forward once at least `window_slots` slots of
+/// prices have been accumulated, so the price cannot be moved within a single
+/// transaction and follows the curve of the pool.
+///
+/// Within a window the previous price is kept, which was averaged up to the
+/// slot of the stored observation. It is rejected as stale once that slot is
+/// more than `max_staleness_slots` old.
+pub struct TokenSwapTwapOracle {
+    current: PriceAccumulator,
+    observation: PriceAccumulator,
+    liquidity_is_token_a: bool,
+    previous_price: Decimal,
+    current_slot: Slot,
+    window_slots: u64,
+    max_staleness_slots: u64,
+}
+
+impl TokenSwapTwapOracle {
+    /// Create a new TokenSwapTwapOracle
+    pub fn new(
+        swap_info: &AccountInfo,
+        liquidity_mint: &Pubkey,
+        observation: PriceAccumulator,
+        previous_price: Decimal,
+        current_slot: Slot,
+        window_slots: u64,
+        max_staleness_slots: u64,
+    ) -> Result<Self, ProgramError> {
+        let swap = unpack_token_swap(swap_info)?;
+        let current = *swap.price_accumulator().ok_or_else(|| {
+            msg!("Token swap pool does not track cumulative prices");
+            LendingError::InvalidOracle
+        })?;
+
+        Ok(Self {
+            current,
+            observation,
+            liquidity_is_token_a: swap.token_a_mint() == liquidity_mint,
+            previous_price,
+            current_slot,
+            window_slots,
+            max_staleness_slots,
+        })
+    }
+
+    /// Cumulative prices the next refresh averages from
+    pub fn observation(&self) -> PriceAccumulator {
+        self.observation
+    }
+}
+
+impl PriceOracle for TokenSwapTwapOracle {
+    fn market_price(&mut self) -> Result<Decimal, ProgramError> {
+        let slots_observed = self
+            .current
+            .last_update_slot
+            .saturating_sub(self.observation.last_update_slot);
+        if slots_observed < self.window_slots {
+            if self.previous_price == Decimal::zero() {
+                msg!("Token swap pool has not accumulated prices over a full window yet");
+                return Err(LendingError::OraclePriceStale.into());
+            }
+
+            let slots_elapsed = self
+                .current_slot
+                .saturating_sub(self.observation.last_update_slot);
+            if slots_elapsed > self.max_staleness_slots {
+                msg!(
+                    "Token swap pool average price was last updated {} slots ago",
+                    slots_elapsed
+                );
+                return Err(LendingError::OraclePriceStale.into());
+            }
+            return Ok(self.previous_price);
+        }
+
+        let average = self
+            .current
+            .time_weighted_average(&self.observation)
+            .ok_or(LendingError::InvalidOracle)?;
+        let fixed_price = if self.liquidity_is_token_a {
+            average.price_a
+        } else {
+            average.price_b
+        };
+        if fixed_price == 0 {
+            msg!("Token swap pool average price is zero");
+            return Err(LendingError::InvalidOracle.into());
+        }
+
+        self.observation = self.current;
+        Decimal::from(fixed_price)
+            .try_div(FIXED_PRICE_HALF_SCALE)?
+            .try_div(FIXED_PRICE_HALF_SCALE)
+    }
+}
+
+/// Square root of the scale of the unsigned Q64.64 token swap prices, which
+/// is too large to divide by in one step
+const FIXED_PRICE_HALF_SCALE: u64 = 1 << 32;
+
+/// Check that a token swap pool exchanges reserve liquidity and lending
+/// market quote currency and tracks cumulative prices, returning its current
+/// cumulative prices as the first observation of the reserve
+pub fn validate_token_swap(
+    swap_info: &AccountInfo,
+    liquidity_mint: &Pubkey,
+    quote_token_mint: &Pubkey,
+) -> Result<PriceAccumulator, ProgramError> {
+    let swap = unpack_token_swap(swap_info)?;
+    let mints = (swap.token_a_mint(), swap.token_b_mint());
+    if mints != (liquidity_mint, quote_token_mint) && mints != (quote_token_mint, liquidity_mint) {
+        msg!("Token swap pool must exchange reserve liquidity and quote currency");
+        return Err(LendingError::InvalidOracle.into());
+    }
+    swap.price_accumulator().copied().ok_or_else(|| {
+        msg!("Token swap pool does not track cumulative prices");
+        LendingError::InvalidOracle.into()
+    })
+}
+
+/// Unpack an initialized push oracle feed
+pub fn unpack_push_oracle_feed(feed_info: &AccountInfo) -> Result<PushOracleFeed, ProgramError> {
+    PushOracleFeed::unpack(&feed_info.data.borrow()).map_err(|_| LendingError::InvalidOracle.into())
+}
+
+fn unpack_token_swap(swap_info: &AccountInfo) -> Result<Box<dyn SwapState>, ProgramError> {
+    if swap_info.owner != &spl_token_swap::id() {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    let swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+    if !swap.is_initialized() {
+        return Err(LendingError::InvalidOracle.into());
+    }
+    Ok(swap)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn push_oracle(feed: PushOracleFeed, current_slot: Slot) -> PushOracle {
+        PushOracle {
+            feed,
+            current_slot,
+            max_staleness_slots: 10,
+            max_confidence_bps: 100,
+        }
+    }
+
+    const ONE_FIXED: u128 = 1 << 64;
+
+    fn accumulator(last_update_slot: Slot, price_a_cumulative: u128) -> PriceAccumulator {
+        PriceAccumulator {
+            last_update_slot,
+            price_a_cumulative,
+            price_b_cumulative: price_a_cumulative / 4,
+        }
+    }
+
+    fn twap_oracle(observation: PriceAccumulator, previous_price: u64) -> TokenSwapTwapOracle {
+        // token A traded at 2 for 100 slots, then at 4 for 100 slots
+        TokenSwapTwapOracle {
+            current: accumulator(200, 100 * 2 * ONE_FIXED + 100 * 4 * ONE_FIXED),
+            observation,
+            liquidity_is_token_a: true,
+            previous_price: Decimal::from(previous_price),
+            current_slot: 210,
+            window_slots: 100,
+            max_staleness_slots: 100,
+        }
+    }
+
+    #[test]
+    fn push_oracle_feed_pack_unpack() {
+        let feed = PushOracleFeed {
+            version: 1,
+            publish_slot: 42,
+            price: 13_988,
+            confidence: 7,
+            exponent: -6,
+        };
+        let mut packed = [0u8; PushOracleFeed::LEN];
+        PushOracleFeed::pack(feed.clone(), &mut packed).unwrap();
+        assert_eq!(PushOracleFeed::unpack(&packed).unwrap(), feed);
+    }
+
+    #[test]
+    fn push_oracle_price() {
+        let feed = PushOracleFeed {
+            version: 1,
+            publish_slot: 100,
+            price: 13_988,
+            confidence: 100,
+            exponent: -6,
+        };
+
+        let mut oracle = push_oracle(feed.clone(), 110);
+        assert_eq!(
+            oracle.market_price().unwrap(),
+            Decimal::from(13_988u64).try_div(1_000_000).unwrap()
+        );
+
+        let mut oracle = push_oracle(feed.clone(), 111);
+        assert_eq!(
+            oracle.market_price().unwrap_err(),
+            LendingError::OraclePriceStale.into()
+        );
+
+        let mut oracle = push_oracle(
+            PushOracleFeed {
+                confidence: 140,
+                ..feed.clone()
+            },
+            110,
+        );
+        assert_eq!(
+            oracle.market_price().unwrap_err(),
+            LendingError::OraclePriceUncertain.into()
+        );
+
+        let mut oracle = push_oracle(PushOracleFeed { price: 0, ..feed }, 110);
+        assert_eq!(
+            oracle.market_price().unwrap_err(),
+            LendingError::InvalidOracle.into()
+        );
+    }
+
+    #[test]
+    fn push_oracle_feed_scaled_price() {
+        let feed = PushOracleFeed {
+            version: 1,
+            price: 5,
+            exponent: 2,
+            ..PushOracleFeed::default()
+        };
+        assert_eq!(feed.scaled_price().unwrap(), Decimal::from(500u64));
+
+        let feed = PushOracleFeed {
+            exponent: MAX_PUSH_ORACLE_EXPONENT as i8 + 1,
+            ..feed
+        };
+        assert_eq!(
+            feed.scaled_price().unwrap_err(),
+            LendingError::InvalidOracle.into()
+        );
+    }
+
+    #[test]
+    fn token_swap_twap_price() {
+        // averages the whole period since the observation
+        let mut oracle = twap_oracle(accumulator(0, 0), 0);
+        assert_eq!(oracle.market_price().unwrap(), Decimal::from(3u64));
+        assert_eq!(oracle.observation(), oracle.current);
+
+        // token B reserves are priced with the cumulative price of token B
+        let mut oracle = twap_oracle(accumulator(0, 0), 0);
+        oracle.liquidity_is_token_a = false;
+        assert_eq!(
+            oracle.market_price().unwrap(),
+            Decimal::from_scaled_val(750_000_000_000_000_000)
+        );
+
+        // only the period since the observation counts
+        let mut oracle = twap_oracle(accumulator(100, 100 * 2 * ONE_FIXED), 0);
+        assert_eq!(oracle.market_price().unwrap(), Decimal::from(4u64));
+
+        // cumulative prices wrap around
+        let mut oracle = twap_oracle(accumulator(100, 100 * 2 * ONE_FIXED), 0);
+        oracle.observation.price_a_cumulative = u128::MAX - 99 * 4 * ONE_FIXED;
+        oracle.current.price_a_cumulative = 4 * ONE_FIXED - 1;
+        assert_eq!(oracle.market_price().unwrap(), Decimal::from(4u64));
+    }
+
+    #[test]
+    fn token_swap_twap_price_within_window() {
+        // the previous price is kept until a full window has been accumulated
+        let observation = accumulator(150, 150 * 2 * ONE_FIXED);
+        let mut oracle = twap_oracle(observation, 10);
+        assert_eq!(oracle.market_price().unwrap(), Decimal::from(10u64));
+        assert_eq!(oracle.observation(), observation);
+
+        // and there is no price to keep before the first window
+        let mut oracle = twap_oracle(observation, 0);
+        assert_eq!(
+            oracle.market_price().unwrap_err(),
+            LendingError::OraclePriceStale.into()
+        );
+        // or once it was averaged too long ago
+        let mut oracle = twap_oracle(observation, 10);
+        oracle.current_slot = 251;
+        assert_eq!(
+            oracle.market_price().unwrap_err(),
+            LendingError::OraclePriceStale.into()
+        );
+    }
+}
@@ -1,12 +1,17 @@
 //! Program state processor
 
 use crate::{
-    dex_market::{DexMarket, TradeSimulator, BASE_MINT_OFFSET, QUOTE_MINT_OFFSET},
+    dex_market::{DexMarket, BASE_MINT_OFFSET, QUOTE_MINT_OFFSET},
     error::LendingError,
-    instruction::{BorrowAmountType, LendingInstruction},
-    math::{Decimal, TryAdd, WAD},
+    instruction::LendingInstruction,
+    math::{Decimal, Rate, TryDiv, TryMul, WAD},
+    oracle::{
+        unpack_push_oracle_feed, validate_token_swap, DexMarketOracle, PriceOracle, PushOracle,
+        TokenSwapTwapOracle,
+    },
     state::{
-        LendingMarket, LiquidateResult, NewObligationParams, NewReserveParams, Obligation,
+        BorrowResult, DepositPosition, LendingMarket, LiquidateResult, NewDepositPositionParams,
+        NewObligationParams, NewReserveParams, Obligation, ObligationCollateral, OracleConfig,
         RepayResult, Reserve, ReserveCollateral, ReserveConfig, ReserveLiquidity, PROGRAM_VERSION,
     },
 };
@@ -16,6 +21,7 @@ use solana_program::{
     clock::Slot,
     decode_error::DecodeError,
     entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
     msg,
     program::{invoke, invoke_signed},
     program_error::{PrintProgramError, ProgramError},
@@ -24,7 +30,8 @@ use solana_program::{
     pubkey::Pubkey,
     sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
-use spl_token::state::Account as Token;
+use spl_token_swap::oracle::PriceAccumulator;
+use std::mem::discriminant;
 
 /// Processes an instruction
 pub fn process_instruction(
@@ -57,14 +64,11 @@ pub fn process_instruction(
             msg!("Instruction: Withdraw");
             process_withdraw(program_id, collateral_amount, accounts)
         }
-        LendingInstruction::BorrowReserveLiquidity {
-            amount,
-            amount_type,
-        } => {
+        LendingInstruction::BorrowObligationLiquidity { liquidity_amount } => {
             msg!("Instruction: Borrow");
-            process_borrow(program_id, amount, amount_type, accounts)
+            process_borrow(program_id, liquidity_amount, accounts)
         }
-        LendingInstruction::RepayReserveLiquidity { liquidity_amount } => {
+        LendingInstruction::RepayObligationLiquidity { liquidity_amount } => {
             msg!("Instruction: Repay");
             process_repay(program_id, liquidity_amount, accounts)
         }
@@ -72,9 +76,9 @@ pub fn process_instruction(
             msg!("Instruction: Liquidate");
             process_liquidate(program_id, liquidity_amount, accounts)
         }
-        LendingInstruction::AccrueReserveInterest => {
-            msg!("Instruction: Accrue Interest");
-            process_accrue_interest(program_id, accounts)
+        LendingInstruction::RefreshReserve => {
+            msg!("Instruction: Refresh Reserve");
+            process_refresh_reserve(program_id, accounts)
         }
         LendingInstruction::DepositObligationCollateral { collateral_amount } => {
             msg!("Instruction: Deposit Obligation Collateral");
@@ -88,6 +92,42 @@ pub fn process_instruction(
             msg!("Instruction: Set Lending Market Owner");
             process_set_lending_market_owner(program_id, new_owner, accounts)
         }
+        LendingInstruction::RefreshObligation => {
+            msg!("Instruction: Refresh Obligation");
+            process_refresh_obligation(program_id, accounts)
+        }
+        LendingInstruction::FlashLoan { amount } => {
+            msg!("Instruction: Flash Loan");
+            process_flash_loan(program_id, amount, accounts)
+        }
+        LendingInstruction::UpdateReserveConfig { config } => {
+            msg!("Instruction: Update Reserve Config");
+            process_update_reserve_config(program_id, config, accounts)
+        }
+        LendingInstruction::WithdrawProtocolFees { liquidity_amount } => {
+            msg!("Instruction: Withdraw Protocol Fees");
+            process_withdraw_protocol_fees(program_id, liquidity_amount, accounts)
+        }
+        LendingInstruction::InitDepositPosition => {
+            msg!("Instruction: Init Deposit Position");
+            process_init_deposit_position(program_id, accounts)
+        }
+        LendingInstruction::DepositPositionLiquidity { liquidity_amount } => {
+            msg!("Instruction: Deposit Position Liquidity");
+            process_deposit_position_liquidity(program_id, liquidity_amount, accounts)
+        }
+        LendingInstruction::WithdrawPositionLiquidity { collateral_amount } => {
+            msg!("Instruction: Withdraw Position Liquidity");
+            process_withdraw_position_liquidity(program_id, collateral_amount, accounts)
+        }
+        LendingInstruction::DepositPositionCollateral { collateral_amount } => {
+            msg!("Instruction: Deposit Position Collateral");
+            process_deposit_position_collateral(program_id, collateral_amount, accounts)
+        }
+        LendingInstruction::WithdrawPositionCollateral { collateral_amount } => {
+            msg!("Instruction: Withdraw Position Collateral");
+            process_withdraw_position_collateral(program_id, collateral_amount, accounts)
+        }
     }
 }
 
@@ -133,40 +173,7 @@ fn process_init_reserve(
         msg!("Reserve must be initialized with liquidity");
         return Err(LendingError::InvalidAmount.into());
     }
-    if config.optimal_utilization_rate > 100 {
-        msg!("Optimal utilization rate must be in range [0, 100]");
-        return Err(LendingError::InvalidConfig.into());
-    }
-    if config.loan_to_value_ratio >= 100 {
-        msg!("Loan to value ratio must be in range [0, 100)");
-        return Err(LendingError::InvalidConfig.into());
-    }
-    if config.liquidation_bonus > 100 {
-        msg!("Liquidation bonus must be in range [0, 100]");
-        return Err(LendingError::InvalidConfig.into());
-    }
-    if config.liquidation_threshold <= config.loan_to_value_ratio
-        || config.liquidation_threshold > 100
-    {
-        msg!("Liquidation threshold must be in range (LTV, 100]");
-        return Err(LendingError::InvalidConfig.into());
-    }
-    if config.optimal_borrow_rate < config.min_borrow_rate {
-        msg!("Optimal borrow rate must be >= min borrow rate");
-        return Err(LendingError::InvalidConfig.into());
-    }
-    if config.optimal_borrow_rate > config.max_borrow_rate {
-        msg!("Optimal borrow rate must be <= max borrow rate");
-        return Err(LendingError::InvalidConfig.into());
-    }
-    if config.fees.borrow_fee_wad >= WAD {
-        msg!("Borrow fee must be in range [0, 1_000_000_000_000_000_000)");
-        return Err(LendingError::InvalidConfig.into());
-    }
-    if config.fees.host_fee_percentage > 100 {
-        msg!("Host fee percentage must be in range [0, 100]");
-        return Err(LendingError::InvalidConfig.into());
-    }
+    validate_reserve_config(&config)?;
 
     let account_info_iter = &mut accounts.iter();
     let source_liquidity_info = next_account_info(account_info_iter)?;
@@ -176,7 +183,7 @@ fn process_init_reserve(
     let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
     let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
     let reserve_collateral_supply_info = next_account_info(account_info_iter)?;
-    let reserve_collateral_fees_receiver_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_owner_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
@@ -208,26 +215,53 @@ fn process_init_reserve(
         return Err(LendingError::InvalidSigner.into());
     }
 
-    let dex_market = if reserve_liquidity_mint_info.key != &lending_market.quote_token_mint {
-        let dex_market_info = next_account_info(account_info_iter)?;
-        // TODO: check that market state is owned by real serum dex program
-        if !rent.is_exempt(dex_market_info.lamports(), dex_market_info.data_len()) {
-            return Err(LendingError::NotRentExempt.into());
-        }
+    let is_quote_currency = reserve_liquidity_mint_info.key == &lending_market.quote_token_mint;
+    if is_quote_currency && config.oracle != OracleConfig::QuoteCurrency {
+        msg!("Quote currency reserves must be priced at one");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if !is_quote_currency && config.oracle == OracleConfig::QuoteCurrency {
+        msg!("Reserves that are not the quote currency must use a price oracle");
+        return Err(LendingError::InvalidConfig.into());
+    }
 
-        let dex_market_data = &dex_market_info.data.borrow();
-        let market_quote_mint = DexMarket::pubkey_at_offset(&dex_market_data, QUOTE_MINT_OFFSET);
-        if lending_market.quote_token_mint != market_quote_mint {
-            return Err(LendingError::DexMarketMintMismatch.into());
+    let mut oracle_observation = PriceAccumulator::default();
+    let oracle = match config.oracle {
+        OracleConfig::QuoteCurrency => COption::None,
+        OracleConfig::DexMarket => {
+            let dex_market_info = next_account_info(account_info_iter)?;
+            // TODO: check that market state is owned by real serum dex program
+            if !rent.is_exempt(dex_market_info.lamports(), dex_market_info.data_len()) {
+                return Err(LendingError::NotRentExempt.into());
+            }
+
+            let dex_market_data = &dex_market_info.data.borrow();
+            let market_quote_mint =
+                DexMarket::pubkey_at_offset(&dex_market_data, QUOTE_MINT_OFFSET);
+            if lending_market.quote_token_mint != market_quote_mint {
+                return Err(LendingError::DexMarketMintMismatch.into());
+            }
+            let market_base_mint = DexMarket::pubkey_at_offset(&dex_market_data, BASE_MINT_OFFSET);
+            if reserve_liquidity_mint_info.key != &market_base_mint {
+                return Err(LendingError::DexMarketMintMismatch.into());
+            }
+
+            COption::Some(*dex_market_info.key)
         }
-        let market_base_mint = DexMarket::pubkey_at_offset(&dex_market_data, BASE_MINT_OFFSET);
-        if reserve_liquidity_mint_info.key != &market_base_mint {
-            return Err(LendingError::DexMarketMintMismatch.into());
+        OracleConfig::PushOracle { .. } => {
+            let feed_info = next_account_info(account_info_iter)?;
+            unpack_push_oracle_feed(feed_info)?;
+            COption::Some(*feed_info.key)
+        }
+        OracleConfig::TokenSwapTwap { .. } => {
+            let swap_info = next_account_info(account_info_iter)?;
+            oracle_observation = validate_token_swap(
+                swap_info,
+                reserve_liquidity_mint_info.key,
+                &lending_market.quote_token_mint,
+            )?;
+            COption::Some(*swap_info.key)
         }
-
-        COption::Some(*dex_market_info.key)
-    } else {
-        COption::None
     };
 
     let authority_signer_seeds = &[
@@ -245,24 +279,34 @@ fn process_init_reserve(
         return Err(LendingError::InvalidTokenOwner.into());
     }
 
+    // Quote currency reserves are priced at one, other reserves are priced
+    // from their oracle when refreshed
+    let market_price = if is_quote_currency {
+        Decimal::one()
+    } else {
+        Decimal::zero()
+    };
     let reserve_liquidity_info = ReserveLiquidity::new(
         *reserve_liquidity_mint_info.key,
         reserve_liquidity_mint.decimals,
         *reserve_liquidity_supply_info.key,
+        *reserve_liquidity_fee_receiver_info.key,
+        market_price,
     );
     let reserve_collateral_info = ReserveCollateral::new(
         *reserve_collateral_mint_info.key,
         *reserve_collateral_supply_info.key,
-        *reserve_collateral_fees_receiver_info.key,
     );
     let mut reserve = Reserve::new(NewReserveParams {
         current_slot: clock.slot,
         lending_market: *lending_market_info.key,
         collateral: reserve_collateral_info,
         liquidity: reserve_liquidity_info,
-        dex_market,
+        oracle,
         config,
     });
+    reserve.oracle_observation = oracle_observation;
+    reserve.check_supply_cap(liquidity_amount)?;
     let collateral_amount = reserve.deposit_liquidity(liquidity_amount)?;
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
@@ -291,8 +335,8 @@ fn process_init_reserve(
     })?;
 
     spl_token_init_account(TokenInitializeAccountParams {
-        account: reserve_collateral_fees_receiver_info.clone(),
-        mint: reserve_collateral_mint_info.clone(),
+        account: reserve_liquidity_fee_receiver_info.clone(),
+        mint: reserve_liquidity_mint_info.clone(),
         owner: lending_market_owner_info.clone(),
         rent: rent_info.clone(),
         token_program: token_program_id.clone(),
@@ -330,96 +374,34 @@ fn process_init_reserve(
 #[inline(never)] // avoid stack frame limit
 fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    let deposit_reserve_info = next_account_info(account_info_iter)?;
-    let borrow_reserve_info = next_account_info(account_info_iter)?;
     let obligation_info = next_account_info(account_info_iter)?;
-    let obligation_token_mint_info = next_account_info(account_info_iter)?;
-    let obligation_token_output_info = next_account_info(account_info_iter)?;
-    let obligation_token_owner_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
-    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
-    let rent_info = next_account_info(account_info_iter)?;
-    let rent = &Rent::from_account_info(rent_info)?;
-    let token_program_id = next_account_info(account_info_iter)?;
-
-    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
-    if lending_market_info.owner != program_id {
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-    if &lending_market.token_program_id != token_program_id.key {
-        return Err(LendingError::InvalidTokenProgram.into());
-    }
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
 
-    let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
-    if deposit_reserve_info.owner != program_id {
+    assert_rent_exempt(rent, obligation_info)?;
+    assert_uninitialized::<Obligation>(obligation_info)?;
+    if obligation_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if &deposit_reserve.lending_market != lending_market_info.key {
-        msg!("Invalid reserve lending market account");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
 
-    let borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
-    if borrow_reserve_info.owner != program_id {
+    LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if borrow_reserve.lending_market != deposit_reserve.lending_market {
-        return Err(LendingError::LendingMarketMismatch.into());
-    }
 
-    if deposit_reserve.config.loan_to_value_ratio == 0 {
-        return Err(LendingError::ReserveCollateralDisabled.into());
-    }
-    if deposit_reserve_info.key == borrow_reserve_info.key {
-        return Err(LendingError::DuplicateReserve.into());
-    }
-    if deposit_reserve.liquidity.mint_pubkey == borrow_reserve.liquidity.mint_pubkey {
-        return Err(LendingError::DuplicateReserveMint.into());
+    if !obligation_owner_info.is_signer {
+        return Err(LendingError::InvalidSigner.into());
     }
 
-    assert_rent_exempt(rent, obligation_info)?;
-    assert_uninitialized::<Obligation>(obligation_info)?;
-    assert_last_update_slot(&borrow_reserve, clock.slot)?;
-
-    let cumulative_borrow_rate = borrow_reserve.cumulative_borrow_rate_wads;
-    let obligation_mint_decimals = deposit_reserve.liquidity.mint_decimals;
     let obligation = Obligation::new(NewObligationParams {
-        collateral_reserve: *deposit_reserve_info.key,
-        cumulative_borrow_rate_wads: cumulative_borrow_rate,
-        borrow_reserve: *borrow_reserve_info.key,
-        token_mint: *obligation_token_mint_info.key,
+        current_slot: clock.slot,
+        lending_market: *lending_market_info.key,
+        owner: *obligation_owner_info.key,
     });
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
-    let authority_signer_seeds = &[
-        lending_market_info.key.as_ref(),
-        &[lending_market.bump_seed],
-    ];
-    let lending_market_authority_pubkey =
-        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
-    if lending_market_authority_info.key != &lending_market_authority_pubkey {
-        return Err(LendingError::InvalidMarketAuthority.into());
-    }
-
-    // init obligation token mint
-    spl_token_init_mint(TokenInitializeMintParams {
-        mint: obligation_token_mint_info.clone(),
-        authority: lending_market_authority_info.key,
-        rent: rent_info.clone(),
-        decimals: obligation_mint_decimals,
-        token_program: token_program_id.clone(),
-    })?;
-
-    // init obligation token output account
-    spl_token_init_account(TokenInitializeAccountParams {
-        account: obligation_token_output_info.clone(),
-        mint: obligation_token_mint_info.clone(),
-        owner: obligation_token_owner_info.clone(),
-        rent: rent_info.clone(),
-        token_program: token_program_id.clone(),
-    })?;
-
     Ok(())
 }
 
@@ -460,6 +442,10 @@ fn process_deposit(
         msg!("Invalid reserve lending market account");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    if reserve.config.deposits_disabled {
+        msg!("Reserve deposits are disabled");
+        return Err(LendingError::ReserveDepositsDisabled.into());
+    }
     if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
         msg!("Invalid reserve liquidity supply account");
         return Err(LendingError::InvalidAccountInput.into());
@@ -478,6 +464,7 @@ fn process_deposit(
     }
 
     assert_last_update_slot(&reserve, clock.slot)?;
+    reserve.check_supply_cap(liquidity_amount)?;
     let collateral_amount = reserve.deposit_liquidity(liquidity_amount)?;
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
@@ -604,39 +591,25 @@ fn process_withdraw(
 #[inline(never)] // avoid stack frame limit
 fn process_borrow(
     program_id: &Pubkey,
-    token_amount: u64,
-    token_amount_type: BorrowAmountType,
+    liquidity_amount: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
-    if token_amount == 0 {
+    if liquidity_amount == 0 {
         return Err(LendingError::InvalidAmount.into());
     }
 
     let account_info_iter = &mut accounts.iter();
-    let source_collateral_info = next_account_info(account_info_iter)?;
+    let source_liquidity_info = next_account_info(account_info_iter)?;
     let destination_liquidity_info = next_account_info(account_info_iter)?;
-    let deposit_reserve_info = next_account_info(account_info_iter)?;
-    let deposit_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
-    let deposit_reserve_collateral_fees_receiver_info = next_account_info(account_info_iter)?;
     let borrow_reserve_info = next_account_info(account_info_iter)?;
-    let borrow_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
     let obligation_info = next_account_info(account_info_iter)?;
-    let obligation_token_mint_info = next_account_info(account_info_iter)?;
-    let obligation_token_output_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
-    let user_transfer_authority_info = next_account_info(account_info_iter)?;
-    let dex_market_info = next_account_info(account_info_iter)?;
-    let dex_market_orders_info = next_account_info(account_info_iter)?;
-    let memory = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
-    // Ensure memory is owned by this program so that we don't have to zero it out
-    if memory.owner != program_id {
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
@@ -645,128 +618,83 @@ fn process_borrow(
         return Err(LendingError::InvalidTokenProgram.into());
     }
 
-    let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
-    if deposit_reserve_info.owner != program_id {
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-    if &deposit_reserve.lending_market != lending_market_info.key {
-        msg!("Invalid reserve lending market account");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-
     let mut borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
     if borrow_reserve_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if borrow_reserve.lending_market != deposit_reserve.lending_market {
-        return Err(LendingError::LendingMarketMismatch.into());
-    }
-
-    if deposit_reserve.config.loan_to_value_ratio == 0 {
-        return Err(LendingError::ReserveCollateralDisabled.into());
-    }
-    if deposit_reserve_info.key == borrow_reserve_info.key {
-        return Err(LendingError::DuplicateReserve.into());
-    }
-    if deposit_reserve.liquidity.mint_pubkey == borrow_reserve.liquidity.mint_pubkey {
-        return Err(LendingError::DuplicateReserveMint.into());
-    }
-    if &borrow_reserve.liquidity.supply_pubkey != borrow_reserve_liquidity_supply_info.key {
-        msg!("Invalid borrow reserve liquidity supply account input");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &deposit_reserve.collateral.supply_pubkey != deposit_reserve_collateral_supply_info.key {
-        msg!("Invalid deposit reserve collateral supply account input");
+    if &borrow_reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &deposit_reserve.collateral.supply_pubkey == source_collateral_info.key {
-        msg!("Cannot use deposit reserve collateral supply as source account input");
-        return Err(LendingError::InvalidAccountInput.into());
+    if borrow_reserve.config.borrows_disabled {
+        msg!("Reserve borrows are disabled");
+        return Err(LendingError::ReserveBorrowsDisabled.into());
     }
-    if &deposit_reserve.collateral.fees_receiver
-        != deposit_reserve_collateral_fees_receiver_info.key
-    {
-        msg!("Invalid deposit reserve collateral fees receiver account");
+    if &borrow_reserve.liquidity.supply_pubkey != source_liquidity_info.key {
+        msg!("Invalid borrow reserve liquidity supply account input");
         return Err(LendingError::InvalidAccountInput.into());
     }
     if &borrow_reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
         msg!("Cannot use borrow reserve liquidity supply as destination account input");
         return Err(LendingError::InvalidAccountInput.into());
     }
-
-    // TODO: handle case when neither reserve is the quote currency
-    if borrow_reserve.dex_market.is_none() && deposit_reserve.dex_market.is_none() {
-        msg!("One reserve must have a dex market");
+    if &borrow_reserve.liquidity.fee_receiver != borrow_reserve_liquidity_fee_receiver_info.key {
+        msg!("Invalid borrow reserve liquidity fee receiver account");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if let COption::Some(dex_market_pubkey) = borrow_reserve.dex_market {
-        if &dex_market_pubkey != dex_market_info.key {
-            msg!("Invalid dex market account input");
-            return Err(LendingError::InvalidAccountInput.into());
-        }
-    }
-    if let COption::Some(dex_market_pubkey) = deposit_reserve.dex_market {
-        if &dex_market_pubkey != dex_market_info.key {
-            msg!("Invalid dex market account input");
-            return Err(LendingError::InvalidAccountInput.into());
-        }
+    if borrow_reserve.last_update_slot != clock.slot {
+        msg!("Borrow reserve must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
     }
 
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
     if obligation_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if &obligation.borrow_reserve != borrow_reserve_info.key {
-        msg!("Borrow reserve input doesn't match existing obligation borrow reserve");
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Invalid obligation lending market account");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &obligation.collateral_reserve != deposit_reserve_info.key {
-        msg!("Collateral reserve input doesn't match existing obligation collateral reserve");
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Invalid obligation owner account");
         return Err(LendingError::InvalidAccountInput.into());
     }
-
-    unpack_mint(&obligation_token_mint_info.data.borrow())?;
-    if &obligation.token_mint != obligation_token_mint_info.key {
-        msg!("Obligation token mint input doesn't match existing obligation token mint");
-        return Err(LendingError::InvalidTokenMint.into());
+    if !obligation_owner_info.is_signer {
+        return Err(LendingError::InvalidSigner.into());
     }
-
-    let obligation_token_output = Token::unpack(&obligation_token_output_info.data.borrow())?;
-    if obligation_token_output_info.owner != token_program_id.key {
-        return Err(LendingError::InvalidTokenOwner.into());
+    if obligation.is_stale(clock.slot) {
+        msg!("Obligation must be refreshed in the current slot");
+        return Err(LendingError::ObligationStale.into());
     }
-    if &obligation_token_output.mint != obligation_token_mint_info.key {
-        return Err(LendingError::InvalidTokenMint.into());
+    if obligation.deposits.is_empty() {
+        return Err(LendingError::ObligationDepositsEmpty.into());
+    }
+    if obligation.deposited_value == Decimal::zero() {
+        return Err(LendingError::ObligationDepositsZero.into());
     }
 
-    assert_last_update_slot(&borrow_reserve, clock.slot)?;
-    assert_last_update_slot(&deposit_reserve, clock.slot)?;
-    obligation.accrue_interest(borrow_reserve.cumulative_borrow_rate_wads)?;
-
-    let trade_simulator = TradeSimulator::new(
-        dex_market_info,
-        dex_market_orders_info,
-        memory,
-        &lending_market.quote_token_mint,
-        &borrow_reserve.liquidity.mint_pubkey,
-        &deposit_reserve.liquidity.mint_pubkey,
-    )?;
-
-    let loan = deposit_reserve.create_loan(
-        token_amount,
-        token_amount_type,
-        trade_simulator,
-        &borrow_reserve.liquidity.mint_pubkey,
-    )?;
+    let BorrowResult {
+        borrow_amount,
+        receive_amount,
+        borrow_fee,
+        host_fee,
+    } = borrow_reserve.calculate_borrow(liquidity_amount, obligation.remaining_borrow_value()?)?;
+    if receive_amount == 0 {
+        return Err(LendingError::BorrowTooSmall.into());
+    }
 
-    borrow_reserve.liquidity.borrow(loan.borrow_amount)?;
-    obligation.borrowed_liquidity_wads = obligation
-        .borrowed_liquidity_wads
-        .try_add(Decimal::from(loan.borrow_amount))?;
-    obligation.deposited_collateral_tokens += loan.collateral_amount;
+    borrow_reserve.check_borrow_cap(liquidity_amount)?;
+    borrow_reserve.liquidity.borrow(liquidity_amount)?;
+    obligation
+        .find_or_add_liquidity_to_borrows(
+            *borrow_reserve_info.key,
+            borrow_reserve.cumulative_borrow_rate_wads,
+        )?
+        .borrow(borrow_amount)?;
+    obligation.mark_stale();
 
-    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
     Reserve::pack(borrow_reserve, &mut borrow_reserve_info.data.borrow_mut())?;
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
@@ -778,27 +706,17 @@ fn process_borrow(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    // deposit collateral
-    spl_token_transfer(TokenTransferParams {
-        source: source_collateral_info.clone(),
-        destination: deposit_reserve_collateral_supply_info.clone(),
-        amount: loan.collateral_amount,
-        authority: user_transfer_authority_info.clone(),
-        authority_signer_seeds: &[],
-        token_program: token_program_id.clone(),
-    })?;
-
     // transfer host fees if host is specified
-    let mut owner_fee = loan.origination_fee;
-    if let Ok(host_fee_recipient) = next_account_info(account_info_iter) {
-        if loan.host_fee > 0 {
-            owner_fee -= loan.host_fee;
+    let mut owner_fee = borrow_fee;
+    if let Ok(host_fee_receiver_info) = next_account_info(account_info_iter) {
+        if host_fee > 0 {
+            owner_fee -= host_fee;
             spl_token_transfer(TokenTransferParams {
-                source: source_collateral_info.clone(),
-                destination: host_fee_recipient.clone(),
-                amount: loan.host_fee,
-                authority: user_transfer_authority_info.clone(),
-                authority_signer_seeds: &[],
+                source: source_liquidity_info.clone(),
+                destination: host_fee_receiver_info.clone(),
+                amount: host_fee,
+                authority: lending_market_authority_info.clone(),
+                authority_signer_seeds,
                 token_program: token_program_id.clone(),
             })?;
         }
@@ -807,30 +725,20 @@ fn process_borrow(
     // transfer remaining fees to owner
     if owner_fee > 0 {
         spl_token_transfer(TokenTransferParams {
-            source: source_collateral_info.clone(),
-            destination: deposit_reserve_collateral_fees_receiver_info.clone(),
+            source: source_liquidity_info.clone(),
+            destination: borrow_reserve_liquidity_fee_receiver_info.clone(),
             amount: owner_fee,
-            authority: user_transfer_authority_info.clone(),
-            authority_signer_seeds: &[],
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
             token_program: token_program_id.clone(),
         })?;
     }
 
     // borrow liquidity
     spl_token_transfer(TokenTransferParams {
-        source: borrow_reserve_liquidity_supply_info.clone(),
+        source: source_liquidity_info.clone(),
         destination: destination_liquidity_info.clone(),
-        amount: loan.borrow_amount,
-        authority: lending_market_authority_info.clone(),
-        authority_signer_seeds,
-        token_program: token_program_id.clone(),
-    })?;
-
-    // mint obligation tokens to output account
-    spl_token_mint_to(TokenMintToParams {
-        mint: obligation_token_mint_info.clone(),
-        destination: obligation_token_output_info.clone(),
-        amount: loan.collateral_amount,
+        amount: receive_amount,
         authority: lending_market_authority_info.clone(),
         authority_signer_seeds,
         token_program: token_program_id.clone(),
@@ -851,16 +759,10 @@ fn process_repay(
 
     let account_info_iter = &mut accounts.iter();
     let source_liquidity_info = next_account_info(account_info_iter)?;
-    let destination_collateral_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
     let repay_reserve_info = next_account_info(account_info_iter)?;
-    let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
-    let withdraw_reserve_info = next_account_info(account_info_iter)?;
-    let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
     let obligation_info = next_account_info(account_info_iter)?;
-    let obligation_token_mint_info = next_account_info(account_info_iter)?;
-    let obligation_token_input_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
-    let lending_market_authority_info = next_account_info(account_info_iter)?;
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
@@ -873,28 +775,6 @@ fn process_repay(
         return Err(LendingError::InvalidTokenProgram.into());
     }
 
-    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
-    if obligation_info.owner != program_id {
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-    if &obligation.borrow_reserve != repay_reserve_info.key {
-        msg!("Invalid repay reserve account");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &obligation.collateral_reserve != withdraw_reserve_info.key {
-        msg!("Invalid withdraw reserve account");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if obligation.deposited_collateral_tokens == 0 {
-        return Err(LendingError::ObligationEmpty.into());
-    }
-
-    let obligation_mint = unpack_mint(&obligation_token_mint_info.data.borrow())?;
-    if &obligation.token_mint != obligation_token_mint_info.key {
-        msg!("Invalid obligation token mint account");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-
     let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
     if repay_reserve_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
@@ -903,95 +783,59 @@ fn process_repay(
         msg!("Invalid reserve lending market account");
         return Err(LendingError::InvalidAccountInput.into());
     }
-
-    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
-    if withdraw_reserve_info.owner != program_id {
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-    if withdraw_reserve.lending_market != repay_reserve.lending_market {
-        return Err(LendingError::LendingMarketMismatch.into());
-    }
-
-    if repay_reserve_info.key == withdraw_reserve_info.key {
-        return Err(LendingError::DuplicateReserve.into());
-    }
-    if repay_reserve.liquidity.mint_pubkey == withdraw_reserve.liquidity.mint_pubkey {
-        return Err(LendingError::DuplicateReserveMint.into());
-    }
-    if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
+    if &repay_reserve.liquidity.supply_pubkey != destination_liquidity_info.key {
         msg!("Invalid repay reserve liquidity supply account");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &withdraw_reserve.collateral.supply_pubkey != withdraw_reserve_collateral_supply_info.key {
-        msg!("Invalid withdraw reserve collateral supply account");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
     if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
         msg!("Cannot use repay reserve liquidity supply as source account input");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &withdraw_reserve.collateral.supply_pubkey == destination_collateral_info.key {
-        msg!("Cannot use withdraw reserve collateral supply as destination account input");
+    if repay_reserve.last_update_slot != clock.slot {
+        msg!("Repay reserve must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Invalid obligation lending market account");
         return Err(LendingError::InvalidAccountInput.into());
     }
 
-    // accrue interest and update rates
-    assert_last_update_slot(&repay_reserve, clock.slot)?;
-    obligation.accrue_interest(repay_reserve.cumulative_borrow_rate_wads)?;
+    let liquidity_index = obligation
+        .find_liquidity_in_borrows(repay_reserve_info.key)?
+        .1;
+    let liquidity = &mut obligation.borrows[liquidity_index];
+    liquidity.accrue_interest(repay_reserve.cumulative_borrow_rate_wads)?;
+    if liquidity.borrowed_amount_wads == Decimal::zero() {
+        return Err(LendingError::ObligationLiquidityEmpty.into());
+    }
 
     let RepayResult {
-        integer_repay_amount,
-        decimal_repay_amount,
-        collateral_withdraw_amount,
-        obligation_token_amount,
-    } = obligation.repay(liquidity_amount, obligation_mint.supply)?;
-    repay_reserve
-        .liquidity
-        .repay(integer_repay_amount, decimal_repay_amount)?;
+        settle_amount,
+        repay_amount,
+    } = repay_reserve.calculate_repay(liquidity_amount, liquidity.borrowed_amount_wads)?;
+
+    repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
+    obligation.repay(settle_amount, liquidity_index)?;
+    obligation.mark_stale();
 
     Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
-    let authority_signer_seeds = &[
-        lending_market_info.key.as_ref(),
-        &[lending_market.bump_seed],
-    ];
-    let lending_market_authority_pubkey =
-        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
-    if lending_market_authority_info.key != &lending_market_authority_pubkey {
-        return Err(LendingError::InvalidMarketAuthority.into());
-    }
-
-    // burn obligation tokens
-    spl_token_burn(TokenBurnParams {
-        mint: obligation_token_mint_info.clone(),
-        source: obligation_token_input_info.clone(),
-        amount: obligation_token_amount,
-        authority: user_transfer_authority_info.clone(),
-        authority_signer_seeds: &[],
-        token_program: token_program_id.clone(),
-    })?;
-
     // deposit repaid liquidity
     spl_token_transfer(TokenTransferParams {
         source: source_liquidity_info.clone(),
-        destination: repay_reserve_liquidity_supply_info.clone(),
-        amount: integer_repay_amount,
+        destination: destination_liquidity_info.clone(),
+        amount: repay_amount,
         authority: user_transfer_authority_info.clone(),
         authority_signer_seeds: &[],
         token_program: token_program_id.clone(),
     })?;
 
-    // withdraw collateral
-    spl_token_transfer(TokenTransferParams {
-        source: withdraw_reserve_collateral_supply_info.clone(),
-        destination: destination_collateral_info.clone(),
-        amount: collateral_withdraw_amount,
-        authority: lending_market_authority_info.clone(),
-        authority_signer_seeds,
-        token_program: token_program_id.clone(),
-    })?;
-
     Ok(())
 }
 
@@ -1016,17 +860,9 @@ fn process_liquidate(
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
-    let dex_market_info = next_account_info(account_info_iter)?;
-    let dex_market_orders_info = next_account_info(account_info_iter)?;
-    let memory = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
-    // Ensure memory is owned by this program so that we don't have to zero it out
-    if memory.owner != program_id {
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
@@ -1035,22 +871,6 @@ fn process_liquidate(
         return Err(LendingError::InvalidTokenProgram.into());
     }
 
-    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
-    if obligation_info.owner != program_id {
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-    if &obligation.borrow_reserve != repay_reserve_info.key {
-        msg!("Invalid repay reserve account");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &obligation.collateral_reserve != withdraw_reserve_info.key {
-        msg!("Invalid withdraw reserve account");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if obligation.deposited_collateral_tokens == 0 {
-        return Err(LendingError::ObligationEmpty.into());
-    }
-
     let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
     if repay_reserve_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
@@ -1059,6 +879,22 @@ fn process_liquidate(
         msg!("Invalid reserve lending market account");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    if repay_reserve.config.liquidations_disabled {
+        msg!("Repay reserve liquidations are disabled");
+        return Err(LendingError::ReserveLiquidationsDisabled.into());
+    }
+    if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
+        msg!("Invalid repay reserve liquidity supply account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+        msg!("Cannot use repay reserve liquidity supply as source account input");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if repay_reserve.last_update_slot != clock.slot {
+        msg!("Repay reserve must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
 
     let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
     if withdraw_reserve_info.owner != program_id {
@@ -1067,77 +903,73 @@ fn process_liquidate(
     if withdraw_reserve.lending_market != repay_reserve.lending_market {
         return Err(LendingError::LendingMarketMismatch.into());
     }
-
-    if repay_reserve_info.key == withdraw_reserve_info.key {
-        return Err(LendingError::DuplicateReserve.into());
-    }
-    if repay_reserve.liquidity.mint_pubkey == withdraw_reserve.liquidity.mint_pubkey {
-        return Err(LendingError::DuplicateReserveMint.into());
-    }
-    if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
-        msg!("Invalid repay reserve liquidity supply account");
-        return Err(LendingError::InvalidAccountInput.into());
+    if withdraw_reserve.config.liquidations_disabled {
+        msg!("Withdraw reserve liquidations are disabled");
+        return Err(LendingError::ReserveLiquidationsDisabled.into());
     }
     if &withdraw_reserve.collateral.supply_pubkey != withdraw_reserve_collateral_supply_info.key {
         msg!("Invalid withdraw reserve collateral supply account");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
-        msg!("Cannot use repay reserve liquidity supply as source account input");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
     if &withdraw_reserve.collateral.supply_pubkey == destination_collateral_info.key {
         msg!("Cannot use withdraw reserve collateral supply as destination account input");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    if withdraw_reserve.last_update_slot != clock.slot {
+        msg!("Withdraw reserve must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
 
-    // TODO: handle case when neither reserve is the quote currency
-    if repay_reserve.dex_market.is_none() && withdraw_reserve.dex_market.is_none() {
-        msg!("One reserve must have a dex market");
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Invalid obligation lending market account");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if let COption::Some(dex_market_pubkey) = repay_reserve.dex_market {
-        if &dex_market_pubkey != dex_market_info.key {
-            msg!("Invalid dex market account");
-            return Err(LendingError::InvalidAccountInput.into());
-        }
+    if obligation.is_stale(clock.slot) {
+        msg!("Obligation must be refreshed in the current slot");
+        return Err(LendingError::ObligationStale.into());
     }
-    if let COption::Some(dex_market_pubkey) = withdraw_reserve.dex_market {
-        if &dex_market_pubkey != dex_market_info.key {
-            msg!("Invalid dex market account");
-            return Err(LendingError::InvalidAccountInput.into());
-        }
+    if obligation.deposits.is_empty() {
+        return Err(LendingError::ObligationDepositsEmpty.into());
+    }
+    if obligation.borrows.is_empty() {
+        return Err(LendingError::ObligationBorrowsEmpty.into());
+    }
+    if obligation.borrowed_value < obligation.unhealthy_borrow_value {
+        return Err(LendingError::HealthyObligation.into());
     }
 
-    // accrue interest and update rates
-    assert_last_update_slot(&repay_reserve, clock.slot)?;
-    assert_last_update_slot(&withdraw_reserve, clock.slot)?;
-    obligation.accrue_interest(repay_reserve.cumulative_borrow_rate_wads)?;
-
-    let trade_simulator = TradeSimulator::new(
-        dex_market_info,
-        dex_market_orders_info,
-        memory,
-        &lending_market.quote_token_mint,
-        &withdraw_reserve.liquidity.mint_pubkey,
-        &repay_reserve.liquidity.mint_pubkey,
-    )?;
+    let (liquidity, liquidity_index) =
+        obligation.find_liquidity_in_borrows(repay_reserve_info.key)?;
+    if liquidity.market_value == Decimal::zero() {
+        return Err(LendingError::ObligationLiquidityEmpty.into());
+    }
+    let (collateral, collateral_index) =
+        obligation.find_collateral_in_deposits(withdraw_reserve_info.key)?;
+    if collateral.market_value == Decimal::zero() {
+        return Err(LendingError::ObligationCollateralEmpty.into());
+    }
 
     let LiquidateResult {
         withdraw_amount,
-        repay_amount,
         settle_amount,
-    } = withdraw_reserve.liquidate_obligation(
-        &obligation,
+        repay_amount,
+    } = withdraw_reserve.calculate_liquidation(
         liquidity_amount,
-        &repay_reserve.liquidity.mint_pubkey,
-        trade_simulator,
+        &obligation,
+        liquidity,
+        collateral,
     )?;
 
     repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
-    Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
+    obligation.repay(settle_amount, liquidity_index)?;
+    obligation.withdraw(withdraw_amount, collateral_index)?;
+    obligation.mark_stale();
 
-    obligation.liquidate(settle_amount, withdraw_amount)?;
+    Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     let authority_signer_seeds = &[
@@ -1174,19 +1006,82 @@ fn process_liquidate(
 }
 
 #[inline(never)] // avoid stack frame limit
-fn process_accrue_interest(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+fn process_refresh_reserve(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
-    for reserve_info in account_info_iter {
-        let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
-        if reserve_info.owner != program_id {
-            return Err(LendingError::InvalidAccountOwner.into());
-        }
 
-        reserve.accrue_interest(clock.slot)?;
-        Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    match reserve.config.oracle {
+        OracleConfig::QuoteCurrency => {}
+        OracleConfig::DexMarket => {
+            let dex_market_info = next_account_info(account_info_iter)?;
+            let dex_market_orders_info = next_account_info(account_info_iter)?;
+            let memory = next_account_info(account_info_iter)?;
+
+            assert_reserve_oracle(&reserve, dex_market_info)?;
+            // Ensure memory is owned by this program so that we don't have to zero it out
+            if memory.owner != program_id {
+                return Err(LendingError::InvalidAccountOwner.into());
+            }
+
+            // Reserve dex markets are checked to be quoted in the lending market
+            // quote currency when the reserve is initialized
+            let quote_token_mint =
+                DexMarket::pubkey_at_offset(&dex_market_info.data.borrow(), QUOTE_MINT_OFFSET);
+            let liquidity_mint = reserve.liquidity.mint_pubkey;
+            reserve.liquidity.market_price = DexMarketOracle::new(
+                dex_market_info,
+                dex_market_orders_info,
+                memory,
+                &quote_token_mint,
+                &liquidity_mint,
+            )?
+            .market_price()?;
+        }
+        OracleConfig::PushOracle {
+            max_staleness_slots,
+            max_confidence_bps,
+        } => {
+            let feed_info = next_account_info(account_info_iter)?;
+
+            assert_reserve_oracle(&reserve, feed_info)?;
+            reserve.liquidity.market_price = PushOracle::new(
+                feed_info,
+                clock.slot,
+                max_staleness_slots,
+                max_confidence_bps,
+            )?
+            .market_price()?;
+        }
+        OracleConfig::TokenSwapTwap {
+            window_slots,
+            max_staleness_slots,
+        } => {
+            let swap_info = next_account_info(account_info_iter)?;
+
+            assert_reserve_oracle(&reserve, swap_info)?;
+            let mut oracle = TokenSwapTwapOracle::new(
+                swap_info,
+                &reserve.liquidity.mint_pubkey,
+                reserve.oracle_observation,
+                reserve.liquidity.market_price,
+                clock.slot,
+                window_slots,
+                max_staleness_slots,
+            )?;
+            reserve.liquidity.market_price = oracle.market_price()?;
+            reserve.oracle_observation = oracle.observation();
+        }
     }
 
+    reserve.accrue_interest(clock.slot)?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
     Ok(())
 }
 
@@ -1205,10 +1100,8 @@ fn process_deposit_obligation_collateral(
     let destination_collateral_info = next_account_info(account_info_iter)?;
     let deposit_reserve_info = next_account_info(account_info_iter)?;
     let obligation_info = next_account_info(account_info_iter)?;
-    let obligation_token_mint_info = next_account_info(account_info_iter)?;
-    let obligation_token_output_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
-    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
@@ -1228,6 +1121,10 @@ fn process_deposit_obligation_collateral(
         msg!("Invalid reserve lending market account");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    if deposit_reserve.config.deposits_disabled {
+        msg!("Reserve deposits are disabled");
+        return Err(LendingError::ReserveDepositsDisabled.into());
+    }
     if deposit_reserve.config.loan_to_value_ratio == 0 {
         return Err(LendingError::ReserveCollateralDisabled.into());
     }
@@ -1244,42 +1141,24 @@ fn process_deposit_obligation_collateral(
     if obligation_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
     }
-
-    if &obligation.collateral_reserve != deposit_reserve_info.key {
-        msg!("Invalid deposit reserve account");
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Invalid obligation lending market account");
         return Err(LendingError::InvalidAccountInput.into());
     }
-
-    if &obligation.token_mint != obligation_token_mint_info.key {
-        msg!("Obligation token mint input doesn't match existing obligation token mint");
-        return Err(LendingError::InvalidTokenMint.into());
-    }
-
-    let obligation_token_output = Token::unpack(&obligation_token_output_info.data.borrow())?;
-    if obligation_token_output_info.owner != token_program_id.key {
-        return Err(LendingError::InvalidTokenOwner.into());
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Invalid obligation owner account");
+        return Err(LendingError::InvalidAccountInput.into());
     }
-    if &obligation_token_output.mint != obligation_token_mint_info.key {
-        return Err(LendingError::InvalidTokenMint.into());
+    if !obligation_owner_info.is_signer {
+        return Err(LendingError::InvalidSigner.into());
     }
 
-    obligation.deposited_collateral_tokens = obligation
-        .deposited_collateral_tokens
-        .checked_add(collateral_amount)
-        .ok_or(LendingError::MathOverflow)?;
-
+    obligation
+        .find_or_add_collateral_to_deposits(*deposit_reserve_info.key)?
+        .deposit(collateral_amount)?;
+    obligation.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
-    let authority_signer_seeds = &[
-        lending_market_info.key.as_ref(),
-        &[lending_market.bump_seed],
-    ];
-    let lending_market_authority_pubkey =
-        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
-    if lending_market_authority_info.key != &lending_market_authority_pubkey {
-        return Err(LendingError::InvalidMarketAuthority.into());
-    }
-
     // deposit collateral
     spl_token_transfer(TokenTransferParams {
         source: source_collateral_info.clone(),
@@ -1290,16 +1169,6 @@ fn process_deposit_obligation_collateral(
         token_program: token_program_id.clone(),
     })?;
 
-    // mint obligation tokens to output account
-    spl_token_mint_to(TokenMintToParams {
-        mint: obligation_token_mint_info.clone(),
-        destination: obligation_token_output_info.clone(),
-        amount: collateral_amount,
-        authority: lending_market_authority_info.clone(),
-        authority_signer_seeds,
-        token_program: token_program_id.clone(),
-    })?;
-
     Ok(())
 }
 
@@ -1317,24 +1186,13 @@ fn process_withdraw_obligation_collateral(
     let source_collateral_info = next_account_info(account_info_iter)?;
     let destination_collateral_info = next_account_info(account_info_iter)?;
     let withdraw_reserve_info = next_account_info(account_info_iter)?;
-    let borrow_reserve_info = next_account_info(account_info_iter)?;
     let obligation_info = next_account_info(account_info_iter)?;
-    let obligation_token_mint_info = next_account_info(account_info_iter)?;
-    let obligation_token_input_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
-    let user_transfer_authority_info = next_account_info(account_info_iter)?;
-    let dex_market_info = next_account_info(account_info_iter)?;
-    let dex_market_orders_info = next_account_info(account_info_iter)?;
-    let memory = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
-    // Ensure memory is owned by this program so that we don't have to zero it out
-    if memory.owner != program_id {
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
@@ -1351,121 +1209,60 @@ fn process_withdraw_obligation_collateral(
         msg!("Invalid reserve lending market account");
         return Err(LendingError::InvalidAccountInput.into());
     }
-
-    let borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
-    if borrow_reserve_info.owner != program_id {
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-    if borrow_reserve.lending_market != withdraw_reserve.lending_market {
-        return Err(LendingError::LendingMarketMismatch.into());
-    }
-
-    if withdraw_reserve.config.loan_to_value_ratio == 0 {
-        return Err(LendingError::ReserveCollateralDisabled.into());
-    }
-
-    if withdraw_reserve_info.key == borrow_reserve_info.key {
-        return Err(LendingError::DuplicateReserve.into());
-    }
-
     if &withdraw_reserve.collateral.supply_pubkey != source_collateral_info.key {
         msg!("Invalid withdraw reserve collateral supply account input");
         return Err(LendingError::InvalidAccountInput.into());
     }
-
     if &withdraw_reserve.collateral.supply_pubkey == destination_collateral_info.key {
         msg!(
             "Cannot use withdraw reserve collateral supply as destination collateral account input"
         );
         return Err(LendingError::InvalidAccountInput.into());
     }
-
-    // TODO: handle case when neither reserve is the quote currency
-    if borrow_reserve.dex_market.is_none() && withdraw_reserve.dex_market.is_none() {
-        msg!("One reserve must have a dex market");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if let COption::Some(dex_market_pubkey) = borrow_reserve.dex_market {
-        if &dex_market_pubkey != dex_market_info.key {
-            msg!("Invalid dex market account input");
-            return Err(LendingError::InvalidAccountInput.into());
-        }
-    }
-    if let COption::Some(dex_market_pubkey) = withdraw_reserve.dex_market {
-        if &dex_market_pubkey != dex_market_info.key {
-            msg!("Invalid dex market account input");
-            return Err(LendingError::InvalidAccountInput.into());
-        }
+    if withdraw_reserve.last_update_slot != clock.slot {
+        msg!("Withdraw reserve must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
     }
 
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
     if obligation_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
     }
-
-    if &obligation.collateral_reserve != withdraw_reserve_info.key {
-        msg!("Invalid withdraw reserve account");
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Invalid obligation lending market account");
         return Err(LendingError::InvalidAccountInput.into());
     }
-
-    let obligation_token_mint = unpack_mint(&obligation_token_mint_info.data.borrow())?;
-    if &obligation.token_mint != obligation_token_mint_info.key {
-        msg!("Obligation token mint input doesn't match existing obligation token mint");
-        return Err(LendingError::InvalidTokenMint.into());
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Invalid obligation owner account");
+        return Err(LendingError::InvalidAccountInput.into());
     }
-
-    let obligation_token_input = Token::unpack(&obligation_token_input_info.data.borrow())?;
-    if obligation_token_input_info.owner != token_program_id.key {
-        return Err(LendingError::InvalidTokenOwner.into());
+    if !obligation_owner_info.is_signer {
+        return Err(LendingError::InvalidSigner.into());
     }
-    if &obligation_token_input.mint != obligation_token_mint_info.key {
-        return Err(LendingError::InvalidTokenMint.into());
+    if obligation.is_stale(clock.slot) {
+        msg!("Obligation must be refreshed in the current slot");
+        return Err(LendingError::ObligationStale.into());
     }
 
-    // accrue interest and update rates
-    assert_last_update_slot(&borrow_reserve, clock.slot)?;
-    assert_last_update_slot(&withdraw_reserve, clock.slot)?;
-
-    obligation.accrue_interest(borrow_reserve.cumulative_borrow_rate_wads)?;
-
-    let obligation_collateral_amount = obligation.deposited_collateral_tokens;
-    if obligation_collateral_amount == 0 {
-        return Err(LendingError::ObligationEmpty.into());
+    let (collateral, collateral_index) =
+        obligation.find_collateral_in_deposits(withdraw_reserve_info.key)?;
+    if collateral.deposited_amount == 0 {
+        return Err(LendingError::ObligationCollateralEmpty.into());
     }
-    if obligation_collateral_amount < collateral_amount {
+    if collateral_amount > collateral.deposited_amount {
         return Err(LendingError::InvalidObligationCollateral.into());
     }
-
-    let trade_simulator = TradeSimulator::new(
-        dex_market_info,
-        dex_market_orders_info,
-        memory,
-        &lending_market.quote_token_mint,
-        &borrow_reserve.liquidity.mint_pubkey,
-        &withdraw_reserve.liquidity.mint_pubkey,
-    )?;
-
-    let required_collateral = withdraw_reserve.required_collateral_for_borrow(
-        obligation.borrowed_liquidity_wads.try_ceil_u64()?,
-        &borrow_reserve.liquidity.mint_pubkey,
-        trade_simulator,
-    )?;
-    if obligation_collateral_amount < required_collateral {
-        return Err(LendingError::ObligationCollateralBelowRequired.into());
+    if collateral_amount > collateral.transferable_amount() {
+        msg!("Collateral deposited from a deposit position can only be withdrawn to a deposit position");
+        return Err(LendingError::ObligationCollateralRestricted.into());
     }
-
-    let remaining_collateral = obligation_collateral_amount
-        .checked_sub(collateral_amount)
-        .ok_or(LendingError::MathOverflow)?;
-    if remaining_collateral < required_collateral {
-        return Err(LendingError::ObligationCollateralWithdrawBelowRequired.into());
+    if collateral_amount > max_withdraw_amount(&obligation, collateral, &withdraw_reserve)? {
+        msg!("Withdraw amount is too large for the remaining borrow value");
+        return Err(LendingError::WithdrawTooLarge.into());
     }
 
-    let obligation_token_amount = obligation
-        .collateral_to_obligation_token_amount(collateral_amount, obligation_token_mint.supply)?;
-
-    obligation.deposited_collateral_tokens = remaining_collateral;
-
+    obligation.withdraw(collateral_amount, collateral_index)?;
+    obligation.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     let authority_signer_seeds = &[
@@ -1478,16 +1275,6 @@ fn process_withdraw_obligation_collateral(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    // burn obligation tokens
-    spl_token_burn(TokenBurnParams {
-        mint: obligation_token_mint_info.clone(),
-        source: obligation_token_input_info.clone(),
-        amount: obligation_token_amount,
-        authority: user_transfer_authority_info.clone(),
-        authority_signer_seeds: &[],
-        token_program: token_program_id.clone(),
-    })?;
-
     // withdraw collateral
     spl_token_transfer(TokenTransferParams {
         source: source_collateral_info.clone(),
@@ -1528,37 +1315,894 @@ fn process_set_lending_market_owner(
     Ok(())
 }
 
-fn assert_rent_exempt(rent: &Rent, account_info: &AccountInfo) -> ProgramResult {
-    if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
-        msg!(&rent.minimum_balance(account_info.data_len()).to_string());
-        Err(LendingError::NotRentExempt.into())
-    } else {
-        Ok(())
+#[inline(never)] // avoid stack frame limit
+fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
     }
-}
 
-fn assert_last_update_slot(reserve: &Reserve, slot: Slot) -> ProgramResult {
-    if !reserve.last_update_slot == slot {
-        Err(LendingError::ReserveStale.into())
-    } else {
-        Ok(())
+    // Reserves are passed in the order of the obligation deposits, then borrows
+    obligation.refresh_values(|reserve_pubkey| -> Result<Reserve, ProgramError> {
+        let reserve_info = next_account_info(account_info_iter)?;
+        if reserve_pubkey != reserve_info.key {
+            msg!("Reserve account does not match the obligation deposit or borrow");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+        if reserve_info.owner != program_id {
+            return Err(LendingError::InvalidAccountOwner.into());
+        }
+        if reserve.last_update_slot != clock.slot {
+            msg!("Reserve must be refreshed in the current slot");
+            return Err(LendingError::ReserveStale.into());
+        }
+        Ok(reserve)
+    })?;
+
+    if account_info_iter.next().is_some() {
+        msg!("Too many reserve accounts for the obligation deposits and borrows");
+        return Err(LendingError::InvalidAccountInput.into());
     }
+
+    obligation.update_slot(clock.slot);
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    Ok(())
 }
 
-fn assert_uninitialized<T: Pack + IsInitialized>(
-    account_info: &AccountInfo,
-) -> Result<T, ProgramError> {
-    let account: T = T::unpack_unchecked(&account_info.data.borrow())?;
-    if account.is_initialized() {
-        Err(LendingError::AlreadyInitialized.into())
-    } else {
-        Ok(account)
+#[inline(never)] // avoid stack frame limit
+fn process_flash_loan(program_id: &Pubkey, amount: u64, accounts: &[AccountInfo]) -> ProgramResult {
+    if amount == 0 {
+        return Err(LendingError::InvalidAmount.into());
     }
-}
 
-/// Unpacks a spl_token `Mint`.
-fn unpack_mint(data: &[u8]) -> Result<spl_token::state::Mint, LendingError> {
-    spl_token::state::Mint::unpack(data).map_err(|_| LendingError::InvalidTokenMint)
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let host_fee_receiver_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let flash_loan_receiver_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve.config.borrows_disabled {
+        msg!("Reserve borrows are disabled");
+        return Err(LendingError::ReserveBorrowsDisabled.into());
+    }
+    if &reserve.liquidity.supply_pubkey != source_liquidity_info.key {
+        msg!("Invalid reserve liquidity supply account input");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
+        msg!("Cannot use reserve liquidity supply as destination account input");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.fee_receiver != reserve_liquidity_fee_receiver_info.key {
+        msg!("Invalid reserve liquidity fee receiver account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if flash_loan_receiver_program_id.key == program_id {
+        msg!("Lending program cannot be used as the flash loan receiver program");
+        return Err(LendingError::InvalidFlashLoanReceiverProgram.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if lending_market_authority_info.key != &lending_market_authority_pubkey {
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let (flash_loan_fee, host_fee) = reserve.config.fees.calculate_flash_loan_fees(amount)?;
+    let balance_before_flash_loan =
+        unpack_token_account(&source_liquidity_info.data.borrow())?.amount;
+    let expected_balance_after_flash_loan = balance_before_flash_loan
+        .checked_add(flash_loan_fee)
+        .ok_or(LendingError::MathOverflow)?;
+
+    // record the borrow before handing control to the receiver program so any
+    // account state it observes reflects the outstanding flash loan
+    reserve.liquidity.borrow(amount)?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_liquidity_info.clone(),
+        destination: destination_liquidity_info.clone(),
+        amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    // receiver program gets the destination liquidity and token program, followed
+    // by any remaining accounts passed to this instruction
+    let mut receiver_accounts = vec![
+        AccountMeta::new(*destination_liquidity_info.key, false),
+        AccountMeta::new_readonly(*token_program_id.key, false),
+    ];
+    let mut receiver_account_infos =
+        vec![destination_liquidity_info.clone(), token_program_id.clone()];
+    for account_info in account_info_iter {
+        if account_info.key == reserve_info.key || account_info.key == program_id {
+            msg!("Flash loan receiver cannot be passed the reserve or lending program");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        receiver_accounts.push(AccountMeta {
+            pubkey: *account_info.key,
+            is_signer: account_info.is_signer,
+            is_writable: account_info.is_writable,
+        });
+        receiver_account_infos.push(account_info.clone());
+    }
+    receiver_account_infos.push(flash_loan_receiver_program_id.clone());
+
+    let mut receiver_data = Vec::with_capacity(9);
+    receiver_data.push(0);
+    receiver_data.extend_from_slice(&amount.to_le_bytes());
+    invoke(
+        &Instruction {
+            program_id: *flash_loan_receiver_program_id.key,
+            accounts: receiver_accounts,
+            data: receiver_data,
+        },
+        &receiver_account_infos,
+    )?;
+
+    let balance_after_flash_loan =
+        unpack_token_account(&source_liquidity_info.data.borrow())?.amount;
+    if balance_after_flash_loan < expected_balance_after_flash_loan {
+        msg!("Insufficient reserve liquidity after flash loan");
+        return Err(LendingError::FlashLoanNotRepaid.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    reserve.liquidity.repay(amount, Decimal::from(amount))?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    let owner_fee = flash_loan_fee
+        .checked_sub(host_fee)
+        .ok_or(LendingError::MathOverflow)?;
+
+    if host_fee > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: source_liquidity_info.clone(),
+            destination: host_fee_receiver_info.clone(),
+            amount: host_fee,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    if owner_fee > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: source_liquidity_info.clone(),
+            destination: reserve_liquidity_fee_receiver_info.clone(),
+            amount: owner_fee,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_update_reserve_config(
+    program_id: &Pubkey,
+    config: ReserveConfig,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    validate_reserve_config(&config)?;
+
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    // The reserve oracle account was validated for its oracle type at init
+    if discriminant(&reserve.config.oracle) != discriminant(&config.oracle) {
+        msg!("Reserve oracle type cannot be changed");
+        return Err(LendingError::InvalidConfig.into());
+    }
+
+    reserve.config = config;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_withdraw_protocol_fees(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 {
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
+        msg!("Invalid reserve liquidity supply account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
+        msg!("Cannot use reserve liquidity supply as destination account input");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let withdraw_amount = reserve.liquidity.withdraw_protocol_fees(liquidity_amount)?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if lending_market_authority_info.key != &lending_market_authority_pubkey {
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    spl_token_transfer(TokenTransferParams {
+        source: reserve_liquidity_supply_info.clone(),
+        destination: destination_liquidity_info.clone(),
+        amount: withdraw_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+fn process_init_deposit_position(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let deposit_position_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let deposit_position_owner_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+
+    assert_rent_exempt(rent, deposit_position_info)?;
+    assert_uninitialized::<DepositPosition>(deposit_position_info)?;
+    if deposit_position_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    if !deposit_position_owner_info.is_signer {
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let deposit_position = DepositPosition::new(NewDepositPositionParams {
+        lending_market: *lending_market_info.key,
+        reserve: *reserve_info.key,
+        owner: *deposit_position_owner_info.key,
+    });
+    DepositPosition::pack(
+        deposit_position,
+        &mut deposit_position_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_deposit_position_liquidity(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 {
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let deposit_position_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let deposit_position_owner_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve.config.deposits_disabled {
+        msg!("Reserve deposits are disabled");
+        return Err(LendingError::ReserveDepositsDisabled.into());
+    }
+    if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
+        msg!("Invalid reserve liquidity supply account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.collateral.mint_pubkey != reserve_collateral_mint_info.key {
+        msg!("Invalid reserve collateral mint account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.collateral.supply_pubkey != reserve_collateral_supply_info.key {
+        msg!("Invalid reserve collateral supply account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+        msg!("Cannot use reserve liquidity supply as source account input");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let mut deposit_position = unpack_deposit_position(
+        program_id,
+        deposit_position_info,
+        reserve_info,
+        deposit_position_owner_info,
+    )?;
+
+    assert_last_update_slot(&reserve, clock.slot)?;
+    reserve.check_supply_cap(liquidity_amount)?;
+    let collateral_amount = reserve.deposit_liquidity(liquidity_amount)?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    deposit_position.deposit(collateral_amount)?;
+    DepositPosition::pack(
+        deposit_position,
+        &mut deposit_position_info.data.borrow_mut(),
+    )?;
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if lending_market_authority_info.key != &lending_market_authority_pubkey {
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_liquidity_info.clone(),
+        destination: reserve_liquidity_supply_info.clone(),
+        amount: liquidity_amount,
+        authority: user_transfer_authority_info.clone(),
+        authority_signer_seeds: &[],
+        token_program: token_program_id.clone(),
+    })?;
+
+    // the collateral stays in the reserve and is only recorded by the position
+    spl_token_mint_to(TokenMintToParams {
+        mint: reserve_collateral_mint_info.clone(),
+        destination: reserve_collateral_supply_info.clone(),
+        amount: collateral_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_withdraw_position_liquidity(
+    program_id: &Pubkey,
+    collateral_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if collateral_amount == 0 {
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let deposit_position_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let deposit_position_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
+        msg!("Invalid reserve liquidity supply account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.collateral.mint_pubkey != reserve_collateral_mint_info.key {
+        msg!("Invalid reserve collateral mint account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.collateral.supply_pubkey != reserve_collateral_supply_info.key {
+        msg!("Invalid reserve collateral supply account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
+        msg!("Cannot use reserve liquidity supply as destination account input");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve.last_update_slot != clock.slot {
+        msg!("Reserve must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let mut deposit_position = unpack_deposit_position(
+        program_id,
+        deposit_position_info,
+        reserve_info,
+        deposit_position_owner_info,
+    )?;
+    deposit_position.withdraw(collateral_amount)?;
+    DepositPosition::pack(
+        deposit_position,
+        &mut deposit_position_info.data.borrow_mut(),
+    )?;
+
+    let liquidity_withdraw_amount = reserve.redeem_collateral(collateral_amount)?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if lending_market_authority_info.key != &lending_market_authority_pubkey {
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    spl_token_burn(TokenBurnParams {
+        mint: reserve_collateral_mint_info.clone(),
+        source: reserve_collateral_supply_info.clone(),
+        amount: collateral_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: reserve_liquidity_supply_info.clone(),
+        destination: destination_liquidity_info.clone(),
+        amount: liquidity_withdraw_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_deposit_position_collateral(
+    program_id: &Pubkey,
+    collateral_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if collateral_amount == 0 {
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let deposit_position_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+
+    LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
+    if deposit_reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &deposit_reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if deposit_reserve.config.deposits_disabled {
+        msg!("Reserve deposits are disabled");
+        return Err(LendingError::ReserveDepositsDisabled.into());
+    }
+    if deposit_reserve.config.loan_to_value_ratio == 0 {
+        return Err(LendingError::ReserveCollateralDisabled.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Invalid obligation lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.owner != owner_info.key {
+        msg!("Invalid obligation owner account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let mut deposit_position = unpack_deposit_position(
+        program_id,
+        deposit_position_info,
+        deposit_reserve_info,
+        owner_info,
+    )?;
+    deposit_position.withdraw(collateral_amount)?;
+    DepositPosition::pack(
+        deposit_position,
+        &mut deposit_position_info.data.borrow_mut(),
+    )?;
+
+    obligation
+        .find_or_add_collateral_to_deposits(*deposit_reserve_info.key)?
+        .deposit_from_position(collateral_amount)?;
+    obligation.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_withdraw_position_collateral(
+    program_id: &Pubkey,
+    collateral_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if collateral_amount == 0 {
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let deposit_position_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+    if withdraw_reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &withdraw_reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if withdraw_reserve.last_update_slot != clock.slot {
+        msg!("Withdraw reserve must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Invalid obligation lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.owner != owner_info.key {
+        msg!("Invalid obligation owner account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if obligation.is_stale(clock.slot) {
+        msg!("Obligation must be refreshed in the current slot");
+        return Err(LendingError::ObligationStale.into());
+    }
+
+    let mut deposit_position = unpack_deposit_position(
+        program_id,
+        deposit_position_info,
+        withdraw_reserve_info,
+        owner_info,
+    )?;
+
+    let (collateral, collateral_index) =
+        obligation.find_collateral_in_deposits(withdraw_reserve_info.key)?;
+    if collateral.deposited_amount == 0 {
+        return Err(LendingError::ObligationCollateralEmpty.into());
+    }
+    if collateral_amount > collateral.deposited_amount {
+        return Err(LendingError::InvalidObligationCollateral.into());
+    }
+    if collateral_amount > max_withdraw_amount(&obligation, collateral, &withdraw_reserve)? {
+        msg!("Withdraw amount is too large for the remaining borrow value");
+        return Err(LendingError::WithdrawTooLarge.into());
+    }
+
+    obligation.withdraw_to_position(collateral_amount, collateral_index)?;
+    obligation.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    deposit_position.deposit(collateral_amount)?;
+    DepositPosition::pack(
+        deposit_position,
+        &mut deposit_position_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+fn validate_reserve_config(config: &ReserveConfig) -> ProgramResult {
+    if config.loan_to_value_ratio >= 100 {
+        msg!("Loan to value ratio must be in range [0, 100)");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.liquidation_bonus > 100 {
+        msg!("Liquidation bonus must be in range [0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.liquidation_threshold <= config.loan_to_value_ratio
+        || config.liquidation_threshold > 100
+    {
+        msg!("Liquidation threshold must be in range (LTV, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if !config.borrow_rate_curve.is_valid() {
+        msg!("Borrow rate curve must span utilization [0, 10_000] bps with increasing utilization and non-decreasing borrow rates");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.fees.borrow_fee_wad >= WAD {
+        msg!("Borrow fee must be in range [0, 1_000_000_000_000_000_000)");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.fees.flash_loan_fee_wad >= WAD {
+        msg!("Flash loan fee must be in range [0, 1_000_000_000_000_000_000)");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.fees.host_fee_percentage > 100 {
+        msg!("Host fee percentage must be in range [0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.reserve_factor > 100 {
+        msg!("Reserve factor must be in range [0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    match config.oracle {
+        OracleConfig::PushOracle {
+            max_confidence_bps, ..
+        } if max_confidence_bps > 10_000 => {
+            msg!("Push oracle max confidence must be in range [0, 10_000] basis points");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        OracleConfig::TokenSwapTwap {
+            window_slots: 0, ..
+        } => {
+            msg!("Token swap TWAP window must be greater than zero slots");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        OracleConfig::TokenSwapTwap {
+            window_slots,
+            max_staleness_slots,
+        } if max_staleness_slots < window_slots => {
+            msg!("Token swap TWAP max staleness must be at least the window");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn unpack_deposit_position(
+    program_id: &Pubkey,
+    deposit_position_info: &AccountInfo,
+    reserve_info: &AccountInfo,
+    deposit_position_owner_info: &AccountInfo,
+) -> Result<DepositPosition, ProgramError> {
+    let deposit_position = DepositPosition::unpack(&deposit_position_info.data.borrow())?;
+    if deposit_position_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &deposit_position.reserve != reserve_info.key {
+        msg!("Invalid deposit position reserve account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &deposit_position.owner != deposit_position_owner_info.key {
+        msg!("Invalid deposit position owner account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if !deposit_position_owner_info.is_signer {
+        return Err(LendingError::InvalidSigner.into());
+    }
+    Ok(deposit_position)
+}
+
+/// Maximum amount of collateral that can be withdrawn from a refreshed
+/// obligation without exceeding its allowed borrow value
+fn max_withdraw_amount(
+    obligation: &Obligation,
+    collateral: &ObligationCollateral,
+    withdraw_reserve: &Reserve,
+) -> Result<u64, ProgramError> {
+    if obligation.borrows.is_empty() || collateral.market_value == Decimal::zero() {
+        return Ok(collateral.deposited_amount);
+    }
+    let max_withdraw_value = obligation.max_withdraw_value(
+        collateral,
+        Rate::from_percent(withdraw_reserve.config.loan_to_value_ratio),
+    )?;
+    let max_withdraw_pct = max_withdraw_value.try_div(collateral.market_value)?;
+    max_withdraw_pct
+        .try_mul(collateral.deposited_amount)?
+        .try_floor_u64()
+}
+
+fn assert_rent_exempt(rent: &Rent, account_info: &AccountInfo) -> ProgramResult {
+    if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
+        msg!(&rent.minimum_balance(account_info.data_len()).to_string());
+        Err(LendingError::NotRentExempt.into())
+    } else {
+        Ok(())
+    }
+}
+
+fn assert_reserve_oracle(reserve: &Reserve, oracle_info: &AccountInfo) -> ProgramResult {
+    if reserve.oracle != COption::Some(*oracle_info.key) {
+        msg!("Invalid reserve oracle account");
+        Err(LendingError::InvalidAccountInput.into())
+    } else {
+        Ok(())
+    }
+}
+
+fn assert_last_update_slot(reserve: &Reserve, slot: Slot) -> ProgramResult {
+    if !reserve.last_update_slot == slot {
+        Err(LendingError::ReserveStale.into())
+    } else {
+        Ok(())
+    }
+}
+
+fn assert_uninitialized<T: Pack + IsInitialized>(
+    account_info: &AccountInfo,
+) -> Result<T, ProgramError> {
+    let account: T = T::unpack_unchecked(&account_info.data.borrow())?;
+    if account.is_initialized() {
+        Err(LendingError::AlreadyInitialized.into())
+    } else {
+        Ok(account)
+    }
+}
+
+/// Unpacks a spl_token `Mint`.
+fn unpack_mint(data: &[u8]) -> Result<spl_token::state::Mint, LendingError> {
+    spl_token::state::Mint::unpack(data).map_err(|_| LendingError::InvalidTokenMint)
+}
+
+/// Unpacks a spl_token `Account`.
+fn unpack_token_account(data: &[u8]) -> Result<spl_token::state::Account, LendingError> {
+    spl_token::state::Account::unpack(data).map_err(|_| LendingError::InvalidTokenAccount)
 }
 
 /// Issue a spl_token `InitializeMint` instruction.
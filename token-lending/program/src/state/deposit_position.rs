@@ -0,0 +1,150 @@
+use super::*;
+use crate::error::LendingError;
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::Pubkey,
+};
+
+/// Non-transferable deposit position state. Collateral recorded by a position
+/// is held in the reserve collateral supply, so it follows the same exchange
+/// rate as collateral tokens but can only be moved by the position owner.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DepositPosition {
+    /// Version of the deposit position
+    pub version: u8,
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Reserve which liquidity was deposited into
+    pub reserve: Pubkey,
+    /// Owner authority which can redeem the position or use it as obligation collateral
+    pub owner: Pubkey,
+    /// Amount of collateral tokens held by the position
+    pub collateral_amount: u64,
+}
+
+impl DepositPosition {
+    /// Create new deposit position
+    pub fn new(params: NewDepositPositionParams) -> Self {
+        let NewDepositPositionParams {
+            lending_market,
+            reserve,
+            owner,
+        } = params;
+
+        Self {
+            version: PROGRAM_VERSION,
+            lending_market,
+            reserve,
+            owner,
+            collateral_amount: 0,
+        }
+    }
+
+    /// Increase position collateral
+    pub fn deposit(&mut self, collateral_amount: u64) -> ProgramResult {
+        self.collateral_amount = self
+            .collateral_amount
+            .checked_add(collateral_amount)
+            .ok_or(LendingError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Decrease position collateral
+    pub fn withdraw(&mut self, collateral_amount: u64) -> ProgramResult {
+        self.collateral_amount = self
+            .collateral_amount
+            .checked_sub(collateral_amount)
+            .ok_or(LendingError::InsufficientPositionCollateral)?;
+        Ok(())
+    }
+}
+
+/// Create new deposit position
+pub struct NewDepositPositionParams {
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Reserve address
+    pub reserve: Pubkey,
+    /// Owner authority
+    pub owner: Pubkey,
+}
+
+impl Sealed for DepositPosition {}
+impl IsInitialized for DepositPosition {
+    fn is_initialized(&self) -> bool {
+        self.version != UNINITIALIZED_VERSION
+    }
+}
+
+const DEPOSIT_POSITION_LEN: usize = 169; // 1 + 32 + 32 + 32 + 8 + 64
+impl Pack for DepositPosition {
+    const LEN: usize = DEPOSIT_POSITION_LEN;
+
+    /// Unpacks a byte buffer into a [DepositPosition](struct.DepositPosition.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, DEPOSIT_POSITION_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, lending_market, reserve, owner, collateral_amount, _padding) =
+            array_refs![input, 1, 32, 32, 32, 8, 64];
+        let version = u8::from_le_bytes(*version);
+        if version > PROGRAM_VERSION {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Self {
+            version,
+            lending_market: Pubkey::new_from_array(*lending_market),
+            reserve: Pubkey::new_from_array(*reserve),
+            owner: Pubkey::new_from_array(*owner),
+            collateral_amount: u64::from_le_bytes(*collateral_amount),
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, DEPOSIT_POSITION_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, lending_market, reserve, owner, collateral_amount, _padding) =
+            mut_array_refs![output, 1, 32, 32, 32, 8, 64];
+        *version = self.version.to_le_bytes();
+        lending_market.copy_from_slice(self.lending_market.as_ref());
+        reserve.copy_from_slice(self.reserve.as_ref());
+        owner.copy_from_slice(self.owner.as_ref());
+        *collateral_amount = self.collateral_amount.to_le_bytes();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deposit_position_pack_unpack() {
+        let mut position = DepositPosition::new(NewDepositPositionParams {
+            lending_market: Pubkey::new_unique(),
+            reserve: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+        });
+        position.deposit(100).unwrap();
+
+        let mut packed = [0u8; DepositPosition::LEN];
+        DepositPosition::pack(position.clone(), &mut packed).unwrap();
+        assert_eq!(DepositPosition::unpack(&packed).unwrap(), position);
+    }
+
+    #[test]
+    fn deposit_position_withdraw() {
+        let mut position = DepositPosition {
+            collateral_amount: 100,
+            ..DepositPosition::default()
+        };
+        assert_eq!(
+            position.withdraw(101),
+            Err(LendingError::InsufficientPositionCollateral.into())
+        );
+        position.withdraw(100).unwrap();
+        assert_eq!(position.collateral_amount, 0);
+    }
+}
@@ -1,9 +1,11 @@
 //! State types
 
+mod deposit_position;
 mod lending_market;
 mod obligation;
 mod reserve;
 
+pub use deposit_position::*;
 pub use lending_market::*;
 pub use obligation::*;
 pub use reserve::*;
@@ -68,6 +70,18 @@ fn unpack_coption_key(src: &[u8; 36]) -> Result<COption<Pubkey>, ProgramError> {
     }
 }
 
+fn pack_bool(boolean: bool, dst: &mut [u8; 1]) {
+    *dst = (boolean as u8).to_le_bytes()
+}
+
+fn unpack_bool(src: &[u8; 1]) -> Result<bool, ProgramError> {
+    match u8::from_le_bytes(*src) {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
 fn pack_decimal(decimal: Decimal, dst: &mut [u8; 16]) {
     *dst = decimal
         .to_scaled_val()
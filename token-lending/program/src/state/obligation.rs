@@ -1,10 +1,11 @@
 use super::*;
 use crate::{
     error::LendingError,
-    math::{Decimal, Rate, TryDiv, TryMul, TrySub},
+    math::{Decimal, Rate, TryAdd, TryDiv, TryMul, TrySub},
 };
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use solana_program::{
+    clock::Slot,
     entrypoint::ProgramResult,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
@@ -12,174 +13,430 @@ use solana_program::{
 };
 use std::convert::TryInto;
 
+/// Max number of collateral and liquidity reserve accounts combined for an obligation
+pub const MAX_OBLIGATION_RESERVES: usize = 10;
+
 /// Borrow obligation state
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Obligation {
     /// Version of the obligation
     pub version: u8,
-    /// Amount of collateral tokens deposited for this obligation
-    pub deposited_collateral_tokens: u64,
-    /// Reserve which collateral tokens were deposited into
-    pub collateral_reserve: Pubkey,
-    /// Borrow rate used for calculating interest.
-    pub cumulative_borrow_rate_wads: Decimal,
-    /// Amount of tokens borrowed for this obligation plus interest
-    pub borrowed_liquidity_wads: Decimal,
-    /// Reserve which tokens were borrowed from
-    pub borrow_reserve: Pubkey,
-    /// Mint address of the tokens for this obligation
-    pub token_mint: Pubkey,
+    /// Last slot when the obligation values were refreshed
+    pub last_update_slot: Slot,
+    /// True when the obligation values must be refreshed before they can be used
+    pub stale: bool,
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Owner authority which can borrow liquidity and withdraw collateral
+    pub owner: Pubkey,
+    /// Deposited collateral for the obligation, unique by deposit reserve address
+    pub deposits: Vec<ObligationCollateral>,
+    /// Borrowed liquidity for the obligation, unique by borrow reserve address
+    pub borrows: Vec<ObligationLiquidity>,
+    /// Market value of deposits, in quote currency
+    pub deposited_value: Decimal,
+    /// Market value of borrows, in quote currency
+    pub borrowed_value: Decimal,
+    /// The maximum borrow value, weighted by each deposit's loan to value ratio
+    pub allowed_borrow_value: Decimal,
+    /// The borrow value at which the obligation can be liquidated, weighted by
+    /// each deposit's liquidation threshold
+    pub unhealthy_borrow_value: Decimal,
 }
 
 impl Obligation {
     /// Create new obligation
     pub fn new(params: NewObligationParams) -> Self {
         let NewObligationParams {
-            collateral_reserve,
-            borrow_reserve,
-            token_mint,
-            cumulative_borrow_rate_wads,
+            current_slot,
+            lending_market,
+            owner,
         } = params;
 
         Self {
             version: PROGRAM_VERSION,
-            deposited_collateral_tokens: 0,
-            collateral_reserve,
-            cumulative_borrow_rate_wads,
-            borrowed_liquidity_wads: Decimal::zero(),
-            borrow_reserve,
-            token_mint,
+            last_update_slot: current_slot,
+            stale: true,
+            lending_market,
+            owner,
+            ..Self::default()
         }
     }
 
-    /// Maximum amount of loan that can be closed out by a liquidator due
-    /// to the remaining balance being too small to be liquidated normally.
-    pub fn max_closeable_amount(&self) -> Result<u64, ProgramError> {
-        if self.borrowed_liquidity_wads < Decimal::from(CLOSEABLE_AMOUNT) {
-            self.borrowed_liquidity_wads.try_ceil_u64()
-        } else {
-            Ok(0)
+    /// Return true if the obligation values were not refreshed in the current slot
+    pub fn is_stale(&self, current_slot: Slot) -> bool {
+        self.stale || self.last_update_slot != current_slot
+    }
+
+    /// Record that the obligation values were refreshed in the current slot
+    pub fn update_slot(&mut self, current_slot: Slot) {
+        self.last_update_slot = current_slot;
+        self.stale = false;
+    }
+
+    /// Mark the obligation values as needing a refresh
+    pub fn mark_stale(&mut self) {
+        self.stale = true;
+    }
+
+    /// Recalculate the market values of the deposits and borrows and the total
+    /// values of the obligation. `get_reserve` is called with the reserve of
+    /// each deposit, then of each borrow, in order.
+    pub fn refresh_values<E, F>(&mut self, mut get_reserve: F) -> Result<(), E>
+    where
+        E: From<ProgramError>,
+        F: FnMut(&Pubkey) -> Result<Reserve, E>,
+    {
+        let mut deposited_value = Decimal::zero();
+        let mut allowed_borrow_value = Decimal::zero();
+        let mut unhealthy_borrow_value = Decimal::zero();
+        for collateral in self.deposits.iter_mut() {
+            let deposit_reserve = get_reserve(&collateral.deposit_reserve)?;
+
+            let liquidity_amount = deposit_reserve
+                .collateral_exchange_rate()?
+                .decimal_collateral_to_liquidity(collateral.deposited_amount.into())?;
+            let market_value = deposit_reserve.market_value(liquidity_amount)?;
+            let loan_to_value_rate = Rate::from_percent(deposit_reserve.config.loan_to_value_ratio);
+            let liquidation_threshold_rate =
+                Rate::from_percent(deposit_reserve.config.liquidation_threshold);
+
+            collateral.market_value = market_value;
+            deposited_value = deposited_value.try_add(market_value)?;
+            allowed_borrow_value =
+                allowed_borrow_value.try_add(market_value.try_mul(loan_to_value_rate)?)?;
+            unhealthy_borrow_value = unhealthy_borrow_value
+                .try_add(market_value.try_mul(liquidation_threshold_rate)?)?;
+        }
+
+        let mut borrowed_value = Decimal::zero();
+        for liquidity in self.borrows.iter_mut() {
+            let borrow_reserve = get_reserve(&liquidity.borrow_reserve)?;
+
+            liquidity.accrue_interest(borrow_reserve.cumulative_borrow_rate_wads)?;
+            let market_value = borrow_reserve.market_value(liquidity.borrowed_amount_wads)?;
+
+            liquidity.market_value = market_value;
+            borrowed_value = borrowed_value.try_add(market_value)?;
         }
+
+        self.deposited_value = deposited_value;
+        self.borrowed_value = borrowed_value;
+        self.allowed_borrow_value = allowed_borrow_value;
+        self.unhealthy_borrow_value = unhealthy_borrow_value;
+        Ok(())
     }
 
-    /// Maximum amount of loan that can be repaid by liquidators
-    pub fn max_liquidation_amount(&self) -> Result<u64, ProgramError> {
-        self.borrowed_liquidity_wads
-            .try_mul(Rate::from_percent(LIQUIDATION_CLOSE_FACTOR))?
-            .try_floor_u64()
+    /// Ratio of borrowed value to deposited value
+    pub fn loan_to_value(&self) -> Result<Decimal, ProgramError> {
+        self.borrowed_value.try_div(self.deposited_value)
     }
 
-    /// Ratio of loan balance to collateral value
-    pub fn loan_to_value(
+    /// Calculate the borrow value that can still be taken against the deposits
+    pub fn remaining_borrow_value(&self) -> Result<Decimal, ProgramError> {
+        if self.borrowed_value >= self.allowed_borrow_value {
+            return Ok(Decimal::zero());
+        }
+        self.allowed_borrow_value.try_sub(self.borrowed_value)
+    }
+
+    /// Calculate the maximum value that can be withdrawn from a deposit without
+    /// exceeding the allowed borrow value
+    pub fn max_withdraw_value(
         &self,
-        collateral_exchange_rate: CollateralExchangeRate,
-        borrow_token_price: Decimal,
+        collateral: &ObligationCollateral,
+        loan_to_value_ratio: Rate,
     ) -> Result<Decimal, ProgramError> {
-        let loan = self.borrowed_liquidity_wads;
-        let collateral_value = collateral_exchange_rate
-            .decimal_collateral_to_liquidity(self.deposited_collateral_tokens.into())?
-            .try_div(borrow_token_price)?;
-        loan.try_div(collateral_value)
+        if self.borrows.is_empty() || loan_to_value_ratio == Rate::zero() {
+            return Ok(collateral.market_value);
+        }
+        Ok(self
+            .remaining_borrow_value()?
+            .try_div(loan_to_value_ratio)?
+            .min(collateral.market_value))
     }
 
-    /// Amount of obligation tokens for given collateral
-    pub fn collateral_to_obligation_token_amount(
+    /// Maximum amount of a borrow that can be repaid by liquidators
+    pub fn max_liquidation_amount(
         &self,
-        collateral_amount: u64,
-        obligation_token_supply: u64,
-    ) -> Result<u64, ProgramError> {
-        let withdraw_pct =
-            Decimal::from(collateral_amount).try_div(self.deposited_collateral_tokens)?;
-        let token_amount: Decimal = withdraw_pct.try_mul(obligation_token_supply)?;
-        token_amount.try_floor_u64()
+        liquidity: &ObligationLiquidity,
+    ) -> Result<Decimal, ProgramError> {
+        if liquidity.market_value == Decimal::zero() {
+            return liquidity
+                .borrowed_amount_wads
+                .try_mul(Rate::from_percent(LIQUIDATION_CLOSE_FACTOR));
+        }
+
+        // The close factor applies to the total borrowed value, so a single
+        // borrow can be liquidated further than its own close factor amount
+        let max_liquidation_value = self
+            .borrowed_value
+            .try_mul(Rate::from_percent(LIQUIDATION_CLOSE_FACTOR))?
+            .min(liquidity.market_value);
+        let max_liquidation_pct = max_liquidation_value.try_div(liquidity.market_value)?;
+        liquidity.borrowed_amount_wads.try_mul(max_liquidation_pct)
     }
 
-    /// Accrue interest
-    pub fn accrue_interest(&mut self, cumulative_borrow_rate: Decimal) -> ProgramResult {
-        if cumulative_borrow_rate < self.cumulative_borrow_rate_wads {
-            return Err(LendingError::NegativeInterestRate.into());
+    /// Withdraw collateral and remove the deposit if it is emptied
+    pub fn withdraw(&mut self, withdraw_amount: u64, collateral_index: usize) -> ProgramResult {
+        let collateral = &mut self.deposits[collateral_index];
+        if withdraw_amount == collateral.deposited_amount {
+            self.deposits.remove(collateral_index);
+        } else {
+            collateral.withdraw(withdraw_amount)?;
         }
+        Ok(())
+    }
 
-        let compounded_interest_rate: Rate = cumulative_borrow_rate
-            .try_div(self.cumulative_borrow_rate_wads)?
-            .try_into()?;
+    /// Withdraw collateral into a deposit position and remove the deposit if
+    /// it is emptied
+    pub fn withdraw_to_position(
+        &mut self,
+        withdraw_amount: u64,
+        collateral_index: usize,
+    ) -> ProgramResult {
+        let collateral = &mut self.deposits[collateral_index];
+        if withdraw_amount == collateral.deposited_amount {
+            self.deposits.remove(collateral_index);
+        } else {
+            collateral.withdraw_to_position(withdraw_amount)?;
+        }
+        Ok(())
+    }
 
-        self.borrowed_liquidity_wads = self
-            .borrowed_liquidity_wads
-            .try_mul(compounded_interest_rate)?;
+    /// Repay liquidity and remove the borrow if it is settled in full
+    pub fn repay(&mut self, settle_amount: Decimal, liquidity_index: usize) -> ProgramResult {
+        let liquidity = &mut self.borrows[liquidity_index];
+        if settle_amount == liquidity.borrowed_amount_wads {
+            self.borrows.remove(liquidity_index);
+        } else {
+            liquidity.repay(settle_amount)?;
+        }
+        Ok(())
+    }
 
-        self.cumulative_borrow_rate_wads = cumulative_borrow_rate;
+    /// Find the deposit for a reserve
+    pub fn find_collateral_in_deposits(
+        &self,
+        deposit_reserve: &Pubkey,
+    ) -> Result<(&ObligationCollateral, usize), ProgramError> {
+        if self.deposits.is_empty() {
+            return Err(LendingError::ObligationDepositsEmpty.into());
+        }
+        self.deposits
+            .iter()
+            .enumerate()
+            .find(|(_, collateral)| &collateral.deposit_reserve == deposit_reserve)
+            .map(|(index, collateral)| (collateral, index))
+            .ok_or_else(|| LendingError::InvalidObligationCollateral.into())
+    }
 
-        Ok(())
+    /// Find the deposit for a reserve, or add a new one if there is room
+    pub fn find_or_add_collateral_to_deposits(
+        &mut self,
+        deposit_reserve: Pubkey,
+    ) -> Result<&mut ObligationCollateral, ProgramError> {
+        if let Some(index) = self
+            .deposits
+            .iter()
+            .position(|collateral| collateral.deposit_reserve == deposit_reserve)
+        {
+            return Ok(&mut self.deposits[index]);
+        }
+        if self.deposits.len() + self.borrows.len() >= MAX_OBLIGATION_RESERVES {
+            return Err(LendingError::ObligationReserveLimit.into());
+        }
+        self.deposits
+            .push(ObligationCollateral::new(deposit_reserve));
+        Ok(self.deposits.last_mut().unwrap())
     }
 
-    /// Liquidate part of obligation
-    pub fn liquidate(&mut self, repay_amount: Decimal, withdraw_amount: u64) -> ProgramResult {
-        self.borrowed_liquidity_wads = self.borrowed_liquidity_wads.try_sub(repay_amount)?;
-        self.deposited_collateral_tokens = self
-            .deposited_collateral_tokens
-            .checked_sub(withdraw_amount)
-            .ok_or(LendingError::MathOverflow)?;
-        Ok(())
+    /// Find the borrow for a reserve
+    pub fn find_liquidity_in_borrows(
+        &self,
+        borrow_reserve: &Pubkey,
+    ) -> Result<(&ObligationLiquidity, usize), ProgramError> {
+        if self.borrows.is_empty() {
+            return Err(LendingError::ObligationBorrowsEmpty.into());
+        }
+        self.borrows
+            .iter()
+            .enumerate()
+            .find(|(_, liquidity)| &liquidity.borrow_reserve == borrow_reserve)
+            .map(|(index, liquidity)| (liquidity, index))
+            .ok_or_else(|| LendingError::InvalidObligationLiquidity.into())
     }
 
-    /// Repay borrowed tokens
-    pub fn repay(
+    /// Find the borrow for a reserve, or add a new one if there is room
+    pub fn find_or_add_liquidity_to_borrows(
         &mut self,
-        liquidity_amount: u64,
-        obligation_token_supply: u64,
-    ) -> Result<RepayResult, ProgramError> {
-        let decimal_repay_amount =
-            Decimal::from(liquidity_amount).min(self.borrowed_liquidity_wads);
-        let integer_repay_amount = decimal_repay_amount.try_ceil_u64()?;
-        if integer_repay_amount == 0 {
-            return Err(LendingError::ObligationEmpty.into());
+        borrow_reserve: Pubkey,
+        cumulative_borrow_rate_wads: Decimal,
+    ) -> Result<&mut ObligationLiquidity, ProgramError> {
+        if let Some(index) = self
+            .borrows
+            .iter()
+            .position(|liquidity| liquidity.borrow_reserve == borrow_reserve)
+        {
+            return Ok(&mut self.borrows[index]);
+        }
+        if self.deposits.len() + self.borrows.len() >= MAX_OBLIGATION_RESERVES {
+            return Err(LendingError::ObligationReserveLimit.into());
         }
+        self.borrows.push(ObligationLiquidity::new(
+            borrow_reserve,
+            cumulative_borrow_rate_wads,
+        ));
+        Ok(self.borrows.last_mut().unwrap())
+    }
+}
 
-        let repay_pct: Decimal = decimal_repay_amount.try_div(self.borrowed_liquidity_wads)?;
-        let collateral_withdraw_amount = {
-            let withdraw_amount: Decimal = repay_pct.try_mul(self.deposited_collateral_tokens)?;
-            withdraw_amount.try_floor_u64()?
-        };
+/// Create new obligation
+pub struct NewObligationParams {
+    /// Current slot
+    pub current_slot: Slot,
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Owner authority which can borrow liquidity and withdraw collateral
+    pub owner: Pubkey,
+}
+
+/// Obligation collateral state
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObligationCollateral {
+    /// Reserve which collateral tokens were deposited into
+    pub deposit_reserve: Pubkey,
+    /// Amount of collateral tokens deposited
+    pub deposited_amount: u64,
+    /// Collateral market value in quote currency
+    pub market_value: Decimal,
+    /// Part of the deposited amount that came from deposit positions, which
+    /// can only be withdrawn back into deposit positions
+    pub position_amount: u64,
+}
 
-        let obligation_token_amount = self.collateral_to_obligation_token_amount(
-            collateral_withdraw_amount,
-            obligation_token_supply,
-        )?;
+impl ObligationCollateral {
+    /// Create new obligation collateral
+    pub fn new(deposit_reserve: Pubkey) -> Self {
+        Self {
+            deposit_reserve,
+            deposited_amount: 0,
+            market_value: Decimal::zero(),
+            position_amount: 0,
+        }
+    }
 
-        self.liquidate(decimal_repay_amount, collateral_withdraw_amount)?;
+    /// Amount of deposited collateral that can be withdrawn as collateral tokens
+    pub fn transferable_amount(&self) -> u64 {
+        self.deposited_amount.saturating_sub(self.position_amount)
+    }
 
-        Ok(RepayResult {
-            collateral_withdraw_amount,
-            obligation_token_amount,
-            decimal_repay_amount,
-            integer_repay_amount,
-        })
+    /// Increase deposited collateral
+    pub fn deposit(&mut self, collateral_amount: u64) -> ProgramResult {
+        self.deposited_amount = self
+            .deposited_amount
+            .checked_add(collateral_amount)
+            .ok_or(LendingError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Increase deposited collateral with collateral from a deposit position
+    pub fn deposit_from_position(&mut self, collateral_amount: u64) -> ProgramResult {
+        self.deposit(collateral_amount)?;
+        self.position_amount = self
+            .position_amount
+            .checked_add(collateral_amount)
+            .ok_or(LendingError::MathOverflow)?;
+        Ok(())
     }
-}
 
-/// Obligation repay result
-pub struct RepayResult {
-    /// Amount of collateral to withdraw
-    pub collateral_withdraw_amount: u64,
-    /// Amount of obligation tokens to burn
-    pub obligation_token_amount: u64,
-    /// Amount that will be repaid as precise decimal
-    pub decimal_repay_amount: Decimal,
-    /// Amount that will be repaid as u64
-    pub integer_repay_amount: u64,
+    /// Decrease deposited collateral, taking collateral that came from deposit
+    /// positions last
+    pub fn withdraw(&mut self, collateral_amount: u64) -> ProgramResult {
+        self.deposited_amount = self
+            .deposited_amount
+            .checked_sub(collateral_amount)
+            .ok_or(LendingError::MathOverflow)?;
+        self.position_amount = self.position_amount.min(self.deposited_amount);
+        Ok(())
+    }
+
+    /// Decrease deposited collateral into a deposit position, taking collateral
+    /// that came from deposit positions first
+    pub fn withdraw_to_position(&mut self, collateral_amount: u64) -> ProgramResult {
+        self.deposited_amount = self
+            .deposited_amount
+            .checked_sub(collateral_amount)
+            .ok_or(LendingError::MathOverflow)?;
+        self.position_amount = self.position_amount.saturating_sub(collateral_amount);
+        Ok(())
+    }
 }
 
-/// Create new obligation
-pub struct NewObligationParams {
-    /// Collateral reserve address
-    pub collateral_reserve: Pubkey,
-    /// Borrow reserve address
+/// Obligation liquidity state
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObligationLiquidity {
+    /// Reserve which liquidity tokens were borrowed from
     pub borrow_reserve: Pubkey,
-    /// Obligation token mint address
-    pub token_mint: Pubkey,
-    /// Borrow rate used for calculating interest.
+    /// Borrow rate used for calculating interest
     pub cumulative_borrow_rate_wads: Decimal,
+    /// Amount of liquidity borrowed plus interest
+    pub borrowed_amount_wads: Decimal,
+    /// Liquidity market value in quote currency
+    pub market_value: Decimal,
+}
+
+impl ObligationLiquidity {
+    /// Create new obligation liquidity
+    pub fn new(borrow_reserve: Pubkey, cumulative_borrow_rate_wads: Decimal) -> Self {
+        Self {
+            borrow_reserve,
+            cumulative_borrow_rate_wads,
+            borrowed_amount_wads: Decimal::zero(),
+            market_value: Decimal::zero(),
+        }
+    }
+
+    /// Increase borrowed liquidity
+    pub fn borrow(&mut self, borrow_amount: Decimal) -> ProgramResult {
+        self.borrowed_amount_wads = self.borrowed_amount_wads.try_add(borrow_amount)?;
+        Ok(())
+    }
+
+    /// Decrease borrowed liquidity
+    pub fn repay(&mut self, settle_amount: Decimal) -> ProgramResult {
+        self.borrowed_amount_wads = self.borrowed_amount_wads.try_sub(settle_amount)?;
+        Ok(())
+    }
+
+    /// Maximum amount of loan that can be closed out by a liquidator due
+    /// to the remaining balance being too small to be liquidated normally.
+    pub fn max_closeable_amount(&self) -> Decimal {
+        if self.borrowed_amount_wads < Decimal::from(CLOSEABLE_AMOUNT) {
+            self.borrowed_amount_wads
+        } else {
+            Decimal::zero()
+        }
+    }
+
+    /// Accrue interest
+    pub fn accrue_interest(&mut self, cumulative_borrow_rate_wads: Decimal) -> ProgramResult {
+        if cumulative_borrow_rate_wads < self.cumulative_borrow_rate_wads {
+            return Err(LendingError::NegativeInterestRate.into());
+        }
+
+        let compounded_interest_rate: Rate = cumulative_borrow_rate_wads
+            .try_div(self.cumulative_borrow_rate_wads)?
+            .try_into()?;
+
+        self.borrowed_amount_wads = self
+            .borrowed_amount_wads
+            .try_mul(compounded_interest_rate)?;
+
+        self.cumulative_borrow_rate_wads = cumulative_borrow_rate_wads;
+
+        Ok(())
+    }
 }
 
 impl Sealed for Obligation {}
@@ -189,62 +446,177 @@ impl IsInitialized for Obligation {
     }
 }
 
-const OBLIGATION_LEN: usize = 265;
+const OBLIGATION_COLLATERAL_LEN: usize = 64; // 32 + 8 + 16 + 8
+const OBLIGATION_LIQUIDITY_LEN: usize = 80; // 32 + 16 + 16 + 16
+const OBLIGATION_DATA_LEN: usize = OBLIGATION_LIQUIDITY_LEN * MAX_OBLIGATION_RESERVES;
+const OBLIGATION_LEN: usize = 1004; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 1 + 1 + 800 + 64
 impl Pack for Obligation {
-    const LEN: usize = 265;
+    const LEN: usize = OBLIGATION_LEN;
 
-    /// Unpacks a byte buffer into a [ObligationInfo](struct.ObligationInfo.html).
+    /// Unpacks a byte buffer into an [Obligation](struct.Obligation.html).
     fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
         let input = array_ref![input, 0, OBLIGATION_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
         let (
             version,
-            deposited_collateral_tokens,
-            collateral_supply,
-            cumulative_borrow_rate,
-            borrowed_liquidity_wads,
-            borrow_reserve,
-            token_mint,
+            last_update_slot,
+            stale,
+            lending_market,
+            owner,
+            deposited_value,
+            borrowed_value,
+            allowed_borrow_value,
+            unhealthy_borrow_value,
+            deposits_len,
+            borrows_len,
+            data_flat,
             _padding,
-        ) = array_refs![input, 1, 8, 32, 16, 16, 32, 32, 128];
+        ) = array_refs![
+            input,
+            1,
+            8,
+            1,
+            32,
+            32,
+            16,
+            16,
+            16,
+            16,
+            1,
+            1,
+            OBLIGATION_DATA_LEN,
+            64
+        ];
+
+        let deposits_len = u8::from_le_bytes(*deposits_len) as usize;
+        let borrows_len = u8::from_le_bytes(*borrows_len) as usize;
+        if deposits_len + borrows_len > MAX_OBLIGATION_RESERVES {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut deposits = Vec::with_capacity(deposits_len);
+        let mut borrows = Vec::with_capacity(borrows_len);
+        let mut offset = 0;
+        for _ in 0..deposits_len {
+            let collateral_flat = array_ref![data_flat, offset, OBLIGATION_COLLATERAL_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (deposit_reserve, deposited_amount, market_value, position_amount) =
+                array_refs![collateral_flat, 32, 8, 16, 8];
+            deposits.push(ObligationCollateral {
+                deposit_reserve: Pubkey::new_from_array(*deposit_reserve),
+                deposited_amount: u64::from_le_bytes(*deposited_amount),
+                market_value: unpack_decimal(market_value),
+                position_amount: u64::from_le_bytes(*position_amount),
+            });
+            offset += OBLIGATION_COLLATERAL_LEN;
+        }
+        for _ in 0..borrows_len {
+            let liquidity_flat = array_ref![data_flat, offset, OBLIGATION_LIQUIDITY_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (borrow_reserve, cumulative_borrow_rate, borrowed_amount, market_value) =
+                array_refs![liquidity_flat, 32, 16, 16, 16];
+            borrows.push(ObligationLiquidity {
+                borrow_reserve: Pubkey::new_from_array(*borrow_reserve),
+                cumulative_borrow_rate_wads: unpack_decimal(cumulative_borrow_rate),
+                borrowed_amount_wads: unpack_decimal(borrowed_amount),
+                market_value: unpack_decimal(market_value),
+            });
+            offset += OBLIGATION_LIQUIDITY_LEN;
+        }
+
         Ok(Self {
             version: u8::from_le_bytes(*version),
-            deposited_collateral_tokens: u64::from_le_bytes(*deposited_collateral_tokens),
-            collateral_reserve: Pubkey::new_from_array(*collateral_supply),
-            cumulative_borrow_rate_wads: unpack_decimal(cumulative_borrow_rate),
-            borrowed_liquidity_wads: unpack_decimal(borrowed_liquidity_wads),
-            borrow_reserve: Pubkey::new_from_array(*borrow_reserve),
-            token_mint: Pubkey::new_from_array(*token_mint),
+            last_update_slot: u64::from_le_bytes(*last_update_slot),
+            stale: unpack_bool(stale)?,
+            lending_market: Pubkey::new_from_array(*lending_market),
+            owner: Pubkey::new_from_array(*owner),
+            deposits,
+            borrows,
+            deposited_value: unpack_decimal(deposited_value),
+            borrowed_value: unpack_decimal(borrowed_value),
+            allowed_borrow_value: unpack_decimal(allowed_borrow_value),
+            unhealthy_borrow_value: unpack_decimal(unhealthy_borrow_value),
         })
     }
 
     fn pack_into_slice(&self, output: &mut [u8]) {
         let output = array_mut_ref![output, 0, OBLIGATION_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
         let (
             version,
-            deposited_collateral_tokens,
-            collateral_supply,
-            cumulative_borrow_rate,
-            borrowed_liquidity_wads,
-            borrow_reserve,
-            token_mint,
+            last_update_slot,
+            stale,
+            lending_market,
+            owner,
+            deposited_value,
+            borrowed_value,
+            allowed_borrow_value,
+            unhealthy_borrow_value,
+            deposits_len,
+            borrows_len,
+            data_flat,
             _padding,
-        ) = mut_array_refs![output, 1, 8, 32, 16, 16, 32, 32, 128];
+        ) = mut_array_refs![
+            output,
+            1,
+            8,
+            1,
+            32,
+            32,
+            16,
+            16,
+            16,
+            16,
+            1,
+            1,
+            OBLIGATION_DATA_LEN,
+            64
+        ];
 
         *version = self.version.to_le_bytes();
-        *deposited_collateral_tokens = self.deposited_collateral_tokens.to_le_bytes();
-        collateral_supply.copy_from_slice(self.collateral_reserve.as_ref());
-        pack_decimal(self.cumulative_borrow_rate_wads, cumulative_borrow_rate);
-        pack_decimal(self.borrowed_liquidity_wads, borrowed_liquidity_wads);
-        borrow_reserve.copy_from_slice(self.borrow_reserve.as_ref());
-        token_mint.copy_from_slice(self.token_mint.as_ref());
+        *last_update_slot = self.last_update_slot.to_le_bytes();
+        pack_bool(self.stale, stale);
+        lending_market.copy_from_slice(self.lending_market.as_ref());
+        owner.copy_from_slice(self.owner.as_ref());
+        pack_decimal(self.deposited_value, deposited_value);
+        pack_decimal(self.borrowed_value, borrowed_value);
+        pack_decimal(self.allowed_borrow_value, allowed_borrow_value);
+        pack_decimal(self.unhealthy_borrow_value, unhealthy_borrow_value);
+        *deposits_len = (self.deposits.len() as u8).to_le_bytes();
+        *borrows_len = (self.borrows.len() as u8).to_le_bytes();
+
+        let mut offset = 0;
+        for collateral in &self.deposits {
+            let collateral_flat = array_mut_ref![data_flat, offset, OBLIGATION_COLLATERAL_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (deposit_reserve, deposited_amount, market_value, position_amount) =
+                mut_array_refs![collateral_flat, 32, 8, 16, 8];
+            deposit_reserve.copy_from_slice(collateral.deposit_reserve.as_ref());
+            *deposited_amount = collateral.deposited_amount.to_le_bytes();
+            pack_decimal(collateral.market_value, market_value);
+            *position_amount = collateral.position_amount.to_le_bytes();
+            offset += OBLIGATION_COLLATERAL_LEN;
+        }
+        for liquidity in &self.borrows {
+            let liquidity_flat = array_mut_ref![data_flat, offset, OBLIGATION_LIQUIDITY_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (borrow_reserve, cumulative_borrow_rate, borrowed_amount, market_value) =
+                mut_array_refs![liquidity_flat, 32, 16, 16, 16];
+            borrow_reserve.copy_from_slice(liquidity.borrow_reserve.as_ref());
+            pack_decimal(
+                liquidity.cumulative_borrow_rate_wads,
+                cumulative_borrow_rate,
+            );
+            pack_decimal(liquidity.borrowed_amount_wads, borrowed_amount);
+            pack_decimal(liquidity.market_value, market_value);
+            offset += OBLIGATION_LIQUIDITY_LEN;
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::math::TryAdd;
     use proptest::prelude::*;
 
     const MAX_COMPOUNDED_INTEREST: u64 = 100; // 10,000%
@@ -252,34 +624,222 @@ mod test {
     #[test]
     fn obligation_accrue_interest_failure() {
         assert_eq!(
-            Obligation {
+            ObligationLiquidity {
                 cumulative_borrow_rate_wads: Decimal::zero(),
-                ..Obligation::default()
+                ..ObligationLiquidity::default()
             }
             .accrue_interest(Decimal::one()),
             Err(LendingError::MathOverflow.into())
         );
 
         assert_eq!(
-            Obligation {
+            ObligationLiquidity {
                 cumulative_borrow_rate_wads: Decimal::from(2u64),
-                ..Obligation::default()
+                ..ObligationLiquidity::default()
             }
             .accrue_interest(Decimal::one()),
             Err(LendingError::NegativeInterestRate.into())
         );
 
         assert_eq!(
-            Obligation {
+            ObligationLiquidity {
                 cumulative_borrow_rate_wads: Decimal::one(),
-                borrowed_liquidity_wads: Decimal::from(u64::MAX),
-                ..Obligation::default()
+                borrowed_amount_wads: Decimal::from(u64::MAX),
+                ..ObligationLiquidity::default()
             }
             .accrue_interest(Decimal::from(10 * MAX_COMPOUNDED_INTEREST)),
             Err(LendingError::MathOverflow.into())
         );
     }
 
+    #[test]
+    fn obligation_refresh_values() {
+        let deposit_reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 100,
+                market_price: Decimal::from(2u64),
+                ..ReserveLiquidity::default()
+            },
+            collateral: ReserveCollateral {
+                mint_total_supply: 500,
+                ..ReserveCollateral::default()
+            },
+            config: ReserveConfig {
+                loan_to_value_ratio: 50,
+                liquidation_threshold: 80,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let borrow_reserve = Reserve {
+            cumulative_borrow_rate_wads: Decimal::from(2u64),
+            liquidity: ReserveLiquidity {
+                market_price: Decimal::one(),
+                ..ReserveLiquidity::default()
+            },
+            ..Reserve::default()
+        };
+
+        let mut obligation = Obligation::default();
+        let collateral = obligation
+            .find_or_add_collateral_to_deposits(Pubkey::new_unique())
+            .unwrap();
+        collateral.deposit(50).unwrap();
+        let deposit_reserve_pubkey = collateral.deposit_reserve;
+        let liquidity = obligation
+            .find_or_add_liquidity_to_borrows(Pubkey::new_unique(), Decimal::one())
+            .unwrap();
+        liquidity.borrow(Decimal::from(3u64)).unwrap();
+
+        let mut requested = vec![];
+        obligation
+            .refresh_values(|reserve_pubkey| -> Result<Reserve, ProgramError> {
+                requested.push(*reserve_pubkey);
+                if *reserve_pubkey == deposit_reserve_pubkey {
+                    Ok(deposit_reserve.clone())
+                } else {
+                    Ok(borrow_reserve.clone())
+                }
+            })
+            .unwrap();
+
+        assert_eq!(
+            requested,
+            vec![
+                obligation.deposits[0].deposit_reserve,
+                obligation.borrows[0].borrow_reserve
+            ]
+        );
+        // 50 collateral is 10 liquidity worth 2 each
+        assert_eq!(obligation.deposits[0].market_value, Decimal::from(20u64));
+        assert_eq!(obligation.deposited_value, Decimal::from(20u64));
+        assert_eq!(obligation.allowed_borrow_value, Decimal::from(10u64));
+        assert_eq!(obligation.unhealthy_borrow_value, Decimal::from(16u64));
+        // the borrow doubled with the cumulative borrow rate
+        assert_eq!(obligation.borrows[0].market_value, Decimal::from(6u64));
+        assert_eq!(obligation.borrowed_value, Decimal::from(6u64));
+
+        assert_eq!(
+            obligation
+                .refresh_values::<ProgramError, _>(|_| Err(LendingError::ReserveStale.into())),
+            Err(LendingError::ReserveStale.into())
+        );
+    }
+
+    #[test]
+    fn obligation_reserve_limit() {
+        let mut obligation = Obligation::default();
+        for _ in 0..MAX_OBLIGATION_RESERVES / 2 {
+            obligation
+                .find_or_add_collateral_to_deposits(Pubkey::new_unique())
+                .unwrap();
+            obligation
+                .find_or_add_liquidity_to_borrows(Pubkey::new_unique(), Decimal::one())
+                .unwrap();
+        }
+
+        // existing entries can still be found
+        let deposit_reserve = obligation.deposits[0].deposit_reserve;
+        obligation
+            .find_or_add_collateral_to_deposits(deposit_reserve)
+            .unwrap();
+
+        assert_eq!(
+            obligation.find_or_add_collateral_to_deposits(Pubkey::new_unique()),
+            Err(LendingError::ObligationReserveLimit.into())
+        );
+        assert_eq!(
+            obligation.find_or_add_liquidity_to_borrows(Pubkey::new_unique(), Decimal::one()),
+            Err(LendingError::ObligationReserveLimit.into())
+        );
+    }
+
+    #[test]
+    fn obligation_collateral_position_amount() {
+        let mut collateral = ObligationCollateral::new(Pubkey::new_unique());
+        collateral.deposit(100).unwrap();
+        collateral.deposit_from_position(50).unwrap();
+        assert_eq!(collateral.deposited_amount, 150);
+        assert_eq!(collateral.transferable_amount(), 100);
+
+        // collateral tokens are withdrawn from the transferable part
+        collateral.withdraw(60).unwrap();
+        assert_eq!(collateral.position_amount, 50);
+        assert_eq!(collateral.transferable_amount(), 40);
+
+        // withdrawals into deposit positions take the position part first
+        collateral.withdraw_to_position(70).unwrap();
+        assert_eq!(collateral.deposited_amount, 20);
+        assert_eq!(collateral.position_amount, 0);
+        assert_eq!(collateral.transferable_amount(), 20);
+
+        // liquidations can reach the position part once the rest is gone
+        collateral.deposit_from_position(30).unwrap();
+        collateral.withdraw(40).unwrap();
+        assert_eq!(collateral.deposited_amount, 10);
+        assert_eq!(collateral.position_amount, 10);
+        assert_eq!(collateral.transferable_amount(), 0);
+    }
+
+    #[test]
+    fn obligation_pack_unpack() {
+        let mut obligation = Obligation::new(NewObligationParams {
+            current_slot: 1,
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+        });
+        let collateral = obligation
+            .find_or_add_collateral_to_deposits(Pubkey::new_unique())
+            .unwrap();
+        collateral.deposit(100).unwrap();
+        collateral.deposit_from_position(20).unwrap();
+        obligation
+            .find_or_add_liquidity_to_borrows(Pubkey::new_unique(), Decimal::one())
+            .unwrap()
+            .borrow(Decimal::from(10u64))
+            .unwrap();
+        obligation.deposited_value = Decimal::from(120u64);
+        obligation.borrowed_value = Decimal::from(10u64);
+
+        let mut packed = [0u8; Obligation::LEN];
+        Obligation::pack(obligation.clone(), &mut packed).unwrap();
+        assert_eq!(Obligation::unpack(&packed).unwrap(), obligation);
+    }
+
+    #[test]
+    fn max_withdraw_value() {
+        let collateral = ObligationCollateral {
+            deposited_amount: 100,
+            market_value: Decimal::from(100u64),
+            ..ObligationCollateral::default()
+        };
+        let mut obligation = Obligation {
+            deposits: vec![collateral.clone()],
+            deposited_value: Decimal::from(100u64),
+            allowed_borrow_value: Decimal::from(50u64),
+            ..Obligation::default()
+        };
+
+        // without borrows, everything can be withdrawn
+        assert_eq!(
+            obligation
+                .max_withdraw_value(&collateral, Rate::from_percent(50))
+                .unwrap(),
+            Decimal::from(100u64)
+        );
+
+        obligation.borrows = vec![ObligationLiquidity::default()];
+        obligation.borrowed_value = Decimal::from(40u64);
+
+        // 10 of remaining borrow value at 50% LTV frees up 20 of collateral value
+        assert_eq!(
+            obligation
+                .max_withdraw_value(&collateral, Rate::from_percent(50))
+                .unwrap(),
+            Decimal::from(20u64)
+        );
+    }
+
     // Creates rates (r1, r2) where 0 < r1 <= r2 <= 100*r1
     prop_compose! {
         fn cumulative_rates()(rate in 1..=u128::MAX)(
@@ -294,71 +854,49 @@ mod test {
 
     // Creates liquidity amounts (repay, borrow) where repay < borrow
     prop_compose! {
-        fn repay_partial_amounts()(repay in 1..=u64::MAX)(
-            liquidity_amount in Just(repay),
-            borrowed_liquidity in (WAD as u128 * repay as u128 + 1)..=MAX_BORROWED
-        ) -> (u64, u128) {
-            (liquidity_amount, borrowed_liquidity)
-        }
-    }
-
-    // Creates liquidity amounts (repay, borrow) where repay >= borrow
-    prop_compose! {
-        fn repay_full_amounts()(repay in 1..=u64::MAX)(
-            liquidity_amount in Just(repay),
-            borrowed_liquidity in 0..=(WAD as u128 * repay as u128)
-        ) -> (u64, u128) {
-            (liquidity_amount, borrowed_liquidity)
-        }
-    }
-
-    // Creates collateral amounts (collateral, obligation tokens) where c <= ot
-    prop_compose! {
-        fn collateral_amounts()(collateral in 1..=u64::MAX)(
-            deposited_collateral_tokens in Just(collateral),
-            obligation_tokens in collateral..=u64::MAX
-        ) -> (u64, u64) {
-            (deposited_collateral_tokens, obligation_tokens)
+        fn repay_partial_amounts()(amount in 1..=u64::MAX)(
+            repay_amount in Just(WAD as u128 * amount as u128),
+            borrowed_amount in (WAD as u128 * amount as u128 + 1)..=MAX_BORROWED,
+        ) -> (u128, u128) {
+            (repay_amount, borrowed_amount)
         }
     }
 
     proptest! {
         #[test]
         fn repay_partial(
-            (liquidity_amount, borrowed_liquidity) in repay_partial_amounts(),
-            (deposited_collateral_tokens, obligation_tokens) in collateral_amounts(),
+            (repay_amount, borrowed_amount) in repay_partial_amounts(),
         ) {
-            let borrowed_liquidity_wads = Decimal::from_scaled_val(borrowed_liquidity);
-            let mut state = Obligation { deposited_collateral_tokens, borrowed_liquidity_wads, ..Obligation::default() };
-
-            let repay_result = state.repay(liquidity_amount, obligation_tokens)?;
-            assert!(repay_result.decimal_repay_amount <= Decimal::from(repay_result.integer_repay_amount));
-            assert!(repay_result.collateral_withdraw_amount < deposited_collateral_tokens);
-            assert!(repay_result.obligation_token_amount < obligation_tokens);
-            assert!(state.borrowed_liquidity_wads < borrowed_liquidity_wads);
-            assert!(state.borrowed_liquidity_wads > Decimal::zero());
-            assert!(state.deposited_collateral_tokens > 0);
-
-            let obligation_token_rate = Decimal::from(repay_result.obligation_token_amount).try_div(Decimal::from(obligation_tokens))?;
-            let collateral_withdraw_rate = Decimal::from(repay_result.collateral_withdraw_amount).try_div(Decimal::from(deposited_collateral_tokens))?;
-            assert!(obligation_token_rate <= collateral_withdraw_rate);
+            let borrowed_amount_wads = Decimal::from_scaled_val(borrowed_amount);
+            let repay_amount_wads = Decimal::from_scaled_val(repay_amount);
+            let mut obligation = Obligation {
+                borrows: vec![ObligationLiquidity {
+                    borrowed_amount_wads,
+                    ..ObligationLiquidity::default()
+                }],
+                ..Obligation::default()
+            };
+
+            obligation.repay(repay_amount_wads, 0)?;
+            assert!(obligation.borrows[0].borrowed_amount_wads < borrowed_amount_wads);
+            assert!(obligation.borrows[0].borrowed_amount_wads > Decimal::zero());
         }
 
         #[test]
         fn repay_full(
-            (liquidity_amount, borrowed_liquidity) in repay_full_amounts(),
-            (deposited_collateral_tokens, obligation_tokens) in collateral_amounts(),
+            borrowed_amount in 0..=MAX_BORROWED,
         ) {
-            let borrowed_liquidity_wads = Decimal::from_scaled_val(borrowed_liquidity);
-            let mut state = Obligation { deposited_collateral_tokens, borrowed_liquidity_wads, ..Obligation::default() } ;
-
-            let repay_result = state.repay(liquidity_amount, obligation_tokens)?;
-            assert!(repay_result.decimal_repay_amount <= Decimal::from(repay_result.integer_repay_amount));
-            assert_eq!(repay_result.collateral_withdraw_amount, deposited_collateral_tokens);
-            assert_eq!(repay_result.obligation_token_amount, obligation_tokens);
-            assert_eq!(repay_result.decimal_repay_amount, borrowed_liquidity_wads);
-            assert_eq!(state.borrowed_liquidity_wads, Decimal::zero());
-            assert_eq!(state.deposited_collateral_tokens, 0);
+            let borrowed_amount_wads = Decimal::from_scaled_val(borrowed_amount);
+            let mut obligation = Obligation {
+                borrows: vec![ObligationLiquidity {
+                    borrowed_amount_wads,
+                    ..ObligationLiquidity::default()
+                }],
+                ..Obligation::default()
+            };
+
+            obligation.repay(borrowed_amount_wads, 0)?;
+            assert_eq!(obligation.borrows.len(), 0);
         }
 
         #[test]
@@ -366,17 +904,21 @@ mod test {
             borrowed_liquidity in 0..=u64::MAX,
             (current_borrow_rate, new_borrow_rate) in cumulative_rates(),
         ) {
-            let borrowed_liquidity_wads = Decimal::from(borrowed_liquidity);
+            let borrowed_amount_wads = Decimal::from(borrowed_liquidity);
             let cumulative_borrow_rate_wads = Decimal::one().try_add(Decimal::from_scaled_val(current_borrow_rate))?;
-            let mut state = Obligation { cumulative_borrow_rate_wads, borrowed_liquidity_wads, ..Obligation::default() };
+            let mut liquidity = ObligationLiquidity {
+                cumulative_borrow_rate_wads,
+                borrowed_amount_wads,
+                ..ObligationLiquidity::default()
+            };
 
             let next_cumulative_borrow_rate = Decimal::one().try_add(Decimal::from_scaled_val(new_borrow_rate))?;
-            state.accrue_interest(next_cumulative_borrow_rate)?;
+            liquidity.accrue_interest(next_cumulative_borrow_rate)?;
 
             if next_cumulative_borrow_rate > cumulative_borrow_rate_wads {
-                assert!(state.borrowed_liquidity_wads > borrowed_liquidity_wads);
+                assert!(liquidity.borrowed_amount_wads > borrowed_amount_wads);
             } else {
-                assert!(state.borrowed_liquidity_wads == borrowed_liquidity_wads);
+                assert!(liquidity.borrowed_amount_wads == borrowed_amount_wads);
             }
         }
     }
@@ -1,7 +1,6 @@
 use super::*;
 use crate::{
     error::LendingError,
-    instruction::BorrowAmountType,
     math::{Decimal, Rate, TryAdd, TryDiv, TryMul, TrySub},
 };
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
@@ -13,6 +12,7 @@ use solana_program::{
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
+use spl_token_swap::oracle::PriceAccumulator;
 use std::convert::{TryFrom, TryInto};
 
 /// Percentage of an obligation that can be repaid during each liquidation call
@@ -32,14 +32,17 @@ pub struct Reserve {
     pub cumulative_borrow_rate_wads: Decimal,
     /// Lending market address
     pub lending_market: Pubkey,
-    /// Dex market state account
-    pub dex_market: COption<Pubkey>,
+    /// Price oracle account, as selected by the reserve config
+    pub oracle: COption<Pubkey>,
     /// Reserve liquidity info
     pub liquidity: ReserveLiquidity,
     /// Reserve collateral info
     pub collateral: ReserveCollateral,
     /// Reserve configuration values
     pub config: ReserveConfig,
+    /// Cumulative prices of the token swap oracle at the start of the
+    /// averaging period, unused by other oracles
+    pub oracle_observation: PriceAccumulator,
 }
 
 impl Reserve {
@@ -50,7 +53,7 @@ impl Reserve {
             lending_market,
             collateral: collateral_info,
             liquidity: liquidity_info,
-            dex_market,
+            oracle,
             config,
         } = params;
 
@@ -61,207 +64,149 @@ impl Reserve {
             lending_market,
             collateral: collateral_info,
             liquidity: liquidity_info,
-            dex_market,
+            oracle,
             config,
+            oracle_observation: PriceAccumulator::default(),
         }
     }
 
     /// Calculate the current borrow rate
     pub fn current_borrow_rate(&self) -> Result<Rate, ProgramError> {
         let utilization_rate = self.liquidity.utilization_rate()?;
-        let optimal_utilization_rate = Rate::from_percent(self.config.optimal_utilization_rate);
-        let low_utilization = utilization_rate < optimal_utilization_rate;
-        if low_utilization || self.config.optimal_utilization_rate == 100 {
-            let normalized_rate = utilization_rate.try_div(optimal_utilization_rate)?;
-            let min_rate = Rate::from_percent(self.config.min_borrow_rate);
-            let rate_range =
-                Rate::from_percent(self.config.optimal_borrow_rate - self.config.min_borrow_rate);
-
-            Ok(normalized_rate.try_mul(rate_range)?.try_add(min_rate)?)
-        } else {
-            let normalized_rate = utilization_rate
-                .try_sub(optimal_utilization_rate)?
-                .try_div(Rate::from_percent(
-                    100 - self.config.optimal_utilization_rate,
-                ))?;
-            let min_rate = Rate::from_percent(self.config.optimal_borrow_rate);
-            let rate_range =
-                Rate::from_percent(self.config.max_borrow_rate - self.config.optimal_borrow_rate);
-
-            Ok(normalized_rate.try_mul(rate_range)?.try_add(min_rate)?)
-        }
+        self.config.borrow_rate_curve.borrow_rate(utilization_rate)
     }
 
-    /// Liquidate part of an unhealthy obligation
-    pub fn liquidate_obligation(
-        &self,
-        obligation: &Obligation,
-        liquidate_amount: u64,
-        liquidity_token_mint: &Pubkey,
-        token_converter: impl TokenConverter,
-    ) -> Result<LiquidateResult, ProgramError> {
-        Self::_liquidate_obligation(
-            obligation,
-            liquidate_amount,
-            liquidity_token_mint,
-            self.collateral_exchange_rate()?,
-            &self.config,
-            token_converter,
-        )
+    /// Calculate the market value of a liquidity amount in quote currency
+    pub fn market_value(&self, liquidity_amount: Decimal) -> Result<Decimal, ProgramError> {
+        liquidity_amount.try_mul(self.liquidity.market_price)
     }
 
-    fn _liquidate_obligation(
-        obligation: &Obligation,
+    /// Calculate the fees and received amount for a borrow, checking that its
+    /// value fits within the remaining borrow value of an obligation
+    pub fn calculate_borrow(
+        &self,
         liquidity_amount: u64,
-        liquidity_token_mint: &Pubkey,
-        collateral_exchange_rate: CollateralExchangeRate,
-        collateral_reserve_config: &ReserveConfig,
-        mut token_converter: impl TokenConverter,
-    ) -> Result<LiquidateResult, ProgramError> {
-        // Check obligation health
-        let borrow_token_price = token_converter.best_price(liquidity_token_mint)?;
-        let liquidation_threshold =
-            Rate::from_percent(collateral_reserve_config.liquidation_threshold);
-        let obligation_loan_to_value =
-            obligation.loan_to_value(collateral_exchange_rate, borrow_token_price)?;
-        if obligation_loan_to_value < liquidation_threshold.into() {
-            return Err(LendingError::HealthyObligation.into());
-        }
-
-        // Special handling for small, closeable obligations
-        let max_closeable_amount = obligation.max_closeable_amount()?;
-        let close_amount = liquidity_amount.min(max_closeable_amount);
-        if close_amount > 0 {
-            return Ok(LiquidateResult {
-                withdraw_amount: obligation.deposited_collateral_tokens,
-                repay_amount: close_amount,
-                settle_amount: obligation.borrowed_liquidity_wads,
-            });
+        remaining_borrow_value: Decimal,
+    ) -> Result<BorrowResult, ProgramError> {
+        let borrow_amount = Decimal::from(liquidity_amount);
+        if self.market_value(borrow_amount)? > remaining_borrow_value {
+            return Err(LendingError::BorrowTooLarge.into());
         }
 
-        // Calculate the amount of liquidity that will be repaid
-        let max_liquidation_amount = obligation.max_liquidation_amount()?;
-        let repay_amount = liquidity_amount.min(max_liquidation_amount);
-        let decimal_repay_amount = Decimal::from(repay_amount);
-
-        // Calculate the amount of collateral that will be received
-        let withdraw_amount = {
-            let receive_liquidity_amount =
-                token_converter.convert(decimal_repay_amount, liquidity_token_mint)?;
-            let collateral_amount = collateral_exchange_rate
-                .decimal_liquidity_to_collateral(receive_liquidity_amount)?;
-            let bonus_rate = Rate::from_percent(collateral_reserve_config.liquidation_bonus);
-            let bonus_amount = collateral_amount.try_mul(bonus_rate)?;
-            let withdraw_amount = collateral_amount.try_add(bonus_amount)?;
-            let withdraw_amount =
-                withdraw_amount.min(obligation.deposited_collateral_tokens.into());
-            if repay_amount == max_liquidation_amount {
-                withdraw_amount.try_ceil_u64()?
-            } else {
-                withdraw_amount.try_floor_u64()?
-            }
-        };
+        let (borrow_fee, host_fee) = self.config.fees.calculate_borrow_fees(liquidity_amount)?;
+        let receive_amount = liquidity_amount
+            .checked_sub(borrow_fee)
+            .ok_or(LendingError::MathOverflow)?;
 
-        if withdraw_amount > 0 {
-            // TODO: charge less liquidity if withdraw value exceeds loan collateral
-            let settle_amount = if withdraw_amount == obligation.deposited_collateral_tokens {
-                obligation.borrowed_liquidity_wads
-            } else {
-                decimal_repay_amount
-            };
+        Ok(BorrowResult {
+            borrow_amount,
+            receive_amount,
+            borrow_fee,
+            host_fee,
+        })
+    }
 
-            Ok(LiquidateResult {
-                withdraw_amount,
-                settle_amount,
-                repay_amount,
-            })
-        } else {
-            Err(LendingError::LiquidationTooSmall.into())
-        }
+    /// Calculate the amount of liquidity that will be repaid and settled from a borrow
+    pub fn calculate_repay(
+        &self,
+        liquidity_amount: u64,
+        borrowed_amount: Decimal,
+    ) -> Result<RepayResult, ProgramError> {
+        let settle_amount = Decimal::from(liquidity_amount).min(borrowed_amount);
+        let repay_amount = settle_amount.try_ceil_u64()?;
+
+        Ok(RepayResult {
+            settle_amount,
+            repay_amount,
+        })
     }
 
-    /// Create new loan
-    pub fn create_loan(
+    /// Calculate the amount of liquidity to repay and collateral to withdraw
+    /// when liquidating an unhealthy obligation, where this reserve is the
+    /// reserve that collateral is withdrawn from
+    pub fn calculate_liquidation(
         &self,
-        token_amount: u64,
-        token_amount_type: BorrowAmountType,
-        token_converter: impl TokenConverter,
-        borrow_amount_token_mint: &Pubkey,
-    ) -> Result<LoanResult, ProgramError> {
-        let (borrow_amount, mut collateral_amount) = match token_amount_type {
-            BorrowAmountType::CollateralDepositAmount => {
-                let collateral_amount = token_amount;
-                let borrow_amount =
-                    self.allowed_borrow_for_collateral(collateral_amount, token_converter)?;
-                if borrow_amount == 0 {
-                    return Err(LendingError::InvalidAmount.into());
-                }
-                (borrow_amount, collateral_amount)
-            }
-            BorrowAmountType::LiquidityBorrowAmount => {
-                let borrow_amount = token_amount;
-                let collateral_amount = self.required_collateral_for_borrow(
-                    borrow_amount,
-                    borrow_amount_token_mint,
-                    token_converter,
-                )?;
-                if collateral_amount == 0 {
-                    return Err(LendingError::InvalidAmount.into());
-                }
-                (borrow_amount, collateral_amount)
-            }
+        liquidity_amount: u64,
+        obligation: &Obligation,
+        liquidity: &ObligationLiquidity,
+        collateral: &ObligationCollateral,
+    ) -> Result<LiquidateResult, ProgramError> {
+        // Special handling for small, closeable borrows
+        let max_closeable_amount = liquidity.max_closeable_amount();
+        let settle_amount = if max_closeable_amount > Decimal::zero() {
+            max_closeable_amount
+        } else {
+            Decimal::from(liquidity_amount).min(obligation.max_liquidation_amount(liquidity)?)
         };
+        if settle_amount == Decimal::zero() {
+            return Err(LendingError::LiquidationTooSmall.into());
+        }
 
-        let (origination_fee, host_fee) =
-            self.config.fees.calculate_borrow_fees(collateral_amount)?;
+        // Calculate the value of collateral that will be received, including the bonus
+        let settle_pct = settle_amount.try_div(liquidity.borrowed_amount_wads)?;
+        let bonus_rate = Rate::one().try_add(Rate::from_percent(self.config.liquidation_bonus))?;
+        let withdraw_value = liquidity
+            .market_value
+            .try_mul(settle_pct)?
+            .try_mul(bonus_rate)?;
+
+        let (settle_amount, withdraw_amount) = if withdraw_value >= collateral.market_value {
+            // Only settle the part of the borrow that the remaining collateral covers
+            let repay_pct = collateral.market_value.try_div(withdraw_value)?;
+            (
+                settle_amount.try_mul(repay_pct)?,
+                collateral.deposited_amount,
+            )
+        } else {
+            let withdraw_pct = withdraw_value.try_div(collateral.market_value)?;
+            (
+                settle_amount,
+                withdraw_pct
+                    .try_mul(collateral.deposited_amount)?
+                    .try_floor_u64()?,
+            )
+        };
 
-        collateral_amount = collateral_amount
-            .checked_sub(origination_fee)
-            .ok_or(LendingError::MathOverflow)?;
+        let repay_amount = settle_amount.try_ceil_u64()?;
+        if repay_amount == 0 || withdraw_amount == 0 {
+            return Err(LendingError::LiquidationTooSmall.into());
+        }
 
-        Ok(LoanResult {
-            borrow_amount,
-            collateral_amount,
-            origination_fee,
-            host_fee,
+        Ok(LiquidateResult {
+            withdraw_amount,
+            settle_amount,
+            repay_amount,
         })
     }
 
-    /// Calculate allowed borrow for collateral
-    pub fn allowed_borrow_for_collateral(
-        &self,
-        collateral_amount: u64,
-        converter: impl TokenConverter,
-    ) -> Result<u64, ProgramError> {
-        let collateral_exchange_rate = self.collateral_exchange_rate()?;
-        let collateral_amount = Decimal::from(collateral_amount)
-            .try_mul(Rate::from_percent(self.config.loan_to_value_ratio))?;
-        let liquidity_amount =
-            collateral_exchange_rate.decimal_collateral_to_liquidity(collateral_amount)?;
-
-        let borrow_amount = converter
-            .convert(liquidity_amount, &self.liquidity.mint_pubkey)?
-            .try_floor_u64()?;
-
-        Ok(borrow_amount)
+    /// Check that depositing liquidity keeps the reserve total supply within its supply cap
+    pub fn check_supply_cap(&self, liquidity_amount: u64) -> ProgramResult {
+        if self.config.supply_cap == 0 {
+            return Ok(());
+        }
+        let total_supply = self
+            .liquidity
+            .total_supply()?
+            .try_add(Decimal::from(liquidity_amount))?;
+        if total_supply > Decimal::from(self.config.supply_cap) {
+            return Err(LendingError::ReserveCapExceeded.into());
+        }
+        Ok(())
     }
 
-    /// Calculate required collateral for borrow
-    pub fn required_collateral_for_borrow(
-        &self,
-        borrow_amount: u64,
-        borrow_amount_token_mint: &Pubkey,
-        converter: impl TokenConverter,
-    ) -> Result<u64, ProgramError> {
-        let collateral_exchange_rate = self.collateral_exchange_rate()?;
-        let liquidity_amount =
-            converter.convert(Decimal::from(borrow_amount), borrow_amount_token_mint)?;
-        let collateral_amount = collateral_exchange_rate
-            .decimal_liquidity_to_collateral(liquidity_amount)?
-            .try_div(Rate::from_percent(self.config.loan_to_value_ratio))?
-            .try_ceil_u64()?;
-
-        Ok(collateral_amount)
+    /// Check that borrowing liquidity keeps the reserve total borrows within its borrow cap
+    pub fn check_borrow_cap(&self, liquidity_amount: u64) -> ProgramResult {
+        if self.config.borrow_cap == 0 {
+            return Ok(());
+        }
+        let borrowed_amount = self
+            .liquidity
+            .borrowed_amount_wads
+            .try_add(Decimal::from(liquidity_amount))?;
+        if borrowed_amount > Decimal::from(self.config.borrow_cap) {
+            return Err(LendingError::ReserveCapExceeded.into());
+        }
+        Ok(())
     }
 
     /// Record deposited liquidity and return amount of collateral tokens to mint
@@ -298,10 +243,18 @@ impl Reserve {
             let current_borrow_rate = self.current_borrow_rate()?;
             let compounded_interest_rate =
                 self.compound_interest(current_borrow_rate, slots_elapsed)?;
-            self.liquidity.borrowed_amount_wads = self
+            let previous_borrowed_amount = self.liquidity.borrowed_amount_wads;
+            self.liquidity.borrowed_amount_wads =
+                previous_borrowed_amount.try_mul(compounded_interest_rate)?;
+
+            // Set aside the reserve factor share of accrued interest for the protocol
+            let protocol_fees = self
                 .liquidity
                 .borrowed_amount_wads
-                .try_mul(compounded_interest_rate)?;
+                .try_sub(previous_borrowed_amount)?
+                .try_mul(Rate::from_percent(self.config.reserve_factor))?;
+            self.liquidity.protocol_fees_wads =
+                self.liquidity.protocol_fees_wads.try_add(protocol_fees)?;
         }
         Ok(())
     }
@@ -346,31 +299,38 @@ pub struct NewReserveParams {
     pub collateral: ReserveCollateral,
     /// Reserve liquidity info
     pub liquidity: ReserveLiquidity,
-    /// Optional dex market address
-    pub dex_market: COption<Pubkey>,
+    /// Optional price oracle account
+    pub oracle: COption<Pubkey>,
     /// Reserve configuration values
     pub config: ReserveConfig,
 }
 
-/// Create loan result
-pub struct LoanResult {
-    /// Approved borrow amount
-    pub borrow_amount: u64,
-    /// Required collateral amount
-    pub collateral_amount: u64,
-    /// Loan origination fee
-    pub origination_fee: u64,
-    /// Host fee portion of origination fee
+/// Borrow liquidity result
+pub struct BorrowResult {
+    /// Amount of liquidity added to the obligation borrow, including fees
+    pub borrow_amount: Decimal,
+    /// Amount of liquidity received by the borrower
+    pub receive_amount: u64,
+    /// Borrow fee, including the host fee
+    pub borrow_fee: u64,
+    /// Host fee portion of the borrow fee
     pub host_fee: u64,
 }
 
+/// Repay liquidity result
+pub struct RepayResult {
+    /// Amount of liquidity that is settled from the obligation borrow
+    pub settle_amount: Decimal,
+    /// Amount that will be repaid as u64
+    pub repay_amount: u64,
+}
+
 /// Liquidate obligation result
 #[derive(Debug)]
 pub struct LiquidateResult {
     /// Amount of collateral to withdraw in exchange for repay amount
     pub withdraw_amount: u64,
-    /// Amount of liquidity that is settled from the obligation. It includes
-    /// the amount of loan that was defaulted if collateral is depleted.
+    /// Amount of liquidity that is settled from the obligation borrow
     pub settle_amount: Decimal,
     /// Amount that will be repaid as u64
     pub repay_amount: u64,
@@ -385,27 +345,45 @@ pub struct ReserveLiquidity {
     pub mint_decimals: u8,
     /// Reserve liquidity supply address
     pub supply_pubkey: Pubkey,
+    /// Reserve liquidity fee receiver address
+    pub fee_receiver: Pubkey,
     /// Reserve liquidity available
     pub available_amount: u64,
     /// Reserve liquidity borrowed
     pub borrowed_amount_wads: Decimal,
+    /// Reserve liquidity market price in quote currency, per smallest unit
+    pub market_price: Decimal,
+    /// Reserve liquidity owned by the protocol from its share of accrued interest
+    pub protocol_fees_wads: Decimal,
 }
 
 impl ReserveLiquidity {
     /// New reserve liquidity info
-    pub fn new(mint_pubkey: Pubkey, mint_decimals: u8, supply_pubkey: Pubkey) -> Self {
+    pub fn new(
+        mint_pubkey: Pubkey,
+        mint_decimals: u8,
+        supply_pubkey: Pubkey,
+        fee_receiver: Pubkey,
+        market_price: Decimal,
+    ) -> Self {
         Self {
             mint_pubkey,
             mint_decimals,
             supply_pubkey,
+            fee_receiver,
             available_amount: 0,
             borrowed_amount_wads: Decimal::zero(),
+            market_price,
+            protocol_fees_wads: Decimal::zero(),
         }
     }
 
-    /// Calculate the total reserve supply including active loans
+    /// Calculate the total reserve supply including active loans, excluding
+    /// liquidity owned by the protocol
     pub fn total_supply(&self) -> Result<Decimal, ProgramError> {
-        Decimal::from(self.available_amount).try_add(self.borrowed_amount_wads)
+        Decimal::from(self.available_amount)
+            .try_add(self.borrowed_amount_wads)?
+            .try_sub(self.protocol_fees_wads)
     }
 
     /// Add new borrow amount to total borrows
@@ -432,6 +410,25 @@ impl ReserveLiquidity {
         Ok(())
     }
 
+    /// Subtract protocol fees from available liquidity and return the amount to withdraw
+    pub fn withdraw_protocol_fees(&mut self, liquidity_amount: u64) -> Result<u64, ProgramError> {
+        let withdraw_amount = self
+            .protocol_fees_wads
+            .try_floor_u64()?
+            .min(liquidity_amount)
+            .min(self.available_amount);
+        if withdraw_amount == 0 {
+            return Err(LendingError::InsufficientLiquidity.into());
+        }
+
+        self.available_amount -= withdraw_amount;
+        self.protocol_fees_wads = self
+            .protocol_fees_wads
+            .try_sub(Decimal::from(withdraw_amount))?;
+
+        Ok(withdraw_amount)
+    }
+
     /// Calculate the liquidity utilization rate of the reserve
     pub fn utilization_rate(&self) -> Result<Rate, ProgramError> {
         let total_supply = self.total_supply()?;
@@ -451,17 +448,14 @@ pub struct ReserveCollateral {
     pub mint_total_supply: u64,
     /// Reserve collateral supply address
     pub supply_pubkey: Pubkey,
-    /// Reserve collateral fees receiver address
-    pub fees_receiver: Pubkey,
 }
 
 impl ReserveCollateral {
     /// New reserve collateral info
-    pub fn new(mint_pubkey: Pubkey, supply_pubkey: Pubkey, fees_receiver: Pubkey) -> Self {
+    pub fn new(mint_pubkey: Pubkey, supply_pubkey: Pubkey) -> Self {
         Self {
             mint_pubkey,
             supply_pubkey,
-            fees_receiver,
             ..Self::default()
         }
     }
@@ -525,51 +519,256 @@ impl From<CollateralExchangeRate> for Rate {
 /// Reserve configuration values
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct ReserveConfig {
-    /// Optimal utilization rate as a percent
-    pub optimal_utilization_rate: u8,
     /// The ratio of the loan to the value of the collateral as a percent
     pub loan_to_value_ratio: u8,
     /// The percent discount the liquidator gets when buying collateral for an unhealthy obligation
     pub liquidation_bonus: u8,
     /// The percent at which an obligation is considered unhealthy
     pub liquidation_threshold: u8,
-    /// Min borrow APY
-    pub min_borrow_rate: u8,
-    /// Optimal (utilization) borrow APY
-    pub optimal_borrow_rate: u8,
-    /// Max borrow APY
-    pub max_borrow_rate: u8,
+    /// Borrow APY as a piecewise linear function of utilization
+    pub borrow_rate_curve: BorrowRateCurve,
     /// Program owner fees assessed, separate from gains due to interest accrual
     pub fees: ReserveFees,
+    /// Price oracle used to value the reserve liquidity
+    pub oracle: OracleConfig,
+    /// Disable liquidity deposits and obligation collateral deposits
+    pub deposits_disabled: bool,
+    /// Disable borrows and flash loans of reserve liquidity
+    pub borrows_disabled: bool,
+    /// Disable liquidations which repay to or withdraw from the reserve
+    pub liquidations_disabled: bool,
+    /// Maximum total liquidity supplied to the reserve, or zero for no cap
+    pub supply_cap: u64,
+    /// Maximum total liquidity borrowed from the reserve, or zero for no cap
+    pub borrow_cap: u64,
+    /// Percent of accrued interest that is set aside for the protocol
+    pub reserve_factor: u8,
+}
+
+/// Number of points on a borrow rate curve
+pub const BORROW_RATE_CURVE_POINTS: usize = 8;
+
+/// Point on a borrow rate curve
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BorrowRateCurvePoint {
+    /// Utilization rate in basis points
+    pub utilization_rate_bps: u16,
+    /// Borrow APY at this utilization rate in basis points
+    pub borrow_rate_bps: u16,
+}
+
+/// Borrow APY as a piecewise linear function of utilization
+///
+/// The curve starts at 0% utilization and increases utilization strictly
+/// until it reaches 100%, after which the last point is repeated to fill the
+/// unused points.  Borrow rates may not decrease as utilization increases, so
+/// the segments near 100% utilization can be made steep to discourage
+/// draining the reserve.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BorrowRateCurve {
+    /// Points on the curve, in order of utilization
+    pub points: [BorrowRateCurvePoint; BORROW_RATE_CURVE_POINTS],
+}
+
+impl Default for BorrowRateCurve {
+    fn default() -> Self {
+        let mut points = [BorrowRateCurvePoint {
+            utilization_rate_bps: 10_000,
+            borrow_rate_bps: 0,
+        }; BORROW_RATE_CURVE_POINTS];
+        points[0].utilization_rate_bps = 0;
+        Self { points }
+    }
+}
+
+impl BorrowRateCurve {
+    /// Create a curve from its used points, repeating the last point to fill
+    /// the unused points
+    pub fn from_points(points: &[BorrowRateCurvePoint]) -> Option<Self> {
+        let last_point = *points.last()?;
+        if points.len() > BORROW_RATE_CURVE_POINTS {
+            return None;
+        }
+        let mut curve = Self {
+            points: [last_point; BORROW_RATE_CURVE_POINTS],
+        };
+        curve.points[..points.len()].copy_from_slice(points);
+        Some(curve)
+    }
+
+    /// Check that the curve starts at 0% utilization, ends at 100% utilization,
+    /// and has strictly increasing utilization and non-decreasing borrow rates
+    pub fn is_valid(&self) -> bool {
+        if self.points[0].utilization_rate_bps != 0
+            || self.points[BORROW_RATE_CURVE_POINTS - 1].utilization_rate_bps != 10_000
+        {
+            return false;
+        }
+        self.points.windows(2).all(|window| {
+            let (start, end) = (window[0], window[1]);
+            if start.utilization_rate_bps == 10_000 {
+                end == start
+            } else {
+                end.utilization_rate_bps > start.utilization_rate_bps
+                    && end.utilization_rate_bps <= 10_000
+                    && end.borrow_rate_bps >= start.borrow_rate_bps
+            }
+        })
+    }
+
+    /// Calculate the borrow rate at a utilization rate by interpolating
+    /// between the points of the curve
+    pub fn borrow_rate(&self, utilization_rate: Rate) -> Result<Rate, ProgramError> {
+        for window in self.points.windows(2) {
+            let (start, end) = (window[0], window[1]);
+            if end.utilization_rate_bps <= start.utilization_rate_bps {
+                continue;
+            }
+
+            let end_utilization_rate = Rate::from_bps(end.utilization_rate_bps);
+            if utilization_rate <= end_utilization_rate {
+                let start_utilization_rate = Rate::from_bps(start.utilization_rate_bps);
+                let normalized_rate = utilization_rate
+                    .try_sub(start_utilization_rate)?
+                    .try_div(end_utilization_rate.try_sub(start_utilization_rate)?)?;
+                let min_rate = Rate::from_bps(start.borrow_rate_bps);
+                let rate_range = Rate::from_bps(end.borrow_rate_bps).try_sub(min_rate)?;
+
+                return normalized_rate.try_mul(rate_range)?.try_add(min_rate);
+            }
+        }
+
+        Ok(Rate::from_bps(
+            self.points[BORROW_RATE_CURVE_POINTS - 1].borrow_rate_bps,
+        ))
+    }
+}
+
+/// Price oracle a reserve uses to value its liquidity in lending market quote currency
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OracleConfig {
+    /// Liquidity is the lending market quote currency, which is always priced at one
+    QuoteCurrency,
+    /// Best order price on a Serum dex market quoted in the lending market quote currency
+    DexMarket,
+    /// Aggregated price published to a push oracle feed account
+    PushOracle {
+        /// Maximum number of slots since the feed was last published
+        max_staleness_slots: u64,
+        /// Maximum width of the feed confidence interval, in basis points of the price
+        max_confidence_bps: u16,
+    },
+    /// Time-weighted average price of a token swap pool, from its cumulative prices
+    TokenSwapTwap {
+        /// Minimum number of slots of accumulated prices between two price updates
+        window_slots: u64,
+        /// Maximum number of slots since the end of the window the previous
+        /// price was averaged over
+        max_staleness_slots: u64,
+    },
+}
+
+impl Default for OracleConfig {
+    fn default() -> Self {
+        Self::QuoteCurrency
+    }
+}
+
+impl OracleConfig {
+    /// Split the oracle config into a type tag and two parameters for packing
+    pub fn to_parts(&self) -> (u8, u64, u64) {
+        match *self {
+            Self::QuoteCurrency => (0, 0, 0),
+            Self::DexMarket => (1, 0, 0),
+            Self::PushOracle {
+                max_staleness_slots,
+                max_confidence_bps,
+            } => (2, max_staleness_slots, max_confidence_bps as u64),
+            Self::TokenSwapTwap {
+                window_slots,
+                max_staleness_slots,
+            } => (3, window_slots, max_staleness_slots),
+        }
+    }
+
+    /// Join a type tag and its parameters back into an oracle config
+    pub fn from_parts(tag: u8, param_a: u64, param_b: u64) -> Option<Self> {
+        match tag {
+            0 => Some(Self::QuoteCurrency),
+            1 => Some(Self::DexMarket),
+            2 => Some(Self::PushOracle {
+                max_staleness_slots: param_a,
+                max_confidence_bps: u16::try_from(param_b).ok()?,
+            }),
+            3 => Some(Self::TokenSwapTwap {
+                window_slots: param_a,
+                max_staleness_slots: param_b,
+            }),
+            _ => None,
+        }
+    }
 }
 
 /// Additional fee information on a reserve
 ///
 /// These exist separately from interest accrual fees, and are specifically for
 /// the program owner and frontend host.  The fees are paid out as a percentage
-/// of collateral token amounts during repayments and liquidations.
+/// of liquidity token amounts during borrows and flash loans.
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct ReserveFees {
-    /// Fee assessed on `BorrowReserveLiquidity`, expressed as a Wad.
+    /// Fee assessed on `BorrowObligationLiquidity`, expressed as a Wad.
     /// Must be between 0 and 10^18, such that 10^18 = 1.  A few examples for
     /// clarity:
     /// 1% = 10_000_000_000_000_000
     /// 0.01% (1 basis point) = 100_000_000_000_000
     /// 0.00001% (Aave borrow fee) = 100_000_000_000
     pub borrow_fee_wad: u64,
-    /// Amount of fee going to host account, if provided in liquidate and repay
+    /// Fee for flash loan, expressed as a Wad.
+    /// 0.3% (Aave flash loan fee) = 3_000_000_000_000_000
+    pub flash_loan_fee_wad: u64,
+    /// Amount of fee going to host account, if provided in borrow
     pub host_fee_percentage: u8,
 }
 
+/// Whether a fee is taken out of an amount or charged on top of it
+enum FeeCalculation {
+    /// Fee is included in the amount, e.g. deducted from a borrow
+    Inclusive,
+    /// Fee is added to the amount, e.g. repaid on top of a flash loan
+    Exclusive,
+}
+
 impl ReserveFees {
     /// Calculate the owner and host fees on borrow
-    pub fn calculate_borrow_fees(
+    pub fn calculate_borrow_fees(&self, borrow_amount: u64) -> Result<(u64, u64), ProgramError> {
+        self.calculate_fees(
+            borrow_amount,
+            self.borrow_fee_wad,
+            FeeCalculation::Inclusive,
+        )
+    }
+
+    /// Calculate the owner and host fees on flash loan
+    pub fn calculate_flash_loan_fees(
+        &self,
+        flash_loan_amount: u64,
+    ) -> Result<(u64, u64), ProgramError> {
+        self.calculate_fees(
+            flash_loan_amount,
+            self.flash_loan_fee_wad,
+            FeeCalculation::Exclusive,
+        )
+    }
+
+    fn calculate_fees(
         &self,
-        collateral_amount: u64,
+        amount: u64,
+        fee_wad: u64,
+        fee_calculation: FeeCalculation,
     ) -> Result<(u64, u64), ProgramError> {
-        let borrow_fee_rate = Rate::from_scaled_val(self.borrow_fee_wad);
+        let borrow_fee_rate = Rate::from_scaled_val(fee_wad);
         let host_fee_rate = Rate::from_percent(self.host_fee_percentage);
-        if borrow_fee_rate > Rate::zero() && collateral_amount > 0 {
+        if borrow_fee_rate > Rate::zero() && amount > 0 {
             let need_to_assess_host_fee = host_fee_rate > Rate::zero();
             let minimum_fee = if need_to_assess_host_fee {
                 2 // 1 token to owner, 1 to host
@@ -578,7 +777,7 @@ impl ReserveFees {
             };
 
             let borrow_fee = borrow_fee_rate
-                .try_mul(collateral_amount)?
+                .try_mul(amount)?
                 .try_round_u64()?
                 .max(minimum_fee);
 
@@ -588,10 +787,11 @@ impl ReserveFees {
                 0
             };
 
-            if borrow_fee >= collateral_amount {
-                Err(LendingError::BorrowTooSmall.into())
-            } else {
-                Ok((borrow_fee, host_fee))
+            match fee_calculation {
+                FeeCalculation::Inclusive if borrow_fee >= amount => {
+                    Err(LendingError::BorrowTooSmall.into())
+                }
+                _ => Ok((borrow_fee, host_fee)),
             }
         } else {
             Ok((0, 0))
@@ -623,58 +823,81 @@ impl Pack for Reserve {
             liquidity_supply,
             collateral_mint,
             collateral_supply,
-            collateral_fees_receiver,
-            dex_market,
-            optimal_utilization_rate,
+            liquidity_fee_receiver,
+            oracle,
             loan_to_value_ratio,
             liquidation_bonus,
             liquidation_threshold,
-            min_borrow_rate,
-            optimal_borrow_rate,
-            max_borrow_rate,
             borrow_fee_wad,
+            flash_loan_fee_wad,
             host_fee_percentage,
             cumulative_borrow_rate,
             total_borrows,
             available_liquidity,
             collateral_mint_supply,
+            liquidity_market_price,
+            oracle_type,
+            oracle_param_a,
+            oracle_param_b,
+            deposits_disabled,
+            borrows_disabled,
+            liquidations_disabled,
+            supply_cap,
+            borrow_cap,
+            protocol_fees,
+            reserve_factor,
+            borrow_rate_curve,
+            oracle_observation,
             __padding,
         ) = array_refs![
-            input, 1, 8, 32, 32, 1, 32, 32, 32, 32, 36, 1, 1, 1, 1, 1, 1, 1, 8, 1, 16, 16, 8, 8,
-            300
+            input, 1, 8, 32, 32, 1, 32, 32, 32, 32, 36, 1, 1, 1, 8, 8, 1, 16, 16, 8, 8, 16, 1, 8,
+            8, 1, 1, 1, 8, 8, 16, 1, 32, 40, 155
         ];
         Ok(Self {
             version: u8::from_le_bytes(*version),
             last_update_slot: u64::from_le_bytes(*last_update_slot),
             cumulative_borrow_rate_wads: unpack_decimal(cumulative_borrow_rate),
             lending_market: Pubkey::new_from_array(*lending_market),
-            dex_market: unpack_coption_key(dex_market)?,
+            oracle: unpack_coption_key(oracle)?,
             liquidity: ReserveLiquidity {
                 mint_pubkey: Pubkey::new_from_array(*liquidity_mint),
                 mint_decimals: u8::from_le_bytes(*liquidity_mint_decimals),
                 supply_pubkey: Pubkey::new_from_array(*liquidity_supply),
+                fee_receiver: Pubkey::new_from_array(*liquidity_fee_receiver),
                 available_amount: u64::from_le_bytes(*available_liquidity),
                 borrowed_amount_wads: unpack_decimal(total_borrows),
+                market_price: unpack_decimal(liquidity_market_price),
+                protocol_fees_wads: unpack_decimal(protocol_fees),
             },
             collateral: ReserveCollateral {
                 mint_pubkey: Pubkey::new_from_array(*collateral_mint),
                 mint_total_supply: u64::from_le_bytes(*collateral_mint_supply),
                 supply_pubkey: Pubkey::new_from_array(*collateral_supply),
-                fees_receiver: Pubkey::new_from_array(*collateral_fees_receiver),
             },
             config: ReserveConfig {
-                optimal_utilization_rate: u8::from_le_bytes(*optimal_utilization_rate),
                 loan_to_value_ratio: u8::from_le_bytes(*loan_to_value_ratio),
                 liquidation_bonus: u8::from_le_bytes(*liquidation_bonus),
                 liquidation_threshold: u8::from_le_bytes(*liquidation_threshold),
-                min_borrow_rate: u8::from_le_bytes(*min_borrow_rate),
-                optimal_borrow_rate: u8::from_le_bytes(*optimal_borrow_rate),
-                max_borrow_rate: u8::from_le_bytes(*max_borrow_rate),
                 fees: ReserveFees {
                     borrow_fee_wad: u64::from_le_bytes(*borrow_fee_wad),
+                    flash_loan_fee_wad: u64::from_le_bytes(*flash_loan_fee_wad),
                     host_fee_percentage: u8::from_le_bytes(*host_fee_percentage),
                 },
+                oracle: OracleConfig::from_parts(
+                    u8::from_le_bytes(*oracle_type),
+                    u64::from_le_bytes(*oracle_param_a),
+                    u64::from_le_bytes(*oracle_param_b),
+                )
+                .ok_or(ProgramError::InvalidAccountData)?,
+                deposits_disabled: unpack_bool(deposits_disabled)?,
+                borrows_disabled: unpack_bool(borrows_disabled)?,
+                liquidations_disabled: unpack_bool(liquidations_disabled)?,
+                supply_cap: u64::from_le_bytes(*supply_cap),
+                borrow_cap: u64::from_le_bytes(*borrow_cap),
+                reserve_factor: u8::from_le_bytes(*reserve_factor),
+                borrow_rate_curve: unpack_borrow_rate_curve(borrow_rate_curve),
             },
+            oracle_observation: PriceAccumulator::unpack_from_slice(oracle_observation)?,
         })
     }
 
@@ -689,55 +912,101 @@ impl Pack for Reserve {
             liquidity_supply,
             collateral_mint,
             collateral_supply,
-            collateral_fees_receiver,
-            dex_market,
-            optimal_utilization_rate,
+            liquidity_fee_receiver,
+            oracle,
             loan_to_value_ratio,
             liquidation_bonus,
             liquidation_threshold,
-            min_borrow_rate,
-            optimal_borrow_rate,
-            max_borrow_rate,
             borrow_fee_wad,
+            flash_loan_fee_wad,
             host_fee_percentage,
             cumulative_borrow_rate,
             total_borrows,
             available_liquidity,
             collateral_mint_supply,
+            liquidity_market_price,
+            oracle_type,
+            oracle_param_a,
+            oracle_param_b,
+            deposits_disabled,
+            borrows_disabled,
+            liquidations_disabled,
+            supply_cap,
+            borrow_cap,
+            protocol_fees,
+            reserve_factor,
+            borrow_rate_curve,
+            oracle_observation,
             _padding,
         ) = mut_array_refs![
-            output, 1, 8, 32, 32, 1, 32, 32, 32, 32, 36, 1, 1, 1, 1, 1, 1, 1, 8, 1, 16, 16, 8, 8,
-            300
+            output, 1, 8, 32, 32, 1, 32, 32, 32, 32, 36, 1, 1, 1, 8, 8, 1, 16, 16, 8, 8, 16, 1, 8,
+            8, 1, 1, 1, 8, 8, 16, 1, 32, 40, 155
         ];
         *version = self.version.to_le_bytes();
         *last_update_slot = self.last_update_slot.to_le_bytes();
         pack_decimal(self.cumulative_borrow_rate_wads, cumulative_borrow_rate);
         lending_market.copy_from_slice(self.lending_market.as_ref());
-        pack_coption_key(&self.dex_market, dex_market);
+        pack_coption_key(&self.oracle, oracle);
 
         // liquidity info
         liquidity_mint.copy_from_slice(self.liquidity.mint_pubkey.as_ref());
         *liquidity_mint_decimals = self.liquidity.mint_decimals.to_le_bytes();
         liquidity_supply.copy_from_slice(self.liquidity.supply_pubkey.as_ref());
+        liquidity_fee_receiver.copy_from_slice(self.liquidity.fee_receiver.as_ref());
         *available_liquidity = self.liquidity.available_amount.to_le_bytes();
         pack_decimal(self.liquidity.borrowed_amount_wads, total_borrows);
+        pack_decimal(self.liquidity.market_price, liquidity_market_price);
+        pack_decimal(self.liquidity.protocol_fees_wads, protocol_fees);
 
         // collateral info
         collateral_mint.copy_from_slice(self.collateral.mint_pubkey.as_ref());
         collateral_supply.copy_from_slice(self.collateral.supply_pubkey.as_ref());
-        collateral_fees_receiver.copy_from_slice(self.collateral.fees_receiver.as_ref());
         *collateral_mint_supply = self.collateral.mint_total_supply.to_le_bytes();
 
         // config
-        *optimal_utilization_rate = self.config.optimal_utilization_rate.to_le_bytes();
         *loan_to_value_ratio = self.config.loan_to_value_ratio.to_le_bytes();
         *liquidation_bonus = self.config.liquidation_bonus.to_le_bytes();
         *liquidation_threshold = self.config.liquidation_threshold.to_le_bytes();
-        *min_borrow_rate = self.config.min_borrow_rate.to_le_bytes();
-        *optimal_borrow_rate = self.config.optimal_borrow_rate.to_le_bytes();
-        *max_borrow_rate = self.config.max_borrow_rate.to_le_bytes();
         *borrow_fee_wad = self.config.fees.borrow_fee_wad.to_le_bytes();
+        *flash_loan_fee_wad = self.config.fees.flash_loan_fee_wad.to_le_bytes();
         *host_fee_percentage = self.config.fees.host_fee_percentage.to_le_bytes();
+        let (oracle_tag, oracle_a, oracle_b) = self.config.oracle.to_parts();
+        *oracle_type = oracle_tag.to_le_bytes();
+        *oracle_param_a = oracle_a.to_le_bytes();
+        *oracle_param_b = oracle_b.to_le_bytes();
+        pack_bool(self.config.deposits_disabled, deposits_disabled);
+        pack_bool(self.config.borrows_disabled, borrows_disabled);
+        pack_bool(self.config.liquidations_disabled, liquidations_disabled);
+        *supply_cap = self.config.supply_cap.to_le_bytes();
+        *borrow_cap = self.config.borrow_cap.to_le_bytes();
+        *reserve_factor = self.config.reserve_factor.to_le_bytes();
+        pack_borrow_rate_curve(&self.config.borrow_rate_curve, borrow_rate_curve);
+
+        // oracle
+        self.oracle_observation
+            .pack_into_slice(&mut oracle_observation[..]);
+    }
+}
+
+const BORROW_RATE_CURVE_LEN: usize = 32; // 8 points * (2 + 2)
+
+fn unpack_borrow_rate_curve(src: &[u8; BORROW_RATE_CURVE_LEN]) -> BorrowRateCurve {
+    let mut curve = BorrowRateCurve::default();
+    for (point, src) in curve.points.iter_mut().zip(src.chunks_exact(4)) {
+        let src = array_ref![src, 0, 4];
+        let (utilization_rate_bps, borrow_rate_bps) = array_refs![src, 2, 2];
+        point.utilization_rate_bps = u16::from_le_bytes(*utilization_rate_bps);
+        point.borrow_rate_bps = u16::from_le_bytes(*borrow_rate_bps);
+    }
+    curve
+}
+
+fn pack_borrow_rate_curve(curve: &BorrowRateCurve, dst: &mut [u8; BORROW_RATE_CURVE_LEN]) {
+    for (point, dst) in curve.points.iter().zip(dst.chunks_exact_mut(4)) {
+        let dst = array_mut_ref![dst, 0, 4];
+        let (utilization_rate_bps, borrow_rate_bps) = mut_array_refs![dst, 2, 2];
+        *utilization_rate_bps = point.utilization_rate_bps.to_le_bytes();
+        *borrow_rate_bps = point.borrow_rate_bps.to_le_bytes();
     }
 }
 
@@ -750,39 +1019,25 @@ mod test {
 
     const MAX_LIQUIDITY: u64 = u64::MAX / 5;
 
-    struct MockConverter(Decimal);
-    impl TokenConverter for MockConverter {
-        fn best_price(&mut self, _token_mint: &Pubkey) -> Result<Decimal, ProgramError> {
-            Ok(self.0)
-        }
-
-        fn convert(
-            self,
-            from_amount: Decimal,
-            _from_token_mint: &Pubkey,
-        ) -> Result<Decimal, ProgramError> {
-            from_amount.try_mul(self.0)
+    fn point(utilization_rate_bps: u16, borrow_rate_bps: u16) -> BorrowRateCurvePoint {
+        BorrowRateCurvePoint {
+            utilization_rate_bps,
+            borrow_rate_bps,
         }
     }
 
-    /// Convert reserve liquidity tokens to the collateral tokens of another reserve
-    fn liquidity_in_other_collateral(
-        liquidity_amount: u64,
-        collateral_exchange_rate: CollateralExchangeRate,
-        conversion_rate: Decimal,
-    ) -> Result<Decimal, ProgramError> {
-        collateral_exchange_rate.decimal_liquidity_to_collateral(
-            Decimal::from(liquidity_amount).try_mul(conversion_rate)?,
-        )
+    fn flat_borrow_rate_curve(borrow_rate_bps: u16) -> BorrowRateCurve {
+        BorrowRateCurve::from_points(&[point(0, borrow_rate_bps), point(10_000, borrow_rate_bps)])
+            .unwrap()
     }
 
     // Creates rates (min, opt, max) where 0 <= min <= opt <= max <= MAX
     prop_compose! {
-        fn borrow_rates()(optimal_rate in 0..=u8::MAX)(
+        fn borrow_rates()(optimal_rate in 0..=u16::MAX)(
             min_rate in 0..=optimal_rate,
             optimal_rate in Just(optimal_rate),
-            max_rate in optimal_rate..=u8::MAX,
-        ) -> (u8, u8, u8) {
+            max_rate in optimal_rate..=u16::MAX,
+        ) -> (u16, u16, u16) {
             (min_rate, optimal_rate, max_rate)
         }
     }
@@ -797,110 +1052,64 @@ mod test {
         }
     }
 
-    // Creates a range of reasonable token conversion rates
-    prop_compose! {
-        fn token_conversion_rate()(
-            conversion_rate in 1..=u16::MAX,
-            invert_conversion_rate: bool,
-        ) -> Decimal {
-            let conversion_rate = Decimal::from(conversion_rate as u64);
-            if invert_conversion_rate {
-                Decimal::one().try_div(conversion_rate).unwrap()
-            } else {
-                conversion_rate
-            }
-        }
-    }
-
-    // Creates a range of reasonable collateral exchange rates
-    prop_compose! {
-        fn collateral_exchange_rate_range()(percent in 1..=500u64) -> CollateralExchangeRate {
-            CollateralExchangeRate(Rate::from_scaled_val(percent * PERCENT_SCALER))
-        }
-    }
-
     proptest! {
         #[test]
         fn unhealthy_obligations_can_be_liquidated(
-            obligation_collateral in 1..=u64::MAX,
+            borrowed_amount in CLOSEABLE_AMOUNT..=u32::MAX as u64,
+            deposited_amount in 1_000..=u32::MAX as u64,
             (obligation_ltv, liquidation_threshold) in unhealthy_rates(),
-            collateral_exchange_rate in collateral_exchange_rate_range(),
-            token_conversion_rate in token_conversion_rate(),
+            liquidation_bonus in 0..=100u8,
         ) {
-            let collateral_reserve_config = &ReserveConfig {
-                liquidation_threshold,
-                ..ReserveConfig::default()
-            };
-
             // Create unhealthy obligation at target LTV
-            let collateral_value = collateral_exchange_rate
-                .decimal_collateral_to_liquidity(Decimal::from(obligation_collateral as u64))?
-                .try_div(token_conversion_rate)?;
-
-            // Ensure that collateral value fits in u64
-            prop_assume!(collateral_value.try_round_u64().is_ok());
-
-            let borrowed_liquidity_wads = collateral_value
-                .try_mul(obligation_ltv)?
-                .try_add(Decimal::from_scaled_val(1u128))? // ensure loan is unhealthy
-                .max(Decimal::from(2u64)); // avoid dust account closure
-
-            // Ensure that borrow value fits in u64
-            prop_assume!(borrowed_liquidity_wads.try_round_u64().is_ok());
-
+            let borrowed_value = Decimal::from(borrowed_amount);
+            let deposited_value = borrowed_value.try_div(obligation_ltv)?;
+            let liquidity = ObligationLiquidity {
+                borrowed_amount_wads: Decimal::from(borrowed_amount),
+                market_value: borrowed_value,
+                ..ObligationLiquidity::default()
+            };
+            let collateral = ObligationCollateral {
+                deposited_amount,
+                market_value: deposited_value,
+                ..ObligationCollateral::default()
+            };
             let obligation = Obligation {
-                deposited_collateral_tokens: obligation_collateral as u64,
-                borrowed_liquidity_wads,
+                deposits: vec![collateral.clone()],
+                borrows: vec![liquidity.clone()],
+                deposited_value,
+                borrowed_value,
+                unhealthy_borrow_value: deposited_value
+                    .try_mul(Rate::from_percent(liquidation_threshold))?,
                 ..Obligation::default()
             };
+            assert!(obligation.borrowed_value >= obligation.unhealthy_borrow_value);
 
-            // Ensure that the token conversion fits in a Decimal
-            {
-                let token_converter = MockConverter(token_conversion_rate);
-                let decimal_repay_amount = Decimal::from(obligation.max_liquidation_amount()?);
-                // Calculate the amount of collateral that will be received
-                let receive_liquidity_amount_result =
-                    token_converter.convert(decimal_repay_amount, &Pubkey::default());
-                prop_assume!(receive_liquidity_amount_result.is_ok());
-            }
+            let reserve = Reserve {
+                config: ReserveConfig {
+                    liquidation_threshold,
+                    liquidation_bonus,
+                    ..ReserveConfig::default()
+                },
+                ..Reserve::default()
+            };
 
             // Liquidate with max amount to ensure obligation can be liquidated
-            let liquidate_result = Reserve::_liquidate_obligation(
-                &obligation,
+            let liquidate_result = reserve.calculate_liquidation(
                 u64::MAX,
-                &Pubkey::default(),
-                collateral_exchange_rate,
-                collateral_reserve_config,
-                MockConverter(token_conversion_rate)
-            );
-
-            let liquidate_result = liquidate_result.unwrap();
-            let expected_withdraw_amount = liquidity_in_other_collateral(
-                liquidate_result.repay_amount,
-                collateral_exchange_rate,
-                token_conversion_rate,
-            )?.min(obligation.deposited_collateral_tokens.into());
+                &obligation,
+                &liquidity,
+                &collateral,
+            )?;
 
+            let max_liquidation_amount = obligation.max_liquidation_amount(&liquidity)?;
             assert!(liquidate_result.repay_amount > 0);
             assert!(liquidate_result.withdraw_amount > 0);
+            assert!(liquidate_result.withdraw_amount <= deposited_amount);
+            assert!(liquidate_result.settle_amount <= max_liquidation_amount);
+            assert_eq!(liquidate_result.repay_amount, liquidate_result.settle_amount.try_ceil_u64()?);
 
-            let min_withdraw_amount = expected_withdraw_amount.try_floor_u64()?;
-            let max_withdraw_amount = expected_withdraw_amount.try_ceil_u64()?;
-            let max_repay_amount = obligation.borrowed_liquidity_wads
-                .try_mul(Rate::from_percent(LIQUIDATION_CLOSE_FACTOR))?
-                .try_ceil_u64()?;
-
-            assert!(liquidate_result.withdraw_amount >= min_withdraw_amount);
-            assert!(liquidate_result.withdraw_amount <= max_withdraw_amount);
-            assert!(liquidate_result.repay_amount <= max_repay_amount);
-
-            let defaulted = liquidate_result.withdraw_amount == obligation.deposited_collateral_tokens;
-            if defaulted {
-                assert_eq!(liquidate_result.settle_amount, borrowed_liquidity_wads);
-                assert!(liquidate_result.repay_amount < liquidate_result.settle_amount.try_floor_u64()?);
-            } else {
-                assert_eq!(liquidate_result.settle_amount.try_ceil_u64()?, liquidate_result.repay_amount);
-                assert!(liquidate_result.withdraw_amount < obligation.deposited_collateral_tokens);
+            if liquidate_result.withdraw_amount < deposited_amount {
+                assert_eq!(liquidate_result.settle_amount, max_liquidation_amount);
             }
         }
 
@@ -908,7 +1117,7 @@ mod test {
         fn current_borrow_rate(
             total_liquidity in 0..=MAX_LIQUIDITY,
             borrowed_percent in 0..=WAD,
-            optimal_utilization_rate in 0..=100u8,
+            optimal_utilization_rate in 1..10_000u16,
             (min_borrow_rate, optimal_borrow_rate, max_borrow_rate) in borrow_rates(),
         ) {
             let borrowed_amount_wads = Decimal::from(total_liquidity).try_mul(Rate::from_scaled_val(borrowed_percent))?;
@@ -918,19 +1127,28 @@ mod test {
                     available_amount: total_liquidity - borrowed_amount_wads.try_round_u64()?,
                     ..ReserveLiquidity::default()
                 },
-                config: ReserveConfig { optimal_utilization_rate, min_borrow_rate, optimal_borrow_rate, max_borrow_rate, ..ReserveConfig::default() },
+                config: ReserveConfig {
+                    borrow_rate_curve: BorrowRateCurve::from_points(&[
+                        point(0, min_borrow_rate),
+                        point(optimal_utilization_rate, optimal_borrow_rate),
+                        point(10_000, max_borrow_rate),
+                    ]).unwrap(),
+                    ..ReserveConfig::default()
+                },
                 ..Reserve::default()
             };
+            assert!(reserve.config.borrow_rate_curve.is_valid());
 
             let current_borrow_rate = reserve.current_borrow_rate()?;
-            assert!(current_borrow_rate >= Rate::from_percent(min_borrow_rate));
-            assert!(current_borrow_rate <= Rate::from_percent(max_borrow_rate));
+            assert!(current_borrow_rate >= Rate::from_bps(min_borrow_rate));
+            assert!(current_borrow_rate <= Rate::from_bps(max_borrow_rate));
 
-            let optimal_borrow_rate = Rate::from_percent(optimal_borrow_rate);
+            let optimal_borrow_rate_bps = optimal_borrow_rate;
+            let optimal_borrow_rate = Rate::from_bps(optimal_borrow_rate);
             let current_rate = reserve.liquidity.utilization_rate()?;
-            match current_rate.cmp(&Rate::from_percent(optimal_utilization_rate)) {
+            match current_rate.cmp(&Rate::from_bps(optimal_utilization_rate)) {
                 Ordering::Less => {
-                    if min_borrow_rate == reserve.config.optimal_borrow_rate {
+                    if min_borrow_rate == optimal_borrow_rate_bps {
                         assert_eq!(current_borrow_rate, optimal_borrow_rate);
                     } else {
                         assert!(current_borrow_rate < optimal_borrow_rate);
@@ -938,7 +1156,7 @@ mod test {
                 }
                 Ordering::Equal => assert!(current_borrow_rate == optimal_borrow_rate),
                 Ordering::Greater => {
-                    if max_borrow_rate == reserve.config.optimal_borrow_rate {
+                    if max_borrow_rate == optimal_borrow_rate_bps {
                         assert_eq!(current_borrow_rate, optimal_borrow_rate);
                     } else {
                         assert!(current_borrow_rate > optimal_borrow_rate);
@@ -947,119 +1165,6 @@ mod test {
             }
         }
 
-        #[test]
-        fn allowed_borrow_for_collateral(
-            collateral_amount in 0..=u32::MAX as u64,
-            collateral_exchange_rate in collateral_exchange_rate_range(),
-            token_conversion_rate in 1..=u64::MAX,
-            loan_to_value_ratio in 1..100u8,
-        ) {
-            let total_liquidity = 1_000_000;
-            let collateral_token_supply = collateral_exchange_rate
-                .liquidity_to_collateral(total_liquidity)?;
-            let reserve = Reserve {
-                collateral: ReserveCollateral {
-                    mint_total_supply: collateral_token_supply,
-                    ..ReserveCollateral::default()
-                },
-                liquidity: ReserveLiquidity {
-                    available_amount: total_liquidity,
-                    ..ReserveLiquidity::default()
-                },
-                config: ReserveConfig {
-                    loan_to_value_ratio,
-                    ..ReserveConfig::default()
-                },
-                ..Reserve::default()
-            };
-
-            let conversion_rate = Decimal::from_scaled_val(token_conversion_rate as u128);
-            let borrow_amount = reserve.allowed_borrow_for_collateral(
-                collateral_amount,
-                MockConverter(conversion_rate)
-            )?;
-
-            // Allowed borrow should be conservatively low and therefore slightly less or equal
-            // to the precise equivalent for the given collateral. When it is converted back
-            // to collateral, the returned value should be equal or lower than the original
-            // collateral amount.
-            let collateral_amount_lower = reserve.required_collateral_for_borrow(
-                borrow_amount,
-                &Pubkey::default(),
-                MockConverter(Decimal::one().try_div(conversion_rate)?)
-            )?;
-
-            // After incrementing the allowed borrow, its value should be slightly more or equal
-            // to the precise equivalent of the original collateral. When it is converted back
-            // to collateral, the returned value should be equal or higher than the original
-            // collateral amount since required collateral should be conservatively high.
-            let collateral_amount_upper = reserve.required_collateral_for_borrow(
-                borrow_amount + 1,
-                &Pubkey::default(),
-                MockConverter(Decimal::one().try_div(conversion_rate)?)
-            )?;
-
-            // Assert that reversing the calculation returns approx original amount
-            assert!(collateral_amount >= collateral_amount_lower);
-            assert!(collateral_amount <= collateral_amount_upper);
-        }
-
-        #[test]
-        fn required_collateral_for_borrow(
-            borrow_amount in 0..=u32::MAX as u64,
-            collateral_exchange_rate in collateral_exchange_rate_range(),
-            token_conversion_rate in 1..=u64::MAX,
-            loan_to_value_ratio in 1..=100u8,
-        ) {
-            let total_liquidity = 1_000_000;
-            let collateral_token_supply = collateral_exchange_rate
-                .liquidity_to_collateral(total_liquidity)?;
-            let reserve = Reserve {
-                collateral: ReserveCollateral {
-                    mint_total_supply: collateral_token_supply,
-                    ..ReserveCollateral::default()
-                },
-                liquidity: ReserveLiquidity {
-                    available_amount: total_liquidity,
-                    ..ReserveLiquidity::default()
-                },
-                config: ReserveConfig {
-                    loan_to_value_ratio,
-                    ..ReserveConfig::default()
-                },
-                ..Reserve::default()
-            };
-
-            let conversion_rate = Decimal::from_scaled_val(token_conversion_rate as u128);
-            let collateral_amount = reserve.required_collateral_for_borrow(
-                borrow_amount,
-                &Pubkey::default(),
-                MockConverter(conversion_rate)
-            )?;
-
-            // Required collateral should be conservatively high and therefore slightly more or equal
-            // to the precise equivalent for the desired borrow amount. When it is converted back
-            // to borrow, the returned value should be equal or higher than the original
-            // borrow amount.
-            let borrow_amount_upper = reserve.allowed_borrow_for_collateral(
-                collateral_amount,
-                MockConverter(Decimal::one().try_div(conversion_rate)?)
-            )?;
-
-            // After decrementing the required collateral, its value should be slightly less or equal
-            // to the precise equivalent of the original borrow. When it is converted back
-            // to borrow, the returned value should be equal or lower than the original
-            // borrow amount since allowed borrow should be conservatively low.
-            let borrow_amount_lower = reserve.allowed_borrow_for_collateral(
-                collateral_amount.saturating_sub(1),
-                MockConverter(Decimal::one().try_div(conversion_rate)?)
-            )?;
-
-            // Assert that reversing the calculation returns approx original amount
-            assert!(borrow_amount >= borrow_amount_lower);
-            assert!(borrow_amount <= borrow_amount_upper);
-        }
-
         #[test]
         fn current_utilization_rate(
             total_liquidity in 0..=MAX_LIQUIDITY,
@@ -1081,7 +1186,7 @@ mod test {
             total_liquidity in 0..=MAX_LIQUIDITY,
             borrowed_percent in 0..=WAD,
             collateral_multiplier in 0..=(5*WAD),
-            borrow_rate in 0..=u8::MAX,
+            borrow_rate in 0..=u16::MAX,
         ) {
             let borrowed_liquidity_wads = Decimal::from(total_liquidity).try_mul(Rate::from_scaled_val(borrowed_percent))?;
             let available_liquidity = total_liquidity - borrowed_liquidity_wads.try_round_u64()?;
@@ -1098,9 +1203,7 @@ mod test {
                     ..ReserveLiquidity::default()
                 },
                 config: ReserveConfig {
-                    min_borrow_rate: borrow_rate,
-                    optimal_borrow_rate: borrow_rate,
-                    optimal_utilization_rate: 100,
+                    borrow_rate_curve: flat_borrow_rate_curve(borrow_rate),
                     ..ReserveConfig::default()
                 },
                 ..Reserve::default()
@@ -1140,7 +1243,7 @@ mod test {
         fn reserve_accrue_interest(
             slots_elapsed in 0..=SLOTS_PER_YEAR,
             borrowed_liquidity in 0..=u64::MAX,
-            borrow_rate in 0..=u8::MAX,
+            borrow_rate in 0..=50_000u16, // compounding overflows above ~580% a year
         ) {
             let borrowed_amount_wads = Decimal::from(borrowed_liquidity);
             let mut reserve = Reserve {
@@ -1149,7 +1252,7 @@ mod test {
                     ..ReserveLiquidity::default()
                 },
                 config: ReserveConfig {
-                    max_borrow_rate: borrow_rate,
+                    borrow_rate_curve: flat_borrow_rate_curve(borrow_rate),
                     ..ReserveConfig::default()
                 },
                 ..Reserve::default()
@@ -1173,6 +1276,7 @@ mod test {
         ) {
             let fees = ReserveFees {
                 borrow_fee_wad,
+                flash_loan_fee_wad: 0,
                 host_fee_percentage,
             };
             let (total_fee, host_fee) = fees.calculate_borrow_fees(borrow_amount)?;
@@ -1207,27 +1311,34 @@ mod test {
 
     #[test]
     fn liquidate_amount_too_small() {
-        let conversion_rate = Decimal::from_scaled_val(PERCENT_SCALER as u128); // 1%
-        let collateral_exchange_rate = CollateralExchangeRate(Rate::one());
-        let collateral_reserve_config = &ReserveConfig {
-            liquidation_threshold: 80u8,
-            liquidation_bonus: 5u8,
-            ..ReserveConfig::default()
+        let reserve = Reserve {
+            config: ReserveConfig {
+                liquidation_threshold: 80u8,
+                liquidation_bonus: 5u8,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let liquidity = ObligationLiquidity {
+            borrowed_amount_wads: Decimal::from(100u64),
+            market_value: Decimal::from(100u64),
+            ..ObligationLiquidity::default()
+        };
+        let collateral = ObligationCollateral {
+            deposited_amount: 1,
+            market_value: Decimal::from(100u64),
+            ..ObligationCollateral::default()
         };
-
         let obligation = Obligation {
-            deposited_collateral_tokens: 1,
-            borrowed_liquidity_wads: Decimal::from(100u64),
+            borrowed_value: Decimal::from(100u64),
             ..Obligation::default()
         };
 
-        let liquidate_result = Reserve::_liquidate_obligation(
+        let liquidate_result = reserve.calculate_liquidation(
+            1u64, // converts to 0.0105 collateral
             &obligation,
-            1u64, // converts to 0.01 collateral
-            &Pubkey::default(),
-            collateral_exchange_rate,
-            collateral_reserve_config,
-            MockConverter(conversion_rate),
+            &liquidity,
+            &collateral,
         );
 
         assert_eq!(
@@ -1238,50 +1349,96 @@ mod test {
 
     #[test]
     fn liquidate_dust_obligation() {
-        let conversion_rate = Decimal::one();
-        let collateral_exchange_rate = CollateralExchangeRate(Rate::one());
-        let collateral_reserve_config = &ReserveConfig {
-            liquidation_threshold: 80u8,
-            liquidation_bonus: 5u8,
-            ..ReserveConfig::default()
+        let reserve = Reserve {
+            config: ReserveConfig {
+                liquidation_threshold: 80u8,
+                liquidation_bonus: 5u8,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let borrowed_amount_wads = Decimal::one()
+            .try_add(Decimal::from_scaled_val(1u128))
+            .unwrap();
+        let liquidity = ObligationLiquidity {
+            borrowed_amount_wads,
+            market_value: borrowed_amount_wads,
+            ..ObligationLiquidity::default()
+        };
+        let collateral = ObligationCollateral {
+            deposited_amount: 10,
+            market_value: Decimal::from(10u64),
+            ..ObligationCollateral::default()
         };
-
         let obligation = Obligation {
-            deposited_collateral_tokens: 1,
-            borrowed_liquidity_wads: Decimal::one()
-                .try_add(Decimal::from_scaled_val(1u128))
-                .unwrap(),
+            borrowed_value: borrowed_amount_wads,
             ..Obligation::default()
         };
 
-        let liquidate_result = Reserve::_liquidate_obligation(
-            &obligation,
-            2,
-            &Pubkey::default(),
-            collateral_exchange_rate,
-            collateral_reserve_config,
-            MockConverter(conversion_rate),
-        )
-        .unwrap();
+        let liquidate_result = reserve
+            .calculate_liquidation(1, &obligation, &liquidity, &collateral)
+            .unwrap();
 
         assert_eq!(
             liquidate_result.repay_amount,
-            obligation.borrowed_liquidity_wads.try_ceil_u64().unwrap()
-        );
-        assert_eq!(
-            liquidate_result.withdraw_amount,
-            obligation.deposited_collateral_tokens
+            borrowed_amount_wads.try_ceil_u64().unwrap()
         );
+        assert_eq!(liquidate_result.withdraw_amount, 1);
+        assert_eq!(liquidate_result.settle_amount, borrowed_amount_wads);
+    }
+
+    #[test]
+    fn calculate_borrow_within_remaining_value() {
+        let reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                market_price: Decimal::from(2u64),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                fees: ReserveFees {
+                    borrow_fee_wad: 10_000_000_000_000_000, // 1%
+                    flash_loan_fee_wad: 0,
+                    host_fee_percentage: 0,
+                },
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+
+        let borrow_result = reserve
+            .calculate_borrow(500, Decimal::from(1_000u64))
+            .unwrap();
+        assert_eq!(borrow_result.borrow_amount, Decimal::from(500u64));
+        assert_eq!(borrow_result.borrow_fee, 5);
+        assert_eq!(borrow_result.receive_amount, 495);
+
         assert_eq!(
-            liquidate_result.settle_amount,
-            obligation.borrowed_liquidity_wads
+            reserve.calculate_borrow(501, Decimal::from(1_000u64)).err(),
+            Some(LendingError::BorrowTooLarge.into())
         );
     }
 
+    #[test]
+    fn calculate_repay_caps_at_borrowed_amount() {
+        let reserve = Reserve::default();
+        let borrowed_amount = Decimal::from(100u64)
+            .try_add(Decimal::from_scaled_val(1u128))
+            .unwrap();
+
+        let repay_result = reserve.calculate_repay(50, borrowed_amount).unwrap();
+        assert_eq!(repay_result.settle_amount, Decimal::from(50u64));
+        assert_eq!(repay_result.repay_amount, 50);
+
+        let repay_result = reserve.calculate_repay(u64::MAX, borrowed_amount).unwrap();
+        assert_eq!(repay_result.settle_amount, borrowed_amount);
+        assert_eq!(repay_result.repay_amount, 101);
+    }
+
     #[test]
     fn borrow_fee_calculation_min_host() {
         let fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
+            flash_loan_fee_wad: 0,
             host_fee_percentage: 20,
         };
 
@@ -1303,6 +1460,7 @@ mod test {
     fn borrow_fee_calculation_min_no_host() {
         let fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
+            flash_loan_fee_wad: 0,
             host_fee_percentage: 0,
         };
 
@@ -1325,6 +1483,7 @@ mod test {
     fn borrow_fee_calculation_host() {
         let fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
+            flash_loan_fee_wad: 0,
             host_fee_percentage: 20,
         };
 
@@ -1338,6 +1497,7 @@ mod test {
     fn borrow_fee_calculation_no_host() {
         let fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
+            flash_loan_fee_wad: 0,
             host_fee_percentage: 0,
         };
 
@@ -1346,4 +1506,273 @@ mod test {
         assert_eq!(total_fee, 10); // 1% of 1000
         assert_eq!(host_fee, 0); // 0 host fee
     }
+
+    #[test]
+    fn flash_loan_fee_calculation() {
+        let fees = ReserveFees {
+            borrow_fee_wad: 0,
+            flash_loan_fee_wad: 3_000_000_000_000_000, // 0.3%
+            host_fee_percentage: 20,
+        };
+
+        let (total_fee, host_fee) = fees.calculate_flash_loan_fees(10_000).unwrap();
+        assert_eq!(total_fee, 30); // 0.3% of 10_000
+        assert_eq!(host_fee, 6); // 20% of 30
+
+        // fees are charged on top of the loan, so small loans still pay the minimum
+        let (total_fee, host_fee) = fees.calculate_flash_loan_fees(1).unwrap();
+        assert_eq!(total_fee, 2);
+        assert_eq!(host_fee, 1);
+
+        // 0 amount borrowed, 0 fee
+        let (total_fee, host_fee) = fees.calculate_flash_loan_fees(0).unwrap();
+        assert_eq!(total_fee, 0);
+        assert_eq!(host_fee, 0);
+    }
+
+    #[test]
+    fn borrow_rate_curve_interpolation() {
+        let curve = BorrowRateCurve::from_points(&[
+            point(0, 0),
+            point(8_000, 400),
+            point(9_000, 1_000),
+            point(10_000, 30_000),
+        ])
+        .unwrap();
+        assert!(curve.is_valid());
+
+        let borrow_rate = |utilization_rate_bps| {
+            curve
+                .borrow_rate(Rate::from_bps(utilization_rate_bps))
+                .unwrap()
+        };
+        assert_eq!(borrow_rate(0), Rate::zero());
+        assert_eq!(borrow_rate(4_000), Rate::from_bps(200));
+        assert_eq!(borrow_rate(8_000), Rate::from_bps(400));
+        assert_eq!(borrow_rate(8_500), Rate::from_bps(700));
+        assert_eq!(borrow_rate(9_500), Rate::from_bps(15_500));
+        assert_eq!(borrow_rate(10_000), Rate::from_bps(30_000));
+        assert_eq!(
+            curve.borrow_rate(Rate::from_percent(101)).unwrap(),
+            Rate::from_bps(30_000)
+        );
+    }
+
+    #[test]
+    fn borrow_rate_curve_validation() {
+        assert!(BorrowRateCurve::default().is_valid());
+        assert!(BorrowRateCurve::from_points(&[]).is_none());
+        assert!(
+            BorrowRateCurve::from_points(&[point(0, 0); BORROW_RATE_CURVE_POINTS + 1]).is_none()
+        );
+
+        // must start at 0% utilization
+        let curve = BorrowRateCurve::from_points(&[point(100, 0), point(10_000, 100)]).unwrap();
+        assert!(!curve.is_valid());
+
+        // must end at 100% utilization
+        let curve = BorrowRateCurve::from_points(&[point(0, 0), point(9_000, 100)]).unwrap();
+        assert!(!curve.is_valid());
+
+        // utilization must strictly increase
+        let curve = BorrowRateCurve::from_points(&[
+            point(0, 0),
+            point(5_000, 100),
+            point(5_000, 200),
+            point(10_000, 300),
+        ])
+        .unwrap();
+        assert!(!curve.is_valid());
+
+        // borrow rate must not decrease
+        let curve =
+            BorrowRateCurve::from_points(&[point(0, 0), point(5_000, 200), point(10_000, 100)])
+                .unwrap();
+        assert!(!curve.is_valid());
+
+        // utilization must not exceed 100%
+        let curve =
+            BorrowRateCurve::from_points(&[point(0, 0), point(10_001, 200), point(10_000, 300)])
+                .unwrap();
+        assert!(!curve.is_valid());
+    }
+
+    #[test]
+    fn accrue_interest_reserve_factor() {
+        let mut reserve = Reserve {
+            last_update_slot: 0,
+            cumulative_borrow_rate_wads: Decimal::one(),
+            liquidity: ReserveLiquidity {
+                available_amount: 500_000,
+                borrowed_amount_wads: Decimal::from(500_000u64),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                borrow_rate_curve: flat_borrow_rate_curve(2_000),
+                reserve_factor: 25,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let total_supply = reserve.liquidity.total_supply().unwrap();
+
+        reserve.accrue_interest(SLOTS_PER_YEAR).unwrap();
+        let interest = reserve
+            .liquidity
+            .total_supply()
+            .unwrap()
+            .try_add(reserve.liquidity.protocol_fees_wads)
+            .unwrap()
+            .try_sub(total_supply)
+            .unwrap();
+        assert!(interest > Decimal::zero());
+        assert_eq!(
+            reserve.liquidity.protocol_fees_wads,
+            interest.try_mul(Rate::from_percent(25)).unwrap()
+        );
+    }
+
+    #[test]
+    fn withdraw_protocol_fees() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 100,
+                borrowed_amount_wads: Decimal::from(900u64),
+                protocol_fees_wads: Decimal::from_scaled_val(150_500_000_000_000_000_000),
+                ..ReserveLiquidity::default()
+            },
+            collateral: ReserveCollateral {
+                mint_total_supply: 5_000,
+                ..ReserveCollateral::default()
+            },
+            ..Reserve::default()
+        };
+        let exchange_rate = Rate::from(reserve.collateral_exchange_rate().unwrap());
+
+        // Limited by available liquidity
+        assert_eq!(reserve.liquidity.withdraw_protocol_fees(u64::MAX), Ok(100));
+        assert_eq!(
+            reserve.liquidity.protocol_fees_wads,
+            Decimal::from_scaled_val(50_500_000_000_000_000_000)
+        );
+        assert_eq!(
+            Rate::from(reserve.collateral_exchange_rate().unwrap()),
+            exchange_rate
+        );
+
+        // Limited by protocol fees
+        reserve.liquidity.available_amount = 100;
+        assert_eq!(reserve.liquidity.withdraw_protocol_fees(u64::MAX), Ok(50));
+        assert_eq!(
+            reserve.liquidity.withdraw_protocol_fees(u64::MAX),
+            Err(LendingError::InsufficientLiquidity.into())
+        );
+    }
+
+    #[test]
+    fn supply_cap() {
+        let reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 600,
+                borrowed_amount_wads: Decimal::from(300u64),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                supply_cap: 1_000,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        assert_eq!(reserve.check_supply_cap(100), Ok(()));
+        assert_eq!(
+            reserve.check_supply_cap(101),
+            Err(LendingError::ReserveCapExceeded.into())
+        );
+
+        let uncapped_reserve = Reserve {
+            config: ReserveConfig::default(),
+            ..reserve
+        };
+        assert_eq!(uncapped_reserve.check_supply_cap(u64::MAX), Ok(()));
+    }
+
+    #[test]
+    fn borrow_cap() {
+        let reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 600,
+                borrowed_amount_wads: Decimal::from(300u64),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                borrow_cap: 500,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        assert_eq!(reserve.check_borrow_cap(200), Ok(()));
+        assert_eq!(
+            reserve.check_borrow_cap(201),
+            Err(LendingError::ReserveCapExceeded.into())
+        );
+
+        let uncapped_reserve = Reserve {
+            config: ReserveConfig::default(),
+            ..reserve
+        };
+        assert_eq!(uncapped_reserve.check_borrow_cap(600), Ok(()));
+    }
+
+    #[test]
+    fn reserve_pack_unpack() {
+        let oracles = [
+            OracleConfig::QuoteCurrency,
+            OracleConfig::DexMarket,
+            OracleConfig::PushOracle {
+                max_staleness_slots: 25,
+                max_confidence_bps: 200,
+            },
+            OracleConfig::TokenSwapTwap {
+                window_slots: 150,
+                max_staleness_slots: 600,
+            },
+        ];
+        for oracle in oracles.iter() {
+            let mut reserve = Reserve::new(NewReserveParams {
+                current_slot: 1,
+                lending_market: Pubkey::new_unique(),
+                collateral: ReserveCollateral::new(Pubkey::new_unique(), Pubkey::new_unique()),
+                liquidity: ReserveLiquidity::new(
+                    Pubkey::new_unique(),
+                    6,
+                    Pubkey::new_unique(),
+                    Pubkey::new_unique(),
+                    Decimal::one(),
+                ),
+                oracle: COption::Some(Pubkey::new_unique()),
+                config: ReserveConfig {
+                    oracle: *oracle,
+                    borrows_disabled: true,
+                    supply_cap: 1_000_000,
+                    reserve_factor: 10,
+                    borrow_rate_curve: BorrowRateCurve::from_points(&[
+                        point(0, 100),
+                        point(8_000, 400),
+                        point(10_000, 30_000),
+                    ])
+                    .unwrap(),
+                    ..ReserveConfig::default()
+                },
+            });
+            reserve.oracle_observation = PriceAccumulator {
+                last_update_slot: 42,
+                price_a_cumulative: u128::MAX - 7,
+                price_b_cumulative: 3 << 64,
+            };
+
+            let mut packed = [0u8; Reserve::LEN];
+            Reserve::pack(reserve.clone(), &mut packed).unwrap();
+            assert_eq!(Reserve::unpack(&packed).unwrap(), reserve);
+        }
+    }
 }
@@ -4,10 +4,9 @@ mod helpers;
 
 use helpers::*;
 use solana_program_test::*;
-use solana_sdk::{pubkey::Pubkey, signature::Keypair};
+use solana_sdk::signature::Keypair;
 use spl_token_lending::{
-    instruction::BorrowAmountType, math::Decimal, processor::process_instruction,
-    state::INITIAL_COLLATERAL_RATIO,
+    math::Decimal, processor::process_instruction, state::INITIAL_COLLATERAL_RATIO,
 };
 
 const LAMPORTS_TO_SOL: u64 = 1_000_000_000;
@@ -15,15 +14,13 @@ const FRACTIONAL_TO_USDC: u64 = 1_000_000;
 
 #[tokio::test]
 async fn test_borrow_quote_currency() {
-    // Using SOL/USDC max 3 bids:
+    // Using SOL/USDC best bid:
     //  $13.988,  300.0 SOL
-    //  $13.960,  206.8 SOL
-    //  $13.928, 1000.0 SOL
     //
-    // Collateral amount = 750 * 0.8 (LTV) = 600 SOL
-    // Borrow amount = 13.988 * 300 + 13.960 * 206.8 + 13.928 * 93.2 = 8,381.4176 USDC
+    // Collateral value = 750 * 13.988 = 10,491 USDC
+    // Allowed borrow value = 10,491 * 0.5 (LTV) = 5,245.5 USDC
     const SOL_COLLATERAL_AMOUNT_LAMPORTS: u64 = 750 * LAMPORTS_TO_SOL;
-    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 8_381_417_600;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 5_000 * FRACTIONAL_TO_USDC;
     const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 20_000 * FRACTIONAL_TO_USDC;
     const INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS: u64 = 2 * SOL_COLLATERAL_AMOUNT_LAMPORTS;
 
@@ -41,9 +38,6 @@ async fn test_borrow_quote_currency() {
     let usdc_mint = add_usdc_mint(&mut test);
     let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
 
-    let mut reserve_config = TEST_RESERVE_CONFIG;
-    reserve_config.loan_to_value_ratio = 80;
-
     let usdc_reserve = add_reserve(
         &mut test,
         &user_accounts_owner,
@@ -52,136 +46,104 @@ async fn test_borrow_quote_currency() {
             liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
             liquidity_mint_pubkey: usdc_mint.pubkey,
             liquidity_mint_decimals: usdc_mint.decimals,
-            config: reserve_config,
+            config: TEST_RESERVE_CONFIG,
             ..AddReserveArgs::default()
         },
     );
 
+    let collateral_amount = INITIAL_COLLATERAL_RATIO * SOL_COLLATERAL_AMOUNT_LAMPORTS;
     let sol_reserve = add_reserve(
         &mut test,
         &user_accounts_owner,
         &lending_market,
         AddReserveArgs {
-            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            dex_market: Some(&sol_usdc_dex_market),
             liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
             liquidity_mint_pubkey: spl_token::native_mint::id(),
             liquidity_mint_decimals: 9,
-            config: reserve_config,
+            collateral_amount,
+            config: TEST_RESERVE_CONFIG,
             ..AddReserveArgs::default()
         },
     );
 
-    let usdc_obligation = add_obligation(
+    let obligation = add_obligation(
         &mut test,
         &user_accounts_owner,
         &lending_market,
         AddObligationArgs {
-            borrow_reserve: &usdc_reserve,
-            collateral_reserve: &sol_reserve,
-            collateral_amount: 0,
-            borrowed_liquidity_wads: Decimal::zero(),
+            deposits: &[(&sol_reserve, collateral_amount)],
+            borrows: &[],
         },
     );
 
     let (mut banks_client, payer, _recent_blockhash) = test.start().await;
 
-    let borrow_amount =
-        get_token_balance(&mut banks_client, usdc_reserve.user_liquidity_account).await;
-    assert_eq!(borrow_amount, 0);
-
-    let collateral_supply =
-        get_token_balance(&mut banks_client, sol_reserve.collateral_supply).await;
-    assert_eq!(collateral_supply, 0);
-
-    let collateral_deposit_amount = INITIAL_COLLATERAL_RATIO * SOL_COLLATERAL_AMOUNT_LAMPORTS;
-    lending_market
-        .borrow(
-            &mut banks_client,
-            &payer,
-            BorrowArgs {
-                deposit_reserve: &sol_reserve,
-                borrow_reserve: &usdc_reserve,
-                dex_market: &sol_usdc_dex_market,
-                borrow_amount_type: BorrowAmountType::CollateralDepositAmount,
-                amount: collateral_deposit_amount,
-                user_accounts_owner: &user_accounts_owner,
-                obligation: &usdc_obligation,
-            },
-        )
-        .await;
-
-    let borrow_amount =
-        get_token_balance(&mut banks_client, usdc_reserve.user_liquidity_account).await;
-    assert_eq!(borrow_amount, USDC_BORROW_AMOUNT_FRACTIONAL);
-
-    let borrow_fees = TEST_RESERVE_CONFIG
-        .fees
-        .calculate_borrow_fees(collateral_deposit_amount)
-        .unwrap()
-        .0;
-
-    let collateral_supply =
-        get_token_balance(&mut banks_client, sol_reserve.collateral_supply).await;
-    assert_eq!(collateral_supply, collateral_deposit_amount - borrow_fees);
+    let initial_liquidity_supply =
+        get_token_balance(&mut banks_client, usdc_reserve.liquidity_supply).await;
 
     lending_market
         .borrow(
             &mut banks_client,
             &payer,
             BorrowArgs {
-                deposit_reserve: &sol_reserve,
                 borrow_reserve: &usdc_reserve,
-                dex_market: &sol_usdc_dex_market,
-                borrow_amount_type: BorrowAmountType::LiquidityBorrowAmount,
-                amount: borrow_amount,
+                reserves: &[&usdc_reserve, &sol_reserve],
+                amount: USDC_BORROW_AMOUNT_FRACTIONAL,
                 user_accounts_owner: &user_accounts_owner,
-                obligation: &usdc_obligation,
+                obligation: &obligation,
             },
         )
         .await;
 
-    let borrow_amount =
-        get_token_balance(&mut banks_client, usdc_reserve.user_liquidity_account).await;
-    assert_eq!(borrow_amount, 2 * USDC_BORROW_AMOUNT_FRACTIONAL);
-
-    let user_collateral_balance =
-        get_token_balance(&mut banks_client, sol_reserve.user_collateral_account).await;
-    assert_eq!(user_collateral_balance, 0);
-
-    let collateral_deposited = 2 * collateral_deposit_amount;
     let (total_fee, host_fee) = TEST_RESERVE_CONFIG
         .fees
-        .calculate_borrow_fees(collateral_deposited)
+        .calculate_borrow_fees(USDC_BORROW_AMOUNT_FRACTIONAL)
         .unwrap();
 
-    assert!(total_fee > 0);
-    assert!(host_fee > 0);
+    let borrow_amount =
+        get_token_balance(&mut banks_client, usdc_reserve.user_liquidity_account).await;
+    assert_eq!(borrow_amount, USDC_BORROW_AMOUNT_FRACTIONAL - total_fee);
 
-    let collateral_supply =
-        get_token_balance(&mut banks_client, sol_reserve.collateral_supply).await;
-    assert_eq!(collateral_supply, collateral_deposited - total_fee);
+    let liquidity_supply =
+        get_token_balance(&mut banks_client, usdc_reserve.liquidity_supply).await;
+    assert_eq!(
+        liquidity_supply,
+        initial_liquidity_supply - USDC_BORROW_AMOUNT_FRACTIONAL
+    );
 
     let fee_balance =
-        get_token_balance(&mut banks_client, sol_reserve.collateral_fees_receiver).await;
+        get_token_balance(&mut banks_client, usdc_reserve.liquidity_fee_receiver).await;
     assert_eq!(fee_balance, total_fee - host_fee);
 
-    let host_fee_balance = get_token_balance(&mut banks_client, sol_reserve.collateral_host).await;
+    let host_fee_balance = get_token_balance(&mut banks_client, usdc_reserve.liquidity_host).await;
     assert_eq!(host_fee_balance, host_fee);
+
+    let obligation_state = obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation_state.borrows.len(), 1);
+    assert_eq!(
+        obligation_state.borrows[0].borrow_reserve,
+        usdc_reserve.pubkey
+    );
+    assert_eq!(
+        obligation_state.borrows[0].borrowed_amount_wads,
+        Decimal::from(USDC_BORROW_AMOUNT_FRACTIONAL)
+    );
+    assert!(obligation_state.stale);
 }
 
 #[tokio::test]
 async fn test_borrow_base_currency() {
-    // Using SOL/USDC min 2 asks:
-    //  $14.074, 4707.1 SOL
-    //  $14.055, 1751.7 SOL
-    //  $13.989,   12.1 SOL
+    // Using SOL/USDC best bid:
+    //  $13.988,  300.0 SOL
     //
-    // Borrow amount = 2000 SOL
-    // Collateral amount = 13.989 * 12.1 + 14.055 * 1751.7 + 14.074 * 236.2 = 28,113.6892 USDC
-    const SOL_BORROW_AMOUNT_LAMPORTS: u64 = 2000 * LAMPORTS_TO_SOL;
-    const USDC_COLLATERAL_LAMPORTS: u64 = 28_113_689_200;
+    // Collateral value = 10,000 USDC
+    // Allowed borrow value = 10,000 * 0.5 (LTV) = 5,000 USDC
+    // Borrow value = 300 * 13.988 = 4,196.4 USDC
+    const USDC_COLLATERAL_AMOUNT_FRACTIONAL: u64 = 10_000 * FRACTIONAL_TO_USDC;
+    const SOL_BORROW_AMOUNT_LAMPORTS: u64 = 300 * LAMPORTS_TO_SOL;
     const INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS: u64 = 5000 * LAMPORTS_TO_SOL;
-    const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 2 * USDC_COLLATERAL_LAMPORTS;
+    const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 2 * USDC_COLLATERAL_AMOUNT_FRACTIONAL;
 
     let mut test = ProgramTest::new(
         "spl_token_lending",
@@ -197,9 +159,7 @@ async fn test_borrow_base_currency() {
     let usdc_mint = add_usdc_mint(&mut test);
     let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
 
-    let mut reserve_config = TEST_RESERVE_CONFIG;
-    reserve_config.loan_to_value_ratio = 100;
-
+    let collateral_amount = INITIAL_COLLATERAL_RATIO * USDC_COLLATERAL_AMOUNT_FRACTIONAL;
     let usdc_reserve = add_reserve(
         &mut test,
         &user_accounts_owner,
@@ -208,7 +168,8 @@ async fn test_borrow_base_currency() {
             liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
             liquidity_mint_pubkey: usdc_mint.pubkey,
             liquidity_mint_decimals: usdc_mint.decimals,
-            config: reserve_config,
+            collateral_amount,
+            config: TEST_RESERVE_CONFIG,
             ..AddReserveArgs::default()
         },
     );
@@ -218,108 +179,74 @@ async fn test_borrow_base_currency() {
         &user_accounts_owner,
         &lending_market,
         AddReserveArgs {
-            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            dex_market: Some(&sol_usdc_dex_market),
             liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
             liquidity_mint_pubkey: spl_token::native_mint::id(),
             liquidity_mint_decimals: 9,
-            config: reserve_config,
+            config: TEST_RESERVE_CONFIG,
             ..AddReserveArgs::default()
         },
     );
 
-    let sol_obligation = add_obligation(
+    let obligation = add_obligation(
         &mut test,
         &user_accounts_owner,
         &lending_market,
         AddObligationArgs {
-            borrow_reserve: &sol_reserve,
-            collateral_reserve: &usdc_reserve,
-            collateral_amount: 0,
-            borrowed_liquidity_wads: Decimal::zero(),
+            deposits: &[(&usdc_reserve, collateral_amount)],
+            borrows: &[],
         },
     );
 
     let (mut banks_client, payer, _recent_blockhash) = test.start().await;
 
-    let borrow_amount =
-        get_token_balance(&mut banks_client, sol_reserve.user_liquidity_account).await;
-    assert_eq!(borrow_amount, 0);
-
-    let collateral_supply =
-        get_token_balance(&mut banks_client, usdc_reserve.collateral_supply).await;
-    assert_eq!(collateral_supply, 0);
-
-    let collateral_deposit_amount = INITIAL_COLLATERAL_RATIO * USDC_COLLATERAL_LAMPORTS;
-    lending_market
-        .borrow(
-            &mut banks_client,
-            &payer,
-            BorrowArgs {
-                deposit_reserve: &usdc_reserve,
-                borrow_reserve: &sol_reserve,
-                dex_market: &sol_usdc_dex_market,
-                borrow_amount_type: BorrowAmountType::CollateralDepositAmount,
-                amount: collateral_deposit_amount,
-                user_accounts_owner: &user_accounts_owner,
-                obligation: &sol_obligation,
-            },
-        )
-        .await;
-
-    let borrow_amount =
+    let initial_borrow_balance =
         get_token_balance(&mut banks_client, sol_reserve.user_liquidity_account).await;
-    assert_eq!(borrow_amount, SOL_BORROW_AMOUNT_LAMPORTS);
-
-    let borrow_fees = TEST_RESERVE_CONFIG
-        .fees
-        .calculate_borrow_fees(collateral_deposit_amount)
-        .unwrap()
-        .0;
-
-    let collateral_supply =
-        get_token_balance(&mut banks_client, usdc_reserve.collateral_supply).await;
-    assert_eq!(collateral_supply, collateral_deposit_amount - borrow_fees);
 
     lending_market
         .borrow(
             &mut banks_client,
             &payer,
             BorrowArgs {
-                deposit_reserve: &usdc_reserve,
                 borrow_reserve: &sol_reserve,
-                dex_market: &sol_usdc_dex_market,
-                borrow_amount_type: BorrowAmountType::LiquidityBorrowAmount,
-                amount: borrow_amount,
+                reserves: &[&usdc_reserve, &sol_reserve],
+                amount: SOL_BORROW_AMOUNT_LAMPORTS,
                 user_accounts_owner: &user_accounts_owner,
-                obligation: &sol_obligation,
+                obligation: &obligation,
             },
         )
         .await;
 
-    let borrow_amount =
-        get_token_balance(&mut banks_client, sol_reserve.user_liquidity_account).await;
-    assert_eq!(borrow_amount, 2 * SOL_BORROW_AMOUNT_LAMPORTS);
-
-    let (mut total_fee, mut host_fee) = TEST_RESERVE_CONFIG
+    let (total_fee, host_fee) = TEST_RESERVE_CONFIG
         .fees
-        .calculate_borrow_fees(collateral_deposit_amount)
+        .calculate_borrow_fees(SOL_BORROW_AMOUNT_LAMPORTS)
         .unwrap();
 
-    // avoid rounding error by assessing fees individually
-    total_fee *= 2;
-    host_fee *= 2;
-
-    assert!(total_fee > 0);
-    assert!(host_fee > 0);
-
-    let collateral_supply =
-        get_token_balance(&mut banks_client, usdc_reserve.collateral_supply).await;
-    assert_eq!(collateral_supply, 2 * collateral_deposit_amount - total_fee);
+    let borrow_balance =
+        get_token_balance(&mut banks_client, sol_reserve.user_liquidity_account).await;
+    assert_eq!(
+        borrow_balance,
+        initial_borrow_balance + SOL_BORROW_AMOUNT_LAMPORTS - total_fee
+    );
 
     let fee_balance =
-        get_token_balance(&mut banks_client, usdc_reserve.collateral_fees_receiver).await;
+        get_token_balance(&mut banks_client, sol_reserve.liquidity_fee_receiver).await;
     assert_eq!(fee_balance, total_fee - host_fee);
 
-    let host_fee_balance = get_token_balance(&mut banks_client, usdc_reserve.collateral_host).await;
+    let host_fee_balance = get_token_balance(&mut banks_client, sol_reserve.liquidity_host).await;
     assert_eq!(host_fee_balance, host_fee);
+
+    let sol_reserve_state = sol_reserve.get_state(&mut banks_client).await;
+    assert!(sol_reserve_state.liquidity.market_price > Decimal::zero());
+    assert_eq!(
+        sol_reserve_state.liquidity.borrowed_amount_wads,
+        Decimal::from(SOL_BORROW_AMOUNT_LAMPORTS)
+    );
+
+    let obligation_state = obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation_state.borrows.len(), 1);
+    assert_eq!(
+        obligation_state.borrows[0].borrow_reserve,
+        sol_reserve.pubkey
+    );
 }
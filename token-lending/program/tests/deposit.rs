@@ -4,8 +4,17 @@ mod helpers;
 
 use helpers::*;
 use solana_program_test::*;
-use solana_sdk::{pubkey::Pubkey, signature::Keypair};
-use spl_token_lending::processor::process_instruction;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::instruction::approve;
+use spl_token_lending::{
+    error::LendingError, instruction::deposit_reserve_liquidity, processor::process_instruction,
+    state::ReserveConfig,
+};
 
 const FRACTIONAL_TO_USDC: u64 = 1_000_000;
 
@@ -50,3 +59,82 @@ async fn test_success() {
         )
         .await;
 }
+
+#[tokio::test]
+async fn test_supply_cap_exceeded() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(35_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            user_liquidity_amount: 100 * FRACTIONAL_TO_USDC,
+            liquidity_amount: 10_000 * FRACTIONAL_TO_USDC,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: ReserveConfig {
+                supply_cap: 10_050 * FRACTIONAL_TO_USDC,
+                ..TEST_RESERVE_CONFIG
+            },
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &usdc_reserve.user_liquidity_account,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                100 * FRACTIONAL_TO_USDC,
+            )
+            .unwrap(),
+            deposit_reserve_liquidity(
+                spl_token_lending::id(),
+                100 * FRACTIONAL_TO_USDC,
+                usdc_reserve.user_liquidity_account,
+                usdc_reserve.user_collateral_account,
+                usdc_reserve.pubkey,
+                usdc_reserve.liquidity_supply,
+                usdc_reserve.collateral_mint,
+                lending_market.pubkey,
+                lending_market.authority,
+                user_transfer_authority.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ReserveCapExceeded as u32)
+        )
+    );
+}
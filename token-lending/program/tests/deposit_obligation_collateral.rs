@@ -5,13 +5,12 @@ mod helpers;
 use helpers::*;
 use solana_program_test::*;
 use solana_sdk::{
-    pubkey::Pubkey,
     signature::{Keypair, Signer},
     transaction::Transaction,
 };
 use spl_token::instruction::approve;
 use spl_token_lending::{
-    instruction::deposit_obligation_collateral, math::Decimal, processor::process_instruction,
+    instruction::deposit_obligation_collateral, processor::process_instruction,
     state::INITIAL_COLLATERAL_RATIO,
 };
 
@@ -77,10 +76,8 @@ async fn test_success() {
         &user_accounts_owner,
         &lending_market,
         AddObligationArgs {
-            borrow_reserve: &usdc_reserve,
-            collateral_reserve: &sol_reserve,
-            collateral_amount: OBLIGATION_COLLATERAL,
-            borrowed_liquidity_wads: Decimal::from(OBLIGATION_LOAN),
+            deposits: &[(&sol_reserve, OBLIGATION_COLLATERAL)],
+            borrows: &[(&usdc_reserve, OBLIGATION_LOAN)],
         },
     );
 
@@ -90,8 +87,6 @@ async fn test_success() {
         get_token_balance(&mut banks_client, sol_reserve.collateral_supply).await;
     let initial_user_collateral_balance =
         get_token_balance(&mut banks_client, sol_reserve.user_collateral_account).await;
-    let initial_obligation_token_balance =
-        get_token_balance(&mut banks_client, obligation.token_account).await;
 
     let mut transaction = Transaction::new_with_payer(
         &[
@@ -111,10 +106,8 @@ async fn test_success() {
                 sol_reserve.collateral_supply,
                 sol_reserve.pubkey,
                 obligation.pubkey,
-                obligation.token_mint,
-                obligation.token_account,
                 lending_market.pubkey,
-                lending_market.authority,
+                user_accounts_owner.pubkey(),
                 user_transfer_authority.pubkey(),
             ),
         ],
@@ -141,11 +134,12 @@ async fn test_success() {
         initial_user_collateral_balance - DEPOSIT_COLLATERAL
     );
 
-    // check that obligation tokens were minted
-    let obligation_token_balance =
-        get_token_balance(&mut banks_client, obligation.token_account).await;
+    // check that the deposit was recorded and the obligation needs a refresh
+    let obligation_state = obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation_state.deposits.len(), 1);
     assert_eq!(
-        obligation_token_balance,
-        initial_obligation_token_balance + DEPOSIT_COLLATERAL
+        obligation_state.deposits[0].deposited_amount,
+        OBLIGATION_COLLATERAL + DEPOSIT_COLLATERAL
     );
+    assert!(obligation_state.stale);
 }
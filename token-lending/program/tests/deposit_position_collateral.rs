@@ -0,0 +1,190 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError, instruction::deposit_position_collateral, processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+
+const FRACTIONAL_TO_USDC: u64 = 1_000_000;
+const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+const POSITION_COLLATERAL_AMOUNT: u64 = 10 * FRACTIONAL_TO_USDC * INITIAL_COLLATERAL_RATIO;
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(30_000);
+
+    const DEPOSIT_COLLATERAL: u64 = POSITION_COLLATERAL_AMOUNT / 2;
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            collateral_amount: POSITION_COLLATERAL_AMOUNT,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let deposit_position = add_deposit_position(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        &usdc_reserve,
+        POSITION_COLLATERAL_AMOUNT,
+    );
+
+    let obligation = add_obligation(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            deposits: &[],
+            borrows: &[],
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_collateral_supply_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.collateral_supply).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[deposit_position_collateral(
+            spl_token_lending::id(),
+            DEPOSIT_COLLATERAL,
+            deposit_position.pubkey,
+            usdc_reserve.pubkey,
+            obligation.pubkey,
+            lending_market.pubkey,
+            user_accounts_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // check that no collateral tokens were moved
+    let collateral_supply_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.collateral_supply).await;
+    assert_eq!(collateral_supply_balance, initial_collateral_supply_balance);
+
+    let deposit_position_state = deposit_position.get_state(&mut banks_client).await;
+    assert_eq!(
+        deposit_position_state.collateral_amount,
+        POSITION_COLLATERAL_AMOUNT - DEPOSIT_COLLATERAL
+    );
+
+    // check that the deposit was recorded and restricts collateral withdrawals
+    let obligation_state = obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation_state.deposits.len(), 1);
+    assert_eq!(
+        obligation_state.deposits[0].deposited_amount,
+        DEPOSIT_COLLATERAL
+    );
+    assert_eq!(
+        obligation_state.deposits[0].position_amount,
+        DEPOSIT_COLLATERAL
+    );
+    assert!(obligation_state.stale);
+}
+
+#[tokio::test]
+async fn test_insufficient_position_collateral() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(30_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            collateral_amount: POSITION_COLLATERAL_AMOUNT,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let deposit_position = add_deposit_position(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        &usdc_reserve,
+        POSITION_COLLATERAL_AMOUNT,
+    );
+
+    let obligation = add_obligation(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            deposits: &[],
+            borrows: &[],
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[deposit_position_collateral(
+            spl_token_lending::id(),
+            POSITION_COLLATERAL_AMOUNT + 1,
+            deposit_position.pubkey,
+            usdc_reserve.pubkey,
+            obligation.pubkey,
+            lending_market.pubkey,
+            user_accounts_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InsufficientPositionCollateral as u32)
+        )
+    );
+}
@@ -0,0 +1,118 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use spl_token::instruction::approve;
+use spl_token_lending::{
+    instruction::deposit_position_liquidity, processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+
+const FRACTIONAL_TO_USDC: u64 = 1_000_000;
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(40_000);
+
+    const DEPOSIT_AMOUNT: u64 = 100 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let user_transfer_authority = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            user_liquidity_amount: DEPOSIT_AMOUNT,
+            liquidity_amount: 10_000 * FRACTIONAL_TO_USDC,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let deposit_position = add_deposit_position(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        &usdc_reserve,
+        0,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_collateral_supply_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.collateral_supply).await;
+    let initial_user_collateral_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.user_collateral_account).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &usdc_reserve.user_liquidity_account,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                DEPOSIT_AMOUNT,
+            )
+            .unwrap(),
+            deposit_position_liquidity(
+                spl_token_lending::id(),
+                DEPOSIT_AMOUNT,
+                usdc_reserve.user_liquidity_account,
+                deposit_position.pubkey,
+                usdc_reserve.pubkey,
+                usdc_reserve.liquidity_supply,
+                usdc_reserve.collateral_mint,
+                usdc_reserve.collateral_supply,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+                user_transfer_authority.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // check that the collateral was minted into the reserve and recorded by the position
+    let collateral_amount = DEPOSIT_AMOUNT * INITIAL_COLLATERAL_RATIO;
+    let collateral_supply_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.collateral_supply).await;
+    assert_eq!(
+        collateral_supply_balance,
+        initial_collateral_supply_balance + collateral_amount
+    );
+    let user_collateral_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.user_collateral_account).await;
+    assert_eq!(user_collateral_balance, initial_user_collateral_balance);
+
+    let deposit_position_state = deposit_position.get_state(&mut banks_client).await;
+    assert_eq!(deposit_position_state.collateral_amount, collateral_amount);
+
+    let user_liquidity_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.user_liquidity_account).await;
+    assert_eq!(user_liquidity_balance, 0);
+}
@@ -0,0 +1,331 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::AccountMeta,
+    program::invoke,
+    program_error::ProgramError,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    sysvar,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{flash_loan, refresh_reserve, RefreshReserveOracle},
+    processor::process_instruction,
+};
+use std::convert::TryInto;
+
+const FRACTIONAL_TO_USDC: u64 = 1_000_000;
+const FLASH_LOAN_AMOUNT_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 10_000 * FRACTIONAL_TO_USDC;
+
+/// Flash loan receiver which returns the borrowed amount plus the flash loan fee
+fn process_repay_with_fee(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let amount = unpack_flash_loan_amount(input)?;
+    let (flash_loan_fee, _host_fee) = TEST_RESERVE_CONFIG.fees.calculate_flash_loan_fees(amount)?;
+    return_flash_loan(accounts, amount + flash_loan_fee)
+}
+
+/// Flash loan receiver which returns only the borrowed amount
+fn process_repay_without_fee(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let amount = unpack_flash_loan_amount(input)?;
+    return_flash_loan(accounts, amount)
+}
+
+/// Flash loan receiver which re-enters the lending program with the reserve
+/// before returning the borrowed amount plus the flash loan fee
+fn process_reenter_and_repay(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let amount = unpack_flash_loan_amount(input)?;
+    let (flash_loan_fee, _host_fee) = TEST_RESERVE_CONFIG.fees.calculate_flash_loan_fees(amount)?;
+
+    let reserve_info = &accounts[4];
+    let clock_info = &accounts[5];
+    let lending_program_info = &accounts[6];
+    invoke(
+        &refresh_reserve(
+            *lending_program_info.key,
+            *reserve_info.key,
+            RefreshReserveOracle::QuoteCurrency,
+        ),
+        &[
+            reserve_info.clone(),
+            clock_info.clone(),
+            lending_program_info.clone(),
+        ],
+    )?;
+
+    return_flash_loan(accounts, amount + flash_loan_fee)
+}
+
+fn unpack_flash_loan_amount(input: &[u8]) -> Result<u64, ProgramError> {
+    match input.split_first() {
+        Some((&0, rest)) if rest.len() == 8 => Ok(u64::from_le_bytes(rest.try_into().unwrap())),
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+fn return_flash_loan(accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program_id.key,
+            destination_liquidity_info.key,
+            reserve_liquidity_supply_info.key,
+            user_transfer_authority_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            destination_liquidity_info.clone(),
+            reserve_liquidity_supply_info.clone(),
+            user_transfer_authority_info.clone(),
+            token_program_id.clone(),
+        ],
+    )
+}
+
+struct FlashLoanTest {
+    banks_client: BanksClient,
+    payer: Keypair,
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    usdc_reserve: TestReserve,
+    receiver_program_id: Pubkey,
+}
+
+fn program_test() -> ProgramTest {
+    ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    )
+}
+
+async fn setup(mut test: ProgramTest, receiver_program_id: Pubkey) -> FlashLoanTest {
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(60_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            user_liquidity_amount: FRACTIONAL_TO_USDC * 10,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (banks_client, payer, _recent_blockhash) = test.start().await;
+
+    FlashLoanTest {
+        banks_client,
+        payer,
+        user_accounts_owner,
+        lending_market,
+        usdc_reserve,
+        receiver_program_id,
+    }
+}
+
+async fn process_flash_loan(test: &mut FlashLoanTest) -> Result<(), TransactionError> {
+    process_flash_loan_with_accounts(test, vec![]).await
+}
+
+async fn process_flash_loan_with_accounts(
+    test: &mut FlashLoanTest,
+    extra_accounts: Vec<AccountMeta>,
+) -> Result<(), TransactionError> {
+    let mut receiver_accounts = vec![
+        AccountMeta::new(test.usdc_reserve.liquidity_supply, false),
+        AccountMeta::new_readonly(test.user_accounts_owner.pubkey(), true),
+    ];
+    receiver_accounts.extend(extra_accounts);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_loan(
+            spl_token_lending::id(),
+            FLASH_LOAN_AMOUNT_FRACTIONAL,
+            test.usdc_reserve.liquidity_supply,
+            test.usdc_reserve.user_liquidity_account,
+            test.usdc_reserve.pubkey,
+            test.usdc_reserve.liquidity_fee_receiver,
+            test.usdc_reserve.liquidity_host,
+            test.lending_market.pubkey,
+            test.receiver_program_id,
+            receiver_accounts,
+        )],
+        Some(&test.payer.pubkey()),
+    );
+
+    let recent_blockhash = test.banks_client.get_recent_blockhash().await.unwrap();
+    transaction.sign(&[&test.payer, &test.user_accounts_owner], recent_blockhash);
+    test.banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = program_test();
+    let receiver_program_id = Pubkey::new_unique();
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id,
+        processor!(process_repay_with_fee),
+    );
+    let mut test = setup(test, receiver_program_id).await;
+
+    let initial_liquidity_supply =
+        get_token_balance(&mut test.banks_client, test.usdc_reserve.liquidity_supply).await;
+    let initial_user_liquidity = get_token_balance(
+        &mut test.banks_client,
+        test.usdc_reserve.user_liquidity_account,
+    )
+    .await;
+    let initial_reserve = test.usdc_reserve.get_state(&mut test.banks_client).await;
+
+    process_flash_loan(&mut test).await.unwrap();
+
+    let (flash_loan_fee, host_fee) = TEST_RESERVE_CONFIG
+        .fees
+        .calculate_flash_loan_fees(FLASH_LOAN_AMOUNT_FRACTIONAL)
+        .unwrap();
+    assert!(flash_loan_fee > host_fee);
+    assert!(host_fee > 0);
+
+    let liquidity_supply =
+        get_token_balance(&mut test.banks_client, test.usdc_reserve.liquidity_supply).await;
+    assert_eq!(liquidity_supply, initial_liquidity_supply);
+
+    let user_liquidity = get_token_balance(
+        &mut test.banks_client,
+        test.usdc_reserve.user_liquidity_account,
+    )
+    .await;
+    assert_eq!(user_liquidity, initial_user_liquidity - flash_loan_fee);
+
+    let fee_balance = get_token_balance(
+        &mut test.banks_client,
+        test.usdc_reserve.liquidity_fee_receiver,
+    )
+    .await;
+    assert_eq!(fee_balance, flash_loan_fee - host_fee);
+
+    let host_fee_balance =
+        get_token_balance(&mut test.banks_client, test.usdc_reserve.liquidity_host).await;
+    assert_eq!(host_fee_balance, host_fee);
+
+    let reserve = test.usdc_reserve.get_state(&mut test.banks_client).await;
+    assert_eq!(
+        reserve.liquidity.available_amount,
+        initial_reserve.liquidity.available_amount
+    );
+    assert_eq!(
+        reserve.liquidity.borrowed_amount_wads,
+        initial_reserve.liquidity.borrowed_amount_wads
+    );
+}
+
+#[tokio::test]
+async fn test_fee_not_repaid() {
+    let mut test = program_test();
+    let receiver_program_id = Pubkey::new_unique();
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id,
+        processor!(process_repay_without_fee),
+    );
+    let mut test = setup(test, receiver_program_id).await;
+
+    assert_eq!(
+        process_flash_loan(&mut test).await.unwrap_err(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::FlashLoanNotRepaid as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_receiver_program() {
+    let mut test = setup(program_test(), spl_token_lending::id()).await;
+
+    assert_eq!(
+        process_flash_loan(&mut test).await.unwrap_err(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidFlashLoanReceiverProgram as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_reentrant_receiver() {
+    let mut test = program_test();
+    let receiver_program_id = Pubkey::new_unique();
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id,
+        processor!(process_reenter_and_repay),
+    );
+    let mut test = setup(test, receiver_program_id).await;
+
+    let initial_reserve = test.usdc_reserve.get_state(&mut test.banks_client).await;
+
+    let extra_accounts = vec![
+        AccountMeta::new(test.usdc_reserve.pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token_lending::id(), false),
+    ];
+    assert_eq!(
+        process_flash_loan_with_accounts(&mut test, extra_accounts)
+            .await
+            .unwrap_err(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+
+    let reserve = test.usdc_reserve.get_state(&mut test.banks_client).await;
+    assert_eq!(
+        reserve.liquidity.borrowed_amount_wads,
+        initial_reserve.liquidity.borrowed_amount_wads
+    );
+}
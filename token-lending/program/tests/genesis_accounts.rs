@@ -5,11 +5,7 @@ mod helpers;
 use helpers::*;
 use solana_program_test::*;
 use solana_sdk::signature::Keypair;
-use spl_token_lending::{
-    instruction::BorrowAmountType,
-    math::Decimal,
-    state::{INITIAL_COLLATERAL_RATIO, PROGRAM_VERSION},
-};
+use spl_token_lending::state::{INITIAL_COLLATERAL_RATIO, PROGRAM_VERSION};
 
 #[tokio::test]
 async fn test_genesis_accounts() {
@@ -45,6 +41,14 @@ async fn test_genesis_accounts() {
     const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = lamports_to_usdc_fractional(TOTAL_SOL);
     const INITIAL_SRM_RESERVE_SUPPLY_FRACTIONAL: u64 = 20_000 * FRACTIONAL_TO_SRM;
 
+    const USER_SOL_COLLATERAL: u64 = INITIAL_COLLATERAL_RATIO * USER_SOL_COLLATERAL_LAMPORTS;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = lamports_to_usdc_fractional(
+        TEST_RESERVE_CONFIG.loan_to_value_ratio as u64 * USER_SOL_COLLATERAL_LAMPORTS / 100,
+    );
+    const USER_USDC_COLLATERAL: u64 = 2 * INITIAL_COLLATERAL_RATIO * USDC_BORROW_AMOUNT_FRACTIONAL;
+    const SOL_BORROW_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL;
+    const SRM_BORROW_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_SRM;
+
     let user_accounts_owner = Keypair::new();
 
     let usdc_reserve = add_reserve(
@@ -53,11 +57,12 @@ async fn test_genesis_accounts() {
         &lending_market,
         AddReserveArgs {
             name: "usdc".to_owned(),
-            dex_market_pubkey: None,
+            dex_market: None,
             liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
             liquidity_mint_decimals: usdc_mint.decimals,
             liquidity_mint_pubkey: usdc_mint.pubkey,
             user_liquidity_amount: USER_SOL_DEPOSIT_LAMPORTS,
+            collateral_amount: 2 * USER_USDC_COLLATERAL,
             config: TEST_RESERVE_CONFIG,
             ..AddReserveArgs::default()
         },
@@ -69,11 +74,12 @@ async fn test_genesis_accounts() {
         &lending_market,
         AddReserveArgs {
             name: "sol".to_owned(),
-            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            dex_market: Some(&sol_usdc_dex_market),
             liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
             liquidity_mint_pubkey: spl_token::native_mint::id(),
             liquidity_mint_decimals: 9,
             user_liquidity_amount: USER_SOL_DEPOSIT_LAMPORTS,
+            collateral_amount: USER_SOL_COLLATERAL,
             config: TEST_RESERVE_CONFIG,
             ..AddReserveArgs::default()
         },
@@ -85,7 +91,7 @@ async fn test_genesis_accounts() {
         &lending_market,
         AddReserveArgs {
             name: "srm".to_owned(),
-            dex_market_pubkey: Some(srm_usdc_dex_market.pubkey),
+            dex_market: Some(&srm_usdc_dex_market),
             liquidity_amount: INITIAL_SRM_RESERVE_SUPPLY_FRACTIONAL,
             liquidity_mint_decimals: srm_mint.decimals,
             liquidity_mint_pubkey: srm_mint.pubkey,
@@ -99,10 +105,8 @@ async fn test_genesis_accounts() {
         &user_accounts_owner,
         &lending_market,
         AddObligationArgs {
-            collateral_reserve: &sol_reserve,
-            borrow_reserve: &usdc_reserve,
-            collateral_amount: 0,
-            borrowed_liquidity_wads: Decimal::zero(),
+            deposits: &[(&sol_reserve, USER_SOL_COLLATERAL)],
+            borrows: &[],
         },
     );
 
@@ -111,10 +115,8 @@ async fn test_genesis_accounts() {
         &user_accounts_owner,
         &lending_market,
         AddObligationArgs {
-            collateral_reserve: &usdc_reserve,
-            borrow_reserve: &sol_reserve,
-            collateral_amount: 0,
-            borrowed_liquidity_wads: Decimal::zero(),
+            deposits: &[(&usdc_reserve, USER_USDC_COLLATERAL)],
+            borrows: &[],
         },
     );
 
@@ -123,10 +125,8 @@ async fn test_genesis_accounts() {
         &user_accounts_owner,
         &lending_market,
         AddObligationArgs {
-            collateral_reserve: &usdc_reserve,
-            borrow_reserve: &srm_reserve,
-            collateral_amount: 0,
-            borrowed_liquidity_wads: Decimal::zero(),
+            deposits: &[(&usdc_reserve, USER_USDC_COLLATERAL)],
+            borrows: &[],
         },
     );
 
@@ -202,11 +202,9 @@ async fn test_genesis_accounts() {
             &mut banks_client,
             &payer,
             BorrowArgs {
-                deposit_reserve: &sol_reserve,
                 borrow_reserve: &usdc_reserve,
-                dex_market: &sol_usdc_dex_market,
-                borrow_amount_type: BorrowAmountType::CollateralDepositAmount,
-                amount: INITIAL_COLLATERAL_RATIO * USER_SOL_COLLATERAL_LAMPORTS,
+                reserves: &[&usdc_reserve, &sol_reserve],
+                amount: USDC_BORROW_AMOUNT_FRACTIONAL / 2,
                 user_accounts_owner: &user_accounts_owner,
                 obligation: &usdc_obligation,
             },
@@ -219,51 +217,24 @@ async fn test_genesis_accounts() {
             &mut banks_client,
             &payer,
             BorrowArgs {
-                deposit_reserve: &sol_reserve,
                 borrow_reserve: &usdc_reserve,
-                dex_market: &sol_usdc_dex_market,
-                borrow_amount_type: BorrowAmountType::CollateralDepositAmount,
-                amount: lamports_to_usdc_fractional(
-                    usdc_reserve.config.loan_to_value_ratio as u64 * USER_SOL_COLLATERAL_LAMPORTS
-                        / 100,
-                ),
+                reserves: &[&usdc_reserve, &sol_reserve],
+                amount: USDC_BORROW_AMOUNT_FRACTIONAL / 2,
                 user_accounts_owner: &user_accounts_owner,
                 obligation: &usdc_obligation,
             },
         )
         .await;
 
-    // Deposit USDC
-    lending_market
-        .deposit(
-            &mut banks_client,
-            &user_accounts_owner,
-            &payer,
-            &usdc_reserve,
-            2 * INITIAL_COLLATERAL_RATIO
-                * lamports_to_usdc_fractional(
-                    usdc_reserve.config.loan_to_value_ratio as u64 * USER_SOL_COLLATERAL_LAMPORTS
-                        / 100,
-                ),
-        )
-        .await;
-
     // Borrow SOL with USDC collateral
     lending_market
         .borrow(
             &mut banks_client,
             &payer,
             BorrowArgs {
-                deposit_reserve: &usdc_reserve,
                 borrow_reserve: &sol_reserve,
-                dex_market: &sol_usdc_dex_market,
-                borrow_amount_type: BorrowAmountType::CollateralDepositAmount,
-                amount: INITIAL_COLLATERAL_RATIO
-                    * lamports_to_usdc_fractional(
-                        usdc_reserve.config.loan_to_value_ratio as u64
-                            * USER_SOL_COLLATERAL_LAMPORTS
-                            / 100,
-                    ),
+                reserves: &[&usdc_reserve, &sol_reserve],
+                amount: SOL_BORROW_AMOUNT_LAMPORTS,
                 user_accounts_owner: &user_accounts_owner,
                 obligation: &sol_obligation,
             },
@@ -276,16 +247,9 @@ async fn test_genesis_accounts() {
             &mut banks_client,
             &payer,
             BorrowArgs {
-                deposit_reserve: &usdc_reserve,
                 borrow_reserve: &srm_reserve,
-                dex_market: &srm_usdc_dex_market,
-                borrow_amount_type: BorrowAmountType::CollateralDepositAmount,
-                amount: INITIAL_COLLATERAL_RATIO
-                    * lamports_to_usdc_fractional(
-                        usdc_reserve.config.loan_to_value_ratio as u64
-                            * USER_SOL_COLLATERAL_LAMPORTS
-                            / 100,
-                    ),
+                reserves: &[&usdc_reserve, &srm_reserve],
+                amount: SRM_BORROW_AMOUNT_FRACTIONAL,
                 user_accounts_owner: &user_accounts_owner,
                 obligation: &srm_obligation,
             },
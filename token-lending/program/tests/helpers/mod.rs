@@ -5,6 +5,7 @@ use solana_program::{program_option::COption, program_pack::Pack, pubkey::Pubkey
 use solana_program_test::*;
 use solana_sdk::{
     account::Account,
+    instruction::Instruction,
     signature::{read_keypair_file, Keypair, Signer},
     system_instruction::create_account,
     transaction::{Transaction, TransactionError},
@@ -15,35 +16,69 @@ use spl_token::{
 };
 use spl_token_lending::{
     instruction::{
-        borrow_reserve_liquidity, deposit_reserve_liquidity, init_lending_market, init_obligation,
-        init_reserve, liquidate_obligation, BorrowAmountType,
+        borrow_obligation_liquidity, deposit_reserve_liquidity, init_deposit_position,
+        init_lending_market, init_obligation, init_reserve, liquidate_obligation,
+        refresh_obligation, refresh_reserve, RefreshReserveOracle,
     },
     math::{Decimal, Rate, TryAdd, TryMul},
+    oracle::PushOracleFeed,
     processor::process_instruction,
     state::{
-        LendingMarket, NewReserveParams, Obligation, Reserve, ReserveCollateral, ReserveConfig,
-        ReserveFees, ReserveLiquidity, INITIAL_COLLATERAL_RATIO, PROGRAM_VERSION,
+        BorrowRateCurve, BorrowRateCurvePoint, DepositPosition, LendingMarket,
+        NewDepositPositionParams, NewObligationParams, NewReserveParams, Obligation,
+        ObligationCollateral, ObligationLiquidity, OracleConfig, Reserve, ReserveCollateral,
+        ReserveConfig, ReserveFees, ReserveLiquidity, INITIAL_COLLATERAL_RATIO, PROGRAM_VERSION,
     },
 };
+use spl_token_swap::{
+    oracle::PriceAccumulator,
+    state::{SwapV2, SwapVersion},
+};
 use std::str::FromStr;
 pub mod genesis;
 use genesis::GenesisAccounts;
 
 pub const TEST_RESERVE_CONFIG: ReserveConfig = ReserveConfig {
-    optimal_utilization_rate: 80,
     loan_to_value_ratio: 50,
     liquidation_bonus: 5,
     liquidation_threshold: 55,
-    min_borrow_rate: 0,
-    optimal_borrow_rate: 4,
-    max_borrow_rate: 30,
+    borrow_rate_curve: BorrowRateCurve {
+        points: [
+            borrow_rate_curve_point(0, 0),
+            borrow_rate_curve_point(8_000, 400),
+            borrow_rate_curve_point(10_000, 3_000),
+            borrow_rate_curve_point(10_000, 3_000),
+            borrow_rate_curve_point(10_000, 3_000),
+            borrow_rate_curve_point(10_000, 3_000),
+            borrow_rate_curve_point(10_000, 3_000),
+            borrow_rate_curve_point(10_000, 3_000),
+        ],
+    },
     fees: ReserveFees {
         borrow_fee_wad: 100_000_000_000,
         /// 0.00001% (Aave borrow fee)
+        flash_loan_fee_wad: 3_000_000_000_000_000, // 0.3% (Aave flash loan fee)
         host_fee_percentage: 20,
     },
+    oracle: OracleConfig::QuoteCurrency,
+    deposits_disabled: false,
+    borrows_disabled: false,
+    liquidations_disabled: false,
+    supply_cap: 0,
+    borrow_cap: 0,
+    reserve_factor: 0,
 };
 
+pub const fn borrow_rate_curve_point(
+    utilization_rate_bps: u16,
+    borrow_rate_bps: u16,
+) -> BorrowRateCurvePoint {
+    BorrowRateCurvePoint {
+        utilization_rate_bps,
+        borrow_rate_bps,
+    }
+}
+
 pub const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
 pub const SRM_MINT: &str = "SRMuApVNdxXokk5GT7XD5cUUgXMBCoAz2LHeuAoKWRt";
 
@@ -145,10 +180,8 @@ pub fn add_lending_market(test: &mut ProgramTest, quote_token_mint: Pubkey) -> T
 }
 
 pub struct AddObligationArgs<'a> {
-    pub borrow_reserve: &'a TestReserve,
-    pub collateral_reserve: &'a TestReserve,
-    pub collateral_amount: u64,
-    pub borrowed_liquidity_wads: Decimal,
+    pub deposits: &'a [(&'a TestReserve, u64)],
+    pub borrows: &'a [(&'a TestReserve, u64)],
 }
 
 pub fn add_obligation(
@@ -157,69 +190,71 @@ pub fn add_obligation(
     lending_market: &TestLendingMarket,
     args: AddObligationArgs,
 ) -> TestObligation {
-    let AddObligationArgs {
-        borrow_reserve,
-        collateral_reserve,
-        collateral_amount,
-        borrowed_liquidity_wads,
-    } = args;
+    let AddObligationArgs { deposits, borrows } = args;
 
-    let token_mint_pubkey = Pubkey::new_unique();
-    test.add_packable_account(
-        token_mint_pubkey,
-        u32::MAX as u64,
-        &Mint {
-            is_initialized: true,
-            decimals: collateral_reserve.liquidity_mint_decimals,
-            mint_authority: COption::Some(lending_market.authority),
-            supply: collateral_amount,
-            ..Mint::default()
-        },
-        &spl_token::id(),
-    );
+    let mut obligation = Obligation::new(NewObligationParams {
+        current_slot: 0,
+        lending_market: lending_market.pubkey,
+        owner: user_accounts_owner.pubkey(),
+    });
+    for (deposit_reserve, collateral_amount) in deposits {
+        let mut collateral = ObligationCollateral::new(deposit_reserve.pubkey);
+        collateral.deposit(*collateral_amount).unwrap();
+        obligation.deposits.push(collateral);
+    }
+    for (borrow_reserve, liquidity_amount) in borrows {
+        let mut liquidity = ObligationLiquidity::new(borrow_reserve.pubkey, Decimal::one());
+        liquidity.borrow(Decimal::from(*liquidity_amount)).unwrap();
+        obligation.borrows.push(liquidity);
+    }
 
-    let token_account_pubkey = Pubkey::new_unique();
+    let obligation_pubkey = Pubkey::new_unique();
     test.add_packable_account(
-        token_account_pubkey,
+        obligation_pubkey,
         u32::MAX as u64,
-        &Token {
-            mint: token_mint_pubkey,
-            owner: user_accounts_owner.pubkey(),
-            state: AccountState::Initialized,
-            amount: collateral_amount,
-            ..Token::default()
-        },
-        &spl_token::id(),
+        &obligation,
+        &spl_token_lending::id(),
     );
 
-    let obligation_keypair = Keypair::new();
-    let obligation_pubkey = obligation_keypair.pubkey();
+    TestObligation {
+        pubkey: obligation_pubkey,
+        lending_market: lending_market.pubkey,
+        owner: user_accounts_owner.pubkey(),
+    }
+}
+
+pub fn add_deposit_position(
+    test: &mut ProgramTest,
+    user_accounts_owner: &Keypair,
+    lending_market: &TestLendingMarket,
+    reserve: &TestReserve,
+    collateral_amount: u64,
+) -> TestDepositPosition {
+    let mut deposit_position = DepositPosition::new(NewDepositPositionParams {
+        lending_market: lending_market.pubkey,
+        reserve: reserve.pubkey,
+        owner: user_accounts_owner.pubkey(),
+    });
+    deposit_position.deposit(collateral_amount).unwrap();
+
+    let deposit_position_pubkey = Pubkey::new_unique();
     test.add_packable_account(
-        obligation_pubkey,
+        deposit_position_pubkey,
         u32::MAX as u64,
-        &Obligation {
-            version: PROGRAM_VERSION,
-            deposited_collateral_tokens: collateral_amount,
-            collateral_reserve: collateral_reserve.pubkey,
-            cumulative_borrow_rate_wads: Decimal::one(),
-            borrowed_liquidity_wads,
-            borrow_reserve: borrow_reserve.pubkey,
-            token_mint: token_mint_pubkey,
-        },
+        &deposit_position,
         &spl_token_lending::id(),
     );
 
-    TestObligation {
-        pubkey: obligation_pubkey,
-        token_mint: token_mint_pubkey,
-        token_account: token_account_pubkey,
-        borrow_reserve: borrow_reserve.pubkey,
-        collateral_reserve: collateral_reserve.pubkey,
+    TestDepositPosition {
+        pubkey: deposit_position_pubkey,
+        lending_market: lending_market.pubkey,
+        reserve: reserve.pubkey,
+        owner: user_accounts_owner.pubkey(),
     }
 }
 
 #[derive(Default)]
-pub struct AddReserveArgs {
+pub struct AddReserveArgs<'a> {
     pub name: String,
     pub slots_elapsed: u64,
     pub config: ReserveConfig,
@@ -231,7 +266,8 @@ pub struct AddReserveArgs {
     pub initial_borrow_rate: u8,
     pub collateral_amount: u64,
     pub fees_amount: u64,
-    pub dex_market_pubkey: Option<Pubkey>,
+    pub dex_market: Option<&'a TestDexMarket>,
+    pub oracle: Option<TestReserveOracle>,
 }
 
 pub fn add_reserve(
@@ -252,9 +288,23 @@ pub fn add_reserve(
         initial_borrow_rate,
         collateral_amount,
         fees_amount,
-        dex_market_pubkey,
+        dex_market,
+        oracle,
     } = args;
 
+    // Dex market reserves don't need any oracle config parameters
+    let mut config = config;
+    let oracle = match dex_market {
+        Some(dex_market) => {
+            config.oracle = OracleConfig::DexMarket;
+            Some(TestReserveOracle::DexMarket {
+                dex_market: dex_market.pubkey,
+                dex_market_bids: dex_market.bids_pubkey,
+            })
+        }
+        None => oracle,
+    };
+
     let is_native = if liquidity_mint_pubkey == spl_token::native_mint::id() {
         COption::Some(1)
     } else {
@@ -289,29 +339,37 @@ pub fn add_reserve(
         &spl_token::id(),
     );
 
-    let collateral_fees_receiver_pubkey = Pubkey::new_unique();
+    let amount = if let COption::Some(rent_reserve) = is_native {
+        fees_amount + rent_reserve
+    } else {
+        u32::MAX as u64
+    };
+
+    let liquidity_fee_receiver_pubkey = Pubkey::new_unique();
     test.add_packable_account(
-        collateral_fees_receiver_pubkey,
-        u32::MAX as u64,
+        liquidity_fee_receiver_pubkey,
+        amount,
         &Token {
-            mint: collateral_mint_pubkey,
+            mint: liquidity_mint_pubkey,
             owner: lending_market.owner.pubkey(),
             amount: fees_amount,
             state: AccountState::Initialized,
+            is_native,
             ..Token::default()
         },
         &spl_token::id(),
     );
 
-    let collateral_host_pubkey = Pubkey::new_unique();
+    let liquidity_host_pubkey = Pubkey::new_unique();
     test.add_packable_account(
-        collateral_host_pubkey,
-        u32::MAX as u64,
+        liquidity_host_pubkey,
+        amount,
         &Token {
-            mint: collateral_mint_pubkey,
+            mint: liquidity_mint_pubkey,
             owner: user_accounts_owner.pubkey(),
             amount: fees_amount,
             state: AccountState::Initialized,
+            is_native,
             ..Token::default()
         },
         &spl_token::id(),
@@ -340,21 +398,25 @@ pub fn add_reserve(
 
     let reserve_keypair = Keypair::new();
     let reserve_pubkey = reserve_keypair.pubkey();
+    let market_price = if oracle.is_none() {
+        Decimal::one()
+    } else {
+        Decimal::zero()
+    };
     let reserve_liquidity = ReserveLiquidity::new(
         liquidity_mint_pubkey,
         liquidity_mint_decimals,
         liquidity_supply_pubkey,
+        liquidity_fee_receiver_pubkey,
+        market_price,
     );
-    let reserve_collateral = ReserveCollateral::new(
-        collateral_mint_pubkey,
-        collateral_supply_pubkey,
-        collateral_fees_receiver_pubkey,
-    );
+    let reserve_collateral =
+        ReserveCollateral::new(collateral_mint_pubkey, collateral_supply_pubkey);
     let mut reserve = Reserve::new(NewReserveParams {
         // intentionally wrapped to simulate elapsed slots
         current_slot: 1u64.wrapping_sub(slots_elapsed),
         lending_market: lending_market.pubkey,
-        dex_market: dex_market_pubkey.into(),
+        oracle: oracle.map(|oracle| oracle.pubkey()).into(),
         liquidity: reserve_liquidity,
         collateral: reserve_collateral,
         config,
@@ -416,11 +478,11 @@ pub fn add_reserve(
         liquidity_supply: liquidity_supply_pubkey,
         collateral_mint: collateral_mint_pubkey,
         collateral_supply: collateral_supply_pubkey,
-        collateral_fees_receiver: collateral_fees_receiver_pubkey,
-        collateral_host: collateral_host_pubkey,
+        liquidity_fee_receiver: liquidity_fee_receiver_pubkey,
+        liquidity_host: liquidity_host_pubkey,
         user_liquidity_account: user_liquidity_pubkey,
         user_collateral_account: user_collateral_pubkey,
-        dex_market: dex_market_pubkey,
+        oracle,
     }
 }
 
@@ -432,11 +494,11 @@ pub struct TestLendingMarket {
 }
 
 pub struct BorrowArgs<'a> {
-    pub deposit_reserve: &'a TestReserve,
     pub borrow_reserve: &'a TestReserve,
-    pub borrow_amount_type: BorrowAmountType,
+    /// Reserves to refresh before borrowing, including the borrow reserve and
+    /// every reserve used by the obligation
+    pub reserves: &'a [&'a TestReserve],
     pub amount: u64,
-    pub dex_market: &'a TestDexMarket,
     pub user_accounts_owner: &'a Keypair,
     pub obligation: &'a TestObligation,
 }
@@ -444,9 +506,11 @@ pub struct BorrowArgs<'a> {
 pub struct LiquidateArgs<'a> {
     pub repay_reserve: &'a TestReserve,
     pub withdraw_reserve: &'a TestReserve,
+    /// Reserves to refresh before liquidating, including the repay and
+    /// withdraw reserves and every reserve used by the obligation
+    pub reserves: &'a [&'a TestReserve],
     pub obligation: &'a TestObligation,
     pub amount: u64,
-    pub dex_market: &'a TestDexMarket,
     pub user_accounts_owner: &'a Keypair,
 }
 
@@ -548,58 +612,49 @@ impl TestLendingMarket {
         let LiquidateArgs {
             repay_reserve,
             withdraw_reserve,
+            reserves,
             obligation,
             amount,
-            dex_market,
             user_accounts_owner,
         } = args;
 
-        let dex_market_orders_pubkey = if repay_reserve.dex_market.is_none() {
-            dex_market.asks_pubkey
-        } else {
-            dex_market.bids_pubkey
-        };
-
         let memory_keypair = Keypair::new();
         let user_transfer_authority = Keypair::new();
-        let mut transaction = Transaction::new_with_payer(
-            &[
-                create_account(
-                    &payer.pubkey(),
-                    &memory_keypair.pubkey(),
-                    0,
-                    65548,
-                    &spl_token_lending::id(),
-                ),
-                approve(
-                    &spl_token::id(),
-                    &repay_reserve.user_liquidity_account,
-                    &user_transfer_authority.pubkey(),
-                    &user_accounts_owner.pubkey(),
-                    &[],
-                    amount,
-                )
-                .unwrap(),
-                liquidate_obligation(
-                    spl_token_lending::id(),
-                    amount,
-                    repay_reserve.user_liquidity_account,
-                    withdraw_reserve.user_collateral_account,
-                    repay_reserve.pubkey,
-                    repay_reserve.liquidity_supply,
-                    withdraw_reserve.pubkey,
-                    withdraw_reserve.collateral_supply,
-                    obligation.pubkey,
-                    self.pubkey,
-                    self.authority,
-                    user_transfer_authority.pubkey(),
-                    dex_market.pubkey,
-                    dex_market_orders_pubkey,
-                    memory_keypair.pubkey(),
-                ),
-            ],
-            Some(&payer.pubkey()),
+
+        let mut instructions = vec![create_memory_account(payer, &memory_keypair)];
+        instructions.extend(
+            reserves
+                .iter()
+                .map(|reserve| reserve.refresh_instruction(memory_keypair.pubkey())),
+        );
+        instructions.push(obligation.refresh_instruction(banks_client).await);
+        instructions.push(
+            approve(
+                &spl_token::id(),
+                &repay_reserve.user_liquidity_account,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                amount,
+            )
+            .unwrap(),
         );
+        instructions.push(liquidate_obligation(
+            spl_token_lending::id(),
+            amount,
+            repay_reserve.user_liquidity_account,
+            withdraw_reserve.user_collateral_account,
+            repay_reserve.pubkey,
+            repay_reserve.liquidity_supply,
+            withdraw_reserve.pubkey,
+            withdraw_reserve.collateral_supply,
+            obligation.pubkey,
+            self.pubkey,
+            self.authority,
+            user_transfer_authority.pubkey(),
+        ));
+
+        let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
 
         let recent_blockhash = banks_client.get_recent_blockhash().await.unwrap();
         transaction.sign(
@@ -611,7 +666,7 @@ impl TestLendingMarket {
             ],
             recent_blockhash,
         );
-        assert!(banks_client.process_transaction(transaction).await.is_ok());
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
     }
 
     pub async fn borrow(
@@ -620,83 +675,42 @@ impl TestLendingMarket {
         payer: &Keypair,
         args: BorrowArgs<'_>,
     ) {
-        let memory_keypair = Keypair::new();
-        let user_transfer_authority = Keypair::new();
-
         let BorrowArgs {
             borrow_reserve,
-            deposit_reserve,
-            borrow_amount_type,
+            reserves,
             amount,
-            dex_market,
             user_accounts_owner,
             obligation,
         } = args;
 
-        let dex_market_orders_pubkey = if deposit_reserve.dex_market.is_none() {
-            dex_market.asks_pubkey
-        } else {
-            dex_market.bids_pubkey
-        };
-
-        let approve_amount = if borrow_amount_type == BorrowAmountType::CollateralDepositAmount {
-            amount
-        } else {
-            get_token_balance(banks_client, deposit_reserve.user_collateral_account).await
-        };
+        let memory_keypair = Keypair::new();
 
-        let mut transaction = Transaction::new_with_payer(
-            &[
-                approve(
-                    &spl_token::id(),
-                    &deposit_reserve.user_collateral_account,
-                    &user_transfer_authority.pubkey(),
-                    &user_accounts_owner.pubkey(),
-                    &[],
-                    approve_amount,
-                )
-                .unwrap(),
-                create_account(
-                    &payer.pubkey(),
-                    &memory_keypair.pubkey(),
-                    0,
-                    65548,
-                    &spl_token_lending::id(),
-                ),
-                borrow_reserve_liquidity(
-                    spl_token_lending::id(),
-                    amount,
-                    borrow_amount_type,
-                    deposit_reserve.user_collateral_account,
-                    borrow_reserve.user_liquidity_account,
-                    deposit_reserve.pubkey,
-                    deposit_reserve.collateral_supply,
-                    deposit_reserve.collateral_fees_receiver,
-                    borrow_reserve.pubkey,
-                    borrow_reserve.liquidity_supply,
-                    self.pubkey,
-                    self.authority,
-                    user_transfer_authority.pubkey(),
-                    obligation.pubkey,
-                    obligation.token_mint,
-                    obligation.token_account,
-                    dex_market.pubkey,
-                    dex_market_orders_pubkey,
-                    memory_keypair.pubkey(),
-                    Some(deposit_reserve.collateral_host),
-                ),
-            ],
-            Some(&payer.pubkey()),
+        let mut instructions = vec![create_memory_account(payer, &memory_keypair)];
+        instructions.extend(
+            reserves
+                .iter()
+                .map(|reserve| reserve.refresh_instruction(memory_keypair.pubkey())),
         );
+        instructions.push(obligation.refresh_instruction(banks_client).await);
+        instructions.push(borrow_obligation_liquidity(
+            spl_token_lending::id(),
+            amount,
+            borrow_reserve.liquidity_supply,
+            borrow_reserve.user_liquidity_account,
+            borrow_reserve.pubkey,
+            borrow_reserve.liquidity_fee_receiver,
+            obligation.pubkey,
+            self.pubkey,
+            self.authority,
+            user_accounts_owner.pubkey(),
+            Some(borrow_reserve.liquidity_host),
+        ));
+
+        let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
 
         let recent_blockhash = banks_client.get_recent_blockhash().await.unwrap();
         transaction.sign(
-            &vec![
-                payer,
-                user_accounts_owner,
-                &memory_keypair,
-                &user_transfer_authority,
-            ],
+            &vec![payer, user_accounts_owner, &memory_keypair],
             recent_blockhash,
         );
 
@@ -735,11 +749,35 @@ pub struct TestReserve {
     pub liquidity_supply: Pubkey,
     pub collateral_mint: Pubkey,
     pub collateral_supply: Pubkey,
-    pub collateral_fees_receiver: Pubkey,
-    pub collateral_host: Pubkey,
+    pub liquidity_fee_receiver: Pubkey,
+    pub liquidity_host: Pubkey,
     pub user_liquidity_account: Pubkey,
     pub user_collateral_account: Pubkey,
-    pub dex_market: Option<Pubkey>,
+    pub oracle: Option<TestReserveOracle>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum TestReserveOracle {
+    DexMarket {
+        dex_market: Pubkey,
+        dex_market_bids: Pubkey,
+    },
+    PushOracle {
+        feed: Pubkey,
+    },
+    TokenSwap {
+        swap: Pubkey,
+    },
+}
+
+impl TestReserveOracle {
+    pub fn pubkey(&self) -> Pubkey {
+        match *self {
+            Self::DexMarket { dex_market, .. } => dex_market,
+            Self::PushOracle { feed } => feed,
+            Self::TokenSwap { swap } => swap,
+        }
+    }
 }
 
 impl TestReserve {
@@ -760,14 +798,19 @@ impl TestReserve {
         let reserve_pubkey = reserve_keypair.pubkey();
         let collateral_mint_keypair = Keypair::new();
         let collateral_supply_keypair = Keypair::new();
-        let collateral_fees_receiver_keypair = Keypair::new();
-        let collateral_host_keypair = Keypair::new();
+        let liquidity_fee_receiver_keypair = Keypair::new();
+        let liquidity_host_keypair = Keypair::new();
         let liquidity_supply_keypair = Keypair::new();
         let user_collateral_token_keypair = Keypair::new();
         let user_transfer_authority_keypair = Keypair::new();
 
-        let dex_market_pubkey = if liquidity_mint_pubkey != lending_market.quote_token_mint {
-            Some(dex_market.pubkey)
+        let mut config = config;
+        let oracle = if liquidity_mint_pubkey != lending_market.quote_token_mint {
+            config.oracle = OracleConfig::DexMarket;
+            Some(TestReserveOracle::DexMarket {
+                dex_market: dex_market.pubkey,
+                dex_market_bids: dex_market.bids_pubkey,
+            })
         } else {
             None
         };
@@ -807,14 +850,14 @@ impl TestReserve {
                 ),
                 create_account(
                     &payer.pubkey(),
-                    &collateral_fees_receiver_keypair.pubkey(),
+                    &liquidity_fee_receiver_keypair.pubkey(),
                     rent.minimum_balance(Token::LEN),
                     Token::LEN as u64,
                     &spl_token::id(),
                 ),
                 create_account(
                     &payer.pubkey(),
-                    &collateral_host_keypair.pubkey(),
+                    &liquidity_host_keypair.pubkey(),
                     rent.minimum_balance(Token::LEN),
                     Token::LEN as u64,
                     &spl_token::id(),
@@ -851,11 +894,11 @@ impl TestReserve {
                     liquidity_supply_keypair.pubkey(),
                     collateral_mint_keypair.pubkey(),
                     collateral_supply_keypair.pubkey(),
-                    collateral_fees_receiver_keypair.pubkey(),
+                    liquidity_fee_receiver_keypair.pubkey(),
                     lending_market.pubkey,
                     lending_market.owner.pubkey(),
                     user_transfer_authority_keypair.pubkey(),
-                    dex_market_pubkey,
+                    oracle.map(|oracle| oracle.pubkey()),
                 ),
             ],
             Some(&payer.pubkey()),
@@ -870,8 +913,8 @@ impl TestReserve {
                 &lending_market.owner,
                 &collateral_mint_keypair,
                 &collateral_supply_keypair,
-                &collateral_fees_receiver_keypair,
-                &collateral_host_keypair,
+                &liquidity_fee_receiver_keypair,
+                &liquidity_host_keypair,
                 &liquidity_supply_keypair,
                 &user_collateral_token_keypair,
                 &user_transfer_authority_keypair,
@@ -892,11 +935,11 @@ impl TestReserve {
                 liquidity_supply: liquidity_supply_keypair.pubkey(),
                 collateral_mint: collateral_mint_keypair.pubkey(),
                 collateral_supply: collateral_supply_keypair.pubkey(),
-                collateral_fees_receiver: collateral_fees_receiver_keypair.pubkey(),
-                collateral_host: collateral_host_keypair.pubkey(),
+                liquidity_fee_receiver: liquidity_fee_receiver_keypair.pubkey(),
+                liquidity_host: liquidity_host_keypair.pubkey(),
                 user_liquidity_account,
                 user_collateral_account: user_collateral_token_keypair.pubkey(),
-                dex_market: dex_market_pubkey,
+                oracle,
             })
             .map_err(|e| e.unwrap())
     }
@@ -919,14 +962,14 @@ impl TestReserve {
             self.name, self.collateral_supply
         );
         genesis_accounts
-            .fetch_and_insert(banks_client, self.collateral_fees_receiver)
+            .fetch_and_insert(banks_client, self.collateral_supply)
             .await;
         println!(
-            "{}_collateral_fees_receiver: {}",
-            self.name, self.collateral_fees_receiver
+            "{}_liquidity_fee_receiver: {}",
+            self.name, self.liquidity_fee_receiver
         );
         genesis_accounts
-            .fetch_and_insert(banks_client, self.collateral_supply)
+            .fetch_and_insert(banks_client, self.liquidity_fee_receiver)
             .await;
         if &self.name != "sol" {
             println!("{}_liquidity_mint: {}", self.name, self.liquidity_mint);
@@ -954,6 +997,25 @@ impl TestReserve {
             .await;
     }
 
+    pub fn refresh_instruction(&self, memory_pubkey: Pubkey) -> Instruction {
+        let oracle = match self.oracle {
+            None => RefreshReserveOracle::QuoteCurrency,
+            Some(TestReserveOracle::DexMarket {
+                dex_market,
+                dex_market_bids,
+            }) => RefreshReserveOracle::DexMarket {
+                dex_market,
+                dex_market_bids,
+                memory: memory_pubkey,
+            },
+            Some(TestReserveOracle::PushOracle { feed }) => {
+                RefreshReserveOracle::PushOracle { feed }
+            }
+            Some(TestReserveOracle::TokenSwap { swap }) => RefreshReserveOracle::TokenSwap { swap },
+        };
+        refresh_reserve(spl_token_lending::id(), self.pubkey, oracle)
+    }
+
     pub async fn get_state(&self, banks_client: &mut BanksClient) -> Reserve {
         let reserve_account: Account = banks_client
             .get_account(self.pubkey)
@@ -972,15 +1034,16 @@ impl TestReserve {
         assert_eq!(self.liquidity_supply, reserve.liquidity.supply_pubkey);
         assert_eq!(self.collateral_mint, reserve.collateral.mint_pubkey);
         assert_eq!(self.collateral_supply, reserve.collateral.supply_pubkey);
+        assert_eq!(self.liquidity_fee_receiver, reserve.liquidity.fee_receiver);
         assert_eq!(self.config, reserve.config);
 
-        let dex_market_coption = if let Some(dex_market_pubkey) = self.dex_market {
-            COption::Some(dex_market_pubkey)
+        let oracle_coption = if let Some(oracle) = &self.oracle {
+            COption::Some(oracle.pubkey())
         } else {
             COption::None
         };
 
-        assert_eq!(dex_market_coption, reserve.dex_market);
+        assert_eq!(oracle_coption, reserve.oracle);
         assert_eq!(reserve.cumulative_borrow_rate_wads, Decimal::one());
         assert_eq!(reserve.liquidity.borrowed_amount_wads, Decimal::zero());
         assert!(reserve.liquidity.available_amount > 0);
@@ -991,50 +1054,27 @@ impl TestReserve {
 #[derive(Debug)]
 pub struct TestObligation {
     pub pubkey: Pubkey,
-    pub token_mint: Pubkey,
-    pub token_account: Pubkey,
-    pub collateral_reserve: Pubkey,
-    pub borrow_reserve: Pubkey,
+    pub lending_market: Pubkey,
+    pub owner: Pubkey,
 }
 
 impl TestObligation {
-    #[allow(clippy::too_many_arguments)]
     pub async fn init(
         banks_client: &mut BanksClient,
         lending_market: &TestLendingMarket,
-        deposit_reserve: &TestReserve,
-        borrow_reserve: &TestReserve,
         payer: &Keypair,
         user_accounts_owner: &Keypair,
     ) -> Result<Self, TransactionError> {
         let obligation_keypair = Keypair::new();
-        let obligation_token_mint_keypair = Keypair::new();
-        let obligation_token_account_keypair = Keypair::new();
         let obligation = TestObligation {
             pubkey: obligation_keypair.pubkey(),
-            token_mint: obligation_token_mint_keypair.pubkey(),
-            token_account: obligation_token_account_keypair.pubkey(),
-            collateral_reserve: deposit_reserve.pubkey,
-            borrow_reserve: borrow_reserve.pubkey,
+            lending_market: lending_market.pubkey,
+            owner: user_accounts_owner.pubkey(),
         };
 
         let rent = banks_client.get_rent().await.unwrap();
         let mut transaction = Transaction::new_with_payer(
             &[
-                create_account(
-                    &payer.pubkey(),
-                    &obligation_token_mint_keypair.pubkey(),
-                    rent.minimum_balance(Mint::LEN),
-                    Mint::LEN as u64,
-                    &spl_token::id(),
-                ),
-                create_account(
-                    &payer.pubkey(),
-                    &obligation_token_account_keypair.pubkey(),
-                    rent.minimum_balance(Token::LEN),
-                    Token::LEN as u64,
-                    &spl_token::id(),
-                ),
                 create_account(
                     &payer.pubkey(),
                     &obligation_keypair.pubkey(),
@@ -1044,12 +1084,8 @@ impl TestObligation {
                 ),
                 init_obligation(
                     spl_token_lending::id(),
-                    deposit_reserve.pubkey,
-                    borrow_reserve.pubkey,
-                    lending_market.pubkey,
                     obligation.pubkey,
-                    obligation.token_mint,
-                    obligation.token_account,
+                    lending_market.pubkey,
                     user_accounts_owner.pubkey(),
                 ),
             ],
@@ -1058,12 +1094,7 @@ impl TestObligation {
 
         let recent_blockhash = banks_client.get_recent_blockhash().await.unwrap();
         transaction.sign(
-            &vec![
-                payer,
-                &obligation_keypair,
-                &obligation_token_account_keypair,
-                &obligation_token_mint_keypair,
-            ],
+            &vec![payer, &obligation_keypair, user_accounts_owner],
             recent_blockhash,
         );
 
@@ -1075,6 +1106,24 @@ impl TestObligation {
         Ok(obligation)
     }
 
+    /// Refresh the obligation using its current deposit and borrow reserves,
+    /// which must already be refreshed
+    pub async fn refresh_instruction(&self, banks_client: &mut BanksClient) -> Instruction {
+        let obligation = self.get_state(banks_client).await;
+        let reserve_pubkeys = obligation
+            .deposits
+            .iter()
+            .map(|collateral| collateral.deposit_reserve)
+            .chain(
+                obligation
+                    .borrows
+                    .iter()
+                    .map(|liquidity| liquidity.borrow_reserve),
+            )
+            .collect();
+        refresh_obligation(spl_token_lending::id(), self.pubkey, reserve_pubkeys)
+    }
+
     pub async fn get_state(&self, banks_client: &mut BanksClient) -> Obligation {
         let obligation_account: Account = banks_client
             .get_account(self.pubkey)
@@ -1087,10 +1136,85 @@ impl TestObligation {
     pub async fn validate_state(&self, banks_client: &mut BanksClient) {
         let obligation = self.get_state(banks_client).await;
         assert_eq!(obligation.version, PROGRAM_VERSION);
-        assert_eq!(obligation.collateral_reserve, self.collateral_reserve);
-        assert!(obligation.cumulative_borrow_rate_wads >= Decimal::one());
-        assert_eq!(obligation.borrow_reserve, self.borrow_reserve);
-        assert_eq!(obligation.token_mint, self.token_mint);
+        assert_eq!(obligation.lending_market, self.lending_market);
+        assert_eq!(obligation.owner, self.owner);
+    }
+}
+
+#[derive(Debug)]
+pub struct TestDepositPosition {
+    pub pubkey: Pubkey,
+    pub lending_market: Pubkey,
+    pub reserve: Pubkey,
+    pub owner: Pubkey,
+}
+
+impl TestDepositPosition {
+    pub async fn init(
+        banks_client: &mut BanksClient,
+        lending_market: &TestLendingMarket,
+        reserve: &TestReserve,
+        payer: &Keypair,
+        user_accounts_owner: &Keypair,
+    ) -> Result<Self, TransactionError> {
+        let deposit_position_keypair = Keypair::new();
+        let deposit_position = TestDepositPosition {
+            pubkey: deposit_position_keypair.pubkey(),
+            lending_market: lending_market.pubkey,
+            reserve: reserve.pubkey,
+            owner: user_accounts_owner.pubkey(),
+        };
+
+        let rent = banks_client.get_rent().await.unwrap();
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                create_account(
+                    &payer.pubkey(),
+                    &deposit_position_keypair.pubkey(),
+                    rent.minimum_balance(DepositPosition::LEN),
+                    DepositPosition::LEN as u64,
+                    &spl_token_lending::id(),
+                ),
+                init_deposit_position(
+                    spl_token_lending::id(),
+                    deposit_position.pubkey,
+                    reserve.pubkey,
+                    lending_market.pubkey,
+                    user_accounts_owner.pubkey(),
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+
+        let recent_blockhash = banks_client.get_recent_blockhash().await.unwrap();
+        transaction.sign(
+            &vec![payer, &deposit_position_keypair, user_accounts_owner],
+            recent_blockhash,
+        );
+
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|e| e.unwrap())?;
+
+        Ok(deposit_position)
+    }
+
+    pub async fn get_state(&self, banks_client: &mut BanksClient) -> DepositPosition {
+        let deposit_position_account: Account = banks_client
+            .get_account(self.pubkey)
+            .await
+            .unwrap()
+            .unwrap();
+        DepositPosition::unpack(&deposit_position_account.data[..]).unwrap()
+    }
+
+    pub async fn validate_state(&self, banks_client: &mut BanksClient) {
+        let deposit_position = self.get_state(banks_client).await;
+        assert_eq!(deposit_position.version, PROGRAM_VERSION);
+        assert_eq!(deposit_position.lending_market, self.lending_market);
+        assert_eq!(deposit_position.reserve, self.reserve);
+        assert_eq!(deposit_position.owner, self.owner);
     }
 }
 
@@ -1100,6 +1224,51 @@ pub struct TestQuoteMint {
     pub decimals: u8,
 }
 
+pub fn add_push_oracle_feed(test: &mut ProgramTest, feed: PushOracleFeed) -> TestReserveOracle {
+    let feed_pubkey = Pubkey::new_unique();
+    test.add_packable_account(feed_pubkey, u32::MAX as u64, &feed, &Pubkey::new_unique());
+    TestReserveOracle::PushOracle { feed: feed_pubkey }
+}
+
+pub fn add_token_swap(
+    test: &mut ProgramTest,
+    token_a_mint: Pubkey,
+    token_b_mint: Pubkey,
+    price_accumulator: PriceAccumulator,
+) -> TestReserveOracle {
+    let swap_pubkey = Pubkey::new_unique();
+    let (_, nonce) = Pubkey::find_program_address(&[swap_pubkey.as_ref()], &spl_token_swap::id());
+
+    let mut data = vec![0; SwapVersion::LATEST_LEN];
+    SwapVersion::pack(
+        SwapVersion::SwapV2(SwapV2 {
+            is_initialized: true,
+            nonce,
+            token_program_id: spl_token::id(),
+            token_a: Pubkey::new_unique(),
+            token_b: Pubkey::new_unique(),
+            token_a_mint,
+            token_b_mint,
+            price_accumulator,
+            ..SwapV2::default()
+        }),
+        &mut data,
+    )
+    .unwrap();
+    test.add_account(
+        swap_pubkey,
+        Account {
+            lamports: u32::MAX as u64,
+            data,
+            owner: spl_token_swap::id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+
+    TestReserveOracle::TokenSwap { swap: swap_pubkey }
+}
+
 pub fn add_usdc_mint(test: &mut ProgramTest) -> TestQuoteMint {
     let authority = Keypair::new();
     let pubkey = Pubkey::from_str(USDC_MINT).unwrap();
@@ -1215,6 +1384,17 @@ impl TestDexMarket {
     }
 }
 
+/// Temporary memory used to simulate trades against a dex market order book
+pub fn create_memory_account(payer: &Keypair, memory_keypair: &Keypair) -> Instruction {
+    create_account(
+        &payer.pubkey(),
+        &memory_keypair.pubkey(),
+        0,
+        65548,
+        &spl_token_lending::id(),
+    )
+}
+
 pub async fn create_and_mint_to_token_account(
     banks_client: &mut BanksClient,
     mint_pubkey: Pubkey,
@@ -96,7 +96,7 @@ async fn test_already_initialized() {
         &user_accounts_owner,
         &lending_market,
         AddReserveArgs {
-            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            dex_market: Some(&sol_usdc_dex_market),
             liquidity_amount: 42,
             liquidity_mint_decimals: usdc_mint.decimals,
             liquidity_mint_pubkey: usdc_mint.pubkey,
@@ -119,7 +119,7 @@ async fn test_already_initialized() {
             usdc_reserve.liquidity_supply,
             usdc_reserve.collateral_mint,
             usdc_reserve.collateral_supply,
-            usdc_reserve.collateral_fees_receiver,
+            usdc_reserve.liquidity_fee_receiver,
             lending_market.pubkey,
             lending_market.owner.pubkey(),
             user_transfer_authority.pubkey(),
@@ -175,6 +175,38 @@ async fn test_invalid_fees() {
         let mut config = TEST_RESERVE_CONFIG;
         config.fees = ReserveFees {
             borrow_fee_wad: 1_000_000_000_000_000_001,
+            flash_loan_fee_wad: 0,
+            host_fee_percentage: 0,
+        };
+
+        assert_eq!(
+            TestReserve::init(
+                "sol".to_owned(),
+                &mut banks_client,
+                &lending_market,
+                RESERVE_AMOUNT,
+                config,
+                spl_token::native_mint::id(),
+                sol_user_liquidity_account,
+                &payer,
+                &user_accounts_owner,
+                &sol_usdc_dex_market,
+            )
+            .await
+            .unwrap_err(),
+            TransactionError::InstructionError(
+                8,
+                InstructionError::Custom(LendingError::InvalidConfig as u32)
+            )
+        );
+    }
+
+    // flash loan fee above 100%
+    {
+        let mut config = TEST_RESERVE_CONFIG;
+        config.fees = ReserveFees {
+            borrow_fee_wad: 10_000_000_000_000_000,
+            flash_loan_fee_wad: 1_000_000_000_000_000_001,
             host_fee_percentage: 0,
         };
 
@@ -205,6 +237,7 @@ async fn test_invalid_fees() {
         let mut config = TEST_RESERVE_CONFIG;
         config.fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000,
+            flash_loan_fee_wad: 0,
             host_fee_percentage: 101,
         };
 
@@ -230,3 +263,54 @@ async fn test_invalid_fees() {
         );
     }
 }
+
+#[tokio::test]
+async fn test_supply_cap_exceeded() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    const RESERVE_AMOUNT: u64 = 42;
+
+    let sol_user_liquidity_account = create_and_mint_to_token_account(
+        &mut banks_client,
+        spl_token::native_mint::id(),
+        None,
+        &payer,
+        user_accounts_owner.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    let mut config = TEST_RESERVE_CONFIG;
+    config.supply_cap = RESERVE_AMOUNT - 1;
+
+    assert_eq!(
+        TestReserve::init(
+            "sol".to_owned(),
+            &mut banks_client,
+            &lending_market,
+            RESERVE_AMOUNT,
+            config,
+            spl_token::native_mint::id(),
+            sol_user_liquidity_account,
+            &payer,
+            &user_accounts_owner,
+            &sol_usdc_dex_market,
+        )
+        .await
+        .unwrap_err(),
+        TransactionError::InstructionError(
+            8,
+            InstructionError::Custom(LendingError::ReserveCapExceeded as u32)
+        )
+    );
+}
@@ -4,7 +4,7 @@ mod helpers;
 
 use helpers::*;
 use solana_program_test::*;
-use solana_sdk::{pubkey::Pubkey, signature::Keypair};
+use solana_sdk::signature::Keypair;
 use spl_token_lending::{
     math::Decimal, processor::process_instruction, state::INITIAL_COLLATERAL_RATIO,
 };
@@ -70,7 +70,7 @@ async fn test_success() {
             liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
             liquidity_mint_decimals: 9,
             liquidity_mint_pubkey: spl_token::native_mint::id(),
-            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            dex_market: Some(&sol_usdc_dex_market),
             collateral_amount: USDC_LOAN_SOL_COLLATERAL,
             borrow_amount: SOL_LOAN * 101 / 100,
             user_liquidity_amount: SOL_LOAN,
@@ -83,10 +83,8 @@ async fn test_success() {
         &user_accounts_owner,
         &lending_market,
         AddObligationArgs {
-            borrow_reserve: &usdc_reserve,
-            collateral_reserve: &sol_reserve,
-            collateral_amount: USDC_LOAN_SOL_COLLATERAL,
-            borrowed_liquidity_wads: Decimal::from(USDC_LOAN),
+            deposits: &[(&sol_reserve, USDC_LOAN_SOL_COLLATERAL)],
+            borrows: &[(&usdc_reserve, USDC_LOAN)],
         },
     );
 
@@ -95,15 +93,18 @@ async fn test_success() {
         &user_accounts_owner,
         &lending_market,
         AddObligationArgs {
-            borrow_reserve: &sol_reserve,
-            collateral_reserve: &usdc_reserve,
-            collateral_amount: SOL_LOAN_USDC_COLLATERAL,
-            borrowed_liquidity_wads: Decimal::from(SOL_LOAN),
+            deposits: &[(&usdc_reserve, SOL_LOAN_USDC_COLLATERAL)],
+            borrows: &[(&sol_reserve, SOL_LOAN)],
         },
     );
 
     let (mut banks_client, payer, _recent_blockhash) = test.start().await;
 
+    let initial_sol_user_collateral_balance =
+        get_token_balance(&mut banks_client, sol_reserve.user_collateral_account).await;
+    let initial_usdc_user_collateral_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.user_collateral_account).await;
+
     lending_market
         .liquidate(
             &mut banks_client,
@@ -111,7 +112,7 @@ async fn test_success() {
             LiquidateArgs {
                 repay_reserve: &usdc_reserve,
                 withdraw_reserve: &sol_reserve,
-                dex_market: &sol_usdc_dex_market,
+                reserves: &[&usdc_reserve, &sol_reserve],
                 amount: USDC_LOAN,
                 user_accounts_owner: &user_accounts_owner,
                 obligation: &usdc_obligation,
@@ -126,7 +127,7 @@ async fn test_success() {
             LiquidateArgs {
                 repay_reserve: &sol_reserve,
                 withdraw_reserve: &usdc_reserve,
-                dex_market: &sol_usdc_dex_market,
+                reserves: &[&usdc_reserve, &sol_reserve],
                 amount: SOL_LOAN,
                 user_accounts_owner: &user_accounts_owner,
                 obligation: &sol_obligation,
@@ -139,12 +140,15 @@ async fn test_success() {
     let usdc_loan_state = usdc_obligation.get_state(&mut banks_client).await;
     let usdc_liquidated = usdc_liquidity_supply - INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL;
     assert!(usdc_liquidated > USDC_LOAN / 2);
+    assert!(usdc_loan_state.borrows[0].borrowed_amount_wads > Decimal::zero());
+
+    let sol_collateral_received =
+        get_token_balance(&mut banks_client, sol_reserve.user_collateral_account).await
+            - initial_sol_user_collateral_balance;
+    assert!(sol_collateral_received > 0);
     assert_eq!(
-        usdc_liquidated,
-        usdc_loan_state
-            .borrowed_liquidity_wads
-            .try_floor_u64()
-            .unwrap()
+        usdc_loan_state.deposits[0].deposited_amount,
+        USDC_LOAN_SOL_COLLATERAL - sol_collateral_received
     );
 
     let sol_liquidity_supply =
@@ -152,11 +156,14 @@ async fn test_success() {
     let sol_loan_state = sol_obligation.get_state(&mut banks_client).await;
     let sol_liquidated = sol_liquidity_supply - INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS;
     assert!(sol_liquidated > SOL_LOAN / 2);
+    assert!(sol_loan_state.borrows[0].borrowed_amount_wads > Decimal::zero());
+
+    let usdc_collateral_received =
+        get_token_balance(&mut banks_client, usdc_reserve.user_collateral_account).await
+            - initial_usdc_user_collateral_balance;
+    assert!(usdc_collateral_received > 0);
     assert_eq!(
-        sol_liquidated,
-        sol_loan_state
-            .borrowed_liquidity_wads
-            .try_floor_u64()
-            .unwrap()
+        sol_loan_state.deposits[0].deposited_amount,
+        SOL_LOAN_USDC_COLLATERAL - usdc_collateral_received
     );
 }
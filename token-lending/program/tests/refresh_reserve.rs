@@ -0,0 +1,370 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{refresh_reserve, RefreshReserveOracle},
+    math::{Decimal, Rate, TryDiv, TryMul},
+    oracle::PushOracleFeed,
+    processor::process_instruction,
+    state::{BorrowRateCurve, OracleConfig, SLOTS_PER_YEAR},
+};
+use spl_token_swap::oracle::PriceAccumulator;
+
+const LAMPORTS_TO_SOL: u64 = 1_000_000_000;
+const FRACTIONAL_TO_USDC: u64 = 1_000_000;
+
+const INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL;
+const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(80_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 80;
+
+    // Configure reserve to a fixed borrow rate of 1%
+    const BORROW_RATE: u8 = 1;
+    reserve_config.borrow_rate_curve = BorrowRateCurve::from_points(&[
+        borrow_rate_curve_point(0, BORROW_RATE as u16 * 100),
+        borrow_rate_curve_point(10_000, BORROW_RATE as u16 * 100),
+    ])
+    .unwrap();
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            borrow_amount: 100,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            slots_elapsed: SLOTS_PER_YEAR,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            borrow_amount: 100,
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            slots_elapsed: SLOTS_PER_YEAR,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_reserve(
+                spl_token_lending::id(),
+                usdc_reserve.pubkey,
+                RefreshReserveOracle::QuoteCurrency,
+            ),
+            refresh_reserve(
+                spl_token_lending::id(),
+                sol_reserve.pubkey,
+                RefreshReserveOracle::QuoteCurrency,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let sol_reserve = sol_reserve.get_state(&mut banks_client).await;
+    let usdc_reserve = usdc_reserve.get_state(&mut banks_client).await;
+
+    let borrow_rate = Rate::from_percent(100u8 + BORROW_RATE);
+    assert!(sol_reserve.cumulative_borrow_rate_wads > borrow_rate.into());
+    assert_eq!(
+        sol_reserve.cumulative_borrow_rate_wads,
+        usdc_reserve.cumulative_borrow_rate_wads
+    );
+    assert!(
+        sol_reserve.liquidity.borrowed_amount_wads
+            > Decimal::from(100u64).try_mul(borrow_rate).unwrap()
+    );
+    assert_eq!(
+        sol_reserve.liquidity.borrowed_amount_wads,
+        usdc_reserve.liquidity.borrowed_amount_wads
+    );
+}
+
+#[tokio::test]
+async fn test_dex_market_price() {
+    // Using SOL/USDC best bid:
+    //  $13.988,  300.0 SOL
+    //
+    // Market price = 13.988 * 10^6 / 10^9 = 0.013988 fractional USDC per lamport
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(200_000);
+
+    let user_accounts_owner = Keypair::new();
+    let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            dex_market: Some(&sol_usdc_dex_market),
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let memory_keypair = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_memory_account(&payer, &memory_keypair),
+            sol_reserve.refresh_instruction(memory_keypair.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &memory_keypair], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let sol_reserve = sol_reserve.get_state(&mut banks_client).await;
+    assert!(sol_reserve.liquidity.market_price > Decimal::from_scaled_val(13_900_000_000_000_000));
+    assert!(sol_reserve.liquidity.market_price < Decimal::from_scaled_val(14_000_000_000_000_000));
+}
+
+#[tokio::test]
+async fn test_push_oracle_price() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(20_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    // $13.988 per SOL, +/- $0.01
+    let oracle = add_push_oracle_feed(
+        &mut test,
+        PushOracleFeed {
+            version: 1,
+            publish_slot: 0,
+            price: 13_988,
+            confidence: 10,
+            exponent: -6,
+        },
+    );
+
+    let mut config = TEST_RESERVE_CONFIG;
+    config.oracle = OracleConfig::PushOracle {
+        max_staleness_slots: 1_000,
+        max_confidence_bps: 10,
+    };
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            oracle: Some(oracle),
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[sol_reserve.refresh_instruction(Pubkey::default())],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let sol_reserve = sol_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        sol_reserve.liquidity.market_price,
+        Decimal::from(13_988u64).try_div(1_000_000).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_push_oracle_price_uncertain() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(20_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    // $13.988 per SOL, +/- $0.10
+    let oracle = add_push_oracle_feed(
+        &mut test,
+        PushOracleFeed {
+            version: 1,
+            publish_slot: 0,
+            price: 13_988,
+            confidence: 100,
+            exponent: -6,
+        },
+    );
+
+    let mut config = TEST_RESERVE_CONFIG;
+    config.oracle = OracleConfig::PushOracle {
+        max_staleness_slots: 1_000,
+        max_confidence_bps: 10,
+    };
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            oracle: Some(oracle),
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[sol_reserve.refresh_instruction(Pubkey::default())],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::OraclePriceUncertain as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_token_swap_twap_price() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(40_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    // SOL traded at 15.625 USDC for the 200 slots since the reserve observation
+    let oracle = add_token_swap(
+        &mut test,
+        usdc_mint.pubkey,
+        spl_token::native_mint::id(),
+        PriceAccumulator {
+            last_update_slot: 200,
+            price_a_cumulative: 200 * (64 << 64),
+            price_b_cumulative: 200 * (1 << 58),
+        },
+    );
+
+    let mut config = TEST_RESERVE_CONFIG;
+    config.oracle = OracleConfig::TokenSwapTwap {
+        window_slots: 150,
+        max_staleness_slots: 600,
+    };
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            oracle: Some(oracle),
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[sol_reserve.refresh_instruction(Pubkey::default())],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // 0.015625 fractional USDC per lamport, averaged from the swap cumulative prices
+    let sol_reserve = sol_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        sol_reserve.liquidity.market_price,
+        Decimal::from_scaled_val(15_625_000_000_000_000)
+    );
+    assert_eq!(sol_reserve.oracle_observation.last_update_slot, 200);
+}
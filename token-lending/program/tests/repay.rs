@@ -5,14 +5,13 @@ mod helpers;
 use helpers::*;
 use solana_program_test::*;
 use solana_sdk::{
-    pubkey::Pubkey,
     signature::{Keypair, Signer},
     transaction::Transaction,
 };
 use spl_token::instruction::approve;
 use spl_token_lending::{
-    instruction::repay_reserve_liquidity,
-    math::{Decimal, TryAdd, TryDiv, TryMul, TrySub},
+    instruction::{refresh_reserve, repay_obligation_liquidity, RefreshReserveOracle},
+    math::{Decimal, TryMul, TrySub},
     processor::process_instruction,
     state::{INITIAL_COLLATERAL_RATIO, SLOTS_PER_YEAR},
 };
@@ -69,7 +68,7 @@ async fn test_success() {
             liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
             liquidity_mint_decimals: 9,
             liquidity_mint_pubkey: spl_token::native_mint::id(),
-            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            dex_market: Some(&sol_usdc_dex_market),
             collateral_amount: OBLIGATION_COLLATERAL,
             ..AddReserveArgs::default()
         },
@@ -80,17 +79,15 @@ async fn test_success() {
         &user_accounts_owner,
         &lending_market,
         AddObligationArgs {
-            borrow_reserve: &usdc_reserve,
-            collateral_reserve: &sol_reserve,
-            collateral_amount: OBLIGATION_COLLATERAL,
-            borrowed_liquidity_wads: Decimal::from(OBLIGATION_LOAN),
+            deposits: &[(&sol_reserve, OBLIGATION_COLLATERAL)],
+            borrows: &[(&usdc_reserve, OBLIGATION_LOAN)],
         },
     );
 
     let (mut banks_client, payer, recent_blockhash) = test.start().await;
 
-    let initial_user_collateral_balance =
-        get_token_balance(&mut banks_client, sol_reserve.user_collateral_account).await;
+    let initial_liquidity_supply_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.liquidity_supply).await;
 
     let mut transaction = Transaction::new_with_payer(
         &[
@@ -103,29 +100,19 @@ async fn test_success() {
                 OBLIGATION_LOAN,
             )
             .unwrap(),
-            approve(
-                &spl_token::id(),
-                &obligation.token_account,
-                &user_transfer_authority.pubkey(),
-                &user_accounts_owner.pubkey(),
-                &[],
-                OBLIGATION_COLLATERAL,
-            )
-            .unwrap(),
-            repay_reserve_liquidity(
+            refresh_reserve(
+                spl_token_lending::id(),
+                usdc_reserve.pubkey,
+                RefreshReserveOracle::QuoteCurrency,
+            ),
+            repay_obligation_liquidity(
                 spl_token_lending::id(),
                 OBLIGATION_LOAN,
                 usdc_reserve.user_liquidity_account,
-                sol_reserve.user_collateral_account,
-                usdc_reserve.pubkey,
                 usdc_reserve.liquidity_supply,
-                sol_reserve.pubkey,
-                sol_reserve.collateral_supply,
+                usdc_reserve.pubkey,
                 obligation.pubkey,
-                obligation.token_mint,
-                obligation.token_account,
                 lending_market.pubkey,
-                lending_market.authority,
                 user_transfer_authority.pubkey(),
             ),
         ],
@@ -138,54 +125,44 @@ async fn test_success() {
     );
     assert!(banks_client.process_transaction(transaction).await.is_ok());
 
-    let collateral_received =
-        get_token_balance(&mut banks_client, sol_reserve.user_collateral_account).await
-            - initial_user_collateral_balance;
-    assert!(collateral_received > 0);
+    let user_liquidity_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.user_liquidity_account).await;
+    assert_eq!(user_liquidity_balance, 0);
 
-    let borrow_reserve_state = usdc_reserve.get_state(&mut banks_client).await;
-    assert!(borrow_reserve_state.cumulative_borrow_rate_wads > Decimal::one());
-
-    let obligation_state = obligation.get_state(&mut banks_client).await;
+    let liquidity_supply_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.liquidity_supply).await;
     assert_eq!(
-        obligation_state.cumulative_borrow_rate_wads,
-        borrow_reserve_state.cumulative_borrow_rate_wads
+        liquidity_supply_balance,
+        initial_liquidity_supply_balance + OBLIGATION_LOAN
     );
+
+    let repay_reserve_state = usdc_reserve.get_state(&mut banks_client).await;
+    assert!(repay_reserve_state.cumulative_borrow_rate_wads > Decimal::one());
+
+    let obligation_state = obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation_state.borrows.len(), 1);
+    let obligation_liquidity = &obligation_state.borrows[0];
     assert_eq!(
-        obligation_state.borrowed_liquidity_wads,
-        borrow_reserve_state.liquidity.borrowed_amount_wads
+        obligation_liquidity.cumulative_borrow_rate_wads,
+        repay_reserve_state.cumulative_borrow_rate_wads
     );
 
     // use cumulative borrow rate directly since test rate starts at 1.0
-    let expected_obligation_interest = obligation_state
+    let expected_obligation_interest = obligation_liquidity
         .cumulative_borrow_rate_wads
         .try_mul(OBLIGATION_LOAN)
         .unwrap()
         .try_sub(Decimal::from(OBLIGATION_LOAN))
         .unwrap();
     assert_eq!(
-        obligation_state.borrowed_liquidity_wads,
+        obligation_liquidity.borrowed_amount_wads,
         expected_obligation_interest
     );
 
-    let expected_obligation_total = Decimal::from(OBLIGATION_LOAN)
-        .try_add(expected_obligation_interest)
-        .unwrap();
-
-    let expected_obligation_repaid_percent = Decimal::from(OBLIGATION_LOAN)
-        .try_div(expected_obligation_total)
-        .unwrap();
-
-    let expected_collateral_received = expected_obligation_repaid_percent
-        .try_mul(OBLIGATION_COLLATERAL)
-        .unwrap()
-        .try_floor_u64()
-        .unwrap();
-    assert_eq!(collateral_received, expected_collateral_received);
-
-    let expected_collateral_remaining = OBLIGATION_COLLATERAL - expected_collateral_received;
+    // collateral is left untouched by a repay
     assert_eq!(
-        obligation_state.deposited_collateral_tokens,
-        expected_collateral_remaining
+        obligation_state.deposits[0].deposited_amount,
+        OBLIGATION_COLLATERAL
     );
+    assert!(obligation_state.stale);
 }
@@ -0,0 +1,215 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::instruction::approve;
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{deposit_reserve_liquidity, update_reserve_config},
+    processor::process_instruction,
+    state::{OracleConfig, ReserveConfig},
+};
+
+const FRACTIONAL_TO_USDC: u64 = 1_000_000;
+const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 10_000 * FRACTIONAL_TO_USDC;
+const USER_DEPOSIT_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+
+struct UpdateReserveConfigTest {
+    banks_client: BanksClient,
+    payer: Keypair,
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    usdc_reserve: TestReserve,
+}
+
+async fn setup() -> UpdateReserveConfigTest {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(20_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            user_liquidity_amount: USER_DEPOSIT_AMOUNT_FRACTIONAL,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (banks_client, payer, _recent_blockhash) = test.start().await;
+
+    UpdateReserveConfigTest {
+        banks_client,
+        payer,
+        user_accounts_owner,
+        lending_market,
+        usdc_reserve,
+    }
+}
+
+async fn process_update_reserve_config(
+    test: &mut UpdateReserveConfigTest,
+    config: ReserveConfig,
+    invalid_owner: Option<&Keypair>,
+) -> Result<(), TransactionError> {
+    let lending_market_owner = invalid_owner.unwrap_or(&test.lending_market.owner);
+    let mut transaction = Transaction::new_with_payer(
+        &[update_reserve_config(
+            spl_token_lending::id(),
+            config,
+            test.usdc_reserve.pubkey,
+            test.lending_market.pubkey,
+            lending_market_owner.pubkey(),
+        )],
+        Some(&test.payer.pubkey()),
+    );
+
+    let recent_blockhash = test.banks_client.get_recent_blockhash().await.unwrap();
+    transaction.sign(&[&test.payer, lending_market_owner], recent_blockhash);
+    test.banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = setup().await;
+
+    let config = ReserveConfig {
+        loan_to_value_ratio: 40,
+        liquidation_threshold: 45,
+        deposits_disabled: true,
+        ..TEST_RESERVE_CONFIG
+    };
+    process_update_reserve_config(&mut test, config, None)
+        .await
+        .unwrap();
+
+    let reserve = test.usdc_reserve.get_state(&mut test.banks_client).await;
+    assert_eq!(reserve.config, config);
+
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &test.usdc_reserve.user_liquidity_account,
+                &user_transfer_authority.pubkey(),
+                &test.user_accounts_owner.pubkey(),
+                &[],
+                USER_DEPOSIT_AMOUNT_FRACTIONAL,
+            )
+            .unwrap(),
+            deposit_reserve_liquidity(
+                spl_token_lending::id(),
+                USER_DEPOSIT_AMOUNT_FRACTIONAL,
+                test.usdc_reserve.user_liquidity_account,
+                test.usdc_reserve.user_collateral_account,
+                test.usdc_reserve.pubkey,
+                test.usdc_reserve.liquidity_supply,
+                test.usdc_reserve.collateral_mint,
+                test.lending_market.pubkey,
+                test.lending_market.authority,
+                user_transfer_authority.pubkey(),
+            ),
+        ],
+        Some(&test.payer.pubkey()),
+    );
+
+    let recent_blockhash = test.banks_client.get_recent_blockhash().await.unwrap();
+    transaction.sign(
+        &[
+            &test.payer,
+            &test.user_accounts_owner,
+            &user_transfer_authority,
+        ],
+        recent_blockhash,
+    );
+    assert_eq!(
+        test.banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ReserveDepositsDisabled as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_config() {
+    let mut test = setup().await;
+
+    let config = ReserveConfig {
+        loan_to_value_ratio: 100,
+        ..TEST_RESERVE_CONFIG
+    };
+    assert_eq!(
+        process_update_reserve_config(&mut test, config, None)
+            .await
+            .unwrap_err(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidConfig as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_oracle_type_changed() {
+    let mut test = setup().await;
+
+    let config = ReserveConfig {
+        oracle: OracleConfig::DexMarket,
+        ..TEST_RESERVE_CONFIG
+    };
+    assert_eq!(
+        process_update_reserve_config(&mut test, config, None)
+            .await
+            .unwrap_err(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidConfig as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_owner() {
+    let mut test = setup().await;
+
+    let invalid_owner = Keypair::new();
+    assert_eq!(
+        process_update_reserve_config(&mut test, TEST_RESERVE_CONFIG, Some(&invalid_owner))
+            .await
+            .unwrap_err(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+}
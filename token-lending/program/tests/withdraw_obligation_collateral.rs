@@ -2,19 +2,16 @@
 
 use solana_program_test::*;
 use solana_sdk::{
-    instruction::InstructionError,
+    instruction::{Instruction, InstructionError},
     pubkey::Pubkey,
     signature::{Keypair, Signer},
-    system_instruction::create_account,
     transaction::{Transaction, TransactionError},
 };
 
 use helpers::*;
-use spl_token::instruction::approve;
 use spl_token_lending::{
     error::LendingError,
-    instruction::withdraw_obligation_collateral,
-    math::Decimal,
+    instruction::{deposit_position_collateral, withdraw_obligation_collateral},
     processor::process_instruction,
     state::{INITIAL_COLLATERAL_RATIO, SLOTS_PER_YEAR},
 };
@@ -41,13 +38,12 @@ async fn test_success() {
     const OBLIGATION_LOAN: u64 = 10 * FRACTIONAL_TO_USDC;
     const OBLIGATION_COLLATERAL: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
 
-    // from Reserve::required_collateral_for_borrow
-    const REQUIRED_COLLATERAL: u64 = 7_220_474_693;
-    const WITHDRAW_COLLATERAL: u64 = OBLIGATION_COLLATERAL - REQUIRED_COLLATERAL;
+    // Collateral value = 10 * 13.988 = 139.88 USDC
+    // Max withdraw value = (139.88 * 0.5 (LTV) - 10.1) / 0.5 = 119.68 USDC
+    const WITHDRAW_COLLATERAL: u64 = OBLIGATION_COLLATERAL / 2;
 
     let user_accounts_owner = Keypair::new();
     let memory_keypair = Keypair::new();
-    let user_transfer_authority = Keypair::new();
     let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
     let usdc_mint = add_usdc_mint(&mut test);
     let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
@@ -61,7 +57,7 @@ async fn test_success() {
             liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
             liquidity_mint_decimals: 9,
             liquidity_mint_pubkey: spl_token::native_mint::id(),
-            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            dex_market: Some(&sol_usdc_dex_market),
             collateral_amount: OBLIGATION_COLLATERAL,
             config: TEST_RESERVE_CONFIG,
             ..AddReserveArgs::default()
@@ -89,10 +85,8 @@ async fn test_success() {
         &user_accounts_owner,
         &lending_market,
         AddObligationArgs {
-            borrow_reserve: &usdc_reserve,
-            collateral_reserve: &sol_reserve,
-            collateral_amount: OBLIGATION_COLLATERAL,
-            borrowed_liquidity_wads: Decimal::from(OBLIGATION_LOAN),
+            deposits: &[(&sol_reserve, OBLIGATION_COLLATERAL)],
+            borrows: &[(&usdc_reserve, OBLIGATION_LOAN)],
         },
     );
 
@@ -102,64 +96,30 @@ async fn test_success() {
         get_token_balance(&mut banks_client, sol_reserve.collateral_supply).await;
     let initial_user_collateral_balance =
         get_token_balance(&mut banks_client, sol_reserve.user_collateral_account).await;
-    let initial_obligation_token_balance =
-        get_token_balance(&mut banks_client, obligation.token_account).await;
 
     let mut transaction = Transaction::new_with_payer(
         &[
-            create_account(
-                &payer.pubkey(),
-                &memory_keypair.pubkey(),
-                0,
-                65548,
-                &spl_token_lending::id(),
-            ),
-            approve(
-                &spl_token::id(),
-                &sol_reserve.user_collateral_account,
-                &user_transfer_authority.pubkey(),
-                &user_accounts_owner.pubkey(),
-                &[],
-                OBLIGATION_LOAN,
-            )
-            .unwrap(),
-            approve(
-                &spl_token::id(),
-                &obligation.token_account,
-                &user_transfer_authority.pubkey(),
-                &user_accounts_owner.pubkey(),
-                &[],
-                OBLIGATION_COLLATERAL,
-            )
-            .unwrap(),
+            create_memory_account(&payer, &memory_keypair),
+            sol_reserve.refresh_instruction(memory_keypair.pubkey()),
+            usdc_reserve.refresh_instruction(memory_keypair.pubkey()),
+            obligation.refresh_instruction(&mut banks_client).await,
             withdraw_obligation_collateral(
                 spl_token_lending::id(),
                 WITHDRAW_COLLATERAL,
                 sol_reserve.collateral_supply,
                 sol_reserve.user_collateral_account,
                 sol_reserve.pubkey,
-                usdc_reserve.pubkey,
                 obligation.pubkey,
-                obligation.token_mint,
-                obligation.token_account,
                 lending_market.pubkey,
                 lending_market.authority,
-                user_transfer_authority.pubkey(),
-                sol_usdc_dex_market.pubkey,
-                sol_usdc_dex_market.bids_pubkey,
-                memory_keypair.pubkey(),
+                user_accounts_owner.pubkey(),
             ),
         ],
         Some(&payer.pubkey()),
     );
 
     transaction.sign(
-        &[
-            &payer,
-            &memory_keypair,
-            &user_accounts_owner,
-            &user_transfer_authority,
-        ],
+        &[&payer, &memory_keypair, &user_accounts_owner],
         recent_blockhash,
     );
     assert!(banks_client.process_transaction(transaction).await.is_ok());
@@ -178,17 +138,16 @@ async fn test_success() {
         initial_user_collateral_balance + WITHDRAW_COLLATERAL
     );
 
-    // check that obligation tokens were burned
-    let obligation_token_balance =
-        get_token_balance(&mut banks_client, obligation.token_account).await;
+    // check that the obligation deposit was reduced
+    let obligation_state = obligation.get_state(&mut banks_client).await;
     assert_eq!(
-        obligation_token_balance,
-        initial_obligation_token_balance - WITHDRAW_COLLATERAL
+        obligation_state.deposits[0].deposited_amount,
+        OBLIGATION_COLLATERAL - WITHDRAW_COLLATERAL
     );
 }
 
 #[tokio::test]
-async fn test_withdraw_below_required() {
+async fn test_withdraw_too_large() {
     let mut test = ProgramTest::new(
         "spl_token_lending",
         spl_token_lending::id(),
@@ -204,13 +163,11 @@ async fn test_withdraw_below_required() {
     const OBLIGATION_LOAN: u64 = 10 * FRACTIONAL_TO_USDC;
     const OBLIGATION_COLLATERAL: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
 
-    // from Reserve::required_collateral_for_borrow
-    const REQUIRED_COLLATERAL: u64 = 7_220_474_693;
-    const WITHDRAW_COLLATERAL: u64 = OBLIGATION_COLLATERAL - REQUIRED_COLLATERAL + 1;
+    // withdrawing all collateral would leave the loan undercollateralized
+    const WITHDRAW_COLLATERAL: u64 = OBLIGATION_COLLATERAL;
 
     let user_accounts_owner = Keypair::new();
     let memory_keypair = Keypair::new();
-    let user_transfer_authority = Keypair::new();
     let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
     let usdc_mint = add_usdc_mint(&mut test);
     let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
@@ -224,7 +181,7 @@ async fn test_withdraw_below_required() {
             liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
             liquidity_mint_decimals: 9,
             liquidity_mint_pubkey: spl_token::native_mint::id(),
-            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            dex_market: Some(&sol_usdc_dex_market),
             collateral_amount: OBLIGATION_COLLATERAL,
             config: TEST_RESERVE_CONFIG,
             ..AddReserveArgs::default()
@@ -252,10 +209,8 @@ async fn test_withdraw_below_required() {
         &user_accounts_owner,
         &lending_market,
         AddObligationArgs {
-            borrow_reserve: &usdc_reserve,
-            collateral_reserve: &sol_reserve,
-            collateral_amount: OBLIGATION_COLLATERAL,
-            borrowed_liquidity_wads: Decimal::from(OBLIGATION_LOAN),
+            deposits: &[(&sol_reserve, OBLIGATION_COLLATERAL)],
+            borrows: &[(&usdc_reserve, OBLIGATION_LOAN)],
         },
     );
 
@@ -263,59 +218,27 @@ async fn test_withdraw_below_required() {
 
     let mut transaction = Transaction::new_with_payer(
         &[
-            create_account(
-                &payer.pubkey(),
-                &memory_keypair.pubkey(),
-                0,
-                65548,
-                &spl_token_lending::id(),
-            ),
-            approve(
-                &spl_token::id(),
-                &sol_reserve.user_collateral_account,
-                &user_transfer_authority.pubkey(),
-                &user_accounts_owner.pubkey(),
-                &[],
-                OBLIGATION_LOAN,
-            )
-            .unwrap(),
-            approve(
-                &spl_token::id(),
-                &obligation.token_account,
-                &user_transfer_authority.pubkey(),
-                &user_accounts_owner.pubkey(),
-                &[],
-                OBLIGATION_COLLATERAL,
-            )
-            .unwrap(),
+            create_memory_account(&payer, &memory_keypair),
+            sol_reserve.refresh_instruction(memory_keypair.pubkey()),
+            usdc_reserve.refresh_instruction(memory_keypair.pubkey()),
+            obligation.refresh_instruction(&mut banks_client).await,
             withdraw_obligation_collateral(
                 spl_token_lending::id(),
                 WITHDRAW_COLLATERAL,
                 sol_reserve.collateral_supply,
                 sol_reserve.user_collateral_account,
                 sol_reserve.pubkey,
-                usdc_reserve.pubkey,
                 obligation.pubkey,
-                obligation.token_mint,
-                obligation.token_account,
                 lending_market.pubkey,
                 lending_market.authority,
-                user_transfer_authority.pubkey(),
-                sol_usdc_dex_market.pubkey,
-                sol_usdc_dex_market.bids_pubkey,
-                memory_keypair.pubkey(),
+                user_accounts_owner.pubkey(),
             ),
         ],
         Some(&payer.pubkey()),
     );
 
     transaction.sign(
-        &[
-            &payer,
-            &memory_keypair,
-            &user_accounts_owner,
-            &user_transfer_authority,
-        ],
+        &[&payer, &memory_keypair, &user_accounts_owner],
         recent_blockhash,
     );
 
@@ -327,10 +250,137 @@ async fn test_withdraw_below_required() {
             .unwrap_err()
             .unwrap(),
         TransactionError::InstructionError(
-            3,
-            InstructionError::Custom(
-                LendingError::ObligationCollateralWithdrawBelowRequired as u32
-            )
+            4,
+            InstructionError::Custom(LendingError::WithdrawTooLarge as u32)
         )
     );
 }
+
+#[tokio::test]
+async fn test_position_collateral_restricted() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(60_000);
+
+    const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+    const OBLIGATION_COLLATERAL: u64 = 10 * FRACTIONAL_TO_USDC * INITIAL_COLLATERAL_RATIO;
+    const POSITION_COLLATERAL: u64 = 10 * FRACTIONAL_TO_USDC * INITIAL_COLLATERAL_RATIO;
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            collateral_amount: OBLIGATION_COLLATERAL + POSITION_COLLATERAL,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let obligation = add_obligation(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            deposits: &[(&usdc_reserve, OBLIGATION_COLLATERAL)],
+            borrows: &[],
+        },
+    );
+
+    let deposit_position = add_deposit_position(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        &usdc_reserve,
+        POSITION_COLLATERAL,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[deposit_position_collateral(
+            spl_token_lending::id(),
+            POSITION_COLLATERAL,
+            deposit_position.pubkey,
+            usdc_reserve.pubkey,
+            obligation.pubkey,
+            lending_market.pubkey,
+            user_accounts_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let withdraw_transaction = |collateral_amount: u64, refresh_obligation: Instruction| {
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                usdc_reserve.refresh_instruction(Pubkey::default()),
+                refresh_obligation,
+                withdraw_obligation_collateral(
+                    spl_token_lending::id(),
+                    collateral_amount,
+                    usdc_reserve.collateral_supply,
+                    usdc_reserve.user_collateral_account,
+                    usdc_reserve.pubkey,
+                    obligation.pubkey,
+                    lending_market.pubkey,
+                    lending_market.authority,
+                    user_accounts_owner.pubkey(),
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+        transaction
+    };
+
+    // check that position collateral cannot leave the obligation as transferable tokens
+    let refresh_obligation = obligation.refresh_instruction(&mut banks_client).await;
+    assert_eq!(
+        banks_client
+            .process_transaction(withdraw_transaction(
+                OBLIGATION_COLLATERAL + 1,
+                refresh_obligation.clone()
+            ))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(LendingError::ObligationCollateralRestricted as u32)
+        )
+    );
+
+    // while collateral deposited from tokens in the same reserve can still be withdrawn
+    assert!(banks_client
+        .process_transaction(withdraw_transaction(
+            OBLIGATION_COLLATERAL,
+            refresh_obligation
+        ))
+        .await
+        .is_ok());
+
+    let obligation_state = obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation_state.deposits.len(), 1);
+    assert_eq!(
+        obligation_state.deposits[0].deposited_amount,
+        POSITION_COLLATERAL
+    );
+    assert_eq!(
+        obligation_state.deposits[0].position_amount,
+        POSITION_COLLATERAL
+    );
+}
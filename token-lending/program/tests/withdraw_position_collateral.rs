@@ -0,0 +1,262 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+use helpers::*;
+use spl_token_lending::{
+    error::LendingError,
+    instruction::withdraw_position_collateral,
+    processor::process_instruction,
+    state::{INITIAL_COLLATERAL_RATIO, SLOTS_PER_YEAR},
+};
+
+mod helpers;
+
+const LAMPORTS_TO_SOL: u64 = 1_000_000_000;
+const FRACTIONAL_TO_USDC: u64 = 1_000_000;
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(190_000);
+
+    const INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL;
+    const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+
+    const OBLIGATION_LOAN: u64 = 10 * FRACTIONAL_TO_USDC;
+    const OBLIGATION_COLLATERAL: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+
+    // Collateral value = 10 * 13.988 = 139.88 USDC
+    // Max withdraw value = (139.88 * 0.5 (LTV) - 10.1) / 0.5 = 119.68 USDC
+    const WITHDRAW_COLLATERAL: u64 = OBLIGATION_COLLATERAL / 2;
+
+    let user_accounts_owner = Keypair::new();
+    let memory_keypair = Keypair::new();
+    let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            slots_elapsed: SLOTS_PER_YEAR,
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            dex_market: Some(&sol_usdc_dex_market),
+            collateral_amount: OBLIGATION_COLLATERAL,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            initial_borrow_rate: 1,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            borrow_amount: OBLIGATION_LOAN * 101 / 100,
+            user_liquidity_amount: OBLIGATION_LOAN,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let obligation = add_obligation(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            deposits: &[(&sol_reserve, OBLIGATION_COLLATERAL)],
+            borrows: &[(&usdc_reserve, OBLIGATION_LOAN)],
+        },
+    );
+
+    let deposit_position = add_deposit_position(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        &sol_reserve,
+        0,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_collateral_supply_balance =
+        get_token_balance(&mut banks_client, sol_reserve.collateral_supply).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_memory_account(&payer, &memory_keypair),
+            sol_reserve.refresh_instruction(memory_keypair.pubkey()),
+            usdc_reserve.refresh_instruction(memory_keypair.pubkey()),
+            obligation.refresh_instruction(&mut banks_client).await,
+            withdraw_position_collateral(
+                spl_token_lending::id(),
+                WITHDRAW_COLLATERAL,
+                deposit_position.pubkey,
+                sol_reserve.pubkey,
+                obligation.pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &[&payer, &memory_keypair, &user_accounts_owner],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // check that the collateral stayed in the reserve
+    let collateral_supply_balance =
+        get_token_balance(&mut banks_client, sol_reserve.collateral_supply).await;
+    assert_eq!(collateral_supply_balance, initial_collateral_supply_balance);
+
+    // check that the collateral moved from the obligation to the position
+    let obligation_state = obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation_state.deposits[0].deposited_amount,
+        OBLIGATION_COLLATERAL - WITHDRAW_COLLATERAL
+    );
+    let deposit_position_state = deposit_position.get_state(&mut banks_client).await;
+    assert_eq!(
+        deposit_position_state.collateral_amount,
+        WITHDRAW_COLLATERAL
+    );
+}
+
+#[tokio::test]
+async fn test_withdraw_too_large() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(180_000);
+
+    const INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL;
+    const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+
+    const OBLIGATION_LOAN: u64 = 10 * FRACTIONAL_TO_USDC;
+    const OBLIGATION_COLLATERAL: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+
+    // withdrawing all collateral would leave the loan undercollateralized
+    const WITHDRAW_COLLATERAL: u64 = OBLIGATION_COLLATERAL;
+
+    let user_accounts_owner = Keypair::new();
+    let memory_keypair = Keypair::new();
+    let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            slots_elapsed: SLOTS_PER_YEAR,
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            dex_market: Some(&sol_usdc_dex_market),
+            collateral_amount: OBLIGATION_COLLATERAL,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            initial_borrow_rate: 1,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            borrow_amount: OBLIGATION_LOAN * 101 / 100,
+            user_liquidity_amount: OBLIGATION_LOAN,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let obligation = add_obligation(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            deposits: &[(&sol_reserve, OBLIGATION_COLLATERAL)],
+            borrows: &[(&usdc_reserve, OBLIGATION_LOAN)],
+        },
+    );
+
+    let deposit_position = add_deposit_position(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        &sol_reserve,
+        0,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_memory_account(&payer, &memory_keypair),
+            sol_reserve.refresh_instruction(memory_keypair.pubkey()),
+            usdc_reserve.refresh_instruction(memory_keypair.pubkey()),
+            obligation.refresh_instruction(&mut banks_client).await,
+            withdraw_position_collateral(
+                spl_token_lending::id(),
+                WITHDRAW_COLLATERAL,
+                deposit_position.pubkey,
+                sol_reserve.pubkey,
+                obligation.pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &[&payer, &memory_keypair, &user_accounts_owner],
+        recent_blockhash,
+    );
+
+    // check that transaction fails
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            4,
+            InstructionError::Custom(LendingError::WithdrawTooLarge as u32)
+        )
+    );
+}
@@ -0,0 +1,173 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError, instruction::withdraw_position_liquidity, processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+
+const FRACTIONAL_TO_USDC: u64 = 1_000_000;
+const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+const POSITION_COLLATERAL_AMOUNT: u64 =
+    INITIAL_COLLATERAL_RATIO * INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL;
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(45_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            collateral_amount: POSITION_COLLATERAL_AMOUNT,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let deposit_position = add_deposit_position(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        &usdc_reserve,
+        POSITION_COLLATERAL_AMOUNT,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_user_liquidity_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.user_liquidity_account).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            usdc_reserve.refresh_instruction(Pubkey::default()),
+            withdraw_position_liquidity(
+                spl_token_lending::id(),
+                POSITION_COLLATERAL_AMOUNT,
+                deposit_position.pubkey,
+                usdc_reserve.user_liquidity_account,
+                usdc_reserve.pubkey,
+                usdc_reserve.collateral_mint,
+                usdc_reserve.collateral_supply,
+                usdc_reserve.liquidity_supply,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // check that the position collateral was burned for liquidity
+    let collateral_supply_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.collateral_supply).await;
+    assert_eq!(collateral_supply_balance, 0);
+    let user_liquidity_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.user_liquidity_account).await;
+    assert_eq!(
+        user_liquidity_balance,
+        initial_user_liquidity_balance + INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL
+    );
+
+    let deposit_position_state = deposit_position.get_state(&mut banks_client).await;
+    assert_eq!(deposit_position_state.collateral_amount, 0);
+}
+
+#[tokio::test]
+async fn test_invalid_owner() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(45_000);
+
+    let user_accounts_owner = Keypair::new();
+    let other_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            collateral_amount: POSITION_COLLATERAL_AMOUNT,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let deposit_position = add_deposit_position(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        &usdc_reserve,
+        POSITION_COLLATERAL_AMOUNT,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            usdc_reserve.refresh_instruction(Pubkey::default()),
+            withdraw_position_liquidity(
+                spl_token_lending::id(),
+                POSITION_COLLATERAL_AMOUNT,
+                deposit_position.pubkey,
+                usdc_reserve.user_liquidity_account,
+                usdc_reserve.pubkey,
+                usdc_reserve.collateral_mint,
+                usdc_reserve.collateral_supply,
+                usdc_reserve.liquidity_supply,
+                lending_market.pubkey,
+                other_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &other_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
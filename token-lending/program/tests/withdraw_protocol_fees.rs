@@ -0,0 +1,157 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::withdraw_protocol_fees,
+    math::Decimal,
+    processor::process_instruction,
+    state::{ReserveConfig, SLOTS_PER_YEAR},
+};
+
+const FRACTIONAL_TO_USDC: u64 = 1_000_000;
+const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 10_000 * FRACTIONAL_TO_USDC;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 5_000 * FRACTIONAL_TO_USDC;
+
+fn add_usdc_reserve(
+    test: &mut ProgramTest,
+    user_accounts_owner: &Keypair,
+    lending_market: &TestLendingMarket,
+    usdc_mint: &TestQuoteMint,
+) -> TestReserve {
+    add_reserve(
+        test,
+        user_accounts_owner,
+        lending_market,
+        AddReserveArgs {
+            slots_elapsed: SLOTS_PER_YEAR,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            config: ReserveConfig {
+                reserve_factor: 20,
+                ..TEST_RESERVE_CONFIG
+            },
+            ..AddReserveArgs::default()
+        },
+    )
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(20_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let usdc_reserve =
+        add_usdc_reserve(&mut test, &user_accounts_owner, &lending_market, &usdc_mint);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[usdc_reserve.refresh_instruction(Pubkey::default())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let reserve = usdc_reserve.get_state(&mut banks_client).await;
+    let protocol_fees = reserve.liquidity.protocol_fees_wads;
+    assert!(protocol_fees > Decimal::zero());
+    let initial_fee_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.liquidity_fee_receiver).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[withdraw_protocol_fees(
+            spl_token_lending::id(),
+            u64::MAX,
+            usdc_reserve.pubkey,
+            usdc_reserve.liquidity_supply,
+            usdc_reserve.liquidity_fee_receiver,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let withdraw_amount = protocol_fees.try_floor_u64().unwrap();
+    let fee_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.liquidity_fee_receiver).await;
+    assert_eq!(fee_balance, initial_fee_balance + withdraw_amount);
+
+    let reserve = usdc_reserve.get_state(&mut banks_client).await;
+    assert!(reserve.liquidity.protocol_fees_wads < Decimal::one());
+    assert_eq!(
+        reserve.liquidity.available_amount,
+        INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL - USDC_BORROW_AMOUNT_FRACTIONAL - withdraw_amount
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_owner() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(20_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let usdc_reserve =
+        add_usdc_reserve(&mut test, &user_accounts_owner, &lending_market, &usdc_mint);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let invalid_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            usdc_reserve.refresh_instruction(Pubkey::default()),
+            withdraw_protocol_fees(
+                spl_token_lending::id(),
+                u64::MAX,
+                usdc_reserve.pubkey,
+                usdc_reserve.liquidity_supply,
+                usdc_reserve.user_liquidity_account,
+                lending_market.pubkey,
+                invalid_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &invalid_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+}
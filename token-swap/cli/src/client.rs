@@ -0,0 +1,180 @@
+use {
+    solana_account_decoder::UiAccountEncoding,
+    solana_client::{
+        client_error::ClientError,
+        rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
+    },
+    solana_program::{program_pack::Pack, pubkey::Pubkey},
+    spl_token_swap::{
+        curve::proactive_market_maker::ProactiveMarketMakerState,
+        quote::Quoter,
+        state::{SwapState, SwapVersion},
+    },
+};
+
+type Error = Box<dyn std::error::Error>;
+
+/// Offset of the token A mint in a swap account, after the version, the
+/// initialized flag, the nonce, the token program id, both token accounts and
+/// the pool mint
+const TOKEN_A_MINT_OFFSET: usize = 3 + 4 * 32;
+/// Offset of the token B mint in a swap account
+const TOKEN_B_MINT_OFFSET: usize = TOKEN_A_MINT_OFFSET + 32;
+
+/// Token swaps found on chain, by address
+pub type TokenSwaps = Vec<(Pubkey, Box<dyn SwapState>)>;
+
+/// Swap state along with the balances needed to quote it
+pub struct Pool {
+    pub token_swap: Box<dyn SwapState>,
+    pub token_a: spl_token::state::Account,
+    pub token_b: spl_token::state::Account,
+    pub pool_mint: spl_token::state::Mint,
+}
+
+impl Pool {
+    pub fn quoter(&self) -> Quoter<'_> {
+        Quoter::new(
+            self.token_swap.as_ref(),
+            self.token_a.amount,
+            self.token_b.amount,
+            self.pool_mint.supply,
+        )
+    }
+}
+
+pub fn get_token_swap(
+    rpc_client: &RpcClient,
+    token_swap_address: &Pubkey,
+) -> Result<Box<dyn SwapState>, Error> {
+    let account_data = rpc_client.get_account_data(token_swap_address)?;
+    let token_swap = SwapVersion::unpack(account_data.as_slice())
+        .map_err(|err| format!("Invalid token swap {}: {}", token_swap_address, err))?;
+    Ok(token_swap)
+}
+
+pub fn get_pool(rpc_client: &RpcClient, token_swap_address: &Pubkey) -> Result<Pool, Error> {
+    let token_swap = get_token_swap(rpc_client, token_swap_address)?;
+    let token_a = get_token_account(
+        rpc_client,
+        token_swap.token_a_account(),
+        token_swap.token_a_mint(),
+    )?;
+    let token_b = get_token_account(
+        rpc_client,
+        token_swap.token_b_account(),
+        token_swap.token_b_mint(),
+    )?;
+    let pool_mint = get_token_mint(rpc_client, token_swap.pool_mint())?;
+    Ok(Pool {
+        token_swap,
+        token_a,
+        token_b,
+        pool_mint,
+    })
+}
+
+pub fn get_proactive_market_maker_state(
+    rpc_client: &RpcClient,
+    token_swap_address: &Pubkey,
+) -> Result<ProactiveMarketMakerState, Error> {
+    let account_data = rpc_client.get_account_data(token_swap_address)?;
+    let state = account_data
+        .get(SwapVersion::LATEST_LEN..)
+        .ok_or_else(|| format!("Missing oracle state in token swap {}", token_swap_address))
+        .and_then(|data| {
+            ProactiveMarketMakerState::unpack(data).map_err(|err| {
+                format!(
+                    "Invalid oracle state in token swap {}: {}",
+                    token_swap_address, err
+                )
+            })
+        })?;
+    Ok(state)
+}
+
+pub fn get_token_account(
+    rpc_client: &RpcClient,
+    token_account_address: &Pubkey,
+    expected_token_mint: &Pubkey,
+) -> Result<spl_token::state::Account, Error> {
+    let token_account = get_any_token_account(rpc_client, token_account_address)?;
+
+    if token_account.mint != *expected_token_mint {
+        Err(format!(
+            "Invalid token mint for {}, expected mint is {}",
+            token_account_address, expected_token_mint
+        )
+        .into())
+    } else {
+        Ok(token_account)
+    }
+}
+
+pub fn get_any_token_account(
+    rpc_client: &RpcClient,
+    token_account_address: &Pubkey,
+) -> Result<spl_token::state::Account, Error> {
+    let account_data = rpc_client.get_account_data(token_account_address)?;
+    let token_account = spl_token::state::Account::unpack_from_slice(account_data.as_slice())
+        .map_err(|err| format!("Invalid token account {}: {}", token_account_address, err))?;
+    Ok(token_account)
+}
+
+pub fn get_token_mint(
+    rpc_client: &RpcClient,
+    token_mint_address: &Pubkey,
+) -> Result<spl_token::state::Mint, Error> {
+    let account_data = rpc_client.get_account_data(token_mint_address)?;
+    let token_mint = spl_token::state::Mint::unpack_from_slice(account_data.as_slice())
+        .map_err(|err| format!("Invalid token mint {}: {}", token_mint_address, err))?;
+
+    Ok(token_mint)
+}
+
+/// Finds the swaps trading `token_a_mint` as token A against `token_b_mint` as
+/// token B
+pub fn get_token_swaps_by_mints(
+    rpc_client: &RpcClient,
+    token_a_mint: &Pubkey,
+    token_b_mint: &Pubkey,
+) -> Result<TokenSwaps, ClientError> {
+    rpc_client
+        .get_program_accounts_with_config(
+            &spl_token_swap::id(),
+            RpcProgramAccountsConfig {
+                filters: Some(vec![
+                    RpcFilterType::Memcmp(Memcmp {
+                        offset: TOKEN_A_MINT_OFFSET,
+                        bytes: MemcmpEncodedBytes::Binary(format!("{}", token_a_mint)),
+                        encoding: None,
+                    }),
+                    RpcFilterType::Memcmp(Memcmp {
+                        offset: TOKEN_B_MINT_OFFSET,
+                        bytes: MemcmpEncodedBytes::Binary(format!("{}", token_b_mint)),
+                        encoding: None,
+                    }),
+                ]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..RpcAccountInfoConfig::default()
+                },
+            },
+        )
+        .map(|accounts| {
+            accounts
+                .into_iter()
+                .filter_map(|(address, account)| {
+                    match SwapVersion::unpack(account.data.as_slice()) {
+                        Ok(token_swap) => Some((address, token_swap)),
+                        Err(err) => {
+                            eprintln!("Invalid token swap data for {}: {}", address, err);
+                            None
+                        }
+                    }
+                })
+                .collect()
+        })
+}
@@ -0,0 +1,1837 @@
+mod client;
+
+use {
+    crate::client::*,
+    clap::{
+        crate_description, crate_name, crate_version, value_t, value_t_or_exit, App, AppSettings,
+        Arg, SubCommand,
+    },
+    solana_clap_utils::{
+        input_parsers::pubkey_of,
+        input_validators::{is_amount, is_keypair, is_parsable, is_pubkey, is_url},
+        keypair::signer_from_path,
+    },
+    solana_client::rpc_client::RpcClient,
+    solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey},
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        native_token::Sol,
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::Transaction,
+    },
+    spl_math::precise_number::PreciseNumber,
+    spl_token_swap::{
+        curve::{
+            base::{CurveType, SwapCurve},
+            calculator::TradeDirection,
+            constant_price::ConstantPriceCurve,
+            constant_product::ConstantProductCurve,
+            fees::Fees,
+            offset::OffsetCurve,
+            proactive_market_maker::{ProactiveMarketMakerCurve, ProactiveMarketMakerState},
+            stable::StableCurve,
+        },
+        instruction::{
+            DepositAllTokenTypes, DepositSingleTokenTypeExactAmountIn, Swap, WithdrawAllTokenTypes,
+            WithdrawSingleTokenTypeExactAmountOut,
+        },
+        quote::QuoteFees,
+        state::{SwapState, SwapVersion},
+    },
+    std::process::exit,
+};
+
+struct Config {
+    rpc_client: RpcClient,
+    verbose: bool,
+    token_owner: Box<dyn Signer>,
+    fee_payer: Box<dyn Signer>,
+    dry_run: bool,
+    slippage: u64,
+}
+
+type Error = Box<dyn std::error::Error>;
+type CommandResult = Result<(), Error>;
+
+const MAX_SLIPPAGE_BASIS_POINTS: u64 = 10_000;
+
+fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(), Error> {
+    let balance = config.rpc_client.get_balance(&config.fee_payer.pubkey())?;
+    if balance < required_balance {
+        Err(format!(
+            "Fee payer, {}, has insufficient balance: {} required, {} available",
+            config.fee_payer.pubkey(),
+            Sol(required_balance),
+            Sol(balance)
+        )
+        .into())
+    } else {
+        Ok(())
+    }
+}
+
+fn send_transaction(
+    config: &Config,
+    transaction: Transaction,
+) -> solana_client::client_error::Result<()> {
+    if config.dry_run {
+        let result = config.rpc_client.simulate_transaction(&transaction)?;
+        println!("Simulate result: {:?}", result);
+    } else {
+        let signature = config
+            .rpc_client
+            .send_and_confirm_transaction_with_spinner(&transaction)?;
+        println!("Signature: {}", signature);
+    }
+    Ok(())
+}
+
+fn sign_and_send_instructions(
+    config: &Config,
+    instructions: &[Instruction],
+    mut signers: Vec<&dyn Signer>,
+    total_rent_free_balances: u64,
+) -> CommandResult {
+    let mut transaction =
+        Transaction::new_with_payer(instructions, Some(&config.fee_payer.pubkey()));
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        total_rent_free_balances + fee_calculator.calculate_fee(transaction.message()),
+    )?;
+    signers.sort_by_key(|signer| signer.pubkey());
+    signers.dedup_by_key(|signer| signer.pubkey());
+    transaction.sign(&signers, recent_blockhash);
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+/// Instructions creating a token account for `mint` owned by `owner`
+fn create_token_account_instructions(
+    config: &Config,
+    account: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    balance: u64,
+) -> Result<Vec<Instruction>, Error> {
+    Ok(vec![
+        system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            account,
+            balance,
+            spl_token::state::Account::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_account(&spl_token::id(), account, mint, owner)?,
+    ])
+}
+
+fn unwrap_create_token_account<F>(
+    config: &Config,
+    token_optional: &Option<Pubkey>,
+    keypair: &Keypair,
+    mint: &Pubkey,
+    instructions: &mut Vec<Instruction>,
+    handler: F,
+) -> Result<Pubkey, Error>
+where
+    F: FnOnce(u64),
+{
+    let result = match token_optional {
+        Some(value) => *value,
+        None => {
+            // Account for tokens not specified, creating one
+            println!("Creating account to receive tokens {}", keypair.pubkey());
+
+            let min_account_balance = config
+                .rpc_client
+                .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)?;
+
+            instructions.extend(create_token_account_instructions(
+                config,
+                &keypair.pubkey(),
+                mint,
+                &config.token_owner.pubkey(),
+                min_account_balance,
+            )?);
+
+            handler(min_account_balance);
+
+            keypair.pubkey()
+        }
+    };
+    Ok(result)
+}
+
+/// Authority of the swap over its token accounts and pool mint
+fn find_authority_program_address(token_swap_address: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&token_swap_address.to_bytes()[..32]],
+        &spl_token_swap::id(),
+    )
+}
+
+/// Proactive market maker pools only trade at an oracle price refreshed in
+/// the same slot, so their instructions are preceded by a refresh
+fn refresh_oracle_price_instructions(
+    config: &Config,
+    token_swap_address: &Pubkey,
+    token_swap: &dyn SwapState,
+) -> Result<Vec<Instruction>, Error> {
+    if token_swap.swap_curve().curve_type != CurveType::ProactiveMarketMaker {
+        return Ok(vec![]);
+    }
+    let state = get_proactive_market_maker_state(&config.rpc_client, token_swap_address)?;
+    Ok(vec![spl_token_swap::instruction::refresh_oracle_price(
+        &spl_token_swap::id(),
+        token_swap_address,
+        &state.oracle_feed,
+        token_swap.token_a_account(),
+        token_swap.token_b_account(),
+    )?])
+}
+
+/// Direction of a trade paying or receiving tokens of `mint`
+fn trade_direction(token_swap: &dyn SwapState, mint: &Pubkey) -> Result<TradeDirection, Error> {
+    if *mint == *token_swap.token_a_mint() {
+        Ok(TradeDirection::AtoB)
+    } else if *mint == *token_swap.token_b_mint() {
+        Ok(TradeDirection::BtoA)
+    } else {
+        Err(format!("Mint {} is not traded by the token swap", mint).into())
+    }
+}
+
+fn minimum_with_slippage(config: &Config, amount: u64) -> u64 {
+    let slippage = (amount as u128 * config.slippage as u128 + MAX_SLIPPAGE_BASIS_POINTS as u128
+        - 1)
+        / MAX_SLIPPAGE_BASIS_POINTS as u128;
+    amount.saturating_sub(slippage as u64)
+}
+
+fn maximum_with_slippage(config: &Config, amount: u64) -> u64 {
+    let slippage = amount as u128 * config.slippage as u128 / MAX_SLIPPAGE_BASIS_POINTS as u128;
+    amount.saturating_add(slippage as u64)
+}
+
+fn format_price_impact(price_impact: &Option<PreciseNumber>) -> String {
+    price_impact
+        .as_ref()
+        .and_then(|price_impact| {
+            // percentage with four decimals
+            price_impact
+                .checked_mul(&PreciseNumber::new(1_000_000)?)?
+                .to_imprecise()
+        })
+        .map(|price_impact| format!("{}%", price_impact as f64 / 10_000.0))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn print_fees(fees: &QuoteFees, trade_fee_decimals: u8, pool_decimals: u8) {
+    println!(
+        "Trade fee: {}",
+        spl_token::amount_to_ui_amount(fees.trade_fee, trade_fee_decimals)
+    );
+    println!(
+        "Owner fee: {} pool tokens",
+        spl_token::amount_to_ui_amount(fees.owner_fee, pool_decimals)
+    );
+    println!(
+        "Host fee: {} pool tokens",
+        spl_token::amount_to_ui_amount(fees.host_fee, pool_decimals)
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_create_pool(
+    config: &Config,
+    token_a_source: &Pubkey,
+    token_a_amount: f64,
+    token_b_source: &Pubkey,
+    token_b_amount: f64,
+    fees: Fees,
+    swap_curve: SwapCurve,
+    oracle_feed: &Option<Pubkey>,
+    fee_owner: &Option<Pubkey>,
+    admin: Option<&dyn Signer>,
+) -> CommandResult {
+    let token_a_source_account = get_any_token_account(&config.rpc_client, token_a_source)?;
+    let token_b_source_account = get_any_token_account(&config.rpc_client, token_b_source)?;
+    let token_a_mint = get_token_mint(&config.rpc_client, &token_a_source_account.mint)?;
+    let token_b_mint = get_token_mint(&config.rpc_client, &token_b_source_account.mint)?;
+    let token_a_amount = spl_token::ui_amount_to_amount(token_a_amount, token_a_mint.decimals);
+    let token_b_amount = spl_token::ui_amount_to_amount(token_b_amount, token_b_mint.decimals);
+
+    let swap_keypair = Keypair::new();
+    println!("Creating token swap {}", swap_keypair.pubkey());
+    let (authority, nonce) = find_authority_program_address(&swap_keypair.pubkey());
+    if config.verbose {
+        println!("Token swap authority {}", authority);
+    }
+
+    let token_a_account = Keypair::new();
+    let token_b_account = Keypair::new();
+    let pool_mint = Keypair::new();
+    println!("Creating pool mint {}", pool_mint.pubkey());
+    let pool_fee_account = Keypair::new();
+    println!(
+        "Creating pool fee collection account {}",
+        pool_fee_account.pubkey()
+    );
+    let pool_token_receiver = Keypair::new();
+    println!(
+        "Creating account to receive pool tokens {}",
+        pool_token_receiver.pubkey()
+    );
+
+    let token_account_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)?;
+    let mint_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)?;
+    let swap_len = if swap_curve.curve_type == CurveType::ProactiveMarketMaker {
+        SwapVersion::LATEST_LEN + ProactiveMarketMakerState::LEN
+    } else {
+        SwapVersion::LATEST_LEN
+    };
+    let swap_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(swap_len)?;
+
+    // Token accounts of the swap, holding the initial liquidity
+    let mut instructions = create_token_account_instructions(
+        config,
+        &token_a_account.pubkey(),
+        &token_a_source_account.mint,
+        &authority,
+        token_account_balance,
+    )?;
+    instructions.extend(create_token_account_instructions(
+        config,
+        &token_b_account.pubkey(),
+        &token_b_source_account.mint,
+        &authority,
+        token_account_balance,
+    )?);
+    instructions.extend(vec![
+        spl_token::instruction::transfer(
+            &spl_token::id(),
+            token_a_source,
+            &token_a_account.pubkey(),
+            &config.token_owner.pubkey(),
+            &[],
+            token_a_amount,
+        )?,
+        spl_token::instruction::transfer(
+            &spl_token::id(),
+            token_b_source,
+            &token_b_account.pubkey(),
+            &config.token_owner.pubkey(),
+            &[],
+            token_b_amount,
+        )?,
+    ]);
+    sign_and_send_instructions(
+        config,
+        &instructions,
+        vec![
+            config.fee_payer.as_ref(),
+            config.token_owner.as_ref(),
+            &token_a_account,
+            &token_b_account,
+        ],
+        2 * token_account_balance,
+    )?;
+
+    // Pool mint, fee account and initial pool token receiver
+    let mut instructions = vec![
+        system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            &pool_mint.pubkey(),
+            mint_balance,
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_mint(
+            &spl_token::id(),
+            &pool_mint.pubkey(),
+            &authority,
+            None,
+            spl_token::native_mint::DECIMALS,
+        )?,
+    ];
+    instructions.extend(create_token_account_instructions(
+        config,
+        &pool_fee_account.pubkey(),
+        &pool_mint.pubkey(),
+        &fee_owner.unwrap_or_else(|| config.token_owner.pubkey()),
+        token_account_balance,
+    )?);
+    instructions.extend(create_token_account_instructions(
+        config,
+        &pool_token_receiver.pubkey(),
+        &pool_mint.pubkey(),
+        &config.token_owner.pubkey(),
+        token_account_balance,
+    )?);
+    sign_and_send_instructions(
+        config,
+        &instructions,
+        vec![
+            config.fee_payer.as_ref(),
+            &pool_mint,
+            &pool_fee_account,
+            &pool_token_receiver,
+        ],
+        mint_balance + 2 * token_account_balance,
+    )?;
+
+    // Token swap itself
+    let admin_pubkey = admin.map(|admin| admin.pubkey());
+    let initialize_instruction = match oracle_feed {
+        Some(oracle_feed) => spl_token_swap::instruction::initialize_proactive_market_maker(
+            &spl_token_swap::id(),
+            &spl_token::id(),
+            &swap_keypair.pubkey(),
+            &authority,
+            &token_a_account.pubkey(),
+            &token_b_account.pubkey(),
+            &pool_mint.pubkey(),
+            &pool_fee_account.pubkey(),
+            &pool_token_receiver.pubkey(),
+            oracle_feed,
+            nonce,
+            fees,
+            swap_curve,
+            admin_pubkey.as_ref(),
+        )?,
+        None => spl_token_swap::instruction::initialize(
+            &spl_token_swap::id(),
+            &spl_token::id(),
+            &swap_keypair.pubkey(),
+            &authority,
+            &token_a_account.pubkey(),
+            &token_b_account.pubkey(),
+            &pool_mint.pubkey(),
+            &pool_fee_account.pubkey(),
+            &pool_token_receiver.pubkey(),
+            nonce,
+            fees,
+            swap_curve,
+            admin_pubkey.as_ref(),
+        )?,
+    };
+    let mut signers: Vec<&dyn Signer> = vec![config.fee_payer.as_ref(), &swap_keypair];
+    if let Some(admin) = admin {
+        signers.push(admin);
+    }
+    sign_and_send_instructions(
+        config,
+        &[
+            system_instruction::create_account(
+                &config.fee_payer.pubkey(),
+                &swap_keypair.pubkey(),
+                swap_balance,
+                swap_len as u64,
+                &spl_token_swap::id(),
+            ),
+            initialize_instruction,
+        ],
+        signers,
+        swap_balance,
+    )
+}
+
+fn command_list(config: &Config, token_a_mint: &Pubkey, token_b_mint: &Pubkey) -> CommandResult {
+    let mut token_swaps = get_token_swaps_by_mints(&config.rpc_client, token_a_mint, token_b_mint)?;
+    token_swaps.extend(get_token_swaps_by_mints(
+        &config.rpc_client,
+        token_b_mint,
+        token_a_mint,
+    )?);
+    if token_swaps.is_empty() {
+        return Err(format!(
+            "No token swaps found for {} and {}",
+            token_a_mint, token_b_mint
+        )
+        .into());
+    }
+
+    for (address, token_swap) in token_swaps {
+        println!(
+            "Token Swap: {}\tToken A Mint: {}\tToken B Mint: {}\tCurve: {:?}{}",
+            address,
+            token_swap.token_a_mint(),
+            token_swap.token_b_mint(),
+            token_swap.swap_curve().curve_type,
+            if token_swap.is_paused() {
+                " [PAUSED]"
+            } else {
+                ""
+            }
+        );
+    }
+    Ok(())
+}
+
+fn command_info(config: &Config, token_swap_address: &Pubkey) -> CommandResult {
+    let pool = get_pool(&config.rpc_client, token_swap_address)?;
+    let token_swap = pool.token_swap.as_ref();
+    let token_a_mint = get_token_mint(&config.rpc_client, token_swap.token_a_mint())?;
+    let token_b_mint = get_token_mint(&config.rpc_client, token_swap.token_b_mint())?;
+
+    println!("Token Swap: {}", token_swap_address);
+    println!(
+        "Authority: {}",
+        find_authority_program_address(token_swap_address).0
+    );
+    println!("Curve: {:?}", token_swap.swap_curve().calculator);
+    if config.verbose {
+        println!("Fees: {:?}", token_swap.fees());
+    }
+    if let Some(admin_authority) = token_swap.admin_authority() {
+        println!("Admin Authority: {}", admin_authority);
+    }
+    if token_swap.is_paused() {
+        println!("Paused");
+    }
+    if token_swap.swap_curve().curve_type == CurveType::ProactiveMarketMaker {
+        let state = get_proactive_market_maker_state(&config.rpc_client, token_swap_address)?;
+        println!(
+            "Oracle Feed: {}\tLast Refresh Slot: {}",
+            state.oracle_feed, state.last_refresh_slot
+        );
+    }
+    println!(
+        "Token A: {}\tMint: {}\tReserve: {}",
+        token_swap.token_a_account(),
+        token_swap.token_a_mint(),
+        spl_token::amount_to_ui_amount(pool.token_a.amount, token_a_mint.decimals)
+    );
+    println!(
+        "Token B: {}\tMint: {}\tReserve: {}",
+        token_swap.token_b_account(),
+        token_swap.token_b_mint(),
+        spl_token::amount_to_ui_amount(pool.token_b.amount, token_b_mint.decimals)
+    );
+    println!(
+        "Pool Mint: {}\tSupply: {}",
+        token_swap.pool_mint(),
+        spl_token::amount_to_ui_amount(pool.pool_mint.supply, pool.pool_mint.decimals)
+    );
+    println!("Pool Fee Account: {}", token_swap.pool_fee_account());
+    if let Some(spot_price) = token_swap
+        .swap_curve()
+        .calculator
+        .spot_price(pool.token_a.amount as u128, pool.token_b.amount as u128)
+        .and_then(|spot_price| {
+            // price of whole tokens, six decimals
+            spot_price
+                .checked_mul(&PreciseNumber::new(
+                    10u128.pow(token_a_mint.decimals as u32),
+                )?)?
+                .checked_mul(&PreciseNumber::new(1_000_000)?)?
+                .checked_div(&PreciseNumber::new(
+                    10u128.pow(token_b_mint.decimals as u32),
+                )?)?
+                .to_imprecise()
+        })
+    {
+        println!(
+            "Spot Price: {} token B per token A",
+            spot_price as f64 / 1_000_000.0
+        );
+    }
+    if let Some(price_accumulator) = token_swap.price_accumulator() {
+        if config.verbose {
+            println!("Price Accumulator: {:?}", price_accumulator);
+        }
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_swap(
+    config: &Config,
+    token_swap_address: &Pubkey,
+    source: &Pubkey,
+    amount: f64,
+    destination: &Option<Pubkey>,
+    minimum_amount_out: Option<f64>,
+    host_fee_account: &Option<Pubkey>,
+) -> CommandResult {
+    let pool = get_pool(&config.rpc_client, token_swap_address)?;
+    let token_swap = pool.token_swap.as_ref();
+    if token_swap.is_paused() {
+        return Err(format!("Token swap {} is paused", token_swap_address).into());
+    }
+    let source_account = get_any_token_account(&config.rpc_client, source)?;
+    let trade_direction = trade_direction(token_swap, &source_account.mint)?;
+    let (swap_source, swap_destination, destination_mint) = match trade_direction {
+        TradeDirection::AtoB => (
+            token_swap.token_a_account(),
+            token_swap.token_b_account(),
+            token_swap.token_b_mint(),
+        ),
+        TradeDirection::BtoA => (
+            token_swap.token_b_account(),
+            token_swap.token_a_account(),
+            token_swap.token_a_mint(),
+        ),
+    };
+    let source_decimals = get_token_mint(&config.rpc_client, &source_account.mint)?.decimals;
+    let destination_decimals = get_token_mint(&config.rpc_client, destination_mint)?.decimals;
+    let amount_in = spl_token::ui_amount_to_amount(amount, source_decimals);
+
+    let minimum_amount_out = match minimum_amount_out {
+        Some(minimum_amount_out) => {
+            spl_token::ui_amount_to_amount(minimum_amount_out, destination_decimals)
+        }
+        None => {
+            let quote =
+                pool.quoter()
+                    .swap(trade_direction, amount_in, host_fee_account.is_some())?;
+            println!(
+                "Expected output: {}\tPrice impact: {}",
+                spl_token::amount_to_ui_amount(quote.amount_out, destination_decimals),
+                format_price_impact(&quote.price_impact)
+            );
+            minimum_with_slippage(config, quote.amount_out)
+        }
+    };
+    if config.verbose {
+        println!(
+            "Minimum output: {}",
+            spl_token::amount_to_ui_amount(minimum_amount_out, destination_decimals)
+        );
+    }
+
+    let mut instructions =
+        refresh_oracle_price_instructions(config, token_swap_address, token_swap)?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.token_owner.as_ref()];
+    let mut total_rent_free_balances: u64 = 0;
+
+    let destination_account = Keypair::new();
+    let destination = unwrap_create_token_account(
+        config,
+        destination,
+        &destination_account,
+        destination_mint,
+        &mut instructions,
+        |balance| {
+            signers.push(&destination_account);
+            total_rent_free_balances += balance;
+        },
+    )?;
+
+    instructions.push(spl_token_swap::instruction::swap(
+        &spl_token_swap::id(),
+        token_swap.token_program_id(),
+        token_swap_address,
+        &find_authority_program_address(token_swap_address).0,
+        &config.token_owner.pubkey(),
+        source,
+        swap_source,
+        swap_destination,
+        &destination,
+        token_swap.pool_mint(),
+        token_swap.pool_fee_account(),
+        host_fee_account.as_ref(),
+        Swap {
+            amount_in,
+            minimum_amount_out,
+        },
+    )?);
+    sign_and_send_instructions(config, &instructions, signers, total_rent_free_balances)
+}
+
+fn command_deposit(
+    config: &Config,
+    token_swap_address: &Pubkey,
+    pool_token_amount: f64,
+    token_a_source: &Pubkey,
+    token_b_source: &Pubkey,
+    pool_token_receiver: &Option<Pubkey>,
+) -> CommandResult {
+    let pool = get_pool(&config.rpc_client, token_swap_address)?;
+    let token_swap = pool.token_swap.as_ref();
+    let pool_token_amount =
+        spl_token::ui_amount_to_amount(pool_token_amount, pool.pool_mint.decimals);
+    let quote = pool.quoter().deposit_all_token_types(pool_token_amount)?;
+    let token_a_decimals = get_token_mint(&config.rpc_client, token_swap.token_a_mint())?.decimals;
+    let token_b_decimals = get_token_mint(&config.rpc_client, token_swap.token_b_mint())?.decimals;
+    println!(
+        "Depositing {} token A and {} token B",
+        spl_token::amount_to_ui_amount(quote.token_a_amount, token_a_decimals),
+        spl_token::amount_to_ui_amount(quote.token_b_amount, token_b_decimals)
+    );
+
+    let mut instructions: Vec<Instruction> = vec![];
+    let mut signers = vec![config.fee_payer.as_ref(), config.token_owner.as_ref()];
+    let mut total_rent_free_balances: u64 = 0;
+
+    let pool_token_receiver_account = Keypair::new();
+    let pool_token_receiver = unwrap_create_token_account(
+        config,
+        pool_token_receiver,
+        &pool_token_receiver_account,
+        token_swap.pool_mint(),
+        &mut instructions,
+        |balance| {
+            signers.push(&pool_token_receiver_account);
+            total_rent_free_balances += balance;
+        },
+    )?;
+
+    instructions.push(spl_token_swap::instruction::deposit_all_token_types(
+        &spl_token_swap::id(),
+        token_swap.token_program_id(),
+        token_swap_address,
+        &find_authority_program_address(token_swap_address).0,
+        &config.token_owner.pubkey(),
+        token_a_source,
+        token_b_source,
+        token_swap.token_a_account(),
+        token_swap.token_b_account(),
+        token_swap.pool_mint(),
+        &pool_token_receiver,
+        DepositAllTokenTypes {
+            pool_token_amount,
+            maximum_token_a_amount: maximum_with_slippage(config, quote.token_a_amount),
+            maximum_token_b_amount: maximum_with_slippage(config, quote.token_b_amount),
+        },
+    )?);
+    sign_and_send_instructions(config, &instructions, signers, total_rent_free_balances)
+}
+
+fn command_deposit_single(
+    config: &Config,
+    token_swap_address: &Pubkey,
+    source: &Pubkey,
+    amount: f64,
+    pool_token_receiver: &Option<Pubkey>,
+) -> CommandResult {
+    let pool = get_pool(&config.rpc_client, token_swap_address)?;
+    let token_swap = pool.token_swap.as_ref();
+    let source_account = get_any_token_account(&config.rpc_client, source)?;
+    let trade_direction = trade_direction(token_swap, &source_account.mint)?;
+    let source_decimals = get_token_mint(&config.rpc_client, &source_account.mint)?.decimals;
+    let source_token_amount = spl_token::ui_amount_to_amount(amount, source_decimals);
+    let quote = pool
+        .quoter()
+        .deposit_single_token_type_exact_amount_in(trade_direction, source_token_amount)?;
+    println!(
+        "Expected pool tokens: {}\tPrice impact: {}",
+        spl_token::amount_to_ui_amount(quote.pool_token_amount, pool.pool_mint.decimals),
+        format_price_impact(&quote.price_impact)
+    );
+
+    let mut instructions =
+        refresh_oracle_price_instructions(config, token_swap_address, token_swap)?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.token_owner.as_ref()];
+    let mut total_rent_free_balances: u64 = 0;
+
+    let pool_token_receiver_account = Keypair::new();
+    let pool_token_receiver = unwrap_create_token_account(
+        config,
+        pool_token_receiver,
+        &pool_token_receiver_account,
+        token_swap.pool_mint(),
+        &mut instructions,
+        |balance| {
+            signers.push(&pool_token_receiver_account);
+            total_rent_free_balances += balance;
+        },
+    )?;
+
+    instructions.push(
+        spl_token_swap::instruction::deposit_single_token_type_exact_amount_in(
+            &spl_token_swap::id(),
+            token_swap.token_program_id(),
+            token_swap_address,
+            &find_authority_program_address(token_swap_address).0,
+            &config.token_owner.pubkey(),
+            source,
+            token_swap.token_a_account(),
+            token_swap.token_b_account(),
+            token_swap.pool_mint(),
+            &pool_token_receiver,
+            DepositSingleTokenTypeExactAmountIn {
+                source_token_amount,
+                minimum_pool_token_amount: minimum_with_slippage(config, quote.pool_token_amount),
+            },
+        )?,
+    );
+    sign_and_send_instructions(config, &instructions, signers, total_rent_free_balances)
+}
+
+fn command_withdraw(
+    config: &Config,
+    token_swap_address: &Pubkey,
+    pool_token_amount: f64,
+    withdraw_from: &Pubkey,
+    token_a_receiver: &Option<Pubkey>,
+    token_b_receiver: &Option<Pubkey>,
+) -> CommandResult {
+    let pool = get_pool(&config.rpc_client, token_swap_address)?;
+    let token_swap = pool.token_swap.as_ref();
+    let pool_token_amount =
+        spl_token::ui_amount_to_amount(pool_token_amount, pool.pool_mint.decimals);
+    let quote = pool.quoter().withdraw_all_token_types(
+        pool_token_amount,
+        *withdraw_from != *token_swap.pool_fee_account(),
+    )?;
+    let token_a_decimals = get_token_mint(&config.rpc_client, token_swap.token_a_mint())?.decimals;
+    let token_b_decimals = get_token_mint(&config.rpc_client, token_swap.token_b_mint())?.decimals;
+    println!(
+        "Withdrawing {} token A and {} token B",
+        spl_token::amount_to_ui_amount(quote.token_a_amount, token_a_decimals),
+        spl_token::amount_to_ui_amount(quote.token_b_amount, token_b_decimals)
+    );
+
+    let mut instructions: Vec<Instruction> = vec![];
+    let mut signers = vec![config.fee_payer.as_ref(), config.token_owner.as_ref()];
+    let mut total_rent_free_balances: u64 = 0;
+
+    let token_a_receiver_account = Keypair::new();
+    let token_a_receiver = unwrap_create_token_account(
+        config,
+        token_a_receiver,
+        &token_a_receiver_account,
+        token_swap.token_a_mint(),
+        &mut instructions,
+        |balance| {
+            signers.push(&token_a_receiver_account);
+            total_rent_free_balances += balance;
+        },
+    )?;
+    let token_b_receiver_account = Keypair::new();
+    let token_b_receiver = unwrap_create_token_account(
+        config,
+        token_b_receiver,
+        &token_b_receiver_account,
+        token_swap.token_b_mint(),
+        &mut instructions,
+        |balance| {
+            signers.push(&token_b_receiver_account);
+            total_rent_free_balances += balance;
+        },
+    )?;
+
+    instructions.push(spl_token_swap::instruction::withdraw_all_token_types(
+        &spl_token_swap::id(),
+        token_swap.token_program_id(),
+        token_swap_address,
+        &find_authority_program_address(token_swap_address).0,
+        &config.token_owner.pubkey(),
+        token_swap.pool_mint(),
+        token_swap.pool_fee_account(),
+        withdraw_from,
+        token_swap.token_a_account(),
+        token_swap.token_b_account(),
+        &token_a_receiver,
+        &token_b_receiver,
+        WithdrawAllTokenTypes {
+            pool_token_amount,
+            minimum_token_a_amount: minimum_with_slippage(config, quote.token_a_amount),
+            minimum_token_b_amount: minimum_with_slippage(config, quote.token_b_amount),
+        },
+    )?);
+    sign_and_send_instructions(config, &instructions, signers, total_rent_free_balances)
+}
+
+fn command_withdraw_single(
+    config: &Config,
+    token_swap_address: &Pubkey,
+    withdraw_from: &Pubkey,
+    destination: &Pubkey,
+    amount: f64,
+) -> CommandResult {
+    let pool = get_pool(&config.rpc_client, token_swap_address)?;
+    let token_swap = pool.token_swap.as_ref();
+    let destination_account = get_any_token_account(&config.rpc_client, destination)?;
+    let trade_direction = trade_direction(token_swap, &destination_account.mint)?;
+    let destination_decimals =
+        get_token_mint(&config.rpc_client, &destination_account.mint)?.decimals;
+    let destination_token_amount = spl_token::ui_amount_to_amount(amount, destination_decimals);
+    let quote = pool.quoter().withdraw_single_token_type_exact_amount_out(
+        trade_direction,
+        destination_token_amount,
+        *withdraw_from != *token_swap.pool_fee_account(),
+    )?;
+    println!(
+        "Expected pool tokens: {}\tPrice impact: {}",
+        spl_token::amount_to_ui_amount(quote.pool_token_amount, pool.pool_mint.decimals),
+        format_price_impact(&quote.price_impact)
+    );
+
+    let mut instructions =
+        refresh_oracle_price_instructions(config, token_swap_address, token_swap)?;
+    instructions.push(
+        spl_token_swap::instruction::withdraw_single_token_type_exact_amount_out(
+            &spl_token_swap::id(),
+            token_swap.token_program_id(),
+            token_swap_address,
+            &find_authority_program_address(token_swap_address).0,
+            &config.token_owner.pubkey(),
+            token_swap.pool_mint(),
+            token_swap.pool_fee_account(),
+            withdraw_from,
+            token_swap.token_a_account(),
+            token_swap.token_b_account(),
+            destination,
+            WithdrawSingleTokenTypeExactAmountOut {
+                destination_token_amount,
+                maximum_pool_token_amount: maximum_with_slippage(config, quote.pool_token_amount),
+            },
+        )?,
+    );
+    sign_and_send_instructions(
+        config,
+        &instructions,
+        vec![config.fee_payer.as_ref(), config.token_owner.as_ref()],
+        0,
+    )
+}
+
+fn command_quote(
+    config: &Config,
+    token_swap_address: &Pubkey,
+    operation: &str,
+    amount: f64,
+    mint: &Option<Pubkey>,
+) -> CommandResult {
+    let pool = get_pool(&config.rpc_client, token_swap_address)?;
+    let token_swap = pool.token_swap.as_ref();
+    let quoter = pool.quoter();
+    let pool_decimals = pool.pool_mint.decimals;
+    let token_a_decimals = get_token_mint(&config.rpc_client, token_swap.token_a_mint())?.decimals;
+    let token_b_decimals = get_token_mint(&config.rpc_client, token_swap.token_b_mint())?.decimals;
+    let trade_direction = mint
+        .as_ref()
+        .map(|mint| trade_direction(token_swap, mint))
+        .transpose()?;
+    let (source_decimals, destination_decimals) = match trade_direction {
+        Some(TradeDirection::BtoA) => (token_b_decimals, token_a_decimals),
+        _ => (token_a_decimals, token_b_decimals),
+    };
+
+    match (operation, trade_direction) {
+        ("swap", Some(trade_direction)) => {
+            let amount_in = spl_token::ui_amount_to_amount(amount, source_decimals);
+            let quote = quoter.swap(trade_direction, amount_in, false)?;
+            println!(
+                "Amount in: {}",
+                spl_token::amount_to_ui_amount(quote.amount_in, source_decimals)
+            );
+            println!(
+                "Amount out: {}",
+                spl_token::amount_to_ui_amount(quote.amount_out, destination_decimals)
+            );
+            print_fees(&quote.fees, source_decimals, pool_decimals);
+            println!("Price impact: {}", format_price_impact(&quote.price_impact));
+        }
+        ("deposit", _) => {
+            let pool_token_amount = spl_token::ui_amount_to_amount(amount, pool_decimals);
+            let quote = quoter.deposit_all_token_types(pool_token_amount)?;
+            println!(
+                "Token A in: {}",
+                spl_token::amount_to_ui_amount(quote.token_a_amount, token_a_decimals)
+            );
+            println!(
+                "Token B in: {}",
+                spl_token::amount_to_ui_amount(quote.token_b_amount, token_b_decimals)
+            );
+            println!(
+                "Pool tokens out: {}",
+                spl_token::amount_to_ui_amount(quote.pool_token_amount, pool_decimals)
+            );
+        }
+        ("withdraw", _) => {
+            let pool_token_amount = spl_token::ui_amount_to_amount(amount, pool_decimals);
+            let quote = quoter.withdraw_all_token_types(pool_token_amount, true)?;
+            println!(
+                "Pool tokens in: {}",
+                spl_token::amount_to_ui_amount(quote.pool_token_amount, pool_decimals)
+            );
+            println!(
+                "Token A out: {}",
+                spl_token::amount_to_ui_amount(quote.token_a_amount, token_a_decimals)
+            );
+            println!(
+                "Token B out: {}",
+                spl_token::amount_to_ui_amount(quote.token_b_amount, token_b_decimals)
+            );
+            print_fees(&quote.fees, 0, pool_decimals);
+        }
+        ("deposit-single", Some(trade_direction)) => {
+            let source_token_amount = spl_token::ui_amount_to_amount(amount, source_decimals);
+            let quote = quoter
+                .deposit_single_token_type_exact_amount_in(trade_direction, source_token_amount)?;
+            println!(
+                "Pool tokens out: {}",
+                spl_token::amount_to_ui_amount(quote.pool_token_amount, pool_decimals)
+            );
+            print_fees(&quote.fees, source_decimals, pool_decimals);
+            println!("Price impact: {}", format_price_impact(&quote.price_impact));
+        }
+        ("withdraw-single", Some(trade_direction)) => {
+            let destination_token_amount = spl_token::ui_amount_to_amount(amount, source_decimals);
+            let quote = quoter.withdraw_single_token_type_exact_amount_out(
+                trade_direction,
+                destination_token_amount,
+                true,
+            )?;
+            println!(
+                "Pool tokens in: {}",
+                spl_token::amount_to_ui_amount(quote.pool_token_amount, pool_decimals)
+            );
+            print_fees(&quote.fees, source_decimals, pool_decimals);
+            println!("Price impact: {}", format_price_impact(&quote.price_impact));
+        }
+        _ => return Err(format!("The {} quote requires a token mint", operation).into()),
+    }
+    Ok(())
+}
+
+fn main() {
+    solana_logger::setup_with_default("solana=info");
+
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg({
+            let arg = Arg::with_name("config_file")
+                .short("C")
+                .long("config")
+                .value_name("PATH")
+                .takes_value(true)
+                .global(true)
+                .help("Configuration file to use");
+            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
+                arg.default_value(&config_file)
+            } else {
+                arg
+            }
+        })
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .takes_value(false)
+                .global(true)
+                .help("Show additional information"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .takes_value(false)
+                .global(true)
+                .help("Simluate transaction instead of executing"),
+        )
+        .arg(
+            Arg::with_name("json_rpc_url")
+                .long("url")
+                .value_name("URL")
+                .takes_value(true)
+                .validator(is_url)
+                .help("JSON RPC URL for the cluster.  Default from the configuration file."),
+        )
+        .arg(
+            Arg::with_name("token_owner")
+                .long("token-owner")
+                .value_name("KEYPAIR")
+                .validator(is_keypair)
+                .takes_value(true)
+                .help(
+                    "Specify the owner of the token accounts used by the command. \
+                     This may be a keypair file, the ASK keyword. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .arg(
+            Arg::with_name("fee_payer")
+                .long("fee-payer")
+                .value_name("KEYPAIR")
+                .validator(is_keypair)
+                .takes_value(true)
+                .help(
+                    "Specify the fee-payer account. \
+                     This may be a keypair file, the ASK keyword. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .arg(
+            Arg::with_name("slippage")
+                .long("slippage")
+                .value_name("BASIS_POINTS")
+                .validator(is_parsable::<u64>)
+                .takes_value(true)
+                .global(true)
+                .default_value("100")
+                .help("Maximum slippage from the quoted amounts, in basis points."),
+        )
+        .subcommand(SubCommand::with_name("create-pool")
+            .about("Create a new token swap, funded from the token owner's accounts")
+            .arg(
+                Arg::with_name("token_a_source")
+                    .long("token-a-source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account funding the initial token A liquidity"),
+            )
+            .arg(
+                Arg::with_name("token_a_amount")
+                    .long("token-a-amount")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Initial token A liquidity"),
+            )
+            .arg(
+                Arg::with_name("token_b_source")
+                    .long("token-b-source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account funding the initial token B liquidity"),
+            )
+            .arg(
+                Arg::with_name("token_b_amount")
+                    .long("token-b-amount")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Initial token B liquidity"),
+            )
+            .arg(
+                Arg::with_name("curve")
+                    .long("curve")
+                    .value_name("CURVE")
+                    .takes_value(true)
+                    .possible_values(&[
+                        "constant-product",
+                        "constant-price",
+                        "stable",
+                        "offset",
+                        "proactive-market-maker",
+                    ])
+                    .default_value("constant-product")
+                    .help("Curve used to price trades"),
+            )
+            .arg(
+                Arg::with_name("token_b_price")
+                    .long("token-b-price")
+                    .validator(is_parsable::<u64>)
+                    .value_name("PRICE")
+                    .takes_value(true)
+                    .required_if("curve", "constant-price")
+                    .help("Amount of token A per token B, for the constant price curve"),
+            )
+            .arg(
+                Arg::with_name("amp")
+                    .long("amp")
+                    .validator(is_parsable::<u64>)
+                    .value_name("AMPLIFIER")
+                    .takes_value(true)
+                    .required_if("curve", "stable")
+                    .help("Amplification coefficient, for the stable curve"),
+            )
+            .arg(
+                Arg::with_name("token_b_offset")
+                    .long("token-b-offset")
+                    .validator(is_parsable::<u64>)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required_if("curve", "offset")
+                    .help("Virtual token B added to the pool, for the offset curve"),
+            )
+            .arg(
+                Arg::with_name("k")
+                    .long("k")
+                    .validator(is_parsable::<u64>)
+                    .value_name("K")
+                    .takes_value(true)
+                    .required_if("curve", "proactive-market-maker")
+                    .help("Liquidity concentration in millionths, for the proactive market maker curve"),
+            )
+            .arg(
+                Arg::with_name("oracle_feed")
+                    .long("oracle-feed")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required_if("curve", "proactive-market-maker")
+                    .help("Oracle price feed, for the proactive market maker curve"),
+            )
+            .arg(
+                Arg::with_name("max_oracle_staleness_slots")
+                    .long("max-oracle-staleness-slots")
+                    .validator(is_parsable::<u32>)
+                    .value_name("SLOTS")
+                    .takes_value(true)
+                    .default_value("25")
+                    .help("Maximum age of the oracle price, for the proactive market maker curve"),
+            )
+            .arg(
+                Arg::with_name("max_oracle_confidence_bps")
+                    .long("max-oracle-confidence-bps")
+                    .validator(is_parsable::<u16>)
+                    .value_name("BASIS_POINTS")
+                    .takes_value(true)
+                    .default_value("100")
+                    .help("Maximum oracle price confidence interval, in basis points of the price, for the proactive market maker curve"),
+            )
+            .arg(
+                Arg::with_name("trade_fee_numerator")
+                    .long("trade-fee-numerator")
+                    .validator(is_parsable::<u64>)
+                    .value_name("NUMERATOR")
+                    .takes_value(true)
+                    .default_value("25")
+                    .help("Trade fee numerator, fee amount is numerator divided by denominator."),
+            )
+            .arg(
+                Arg::with_name("trade_fee_denominator")
+                    .long("trade-fee-denominator")
+                    .validator(is_parsable::<u64>)
+                    .value_name("DENOMINATOR")
+                    .takes_value(true)
+                    .default_value("10000")
+                    .help("Trade fee denominator, fee amount is numerator divided by denominator."),
+            )
+            .arg(
+                Arg::with_name("owner_trade_fee_numerator")
+                    .long("owner-trade-fee-numerator")
+                    .validator(is_parsable::<u64>)
+                    .value_name("NUMERATOR")
+                    .takes_value(true)
+                    .default_value("5")
+                    .help("Owner trade fee numerator, fee amount is numerator divided by denominator."),
+            )
+            .arg(
+                Arg::with_name("owner_trade_fee_denominator")
+                    .long("owner-trade-fee-denominator")
+                    .validator(is_parsable::<u64>)
+                    .value_name("DENOMINATOR")
+                    .takes_value(true)
+                    .default_value("10000")
+                    .help("Owner trade fee denominator, fee amount is numerator divided by denominator."),
+            )
+            .arg(
+                Arg::with_name("owner_withdraw_fee_numerator")
+                    .long("owner-withdraw-fee-numerator")
+                    .validator(is_parsable::<u64>)
+                    .value_name("NUMERATOR")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Owner withdraw fee numerator, fee amount is numerator divided by denominator."),
+            )
+            .arg(
+                Arg::with_name("owner_withdraw_fee_denominator")
+                    .long("owner-withdraw-fee-denominator")
+                    .validator(is_parsable::<u64>)
+                    .value_name("DENOMINATOR")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Owner withdraw fee denominator, fee amount is numerator divided by denominator."),
+            )
+            .arg(
+                Arg::with_name("host_fee_numerator")
+                    .long("host-fee-numerator")
+                    .validator(is_parsable::<u64>)
+                    .value_name("NUMERATOR")
+                    .takes_value(true)
+                    .default_value("20")
+                    .help("Host share of the owner trade fee numerator."),
+            )
+            .arg(
+                Arg::with_name("host_fee_denominator")
+                    .long("host-fee-denominator")
+                    .validator(is_parsable::<u64>)
+                    .value_name("DENOMINATOR")
+                    .takes_value(true)
+                    .default_value("100")
+                    .help("Host share of the owner trade fee denominator."),
+            )
+            .arg(
+                Arg::with_name("fee_owner")
+                    .long("fee-owner")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Owner of the pool fee account. Defaults to the token owner."),
+            )
+            .arg(
+                Arg::with_name("admin")
+                    .long("admin")
+                    .validator(is_keypair)
+                    .value_name("KEYPAIR")
+                    .takes_value(true)
+                    .help("Admin authority allowed to update the fees and pause the swap, \
+                          which signs the pool creation. Without an admin the fees are fixed."),
+            )
+        )
+        .subcommand(SubCommand::with_name("list")
+            .about("List token swaps trading a pair of mints")
+            .arg(
+                Arg::with_name("token_a_mint")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("MINT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Mint of one token of the pair."),
+            )
+            .arg(
+                Arg::with_name("token_b_mint")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("MINT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Mint of the other token of the pair."),
+            )
+        )
+        .subcommand(SubCommand::with_name("info")
+            .about("Show the state and reserves of a token swap")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token swap address."),
+            )
+        )
+        .subcommand(SubCommand::with_name("swap")
+            .about("Swap tokens through a token swap")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token swap address."),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account to swap from. Must be owned by the token owner."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of source tokens to swap."),
+            )
+            .arg(
+                Arg::with_name("destination")
+                    .long("destination")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Token account to receive the swapped tokens. \
+                          Defaults to a new token account."),
+            )
+            .arg(
+                Arg::with_name("minimum_amount_out")
+                    .long("minimum-amount-out")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Minimum amount of destination tokens to receive. \
+                          Defaults to the quoted amount less the slippage."),
+            )
+            .arg(
+                Arg::with_name("host_fee_account")
+                    .long("host-fee-account")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Pool token account receiving the host share of the owner fee."),
+            )
+        )
+        .subcommand(SubCommand::with_name("deposit")
+            .about("Deposit both tokens for an exact amount of pool tokens")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token swap address."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(2)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of pool tokens to receive."),
+            )
+            .arg(
+                Arg::with_name("token_a_source")
+                    .long("token-a-source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token A account to deposit from. Must be owned by the token owner."),
+            )
+            .arg(
+                Arg::with_name("token_b_source")
+                    .long("token-b-source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token B account to deposit from. Must be owned by the token owner."),
+            )
+            .arg(
+                Arg::with_name("token_receiver")
+                    .long("token-receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Account to receive pool tokens. Defaults to a new pool token account."),
+            )
+        )
+        .subcommand(SubCommand::with_name("deposit-single")
+            .about("Deposit an exact amount of one token")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token swap address."),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account to deposit from. Must be owned by the token owner."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of tokens to deposit."),
+            )
+            .arg(
+                Arg::with_name("token_receiver")
+                    .long("token-receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Account to receive pool tokens. Defaults to a new pool token account."),
+            )
+        )
+        .subcommand(SubCommand::with_name("withdraw")
+            .about("Withdraw both tokens for an exact amount of pool tokens")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token swap address."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(2)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of pool tokens to withdraw."),
+            )
+            .arg(
+                Arg::with_name("withdraw_from")
+                    .long("withdraw-from")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Pool token account to withdraw from. Must be owned by the token owner."),
+            )
+            .arg(
+                Arg::with_name("token_a_receiver")
+                    .long("token-a-receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Account to receive token A. Defaults to a new token account."),
+            )
+            .arg(
+                Arg::with_name("token_b_receiver")
+                    .long("token-b-receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Account to receive token B. Defaults to a new token account."),
+            )
+        )
+        .subcommand(SubCommand::with_name("withdraw-single")
+            .about("Withdraw an exact amount of one token")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token swap address."),
+            )
+            .arg(
+                Arg::with_name("destination")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account to receive the withdrawn tokens."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of tokens to withdraw."),
+            )
+            .arg(
+                Arg::with_name("withdraw_from")
+                    .long("withdraw-from")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Pool token account to withdraw from. Must be owned by the token owner."),
+            )
+        )
+        .subcommand(SubCommand::with_name("quote")
+            .about("Quote an operation against the current state of a token swap")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token swap address."),
+            )
+            .arg(
+                Arg::with_name("operation")
+                    .index(2)
+                    .value_name("OPERATION")
+                    .takes_value(true)
+                    .required(true)
+                    .possible_values(&[
+                        "swap",
+                        "deposit",
+                        "withdraw",
+                        "deposit-single",
+                        "withdraw-single",
+                    ])
+                    .help("Operation to quote."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Source tokens to swap or deposit, tokens to withdraw, \
+                          or pool tokens for deposits and withdrawals of both tokens."),
+            )
+            .arg(
+                Arg::with_name("mint")
+                    .long("mint")
+                    .validator(is_pubkey)
+                    .value_name("MINT_ADDRESS")
+                    .takes_value(true)
+                    .required_ifs(&[
+                        ("operation", "swap"),
+                        ("operation", "deposit-single"),
+                        ("operation", "withdraw-single"),
+                    ])
+                    .help("Mint of the source tokens of a swap, or of the token deposited or withdrawn alone."),
+            )
+        )
+        .get_matches();
+
+    let mut wallet_manager = None;
+    let config = {
+        let cli_config = if let Some(config_file) = matches.value_of("config_file") {
+            solana_cli_config::Config::load(config_file).unwrap_or_default()
+        } else {
+            solana_cli_config::Config::default()
+        };
+        let json_rpc_url = value_t!(matches, "json_rpc_url", String)
+            .unwrap_or_else(|_| cli_config.json_rpc_url.clone());
+
+        let token_owner = signer_from_path(
+            &matches,
+            &cli_config.keypair_path,
+            "token_owner",
+            &mut wallet_manager,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        });
+        let fee_payer = signer_from_path(
+            &matches,
+            &cli_config.keypair_path,
+            "fee_payer",
+            &mut wallet_manager,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        });
+        let verbose = matches.is_present("verbose");
+        let dry_run = matches.is_present("dry_run");
+        let slippage = value_t_or_exit!(matches, "slippage", u64);
+        if slippage > MAX_SLIPPAGE_BASIS_POINTS {
+            eprintln!(
+                "error: slippage cannot exceed {}",
+                MAX_SLIPPAGE_BASIS_POINTS
+            );
+            exit(1);
+        }
+
+        Config {
+            rpc_client: RpcClient::new_with_commitment(json_rpc_url, CommitmentConfig::confirmed()),
+            verbose,
+            token_owner,
+            fee_payer,
+            dry_run,
+            slippage,
+        }
+    };
+
+    let _ = match matches.subcommand() {
+        ("create-pool", Some(arg_matches)) => {
+            let token_a_source = pubkey_of(arg_matches, "token_a_source").unwrap();
+            let token_a_amount = value_t_or_exit!(arg_matches, "token_a_amount", f64);
+            let token_b_source = pubkey_of(arg_matches, "token_b_source").unwrap();
+            let token_b_amount = value_t_or_exit!(arg_matches, "token_b_amount", f64);
+            let fees = Fees {
+                trade_fee_numerator: value_t_or_exit!(arg_matches, "trade_fee_numerator", u64),
+                trade_fee_denominator: value_t_or_exit!(arg_matches, "trade_fee_denominator", u64),
+                owner_trade_fee_numerator: value_t_or_exit!(
+                    arg_matches,
+                    "owner_trade_fee_numerator",
+                    u64
+                ),
+                owner_trade_fee_denominator: value_t_or_exit!(
+                    arg_matches,
+                    "owner_trade_fee_denominator",
+                    u64
+                ),
+                owner_withdraw_fee_numerator: value_t_or_exit!(
+                    arg_matches,
+                    "owner_withdraw_fee_numerator",
+                    u64
+                ),
+                owner_withdraw_fee_denominator: value_t_or_exit!(
+                    arg_matches,
+                    "owner_withdraw_fee_denominator",
+                    u64
+                ),
+                host_fee_numerator: value_t_or_exit!(arg_matches, "host_fee_numerator", u64),
+                host_fee_denominator: value_t_or_exit!(arg_matches, "host_fee_denominator", u64),
+            };
+            let swap_curve = match arg_matches.value_of("curve").unwrap() {
+                "constant-price" => SwapCurve {
+                    curve_type: CurveType::ConstantPrice,
+                    calculator: Box::new(ConstantPriceCurve {
+                        token_b_price: value_t_or_exit!(arg_matches, "token_b_price", u64),
+                    }),
+                },
+                "stable" => SwapCurve {
+                    curve_type: CurveType::Stable,
+                    calculator: Box::new(StableCurve {
+                        amp: value_t_or_exit!(arg_matches, "amp", u64),
+                    }),
+                },
+                "offset" => SwapCurve {
+                    curve_type: CurveType::Offset,
+                    calculator: Box::new(OffsetCurve {
+                        token_b_offset: value_t_or_exit!(arg_matches, "token_b_offset", u64),
+                    }),
+                },
+                "proactive-market-maker" => SwapCurve {
+                    curve_type: CurveType::ProactiveMarketMaker,
+                    // the price is read from the oracle feed on initialization
+                    calculator: Box::new(ProactiveMarketMakerCurve {
+                        k: value_t_or_exit!(arg_matches, "k", u64),
+                        oracle_price: 0,
+                        max_oracle_staleness_slots: value_t_or_exit!(
+                            arg_matches,
+                            "max_oracle_staleness_slots",
+                            u32
+                        ),
+                        max_oracle_confidence_bps: value_t_or_exit!(
+                            arg_matches,
+                            "max_oracle_confidence_bps",
+                            u16
+                        ),
+                    }),
+                },
+                _ => SwapCurve {
+                    curve_type: CurveType::ConstantProduct,
+                    calculator: Box::new(ConstantProductCurve {}),
+                },
+            };
+            let oracle_feed = pubkey_of(arg_matches, "oracle_feed");
+            let fee_owner = pubkey_of(arg_matches, "fee_owner");
+            let admin = arg_matches.value_of("admin").map(|admin_path| {
+                signer_from_path(arg_matches, admin_path, "admin", &mut wallet_manager)
+                    .unwrap_or_else(|e| {
+                        eprintln!("error: {}", e);
+                        exit(1);
+                    })
+            });
+            command_create_pool(
+                &config,
+                &token_a_source,
+                token_a_amount,
+                &token_b_source,
+                token_b_amount,
+                fees,
+                swap_curve,
+                &oracle_feed,
+                &fee_owner,
+                admin.as_deref(),
+            )
+        }
+        ("list", Some(arg_matches)) => {
+            let token_a_mint = pubkey_of(arg_matches, "token_a_mint").unwrap();
+            let token_b_mint = pubkey_of(arg_matches, "token_b_mint").unwrap();
+            command_list(&config, &token_a_mint, &token_b_mint)
+        }
+        ("info", Some(arg_matches)) => {
+            let token_swap_address = pubkey_of(arg_matches, "pool").unwrap();
+            command_info(&config, &token_swap_address)
+        }
+        ("swap", Some(arg_matches)) => {
+            let token_swap_address = pubkey_of(arg_matches, "pool").unwrap();
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let destination = pubkey_of(arg_matches, "destination");
+            let minimum_amount_out = value_t!(arg_matches, "minimum_amount_out", f64).ok();
+            let host_fee_account = pubkey_of(arg_matches, "host_fee_account");
+            command_swap(
+                &config,
+                &token_swap_address,
+                &source,
+                amount,
+                &destination,
+                minimum_amount_out,
+                &host_fee_account,
+            )
+        }
+        ("deposit", Some(arg_matches)) => {
+            let token_swap_address = pubkey_of(arg_matches, "pool").unwrap();
+            let pool_token_amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let token_a_source = pubkey_of(arg_matches, "token_a_source").unwrap();
+            let token_b_source = pubkey_of(arg_matches, "token_b_source").unwrap();
+            let token_receiver = pubkey_of(arg_matches, "token_receiver");
+            command_deposit(
+                &config,
+                &token_swap_address,
+                pool_token_amount,
+                &token_a_source,
+                &token_b_source,
+                &token_receiver,
+            )
+        }
+        ("deposit-single", Some(arg_matches)) => {
+            let token_swap_address = pubkey_of(arg_matches, "pool").unwrap();
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let token_receiver = pubkey_of(arg_matches, "token_receiver");
+            command_deposit_single(
+                &config,
+                &token_swap_address,
+                &source,
+                amount,
+                &token_receiver,
+            )
+        }
+        ("withdraw", Some(arg_matches)) => {
+            let token_swap_address = pubkey_of(arg_matches, "pool").unwrap();
+            let pool_token_amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let withdraw_from = pubkey_of(arg_matches, "withdraw_from").unwrap();
+            let token_a_receiver = pubkey_of(arg_matches, "token_a_receiver");
+            let token_b_receiver = pubkey_of(arg_matches, "token_b_receiver");
+            command_withdraw(
+                &config,
+                &token_swap_address,
+                pool_token_amount,
+                &withdraw_from,
+                &token_a_receiver,
+                &token_b_receiver,
+            )
+        }
+        ("withdraw-single", Some(arg_matches)) => {
+            let token_swap_address = pubkey_of(arg_matches, "pool").unwrap();
+            let destination = pubkey_of(arg_matches, "destination").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let withdraw_from = pubkey_of(arg_matches, "withdraw_from").unwrap();
+            command_withdraw_single(
+                &config,
+                &token_swap_address,
+                &withdraw_from,
+                &destination,
+                amount,
+            )
+        }
+        ("quote", Some(arg_matches)) => {
+            let token_swap_address = pubkey_of(arg_matches, "pool").unwrap();
+            let operation = arg_matches.value_of("operation").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let mint = pubkey_of(arg_matches, "mint");
+            command_quote(&config, &token_swap_address, operation, amount, &mint)
+        }
+        _ => unreachable!(),
+    }
+    .map_err(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+}
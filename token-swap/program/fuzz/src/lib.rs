@@ -2,3 +2,4 @@ pub mod native_account_data;
 pub mod native_processor;
 pub mod native_token;
 pub mod native_token_swap;
+pub mod stable_swap_model;
@@ -89,6 +89,7 @@ impl NativeTokenSwap {
             nonce,
             fees.clone(),
             swap_curve.clone(),
+            None,
         )
         .unwrap();
 
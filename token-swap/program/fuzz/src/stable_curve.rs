@@ -0,0 +1,254 @@
+use spl_token_swap_fuzz::stable_swap_model::StableSwapModel;
+
+use spl_token_swap::curve::{
+    calculator::{CurveCalculator, RoundDirection, TradeDirection},
+    stable::StableCurve,
+};
+
+use honggfuzz::fuzz;
+
+use arbitrary::Arbitrary;
+
+/// Pool state and the operation checked against the model
+#[derive(Debug, Arbitrary, Clone)]
+struct StableCurveFuzzInput {
+    amp: u64,
+    swap_token_a_amount: u64,
+    swap_token_b_amount: u64,
+    pool_token_supply: u64,
+    operation: FuzzOperation,
+}
+
+/// Deposits and withdrawals of a single token type are left out: the stable
+/// curve prices them with the constant product approximation, which only
+/// matches the invariant for balanced pools.
+#[derive(Debug, Arbitrary, Clone)]
+enum FuzzOperation {
+    Swap {
+        source_amount: u64,
+        trade_direction: FuzzTradeDirection,
+    },
+    DepositAllTokenTypes {
+        pool_token_amount: u64,
+    },
+    WithdrawAllTokenTypes {
+        pool_token_amount: u64,
+    },
+}
+
+/// Helper enum to tell which direction a swap is meant to go.
+#[derive(Debug, Arbitrary, Clone)]
+enum FuzzTradeDirection {
+    AtoB,
+    BtoA,
+}
+
+/// Highest amplification coefficient checked, as in the Curve contracts
+const MAX_AMP: u64 = 1_000_000;
+
+/// Highest ratio between the pool balances checked.  Neither the curve nor the
+/// model converge on the invariant of more imbalanced pools.
+const MAX_IMBALANCE: u64 = 10_000;
+
+const N_COINS: u8 = 2;
+
+fn main() {
+    loop {
+        fuzz!(|fuzz_input: StableCurveFuzzInput| { run_fuzz(fuzz_input) });
+    }
+}
+
+fn run_fuzz(fuzz_input: StableCurveFuzzInput) {
+    let StableCurveFuzzInput {
+        amp,
+        swap_token_a_amount,
+        swap_token_b_amount,
+        pool_token_supply,
+        operation,
+    } = fuzz_input;
+    if swap_token_a_amount == 0 || swap_token_b_amount == 0 || pool_token_supply == 0 {
+        return;
+    }
+    if swap_token_a_amount / swap_token_b_amount >= MAX_IMBALANCE
+        || swap_token_b_amount / swap_token_a_amount >= MAX_IMBALANCE
+    {
+        return;
+    }
+    let amp = amp % MAX_AMP + 1;
+    let curve = StableCurve { amp };
+    let swap_token_a_amount = swap_token_a_amount as u128;
+    let swap_token_b_amount = swap_token_b_amount as u128;
+    let pool_token_supply = pool_token_supply as u128;
+
+    match operation {
+        FuzzOperation::Swap {
+            source_amount,
+            trade_direction,
+        } => {
+            let (swap_source_amount, swap_destination_amount) = match trade_direction {
+                FuzzTradeDirection::AtoB => (swap_token_a_amount, swap_token_b_amount),
+                FuzzTradeDirection::BtoA => (swap_token_b_amount, swap_token_a_amount),
+            };
+            check_swap(
+                &curve,
+                source_amount as u128,
+                swap_source_amount,
+                swap_destination_amount,
+            );
+        }
+        FuzzOperation::DepositAllTokenTypes { pool_token_amount } => {
+            check_deposit_all_token_types(
+                &curve,
+                pool_token_amount as u128,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+            );
+        }
+        FuzzOperation::WithdrawAllTokenTypes { pool_token_amount } => {
+            check_withdraw_all_token_types(
+                &curve,
+                pool_token_amount as u128,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+            );
+        }
+    }
+}
+
+fn check_swap(
+    curve: &StableCurve,
+    source_amount: u128,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+) {
+    let result = match curve.swap_without_fees(
+        source_amount,
+        swap_source_amount,
+        swap_destination_amount,
+        TradeDirection::AtoB,
+    ) {
+        Some(result) => result,
+        None => return,
+    };
+    let mut model = StableSwapModel::new(
+        curve.amp.into(),
+        vec![swap_source_amount, swap_destination_amount],
+        N_COINS,
+    );
+    let model_amount = model.exchange(0, 1, source_amount);
+    let diff = (model_amount as i128 - result.destination_amount_swapped as i128).abs();
+    assert!(
+        diff <= 1,
+        "result={}, model_result={}, amp={}, source_amount={}, swap_source_amount={}, swap_destination_amount={}",
+        result.destination_amount_swapped,
+        model_amount,
+        curve.amp,
+        source_amount,
+        swap_source_amount,
+        swap_destination_amount
+    );
+}
+
+/// Invariant precision of the model, which stops iterating once the invariant
+/// moves by at most 1, or after 1001 iterations for very imbalanced pools
+const INVARIANT_TOLERANCE: u128 = 4;
+
+/// Pool tokens the model can be off by for `pool_token_amount`, from the error
+/// on the invariant before and after the operation
+fn pool_token_tolerance(pool_token_amount: u128, pool_token_supply: u128, d: u128) -> u128 {
+    (pool_token_supply + pool_token_amount) * 2 * INVARIANT_TOLERANCE / d + 1
+}
+
+fn check_deposit_all_token_types(
+    curve: &StableCurve,
+    pool_token_amount: u128,
+    pool_token_supply: u128,
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+) {
+    let result = match curve.pool_tokens_to_trading_tokens(
+        pool_token_amount,
+        pool_token_supply,
+        swap_token_a_amount,
+        swap_token_b_amount,
+        RoundDirection::Ceiling,
+    ) {
+        Some(result) => result,
+        None => return,
+    };
+    // rejected by the program, as for the quotes
+    if result.token_a_amount == 0
+        || result.token_b_amount == 0
+        || result.token_a_amount > u64::MAX as u128
+        || result.token_b_amount > u64::MAX as u128
+    {
+        return;
+    }
+    let model = StableSwapModel::new_with_pool_tokens(
+        curve.amp.into(),
+        vec![swap_token_a_amount, swap_token_b_amount],
+        N_COINS,
+        pool_token_supply,
+    );
+    // the deposited tokens are worth at least the pool tokens minted
+    let model_pool_token_amount =
+        model.calc_token_amount(&[result.token_a_amount, result.token_b_amount]);
+    let tolerance = pool_token_tolerance(pool_token_amount, pool_token_supply, model.d());
+    assert!(
+        model_pool_token_amount + tolerance >= pool_token_amount,
+        "pool_token_amount={}, model_pool_token_amount={}, amp={}, pool_token_supply={}, swap_token_a_amount={}, swap_token_b_amount={}",
+        pool_token_amount,
+        model_pool_token_amount,
+        curve.amp,
+        pool_token_supply,
+        swap_token_a_amount,
+        swap_token_b_amount
+    );
+}
+
+fn check_withdraw_all_token_types(
+    curve: &StableCurve,
+    pool_token_amount: u128,
+    pool_token_supply: u128,
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+) {
+    if pool_token_amount > pool_token_supply {
+        return;
+    }
+    let result = match curve.pool_tokens_to_trading_tokens(
+        pool_token_amount,
+        pool_token_supply,
+        swap_token_a_amount,
+        swap_token_b_amount,
+        RoundDirection::Floor,
+    ) {
+        Some(result) => result,
+        None => return,
+    };
+    if result.token_a_amount == 0 || result.token_b_amount == 0 {
+        return;
+    }
+    let model = StableSwapModel::new_with_pool_tokens(
+        curve.amp.into(),
+        vec![swap_token_a_amount, swap_token_b_amount],
+        N_COINS,
+        pool_token_supply,
+    );
+    // the withdrawn tokens are worth at most the pool tokens burned
+    let model_pool_token_amount =
+        model.remove_liquidity_imbalance(&[result.token_a_amount, result.token_b_amount]);
+    let tolerance = pool_token_tolerance(pool_token_amount, pool_token_supply, model.d());
+    assert!(
+        model_pool_token_amount <= pool_token_amount + tolerance,
+        "pool_token_amount={}, model_pool_token_amount={}, amp={}, pool_token_supply={}, swap_token_a_amount={}, swap_token_b_amount={}",
+        pool_token_amount,
+        model_pool_token_amount,
+        curve.amp,
+        pool_token_supply,
+        swap_token_a_amount,
+        swap_token_b_amount
+    );
+}
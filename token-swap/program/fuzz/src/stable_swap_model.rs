@@ -0,0 +1,314 @@
+//! Pure Rust model of Curve's StableSwap math, ported from the reference
+//! simulation at
+//! https://github.com/curvefi/curve-contract/blob/master/tests/simulation.py
+//!
+//! The model keeps the arbitrary precision of the Python integers by running
+//! all calculations in 256 bits, and panics if an intermediate value overflows.
+
+use spl_math::uint::U256;
+
+/// Target price of a token, 10**18 for tokens worth the same
+pub const DEFAULT_TARGET_PRICE: u128 = 1_000_000_000_000_000_000;
+/// Denominator of the model fee, 10**10 is a fee of 100%
+pub const FEE_DENOMINATOR: u128 = 10_000_000_000;
+
+/// Iterations after which Newton's method gives up converging, 1001 as in the
+/// reference simulation
+const MAX_ITERATIONS: usize = 1001;
+
+/// Model of a Curve pool
+pub struct StableSwapModel {
+    /// Amplification coefficient, A * n**(n - 1) since it is an invariant
+    pub amp_factor: u128,
+    /// Balances of the pool tokens
+    pub balances: Vec<u128>,
+    /// Number of tokens in the pool
+    pub n_coins: u8,
+    /// Trade fee, over `FEE_DENOMINATOR`
+    pub fee: u128,
+    /// Target prices of the tokens, in 10**18
+    pub target_prices: Vec<u128>,
+    /// Supply of pool tokens
+    pub pool_tokens: u128,
+}
+
+fn abs_diff(a: U256, b: U256) -> U256 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+fn to_u128(value: U256) -> u128 {
+    value.as_u128()
+}
+
+impl StableSwapModel {
+    /// Create a model of a pool with no fees and no pool tokens
+    pub fn new(amp_factor: u128, balances: Vec<u128>, n_coins: u8) -> Self {
+        Self::new_with_pool_tokens(amp_factor, balances, n_coins, 0)
+    }
+
+    /// Create a model of a pool with no fees and the given pool token supply
+    pub fn new_with_pool_tokens(
+        amp_factor: u128,
+        balances: Vec<u128>,
+        n_coins: u8,
+        pool_tokens: u128,
+    ) -> Self {
+        Self {
+            amp_factor,
+            balances,
+            n_coins,
+            fee: 0,
+            target_prices: vec![DEFAULT_TARGET_PRICE; n_coins as usize],
+            pool_tokens,
+        }
+    }
+
+    /// Balances of the pool, brought to the target prices
+    pub fn xp(&self) -> Vec<u128> {
+        self.xp_u256(&self.balances)
+            .into_iter()
+            .map(to_u128)
+            .collect()
+    }
+
+    /// Invariant of the pool
+    pub fn d(&self) -> u128 {
+        to_u128(self.d_u256(&self.balances))
+    }
+
+    /// New balance of token `j` if the balance of token `i` becomes `x`
+    pub fn y(&self, i: usize, j: usize, x: u128) -> u128 {
+        to_u128(self.y_u256(i, j, U256::from(x)))
+    }
+
+    /// Balance of token `i` for which the invariant of the pool becomes `d`
+    pub fn y_d(&self, i: usize, d: u128) -> u128 {
+        to_u128(self.y_d_u256(i, U256::from(d)))
+    }
+
+    /// Amount of token `j` received for `dx` of token `i`, without fees
+    pub fn dy(&self, i: usize, j: usize, dx: u128) -> u128 {
+        let xp = self.xp_u256(&self.balances);
+        to_u128(xp[j] - self.y_u256(i, j, xp[i] + U256::from(dx)))
+    }
+
+    /// Trade `dx` of token `i` for token `j`, returning the amount received
+    /// after fees
+    pub fn exchange(&mut self, i: usize, j: usize, dx: u128) -> u128 {
+        let xp = self.xp_u256(&self.balances);
+        let x = xp[i] + U256::from(dx);
+        let y = self.y_u256(i, j, x);
+        let dy = xp[j] - y;
+        let fee = dy * U256::from(self.fee) / U256::from(FEE_DENOMINATOR);
+        if dy.is_zero() {
+            return 0;
+        }
+
+        let precision = U256::from(DEFAULT_TARGET_PRICE);
+        self.balances[i] = to_u128(x * precision / U256::from(self.target_prices[i]));
+        self.balances[j] = to_u128((y + fee) * precision / U256::from(self.target_prices[j]));
+        to_u128(dy - fee)
+    }
+
+    /// Pool tokens minted for depositing `amounts`, without fees.  Not part of
+    /// the reference simulation, this follows `calc_token_amount` of the Curve
+    /// contracts.
+    pub fn calc_token_amount(&self, amounts: &[u128]) -> u128 {
+        let d0 = self.d_u256(&self.balances);
+        let new_balances = self
+            .balances
+            .iter()
+            .zip(amounts)
+            .map(|(balance, amount)| balance + amount)
+            .collect::<Vec<_>>();
+        let d1 = self.d_u256(&new_balances);
+        to_u128((d1 - d0) * U256::from(self.pool_tokens) / d0)
+    }
+
+    /// Pool tokens burned for withdrawing `amounts`
+    pub fn remove_liquidity_imbalance(&self, amounts: &[u128]) -> u128 {
+        let n = U256::from(self.n_coins);
+        let fee = U256::from(self.fee) * n / (U256::from(4) * (n - U256::one()));
+        let fee_denominator = U256::from(FEE_DENOMINATOR);
+
+        let d0 = self.d_u256(&self.balances);
+        let mut new_balances = self
+            .balances
+            .iter()
+            .zip(amounts)
+            .map(|(balance, amount)| balance - amount)
+            .collect::<Vec<_>>();
+        let d1 = self.d_u256(&new_balances);
+        for (old_balance, new_balance) in self.balances.iter().zip(new_balances.iter_mut()) {
+            let ideal_balance = d1 * U256::from(*old_balance) / d0;
+            let difference = abs_diff(ideal_balance, U256::from(*new_balance));
+            *new_balance -= to_u128(fee * difference / fee_denominator);
+        }
+        let d2 = self.d_u256(&new_balances);
+
+        to_u128((d0 - d2) * U256::from(self.pool_tokens) / d0)
+    }
+
+    /// Amount of token `i` received for burning `token_amount` pool tokens
+    pub fn calc_withdraw_one_coin(&self, token_amount: u128, i: usize) -> u128 {
+        let xp = self.xp_u256(&self.balances);
+        let fee = if self.fee > 0 {
+            let fee = U256::from(self.fee);
+            let sum_x = xp.iter().fold(U256::zero(), |sum, x| sum + *x);
+            fee - fee * xp[i] / sum_x + U256::from(500_000)
+        } else {
+            U256::zero()
+        };
+
+        let d0 = self.d_u256(&self.balances);
+        let d1 = d0 - U256::from(token_amount) * d0 / U256::from(self.pool_tokens);
+        let dy = xp[i] - self.y_d_u256(i, d1);
+
+        to_u128(dy - dy * fee / U256::from(FEE_DENOMINATOR))
+    }
+
+    fn xp_u256(&self, balances: &[u128]) -> Vec<U256> {
+        let precision = U256::from(DEFAULT_TARGET_PRICE);
+        balances
+            .iter()
+            .zip(&self.target_prices)
+            .map(|(x, p)| U256::from(*x) * U256::from(*p) / precision)
+            .collect()
+    }
+
+    /// D invariant calculation in non-overflowing integer operations
+    /// iteratively
+    ///
+    /// A * sum(x_i) * n**n + D = A * D * n**n + D**(n+1) / (n**n * prod(x_i))
+    ///
+    /// Converging solution:
+    /// D[j+1] = (A * n**n * sum(x_i) - D[j]**(n+1) / (n**n prod(x_i))) / (A * n**n - 1)
+    fn d_u256(&self, balances: &[u128]) -> U256 {
+        let n = U256::from(self.n_coins);
+        let xp = self.xp_u256(balances);
+        let sum_x = xp.iter().fold(U256::zero(), |sum, x| sum + *x);
+        let ann = U256::from(self.amp_factor) * n;
+
+        let mut d_prev = U256::zero();
+        let mut d = sum_x;
+        for _ in 0..MAX_ITERATIONS {
+            if abs_diff(d, d_prev) <= U256::one() {
+                break;
+            }
+            let mut d_p = d;
+            for x in &xp {
+                d_p = d_p * d / (n * *x);
+            }
+            d_prev = d;
+            d = (ann * sum_x + d_p * n) * d / ((ann - U256::one()) * d + (n + U256::one()) * d_p);
+        }
+        d
+    }
+
+    /// Calculate x[j] if one makes x[i] = x
+    ///
+    /// Done by solving quadratic equation iteratively.
+    /// x_1**2 + x1 * (sum' - (A*n**n - 1) * D / (A * n**n)) = D ** (n + 1) / (n ** (2 * n) * prod' * A)
+    /// x_1**2 + b*x_1 = c
+    ///
+    /// x_1 = (x_1**2 + c) / (2*x_1 + b)
+    fn y_u256(&self, i: usize, j: usize, x: U256) -> U256 {
+        let d = self.d_u256(&self.balances);
+        let mut xx = self.xp_u256(&self.balances);
+        xx[i] = x;
+        xx.remove(j);
+        self.solve_y(&xx, d)
+    }
+
+    /// Calculate x[i] if one makes D = d, with the same quadratic equation
+    fn y_d_u256(&self, i: usize, d: U256) -> U256 {
+        let mut xx = self.xp_u256(&self.balances);
+        xx.remove(i);
+        self.solve_y(&xx, d)
+    }
+
+    /// Solve y**2 + b*y = c for the balances `xx` of the other tokens
+    fn solve_y(&self, xx: &[U256], d: U256) -> U256 {
+        let n = U256::from(self.n_coins);
+        let ann = U256::from(self.amp_factor) * n;
+        let mut c = d;
+        for y in xx {
+            c = c * d / (*y * n);
+        }
+        c = c * d / (n * ann);
+        // b = sum' + D / Ann - D may be negative, so D is only subtracted
+        // from the positive denominator
+        let b = xx.iter().fold(U256::zero(), |sum, x| sum + *x) + d / ann;
+
+        let mut y_prev = U256::zero();
+        let mut y = d;
+        for _ in 0..MAX_ITERATIONS {
+            if abs_diff(y, y_prev) <= U256::one() {
+                break;
+            }
+            y_prev = y;
+            y = (y * y + c) / (U256::from(2) * y + b - d);
+        }
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use spl_token_swap::curve::{
+        calculator::{CurveCalculator, TradeDirection},
+        stable::StableCurve,
+    };
+
+    const N_COINS: u8 = 2;
+
+    proptest! {
+        #[test]
+        fn constant_product_swap_no_fee(
+            swap_source_amount in 100..1_000_000_000_000_000_000u128,
+            swap_destination_amount in 100..1_000_000_000_000_000_000u128,
+            source_amount in 100..100_000_000_000u128,
+            amp in 1..150u64
+        ) {
+            prop_assume!(source_amount < swap_source_amount);
+
+            let curve = StableCurve { amp };
+
+            let mut model = StableSwapModel::new(
+                curve.amp.into(),
+                vec![swap_source_amount, swap_destination_amount],
+                N_COINS,
+            );
+
+            let result = curve.swap_without_fees(
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            );
+
+            let result = result.unwrap();
+            let model_result = model.exchange(0, 1, source_amount);
+
+            let diff =
+                (model_result as i128 - result.destination_amount_swapped as i128).abs();
+
+            assert!(
+                diff <= 1,
+                "result={}, model_result={}, amp={}, source_amount={}, swap_source_amount={}, swap_destination_amount={}",
+                result.destination_amount_swapped,
+                model_result,
+                amp,
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount
+            );
+        }
+    }
+}
@@ -3,6 +3,7 @@
 use crate::{
     curve::{
         base::{CurveType, SwapCurve},
+        dynamic_fee::DynamicFeeParameters,
         fees::Fees,
     },
     error::SwapError,
@@ -31,11 +32,13 @@ pub struct SwapConstraints<'a> {
 impl<'a> SwapConstraints<'a> {
     /// Checks that the provided curve is valid for the given constraints
     pub fn validate_curve(&self, swap_curve: &SwapCurve) -> Result<(), ProgramError> {
-        if self
-            .valid_curve_types
-            .iter()
-            .any(|x| *x == swap_curve.curve_type)
-        {
+        self.validate_curve_type(swap_curve.curve_type)
+    }
+
+    /// Checks that the provided curve type is valid for the given constraints,
+    /// used by pools which do not hold a SwapCurve
+    pub fn validate_curve_type(&self, curve_type: CurveType) -> Result<(), ProgramError> {
+        if self.valid_curve_types.iter().any(|x| *x == curve_type) {
             Ok(())
         } else {
             Err(SwapError::UnsupportedCurveType.into())
@@ -50,6 +53,26 @@ impl<'a> SwapConstraints<'a> {
             Err(SwapError::InvalidFee.into())
         }
     }
+
+    /// Checks that a dynamic trade fee never goes below the trade fee of the
+    /// constraints, so that it can only raise the fee paid by traders
+    pub fn validate_dynamic_fee(
+        &self,
+        parameters: &DynamicFeeParameters,
+    ) -> Result<(), ProgramError> {
+        if !parameters.is_enabled() {
+            return Ok(());
+        }
+        let min_fee =
+            u128::from(parameters.min_fee_numerator) * u128::from(self.fees.trade_fee_denominator);
+        let constrained_fee =
+            u128::from(self.fees.trade_fee_numerator) * u128::from(parameters.fee_denominator);
+        if min_fee >= constrained_fee {
+            Ok(())
+        } else {
+            Err(SwapError::InvalidFee.into())
+        }
+    }
 }
 
 #[cfg(feature = "production")]
@@ -166,4 +189,45 @@ mod tests {
             constraints.validate_curve(&swap_curve),
         );
     }
+
+    #[test]
+    fn validate_dynamic_fee() {
+        let fees = Fees {
+            trade_fee_numerator: 25,
+            trade_fee_denominator: 10_000,
+            ..Fees::default()
+        };
+        let constraints = SwapConstraints {
+            owner_key: "",
+            valid_curve_types: &[CurveType::ConstantProduct],
+            fees: &fees,
+        };
+        let parameters = DynamicFeeParameters {
+            min_fee_numerator: 25,
+            max_fee_numerator: 100,
+            fee_denominator: 10_000,
+            volatility_multiplier: 5_000,
+            decay_period: 100,
+        };
+
+        constraints.validate_dynamic_fee(&parameters).unwrap();
+        constraints
+            .validate_dynamic_fee(&DynamicFeeParameters::default())
+            .unwrap();
+        constraints
+            .validate_dynamic_fee(&DynamicFeeParameters {
+                min_fee_numerator: 3,
+                max_fee_numerator: 10,
+                fee_denominator: 1_000,
+                ..parameters
+            })
+            .unwrap();
+        assert_eq!(
+            Err(SwapError::InvalidFee.into()),
+            constraints.validate_dynamic_fee(&DynamicFeeParameters {
+                min_fee_numerator: 24,
+                ..parameters
+            }),
+        );
+    }
 }
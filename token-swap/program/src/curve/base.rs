@@ -7,11 +7,14 @@ use solana_program::{
 
 use crate::curve::{
     calculator::{CurveCalculator, RoundDirection, SwapWithoutFeesResult, TradeDirection},
+    concentrated_liquidity::ConcentratedLiquidityCurve,
     constant_price::ConstantPriceCurve,
     constant_product::ConstantProductCurve,
     fees::Fees,
     offset::OffsetCurve,
+    proactive_market_maker::ProactiveMarketMakerCurve,
     stable::StableCurve,
+    weighted::WeightedCurve,
 };
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use std::convert::{TryFrom, TryInto};
@@ -29,6 +32,15 @@ pub enum CurveType {
     Stable,
     /// Offset curve, like Uniswap, but the token B side has a faked offset
     Offset,
+    /// Concentrated liquidity, like Uniswap v3, where liquidity is provided
+    /// within price ranges
+    ConcentratedLiquidity,
+    /// Weighted product, like Balancer, invariant = token_a_amount ^ weight_a *
+    /// token_b_amount ^ weight_b
+    Weighted,
+    /// Proactive market maker, like DODO, trading around an oracle price with
+    /// a slippage parameter k
+    ProactiveMarketMaker,
 }
 
 /// Encodes all results of swapping from a source token to a destination token
@@ -115,11 +127,7 @@ impl SwapCurve {
         if source_amount == 0 {
             return Some(0);
         }
-        // Get the trading fee incurred if *half* the source amount is swapped
-        // for the other side. Reference at:
-        // https://github.com/balancer-labs/balancer-core/blob/f4ed5d65362a8d6cec21662fb6eae233b0babc1f/contracts/BMath.sol#L117
-        let half_source_amount = std::cmp::max(1, source_amount.checked_div(2)?);
-        let trade_fee = fees.trading_fee(half_source_amount)?;
+        let trade_fee = fees.single_token_trading_fee(source_amount)?;
         let source_amount = source_amount.checked_sub(trade_fee)?;
         self.calculator.trading_tokens_to_pool_tokens(
             source_amount,
@@ -194,6 +202,13 @@ impl Pack for SwapCurve {
                 }
                 CurveType::Stable => Box::new(StableCurve::unpack_from_slice(calculator)?),
                 CurveType::Offset => Box::new(OffsetCurve::unpack_from_slice(calculator)?),
+                CurveType::ConcentratedLiquidity => {
+                    Box::new(ConcentratedLiquidityCurve::unpack_from_slice(calculator)?)
+                }
+                CurveType::Weighted => Box::new(WeightedCurve::unpack_from_slice(calculator)?),
+                CurveType::ProactiveMarketMaker => {
+                    Box::new(ProactiveMarketMakerCurve::unpack_from_slice(calculator)?)
+                }
             },
         })
     }
@@ -224,6 +239,9 @@ impl TryFrom<u8> for CurveType {
             1 => Ok(CurveType::ConstantPrice),
             2 => Ok(CurveType::Stable),
             3 => Ok(CurveType::Offset),
+            4 => Ok(CurveType::ConcentratedLiquidity),
+            5 => Ok(CurveType::Weighted),
+            6 => Ok(CurveType::ProactiveMarketMaker),
             _ => Err(ProgramError::InvalidAccountData),
         }
     }
@@ -167,6 +167,17 @@ pub trait CurveCalculator: Debug + DynPack {
         swap_token_a_amount: u128,
         swap_token_b_amount: u128,
     ) -> Option<PreciseNumber>;
+
+    /// Calculates the spot price of token A, denominated in token B, given the
+    /// liquidity parameters.
+    ///
+    /// This is the marginal rate of an infinitely small trade, without fees,
+    /// and is used to accumulate the time-weighted price of the pool.
+    fn spot_price(
+        &self,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+    ) -> Option<PreciseNumber>;
 }
 
 /// Test helpers for curves
@@ -328,6 +339,56 @@ pub mod test {
         assert!(difference <= epsilon);
     }
 
+    /// Test function checking that a swap never gets a better rate than the
+    /// spot price of the curve.
+    ///
+    /// Since the spot price is the marginal rate of an infinitely small trade,
+    /// any real trade in either direction must receive at most the amount
+    /// implied by the spot price, within 1 token of rounding.
+    pub fn check_spot_price_from_swap(
+        curve: &dyn CurveCalculator,
+        source_token_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+    ) {
+        let spot_price = curve
+            .spot_price(swap_token_a_amount, swap_token_b_amount)
+            .unwrap();
+        let epsilon = PreciseNumber::new(1).unwrap();
+
+        // token A in, token B out: b_out <= a_in * price
+        let results = curve
+            .swap_without_fees(
+                source_token_amount,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::AtoB,
+            )
+            .unwrap();
+        let destination_amount = PreciseNumber::new(results.destination_amount_swapped).unwrap();
+        let source_value = PreciseNumber::new(results.source_amount_swapped)
+            .unwrap()
+            .checked_mul(&spot_price)
+            .unwrap();
+        assert!(destination_amount.less_than_or_equal(&source_value.checked_add(&epsilon).unwrap()));
+
+        // token B in, token A out: a_out * price <= b_in
+        let results = curve
+            .swap_without_fees(
+                source_token_amount,
+                swap_token_b_amount,
+                swap_token_a_amount,
+                TradeDirection::BtoA,
+            )
+            .unwrap();
+        let destination_value = PreciseNumber::new(results.destination_amount_swapped)
+            .unwrap()
+            .checked_mul(&spot_price)
+            .unwrap();
+        let source_amount = PreciseNumber::new(results.source_amount_swapped).unwrap();
+        assert!(destination_value.less_than_or_equal(&source_amount.checked_add(&epsilon).unwrap()));
+    }
+
     /// Test function checking that a deposit never reduces the value of pool
     /// tokens.
     ///
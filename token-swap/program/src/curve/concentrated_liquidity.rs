@@ -0,0 +1,1437 @@
+//! Concentrated liquidity curve, where liquidity is provided within price ranges
+//!
+//! Liquidity providers open positions between two ticks, and their liquidity
+//! is only used to trade while the price of the pool is within that range.
+//! The price of the pool is tracked as the square root of the price of token
+//! A, denominated in token B, using unsigned Q64.64 fixed-point numbers.  The
+//! price at tick `i` is `1.0001 ^ i`.
+//!
+//! Since liquidity is not fungible across ranges, the pool keeps its state in
+//! a `ConcentratedLiquidityState` stored after the swap state, and liquidity
+//! providers hold `Position` accounts instead of pool tokens.
+//!
+//! The ticks all live in the pool state, which can only hold `MAX_TICKS` of
+//! them, so every open position must hold at least the minimum liquidity of
+//! the pool.  This makes taking up the whole tick table as expensive as
+//! providing that much liquidity on every range.
+
+use {
+    crate::{
+        curve::{
+            base::SwapResult,
+            calculator::{
+                CurveCalculator, DynPack, RoundDirection, SwapWithoutFeesResult, TradeDirection,
+                TradingTokenResult,
+            },
+            fees::Fees,
+        },
+        error::SwapError,
+    },
+    arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
+    solana_program::{
+        program_error::ProgramError,
+        program_pack::{IsInitialized, Pack, Sealed},
+        pubkey::Pubkey,
+    },
+    spl_math::{
+        precise_number::{PreciseNumber, ONE},
+        uint::U256,
+    },
+    std::convert::TryFrom,
+};
+
+/// Lowest tick supported, where the square root price still fits in a Q64.64
+pub const MIN_TICK: i32 = -443_636;
+/// Highest tick supported, where the square root price still fits in a Q64.64
+pub const MAX_TICK: i32 = 443_636;
+/// Largest spacing allowed between initializable ticks
+pub const MAX_TICK_SPACING: u16 = 16_384;
+/// Maximum number of initialized ticks a pool can track.  Once they are all
+/// in use, positions can only be opened on ranges bounded by existing ticks.
+pub const MAX_TICKS: usize = 64;
+
+/// Number of fractional bits of the Q64.64 square root prices
+const PRICE_FRACTIONAL_BITS: usize = 64;
+
+/// `2^128 / sqrt(1.0001) ^ (2 ^ i)`, to compute square root prices from ticks
+const TICK_RATIOS: [u128; 20] = [
+    0xfffcb933bd6fad37aa2d162d1a594001,
+    0xfff97272373d413259a46990580e2139,
+    0xfff2e50f5f656932ef12357cf3c7fdcb,
+    0xffe5caca7e10e4e61c3624eaa0941ccf,
+    0xffcb9843d60f6159c9db58835c926643,
+    0xff973b41fa98c081472e6896dfb254bf,
+    0xff2ea16466c96a3843ec78b326b52860,
+    0xfe5dee046a99a2a811c461f1969c3052,
+    0xfcbe86c7900a88aedcffc83b479aa3a3,
+    0xf987a7253ac413176f2b074cf7815e53,
+    0xf3392b0822b70005940c7a398e4b70f2,
+    0xe7159475a2c29b7443b29c7fa6e889d8,
+    0xd097f3bdfd2022b8845ad8f792aa5825,
+    0xa9f746462d870fdf8a65dc1f90e061e4,
+    0x70d869a156d2a1b890bb3df62baf32f6,
+    0x31be135f97d08fd981231505542fcfa5,
+    0x09aa508b5b7a84e1c677de54f3e99bc8,
+    0x005d6af8dedb81196699c329225ee604,
+    0x00002216e584f5fa1ea926041bedfe97,
+    0x00000000048a170391f7dc42444e8fa2,
+];
+
+/// Concentrated liquidity curve.  Swaps, deposits and withdrawals go through
+/// the ticks and positions of the pool, so the calculator only holds the
+/// parameters of the pool.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConcentratedLiquidityCurve {
+    /// Positions can only start and end on multiples of the tick spacing
+    pub tick_spacing: u16,
+    /// Smallest liquidity a position can hold, unless it holds none
+    pub min_position_liquidity: u128,
+}
+
+impl ConcentratedLiquidityCurve {
+    /// Check that a position range is ordered, within bounds and aligned on
+    /// the tick spacing
+    pub fn validate_tick_range(&self, tick_lower: i32, tick_upper: i32) -> Result<(), SwapError> {
+        let tick_spacing = i32::from(self.tick_spacing);
+        if tick_lower >= tick_upper
+            || tick_lower < MIN_TICK
+            || tick_upper > MAX_TICK
+            || tick_lower % tick_spacing != 0
+            || tick_upper % tick_spacing != 0
+        {
+            Err(SwapError::InvalidTickRange)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check that a position holds either no liquidity or at least the
+    /// minimum liquidity of the pool
+    pub fn validate_position_liquidity(&self, liquidity: u128) -> Result<(), SwapError> {
+        if liquidity != 0 && liquidity < self.min_position_liquidity {
+            Err(SwapError::PositionLiquidityTooLow)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Owner trade fees are paid in pool tokens, which concentrated liquidity
+/// pools do not have, so only the trade fee can be charged
+pub fn validate_fees(fees: &Fees) -> Result<(), SwapError> {
+    if fees.owner_trade_fee_numerator != 0 {
+        Err(SwapError::InvalidFee)
+    } else {
+        Ok(())
+    }
+}
+
+impl CurveCalculator for ConcentratedLiquidityCurve {
+    /// Swaps depend on the liquidity of every range, see
+    /// `ConcentratedLiquidityState::swap`
+    fn swap_without_fees(
+        &self,
+        _source_amount: u128,
+        _swap_source_amount: u128,
+        _swap_destination_amount: u128,
+        _trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        None
+    }
+
+    /// Liquidity is held in positions, no pool tokens are minted
+    fn new_pool_supply(&self) -> u128 {
+        0
+    }
+
+    /// Liquidity is held in positions instead of pool tokens
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        _pool_tokens: u128,
+        _pool_token_supply: u128,
+        _swap_token_a_amount: u128,
+        _swap_token_b_amount: u128,
+        _round_direction: RoundDirection,
+    ) -> Option<TradingTokenResult> {
+        None
+    }
+
+    /// Liquidity is held in positions instead of pool tokens
+    fn trading_tokens_to_pool_tokens(
+        &self,
+        _source_amount: u128,
+        _swap_token_a_amount: u128,
+        _swap_token_b_amount: u128,
+        _pool_supply: u128,
+        _trade_direction: TradeDirection,
+        _round_direction: RoundDirection,
+    ) -> Option<u128> {
+        None
+    }
+
+    fn validate(&self) -> Result<(), SwapError> {
+        if self.tick_spacing == 0
+            || self.tick_spacing > MAX_TICK_SPACING
+            || self.min_position_liquidity == 0
+        {
+            Err(SwapError::InvalidCurve)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Deposits go through positions
+    fn allows_deposits(&self) -> bool {
+        false
+    }
+
+    /// The token amounts held by the swap do not reflect the liquidity of the
+    /// pool
+    fn normalized_value(
+        &self,
+        _swap_token_a_amount: u128,
+        _swap_token_b_amount: u128,
+    ) -> Option<PreciseNumber> {
+        None
+    }
+
+    /// The price is tracked in the pool state, see
+    /// `ConcentratedLiquidityState::spot_price`
+    fn spot_price(
+        &self,
+        _swap_token_a_amount: u128,
+        _swap_token_b_amount: u128,
+    ) -> Option<PreciseNumber> {
+        None
+    }
+}
+
+/// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
+impl IsInitialized for ConcentratedLiquidityCurve {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+impl Sealed for ConcentratedLiquidityCurve {}
+impl Pack for ConcentratedLiquidityCurve {
+    const LEN: usize = 18;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        (self as &dyn DynPack).pack_into_slice(output);
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<ConcentratedLiquidityCurve, ProgramError> {
+        let input = array_ref![input, 0, 18];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (tick_spacing, min_position_liquidity) = array_refs![input, 2, 16];
+        Ok(Self {
+            tick_spacing: u16::from_le_bytes(*tick_spacing),
+            min_position_liquidity: u128::from_le_bytes(*min_position_liquidity),
+        })
+    }
+}
+
+impl DynPack for ConcentratedLiquidityCurve {
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 18];
+        let (tick_spacing, min_position_liquidity) = mut_array_refs![output, 2, 16];
+        *tick_spacing = self.tick_spacing.to_le_bytes();
+        *min_position_liquidity = self.min_position_liquidity.to_le_bytes();
+    }
+}
+
+/// Square root price at a tick, as a Q64.64
+pub fn sqrt_price_at_tick(tick: i32) -> Option<u128> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return None;
+    }
+    let abs_tick = u64::try_from(i64::from(tick).abs()).ok()?;
+    let mut ratio = if abs_tick & 1 != 0 {
+        U256::from(TICK_RATIOS[0])
+    } else {
+        U256::one() << 128
+    };
+    for (bit, tick_ratio) in TICK_RATIOS.iter().enumerate().skip(1) {
+        if abs_tick & (1 << bit) != 0 {
+            ratio = ratio.checked_mul(U256::from(*tick_ratio))? >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+    // Q128.128 to Q64.64, rounding up so that the price of a tick is never
+    // below the real one
+    let remainder = ratio % (U256::one() << PRICE_FRACTIONAL_BITS);
+    let sqrt_price = ratio >> PRICE_FRACTIONAL_BITS;
+    let sqrt_price = if remainder.is_zero() {
+        sqrt_price
+    } else {
+        sqrt_price.checked_add(U256::one())?
+    };
+    u128::try_from(sqrt_price).ok()
+}
+
+/// Greatest tick whose square root price is lower or equal to the given one
+pub fn tick_at_sqrt_price(sqrt_price: u128) -> Option<i32> {
+    if sqrt_price < sqrt_price_at_tick(MIN_TICK)? || sqrt_price > sqrt_price_at_tick(MAX_TICK)? {
+        return None;
+    }
+    let mut low = MIN_TICK;
+    let mut high = MAX_TICK;
+    while low < high {
+        let middle = low + (high - low + 1) / 2;
+        if sqrt_price_at_tick(middle)? <= sqrt_price {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+    Some(low)
+}
+
+/// Square root price of token A given the amounts of tokens A and B
+pub fn sqrt_price_from_amounts(token_a_amount: u128, token_b_amount: u128) -> Option<u128> {
+    let price = (U256::from(token_b_amount) << (2 * PRICE_FRACTIONAL_BITS))
+        .checked_div(U256::from(token_a_amount))?;
+    u128::try_from(integer_sqrt(price)).ok()
+}
+
+/// Largest integer whose square is lower or equal to the value, using
+/// Newton's method from an overestimate
+pub fn integer_sqrt(value: U256) -> U256 {
+    if value.is_zero() {
+        return value;
+    }
+    let mut root = U256::one() << (value.bits() / 2 + 1);
+    loop {
+        let next = (root + value / root) >> 1;
+        if next >= root {
+            return root;
+        }
+        root = next;
+    }
+}
+
+/// Divide two numbers, rounding the quotient up if requested
+pub fn div_round(numerator: U256, denominator: U256, round_up: bool) -> Option<U256> {
+    let quotient = numerator.checked_div(denominator)?;
+    if round_up && !(numerator % denominator).is_zero() {
+        quotient.checked_add(U256::one())
+    } else {
+        Some(quotient)
+    }
+}
+
+fn sorted(sqrt_price_0: u128, sqrt_price_1: u128) -> (u128, u128) {
+    if sqrt_price_0 > sqrt_price_1 {
+        (sqrt_price_1, sqrt_price_0)
+    } else {
+        (sqrt_price_0, sqrt_price_1)
+    }
+}
+
+/// Amount of token A provided by `liquidity` between two prices:
+/// `liquidity * (1 / lower - 1 / upper)`
+pub fn token_a_delta(
+    sqrt_price_0: u128,
+    sqrt_price_1: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Option<u128> {
+    let (lower, upper) = sorted(sqrt_price_0, sqrt_price_1);
+    if lower == 0 {
+        return None;
+    }
+    let numerator = U256::from(liquidity) << PRICE_FRACTIONAL_BITS;
+    let numerator = numerator.checked_mul(U256::from(upper - lower))?;
+    let amount = div_round(numerator, U256::from(upper), round_up)?;
+    let amount = div_round(amount, U256::from(lower), round_up)?;
+    u128::try_from(amount).ok()
+}
+
+/// Amount of token B provided by `liquidity` between two prices:
+/// `liquidity * (upper - lower)`
+pub fn token_b_delta(
+    sqrt_price_0: u128,
+    sqrt_price_1: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Option<u128> {
+    let (lower, upper) = sorted(sqrt_price_0, sqrt_price_1);
+    let amount = U256::from(liquidity).checked_mul(U256::from(upper - lower))?;
+    let amount = div_round(amount, U256::one() << PRICE_FRACTIONAL_BITS, round_up)?;
+    u128::try_from(amount).ok()
+}
+
+/// Price after adding token A, rounded up so that the pool never gives out
+/// too much of token B: `liquidity * price / (liquidity + amount * price)`
+fn sqrt_price_from_token_a_input(sqrt_price: u128, liquidity: u128, amount: u128) -> Option<u128> {
+    if amount == 0 {
+        return Some(sqrt_price);
+    }
+    let numerator = U256::from(liquidity) << PRICE_FRACTIONAL_BITS;
+    let sqrt_price = U256::from(sqrt_price);
+    let next = match U256::from(amount)
+        .checked_mul(sqrt_price)
+        .and_then(|product| numerator.checked_add(product))
+        .and_then(|denominator| Some((numerator.checked_mul(sqrt_price)?, denominator)))
+    {
+        Some((numerator, denominator)) => div_round(numerator, denominator, true)?,
+        // less precise, but cannot overflow: liquidity / (liquidity / price + amount)
+        None => div_round(
+            numerator,
+            numerator
+                .checked_div(sqrt_price)?
+                .checked_add(U256::from(amount))?,
+            true,
+        )?,
+    };
+    u128::try_from(next).ok()
+}
+
+/// Price after adding token B, rounded down so that the pool never gives out
+/// too much of token A: `price + amount / liquidity`
+fn sqrt_price_from_token_b_input(sqrt_price: u128, liquidity: u128, amount: u128) -> Option<u128> {
+    let delta = (U256::from(amount) << PRICE_FRACTIONAL_BITS).checked_div(U256::from(liquidity))?;
+    u128::try_from(U256::from(sqrt_price).checked_add(delta)?).ok()
+}
+
+/// Result of swapping within a single range of constant liquidity
+#[derive(Debug, PartialEq)]
+pub struct SwapStep {
+    /// Square root price after the step
+    pub sqrt_price: u128,
+    /// Amount of source token added to the pool, without fees
+    pub amount_in: u128,
+    /// Amount of destination token taken out of the pool
+    pub amount_out: u128,
+    /// Amount of source token taken as trade fee
+    pub fee: u128,
+}
+
+/// Swap at most `amount_remaining`, fees included, moving the price towards
+/// `sqrt_price_target` without crossing it.
+pub fn swap_step(
+    sqrt_price: u128,
+    sqrt_price_target: u128,
+    liquidity: u128,
+    amount_remaining: u128,
+    fee_numerator: u128,
+    fee_denominator: u128,
+) -> Option<SwapStep> {
+    let a_to_b = sqrt_price_target <= sqrt_price;
+    let amount_remaining_less_fee = if fee_numerator == 0 {
+        amount_remaining
+    } else {
+        let amount = U256::from(amount_remaining)
+            .checked_mul(U256::from(fee_denominator.checked_sub(fee_numerator)?))?
+            .checked_div(U256::from(fee_denominator))?;
+        u128::try_from(amount).ok()?
+    };
+    let amount_in_to_target = if a_to_b {
+        token_a_delta(sqrt_price_target, sqrt_price, liquidity, true)?
+    } else {
+        token_b_delta(sqrt_price, sqrt_price_target, liquidity, true)?
+    };
+    let reached_target = amount_remaining_less_fee >= amount_in_to_target;
+    let next_sqrt_price = if reached_target {
+        sqrt_price_target
+    } else if a_to_b {
+        sqrt_price_from_token_a_input(sqrt_price, liquidity, amount_remaining_less_fee)?
+    } else {
+        sqrt_price_from_token_b_input(sqrt_price, liquidity, amount_remaining_less_fee)?
+    };
+    let (amount_in, amount_out) = if a_to_b {
+        (
+            token_a_delta(next_sqrt_price, sqrt_price, liquidity, true)?,
+            token_b_delta(next_sqrt_price, sqrt_price, liquidity, false)?,
+        )
+    } else {
+        (
+            token_b_delta(sqrt_price, next_sqrt_price, liquidity, true)?,
+            token_a_delta(sqrt_price, next_sqrt_price, liquidity, false)?,
+        )
+    };
+    let fee = if !reached_target {
+        // whatever is left after moving the price is kept by the pool
+        amount_remaining.checked_sub(amount_in)?
+    } else if fee_numerator == 0 {
+        0
+    } else {
+        let fee = div_round(
+            U256::from(amount_in).checked_mul(U256::from(fee_numerator))?,
+            U256::from(fee_denominator.checked_sub(fee_numerator)?),
+            true,
+        )?;
+        u128::try_from(fee).ok()?
+    };
+    Some(SwapStep {
+        sqrt_price: next_sqrt_price,
+        amount_in,
+        amount_out,
+        fee,
+    })
+}
+
+fn add_liquidity_delta(liquidity: u128, liquidity_delta: i128) -> Option<u128> {
+    if liquidity_delta < 0 {
+        liquidity.checked_sub(u128::try_from(liquidity_delta.checked_neg()?).ok()?)
+    } else {
+        liquidity.checked_add(u128::try_from(liquidity_delta).ok()?)
+    }
+}
+
+/// Fees earned per unit of liquidity, as a Q64.64
+fn fee_growth(fee: u128, liquidity: u128) -> Option<u128> {
+    let growth = (U256::from(fee) << PRICE_FRACTIONAL_BITS).checked_div(U256::from(liquidity))?;
+    u128::try_from(growth).ok()
+}
+
+/// Initialized tick, where the liquidity of the pool changes
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tick {
+    /// Index of the tick
+    pub index: i32,
+    /// Total liquidity of the positions using the tick as a bound
+    pub liquidity_gross: u128,
+    /// Liquidity added to the pool when the price crosses the tick upwards
+    pub liquidity_net: i128,
+    /// Token A fees earned per unit of liquidity on the other side of the
+    /// tick from the current price
+    pub fee_growth_outside_a: u128,
+    /// Token B fees earned per unit of liquidity on the other side of the
+    /// tick from the current price
+    pub fee_growth_outside_b: u128,
+}
+
+impl Tick {
+    const LEN: usize = 68;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 68];
+        let (index, liquidity_gross, liquidity_net, fee_growth_outside_a, fee_growth_outside_b) =
+            mut_array_refs![output, 4, 16, 16, 16, 16];
+        *index = self.index.to_le_bytes();
+        *liquidity_gross = self.liquidity_gross.to_le_bytes();
+        *liquidity_net = self.liquidity_net.to_le_bytes();
+        *fee_growth_outside_a = self.fee_growth_outside_a.to_le_bytes();
+        *fee_growth_outside_b = self.fee_growth_outside_b.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Self {
+        let input = array_ref![input, 0, 68];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (index, liquidity_gross, liquidity_net, fee_growth_outside_a, fee_growth_outside_b) =
+            array_refs![input, 4, 16, 16, 16, 16];
+        Self {
+            index: i32::from_le_bytes(*index),
+            liquidity_gross: u128::from_le_bytes(*liquidity_gross),
+            liquidity_net: i128::from_le_bytes(*liquidity_net),
+            fee_growth_outside_a: u128::from_le_bytes(*fee_growth_outside_a),
+            fee_growth_outside_b: u128::from_le_bytes(*fee_growth_outside_b),
+        }
+    }
+}
+
+/// State of a concentrated liquidity pool, stored after the swap state
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConcentratedLiquidityState {
+    /// Square root of the price of token A, denominated in token B, as a Q64.64
+    pub sqrt_price: u128,
+    /// Greatest tick whose price is lower or equal to the current price
+    pub tick_current: i32,
+    /// Liquidity of the positions in range
+    pub liquidity: u128,
+    /// Token A fees earned per unit of liquidity since the pool was created,
+    /// as a Q64.64 which is expected to wrap around
+    pub fee_growth_global_a: u128,
+    /// Token B fees earned per unit of liquidity since the pool was created,
+    /// as a Q64.64 which is expected to wrap around
+    pub fee_growth_global_b: u128,
+    /// Initialized ticks, sorted by index
+    pub ticks: Vec<Tick>,
+}
+
+impl ConcentratedLiquidityState {
+    /// Create the state of a pool without liquidity at the given price
+    pub fn new(sqrt_price: u128) -> Option<Self> {
+        Some(Self {
+            sqrt_price,
+            tick_current: tick_at_sqrt_price(sqrt_price)?,
+            ..Self::default()
+        })
+    }
+
+    /// Price of token A, denominated in token B
+    pub fn spot_price(&self) -> Option<PreciseNumber> {
+        let sqrt_price = U256::from(self.sqrt_price);
+        let value = sqrt_price
+            .checked_mul(sqrt_price)?
+            .checked_mul(U256::from(ONE))?
+            >> (2 * PRICE_FRACTIONAL_BITS);
+        Some(PreciseNumber { value })
+    }
+
+    fn tick(&self, index: i32) -> Option<&Tick> {
+        self.ticks
+            .binary_search_by_key(&index, |tick| tick.index)
+            .ok()
+            .map(|position| &self.ticks[position])
+    }
+
+    /// Fees earned per unit of liquidity between two ticks, for tokens A and B
+    fn fee_growth_inside(&self, tick_lower: i32, tick_upper: i32) -> Option<(u128, u128)> {
+        let lower = self.tick(tick_lower)?;
+        let upper = self.tick(tick_upper)?;
+        let (below_a, below_b) = if self.tick_current >= tick_lower {
+            (lower.fee_growth_outside_a, lower.fee_growth_outside_b)
+        } else {
+            (
+                self.fee_growth_global_a
+                    .wrapping_sub(lower.fee_growth_outside_a),
+                self.fee_growth_global_b
+                    .wrapping_sub(lower.fee_growth_outside_b),
+            )
+        };
+        let (above_a, above_b) = if self.tick_current < tick_upper {
+            (upper.fee_growth_outside_a, upper.fee_growth_outside_b)
+        } else {
+            (
+                self.fee_growth_global_a
+                    .wrapping_sub(upper.fee_growth_outside_a),
+                self.fee_growth_global_b
+                    .wrapping_sub(upper.fee_growth_outside_b),
+            )
+        };
+        Some((
+            self.fee_growth_global_a
+                .wrapping_sub(below_a)
+                .wrapping_sub(above_a),
+            self.fee_growth_global_b
+                .wrapping_sub(below_b)
+                .wrapping_sub(above_b),
+        ))
+    }
+
+    /// Add liquidity to a tick, initializing it if needed
+    fn update_tick(
+        &mut self,
+        index: i32,
+        liquidity_delta: i128,
+        is_upper: bool,
+    ) -> Result<(), SwapError> {
+        let position = match self.ticks.binary_search_by_key(&index, |tick| tick.index) {
+            Ok(position) => position,
+            Err(position) => {
+                if self.ticks.len() >= MAX_TICKS {
+                    return Err(SwapError::TooManyTicks);
+                }
+                // by convention, all fees were earned below the tick
+                let (fee_growth_outside_a, fee_growth_outside_b) = if index <= self.tick_current {
+                    (self.fee_growth_global_a, self.fee_growth_global_b)
+                } else {
+                    (0, 0)
+                };
+                self.ticks.insert(
+                    position,
+                    Tick {
+                        index,
+                        fee_growth_outside_a,
+                        fee_growth_outside_b,
+                        ..Tick::default()
+                    },
+                );
+                position
+            }
+        };
+        let tick = &mut self.ticks[position];
+        tick.liquidity_gross = add_liquidity_delta(tick.liquidity_gross, liquidity_delta)
+            .ok_or(SwapError::CalculationFailure)?;
+        tick.liquidity_net = if is_upper {
+            tick.liquidity_net.checked_sub(liquidity_delta)
+        } else {
+            tick.liquidity_net.checked_add(liquidity_delta)
+        }
+        .ok_or(SwapError::CalculationFailure)?;
+        Ok(())
+    }
+
+    /// Add `liquidity_delta` to a position, which may be negative to remove
+    /// liquidity.  Fees earned by the position so far are added to its fees
+    /// owed.  Returns the amounts of tokens A and B to deposit, or to
+    /// withdraw if the delta is negative.
+    pub fn update_position(
+        &mut self,
+        position: &mut Position,
+        liquidity_delta: i128,
+    ) -> Result<(u128, u128), SwapError> {
+        let tick_lower = position.tick_lower;
+        let tick_upper = position.tick_upper;
+        let liquidity = add_liquidity_delta(position.liquidity, liquidity_delta)
+            .ok_or(SwapError::InsufficientLiquidity)?;
+
+        if liquidity_delta != 0 {
+            self.update_tick(tick_lower, liquidity_delta, false)?;
+            self.update_tick(tick_upper, liquidity_delta, true)?;
+        }
+        let (fee_growth_inside_a, fee_growth_inside_b) = self
+            .fee_growth_inside(tick_lower, tick_upper)
+            .ok_or(SwapError::CalculationFailure)?;
+        position.collect_fees(fee_growth_inside_a, fee_growth_inside_b)?;
+        position.liquidity = liquidity;
+        self.ticks.retain(|tick| tick.liquidity_gross != 0);
+
+        let sqrt_price_lower = sqrt_price_at_tick(tick_lower).ok_or(SwapError::InvalidTickRange)?;
+        let sqrt_price_upper = sqrt_price_at_tick(tick_upper).ok_or(SwapError::InvalidTickRange)?;
+        // round in favor of the pool
+        let round_up = liquidity_delta > 0;
+        let liquidity_delta_abs = add_liquidity_delta(0, liquidity_delta)
+            .or_else(|| add_liquidity_delta(0, liquidity_delta.checked_neg()?))
+            .ok_or(SwapError::CalculationFailure)?;
+        let (token_a_amount, token_b_amount) = if self.tick_current < tick_lower {
+            (
+                token_a_delta(
+                    sqrt_price_lower,
+                    sqrt_price_upper,
+                    liquidity_delta_abs,
+                    round_up,
+                ),
+                Some(0),
+            )
+        } else if self.tick_current < tick_upper {
+            self.liquidity = add_liquidity_delta(self.liquidity, liquidity_delta)
+                .ok_or(SwapError::CalculationFailure)?;
+            (
+                token_a_delta(
+                    self.sqrt_price,
+                    sqrt_price_upper,
+                    liquidity_delta_abs,
+                    round_up,
+                ),
+                token_b_delta(
+                    sqrt_price_lower,
+                    self.sqrt_price,
+                    liquidity_delta_abs,
+                    round_up,
+                ),
+            )
+        } else {
+            (
+                Some(0),
+                token_b_delta(
+                    sqrt_price_lower,
+                    sqrt_price_upper,
+                    liquidity_delta_abs,
+                    round_up,
+                ),
+            )
+        };
+        Ok((
+            token_a_amount.ok_or(SwapError::CalculationFailure)?,
+            token_b_amount.ok_or(SwapError::CalculationFailure)?,
+        ))
+    }
+
+    /// Swap `source_amount` through the ranges of the pool, crossing ticks as
+    /// the price moves.  If the price reaches the end of the supported range,
+    /// only part of the source amount is swapped.
+    pub fn swap(
+        &mut self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+        fees: &Fees,
+    ) -> Option<SwapResult> {
+        let a_to_b = trade_direction == TradeDirection::AtoB;
+        let fee_numerator = u128::from(fees.trade_fee_numerator);
+        let fee_denominator = u128::from(fees.trade_fee_denominator);
+        let mut amount_remaining = source_amount;
+        let mut destination_amount_swapped = 0u128;
+        let mut trade_fee = 0u128;
+
+        while amount_remaining > 0 {
+            let next_tick = if a_to_b {
+                self.ticks
+                    .iter()
+                    .rev()
+                    .find(|tick| tick.index <= self.tick_current)
+            } else {
+                self.ticks
+                    .iter()
+                    .find(|tick| tick.index > self.tick_current)
+            }
+            .map(|tick| tick.index);
+            let target_tick = next_tick.unwrap_or(if a_to_b { MIN_TICK } else { MAX_TICK });
+            let sqrt_price_target = sqrt_price_at_tick(target_tick)?;
+
+            let step = swap_step(
+                self.sqrt_price,
+                sqrt_price_target,
+                self.liquidity,
+                amount_remaining,
+                fee_numerator,
+                fee_denominator,
+            )?;
+            amount_remaining = amount_remaining
+                .checked_sub(step.amount_in)?
+                .checked_sub(step.fee)?;
+            destination_amount_swapped = destination_amount_swapped.checked_add(step.amount_out)?;
+            trade_fee = trade_fee.checked_add(step.fee)?;
+            if self.liquidity > 0 {
+                let growth = fee_growth(step.fee, self.liquidity)?;
+                if a_to_b {
+                    self.fee_growth_global_a = self.fee_growth_global_a.wrapping_add(growth);
+                } else {
+                    self.fee_growth_global_b = self.fee_growth_global_b.wrapping_add(growth);
+                }
+            }
+            self.sqrt_price = step.sqrt_price;
+
+            if step.sqrt_price != sqrt_price_target {
+                self.tick_current = tick_at_sqrt_price(step.sqrt_price)?;
+            } else if let Some(index) = next_tick {
+                let fee_growth_global_a = self.fee_growth_global_a;
+                let fee_growth_global_b = self.fee_growth_global_b;
+                let position = self
+                    .ticks
+                    .binary_search_by_key(&index, |tick| tick.index)
+                    .ok()?;
+                let tick = &mut self.ticks[position];
+                tick.fee_growth_outside_a =
+                    fee_growth_global_a.wrapping_sub(tick.fee_growth_outside_a);
+                tick.fee_growth_outside_b =
+                    fee_growth_global_b.wrapping_sub(tick.fee_growth_outside_b);
+                if a_to_b {
+                    self.liquidity =
+                        add_liquidity_delta(self.liquidity, tick.liquidity_net.checked_neg()?)?;
+                    self.tick_current = index.checked_sub(1)?;
+                } else {
+                    self.liquidity = add_liquidity_delta(self.liquidity, tick.liquidity_net)?;
+                    self.tick_current = index;
+                }
+            } else {
+                // end of the supported price range
+                self.tick_current = target_tick;
+                break;
+            }
+        }
+
+        if destination_amount_swapped == 0 {
+            return None;
+        }
+        let source_amount_swapped = source_amount.checked_sub(amount_remaining)?;
+        Some(SwapResult {
+            new_swap_source_amount: swap_source_amount.checked_add(source_amount_swapped)?,
+            new_swap_destination_amount: swap_destination_amount
+                .checked_sub(destination_amount_swapped)?,
+            source_amount_swapped,
+            destination_amount_swapped,
+            trade_fee,
+            owner_fee: 0,
+        })
+    }
+}
+
+impl IsInitialized for ConcentratedLiquidityState {
+    fn is_initialized(&self) -> bool {
+        self.sqrt_price != 0
+    }
+}
+
+impl Sealed for ConcentratedLiquidityState {}
+impl Pack for ConcentratedLiquidityState {
+    const LEN: usize = 70 + MAX_TICKS * Tick::LEN;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, ConcentratedLiquidityState::LEN];
+        let (
+            sqrt_price,
+            tick_current,
+            liquidity,
+            fee_growth_global_a,
+            fee_growth_global_b,
+            tick_count,
+            ticks,
+        ) = mut_array_refs![output, 16, 4, 16, 16, 16, 2, MAX_TICKS * Tick::LEN];
+        *sqrt_price = self.sqrt_price.to_le_bytes();
+        *tick_current = self.tick_current.to_le_bytes();
+        *liquidity = self.liquidity.to_le_bytes();
+        *fee_growth_global_a = self.fee_growth_global_a.to_le_bytes();
+        *fee_growth_global_b = self.fee_growth_global_b.to_le_bytes();
+        *tick_count = (self.ticks.len() as u16).to_le_bytes();
+        for (tick, output) in self.ticks.iter().zip(ticks.chunks_mut(Tick::LEN)) {
+            tick.pack_into_slice(output);
+        }
+        for output in ticks.chunks_mut(Tick::LEN).skip(self.ticks.len()) {
+            Tick::default().pack_into_slice(output);
+        }
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, ConcentratedLiquidityState::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            sqrt_price,
+            tick_current,
+            liquidity,
+            fee_growth_global_a,
+            fee_growth_global_b,
+            tick_count,
+            ticks,
+        ) = array_refs![input, 16, 4, 16, 16, 16, 2, MAX_TICKS * Tick::LEN];
+        let tick_count = u16::from_le_bytes(*tick_count) as usize;
+        if tick_count > MAX_TICKS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self {
+            sqrt_price: u128::from_le_bytes(*sqrt_price),
+            tick_current: i32::from_le_bytes(*tick_current),
+            liquidity: u128::from_le_bytes(*liquidity),
+            fee_growth_global_a: u128::from_le_bytes(*fee_growth_global_a),
+            fee_growth_global_b: u128::from_le_bytes(*fee_growth_global_b),
+            ticks: ticks
+                .chunks(Tick::LEN)
+                .take(tick_count)
+                .map(Tick::unpack_from_slice)
+                .collect(),
+        })
+    }
+}
+
+/// Liquidity provided to a concentrated liquidity pool within a price range
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Position {
+    /// Initialized state
+    pub is_initialized: bool,
+    /// Token-swap the position belongs to
+    pub swap: Pubkey,
+    /// Owner allowed to add and remove liquidity
+    pub owner: Pubkey,
+    /// Lower bound of the price range
+    pub tick_lower: i32,
+    /// Upper bound of the price range
+    pub tick_upper: i32,
+    /// Liquidity provided within the range
+    pub liquidity: u128,
+    /// Token A fees earned per unit of liquidity within the range, as of the
+    /// last update of the position
+    pub fee_growth_inside_a: u128,
+    /// Token B fees earned per unit of liquidity within the range, as of the
+    /// last update of the position
+    pub fee_growth_inside_b: u128,
+    /// Token A fees earned by the position and not withdrawn yet
+    pub fees_owed_a: u64,
+    /// Token B fees earned by the position and not withdrawn yet
+    pub fees_owed_b: u64,
+}
+
+impl Position {
+    /// Add the fees earned since the last update to the fees owed
+    fn collect_fees(
+        &mut self,
+        fee_growth_inside_a: u128,
+        fee_growth_inside_b: u128,
+    ) -> Result<(), SwapError> {
+        let earned = |fee_growth_inside: u128, fee_growth_inside_last: u128| {
+            let growth = fee_growth_inside.wrapping_sub(fee_growth_inside_last);
+            let fees = U256::from(self.liquidity).checked_mul(U256::from(growth))?
+                >> PRICE_FRACTIONAL_BITS;
+            u64::try_from(fees).ok()
+        };
+        let earned_a = earned(fee_growth_inside_a, self.fee_growth_inside_a)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let earned_b = earned(fee_growth_inside_b, self.fee_growth_inside_b)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        self.fees_owed_a = self
+            .fees_owed_a
+            .checked_add(earned_a)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        self.fees_owed_b = self
+            .fees_owed_b
+            .checked_add(earned_b)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        self.fee_growth_inside_a = fee_growth_inside_a;
+        self.fee_growth_inside_b = fee_growth_inside_b;
+        Ok(())
+    }
+}
+
+impl IsInitialized for Position {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Sealed for Position {}
+impl Pack for Position {
+    const LEN: usize = 137;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 137];
+        let (
+            is_initialized,
+            swap,
+            owner,
+            tick_lower,
+            tick_upper,
+            liquidity,
+            fee_growth_inside_a,
+            fee_growth_inside_b,
+            fees_owed_a,
+            fees_owed_b,
+        ) = mut_array_refs![output, 1, 32, 32, 4, 4, 16, 16, 16, 8, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        swap.copy_from_slice(self.swap.as_ref());
+        owner.copy_from_slice(self.owner.as_ref());
+        *tick_lower = self.tick_lower.to_le_bytes();
+        *tick_upper = self.tick_upper.to_le_bytes();
+        *liquidity = self.liquidity.to_le_bytes();
+        *fee_growth_inside_a = self.fee_growth_inside_a.to_le_bytes();
+        *fee_growth_inside_b = self.fee_growth_inside_b.to_le_bytes();
+        *fees_owed_a = self.fees_owed_a.to_le_bytes();
+        *fees_owed_b = self.fees_owed_b.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 137];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            swap,
+            owner,
+            tick_lower,
+            tick_upper,
+            liquidity,
+            fee_growth_inside_a,
+            fee_growth_inside_b,
+            fees_owed_a,
+            fees_owed_b,
+        ) = array_refs![input, 1, 32, 32, 4, 4, 16, 16, 16, 8, 8];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            swap: Pubkey::new_from_array(*swap),
+            owner: Pubkey::new_from_array(*owner),
+            tick_lower: i32::from_le_bytes(*tick_lower),
+            tick_upper: i32::from_le_bytes(*tick_upper),
+            liquidity: u128::from_le_bytes(*liquidity),
+            fee_growth_inside_a: u128::from_le_bytes(*fee_growth_inside_a),
+            fee_growth_inside_b: u128::from_le_bytes(*fee_growth_inside_b),
+            fees_owed_a: u64::from_le_bytes(*fees_owed_a),
+            fees_owed_b: u64::from_le_bytes(*fees_owed_b),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const Q64: u128 = 1 << 64;
+
+    fn fees(trade_fee_numerator: u64, trade_fee_denominator: u64) -> Fees {
+        Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            ..Fees::default()
+        }
+    }
+
+    fn open(
+        state: &mut ConcentratedLiquidityState,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: u128,
+    ) -> (Position, u128, u128) {
+        let mut position = Position {
+            is_initialized: true,
+            tick_lower,
+            tick_upper,
+            ..Position::default()
+        };
+        let (token_a_amount, token_b_amount) = state
+            .update_position(&mut position, liquidity as i128)
+            .unwrap();
+        (position, token_a_amount, token_b_amount)
+    }
+
+    #[test]
+    fn pack_curve() {
+        let tick_spacing = 60;
+        let min_position_liquidity = 1_000;
+        let curve = ConcentratedLiquidityCurve {
+            tick_spacing,
+            min_position_liquidity,
+        };
+
+        let mut packed = [0u8; ConcentratedLiquidityCurve::LEN];
+        Pack::pack_into_slice(&curve, &mut packed[..]);
+        let unpacked = ConcentratedLiquidityCurve::unpack(&packed).unwrap();
+        assert_eq!(curve, unpacked);
+
+        let mut packed = vec![];
+        packed.extend_from_slice(&tick_spacing.to_le_bytes());
+        packed.extend_from_slice(&min_position_liquidity.to_le_bytes());
+        let unpacked = ConcentratedLiquidityCurve::unpack(&packed).unwrap();
+        assert_eq!(curve, unpacked);
+    }
+
+    #[test]
+    fn validate_tick_range() {
+        let curve = ConcentratedLiquidityCurve {
+            tick_spacing: 10,
+            min_position_liquidity: 1_000,
+        };
+        assert_eq!(curve.validate(), Ok(()));
+        assert_eq!(curve.validate_tick_range(-20, 30), Ok(()));
+        assert_eq!(
+            curve.validate_tick_range(30, 30),
+            Err(SwapError::InvalidTickRange)
+        );
+        assert_eq!(
+            curve.validate_tick_range(30, -20),
+            Err(SwapError::InvalidTickRange)
+        );
+        assert_eq!(
+            curve.validate_tick_range(-25, 30),
+            Err(SwapError::InvalidTickRange)
+        );
+        assert_eq!(
+            curve.validate_tick_range(-20, MAX_TICK + 4),
+            Err(SwapError::InvalidTickRange)
+        );
+        assert_eq!(
+            ConcentratedLiquidityCurve {
+                tick_spacing: 0,
+                ..curve.clone()
+            }
+            .validate(),
+            Err(SwapError::InvalidCurve)
+        );
+        assert_eq!(
+            ConcentratedLiquidityCurve {
+                min_position_liquidity: 0,
+                ..curve.clone()
+            }
+            .validate(),
+            Err(SwapError::InvalidCurve)
+        );
+
+        assert_eq!(curve.validate_position_liquidity(0), Ok(()));
+        assert_eq!(curve.validate_position_liquidity(1_000), Ok(()));
+        assert_eq!(
+            curve.validate_position_liquidity(999),
+            Err(SwapError::PositionLiquidityTooLow)
+        );
+    }
+
+    #[test]
+    fn sqrt_price_at_ticks() {
+        assert_eq!(sqrt_price_at_tick(0), Some(Q64));
+        // sqrt(1.0001) ^ 100 ~= 1.005012
+        let price = sqrt_price_at_tick(100).unwrap();
+        assert_eq!(price / (Q64 / 1_000_000), 1_005_012);
+        // prices of opposite ticks are inverses
+        let product = U256::from(price) * U256::from(sqrt_price_at_tick(-100).unwrap());
+        let one = U256::from(Q64) * U256::from(Q64);
+        assert!(product >= one);
+        assert!(product - one < U256::from(Q64) * 2);
+
+        assert!(sqrt_price_at_tick(MIN_TICK).unwrap() > 0);
+        assert!(sqrt_price_at_tick(MAX_TICK).is_some());
+        assert_eq!(sqrt_price_at_tick(MIN_TICK - 1), None);
+        assert_eq!(sqrt_price_at_tick(MAX_TICK + 1), None);
+    }
+
+    #[test]
+    fn ticks_at_sqrt_prices() {
+        for tick in &[MIN_TICK, -100_000, -1, 0, 1, 3_000, MAX_TICK] {
+            let price = sqrt_price_at_tick(*tick).unwrap();
+            assert_eq!(tick_at_sqrt_price(price), Some(*tick));
+            if *tick != MAX_TICK {
+                assert_eq!(tick_at_sqrt_price(price + 1), Some(*tick));
+            }
+            if *tick != MIN_TICK {
+                assert_eq!(tick_at_sqrt_price(price - 1), Some(*tick - 1));
+            }
+        }
+        assert_eq!(tick_at_sqrt_price(0), None);
+    }
+
+    #[test]
+    fn sqrt_price_of_amounts() {
+        assert_eq!(sqrt_price_from_amounts(100, 400), Some(2 * Q64));
+        assert_eq!(sqrt_price_from_amounts(400, 100), Some(Q64 / 2));
+        assert_eq!(sqrt_price_from_amounts(0, 100), None);
+    }
+
+    #[test]
+    fn token_deltas() {
+        // between prices 1 and 4, 1000 liquidity holds 500 A or 1000 B
+        let one = Q64;
+        let two = 2 * Q64;
+        assert_eq!(token_a_delta(one, two, 1_000, false), Some(500));
+        assert_eq!(token_a_delta(two, one, 1_000, true), Some(500));
+        assert_eq!(token_b_delta(one, two, 1_000, false), Some(1_000));
+        // rounding
+        let three_halves = 3 * Q64 / 2;
+        assert_eq!(token_a_delta(one, three_halves, 1_000, false), Some(333));
+        assert_eq!(token_a_delta(one, three_halves, 1_000, true), Some(334));
+        assert_eq!(token_b_delta(one, three_halves, 1_001, false), Some(500));
+        assert_eq!(token_b_delta(one, three_halves, 1_001, true), Some(501));
+    }
+
+    #[test]
+    fn swap_step_within_range() {
+        let liquidity = 1_000_000;
+        // B to A without reaching the target
+        let step = swap_step(Q64, 2 * Q64, liquidity, 1_000, 0, 1).unwrap();
+        assert_eq!(step.amount_in, 1_000);
+        assert_eq!(step.fee, 0);
+        assert_eq!(step.amount_out, 999);
+        assert!(step.sqrt_price > Q64 && step.sqrt_price < 2 * Q64);
+
+        // A to B reaching the target, with a 1% fee
+        let target = Q64 / 2;
+        let needed = token_a_delta(target, Q64, liquidity, true).unwrap();
+        let step = swap_step(Q64, target, liquidity, 2 * needed, 1, 100).unwrap();
+        assert_eq!(step.sqrt_price, target);
+        assert_eq!(step.amount_in, needed);
+        assert_eq!(step.amount_out, liquidity / 2);
+        assert_eq!(
+            U256::from(step.fee),
+            div_round(U256::from(needed), U256::from(99), true).unwrap()
+        );
+
+        // fee is whatever is left if the target is not reached
+        let step = swap_step(Q64, target, liquidity, 1_000, 1, 100).unwrap();
+        assert_eq!(step.amount_in + step.fee, 1_000);
+        assert!(step.fee >= 10);
+    }
+
+    #[test]
+    fn positions_around_price() {
+        let mut state = ConcentratedLiquidityState::new(Q64).unwrap();
+        assert_eq!(state.tick_current, 0);
+
+        // above the price, only token A
+        let (_, token_a_amount, token_b_amount) = open(&mut state, 100, 200, Q64);
+        assert!(token_a_amount > 0);
+        assert_eq!(token_b_amount, 0);
+        assert_eq!(state.liquidity, 0);
+
+        // below the price, only token B
+        let (_, token_a_amount, token_b_amount) = open(&mut state, -200, -100, Q64);
+        assert_eq!(token_a_amount, 0);
+        assert!(token_b_amount > 0);
+        assert_eq!(state.liquidity, 0);
+
+        // around the price, both tokens, symmetrical within rounding
+        let (mut position, token_a_amount, token_b_amount) = open(&mut state, -50, 50, Q64);
+        let difference = token_a_amount.max(token_b_amount) - token_a_amount.min(token_b_amount);
+        assert!(difference <= token_a_amount / 1_000_000_000);
+        assert_eq!(state.liquidity, Q64);
+        assert_eq!(state.ticks.len(), 6);
+
+        // removing gives back at most what was deposited
+        let (withdrawn_a, withdrawn_b) = state
+            .update_position(&mut position, -(Q64 as i128))
+            .unwrap();
+        assert!(withdrawn_a <= token_a_amount && token_a_amount - withdrawn_a <= 1);
+        assert!(withdrawn_b <= token_b_amount && token_b_amount - withdrawn_b <= 1);
+        assert_eq!(state.liquidity, 0);
+        assert_eq!(state.ticks.len(), 4);
+        assert_eq!(position.liquidity, 0);
+
+        assert_eq!(
+            state.update_position(&mut position, -1),
+            Err(SwapError::InsufficientLiquidity)
+        );
+    }
+
+    #[test]
+    fn too_many_ticks() {
+        let mut state = ConcentratedLiquidityState::new(Q64).unwrap();
+        for i in 0..(MAX_TICKS as i32 / 2) {
+            open(&mut state, -(i + 1), i + 1, 1);
+        }
+        let mut position = Position {
+            tick_lower: -1,
+            tick_upper: MAX_TICKS as i32,
+            ..Position::default()
+        };
+        assert_eq!(
+            state.update_position(&mut position, 1),
+            Err(SwapError::TooManyTicks)
+        );
+    }
+
+    #[test]
+    fn swap_crossing_ticks() {
+        let trade_fees = fees(3, 1_000);
+        let mut state = ConcentratedLiquidityState::new(Q64).unwrap();
+        let (mut wide, _, _) = open(&mut state, -1_000, 1_000, 1_000_000_000);
+        let (mut narrow, _, _) = open(&mut state, -10, 10, 1_000_000_000);
+        assert_eq!(state.liquidity, 2_000_000_000);
+
+        // swap A to B through the narrow range
+        let result = state
+            .swap(
+                2_000_000,
+                1_000_000_000,
+                1_000_000_000,
+                TradeDirection::AtoB,
+                &trade_fees,
+            )
+            .unwrap();
+        assert_eq!(result.source_amount_swapped, 2_000_000);
+        assert_eq!(result.owner_fee, 0);
+        assert!(result.trade_fee >= 6_000);
+        assert!(result.destination_amount_swapped < 2_000_000 - 6_000);
+        assert!(state.tick_current < -10);
+        assert_eq!(state.liquidity, 1_000_000_000);
+
+        // the narrow position earned fees while in range, the wide one earned
+        // at least as much since it was in range for the whole swap
+        state.update_position(&mut narrow, 0).unwrap();
+        state.update_position(&mut wide, 0).unwrap();
+        assert!(narrow.fees_owed_a > 0);
+        assert!(wide.fees_owed_a > narrow.fees_owed_a);
+        assert!(u128::from(narrow.fees_owed_a + wide.fees_owed_a) <= result.trade_fee);
+        assert_eq!(narrow.fees_owed_b, 0);
+        assert_eq!(wide.fees_owed_b, 0);
+
+        // swap back B to A, crossing the narrow range again
+        let result = state
+            .swap(
+                4_000_000,
+                1_000_000_000,
+                1_000_000_000,
+                TradeDirection::BtoA,
+                &trade_fees,
+            )
+            .unwrap();
+        assert_eq!(result.source_amount_swapped, 4_000_000);
+        assert!(state.tick_current > 10);
+        assert_eq!(state.liquidity, 1_000_000_000);
+        state.update_position(&mut narrow, 0).unwrap();
+        state.update_position(&mut wide, 0).unwrap();
+        assert!(narrow.fees_owed_b > 0);
+        assert!(wide.fees_owed_b > narrow.fees_owed_b);
+    }
+
+    #[test]
+    fn swap_out_of_liquidity() {
+        let mut state = ConcentratedLiquidityState::new(Q64).unwrap();
+        // no liquidity at all
+        assert_eq!(
+            state.swap(1_000, 1_000, 1_000, TradeDirection::AtoB, &fees(0, 1)),
+            None
+        );
+
+        // the price stops at the end of the last range, and the rest of the
+        // source amount is not swapped
+        let mut state = ConcentratedLiquidityState::new(Q64).unwrap();
+        let (_, _, token_b_amount) = open(&mut state, -100, 100, 1_000_000);
+        let result = state
+            .swap(
+                1_000_000,
+                0,
+                token_b_amount,
+                TradeDirection::AtoB,
+                &fees(0, 1),
+            )
+            .unwrap();
+        assert!(result.source_amount_swapped < 1_000_000);
+        assert!(result.destination_amount_swapped <= token_b_amount);
+        assert_eq!(state.tick_current, MIN_TICK);
+        assert_eq!(state.liquidity, 0);
+    }
+
+    #[test]
+    fn pack_state() {
+        let mut state = ConcentratedLiquidityState::new(3 * Q64).unwrap();
+        state.fee_growth_global_a = u128::MAX;
+        open(&mut state, -120, 120, 42);
+        open(&mut state, 60, 180, 7);
+
+        let mut packed = vec![0u8; ConcentratedLiquidityState::LEN];
+        ConcentratedLiquidityState::pack(state.clone(), &mut packed).unwrap();
+        let unpacked = ConcentratedLiquidityState::unpack(&packed).unwrap();
+        assert_eq!(state, unpacked);
+
+        packed[68] = MAX_TICKS as u8 + 1;
+        assert_eq!(
+            ConcentratedLiquidityState::unpack(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn pack_position() {
+        let position = Position {
+            is_initialized: true,
+            swap: Pubkey::new_from_array([1u8; 32]),
+            owner: Pubkey::new_from_array([2u8; 32]),
+            tick_lower: -60,
+            tick_upper: 120,
+            liquidity: u128::MAX,
+            fee_growth_inside_a: 3,
+            fee_growth_inside_b: 4,
+            fees_owed_a: 5,
+            fees_owed_b: u64::MAX,
+        };
+        let mut packed = [0u8; Position::LEN];
+        Position::pack(position.clone(), &mut packed).unwrap();
+        let unpacked = Position::unpack(&packed).unwrap();
+        assert_eq!(position, unpacked);
+
+        let mut expect = vec![1u8];
+        expect.extend_from_slice(&[1u8; 32]);
+        expect.extend_from_slice(&[2u8; 32]);
+        expect.extend_from_slice(&(-60i32).to_le_bytes());
+        expect.extend_from_slice(&120i32.to_le_bytes());
+        expect.extend_from_slice(&u128::MAX.to_le_bytes());
+        expect.extend_from_slice(&3u128.to_le_bytes());
+        expect.extend_from_slice(&4u128.to_le_bytes());
+        expect.extend_from_slice(&5u64.to_le_bytes());
+        expect.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(packed.to_vec(), expect);
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip_swap_does_not_create_value(
+            liquidity in 1_000_000..u64::MAX as u128,
+            source_amount in 1..u32::MAX as u128,
+            tick_lower in -50i32..0,
+            tick_upper in 1i32..50,
+        ) {
+            let mut state = ConcentratedLiquidityState::new(Q64).unwrap();
+            let (_, token_a_amount, token_b_amount) =
+                open(&mut state, tick_lower * 10, tick_upper * 10, liquidity);
+            let trade_fees = fees(0, 1);
+            if let Some(there) = state.swap(
+                source_amount,
+                token_a_amount,
+                token_b_amount,
+                TradeDirection::AtoB,
+                &trade_fees,
+            ) {
+                if let Some(back) = state.swap(
+                    there.destination_amount_swapped,
+                    there.new_swap_destination_amount,
+                    there.new_swap_source_amount,
+                    TradeDirection::BtoA,
+                    &trade_fees,
+                ) {
+                    prop_assert!(back.destination_amount_swapped <= there.source_amount_swapped);
+                }
+            }
+        }
+    }
+}
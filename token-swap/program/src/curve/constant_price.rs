@@ -187,6 +187,16 @@ impl CurveCalculator for ConstantPriceCurve {
         };
         PreciseNumber::new(value)
     }
+
+    /// The spot price of token A is fixed at the inverse of the token B price,
+    /// regardless of the liquidity in the pool
+    fn spot_price(
+        &self,
+        _swap_token_a_amount: u128,
+        _swap_token_b_amount: u128,
+    ) -> Option<PreciseNumber> {
+        PreciseNumber::new(1)?.checked_div(&PreciseNumber::new(self.token_b_price as u128)?)
+    }
 }
 
 /// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
@@ -263,6 +273,19 @@ mod tests {
         assert_eq!(result, expected_result);
     }
 
+    #[test]
+    fn spot_price_is_constant() {
+        let token_b_price = 4;
+        let curve = ConstantPriceCurve { token_b_price };
+        let expected = PreciseNumber::new(1)
+            .unwrap()
+            .checked_div(&PreciseNumber::new(token_b_price as u128).unwrap())
+            .unwrap();
+        assert_eq!(curve.spot_price(100, 100).unwrap(), expected);
+        assert_eq!(curve.spot_price(1, 1_000_000).unwrap(), expected);
+        assert_eq!(curve.spot_price(0, 0).unwrap(), expected);
+    }
+
     #[test]
     fn pack_flat_curve() {
         let token_b_price = 1_251_258;
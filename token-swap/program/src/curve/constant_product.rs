@@ -139,6 +139,16 @@ pub fn normalized_value(
         .sqrt()
 }
 
+/// Calculates the spot price of token A, denominated in token B.
+///
+/// The constant product implementation for this function is the ratio of
+/// token B to token A.
+pub fn spot_price(swap_token_a_amount: u128, swap_token_b_amount: u128) -> Option<PreciseNumber> {
+    let swap_token_a_amount = PreciseNumber::new(swap_token_a_amount)?;
+    let swap_token_b_amount = PreciseNumber::new(swap_token_b_amount)?;
+    swap_token_b_amount.checked_div(&swap_token_a_amount)
+}
+
 impl CurveCalculator for ConstantProductCurve {
     /// Constant product swap ensures x * y = constant
     fn swap_without_fees(
@@ -198,6 +208,14 @@ impl CurveCalculator for ConstantProductCurve {
         normalized_value(swap_token_a_amount, swap_token_b_amount)
     }
 
+    fn spot_price(
+        &self,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+    ) -> Option<PreciseNumber> {
+        spot_price(swap_token_a_amount, swap_token_b_amount)
+    }
+
     fn validate(&self) -> Result<(), SwapError> {
         Ok(())
     }
@@ -231,8 +249,8 @@ mod tests {
     use crate::curve::calculator::{
         test::{
             check_curve_value_from_swap, check_pool_token_conversion,
-            check_pool_value_from_deposit, check_pool_value_from_withdraw, total_and_intermediate,
-            CONVERSION_BASIS_POINTS_GUARANTEE,
+            check_pool_value_from_deposit, check_pool_value_from_withdraw,
+            check_spot_price_from_swap, total_and_intermediate, CONVERSION_BASIS_POINTS_GUARANTEE,
         },
         RoundDirection, INITIAL_SWAP_POOL_AMOUNT,
     };
@@ -284,6 +302,24 @@ mod tests {
         assert!(results.is_none());
     }
 
+    #[test]
+    fn spot_price_ratio() {
+        let calculator = ConstantProductCurve {};
+        let price = calculator.spot_price(100, 250).unwrap();
+        assert_eq!(
+            price,
+            PreciseNumber::new(5)
+                .unwrap()
+                .checked_div(&PreciseNumber::new(2).unwrap())
+                .unwrap()
+        );
+        assert!(calculator.spot_price(0, 250).is_none());
+        assert_eq!(
+            calculator.spot_price(100, 0).unwrap(),
+            PreciseNumber::new(0).unwrap()
+        );
+    }
+
     #[test]
     fn pack_constant_product_curve() {
         let curve = ConstantProductCurve {};
@@ -416,6 +452,23 @@ mod tests {
         }
     }
 
+    proptest! {
+        #[test]
+        fn spot_price_bounds_swap(
+            source_token_amount in 1..u32::MAX,
+            swap_token_a_amount in 1..u32::MAX,
+            swap_token_b_amount in 1..u32::MAX,
+        ) {
+            let curve = ConstantProductCurve {};
+            check_spot_price_from_swap(
+                &curve,
+                source_token_amount as u128,
+                swap_token_a_amount as u128,
+                swap_token_b_amount as u128,
+            );
+        }
+    }
+
     proptest! {
         #[test]
         fn curve_value_does_not_decrease_from_deposit(
@@ -0,0 +1,415 @@
+//! Trade fee that rises with the recent volatility of the pool price
+
+use crate::{
+    curve::fees::Fees,
+    error::SwapError,
+    oracle::{from_fixed_price, to_fixed_price},
+};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    clock::Slot,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+};
+use spl_math::precise_number::PreciseNumber;
+
+/// Precision of the volatility accumulator: a volatility of
+/// `VOLATILITY_PRECISION` is a cumulative price movement of 100%
+pub const VOLATILITY_PRECISION: u64 = 1_000_000_000;
+
+/// Denominator of the volatility multiplier, which is given in basis points
+pub const VOLATILITY_MULTIPLIER_DENOMINATOR: u64 = 10_000;
+
+/// Parameters of the dynamic trade fee, set by the admin authority.  All
+/// zeroes disable the dynamic fee, and trades pay the fixed trade fee.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DynamicFeeParameters {
+    /// Trade fee numerator when the pool price is stable
+    pub min_fee_numerator: u64,
+    /// Highest trade fee numerator, whatever the volatility
+    pub max_fee_numerator: u64,
+    /// Denominator of the minimum and maximum trade fees
+    pub fee_denominator: u64,
+    /// Share of the volatility added to the trade fee, in basis points: at
+    /// 10_000, a cumulative price movement of 1% adds 1% to the trade fee
+    pub volatility_multiplier: u64,
+    /// Number of slots after which the volatility is halved.  The volatility
+    /// decays as `decay_period / (decay_period + slots_elapsed)`, and 0 only
+    /// keeps the price movement of the current slot.
+    pub decay_period: u64,
+}
+
+impl DynamicFeeParameters {
+    /// Is the dynamic fee charged instead of the fixed trade fee
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// Validate that the fee bounds are reasonable
+    pub fn validate(&self) -> Result<(), SwapError> {
+        if !self.is_enabled() {
+            Ok(())
+        } else if self.min_fee_numerator > self.max_fee_numerator
+            || self.max_fee_numerator >= self.fee_denominator
+        {
+            Err(SwapError::InvalidFee)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Volatility accumulator of a swap, driving its dynamic trade fee.
+///
+/// Every instruction which tracks the pool price observes the spot price
+/// before moving any tokens.  The relative movement from the previously
+/// observed price is added to the volatility, after the volatility decayed for
+/// the slots elapsed since the previous observation.  Trades then pay the
+/// minimum fee increased by the volatility times the multiplier, up to the
+/// maximum fee, so arbitrageurs following a price move pay for the move, while
+/// trades in a quiet pool pay the minimum fee.
+///
+/// A swap also pays for the movement it causes itself: its fee is the fee
+/// `after_trade` the price it would leave when charged the fee before the
+/// trade, and the price it actually leaves is observed once it completes.
+/// Off-chain quotes only see the movement up to the start of the trade once
+/// they `observe` the current price.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DynamicFee {
+    /// Fee bounds and volatility response
+    pub parameters: DynamicFeeParameters,
+    /// Decayed sum of the relative price movements, in `VOLATILITY_PRECISION`
+    pub volatility: u64,
+    /// Spot price of token A, denominated in token B, at the last
+    /// observation, as an unsigned Q64.64 number, or 0 if not observed yet
+    pub reference_price: u128,
+    /// Slot of the last observation
+    pub last_update_slot: Slot,
+}
+
+impl DynamicFee {
+    /// Create a dynamic fee with no recorded volatility
+    pub fn new(parameters: DynamicFeeParameters) -> Self {
+        Self {
+            parameters,
+            ..Self::default()
+        }
+    }
+
+    /// Decay the volatility to `slot` and add the movement from the reference
+    /// price to `spot_price`, which becomes the new reference.  A price which
+    /// cannot be observed, for example because one side of the pool is empty,
+    /// only decays the volatility.
+    pub fn observe(&mut self, slot: Slot, spot_price: Option<&PreciseNumber>) {
+        if !self.parameters.is_enabled() {
+            return;
+        }
+        let slots_elapsed = slot.saturating_sub(self.last_update_slot);
+        if slots_elapsed > 0 {
+            let decay_period = u128::from(self.parameters.decay_period);
+            self.volatility = u128::from(self.volatility)
+                .checked_mul(decay_period)
+                .and_then(|volatility| {
+                    volatility.checked_div(decay_period.checked_add(u128::from(slots_elapsed))?)
+                })
+                .map_or(0, |volatility| volatility as u64);
+            self.last_update_slot = slot;
+        }
+        if let Some(price) = spot_price.and_then(to_fixed_price) {
+            if let Some(movement) = self.price_movement(price) {
+                self.volatility = self.volatility.saturating_add(movement);
+            }
+            self.reference_price = price;
+        }
+    }
+
+    /// Dynamic fee once a trade moved the spot price to `post_trade_price`
+    /// within the slot of the last observation, so that the trade is charged
+    /// for its own price movement
+    pub fn after_trade(&self, post_trade_price: Option<&PreciseNumber>) -> Self {
+        let mut dynamic_fee = *self;
+        dynamic_fee.observe(self.last_update_slot, post_trade_price);
+        dynamic_fee
+    }
+
+    /// Relative movement from the reference price, in `VOLATILITY_PRECISION`
+    fn price_movement(&self, price: u128) -> Option<u64> {
+        if self.reference_price == 0 {
+            return None;
+        }
+        let reference_price = from_fixed_price(self.reference_price)?;
+        let price = from_fixed_price(price)?;
+        let difference = if price.greater_than(&reference_price) {
+            price.checked_sub(&reference_price)?
+        } else {
+            reference_price.checked_sub(&price)?
+        };
+        let movement = difference
+            .checked_mul(&PreciseNumber::new(u128::from(VOLATILITY_PRECISION))?)?
+            .checked_div(&reference_price)?
+            .to_imprecise()?;
+        Some(if movement > u128::from(u64::MAX) {
+            u64::MAX
+        } else {
+            movement as u64
+        })
+    }
+
+    /// Trade fee numerator for the current volatility, over the fee
+    /// denominator of the parameters
+    pub fn trade_fee_numerator(&self) -> u64 {
+        let DynamicFeeParameters {
+            min_fee_numerator,
+            max_fee_numerator,
+            fee_denominator,
+            volatility_multiplier,
+            ..
+        } = self.parameters;
+        u128::from(self.volatility)
+            .checked_mul(u128::from(volatility_multiplier))
+            .and_then(|fee| fee.checked_mul(u128::from(fee_denominator)))
+            .map(|fee| {
+                fee / u128::from(VOLATILITY_PRECISION)
+                    / u128::from(VOLATILITY_MULTIPLIER_DENOMINATOR)
+            })
+            .and_then(|fee| fee.checked_add(u128::from(min_fee_numerator)))
+            .filter(|fee| *fee < u128::from(max_fee_numerator))
+            .map_or(max_fee_numerator, |fee| fee as u64)
+    }
+
+    /// Fees charged by the next trade: the dynamic trade fee if enabled, the
+    /// given fixed fees otherwise
+    pub fn apply(&self, fees: &Fees) -> Fees {
+        if self.parameters.is_enabled() {
+            Fees {
+                trade_fee_numerator: self.trade_fee_numerator(),
+                trade_fee_denominator: self.parameters.fee_denominator,
+                ..fees.clone()
+            }
+        } else {
+            fees.clone()
+        }
+    }
+}
+
+impl IsInitialized for DynamicFeeParameters {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+
+impl Sealed for DynamicFeeParameters {}
+impl Pack for DynamicFeeParameters {
+    const LEN: usize = 40;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 40];
+        let (
+            min_fee_numerator,
+            max_fee_numerator,
+            fee_denominator,
+            volatility_multiplier,
+            decay_period,
+        ) = mut_array_refs![output, 8, 8, 8, 8, 8];
+        *min_fee_numerator = self.min_fee_numerator.to_le_bytes();
+        *max_fee_numerator = self.max_fee_numerator.to_le_bytes();
+        *fee_denominator = self.fee_denominator.to_le_bytes();
+        *volatility_multiplier = self.volatility_multiplier.to_le_bytes();
+        *decay_period = self.decay_period.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<DynamicFeeParameters, ProgramError> {
+        let input = array_ref![input, 0, 40];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            min_fee_numerator,
+            max_fee_numerator,
+            fee_denominator,
+            volatility_multiplier,
+            decay_period,
+        ) = array_refs![input, 8, 8, 8, 8, 8];
+        Ok(Self {
+            min_fee_numerator: u64::from_le_bytes(*min_fee_numerator),
+            max_fee_numerator: u64::from_le_bytes(*max_fee_numerator),
+            fee_denominator: u64::from_le_bytes(*fee_denominator),
+            volatility_multiplier: u64::from_le_bytes(*volatility_multiplier),
+            decay_period: u64::from_le_bytes(*decay_period),
+        })
+    }
+}
+
+impl IsInitialized for DynamicFee {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+
+impl Sealed for DynamicFee {}
+impl Pack for DynamicFee {
+    const LEN: usize = 72;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 72];
+        let (parameters, volatility, reference_price, last_update_slot) =
+            mut_array_refs![output, 40, 8, 16, 8];
+        self.parameters.pack_into_slice(&mut parameters[..]);
+        *volatility = self.volatility.to_le_bytes();
+        *reference_price = self.reference_price.to_le_bytes();
+        *last_update_slot = self.last_update_slot.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<DynamicFee, ProgramError> {
+        let input = array_ref![input, 0, 72];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (parameters, volatility, reference_price, last_update_slot) =
+            array_refs![input, 40, 8, 16, 8];
+        Ok(Self {
+            parameters: DynamicFeeParameters::unpack_from_slice(parameters)?,
+            volatility: u64::from_le_bytes(*volatility),
+            reference_price: u128::from_le_bytes(*reference_price),
+            last_update_slot: Slot::from_le_bytes(*last_update_slot),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMETERS: DynamicFeeParameters = DynamicFeeParameters {
+        min_fee_numerator: 25,
+        max_fee_numerator: 200,
+        fee_denominator: 10_000,
+        volatility_multiplier: 5_000,
+        decay_period: 10,
+    };
+
+    fn price(numerator: u128, denominator: u128) -> PreciseNumber {
+        PreciseNumber::new(numerator)
+            .unwrap()
+            .checked_div(&PreciseNumber::new(denominator).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn pack_dynamic_fee() {
+        let dynamic_fee = DynamicFee {
+            parameters: PARAMETERS,
+            volatility: 12_345,
+            reference_price: 3 << 64,
+            last_update_slot: 99,
+        };
+        let mut packed = [0u8; DynamicFee::LEN];
+        Pack::pack_into_slice(&dynamic_fee, &mut packed[..]);
+        let unpacked = DynamicFee::unpack_from_slice(&packed).unwrap();
+        assert_eq!(dynamic_fee, unpacked);
+
+        let mut packed = vec![];
+        packed.extend_from_slice(&PARAMETERS.min_fee_numerator.to_le_bytes());
+        packed.extend_from_slice(&PARAMETERS.max_fee_numerator.to_le_bytes());
+        packed.extend_from_slice(&PARAMETERS.fee_denominator.to_le_bytes());
+        packed.extend_from_slice(&PARAMETERS.volatility_multiplier.to_le_bytes());
+        packed.extend_from_slice(&PARAMETERS.decay_period.to_le_bytes());
+        packed.extend_from_slice(&12_345u64.to_le_bytes());
+        packed.extend_from_slice(&(3u128 << 64).to_le_bytes());
+        packed.extend_from_slice(&99u64.to_le_bytes());
+        let unpacked = DynamicFee::unpack_from_slice(&packed).unwrap();
+        assert_eq!(dynamic_fee, unpacked);
+    }
+
+    #[test]
+    fn validate_parameters() {
+        PARAMETERS.validate().unwrap();
+        DynamicFeeParameters::default().validate().unwrap();
+        let parameters = DynamicFeeParameters {
+            min_fee_numerator: 201,
+            ..PARAMETERS
+        };
+        assert_eq!(parameters.validate(), Err(SwapError::InvalidFee));
+        let parameters = DynamicFeeParameters {
+            max_fee_numerator: 10_000,
+            ..PARAMETERS
+        };
+        assert_eq!(parameters.validate(), Err(SwapError::InvalidFee));
+    }
+
+    #[test]
+    fn fee_follows_volatility() {
+        let mut dynamic_fee = DynamicFee::new(PARAMETERS);
+        dynamic_fee.observe(10, Some(&price(2, 1)));
+        assert_eq!(dynamic_fee.volatility, 0);
+        assert_eq!(dynamic_fee.trade_fee_numerator(), 25);
+
+        // 1% move, half of it added to the fee
+        dynamic_fee.observe(10, Some(&price(202, 100)));
+        assert_eq!(dynamic_fee.volatility, VOLATILITY_PRECISION / 100);
+        assert_eq!(dynamic_fee.trade_fee_numerator(), 25 + 50);
+
+        // moving back adds to the volatility
+        dynamic_fee.observe(10, Some(&price(2, 1)));
+        assert!(dynamic_fee.volatility > VOLATILITY_PRECISION / 100);
+
+        // halved after the decay period
+        let volatility = dynamic_fee.volatility;
+        dynamic_fee.observe(20, Some(&price(2, 1)));
+        assert_eq!(dynamic_fee.volatility, volatility / 2);
+
+        // capped by the maximum fee
+        dynamic_fee.observe(20, Some(&price(3, 1)));
+        assert_eq!(dynamic_fee.trade_fee_numerator(), 200);
+
+        // decays back to the minimum fee
+        dynamic_fee.observe(u64::MAX, None);
+        assert_eq!(dynamic_fee.volatility, 0);
+        assert_eq!(dynamic_fee.trade_fee_numerator(), 25);
+    }
+
+    #[test]
+    fn trade_pays_its_own_movement() {
+        let mut dynamic_fee = DynamicFee::new(PARAMETERS);
+        dynamic_fee.observe(10, Some(&price(2, 1)));
+        assert_eq!(dynamic_fee.trade_fee_numerator(), 25);
+
+        // a trade moving the price by 1% pays for the move, without touching
+        // the recorded volatility
+        let after_trade = dynamic_fee.after_trade(Some(&price(198, 100)));
+        assert_eq!(after_trade.trade_fee_numerator(), 25 + 50);
+        assert_eq!(after_trade.last_update_slot, 10);
+        assert_eq!(dynamic_fee.volatility, 0);
+
+        // the volatility does not decay within the slot of the trade
+        let dynamic_fee = DynamicFee {
+            volatility: VOLATILITY_PRECISION / 100,
+            last_update_slot: 5,
+            ..dynamic_fee
+        };
+        let after_trade = dynamic_fee.after_trade(Some(&price(2, 1)));
+        assert_eq!(after_trade.volatility, VOLATILITY_PRECISION / 100);
+    }
+
+    #[test]
+    fn disabled_fee() {
+        let mut dynamic_fee = DynamicFee::default();
+        dynamic_fee.observe(10, Some(&price(2, 1)));
+        dynamic_fee.observe(10, Some(&price(3, 1)));
+        assert_eq!(dynamic_fee, DynamicFee::default());
+
+        let fees = Fees {
+            trade_fee_numerator: 25,
+            trade_fee_denominator: 10_000,
+            owner_trade_fee_numerator: 5,
+            owner_trade_fee_denominator: 10_000,
+            ..Fees::default()
+        };
+        assert_eq!(dynamic_fee.apply(&fees), fees);
+        let dynamic_fee = DynamicFee {
+            volatility: VOLATILITY_PRECISION / 100,
+            ..DynamicFee::new(PARAMETERS)
+        };
+        assert_eq!(
+            dynamic_fee.apply(&fees),
+            Fees {
+                trade_fee_numerator: 75,
+                ..fees
+            }
+        );
+    }
+}
@@ -90,6 +90,18 @@ impl Fees {
         )
     }
 
+    /// Calculate the trading fee incurred on a single-sided deposit or
+    /// withdrawal, as if *half* the amount were swapped for the other side.
+    /// Reference at:
+    /// https://github.com/balancer-labs/balancer-core/blob/f4ed5d65362a8d6cec21662fb6eae233b0babc1f/contracts/BMath.sol#L117
+    pub fn single_token_trading_fee(&self, trading_tokens: u128) -> Option<u128> {
+        if trading_tokens == 0 {
+            return Some(0);
+        }
+        let half_trading_tokens = std::cmp::max(1, trading_tokens.checked_div(2)?);
+        self.trading_fee(half_trading_tokens)
+    }
+
     /// Calculate the owner trading fee in trading tokens
     pub fn owner_trading_fee(&self, trading_tokens: u128) -> Option<u128> {
         calculate_fee(
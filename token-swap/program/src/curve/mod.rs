@@ -2,8 +2,12 @@
 
 pub mod base;
 pub mod calculator;
+pub mod concentrated_liquidity;
 pub mod constant_price;
 pub mod constant_product;
+pub mod dynamic_fee;
 pub mod fees;
 pub mod offset;
+pub mod proactive_market_maker;
 pub mod stable;
+pub mod weighted;
@@ -8,7 +8,7 @@ use {
                 TradingTokenResult,
             },
             constant_product::{
-                normalized_value, pool_tokens_to_trading_tokens, swap,
+                normalized_value, pool_tokens_to_trading_tokens, spot_price, swap,
                 trading_tokens_to_pool_tokens,
             },
         },
@@ -135,6 +135,20 @@ impl CurveCalculator for OffsetCurve {
             swap_token_b_amount.checked_add(token_b_offset)?,
         )
     }
+
+    /// The spot price of the offset curve also adds the offset to the token B
+    /// side before calculating
+    fn spot_price(
+        &self,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+    ) -> Option<PreciseNumber> {
+        let token_b_offset = self.token_b_offset as u128;
+        spot_price(
+            swap_token_a_amount,
+            swap_token_b_amount.checked_add(token_b_offset)?,
+        )
+    }
 }
 
 /// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
@@ -194,6 +208,24 @@ mod tests {
         assert_eq!(curve, unpacked);
     }
 
+    #[test]
+    fn spot_price_with_offset() {
+        let token_b_offset = 150;
+        let curve = OffsetCurve { token_b_offset };
+        let price = curve.spot_price(100, 100).unwrap();
+        assert_eq!(
+            price,
+            PreciseNumber::new(5)
+                .unwrap()
+                .checked_div(&PreciseNumber::new(2).unwrap())
+                .unwrap()
+        );
+        // with no token B, the price only comes from the offset
+        let price = curve.spot_price(50, 0).unwrap();
+        assert_eq!(price, PreciseNumber::new(3).unwrap());
+        assert!(curve.spot_price(0, 100).is_none());
+    }
+
     #[test]
     fn swap_no_offset() {
         let swap_source_amount: u128 = 1_000;
@@ -0,0 +1,1092 @@
+//! Oracle-anchored proactive market maker calculator, similar to DODO's PMM.
+//!
+//! The pool quotes the oracle price while it holds the same value of both
+//! tokens, and moves its price away from the oracle price as it becomes
+//! imbalanced, by an amount set by the slippage parameter k.  With k = 0, the
+//! pool trades at the oracle price until one side runs out, and with k = 1,
+//! it trades like a constant product pool holding the same value of both
+//! tokens at the oracle price.
+//!
+//! Amounts are valued in token B at the oracle price, as unsigned Q64.64
+//! numbers.  With `x` the value of the side of the pool holding more than its
+//! equilibrium value, `y` the value of the other side, and `z` the value of
+//! each side at equilibrium, the pool stays on the curve:
+//!
+//! y = z - (x - z) * (1 - k + k * z / x)
+//!
+//! Since the equilibrium is the point of the curve where both sides have the
+//! same value, it only depends on the current amounts and the oracle price,
+//! and trades along the curve leave it unchanged.
+
+use {
+    crate::{
+        curve::{
+            calculator::{
+                CurveCalculator, DynPack, RoundDirection, SwapWithoutFeesResult, TradeDirection,
+                TradingTokenResult,
+            },
+            concentrated_liquidity::{div_round, integer_sqrt},
+            constant_product::pool_tokens_to_trading_tokens,
+        },
+        error::SwapError,
+        oracle::{OracleFeed, PRICE_FRACTIONAL_BITS},
+    },
+    arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
+    solana_program::{
+        clock::Slot,
+        program_error::ProgramError,
+        program_pack::{IsInitialized, Pack, Sealed},
+        pubkey::Pubkey,
+    },
+    spl_math::{
+        precise_number::{PreciseNumber, ONE},
+        uint::U256,
+    },
+    std::convert::TryFrom,
+};
+
+/// Denominator of the slippage parameter k
+pub const K_DENOMINATOR: u64 = 1_000_000;
+
+/// Oracle confidence limits are in basis points of the oracle price, so at
+/// most the whole price
+pub const MAX_ORACLE_CONFIDENCE_BPS: u16 = 10_000;
+
+/// Number of bits kept for the values of the pool, so that the products of two
+/// values and the slippage parameter fit in a U256
+const VALUE_BITS: usize = 100;
+
+fn q64() -> U256 {
+    U256::one() << PRICE_FRACTIONAL_BITS
+}
+
+fn sqrt_round(value: U256, round_up: bool) -> Option<U256> {
+    let root = integer_sqrt(value);
+    if round_up && root.checked_mul(root)? != value {
+        root.checked_add(U256::one())
+    } else {
+        Some(root)
+    }
+}
+
+/// Number of bits to drop from the given values so that they all fit within
+/// `VALUE_BITS`
+fn value_shift(values: &[U256]) -> usize {
+    let bits = values.iter().map(|value| value.bits()).max().unwrap_or(0);
+    bits.saturating_sub(VALUE_BITS)
+}
+
+fn shift_down(value: U256, shift: usize, round_up: bool) -> Option<U256> {
+    let shifted = value >> shift;
+    if round_up && shifted << shift != value {
+        shifted.checked_add(U256::one())
+    } else {
+        Some(shifted)
+    }
+}
+
+/// Q64.64 prices of the source and destination tokens, denominated in token B
+fn token_prices(trade_direction: TradeDirection, oracle_price: u128) -> (U256, U256) {
+    match trade_direction {
+        TradeDirection::AtoB => (U256::from(oracle_price), q64()),
+        TradeDirection::BtoA => (q64(), U256::from(oracle_price)),
+    }
+}
+
+/// Values of tokens A and B, denominated in token B, as unsigned Q64.64 numbers
+fn token_values(
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+    oracle_price: u128,
+) -> Option<(U256, U256)> {
+    Some((
+        U256::from(swap_token_a_amount).checked_mul(U256::from(oracle_price))?,
+        U256::from(swap_token_b_amount).checked_mul(q64())?,
+    ))
+}
+
+/// Value of each side of the pool at equilibrium, given the values of both
+/// sides, which must fit within `VALUE_BITS`.  With x the value of the side
+/// holding more than its equilibrium value, and y the value of the other side:
+///
+/// z = x * ((1 - k) * x + y) / (sqrt((1 - k) * x ^ 2 + k * x * y) + (1 - k) * x)
+///
+/// This reduces to the average of the values with k = 0, and to their
+/// geometric mean with k = 1.
+fn equilibrium_value_of_sides(
+    value: U256,
+    other_value: U256,
+    k: u64,
+    round_up: bool,
+) -> Option<U256> {
+    let (long_value, short_value) = if value >= other_value {
+        (value, other_value)
+    } else {
+        (other_value, value)
+    };
+    let k_denominator = U256::from(K_DENOMINATOR);
+    let one_minus_k = k_denominator.checked_sub(U256::from(k))?;
+    let k = U256::from(k);
+    let scaled_long_value = one_minus_k.checked_mul(long_value)?;
+    let numerator = long_value
+        .checked_mul(scaled_long_value.checked_add(k_denominator.checked_mul(short_value)?)?)?;
+    let root = sqrt_round(
+        k_denominator
+            .checked_mul(long_value)?
+            .checked_mul(scaled_long_value.checked_add(k.checked_mul(short_value)?)?)?,
+        !round_up,
+    )?;
+    let denominator = root.checked_add(scaled_long_value)?;
+    if denominator.is_zero() {
+        // k = 1 with one side empty, a constant product pool has no value
+        return Some(U256::zero());
+    }
+    div_round(numerator, denominator, round_up)
+}
+
+/// Value of the destination side of the pool once the source side reaches the
+/// given value, staying on the curve with the given equilibrium value.  All
+/// values must fit within `VALUE_BITS`, and the result is rounded up, against
+/// the trader.
+fn destination_value_on_curve(source_value: U256, equilibrium_value: U256, k: u64) -> Option<U256> {
+    let k_denominator = U256::from(K_DENOMINATOR);
+    let one_minus_k = k_denominator.checked_sub(U256::from(k))?;
+    let k = U256::from(k);
+    if source_value >= equilibrium_value {
+        // the source side is long, so with x the source value:
+        // y = z - (x - z) * ((1 - k) * x + k * z) / x
+        let excess = source_value.checked_sub(equilibrium_value)?;
+        let slope = one_minus_k
+            .checked_mul(source_value)?
+            .checked_add(k.checked_mul(equilibrium_value)?)?;
+        let decrease = div_round(
+            excess.checked_mul(slope)?,
+            k_denominator.checked_mul(source_value)?,
+            false,
+        )?;
+        equilibrium_value.checked_sub(decrease)
+    } else {
+        // the destination side is long, so solving the curve for its value y,
+        // with x the source value, gives:
+        // (1 - k) * y ^ 2 + (x - 2 * (1 - k) * z) * y - k * z ^ 2 = 0
+        let scaled_source_value = k_denominator.checked_mul(source_value)?;
+        let scaled_equilibrium_value = one_minus_k
+            .checked_mul(U256::from(2))?
+            .checked_mul(equilibrium_value)?;
+        let c = k
+            .checked_mul(equilibrium_value)?
+            .checked_mul(equilibrium_value)?;
+        let four_ac = one_minus_k.checked_mul(U256::from(4))?.checked_mul(c)?;
+        if scaled_source_value >= scaled_equilibrium_value {
+            // take the root without cancellation, y = 2c / (b + sqrt(b^2 + 4ac))
+            let b = scaled_source_value.checked_sub(scaled_equilibrium_value)?;
+            let root = sqrt_round(b.checked_mul(b)?.checked_add(four_ac)?, false)?;
+            div_round(c.checked_mul(U256::from(2))?, b.checked_add(root)?, true)
+        } else {
+            // y = (-b + sqrt(b^2 + 4ac)) / 2(1 - k)
+            let negative_b = scaled_equilibrium_value.checked_sub(scaled_source_value)?;
+            let root = sqrt_round(
+                negative_b.checked_mul(negative_b)?.checked_add(four_ac)?,
+                true,
+            )?;
+            div_round(
+                negative_b.checked_add(root)?,
+                one_minus_k.checked_mul(U256::from(2))?,
+                true,
+            )
+        }
+    }
+}
+
+/// Value of each side of the pool at equilibrium, denominated in token B, as
+/// an unsigned Q64.64 number
+pub fn equilibrium_value(
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+    k: u64,
+    oracle_price: u128,
+    round_up: bool,
+) -> Option<U256> {
+    let (token_a_value, token_b_value) =
+        token_values(swap_token_a_amount, swap_token_b_amount, oracle_price)?;
+    let shift = value_shift(&[token_a_value, token_b_value]);
+    let value = equilibrium_value_of_sides(
+        shift_down(token_a_value, shift, round_up)?,
+        shift_down(token_b_value, shift, round_up)?,
+        k,
+        round_up,
+    )?;
+    value.checked_mul(U256::one() << shift)
+}
+
+/// Proactive market maker swap, moving along the curve of the pool without
+/// changing its equilibrium.  The destination amount is rounded down, and the
+/// swap fails if it would take more than the pool holds.
+pub fn swap(
+    source_amount: u128,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+    trade_direction: TradeDirection,
+    k: u64,
+    oracle_price: u128,
+) -> Option<SwapWithoutFeesResult> {
+    let (source_price, destination_price) = token_prices(trade_direction, oracle_price);
+    let source_value = U256::from(swap_source_amount).checked_mul(source_price)?;
+    let destination_value = U256::from(swap_destination_amount).checked_mul(destination_price)?;
+    let new_source_value =
+        U256::from(swap_source_amount.checked_add(source_amount)?).checked_mul(source_price)?;
+    let shift = value_shift(&[source_value, destination_value, new_source_value]);
+
+    // a larger equilibrium, or a smaller source side, leaves more in the pool
+    let equilibrium_value = equilibrium_value_of_sides(
+        shift_down(source_value, shift, true)?,
+        shift_down(destination_value, shift, true)?,
+        k,
+        true,
+    )?;
+    let new_destination_value = destination_value_on_curve(
+        shift_down(new_source_value, shift, false)?,
+        equilibrium_value,
+        k,
+    )?
+    .checked_mul(U256::one() << shift)?;
+    let new_swap_destination_amount =
+        u128::try_from(div_round(new_destination_value, destination_price, true)?).ok()?;
+    Some(SwapWithoutFeesResult {
+        source_amount_swapped: source_amount,
+        destination_amount_swapped: swap_destination_amount
+            .saturating_sub(new_swap_destination_amount),
+    })
+}
+
+/// Get the amount of pool tokens for the given amount of token A or B.  Since
+/// swaps keep the equilibrium value unchanged, and deposits of both tokens
+/// increase it in proportion, the pool tokens are issued in proportion to the
+/// increase of the equilibrium value.
+#[allow(clippy::too_many_arguments)]
+pub fn trading_tokens_to_pool_tokens(
+    source_amount: u128,
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+    pool_supply: u128,
+    trade_direction: TradeDirection,
+    round_direction: RoundDirection,
+    k: u64,
+    oracle_price: u128,
+) -> Option<u128> {
+    let round_up = round_direction == RoundDirection::Ceiling;
+    let (new_swap_token_a_amount, new_swap_token_b_amount) = match trade_direction {
+        TradeDirection::AtoB => (
+            swap_token_a_amount.checked_add(source_amount)?,
+            swap_token_b_amount,
+        ),
+        TradeDirection::BtoA => (
+            swap_token_a_amount,
+            swap_token_b_amount.checked_add(source_amount)?,
+        ),
+    };
+    let (token_a_value, token_b_value) =
+        token_values(swap_token_a_amount, swap_token_b_amount, oracle_price)?;
+    let (new_token_a_value, new_token_b_value) = token_values(
+        new_swap_token_a_amount,
+        new_swap_token_b_amount,
+        oracle_price,
+    )?;
+    let shift = value_shift(&[new_token_a_value, new_token_b_value]);
+    let value = equilibrium_value_of_sides(
+        shift_down(token_a_value, shift, !round_up)?,
+        shift_down(token_b_value, shift, !round_up)?,
+        k,
+        !round_up,
+    )?;
+    let new_value = equilibrium_value_of_sides(
+        shift_down(new_token_a_value, shift, round_up)?,
+        shift_down(new_token_b_value, shift, round_up)?,
+        k,
+        round_up,
+    )?;
+    let value_added = if new_value > value {
+        new_value.checked_sub(value)?
+    } else {
+        U256::zero()
+    };
+    let pool_tokens = div_round(
+        U256::from(pool_supply).checked_mul(value_added)?,
+        value,
+        round_up,
+    )?;
+    u128::try_from(pool_tokens).ok()
+}
+
+/// Spot price of token A, denominated in token B.  The price is the oracle
+/// price at equilibrium, and otherwise the oracle price adjusted by
+/// `1 - k + k * (z / x) ^ 2`, which is applied against the token the pool holds
+/// too much of.
+pub fn spot_price(
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+    k: u64,
+    oracle_price: u128,
+) -> Option<PreciseNumber> {
+    let (token_a_value, token_b_value) =
+        token_values(swap_token_a_amount, swap_token_b_amount, oracle_price)?;
+    let long_value = std::cmp::max(token_a_value, token_b_value);
+    if long_value.is_zero() {
+        return None;
+    }
+    let equilibrium_value = equilibrium_value(
+        swap_token_a_amount,
+        swap_token_b_amount,
+        k,
+        oracle_price,
+        false,
+    )?;
+    let k_denominator = U256::from(K_DENOMINATOR);
+    let equilibrium_ratio = div_round(equilibrium_value.checked_mul(q64())?, long_value, false)?;
+    let squared_ratio = div_round(
+        equilibrium_ratio.checked_mul(equilibrium_ratio)?,
+        q64(),
+        false,
+    )?;
+    let adjustment = k_denominator
+        .checked_sub(U256::from(k))?
+        .checked_mul(q64())?
+        .checked_add(U256::from(k).checked_mul(squared_ratio)?)?;
+    let oracle_price = U256::from(oracle_price);
+    let price = if token_a_value >= token_b_value {
+        div_round(
+            oracle_price.checked_mul(adjustment)?,
+            k_denominator.checked_mul(q64())?,
+            false,
+        )?
+    } else {
+        div_round(
+            oracle_price
+                .checked_mul(k_denominator)?
+                .checked_mul(q64())?,
+            adjustment,
+            false,
+        )?
+    };
+    let value = div_round(price.checked_mul(U256::from(ONE))?, q64(), false)?;
+    Some(PreciseNumber { value })
+}
+
+/// Proactive market maker curve, pricing trades around an oracle price
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProactiveMarketMakerCurve {
+    /// Slippage parameter k, as a fraction of `K_DENOMINATOR`.  0 trades at
+    /// the oracle price, and `K_DENOMINATOR` trades like a constant product
+    /// pool around the oracle price.
+    pub k: u64,
+    /// Price of token A, denominated in token B, last read from the oracle, as
+    /// an unsigned Q64.64 number
+    pub oracle_price: u128,
+    /// Largest number of slots between the publication of an oracle price
+    /// and its use by the pool
+    pub max_oracle_staleness_slots: u32,
+    /// Largest oracle confidence interval accepted, in basis points of the
+    /// oracle price
+    pub max_oracle_confidence_bps: u16,
+}
+
+impl ProactiveMarketMakerCurve {
+    /// Price of an oracle feed as an unsigned Q64.64 number, rejecting prices
+    /// published too long before the given slot, or too uncertain
+    pub fn oracle_feed_price(&self, feed: &OracleFeed, slot: Slot) -> Result<u128, SwapError> {
+        if slot.saturating_sub(feed.publish_slot) > u64::from(self.max_oracle_staleness_slots) {
+            return Err(SwapError::StaleOraclePrice);
+        }
+        let max_confidence = u128::from(feed.price) * u128::from(self.max_oracle_confidence_bps);
+        if u128::from(feed.confidence) * u128::from(MAX_ORACLE_CONFIDENCE_BPS) > max_confidence {
+            return Err(SwapError::UncertainOraclePrice);
+        }
+        feed.fixed_price().ok_or(SwapError::InvalidOracle)
+    }
+}
+
+impl CurveCalculator for ProactiveMarketMakerCurve {
+    /// Proactive market maker swap at the last oracle price
+    fn swap_without_fees(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        swap(
+            source_amount,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_direction,
+            self.k,
+            self.oracle_price,
+        )
+    }
+
+    /// Deposits and withdrawals of both tokens are proportional to the pool
+    /// holdings, which keeps the equilibrium price at the oracle price
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<TradingTokenResult> {
+        pool_tokens_to_trading_tokens(
+            pool_tokens,
+            pool_token_supply,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            round_direction,
+        )
+    }
+
+    fn trading_tokens_to_pool_tokens(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        trading_tokens_to_pool_tokens(
+            source_amount,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+            round_direction,
+            self.k,
+            self.oracle_price,
+        )
+    }
+
+    fn validate(&self) -> Result<(), SwapError> {
+        if self.k > K_DENOMINATOR
+            || self.oracle_price == 0
+            || self.max_oracle_confidence_bps > MAX_ORACLE_CONFIDENCE_BPS
+        {
+            Err(SwapError::InvalidCurve)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The normalized value of the pool is the value of each side at
+    /// equilibrium, denominated in the token worth more, so that rounding by
+    /// one token of either side moves it by at most about one token
+    fn normalized_value(
+        &self,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+    ) -> Option<PreciseNumber> {
+        let value = equilibrium_value(
+            swap_token_a_amount,
+            swap_token_b_amount,
+            self.k,
+            self.oracle_price,
+            false,
+        )?;
+        let token_price = std::cmp::max(U256::from(self.oracle_price), q64());
+        let value = div_round(value.checked_mul(U256::from(ONE))?, token_price, false)?;
+        Some(PreciseNumber { value })
+    }
+
+    fn spot_price(
+        &self,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+    ) -> Option<PreciseNumber> {
+        spot_price(
+            swap_token_a_amount,
+            swap_token_b_amount,
+            self.k,
+            self.oracle_price,
+        )
+    }
+}
+
+/// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
+impl IsInitialized for ProactiveMarketMakerCurve {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+impl Sealed for ProactiveMarketMakerCurve {}
+impl Pack for ProactiveMarketMakerCurve {
+    const LEN: usize = 30;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        (self as &dyn DynPack).pack_into_slice(output);
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<ProactiveMarketMakerCurve, ProgramError> {
+        let input = array_ref![input, 0, 30];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (k, oracle_price, max_oracle_staleness_slots, max_oracle_confidence_bps) =
+            array_refs![input, 8, 16, 4, 2];
+        Ok(Self {
+            k: u64::from_le_bytes(*k),
+            oracle_price: u128::from_le_bytes(*oracle_price),
+            max_oracle_staleness_slots: u32::from_le_bytes(*max_oracle_staleness_slots),
+            max_oracle_confidence_bps: u16::from_le_bytes(*max_oracle_confidence_bps),
+        })
+    }
+}
+
+impl DynPack for ProactiveMarketMakerCurve {
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 30];
+        let (k, oracle_price, max_oracle_staleness_slots, max_oracle_confidence_bps) =
+            mut_array_refs![output, 8, 16, 4, 2];
+        *k = self.k.to_le_bytes();
+        *oracle_price = self.oracle_price.to_le_bytes();
+        *max_oracle_staleness_slots = self.max_oracle_staleness_slots.to_le_bytes();
+        *max_oracle_confidence_bps = self.max_oracle_confidence_bps.to_le_bytes();
+    }
+}
+
+/// State of a proactive market maker pool, stored after the swap state
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProactiveMarketMakerState {
+    /// Oracle price feed of token A, denominated in token B
+    pub oracle_feed: Pubkey,
+    /// Slot the oracle price was last read into the curve
+    pub last_refresh_slot: Slot,
+}
+
+impl IsInitialized for ProactiveMarketMakerState {
+    fn is_initialized(&self) -> bool {
+        self.oracle_feed != Pubkey::default()
+    }
+}
+
+impl Sealed for ProactiveMarketMakerState {}
+impl Pack for ProactiveMarketMakerState {
+    const LEN: usize = 40;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 40];
+        let (oracle_feed, last_refresh_slot) = mut_array_refs![output, 32, 8];
+        oracle_feed.copy_from_slice(self.oracle_feed.as_ref());
+        *last_refresh_slot = self.last_refresh_slot.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 40];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (oracle_feed, last_refresh_slot) = array_refs![input, 32, 8];
+        Ok(Self {
+            oracle_feed: Pubkey::new_from_array(*oracle_feed),
+            last_refresh_slot: Slot::from_le_bytes(*last_refresh_slot),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::{
+        calculator::{
+            test::{
+                check_curve_value_from_swap, check_pool_token_conversion,
+                check_pool_value_from_deposit, check_pool_value_from_withdraw,
+                check_spot_price_from_swap, total_and_intermediate,
+                CONVERSION_BASIS_POINTS_GUARANTEE,
+            },
+            INITIAL_SWAP_POOL_AMOUNT,
+        },
+        constant_product::ConstantProductCurve,
+    };
+    use proptest::prelude::*;
+
+    const Q64: u128 = 1 << 64;
+
+    #[test]
+    fn pack_curve() {
+        let k = 250_000;
+        let oracle_price = 3 * Q64 / 2;
+        let max_oracle_staleness_slots = 25u32;
+        let max_oracle_confidence_bps = 100u16;
+        let curve = ProactiveMarketMakerCurve {
+            k,
+            oracle_price,
+            max_oracle_staleness_slots,
+            max_oracle_confidence_bps,
+        };
+
+        let mut packed = [0u8; ProactiveMarketMakerCurve::LEN];
+        Pack::pack_into_slice(&curve, &mut packed[..]);
+        let unpacked = ProactiveMarketMakerCurve::unpack(&packed).unwrap();
+        assert_eq!(curve, unpacked);
+
+        let mut packed = vec![];
+        packed.extend_from_slice(&k.to_le_bytes());
+        packed.extend_from_slice(&oracle_price.to_le_bytes());
+        packed.extend_from_slice(&max_oracle_staleness_slots.to_le_bytes());
+        packed.extend_from_slice(&max_oracle_confidence_bps.to_le_bytes());
+        let unpacked = ProactiveMarketMakerCurve::unpack(&packed).unwrap();
+        assert_eq!(curve, unpacked);
+    }
+
+    #[test]
+    fn pack_state() {
+        let state = ProactiveMarketMakerState {
+            oracle_feed: Pubkey::new_from_array([3u8; 32]),
+            last_refresh_slot: 42,
+        };
+        let mut packed = [0u8; ProactiveMarketMakerState::LEN];
+        ProactiveMarketMakerState::pack(state.clone(), &mut packed).unwrap();
+        let unpacked = ProactiveMarketMakerState::unpack(&packed).unwrap();
+        assert_eq!(state, unpacked);
+
+        let mut packed = vec![3u8; 32];
+        packed.extend_from_slice(&42u64.to_le_bytes());
+        let unpacked = ProactiveMarketMakerState::unpack(&packed).unwrap();
+        assert_eq!(state, unpacked);
+    }
+
+    #[test]
+    fn validate_curve() {
+        let curve = ProactiveMarketMakerCurve {
+            k: K_DENOMINATOR,
+            oracle_price: Q64,
+            ..ProactiveMarketMakerCurve::default()
+        };
+        assert_eq!(curve.validate(), Ok(()));
+        let curve = ProactiveMarketMakerCurve {
+            k: K_DENOMINATOR + 1,
+            oracle_price: Q64,
+            ..ProactiveMarketMakerCurve::default()
+        };
+        assert_eq!(curve.validate(), Err(SwapError::InvalidCurve));
+        let curve = ProactiveMarketMakerCurve {
+            k: 0,
+            oracle_price: 0,
+            ..ProactiveMarketMakerCurve::default()
+        };
+        assert_eq!(curve.validate(), Err(SwapError::InvalidCurve));
+        let curve = ProactiveMarketMakerCurve {
+            k: 0,
+            oracle_price: Q64,
+            max_oracle_staleness_slots: 25,
+            max_oracle_confidence_bps: MAX_ORACLE_CONFIDENCE_BPS + 1,
+        };
+        assert_eq!(curve.validate(), Err(SwapError::InvalidCurve));
+    }
+
+    #[test]
+    fn oracle_feed_limits() {
+        let curve = ProactiveMarketMakerCurve {
+            k: 0,
+            oracle_price: Q64,
+            max_oracle_staleness_slots: 25,
+            max_oracle_confidence_bps: 100,
+        };
+        // 2.000 token B per token A, give or take 1%
+        let feed = OracleFeed {
+            version: 1,
+            publish_slot: 100,
+            price: 2_000,
+            confidence: 20,
+            exponent: -3,
+        };
+        assert_eq!(curve.oracle_feed_price(&feed, 125), Ok(2 * Q64));
+        assert_eq!(
+            curve.oracle_feed_price(&feed, 126),
+            Err(SwapError::StaleOraclePrice)
+        );
+        let uncertain_feed = OracleFeed {
+            confidence: 21,
+            ..feed.clone()
+        };
+        assert_eq!(
+            curve.oracle_feed_price(&uncertain_feed, 100),
+            Err(SwapError::UncertainOraclePrice)
+        );
+        let zero_feed = OracleFeed {
+            price: 0,
+            confidence: 0,
+            ..feed
+        };
+        assert_eq!(
+            curve.oracle_feed_price(&zero_feed, 100),
+            Err(SwapError::InvalidOracle)
+        );
+    }
+
+    #[test]
+    fn equilibrium_of_balanced_and_imbalanced_pools() {
+        // 1_000 token A worth 2 token B each, against 2_000 token B
+        let oracle_price = 2 * Q64;
+        for k in [0, 500_000, K_DENOMINATOR].iter() {
+            let value = equilibrium_value(1_000, 2_000, *k, oracle_price, false).unwrap();
+            assert_eq!(value, U256::from(2_000u128 * Q64));
+        }
+        // k = 0 averages the values, and k = 1 takes their geometric mean
+        let value = equilibrium_value(4_000, 2_000, 0, oracle_price, false).unwrap();
+        assert_eq!(value, U256::from(5_000u128 * Q64));
+        let value = equilibrium_value(4_000, 2_000, K_DENOMINATOR, oracle_price, false).unwrap();
+        assert_eq!(value, U256::from(4_000u128 * Q64));
+    }
+
+    #[test]
+    fn swap_at_equilibrium() {
+        let swap_token_a_amount = 1_000_000;
+        let swap_token_b_amount = 2_000_000;
+        let oracle_price = 2 * Q64;
+        let source_amount = 100_000;
+
+        // k = 0 trades at the oracle price
+        let curve = ProactiveMarketMakerCurve {
+            k: 0,
+            oracle_price,
+            ..ProactiveMarketMakerCurve::default()
+        };
+        let result = curve
+            .swap_without_fees(
+                source_amount,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::AtoB,
+            )
+            .unwrap();
+        assert_eq!(result.source_amount_swapped, source_amount);
+        assert_eq!(result.destination_amount_swapped, 200_000);
+
+        // k = 1 matches a constant product pool at the oracle price
+        let curve = ProactiveMarketMakerCurve {
+            k: K_DENOMINATOR,
+            oracle_price,
+            ..ProactiveMarketMakerCurve::default()
+        };
+        let result = curve
+            .swap_without_fees(
+                source_amount,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::AtoB,
+            )
+            .unwrap();
+        let expected = ConstantProductCurve {}
+            .swap_without_fees(
+                source_amount,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::AtoB,
+            )
+            .unwrap();
+        assert_eq!(
+            result.destination_amount_swapped,
+            expected.destination_amount_swapped
+        );
+
+        // in between, the output starting from equilibrium is
+        // amount * price * (1 - k + k * a / (a + amount))
+        let curve = ProactiveMarketMakerCurve {
+            k: 500_000,
+            oracle_price,
+            ..ProactiveMarketMakerCurve::default()
+        };
+        let result = curve
+            .swap_without_fees(
+                source_amount,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::AtoB,
+            )
+            .unwrap();
+        assert_eq!(result.destination_amount_swapped, 190_909);
+        let result = curve
+            .swap_without_fees(
+                2 * source_amount,
+                swap_token_b_amount,
+                swap_token_a_amount,
+                TradeDirection::BtoA,
+            )
+            .unwrap();
+        assert_eq!(result.destination_amount_swapped, 95_454);
+    }
+
+    #[test]
+    fn swap_back_towards_equilibrium() {
+        let oracle_price = Q64;
+        let curve = ProactiveMarketMakerCurve {
+            k: 500_000,
+            oracle_price,
+            ..ProactiveMarketMakerCurve::default()
+        };
+        let result = curve
+            .swap_without_fees(100_000, 1_000_000, 1_000_000, TradeDirection::AtoB)
+            .unwrap();
+        // trading the output back returns to the same point of the curve,
+        // less the rounding
+        let result = curve
+            .swap_without_fees(
+                result.destination_amount_swapped,
+                1_000_000 - result.destination_amount_swapped,
+                1_100_000,
+                TradeDirection::BtoA,
+            )
+            .unwrap();
+        assert!(result.destination_amount_swapped <= 100_000);
+        assert!(result.destination_amount_swapped >= 99_998);
+    }
+
+    #[test]
+    fn swap_exceeding_pool() {
+        // at a constant price, the pool runs out of token B
+        let curve = ProactiveMarketMakerCurve {
+            k: 0,
+            oracle_price: Q64,
+            ..ProactiveMarketMakerCurve::default()
+        };
+        assert!(curve
+            .swap_without_fees(1_000_001, 1_000_000, 1_000_000, TradeDirection::AtoB)
+            .is_none());
+        let result = curve
+            .swap_without_fees(1_000_000, 1_000_000, 1_000_000, TradeDirection::AtoB)
+            .unwrap();
+        assert_eq!(result.destination_amount_swapped, 1_000_000);
+    }
+
+    #[test]
+    fn spot_price_around_oracle_price() {
+        let oracle_price = 2 * Q64;
+        let curve = ProactiveMarketMakerCurve {
+            k: 500_000,
+            oracle_price,
+            ..ProactiveMarketMakerCurve::default()
+        };
+        let two = PreciseNumber::new(2).unwrap();
+        let precision = U256::from(1_000);
+        let price = curve.spot_price(1_000, 2_000).unwrap();
+        assert!(price.almost_eq(&two, precision));
+        // too much token A makes it cheaper, too little makes it more expensive
+        let price = curve.spot_price(2_000, 2_000).unwrap();
+        assert!(price.less_than(&two));
+        let price = curve.spot_price(500, 2_000).unwrap();
+        assert!(price.greater_than(&two));
+        // a constant price curve always quotes the oracle price
+        let curve = ProactiveMarketMakerCurve {
+            k: 0,
+            oracle_price,
+            ..ProactiveMarketMakerCurve::default()
+        };
+        let price = curve.spot_price(2_000, 500).unwrap();
+        assert!(price.almost_eq(&two, precision));
+        assert!(curve.spot_price(0, 0).is_none());
+    }
+
+    prop_compose! {
+        fn curve()(
+            k in 0..=K_DENOMINATOR,
+            oracle_price in Q64 / 1_000..1_000 * Q64,
+        ) -> ProactiveMarketMakerCurve {
+            ProactiveMarketMakerCurve { k, oracle_price, ..ProactiveMarketMakerCurve::default() }
+        }
+    }
+
+    /// Keep trades under half of the destination side at the oracle price, so
+    /// that no curve empties the pool
+    fn within_pool_value(
+        curve: &ProactiveMarketMakerCurve,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> bool {
+        let (source_price, destination_price) = token_prices(trade_direction, curve.oracle_price);
+        let source_value = U256::from(source_amount) * source_price;
+        let destination_value = U256::from(swap_destination_amount) * destination_price;
+        swap_source_amount > 0 && source_value * U256::from(2) <= destination_value
+    }
+
+    proptest! {
+        #[test]
+        fn pool_token_conversion_a_to_b(
+            curve in curve(),
+            // in the pool token conversion calcs, we simulate trading half of
+            // source_token_amount, so this needs to be at least 2
+            source_token_amount in 2..u32::MAX as u128,
+            swap_source_amount in 1_000..u64::MAX as u128,
+            swap_destination_amount in 1_000..u64::MAX as u128,
+            pool_supply in INITIAL_SWAP_POOL_AMOUNT..u64::MAX as u128,
+        ) {
+            prop_assume!(within_pool_value(
+                &curve,
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            ));
+            check_pool_token_conversion(
+                &curve,
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+                pool_supply,
+                CONVERSION_BASIS_POINTS_GUARANTEE,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn pool_token_conversion_b_to_a(
+            curve in curve(),
+            source_token_amount in 2..u32::MAX as u128,
+            swap_source_amount in 1_000..u64::MAX as u128,
+            swap_destination_amount in 1_000..u64::MAX as u128,
+            pool_supply in INITIAL_SWAP_POOL_AMOUNT..u64::MAX as u128,
+        ) {
+            prop_assume!(within_pool_value(
+                &curve,
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::BtoA,
+            ));
+            check_pool_token_conversion(
+                &curve,
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::BtoA,
+                pool_supply,
+                CONVERSION_BASIS_POINTS_GUARANTEE,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn curve_value_does_not_decrease_from_swap_a_to_b(
+            curve in curve(),
+            source_token_amount in 1..u32::MAX as u128,
+            swap_source_amount in 1..u64::MAX as u128,
+            swap_destination_amount in 1..u64::MAX as u128,
+        ) {
+            prop_assume!(within_pool_value(
+                &curve,
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            ));
+            check_curve_value_from_swap(
+                &curve,
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn curve_value_does_not_decrease_from_swap_b_to_a(
+            curve in curve(),
+            source_token_amount in 1..u32::MAX as u128,
+            swap_source_amount in 1..u64::MAX as u128,
+            swap_destination_amount in 1..u64::MAX as u128,
+        ) {
+            prop_assume!(within_pool_value(
+                &curve,
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::BtoA,
+            ));
+            check_curve_value_from_swap(
+                &curve,
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::BtoA
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn spot_price_bounds_swap(
+            curve in curve(),
+            source_token_amount in 1..u32::MAX as u128,
+            swap_token_a_amount in 1..u64::MAX as u128,
+            swap_token_b_amount in 1..u64::MAX as u128,
+        ) {
+            prop_assume!(within_pool_value(
+                &curve,
+                source_token_amount,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::AtoB,
+            ));
+            prop_assume!(within_pool_value(
+                &curve,
+                source_token_amount,
+                swap_token_b_amount,
+                swap_token_a_amount,
+                TradeDirection::BtoA,
+            ));
+            check_spot_price_from_swap(
+                &curve,
+                source_token_amount,
+                swap_token_a_amount,
+                swap_token_b_amount,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn curve_value_does_not_decrease_from_deposit(
+            curve in curve(),
+            pool_token_amount in 1..u64::MAX as u128,
+            pool_token_supply in 1..u64::MAX as u128,
+            swap_token_a_amount in 1..u64::MAX as u128,
+            swap_token_b_amount in 1..u64::MAX as u128,
+        ) {
+            // Make sure we will get at least one trading token out for each
+            // side, otherwise the calculation fails
+            prop_assume!(pool_token_amount * swap_token_a_amount / pool_token_supply >= 1);
+            prop_assume!(pool_token_amount * swap_token_b_amount / pool_token_supply >= 1);
+            check_pool_value_from_deposit(
+                &curve,
+                pool_token_amount,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn curve_value_does_not_decrease_from_withdraw(
+            curve in curve(),
+            (pool_token_supply, pool_token_amount) in total_and_intermediate(),
+            swap_token_a_amount in 1..u64::MAX as u128,
+            swap_token_b_amount in 1..u64::MAX as u128,
+        ) {
+            let pool_token_amount = pool_token_amount as u128;
+            let pool_token_supply = pool_token_supply as u128;
+            // Make sure we will get at least one trading token out for each
+            // side, otherwise the calculation fails
+            prop_assume!(pool_token_amount * swap_token_a_amount / pool_token_supply >= 1);
+            prop_assume!(pool_token_amount * swap_token_b_amount / pool_token_supply >= 1);
+            check_pool_value_from_withdraw(
+                &curve,
+                pool_token_amount,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+            );
+        }
+    }
+}
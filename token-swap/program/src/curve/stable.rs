@@ -16,7 +16,10 @@ use crate::curve::{
     },
 };
 use arrayref::{array_mut_ref, array_ref};
-use spl_math::{precise_number::PreciseNumber, uint::U256};
+use spl_math::{
+    precise_number::{PreciseNumber, ONE},
+    uint::U256,
+};
 use std::convert::TryFrom;
 
 const N_COINS: u8 = 2;
@@ -141,6 +144,34 @@ fn compute_new_destination_amount(
     u128::try_from(y).ok()
 }
 
+/// Compute the spot price of token A, denominated in token B
+/// Equation, taking the ratio of the partial derivatives of the invariant,
+/// with leverage Ann = A * n**n:
+/// price = (Ann + D**(n+1) / (n**n * x_a**2 * x_b)) / (Ann + D**(n+1) / (n**n * x_a * x_b**2))
+///       = (n**n * Ann * x_a * x_b + D**(n+1) / x_a) / (n**n * Ann * x_a * x_b + D**(n+1) / x_b)
+fn compute_spot_price(leverage: u64, amount_a: u128, amount_b: u128) -> Option<PreciseNumber> {
+    if amount_a == 0 || amount_b == 0 {
+        return None;
+    }
+    let d_val = U256::from(compute_d(leverage, amount_a, amount_b)?);
+    let d_cubed = checked_u8_power(&d_val, N_COINS.checked_add(1)?)?;
+    let amount_a = U256::from(amount_a);
+    let amount_b = U256::from(amount_b);
+    let leverage_product = checked_u8_mul(
+        &U256::from(leverage)
+            .checked_mul(amount_a)?
+            .checked_mul(amount_b)?,
+        N_COINS_SQUARED,
+    )?;
+
+    let numerator = leverage_product.checked_add(d_cubed.checked_div(amount_a)?)?;
+    let denominator = leverage_product.checked_add(d_cubed.checked_div(amount_b)?)?;
+    let value = numerator
+        .checked_mul(U256::from(ONE))?
+        .checked_div(denominator)?;
+    Some(PreciseNumber { value })
+}
+
 impl CurveCalculator for StableCurve {
     /// Stable curve
     fn swap_without_fees(
@@ -212,6 +243,15 @@ impl CurveCalculator for StableCurve {
         normalized_value(swap_token_a_amount, swap_token_b_amount)
     }
 
+    fn spot_price(
+        &self,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+    ) -> Option<PreciseNumber> {
+        let leverage = self.amp.checked_mul(N_COINS as u64)?;
+        compute_spot_price(leverage, swap_token_a_amount, swap_token_b_amount)
+    }
+
     fn validate(&self) -> Result<(), SwapError> {
         // TODO are all amps valid?
         Ok(())
@@ -249,9 +289,10 @@ impl DynPack for StableCurve {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::curve::calculator::{RoundDirection, INITIAL_SWAP_POOL_AMOUNT};
+    use crate::curve::calculator::{
+        test::check_spot_price_from_swap, RoundDirection, INITIAL_SWAP_POOL_AMOUNT,
+    };
     use proptest::prelude::*;
-    use sim::StableSwapModel;
 
     #[test]
     fn initial_pool_amount() {
@@ -302,46 +343,39 @@ mod tests {
         assert!(results.is_none());
     }
 
+    #[test]
+    fn spot_price_balanced_and_imbalanced() {
+        let calculator = StableCurve { amp: 100 };
+        let one = PreciseNumber::new(1).unwrap();
+        let price = calculator.spot_price(1_000_000, 1_000_000).unwrap();
+        assert!(price.almost_eq(&one, U256::from(1_000)));
+
+        // token A is scarce so it is worth more than token B, but much less
+        // than with the constant product curve
+        let price = calculator.spot_price(500_000, 1_500_000).unwrap();
+        assert!(price.greater_than(&one));
+        assert!(price.less_than(&PreciseNumber::new(3).unwrap()));
+        let inverse = calculator.spot_price(1_500_000, 500_000).unwrap();
+        assert!(inverse.less_than(&one));
+
+        assert!(calculator.spot_price(0, 1_000_000).is_none());
+        assert!(calculator.spot_price(1_000_000, 0).is_none());
+    }
+
     proptest! {
         #[test]
-        fn constant_product_swap_no_fee(
-            swap_source_amount in 100..1_000_000_000_000_000_000u128,
-            swap_destination_amount in 100..1_000_000_000_000_000_000u128,
-            source_amount in 100..100_000_000_000u128,
+        fn spot_price_bounds_swap(
+            source_token_amount in 1..u32::MAX,
+            swap_token_a_amount in 1..u32::MAX,
+            swap_token_b_amount in 1..u32::MAX,
             amp in 1..150u64
         ) {
-            prop_assume!(source_amount < swap_source_amount);
-
             let curve = StableCurve { amp };
-
-            let model: StableSwapModel = StableSwapModel::new(
-                curve.amp.into(),
-                vec![swap_source_amount, swap_destination_amount],
-                N_COINS,
-            );
-
-            let result = curve.swap_without_fees(
-                source_amount,
-                swap_source_amount,
-                swap_destination_amount,
-                TradeDirection::AtoB,
-            );
-
-            let result = result.unwrap();
-            let sim_result = model.sim_exchange(0, 1, source_amount);
-
-            let diff =
-                (sim_result as i128 - result.destination_amount_swapped as i128).abs();
-
-            assert!(
-                diff <= 1,
-                "result={}, sim_result={}, amp={}, source_amount={}, swap_source_amount={}, swap_destination_amount={}",
-                result.destination_amount_swapped,
-                sim_result,
-                amp,
-                source_amount,
-                swap_source_amount,
-                swap_destination_amount
+            check_spot_price_from_swap(
+                &curve,
+                source_token_amount as u128,
+                swap_token_a_amount as u128,
+                swap_token_b_amount as u128,
             );
         }
     }
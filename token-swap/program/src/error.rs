@@ -91,6 +91,43 @@ pub enum SwapError {
     /// The operation cannot be performed on the given curve
     #[error("The operation cannot be performed on the given curve")]
     UnsupportedCurveOperation,
+    /// Swaps and deposits are paused by the admin authority
+    #[error("Swaps and deposits are paused")]
+    SwapPaused,
+    /// The swap does not have an admin authority
+    #[error("The swap does not have an admin authority")]
+    MissingAdminAuthority,
+    /// The provided admin authority does not match the swap admin authority
+    #[error("Admin authority does not match the swap admin authority")]
+    InvalidAdminAuthority,
+    /// The route swap accounts do not describe one or more pools
+    #[error("Route swap accounts do not describe one or more pools")]
+    InvalidRoute,
+    /// The position range is unordered, out of bounds or not aligned on the tick spacing
+    #[error("Invalid tick range")]
+    InvalidTickRange,
+    /// The pool cannot track any more initialized ticks
+    #[error("Too many initialized ticks in the pool")]
+    TooManyTicks,
+    /// The position does not belong to the swap or owner
+    #[error("Position does not match the swap or owner")]
+    IncorrectPosition,
+    /// The position does not hold enough liquidity
+    #[error("Insufficient liquidity in the position")]
+    InsufficientLiquidity,
+    /// The oracle price feed does not match the swap, or has no valid price
+    #[error("Invalid oracle price feed")]
+    InvalidOracle,
+    /// The oracle price feed is stale, or the swap price was not refreshed
+    /// in the current slot
+    #[error("Oracle price is stale")]
+    StaleOraclePrice,
+    /// The position would hold less than the minimum liquidity of the pool
+    #[error("Position liquidity is below the pool minimum")]
+    PositionLiquidityTooLow,
+    /// The oracle price feed confidence interval is wider than the curve allows
+    #[error("Oracle price confidence interval is too wide")]
+    UncertainOraclePrice,
 }
 impl From<SwapError> for ProgramError {
     fn from(e: SwapError) -> Self {
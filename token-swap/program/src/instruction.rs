@@ -2,7 +2,7 @@
 
 #![allow(clippy::too_many_arguments)]
 
-use crate::curve::{base::SwapCurve, fees::Fees};
+use crate::curve::{base::SwapCurve, dynamic_fee::DynamicFeeParameters, fees::Fees};
 use crate::error::SwapError;
 use solana_program::{
     instruction::{AccountMeta, Instruction},
@@ -92,6 +92,96 @@ pub struct WithdrawSingleTokenTypeExactAmountOut {
     pub maximum_pool_token_amount: u64,
 }
 
+/// SetFees instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetFees {
+    /// new swap fees, must satisfy the program owner's constraints
+    pub fees: Fees,
+}
+
+/// SetDynamicFee instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetDynamicFee {
+    /// new dynamic fee parameters, all zeroes to charge the fixed trade fee
+    pub parameters: DynamicFeeParameters,
+}
+
+/// SetPaused instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetPaused {
+    /// pause swaps and deposits if true, resume them if false
+    pub paused: bool,
+}
+
+/// RouteSwap instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteSwap {
+    /// SOURCE amount to transfer into the first pool of the route
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token to output from the last pool of
+    /// the route, prevents excessive slippage
+    pub minimum_amount_out: u64,
+}
+
+/// OpenPosition instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpenPosition {
+    /// Lower bound of the price range, must be a multiple of the tick spacing
+    pub tick_lower: i32,
+    /// Upper bound of the price range, must be a multiple of the tick spacing
+    pub tick_upper: i32,
+}
+
+/// IncreaseLiquidity instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct IncreaseLiquidity {
+    /// Liquidity to add to the position.  token_a and token_b amounts are set
+    /// by the current price and the range of the position
+    pub liquidity: u128,
+    /// Maximum token A amount to deposit, prevents excessive slippage
+    pub maximum_token_a_amount: u64,
+    /// Maximum token B amount to deposit, prevents excessive slippage
+    pub maximum_token_b_amount: u64,
+}
+
+/// DecreaseLiquidity instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecreaseLiquidity {
+    /// Liquidity to remove from the position, or 0 to only collect fees
+    pub liquidity: u128,
+    /// Minimum amount of token A to receive, prevents excessive slippage
+    pub minimum_token_a_amount: u64,
+    /// Minimum amount of token B to receive, prevents excessive slippage
+    pub minimum_token_b_amount: u64,
+}
+
+/// InitializeWeightedPool instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct InitializeWeightedPool {
+    /// nonce used to create valid program address
+    pub nonce: u8,
+    /// all pool fees
+    pub fees: Fees,
+    /// weight of every token in the pool, in the order of the token accounts
+    pub weights: Vec<u64>,
+}
+
+/// Number of accounts given for every pool of a route swap
+pub const ROUTE_SWAP_HOP_ACCOUNTS: usize = 7;
+
 /// Instructions supported by the token swap program.
 #[repr(C)]
 #[derive(Debug, PartialEq)]
@@ -108,11 +198,20 @@ pub enum SwapInstruction {
     ///   6. `[writable]` Pool Token Account to deposit the initial pool token
     ///   supply.  Must be empty, not owned by swap authority.
     ///   7. '[]` Token program id
+    ///   8. `[signer, optional]` Admin authority, which can update the fees and
+    ///   pause the swap.  Without it the fees are fixed and the swap cannot be paused.
+    ///   A Token-swap sized for `SwapV1` is initialized without an admin authority.
+    ///
+    ///   Proactive market maker pools take their oracle price feed before the
+    ///   optional admin authority:
+    ///
+    ///   8. `[]` Oracle price feed of token A, denominated in token B
+    ///   9. `[signer, optional]` Admin authority
     Initialize(Initialize),
 
     ///   Swap the tokens in the pool.
     ///
-    ///   0. `[]` Token-swap
+    ///   0. `[writable]` Token-swap
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` token_(A|B) SOURCE Account, amount is transferable by user transfer authority,
@@ -129,7 +228,7 @@ pub enum SwapInstruction {
     ///   token representing ownership in the pool. Inputs are converted to
     ///   the current ratio.
     ///
-    ///   0. `[]` Token-swap
+    ///   0. `[writable]` Token-swap
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` token_a user transfer authority can transfer amount,
@@ -145,7 +244,7 @@ pub enum SwapInstruction {
     ///   pool tokens.  The pool tokens are burned in exchange for an equivalent
     ///   amount of token A and B.
     ///
-    ///   0. `[]` Token-swap
+    ///   0. `[writable]` Token-swap
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` Pool mint account, swap authority is the owner
@@ -162,7 +261,7 @@ pub enum SwapInstruction {
     ///   representing ownership into the pool. Input token is converted as if
     ///   a swap and deposit all token types were performed.
     ///
-    ///   0. `[]` Token-swap
+    ///   0. `[writable]` Token-swap
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` token_(A|B) SOURCE Account, amount is transferable by user transfer authority,
@@ -176,7 +275,7 @@ pub enum SwapInstruction {
     ///   Withdraw one token type from the pool at the current ratio given the
     ///   exact amount out expected.
     ///
-    ///   0. `[]` Token-swap
+    ///   0. `[writable]` Token-swap
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` Pool mint account, swap authority is the owner
@@ -187,6 +286,155 @@ pub enum SwapInstruction {
     ///   8. `[writable]` Fee account, to receive withdrawal fees
     ///   9. '[]` Token program id
     WithdrawSingleTokenTypeExactAmountOut(WithdrawSingleTokenTypeExactAmountOut),
+
+    ///   Update the fees of a swap created with an admin authority.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Admin authority
+    SetFees(SetFees),
+
+    ///   Pause or resume swaps and deposits on a swap created with an admin
+    ///   authority.  Withdrawals remain open while the swap is paused.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Admin authority
+    SetPaused(SetPaused),
+
+    ///   Swap the tokens through an ordered list of pools, the output of each
+    ///   pool being the input of the next one.  Slippage is only checked on
+    ///   the output of the last pool.
+    ///
+    ///   0. `[]` user transfer authority
+    ///   1. `[writable]` token_(A|B) SOURCE Account, amount is transferable by user transfer authority,
+    ///   2. '[]` Token program id
+    ///
+    ///   Followed by `ROUTE_SWAP_HOP_ACCOUNTS` accounts for every pool:
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[writable]` token_(A|B) Base Account to swap INTO.  Must be the SOURCE token.
+    ///   3. `[writable]` token_(A|B) Base Account to swap FROM.  Must be the DESTINATION token.
+    ///   4. `[writable]` token_(A|B) DESTINATION Account assigned to USER as the owner.  For all
+    ///      pools but the last one, the whole output is transferred from it into the next pool,
+    ///      so it must be transferable by user transfer authority.
+    ///   5. `[writable]` Pool token mint, to generate trading fees
+    ///   6. `[writable]` Fee account, to receive trading fees
+    ///
+    ///   Optionally followed by:
+    ///
+    ///   0. `[writable]` Host fee account to receive additional trading fees
+    ///      of the first pool
+    RouteSwap(RouteSwap),
+
+    ///   Open an empty position on a concentrated liquidity swap.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[writable]` Position account, uninitialized and owned by the swap program
+    ///   2. `[signer]` Position owner
+    OpenPosition(OpenPosition),
+
+    ///   Deposit tokens A and B into a position of a concentrated liquidity
+    ///   swap.  Fees earned by the position so far are set aside.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[signer]` user transfer authority
+    ///   3. `[writable]` Position account
+    ///   4. `[signer]` Position owner
+    ///   5. `[writable]` token_a user transfer authority can transfer amount,
+    ///   6. `[writable]` token_b user transfer authority can transfer amount,
+    ///   7. `[writable]` token_a Base Account to deposit into.
+    ///   8. `[writable]` token_b Base Account to deposit into.
+    ///   9. '[]` Token program id
+    IncreaseLiquidity(IncreaseLiquidity),
+
+    ///   Withdraw tokens A and B from a position of a concentrated liquidity
+    ///   swap, along with all the fees earned by the position.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[writable]` Position account
+    ///   3. `[signer]` Position owner
+    ///   4. `[writable]` token_a Swap Account to withdraw FROM.
+    ///   5. `[writable]` token_b Swap Account to withdraw FROM.
+    ///   6. `[writable]` token_a user Account to credit.
+    ///   7. `[writable]` token_b user Account to credit.
+    ///   8. '[]` Token program id
+    DecreaseLiquidity(DecreaseLiquidity),
+
+    ///   Initializes a new weighted pool, holding between two and eight tokens
+    ///   with a weight each.
+    ///
+    ///   0. `[writable, signer]` New weighted pool to create.
+    ///   1. `[]` pool authority derived from `create_program_address(&[weighted pool account])`
+    ///   2. `[writable]` Pool Token Mint. Must be empty, owned by pool authority.
+    ///   3. `[]` Pool Token Account to deposit trading fees.
+    ///   Must be empty, not owned by pool authority
+    ///   4. `[writable]` Pool Token Account to deposit the initial pool token
+    ///   supply.  Must be empty, not owned by pool authority.
+    ///   5. '[]` Token program id
+    ///   6. ..6+N `[]` token Accounts, one for each weight.  Must be non zero,
+    ///   owned by pool authority.
+    InitializeWeightedPool(InitializeWeightedPool),
+
+    ///   Swap any two tokens of a weighted pool.
+    ///
+    ///   0. `[]` Weighted pool
+    ///   1. `[]` pool authority
+    ///   2. `[]` user transfer authority
+    ///   3. `[writable]` SOURCE Account, amount is transferable by user transfer authority,
+    ///   4. `[writable]` Base Account to swap INTO.  Must be the SOURCE token.
+    ///   5. `[writable]` Base Account to swap FROM.  Must be the DESTINATION token.
+    ///   6. `[writable]` DESTINATION Account assigned to USER as the owner.
+    ///   7. `[writable]` Pool token mint, to generate trading fees
+    ///   8. `[writable]` Fee account, to receive trading fees
+    ///   9. '[]` Token program id
+    WeightedSwap(Swap),
+
+    ///   Deposit one type of tokens into a weighted pool.  The output is a
+    ///   "pool" token representing ownership into the pool.
+    ///
+    ///   0. `[]` Weighted pool
+    ///   1. `[]` pool authority
+    ///   2. `[]` user transfer authority
+    ///   3. `[writable]` SOURCE Account, amount is transferable by user transfer authority,
+    ///   4. `[writable]` Base Account to deposit INTO.  Must be the SOURCE token.
+    ///   5. `[writable]` Pool MINT account, pool authority is the owner.
+    ///   6. `[writable]` Pool Account to deposit the generated tokens, user is the owner.
+    ///   7. '[]` Token program id
+    WeightedDepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn),
+
+    ///   Withdraw one token type from a weighted pool given the exact amount
+    ///   out expected.
+    ///
+    ///   0. `[]` Weighted pool
+    ///   1. `[]` pool authority
+    ///   2. `[]` user transfer authority
+    ///   3. `[writable]` Pool mint account, pool authority is the owner
+    ///   4. `[writable]` SOURCE Pool account, amount is transferable by user transfer authority.
+    ///   5. `[writable]` Base Account to withdraw FROM.  Must be the DESTINATION token.
+    ///   6. `[writable]` DESTINATION Account assigned to USER as the owner.
+    ///   7. `[writable]` Fee account, to receive withdrawal fees
+    ///   8. '[]` Token program id
+    WeightedWithdrawSingleTokenTypeExactAmountOut(WithdrawSingleTokenTypeExactAmountOut),
+
+    ///   Read the oracle price feed of a proactive market maker pool into its
+    ///   curve.  Swaps and single token deposits and withdrawals require the
+    ///   price to be refreshed in the same slot.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` Oracle price feed of the swap
+    ///   2. `[]` token_a Base Account of the swap
+    ///   3. `[]` token_b Base Account of the swap
+    RefreshOraclePrice,
+
+    ///   Set the dynamic trade fee of a swap created with an admin authority,
+    ///   which replaces its fixed trade fee and rises with the volatility of
+    ///   the pool price.  The volatility starts from zero.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Admin authority
+    SetDynamicFee(SetDynamicFee),
 }
 
 impl SwapInstruction {
@@ -253,6 +501,119 @@ impl SwapInstruction {
                     maximum_pool_token_amount,
                 })
             }
+            6 => {
+                if rest.len() >= Fees::LEN {
+                    let (fees, _rest) = rest.split_at(Fees::LEN);
+                    let fees = Fees::unpack_unchecked(fees)?;
+                    Self::SetFees(SetFees { fees })
+                } else {
+                    return Err(SwapError::InvalidInstruction.into());
+                }
+            }
+            7 => {
+                let paused = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(SwapError::InvalidInstruction.into()),
+                };
+                Self::SetPaused(SetPaused { paused })
+            }
+            8 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
+                Self::RouteSwap(RouteSwap {
+                    amount_in,
+                    minimum_amount_out,
+                })
+            }
+            9 => {
+                let (tick_lower, rest) = Self::unpack_i32(rest)?;
+                let (tick_upper, _rest) = Self::unpack_i32(rest)?;
+                Self::OpenPosition(OpenPosition {
+                    tick_lower,
+                    tick_upper,
+                })
+            }
+            10 => {
+                let (liquidity, rest) = Self::unpack_u128(rest)?;
+                let (maximum_token_a_amount, rest) = Self::unpack_u64(rest)?;
+                let (maximum_token_b_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::IncreaseLiquidity(IncreaseLiquidity {
+                    liquidity,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                })
+            }
+            11 => {
+                let (liquidity, rest) = Self::unpack_u128(rest)?;
+                let (minimum_token_a_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_token_b_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DecreaseLiquidity(DecreaseLiquidity {
+                    liquidity,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                })
+            }
+            12 => {
+                let (&nonce, rest) = rest.split_first().ok_or(SwapError::InvalidInstruction)?;
+                if rest.len() >= Fees::LEN {
+                    let (fees, rest) = rest.split_at(Fees::LEN);
+                    let fees = Fees::unpack_unchecked(fees)?;
+                    let (&token_count, mut rest) =
+                        rest.split_first().ok_or(SwapError::InvalidInstruction)?;
+                    let mut weights = Vec::with_capacity(token_count as usize);
+                    for _ in 0..token_count {
+                        let (weight, next) = Self::unpack_u64(rest)?;
+                        weights.push(weight);
+                        rest = next;
+                    }
+                    Self::InitializeWeightedPool(InitializeWeightedPool {
+                        nonce,
+                        fees,
+                        weights,
+                    })
+                } else {
+                    return Err(SwapError::InvalidInstruction.into());
+                }
+            }
+            13 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
+                Self::WeightedSwap(Swap {
+                    amount_in,
+                    minimum_amount_out,
+                })
+            }
+            14 => {
+                let (source_token_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_pool_token_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::WeightedDepositSingleTokenTypeExactAmountIn(
+                    DepositSingleTokenTypeExactAmountIn {
+                        source_token_amount,
+                        minimum_pool_token_amount,
+                    },
+                )
+            }
+            15 => {
+                let (destination_token_amount, rest) = Self::unpack_u64(rest)?;
+                let (maximum_pool_token_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::WeightedWithdrawSingleTokenTypeExactAmountOut(
+                    WithdrawSingleTokenTypeExactAmountOut {
+                        destination_token_amount,
+                        maximum_pool_token_amount,
+                    },
+                )
+            }
+            16 => Self::RefreshOraclePrice,
+            17 => {
+                if rest.len() >= DynamicFeeParameters::LEN {
+                    let (parameters, _rest) = rest.split_at(DynamicFeeParameters::LEN);
+                    let parameters = DynamicFeeParameters::unpack_unchecked(parameters)?;
+                    Self::SetDynamicFee(SetDynamicFee { parameters })
+                } else {
+                    return Err(SwapError::InvalidInstruction.into());
+                }
+            }
             _ => return Err(SwapError::InvalidInstruction.into()),
         })
     }
@@ -271,6 +632,34 @@ impl SwapInstruction {
         }
     }
 
+    fn unpack_i32(input: &[u8]) -> Result<(i32, &[u8]), ProgramError> {
+        if input.len() >= 4 {
+            let (value, rest) = input.split_at(4);
+            let value = value
+                .get(..4)
+                .and_then(|slice| slice.try_into().ok())
+                .map(i32::from_le_bytes)
+                .ok_or(SwapError::InvalidInstruction)?;
+            Ok((value, rest))
+        } else {
+            Err(SwapError::InvalidInstruction.into())
+        }
+    }
+
+    fn unpack_u128(input: &[u8]) -> Result<(u128, &[u8]), ProgramError> {
+        if input.len() >= 16 {
+            let (value, rest) = input.split_at(16);
+            let value = value
+                .get(..16)
+                .and_then(|slice| slice.try_into().ok())
+                .map(u128::from_le_bytes)
+                .ok_or(SwapError::InvalidInstruction)?;
+            Ok((value, rest))
+        } else {
+            Err(SwapError::InvalidInstruction.into())
+        }
+    }
+
     /// Packs a [SwapInstruction](enum.SwapInstruction.html) into a byte buffer.
     pub fn pack(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(size_of::<Self>());
@@ -335,6 +724,102 @@ impl SwapInstruction {
                 buf.extend_from_slice(&destination_token_amount.to_le_bytes());
                 buf.extend_from_slice(&maximum_pool_token_amount.to_le_bytes());
             }
+            Self::SetFees(SetFees { fees }) => {
+                buf.push(6);
+                let mut fees_slice = [0u8; Fees::LEN];
+                Pack::pack_into_slice(fees, &mut fees_slice[..]);
+                buf.extend_from_slice(&fees_slice);
+            }
+            Self::SetPaused(SetPaused { paused }) => {
+                buf.push(7);
+                buf.push(*paused as u8);
+            }
+            Self::RouteSwap(RouteSwap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                buf.push(8);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+            }
+            Self::OpenPosition(OpenPosition {
+                tick_lower,
+                tick_upper,
+            }) => {
+                buf.push(9);
+                buf.extend_from_slice(&tick_lower.to_le_bytes());
+                buf.extend_from_slice(&tick_upper.to_le_bytes());
+            }
+            Self::IncreaseLiquidity(IncreaseLiquidity {
+                liquidity,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+            }) => {
+                buf.push(10);
+                buf.extend_from_slice(&liquidity.to_le_bytes());
+                buf.extend_from_slice(&maximum_token_a_amount.to_le_bytes());
+                buf.extend_from_slice(&maximum_token_b_amount.to_le_bytes());
+            }
+            Self::DecreaseLiquidity(DecreaseLiquidity {
+                liquidity,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                buf.push(11);
+                buf.extend_from_slice(&liquidity.to_le_bytes());
+                buf.extend_from_slice(&minimum_token_a_amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_token_b_amount.to_le_bytes());
+            }
+            Self::InitializeWeightedPool(InitializeWeightedPool {
+                nonce,
+                fees,
+                weights,
+            }) => {
+                buf.push(12);
+                buf.push(*nonce);
+                let mut fees_slice = [0u8; Fees::LEN];
+                Pack::pack_into_slice(fees, &mut fees_slice[..]);
+                buf.extend_from_slice(&fees_slice);
+                buf.push(weights.len() as u8);
+                for weight in weights {
+                    buf.extend_from_slice(&weight.to_le_bytes());
+                }
+            }
+            Self::WeightedSwap(Swap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                buf.push(13);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+            }
+            Self::WeightedDepositSingleTokenTypeExactAmountIn(
+                DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                },
+            ) => {
+                buf.push(14);
+                buf.extend_from_slice(&source_token_amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_pool_token_amount.to_le_bytes());
+            }
+            Self::WeightedWithdrawSingleTokenTypeExactAmountOut(
+                WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                },
+            ) => {
+                buf.push(15);
+                buf.extend_from_slice(&destination_token_amount.to_le_bytes());
+                buf.extend_from_slice(&maximum_pool_token_amount.to_le_bytes());
+            }
+            Self::RefreshOraclePrice => buf.push(16),
+            Self::SetDynamicFee(SetDynamicFee { parameters }) => {
+                buf.push(17);
+                let mut parameters_slice = [0u8; DynamicFeeParameters::LEN];
+                Pack::pack_into_slice(parameters, &mut parameters_slice[..]);
+                buf.extend_from_slice(&parameters_slice);
+            }
         }
         buf
     }
@@ -354,6 +839,7 @@ pub fn initialize(
     nonce: u8,
     fees: Fees,
     swap_curve: SwapCurve,
+    admin_authority_pubkey: Option<&Pubkey>,
 ) -> Result<Instruction, ProgramError> {
     let init_data = SwapInstruction::Initialize(Initialize {
         nonce,
@@ -362,7 +848,7 @@ pub fn initialize(
     });
     let data = init_data.pack();
 
-    let accounts = vec![
+    let mut accounts = vec![
         AccountMeta::new(*swap_pubkey, true),
         AccountMeta::new_readonly(*authority_pubkey, false),
         AccountMeta::new_readonly(*token_a_pubkey, false),
@@ -372,6 +858,9 @@ pub fn initialize(
         AccountMeta::new(*destination_pubkey, false),
         AccountMeta::new_readonly(*token_program_id, false),
     ];
+    if let Some(admin_authority_pubkey) = admin_authority_pubkey {
+        accounts.push(AccountMeta::new_readonly(*admin_authority_pubkey, true));
+    }
 
     Ok(Instruction {
         program_id: *program_id,
@@ -380,6 +869,45 @@ pub fn initialize(
     })
 }
 
+/// Creates an 'initialize' instruction for a proactive market maker pool,
+/// which reads its price from the given oracle price feed.
+pub fn initialize_proactive_market_maker(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    token_a_pubkey: &Pubkey,
+    token_b_pubkey: &Pubkey,
+    pool_pubkey: &Pubkey,
+    fee_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    oracle_feed_pubkey: &Pubkey,
+    nonce: u8,
+    fees: Fees,
+    swap_curve: SwapCurve,
+    admin_authority_pubkey: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = initialize(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        token_a_pubkey,
+        token_b_pubkey,
+        pool_pubkey,
+        fee_pubkey,
+        destination_pubkey,
+        nonce,
+        fees,
+        swap_curve,
+        admin_authority_pubkey,
+    )?;
+    instruction
+        .accounts
+        .insert(8, AccountMeta::new_readonly(*oracle_feed_pubkey, false));
+    Ok(instruction)
+}
+
 /// Creates a 'deposit_all_token_types' instruction.
 pub fn deposit_all_token_types(
     program_id: &Pubkey,
@@ -398,7 +926,7 @@ pub fn deposit_all_token_types(
     let data = SwapInstruction::DepositAllTokenTypes(instruction).pack();
 
     let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new(*swap_pubkey, false),
         AccountMeta::new_readonly(*authority_pubkey, false),
         AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
         AccountMeta::new(*deposit_token_a_pubkey, false),
@@ -436,7 +964,7 @@ pub fn withdraw_all_token_types(
     let data = SwapInstruction::WithdrawAllTokenTypes(instruction).pack();
 
     let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new(*swap_pubkey, false),
         AccountMeta::new_readonly(*authority_pubkey, false),
         AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
         AccountMeta::new(*pool_mint_pubkey, false),
@@ -473,7 +1001,7 @@ pub fn deposit_single_token_type_exact_amount_in(
     let data = SwapInstruction::DepositSingleTokenTypeExactAmountIn(instruction).pack();
 
     let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new(*swap_pubkey, false),
         AccountMeta::new_readonly(*authority_pubkey, false),
         AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
         AccountMeta::new(*source_token_pubkey, false),
@@ -509,7 +1037,7 @@ pub fn withdraw_single_token_type_exact_amount_out(
     let data = SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(instruction).pack();
 
     let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new(*swap_pubkey, false),
         AccountMeta::new_readonly(*authority_pubkey, false),
         AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
         AccountMeta::new(*pool_mint_pubkey, false),
@@ -547,7 +1075,7 @@ pub fn swap(
     let data = SwapInstruction::Swap(instruction).pack();
 
     let mut accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new(*swap_pubkey, false),
         AccountMeta::new_readonly(*authority_pubkey, false),
         AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
         AccountMeta::new(*source_pubkey, false),
@@ -569,10 +1097,401 @@ pub fn swap(
     })
 }
 
-/// Unpacks a reference from a bytes buffer.
-/// TODO actually pack / unpack instead of relying on normal memory layout.
-pub fn unpack<T>(input: &[u8]) -> Result<&T, ProgramError> {
-    if input.len() < size_of::<u8>() + size_of::<T>() {
+/// Creates a 'set_fees' instruction.
+pub fn set_fees(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_authority_pubkey: &Pubkey,
+    fees: Fees,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetFees(SetFees { fees }).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_authority_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_paused' instruction.
+pub fn set_paused(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_authority_pubkey: &Pubkey,
+    paused: bool,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetPaused(SetPaused { paused }).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_authority_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'refresh_oracle_price' instruction.
+pub fn refresh_oracle_price(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    oracle_feed_pubkey: &Pubkey,
+    token_a_pubkey: &Pubkey,
+    token_b_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::RefreshOraclePrice.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*oracle_feed_pubkey, false),
+        AccountMeta::new_readonly(*token_a_pubkey, false),
+        AccountMeta::new_readonly(*token_b_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_dynamic_fee' instruction.
+pub fn set_dynamic_fee(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_authority_pubkey: &Pubkey,
+    parameters: DynamicFeeParameters,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetDynamicFee(SetDynamicFee { parameters }).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_authority_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Accounts of a pool to swap through in a 'route_swap' instruction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteSwapHop {
+    /// Token-swap
+    pub swap_pubkey: Pubkey,
+    /// Swap authority
+    pub authority_pubkey: Pubkey,
+    /// Swap account to swap INTO
+    pub swap_source_pubkey: Pubkey,
+    /// Swap account to swap FROM
+    pub swap_destination_pubkey: Pubkey,
+    /// User account receiving the output of the pool
+    pub destination_pubkey: Pubkey,
+    /// Pool token mint
+    pub pool_mint_pubkey: Pubkey,
+    /// Fee account
+    pub pool_fee_pubkey: Pubkey,
+}
+
+/// Creates a 'route_swap' instruction.
+pub fn route_swap(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    hops: &[RouteSwapHop],
+    host_fee_pubkey: Option<&Pubkey>,
+    instruction: RouteSwap,
+) -> Result<Instruction, ProgramError> {
+    if hops.is_empty() {
+        return Err(SwapError::InvalidRoute.into());
+    }
+    let data = SwapInstruction::RouteSwap(instruction).pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    for hop in hops {
+        accounts.extend_from_slice(&[
+            AccountMeta::new(hop.swap_pubkey, false),
+            AccountMeta::new_readonly(hop.authority_pubkey, false),
+            AccountMeta::new(hop.swap_source_pubkey, false),
+            AccountMeta::new(hop.swap_destination_pubkey, false),
+            AccountMeta::new(hop.destination_pubkey, false),
+            AccountMeta::new(hop.pool_mint_pubkey, false),
+            AccountMeta::new(hop.pool_fee_pubkey, false),
+        ]);
+    }
+    if let Some(host_fee_pubkey) = host_fee_pubkey {
+        accounts.push(AccountMeta::new(*host_fee_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'open_position' instruction.
+pub fn open_position(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    position_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    instruction: OpenPosition,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::OpenPosition(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new(*position_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'increase_liquidity' instruction.
+pub fn increase_liquidity(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    position_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    deposit_token_a_pubkey: &Pubkey,
+    deposit_token_b_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    instruction: IncreaseLiquidity,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::IncreaseLiquidity(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*position_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*deposit_token_a_pubkey, false),
+        AccountMeta::new(*deposit_token_b_pubkey, false),
+        AccountMeta::new(*swap_token_a_pubkey, false),
+        AccountMeta::new(*swap_token_b_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'decrease_liquidity' instruction.
+pub fn decrease_liquidity(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    position_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_token_a_pubkey: &Pubkey,
+    destination_token_b_pubkey: &Pubkey,
+    instruction: DecreaseLiquidity,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::DecreaseLiquidity(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new(*position_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*swap_token_a_pubkey, false),
+        AccountMeta::new(*swap_token_b_pubkey, false),
+        AccountMeta::new(*destination_token_a_pubkey, false),
+        AccountMeta::new(*destination_token_b_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'initialize_weighted_pool' instruction.
+pub fn initialize_weighted_pool(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    pool_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    token_pubkeys: &[Pubkey],
+    nonce: u8,
+    fees: Fees,
+    weights: Vec<u64>,
+) -> Result<Instruction, ProgramError> {
+    if token_pubkeys.len() != weights.len() {
+        return Err(SwapError::InvalidInstruction.into());
+    }
+    let data = SwapInstruction::InitializeWeightedPool(InitializeWeightedPool {
+        nonce,
+        fees,
+        weights,
+    })
+    .pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*pool_pubkey, true),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new_readonly(*fee_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    for token_pubkey in token_pubkeys {
+        accounts.push(AccountMeta::new_readonly(*token_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'weighted_swap' instruction.
+pub fn weighted_swap(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    pool_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    pool_source_pubkey: &Pubkey,
+    pool_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: Swap,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::WeightedSwap(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*pool_source_pubkey, false),
+        AccountMeta::new(*pool_destination_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_fee_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'weighted_deposit_single_token_type_exact_amount_in' instruction.
+pub fn weighted_deposit_single_token_type_exact_amount_in(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    pool_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_token_pubkey: &Pubkey,
+    pool_token_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    instruction: DepositSingleTokenTypeExactAmountIn,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::WeightedDepositSingleTokenTypeExactAmountIn(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*source_token_pubkey, false),
+        AccountMeta::new(*pool_token_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'weighted_withdraw_single_token_type_exact_amount_out' instruction.
+pub fn weighted_withdraw_single_token_type_exact_amount_out(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    pool_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    pool_token_source_pubkey: &Pubkey,
+    pool_token_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    instruction: WithdrawSingleTokenTypeExactAmountOut,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::WeightedWithdrawSingleTokenTypeExactAmountOut(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_token_source_pubkey, false),
+        AccountMeta::new(*pool_token_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new(*fee_account_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Unpacks a reference from a bytes buffer.
+/// TODO actually pack / unpack instead of relying on normal memory layout.
+pub fn unpack<T>(input: &[u8]) -> Result<&T, ProgramError> {
+    if input.len() < size_of::<u8>() + size_of::<T>() {
         return Err(ProgramError::InvalidAccountData);
     }
     #[allow(clippy::cast_ptr_alignment)]
@@ -731,4 +1650,249 @@ mod tests {
         let unpacked = SwapInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
     }
+
+    #[test]
+    fn pack_set_fees() {
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 4,
+            owner_trade_fee_numerator: 2,
+            owner_trade_fee_denominator: 5,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 3,
+            host_fee_numerator: 5,
+            host_fee_denominator: 20,
+        };
+        let check = SwapInstruction::SetFees(SetFees { fees: fees.clone() });
+        let packed = check.pack();
+        let mut expect = vec![6];
+        expect.extend_from_slice(&fees.trade_fee_numerator.to_le_bytes());
+        expect.extend_from_slice(&fees.trade_fee_denominator.to_le_bytes());
+        expect.extend_from_slice(&fees.owner_trade_fee_numerator.to_le_bytes());
+        expect.extend_from_slice(&fees.owner_trade_fee_denominator.to_le_bytes());
+        expect.extend_from_slice(&fees.owner_withdraw_fee_numerator.to_le_bytes());
+        expect.extend_from_slice(&fees.owner_withdraw_fee_denominator.to_le_bytes());
+        expect.extend_from_slice(&fees.host_fee_numerator.to_le_bytes());
+        expect.extend_from_slice(&fees.host_fee_denominator.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn pack_set_paused() {
+        let check = SwapInstruction::SetPaused(SetPaused { paused: true });
+        let packed = check.pack();
+        let expect = vec![7, 1];
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let err = SwapInstruction::unpack(&[7, 2]).unwrap_err();
+        assert_eq!(err, SwapError::InvalidInstruction.into());
+    }
+
+    #[test]
+    fn pack_route_swap() {
+        let amount_in: u64 = 2;
+        let minimum_amount_out: u64 = 10;
+        let check = SwapInstruction::RouteSwap(RouteSwap {
+            amount_in,
+            minimum_amount_out,
+        });
+        let packed = check.pack();
+        let mut expect = vec![8];
+        expect.extend_from_slice(&amount_in.to_le_bytes());
+        expect.extend_from_slice(&minimum_amount_out.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn pack_open_position() {
+        let tick_lower: i32 = -120;
+        let tick_upper: i32 = 60;
+        let check = SwapInstruction::OpenPosition(OpenPosition {
+            tick_lower,
+            tick_upper,
+        });
+        let packed = check.pack();
+        let mut expect = vec![9];
+        expect.extend_from_slice(&tick_lower.to_le_bytes());
+        expect.extend_from_slice(&tick_upper.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn pack_increase_liquidity() {
+        let liquidity: u128 = 1 << 70;
+        let maximum_token_a_amount: u64 = 10;
+        let maximum_token_b_amount: u64 = 20;
+        let check = SwapInstruction::IncreaseLiquidity(IncreaseLiquidity {
+            liquidity,
+            maximum_token_a_amount,
+            maximum_token_b_amount,
+        });
+        let packed = check.pack();
+        let mut expect = vec![10];
+        expect.extend_from_slice(&liquidity.to_le_bytes());
+        expect.extend_from_slice(&maximum_token_a_amount.to_le_bytes());
+        expect.extend_from_slice(&maximum_token_b_amount.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn pack_decrease_liquidity() {
+        let liquidity: u128 = 1 << 70;
+        let minimum_token_a_amount: u64 = 10;
+        let minimum_token_b_amount: u64 = 20;
+        let check = SwapInstruction::DecreaseLiquidity(DecreaseLiquidity {
+            liquidity,
+            minimum_token_a_amount,
+            minimum_token_b_amount,
+        });
+        let packed = check.pack();
+        let mut expect = vec![11];
+        expect.extend_from_slice(&liquidity.to_le_bytes());
+        expect.extend_from_slice(&minimum_token_a_amount.to_le_bytes());
+        expect.extend_from_slice(&minimum_token_b_amount.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn pack_initialize_weighted_pool() {
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 4,
+            owner_trade_fee_numerator: 2,
+            owner_trade_fee_denominator: 5,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 3,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
+        };
+        let nonce: u8 = 255;
+        let weights: Vec<u64> = vec![50, 30, 20];
+        let check = SwapInstruction::InitializeWeightedPool(InitializeWeightedPool {
+            nonce,
+            fees: fees.clone(),
+            weights: weights.clone(),
+        });
+        let packed = check.pack();
+        let mut expect = vec![12u8, nonce];
+        let mut fees_slice = [0u8; Fees::LEN];
+        Pack::pack_into_slice(&fees, &mut fees_slice[..]);
+        expect.extend_from_slice(&fees_slice);
+        expect.push(weights.len() as u8);
+        for weight in &weights {
+            expect.extend_from_slice(&weight.to_le_bytes());
+        }
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        // missing weight
+        expect.truncate(expect.len() - 8);
+        assert_eq!(
+            SwapInstruction::unpack(&expect).unwrap_err(),
+            SwapError::InvalidInstruction.into()
+        );
+    }
+
+    #[test]
+    fn pack_weighted_swap() {
+        let amount_in: u64 = 2;
+        let minimum_amount_out: u64 = 10;
+        let check = SwapInstruction::WeightedSwap(Swap {
+            amount_in,
+            minimum_amount_out,
+        });
+        let packed = check.pack();
+        let mut expect = vec![13];
+        expect.extend_from_slice(&amount_in.to_le_bytes());
+        expect.extend_from_slice(&minimum_amount_out.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn pack_weighted_deposit_one_exact_in() {
+        let source_token_amount: u64 = 10;
+        let minimum_pool_token_amount: u64 = 5;
+        let check = SwapInstruction::WeightedDepositSingleTokenTypeExactAmountIn(
+            DepositSingleTokenTypeExactAmountIn {
+                source_token_amount,
+                minimum_pool_token_amount,
+            },
+        );
+        let packed = check.pack();
+        let mut expect = vec![14];
+        expect.extend_from_slice(&source_token_amount.to_le_bytes());
+        expect.extend_from_slice(&minimum_pool_token_amount.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn pack_weighted_withdraw_one_exact_out() {
+        let destination_token_amount: u64 = 102198761982612;
+        let maximum_pool_token_amount: u64 = 1212438012089;
+        let check = SwapInstruction::WeightedWithdrawSingleTokenTypeExactAmountOut(
+            WithdrawSingleTokenTypeExactAmountOut {
+                destination_token_amount,
+                maximum_pool_token_amount,
+            },
+        );
+        let packed = check.pack();
+        let mut expect = vec![15];
+        expect.extend_from_slice(&destination_token_amount.to_le_bytes());
+        expect.extend_from_slice(&maximum_pool_token_amount.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn pack_refresh_oracle_price() {
+        let check = SwapInstruction::RefreshOraclePrice;
+        let packed = check.pack();
+        let expect = vec![16];
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn pack_set_dynamic_fee() {
+        let parameters = DynamicFeeParameters {
+            min_fee_numerator: 25,
+            max_fee_numerator: 300,
+            fee_denominator: 10_000,
+            volatility_multiplier: 5_000,
+            decay_period: 150,
+        };
+        let check = SwapInstruction::SetDynamicFee(SetDynamicFee { parameters });
+        let packed = check.pack();
+        let mut expect = vec![17];
+        expect.extend_from_slice(&parameters.min_fee_numerator.to_le_bytes());
+        expect.extend_from_slice(&parameters.max_fee_numerator.to_le_bytes());
+        expect.extend_from_slice(&parameters.fee_denominator.to_le_bytes());
+        expect.extend_from_slice(&parameters.volatility_multiplier.to_le_bytes());
+        expect.extend_from_slice(&parameters.decay_period.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let err = SwapInstruction::unpack(&expect[..expect.len() - 1]).unwrap_err();
+        assert_eq!(err, SwapError::InvalidInstruction.into());
+    }
 }
@@ -6,7 +6,9 @@ pub mod constraints;
 pub mod curve;
 pub mod error;
 pub mod instruction;
+pub mod oracle;
 pub mod processor;
+pub mod quote;
 pub mod state;
 
 #[cfg(not(feature = "no-entrypoint"))]
@@ -0,0 +1,433 @@
+//! Cumulative price accumulator, to build time-weighted average price oracles,
+//! and the external price feeds read by oracle-anchored curves
+
+use crate::curve::calculator::CurveCalculator;
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    clock::Slot,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+};
+use spl_math::{
+    precise_number::{PreciseNumber, ONE},
+    uint::U256,
+};
+
+/// Number of fractional bits in the fixed-point prices, which are stored as
+/// unsigned Q64.64 numbers
+pub const PRICE_FRACTIONAL_BITS: usize = 64;
+
+/// Uniswap v2 style cumulative prices of a swap.
+///
+/// On every update, the spot prices which prevailed since the previous update
+/// are multiplied by the number of slots elapsed and added to the cumulative
+/// prices.  The cumulative prices are expected to overflow and wrap around, so
+/// only the difference between two observations is meaningful: divided by the
+/// slots elapsed between the observations, it gives the time-weighted average
+/// price over that period.
+///
+/// Manipulating the average requires moving the spot price and holding it
+/// there for as many slots as the average covers, which gives arbitrageurs a
+/// chance to trade against the manipulation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PriceAccumulator {
+    /// Slot of the last update
+    pub last_update_slot: Slot,
+    /// Sum of the Q64.64 spot prices of token A, denominated in token B,
+    /// multiplied by the slots they were valid for
+    pub price_a_cumulative: u128,
+    /// Sum of the Q64.64 spot prices of token B, denominated in token A,
+    /// multiplied by the slots they were valid for
+    pub price_b_cumulative: u128,
+}
+
+impl PriceAccumulator {
+    /// Create a new accumulator, starting at the given slot
+    pub fn new(slot: Slot) -> Self {
+        Self {
+            last_update_slot: slot,
+            price_a_cumulative: 0,
+            price_b_cumulative: 0,
+        }
+    }
+
+    /// Accumulate the spot prices given by the curve for the swap token
+    /// amounts, which must be the amounts the previous instruction settled
+    /// with, so that tokens sent to the swap since do not move the price.
+    ///
+    /// Periods where a price cannot be represented, for example because one
+    /// side of the pool is empty, do not add to the cumulative price.
+    pub fn update(
+        &mut self,
+        slot: Slot,
+        calculator: &dyn CurveCalculator,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+    ) {
+        if slot > self.last_update_slot {
+            self.accumulate(
+                slot,
+                calculator.spot_price(swap_token_a_amount, swap_token_b_amount),
+            );
+        }
+    }
+
+    /// Accumulate a spot price of token A, denominated in token B, for pools
+    /// whose price does not come from the swap token amounts
+    pub fn accumulate(&mut self, slot: Slot, price_a: Option<PreciseNumber>) {
+        let slots_elapsed = slot.saturating_sub(self.last_update_slot);
+        if slots_elapsed == 0 {
+            return;
+        }
+        let slots_elapsed = u128::from(slots_elapsed);
+        if let Some(price_a) = price_a.as_ref().and_then(to_fixed_price) {
+            self.price_a_cumulative = self
+                .price_a_cumulative
+                .wrapping_add(price_a.wrapping_mul(slots_elapsed));
+        }
+        let price_b = price_a.and_then(|price_a| PreciseNumber::new(1)?.checked_div(&price_a));
+        if let Some(price_b) = price_b.as_ref().and_then(to_fixed_price) {
+            self.price_b_cumulative = self
+                .price_b_cumulative
+                .wrapping_add(price_b.wrapping_mul(slots_elapsed));
+        }
+        self.last_update_slot = slot;
+    }
+
+    /// Time-weighted average prices between an earlier observation and this
+    /// one, or `None` if no slots have elapsed between them
+    pub fn time_weighted_average(&self, earlier: &Self) -> Option<TimeWeightedAveragePrice> {
+        let slots_elapsed = self
+            .last_update_slot
+            .checked_sub(earlier.last_update_slot)
+            .filter(|slots_elapsed| *slots_elapsed > 0)?;
+        let slots_elapsed = u128::from(slots_elapsed);
+        Some(TimeWeightedAveragePrice {
+            price_a: self
+                .price_a_cumulative
+                .wrapping_sub(earlier.price_a_cumulative)
+                / slots_elapsed,
+            price_b: self
+                .price_b_cumulative
+                .wrapping_sub(earlier.price_b_cumulative)
+                / slots_elapsed,
+        })
+    }
+}
+
+/// Time-weighted average prices of a swap over a period, as unsigned Q64.64
+/// numbers
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TimeWeightedAveragePrice {
+    /// Average price of token A, denominated in token B
+    pub price_a: u128,
+    /// Average price of token B, denominated in token A
+    pub price_b: u128,
+}
+
+impl TimeWeightedAveragePrice {
+    /// Average price of token A as a precise number
+    pub fn precise_price_a(&self) -> Option<PreciseNumber> {
+        from_fixed_price(self.price_a)
+    }
+
+    /// Average price of token B as a precise number
+    pub fn precise_price_b(&self) -> Option<PreciseNumber> {
+        from_fixed_price(self.price_b)
+    }
+}
+
+/// Convert a precise number to an unsigned Q64.64 number, if it fits
+pub(crate) fn to_fixed_price(price: &PreciseNumber) -> Option<u128> {
+    let value = (price.value << PRICE_FRACTIONAL_BITS).checked_div(U256::from(ONE))?;
+    if value > U256::from(u128::MAX) {
+        None
+    } else {
+        Some(value.as_u128())
+    }
+}
+
+/// Convert an unsigned Q64.64 number to a precise number
+pub(crate) fn from_fixed_price(price: u128) -> Option<PreciseNumber> {
+    let value = U256::from(price).checked_mul(U256::from(ONE))? >> PRICE_FRACTIONAL_BITS;
+    Some(PreciseNumber { value })
+}
+
+/// Largest absolute exponent an oracle feed price can be scaled by
+pub const MAX_ORACLE_FEED_EXPONENT: u8 = 18;
+
+/// Aggregated push oracle feed account, with the same layout as the feeds read
+/// by the lending program.  Publishers push prices to an aggregator
+/// off-chain, which writes the aggregated price and a confidence interval
+/// around it to the feed.
+///
+/// Prices are for the smallest unit of token A in the smallest unit of
+/// token B, scaled by `10^exponent`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OracleFeed {
+    /// Version of the feed
+    pub version: u8,
+    /// Slot the aggregated price was published in
+    pub publish_slot: Slot,
+    /// Aggregated price
+    pub price: u64,
+    /// Confidence interval around the aggregated price
+    pub confidence: u64,
+    /// Power of ten that the price and confidence are scaled by
+    pub exponent: i8,
+}
+
+impl OracleFeed {
+    /// Feed price with its exponent applied, as an unsigned Q64.64 number, or
+    /// `None` if it is zero or cannot be represented
+    pub fn fixed_price(&self) -> Option<u128> {
+        let exponent = (self.exponent as i16).abs() as u32;
+        if exponent > u32::from(MAX_ORACLE_FEED_EXPONENT) || self.price == 0 {
+            return None;
+        }
+        let price = U256::from(self.price) << PRICE_FRACTIONAL_BITS;
+        let scale = U256::from(10u64.pow(exponent));
+        let price = if self.exponent < 0 {
+            price.checked_div(scale)?
+        } else {
+            price.checked_mul(scale)?
+        };
+        if price.is_zero() || price > U256::from(u128::MAX) {
+            None
+        } else {
+            Some(price.as_u128())
+        }
+    }
+}
+
+impl Sealed for OracleFeed {}
+impl IsInitialized for OracleFeed {
+    fn is_initialized(&self) -> bool {
+        self.version != 0
+    }
+}
+
+impl Pack for OracleFeed {
+    const LEN: usize = 26;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 26];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, publish_slot, price, confidence, exponent) =
+            mut_array_refs![output, 1, 8, 8, 8, 1];
+        *version = self.version.to_le_bytes();
+        *publish_slot = self.publish_slot.to_le_bytes();
+        *price = self.price.to_le_bytes();
+        *confidence = self.confidence.to_le_bytes();
+        *exponent = self.exponent.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<OracleFeed, ProgramError> {
+        let input = array_ref![input, 0, 26];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, publish_slot, price, confidence, exponent) =
+            array_refs![input, 1, 8, 8, 8, 1];
+        Ok(Self {
+            version: u8::from_le_bytes(*version),
+            publish_slot: Slot::from_le_bytes(*publish_slot),
+            price: u64::from_le_bytes(*price),
+            confidence: u64::from_le_bytes(*confidence),
+            exponent: i8::from_le_bytes(*exponent),
+        })
+    }
+}
+
+/// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
+impl IsInitialized for PriceAccumulator {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+
+impl Sealed for PriceAccumulator {}
+impl Pack for PriceAccumulator {
+    const LEN: usize = 40;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 40];
+        let (last_update_slot, price_a_cumulative, price_b_cumulative) =
+            mut_array_refs![output, 8, 16, 16];
+        *last_update_slot = self.last_update_slot.to_le_bytes();
+        *price_a_cumulative = self.price_a_cumulative.to_le_bytes();
+        *price_b_cumulative = self.price_b_cumulative.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<PriceAccumulator, ProgramError> {
+        let input = array_ref![input, 0, 40];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (last_update_slot, price_a_cumulative, price_b_cumulative) =
+            array_refs![input, 8, 16, 16];
+        Ok(Self {
+            last_update_slot: Slot::from_le_bytes(*last_update_slot),
+            price_a_cumulative: u128::from_le_bytes(*price_a_cumulative),
+            price_b_cumulative: u128::from_le_bytes(*price_b_cumulative),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::{
+        constant_price::ConstantPriceCurve, constant_product::ConstantProductCurve,
+    };
+
+    const Q64: u128 = 1 << 64;
+
+    #[test]
+    fn pack_price_accumulator() {
+        let accumulator = PriceAccumulator {
+            last_update_slot: 42,
+            price_a_cumulative: u128::MAX - 7,
+            price_b_cumulative: 3 * Q64,
+        };
+        let mut packed = [0u8; PriceAccumulator::LEN];
+        Pack::pack_into_slice(&accumulator, &mut packed[..]);
+        let unpacked = PriceAccumulator::unpack_from_slice(&packed).unwrap();
+        assert_eq!(accumulator, unpacked);
+
+        let mut packed = vec![];
+        packed.extend_from_slice(&42u64.to_le_bytes());
+        packed.extend_from_slice(&(u128::MAX - 7).to_le_bytes());
+        packed.extend_from_slice(&(3 * Q64).to_le_bytes());
+        let unpacked = PriceAccumulator::unpack_from_slice(&packed).unwrap();
+        assert_eq!(accumulator, unpacked);
+    }
+
+    #[test]
+    fn pack_oracle_feed() {
+        let feed = OracleFeed {
+            version: 1,
+            publish_slot: 42,
+            price: 123_456,
+            confidence: 78,
+            exponent: -3,
+        };
+        let mut packed = [0u8; OracleFeed::LEN];
+        Pack::pack_into_slice(&feed, &mut packed[..]);
+        let unpacked = OracleFeed::unpack(&packed).unwrap();
+        assert_eq!(feed, unpacked);
+
+        let mut packed = vec![1u8];
+        packed.extend_from_slice(&42u64.to_le_bytes());
+        packed.extend_from_slice(&123_456u64.to_le_bytes());
+        packed.extend_from_slice(&78u64.to_le_bytes());
+        packed.push(-3i8 as u8);
+        let unpacked = OracleFeed::unpack(&packed).unwrap();
+        assert_eq!(feed, unpacked);
+
+        // version 0 is not initialized
+        packed[0] = 0;
+        assert_eq!(
+            OracleFeed::unpack(&packed),
+            Err(ProgramError::UninitializedAccount)
+        );
+    }
+
+    #[test]
+    fn oracle_feed_fixed_price() {
+        let mut feed = OracleFeed {
+            version: 1,
+            publish_slot: 0,
+            price: 15,
+            confidence: 0,
+            exponent: -1,
+        };
+        assert_eq!(feed.fixed_price(), Some(3 * Q64 / 2));
+        feed.exponent = 2;
+        assert_eq!(feed.fixed_price(), Some(1_500 * Q64));
+
+        // out of range exponents, and prices which cannot be represented
+        feed.exponent = 19;
+        assert_eq!(feed.fixed_price(), None);
+        feed.exponent = -19;
+        assert_eq!(feed.fixed_price(), None);
+        feed.exponent = 0;
+        feed.price = u64::MAX;
+        assert_eq!(feed.fixed_price(), Some(u128::from(u64::MAX) * Q64));
+        feed.exponent = 1;
+        assert_eq!(feed.fixed_price(), None);
+        feed.price = 0;
+        feed.exponent = 0;
+        assert_eq!(feed.fixed_price(), None);
+    }
+
+    #[test]
+    fn accumulate_prices() {
+        let curve = ConstantProductCurve {};
+        let mut accumulator = PriceAccumulator::new(10);
+
+        // same slot, nothing accumulated
+        accumulator.update(10, &curve, 100, 400);
+        assert_eq!(accumulator, PriceAccumulator::new(10));
+
+        // price of A is 4 B for 5 slots
+        accumulator.update(15, &curve, 100, 400);
+        assert_eq!(accumulator.last_update_slot, 15);
+        assert_eq!(accumulator.price_a_cumulative, 4 * Q64 * 5);
+        assert_eq!(accumulator.price_b_cumulative, Q64 / 4 * 5);
+        let earlier = accumulator;
+
+        // price of A is 1 B for 15 slots
+        accumulator.update(30, &curve, 200, 200);
+        let average = accumulator.time_weighted_average(&earlier).unwrap();
+        assert_eq!(average.price_a, Q64);
+        assert_eq!(average.price_b, Q64);
+
+        // average over the whole period
+        let average = accumulator
+            .time_weighted_average(&PriceAccumulator::new(10))
+            .unwrap();
+        assert_eq!(average.price_a, (4 * Q64 * 5 + Q64 * 15) / 20);
+        assert_eq!(
+            average.precise_price_a().unwrap(),
+            PreciseNumber::new(7)
+                .unwrap()
+                .checked_div(&PreciseNumber::new(4).unwrap())
+                .unwrap()
+        );
+
+        // no slots between observations
+        assert!(accumulator.time_weighted_average(&accumulator).is_none());
+        assert!(earlier.time_weighted_average(&accumulator).is_none());
+    }
+
+    #[test]
+    fn accumulate_prices_empty_side() {
+        let curve = ConstantProductCurve {};
+        let mut accumulator = PriceAccumulator::new(0);
+
+        // token A is empty, neither price can be represented
+        accumulator.update(10, &curve, 0, 400);
+        assert_eq!(accumulator.last_update_slot, 10);
+        assert_eq!(accumulator.price_a_cumulative, 0);
+        assert_eq!(accumulator.price_b_cumulative, 0);
+
+        // token B is empty, token A is worth nothing and the price of token B
+        // cannot be represented
+        accumulator.update(20, &curve, 100, 0);
+        assert_eq!(accumulator.price_a_cumulative, 0);
+        assert_eq!(accumulator.price_b_cumulative, 0);
+        assert_eq!(accumulator.last_update_slot, 20);
+    }
+
+    #[test]
+    fn accumulate_prices_wrapping() {
+        let curve = ConstantPriceCurve { token_b_price: 1 };
+        let mut accumulator = PriceAccumulator {
+            last_update_slot: 0,
+            price_a_cumulative: u128::MAX - Q64 + 1,
+            price_b_cumulative: 0,
+        };
+        let earlier = accumulator;
+
+        accumulator.update(3, &curve, 100, 100);
+        assert_eq!(accumulator.price_a_cumulative, 2 * Q64);
+        let average = accumulator.time_weighted_average(&earlier).unwrap();
+        assert_eq!(average.price_a, Q64);
+        assert_eq!(average.price_b, Q64);
+    }
+}
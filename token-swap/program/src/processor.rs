@@ -3,20 +3,34 @@
 use crate::constraints::{SwapConstraints, SWAP_CONSTRAINTS};
 use crate::{
     curve::{
-        base::SwapCurve,
-        calculator::{RoundDirection, TradeDirection},
+        base::{CurveType, SwapCurve},
+        calculator::{TradeDirection, INITIAL_SWAP_POOL_AMOUNT},
+        concentrated_liquidity::{
+            self, ConcentratedLiquidityCurve, ConcentratedLiquidityState, Position,
+        },
+        dynamic_fee::{DynamicFee, DynamicFeeParameters},
         fees::Fees,
+        proactive_market_maker::{ProactiveMarketMakerCurve, ProactiveMarketMakerState},
+        weighted,
     },
     error::SwapError,
     instruction::{
-        DepositAllTokenTypes, DepositSingleTokenTypeExactAmountIn, Initialize, Swap,
-        SwapInstruction, WithdrawAllTokenTypes, WithdrawSingleTokenTypeExactAmountOut,
+        DecreaseLiquidity, DepositAllTokenTypes, DepositSingleTokenTypeExactAmountIn,
+        IncreaseLiquidity, Initialize, InitializeWeightedPool, OpenPosition, RouteSwap,
+        SetDynamicFee, SetFees, SetPaused, Swap, SwapInstruction, WithdrawAllTokenTypes,
+        WithdrawSingleTokenTypeExactAmountOut, ROUTE_SWAP_HOP_ACCOUNTS,
+    },
+    oracle::{OracleFeed, PriceAccumulator},
+    quote::{Quoter, WeightedPoolQuoter},
+    state::{
+        SwapState, SwapV1, SwapV2, SwapVersion, WeightedPool, WeightedPoolToken,
+        MAX_WEIGHTED_POOL_TOKENS, MIN_WEIGHTED_POOL_TOKENS,
     },
-    state::{SwapState, SwapV1, SwapVersion},
 };
 use num_traits::FromPrimitive;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::{Clock, Slot},
     decode_error::DecodeError,
     entrypoint::ProgramResult,
     msg,
@@ -25,8 +39,9 @@ use solana_program::{
     program_option::COption,
     program_pack::Pack,
     pubkey::Pubkey,
+    sysvar::Sysvar,
 };
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 
 /// Program state handler.
 pub struct Processor {}
@@ -202,6 +217,185 @@ impl Processor {
         Ok(())
     }
 
+    /// Observes the spot price of a SwapV2 swap, given the amounts held by the
+    /// swap before the current instruction moves any tokens, to update the
+    /// volatility of its dynamic fee, and accumulates the price left by the
+    /// previous instruction over the slots since.  Swaps also observe the
+    /// price they leave, once the tokens moved.  SwapV1 swaps do not track
+    /// prices and are left untouched.
+    fn observe_prices(
+        swap_info: &AccountInfo,
+        swap_token_a_amount: u64,
+        swap_token_b_amount: u64,
+    ) -> ProgramResult {
+        let mut token_swap = {
+            let data = swap_info.data.borrow();
+            match data.split_first() {
+                Some((&2, rest)) => SwapV2::unpack(
+                    rest.get(..SwapV2::LEN)
+                        .ok_or(ProgramError::InvalidAccountData)?,
+                )?,
+                _ => return Ok(()),
+            }
+        };
+        let clock = Clock::get()?;
+        // tokens sent to the swap outside of an instruction do not move the
+        // accumulated price, which comes from the amounts the previous
+        // instruction settled with
+        let (spot_price, previous_price) = if token_swap.swap_curve.curve_type
+            == CurveType::ConcentratedLiquidity
+        {
+            let spot_price = Self::unpack_concentrated_liquidity(swap_info)?.spot_price();
+            (spot_price.clone(), spot_price)
+        } else {
+            let calculator = &token_swap.swap_curve.calculator;
+            (
+                calculator.spot_price(to_u128(swap_token_a_amount)?, to_u128(swap_token_b_amount)?),
+                calculator.spot_price(
+                    to_u128(token_swap.last_token_a_amount)?,
+                    to_u128(token_swap.last_token_b_amount)?,
+                ),
+            )
+        };
+        token_swap
+            .dynamic_fee
+            .observe(clock.slot, spot_price.as_ref());
+        token_swap
+            .price_accumulator
+            .accumulate(clock.slot, previous_price);
+        SwapVersion::pack(
+            SwapVersion::SwapV2(token_swap),
+            &mut swap_info.data.borrow_mut(),
+        )
+    }
+
+    /// Records the amounts held by a SwapV2 swap once the current instruction
+    /// has settled, which set the price accumulated until the next
+    /// instruction.  SwapV1 swaps are left untouched.
+    fn record_settled_amounts(
+        swap_info: &AccountInfo,
+        swap_token_a_info: &AccountInfo,
+        swap_token_b_info: &AccountInfo,
+    ) -> ProgramResult {
+        let mut token_swap = {
+            let data = swap_info.data.borrow();
+            match data.split_first() {
+                Some((&2, rest)) => SwapV2::unpack(
+                    rest.get(..SwapV2::LEN)
+                        .ok_or(ProgramError::InvalidAccountData)?,
+                )?,
+                _ => return Ok(()),
+            }
+        };
+        token_swap.last_token_a_amount =
+            Self::unpack_token_account(swap_token_a_info, &token_swap.token_program_id)?.amount;
+        token_swap.last_token_b_amount =
+            Self::unpack_token_account(swap_token_b_info, &token_swap.token_program_id)?.amount;
+        SwapVersion::pack(
+            SwapVersion::SwapV2(token_swap),
+            &mut swap_info.data.borrow_mut(),
+        )
+    }
+
+    /// Unpacks the state of a concentrated liquidity pool, stored after the
+    /// swap state
+    fn unpack_concentrated_liquidity(
+        swap_info: &AccountInfo,
+    ) -> Result<ConcentratedLiquidityState, ProgramError> {
+        let data = swap_info.data.borrow();
+        let input = data
+            .get(SwapVersion::LATEST_LEN..)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        ConcentratedLiquidityState::unpack(input)
+    }
+
+    /// Packs the state of a concentrated liquidity pool after the swap state
+    fn pack_concentrated_liquidity(
+        state: ConcentratedLiquidityState,
+        swap_info: &AccountInfo,
+    ) -> ProgramResult {
+        let mut data = swap_info.data.borrow_mut();
+        let dst = data
+            .get_mut(SwapVersion::LATEST_LEN..)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        ConcentratedLiquidityState::pack(state, dst)
+    }
+
+    /// Unpacks the state of a proactive market maker pool, stored after the
+    /// swap state
+    fn unpack_proactive_market_maker(
+        swap_info: &AccountInfo,
+    ) -> Result<ProactiveMarketMakerState, ProgramError> {
+        let data = swap_info.data.borrow();
+        let input = data
+            .get(SwapVersion::LATEST_LEN..)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        ProactiveMarketMakerState::unpack(input)
+    }
+
+    /// Packs the state of a proactive market maker pool after the swap state
+    fn pack_proactive_market_maker(
+        state: ProactiveMarketMakerState,
+        swap_info: &AccountInfo,
+    ) -> ProgramResult {
+        let mut data = swap_info.data.borrow_mut();
+        let dst = data
+            .get_mut(SwapVersion::LATEST_LEN..)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        ProactiveMarketMakerState::pack(state, dst)
+    }
+
+    /// Unpacks the parameters of a proactive market maker curve
+    fn unpack_proactive_market_maker_curve(
+        swap_curve: &SwapCurve,
+    ) -> Result<ProactiveMarketMakerCurve, ProgramError> {
+        let mut packed_curve = [0u8; ProactiveMarketMakerCurve::LEN];
+        swap_curve.calculator.pack_into_slice(&mut packed_curve);
+        ProactiveMarketMakerCurve::unpack_from_slice(&packed_curve)
+    }
+
+    /// Reads the price of an oracle feed as an unsigned Q64.64 number,
+    /// rejecting prices outside of the staleness and confidence limits of the
+    /// proactive market maker curve
+    fn read_oracle_price(
+        oracle_feed_info: &AccountInfo,
+        swap_curve: &SwapCurve,
+        slot: Slot,
+    ) -> Result<u128, ProgramError> {
+        let feed = OracleFeed::unpack(&oracle_feed_info.data.borrow())
+            .map_err(|_| SwapError::InvalidOracle)?;
+        let curve = Self::unpack_proactive_market_maker_curve(swap_curve)?;
+        Ok(curve.oracle_feed_price(&feed, slot)?)
+    }
+
+    /// Replaces the oracle price of a proactive market maker curve
+    fn with_oracle_price(
+        swap_curve: &SwapCurve,
+        oracle_price: u128,
+    ) -> Result<SwapCurve, ProgramError> {
+        let curve = Self::unpack_proactive_market_maker_curve(swap_curve)?;
+        Ok(SwapCurve {
+            curve_type: CurveType::ProactiveMarketMaker,
+            calculator: Box::new(ProactiveMarketMakerCurve {
+                oracle_price,
+                ..curve
+            }),
+        })
+    }
+
+    /// Checks that the oracle price of a proactive market maker pool was
+    /// refreshed in the current slot.  Other pools do not need a price.
+    fn check_oracle_price(token_swap: &dyn SwapState, swap_info: &AccountInfo) -> ProgramResult {
+        if token_swap.swap_curve().curve_type != CurveType::ProactiveMarketMaker {
+            return Ok(());
+        }
+        let state = Self::unpack_proactive_market_maker(swap_info)?;
+        if state.last_refresh_slot != Clock::get()?.slot {
+            return Err(SwapError::StaleOraclePrice.into());
+        }
+        Ok(())
+    }
+
     /// Processes an [Initialize](enum.Instruction.html).
     pub fn process_initialize(
         program_id: &Pubkey,
@@ -220,11 +414,50 @@ impl Processor {
         let fee_account_info = next_account_info(account_info_iter)?;
         let destination_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
+        let oracle_feed_info = if swap_curve.curve_type == CurveType::ProactiveMarketMaker {
+            Some(next_account_info(account_info_iter)?)
+        } else {
+            None
+        };
+        let admin_authority = match next_account_info(account_info_iter) {
+            Ok(admin_authority_info) => {
+                if !admin_authority_info.is_signer {
+                    return Err(ProgramError::MissingRequiredSignature);
+                }
+                COption::Some(*admin_authority_info.key)
+            }
+            Err(_) => COption::None,
+        };
 
         let token_program_id = *token_program_info.key;
         if SwapVersion::is_initialized(&swap_info.data.borrow()) {
             return Err(SwapError::AlreadyInUse.into());
         }
+        // swap accounts sized for the original layout are still initialized
+        // as a SwapV1, which cannot have an admin authority
+        let is_swap_v1 = swap_info.data_len() == 1 + SwapV1::LEN;
+        let is_concentrated_liquidity = swap_curve.curve_type == CurveType::ConcentratedLiquidity;
+        if is_swap_v1
+            && (admin_authority.is_some()
+                || is_concentrated_liquidity
+                || oracle_feed_info.is_some())
+        {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        // concentrated liquidity pools keep their ticks after the swap state,
+        // and proactive market maker pools their oracle price feed
+        if !is_swap_v1 {
+            let expected_len = if is_concentrated_liquidity {
+                SwapVersion::LATEST_LEN + ConcentratedLiquidityState::LEN
+            } else if oracle_feed_info.is_some() {
+                SwapVersion::LATEST_LEN + ProactiveMarketMakerState::LEN
+            } else {
+                SwapVersion::LATEST_LEN
+            };
+            if swap_info.data_len() != expected_len {
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
 
         if *authority_info.key != Self::authority_id(program_id, swap_info.key, nonce)? {
             return Err(SwapError::InvalidProgramAddress.into());
@@ -291,7 +524,33 @@ impl Processor {
             swap_constraints.validate_fees(&fees)?;
         }
         fees.validate()?;
+        // the curve starts from the current oracle price
+        let swap_curve = if let Some(oracle_feed_info) = oracle_feed_info {
+            let slot = Clock::get()?.slot;
+            let oracle_price = Self::read_oracle_price(oracle_feed_info, &swap_curve, slot)?;
+            let state = ProactiveMarketMakerState {
+                oracle_feed: *oracle_feed_info.key,
+                last_refresh_slot: slot,
+            };
+            Self::pack_proactive_market_maker(state, swap_info)?;
+            Self::with_oracle_price(&swap_curve, oracle_price)?
+        } else {
+            swap_curve
+        };
         swap_curve.calculator.validate()?;
+        if is_concentrated_liquidity {
+            concentrated_liquidity::validate_fees(&fees)?;
+            // the initial amounts only set the price, they do not belong to
+            // any position
+            let sqrt_price = concentrated_liquidity::sqrt_price_from_amounts(
+                to_u128(token_a.amount)?,
+                to_u128(token_b.amount)?,
+            )
+            .ok_or(SwapError::CalculationFailure)?;
+            let state =
+                ConcentratedLiquidityState::new(sqrt_price).ok_or(SwapError::InvalidSupply)?;
+            Self::pack_concentrated_liquidity(state, swap_info)?;
+        }
 
         let initial_amount = swap_curve.calculator.new_pool_supply();
 
@@ -305,19 +564,41 @@ impl Processor {
             to_u64(initial_amount)?,
         )?;
 
-        let obj = SwapVersion::SwapV1(SwapV1 {
-            is_initialized: true,
-            nonce,
-            token_program_id,
-            token_a: *token_a_info.key,
-            token_b: *token_b_info.key,
-            pool_mint: *pool_mint_info.key,
-            token_a_mint: token_a.mint,
-            token_b_mint: token_b.mint,
-            pool_fee_account: *fee_account_info.key,
-            fees,
-            swap_curve,
-        });
+        let obj = if is_swap_v1 {
+            SwapVersion::SwapV1(SwapV1 {
+                is_initialized: true,
+                nonce,
+                token_program_id,
+                token_a: *token_a_info.key,
+                token_b: *token_b_info.key,
+                pool_mint: *pool_mint_info.key,
+                token_a_mint: token_a.mint,
+                token_b_mint: token_b.mint,
+                pool_fee_account: *fee_account_info.key,
+                fees,
+                swap_curve,
+            })
+        } else {
+            SwapVersion::SwapV2(SwapV2 {
+                is_initialized: true,
+                nonce,
+                token_program_id,
+                token_a: *token_a_info.key,
+                token_b: *token_b_info.key,
+                pool_mint: *pool_mint_info.key,
+                token_a_mint: token_a.mint,
+                token_b_mint: token_b.mint,
+                pool_fee_account: *fee_account_info.key,
+                fees,
+                swap_curve,
+                admin_authority,
+                is_paused: false,
+                price_accumulator: PriceAccumulator::new(Clock::get()?.slot),
+                dynamic_fee: DynamicFee::default(),
+                last_token_a_amount: token_a.amount,
+                last_token_b_amount: token_b.amount,
+            })
+        };
         SwapVersion::pack(obj, &mut swap_info.data.borrow_mut())?;
         Ok(())
     }
@@ -340,11 +621,118 @@ impl Processor {
         let pool_mint_info = next_account_info(account_info_iter)?;
         let pool_fee_account_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
+        let host_fee_account_info = next_account_info(account_info_iter).ok();
+
+        Self::swap_through_pool(
+            program_id,
+            amount_in,
+            minimum_amount_out,
+            swap_info,
+            authority_info,
+            user_transfer_authority_info,
+            source_info,
+            swap_source_info,
+            swap_destination_info,
+            destination_info,
+            pool_mint_info,
+            pool_fee_account_info,
+            token_program_info,
+            host_fee_account_info,
+        )?;
+        Ok(())
+    }
+
+    /// Processes a [RouteSwap](enum.Instruction.html).
+    pub fn process_route_swap(
+        program_id: &Pubkey,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let mut source_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut hop_accounts = account_info_iter.as_slice();
+        // the host fee account of the first pool follows the accounts of all pools
+        let mut host_fee_account_info = None;
+        if hop_accounts.len() % ROUTE_SWAP_HOP_ACCOUNTS == 1 {
+            let (last_account, pool_accounts) =
+                hop_accounts.split_last().ok_or(SwapError::InvalidRoute)?;
+            host_fee_account_info = Some(last_account);
+            hop_accounts = pool_accounts;
+        }
+        if hop_accounts.is_empty() || hop_accounts.len() % ROUTE_SWAP_HOP_ACCOUNTS != 0 {
+            return Err(SwapError::InvalidRoute.into());
+        }
+        let last_hop = hop_accounts.len() / ROUTE_SWAP_HOP_ACCOUNTS - 1;
+
+        let mut amount_in = amount_in;
+        for (hop, hop_accounts) in hop_accounts.chunks(ROUTE_SWAP_HOP_ACCOUNTS).enumerate() {
+            let hop_account_iter = &mut hop_accounts.iter();
+            let swap_info = next_account_info(hop_account_iter)?;
+            let authority_info = next_account_info(hop_account_iter)?;
+            let swap_source_info = next_account_info(hop_account_iter)?;
+            let swap_destination_info = next_account_info(hop_account_iter)?;
+            let destination_info = next_account_info(hop_account_iter)?;
+            let pool_mint_info = next_account_info(hop_account_iter)?;
+            let pool_fee_account_info = next_account_info(hop_account_iter)?;
+
+            // only the final output is protected against slippage
+            let minimum_amount_out = if hop == last_hop {
+                minimum_amount_out
+            } else {
+                0
+            };
+            amount_in = Self::swap_through_pool(
+                program_id,
+                amount_in,
+                minimum_amount_out,
+                swap_info,
+                authority_info,
+                user_transfer_authority_info,
+                source_info,
+                swap_source_info,
+                swap_destination_info,
+                destination_info,
+                pool_mint_info,
+                pool_fee_account_info,
+                token_program_info,
+                host_fee_account_info.take(),
+            )?;
+            source_info = destination_info;
+        }
+        Ok(())
+    }
 
+    /// Swaps `amount_in` source tokens through one pool, returning the amount
+    /// of destination tokens sent to the user
+    #[allow(clippy::too_many_arguments)]
+    fn swap_through_pool<'a>(
+        program_id: &Pubkey,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        swap_info: &AccountInfo<'a>,
+        authority_info: &AccountInfo<'a>,
+        user_transfer_authority_info: &AccountInfo<'a>,
+        source_info: &AccountInfo<'a>,
+        swap_source_info: &AccountInfo<'a>,
+        swap_destination_info: &AccountInfo<'a>,
+        destination_info: &AccountInfo<'a>,
+        pool_mint_info: &AccountInfo<'a>,
+        pool_fee_account_info: &AccountInfo<'a>,
+        token_program_info: &AccountInfo<'a>,
+        host_fee_account_info: Option<&AccountInfo<'a>>,
+    ) -> Result<u64, ProgramError> {
         if swap_info.owner != program_id {
             return Err(ProgramError::IncorrectProgramId);
         }
         let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if token_swap.is_paused() {
+            return Err(SwapError::SwapPaused.into());
+        }
+        Self::check_oracle_price(token_swap.as_ref(), swap_info)?;
 
         if *authority_info.key != Self::authority_id(program_id, swap_info.key, token_swap.nonce())?
         {
@@ -390,31 +778,57 @@ impl Processor {
         } else {
             TradeDirection::BtoA
         };
-        let result = token_swap
-            .swap_curve()
-            .swap(
-                to_u128(amount_in)?,
-                to_u128(source_account.amount)?,
-                to_u128(dest_account.amount)?,
-                trade_direction,
-                token_swap.fees(),
-            )
-            .ok_or(SwapError::ZeroTradingTokens)?;
-        if result.destination_amount_swapped < to_u128(minimum_amount_out)? {
+        match trade_direction {
+            TradeDirection::AtoB => {
+                Self::observe_prices(swap_info, source_account.amount, dest_account.amount)
+            }
+            TradeDirection::BtoA => {
+                Self::observe_prices(swap_info, dest_account.amount, source_account.amount)
+            }
+        }?;
+        // the dynamic trade fee depends on the price just observed
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        let (token_a_amount, token_b_amount) = match trade_direction {
+            TradeDirection::AtoB => (source_account.amount, dest_account.amount),
+            TradeDirection::BtoA => (dest_account.amount, source_account.amount),
+        };
+        let quoter = Quoter::new(
+            token_swap.as_ref(),
+            token_a_amount,
+            token_b_amount,
+            pool_mint.supply,
+        );
+        let quote = if token_swap.swap_curve().curve_type == CurveType::ConcentratedLiquidity {
+            let swap_liquidity = |fees: &Fees| -> Result<_, ProgramError> {
+                let mut state = Self::unpack_concentrated_liquidity(swap_info)?;
+                let result = state
+                    .swap(
+                        to_u128(amount_in)?,
+                        to_u128(source_account.amount)?,
+                        to_u128(dest_account.amount)?,
+                        trade_direction,
+                        fees,
+                    )
+                    .ok_or(SwapError::ZeroTradingTokens)?;
+                Ok((state, result))
+            };
+            let quoter = if quoter.dynamic_fee.is_some() {
+                // the swap pays for the price movement it causes
+                let (state, _) = swap_liquidity(&quoter.fees)?;
+                quoter.after_trade(state.spot_price().as_ref())
+            } else {
+                quoter
+            };
+            let (state, result) = swap_liquidity(&quoter.fees)?;
+            Self::pack_concentrated_liquidity(state, swap_info)?;
+            quoter.swap_result(trade_direction, &result, host_fee_account_info.is_some())?
+        } else {
+            quoter.swap(trade_direction, amount_in, host_fee_account_info.is_some())?
+        };
+        if quote.amount_out < minimum_amount_out {
             return Err(SwapError::ExceededSlippage.into());
         }
 
-        let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
-            TradeDirection::AtoB => (
-                result.new_swap_source_amount,
-                result.new_swap_destination_amount,
-            ),
-            TradeDirection::BtoA => (
-                result.new_swap_destination_amount,
-                result.new_swap_source_amount,
-            ),
-        };
-
         Self::token_transfer(
             swap_info.key,
             token_program_info.clone(),
@@ -422,25 +836,11 @@ impl Processor {
             swap_source_info.clone(),
             user_transfer_authority_info.clone(),
             token_swap.nonce(),
-            to_u64(result.source_amount_swapped)?,
+            quote.amount_in,
         )?;
 
-        let mut pool_token_amount = token_swap
-            .swap_curve()
-            .trading_tokens_to_pool_tokens(
-                result.owner_fee,
-                swap_token_a_amount,
-                swap_token_b_amount,
-                to_u128(pool_mint.supply)?,
-                trade_direction,
-                RoundDirection::Ceiling,
-                token_swap.fees(),
-            )
-            .ok_or(SwapError::FeeCalculationFailure)?;
-
-        if pool_token_amount > 0 {
-            // Allow error to fall through
-            if let Ok(host_fee_account_info) = next_account_info(account_info_iter) {
+        if quote.fees.owner_fee > 0 || quote.fees.host_fee > 0 {
+            if let Some(host_fee_account_info) = host_fee_account_info {
                 let host_fee_account = Self::unpack_token_account(
                     host_fee_account_info,
                     token_swap.token_program_id(),
@@ -448,14 +848,7 @@ impl Processor {
                 if *pool_mint_info.key != host_fee_account.mint {
                     return Err(SwapError::IncorrectPoolMint.into());
                 }
-                let host_fee = token_swap
-                    .fees()
-                    .host_fee(pool_token_amount)
-                    .ok_or(SwapError::FeeCalculationFailure)?;
-                if host_fee > 0 {
-                    pool_token_amount = pool_token_amount
-                        .checked_sub(host_fee)
-                        .ok_or(SwapError::FeeCalculationFailure)?;
+                if quote.fees.host_fee > 0 {
                     Self::token_mint_to(
                         swap_info.key,
                         token_program_info.clone(),
@@ -463,7 +856,7 @@ impl Processor {
                         host_fee_account_info.clone(),
                         authority_info.clone(),
                         token_swap.nonce(),
-                        to_u64(host_fee)?,
+                        quote.fees.host_fee,
                     )?;
                 }
             }
@@ -474,7 +867,7 @@ impl Processor {
                 pool_fee_account_info.clone(),
                 authority_info.clone(),
                 token_swap.nonce(),
-                to_u64(pool_token_amount)?,
+                quote.fees.owner_fee,
             )?;
         }
 
@@ -485,10 +878,40 @@ impl Processor {
             destination_info.clone(),
             authority_info.clone(),
             token_swap.nonce(),
-            to_u64(result.destination_amount_swapped)?,
+            quote.amount_out,
         )?;
 
-        Ok(())
+        // the price left by the swap becomes the reference of the dynamic fee
+        let new_swap_source_amount = source_account
+            .amount
+            .checked_add(quote.amount_in)
+            .ok_or(SwapError::CalculationFailure)?;
+        let new_swap_destination_amount = dest_account
+            .amount
+            .checked_sub(quote.amount_out)
+            .ok_or(SwapError::CalculationFailure)?;
+        match trade_direction {
+            TradeDirection::AtoB => Self::observe_prices(
+                swap_info,
+                new_swap_source_amount,
+                new_swap_destination_amount,
+            ),
+            TradeDirection::BtoA => Self::observe_prices(
+                swap_info,
+                new_swap_destination_amount,
+                new_swap_source_amount,
+            ),
+        }?;
+        match trade_direction {
+            TradeDirection::AtoB => {
+                Self::record_settled_amounts(swap_info, swap_source_info, swap_destination_info)
+            }
+            TradeDirection::BtoA => {
+                Self::record_settled_amounts(swap_info, swap_destination_info, swap_source_info)
+            }
+        }?;
+
+        Ok(quote.amount_out)
     }
 
     /// Processes an [DepositAllTokenTypes](enum.Instruction.html).
@@ -512,8 +935,10 @@ impl Processor {
         let token_program_info = next_account_info(account_info_iter)?;
 
         let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
-        let calculator = &token_swap.swap_curve().calculator;
-        if !calculator.allows_deposits() {
+        if token_swap.is_paused() {
+            return Err(SwapError::SwapPaused.into());
+        }
+        if !token_swap.swap_curve().calculator.allows_deposits() {
             return Err(SwapError::UnsupportedCurveOperation.into());
         }
         Self::check_accounts(
@@ -532,35 +957,21 @@ impl Processor {
 
         let token_a = Self::unpack_token_account(token_a_info, &token_swap.token_program_id())?;
         let token_b = Self::unpack_token_account(token_b_info, &token_swap.token_program_id())?;
+        Self::observe_prices(swap_info, token_a.amount, token_b.amount)?;
         let pool_mint = Self::unpack_mint(pool_mint_info, &token_swap.token_program_id())?;
-        let pool_token_amount = to_u128(pool_token_amount)?;
-        let pool_mint_supply = to_u128(pool_mint.supply)?;
 
-        let results = calculator
-            .pool_tokens_to_trading_tokens(
-                pool_token_amount,
-                pool_mint_supply,
-                to_u128(token_a.amount)?,
-                to_u128(token_b.amount)?,
-                RoundDirection::Ceiling,
-            )
-            .ok_or(SwapError::ZeroTradingTokens)?;
-        let token_a_amount = to_u64(results.token_a_amount)?;
-        if token_a_amount > maximum_token_a_amount {
-            return Err(SwapError::ExceededSlippage.into());
-        }
-        if token_a_amount == 0 {
-            return Err(SwapError::ZeroTradingTokens.into());
-        }
-        let token_b_amount = to_u64(results.token_b_amount)?;
-        if token_b_amount > maximum_token_b_amount {
+        let quote = Quoter::new(
+            token_swap.as_ref(),
+            token_a.amount,
+            token_b.amount,
+            pool_mint.supply,
+        )
+        .deposit_all_token_types(pool_token_amount)?;
+        if quote.token_a_amount > maximum_token_a_amount
+            || quote.token_b_amount > maximum_token_b_amount
+        {
             return Err(SwapError::ExceededSlippage.into());
         }
-        if token_b_amount == 0 {
-            return Err(SwapError::ZeroTradingTokens.into());
-        }
-
-        let pool_token_amount = to_u64(pool_token_amount)?;
 
         Self::token_transfer(
             swap_info.key,
@@ -569,7 +980,7 @@ impl Processor {
             token_a_info.clone(),
             user_transfer_authority_info.clone(),
             token_swap.nonce(),
-            token_a_amount,
+            quote.token_a_amount,
         )?;
         Self::token_transfer(
             swap_info.key,
@@ -578,7 +989,7 @@ impl Processor {
             token_b_info.clone(),
             user_transfer_authority_info.clone(),
             token_swap.nonce(),
-            token_b_amount,
+            quote.token_b_amount,
         )?;
         Self::token_mint_to(
             swap_info.key,
@@ -587,10 +998,10 @@ impl Processor {
             dest_info.clone(),
             authority_info.clone(),
             token_swap.nonce(),
-            pool_token_amount,
+            quote.pool_token_amount,
         )?;
 
-        Ok(())
+        Self::record_settled_amounts(swap_info, token_a_info, token_b_info)
     }
 
     /// Processes an [WithdrawAllTokenTypes](enum.Instruction.html).
@@ -615,6 +1026,9 @@ impl Processor {
         let token_program_info = next_account_info(account_info_iter)?;
 
         let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if token_swap.swap_curve().curve_type == CurveType::ConcentratedLiquidity {
+            return Err(SwapError::UnsupportedCurveOperation.into());
+        }
         Self::check_accounts(
             token_swap.as_ref(),
             program_id,
@@ -631,48 +1045,25 @@ impl Processor {
 
         let token_a = Self::unpack_token_account(token_a_info, token_swap.token_program_id())?;
         let token_b = Self::unpack_token_account(token_b_info, token_swap.token_program_id())?;
+        Self::observe_prices(swap_info, token_a.amount, token_b.amount)?;
         let pool_mint = Self::unpack_mint(pool_mint_info, token_swap.token_program_id())?;
 
-        let calculator = &token_swap.swap_curve().calculator;
-
-        let withdraw_fee: u128 = if *pool_fee_account_info.key == *source_info.key {
-            // withdrawing from the fee account, don't assess withdraw fee
-            0
-        } else {
-            token_swap
-                .fees()
-                .owner_withdraw_fee(to_u128(pool_token_amount)?)
-                .ok_or(SwapError::FeeCalculationFailure)?
-        };
-        let pool_token_amount = to_u128(pool_token_amount)?
-            .checked_sub(withdraw_fee)
-            .ok_or(SwapError::CalculationFailure)?;
-
-        let results = calculator
-            .pool_tokens_to_trading_tokens(
-                pool_token_amount,
-                to_u128(pool_mint.supply)?,
-                to_u128(token_a.amount)?,
-                to_u128(token_b.amount)?,
-                RoundDirection::Floor,
-            )
-            .ok_or(SwapError::ZeroTradingTokens)?;
-        let token_a_amount = to_u64(results.token_a_amount)?;
-        if token_a_amount < minimum_token_a_amount {
-            return Err(SwapError::ExceededSlippage.into());
-        }
-        if token_a_amount == 0 && token_a.amount != 0 {
-            return Err(SwapError::ZeroTradingTokens.into());
-        }
-        let token_b_amount = to_u64(results.token_b_amount)?;
-        if token_b_amount < minimum_token_b_amount {
+        // withdrawing from the fee account, don't assess withdraw fee
+        let with_withdraw_fee = *pool_fee_account_info.key != *source_info.key;
+        let quote = Quoter::new(
+            token_swap.as_ref(),
+            token_a.amount,
+            token_b.amount,
+            pool_mint.supply,
+        )
+        .withdraw_all_token_types(pool_token_amount, with_withdraw_fee)?;
+        if quote.token_a_amount < minimum_token_a_amount
+            || quote.token_b_amount < minimum_token_b_amount
+        {
             return Err(SwapError::ExceededSlippage.into());
         }
-        if token_b_amount == 0 && token_b.amount != 0 {
-            return Err(SwapError::ZeroTradingTokens.into());
-        }
 
-        if withdraw_fee > 0 {
+        if quote.fees.owner_fee > 0 {
             Self::token_transfer(
                 swap_info.key,
                 token_program_info.clone(),
@@ -680,7 +1071,7 @@ impl Processor {
                 pool_fee_account_info.clone(),
                 user_transfer_authority_info.clone(),
                 token_swap.nonce(),
-                to_u64(withdraw_fee)?,
+                quote.fees.owner_fee,
             )?;
         }
         Self::token_burn(
@@ -690,11 +1081,13 @@ impl Processor {
             pool_mint_info.clone(),
             user_transfer_authority_info.clone(),
             token_swap.nonce(),
-            to_u64(pool_token_amount)?,
+            quote
+                .pool_token_amount
+                .checked_sub(quote.fees.owner_fee)
+                .ok_or(SwapError::CalculationFailure)?,
         )?;
 
-        let token_a_amount = std::cmp::min(token_a.amount, token_a_amount);
-        if token_a_amount > 0 {
+        if quote.token_a_amount > 0 {
             Self::token_transfer(
                 swap_info.key,
                 token_program_info.clone(),
@@ -702,11 +1095,10 @@ impl Processor {
                 dest_token_a_info.clone(),
                 authority_info.clone(),
                 token_swap.nonce(),
-                token_a_amount,
+                quote.token_a_amount,
             )?;
         }
-        let token_b_amount = std::cmp::min(token_b.amount, token_b_amount);
-        if token_b_amount > 0 {
+        if quote.token_b_amount > 0 {
             Self::token_transfer(
                 swap_info.key,
                 token_program_info.clone(),
@@ -714,10 +1106,10 @@ impl Processor {
                 dest_token_b_info.clone(),
                 authority_info.clone(),
                 token_swap.nonce(),
-                token_b_amount,
+                quote.token_b_amount,
             )?;
         }
-        Ok(())
+        Self::record_settled_amounts(swap_info, token_a_info, token_b_info)
     }
 
     /// Processes DepositSingleTokenTypeExactAmountIn
@@ -739,12 +1131,19 @@ impl Processor {
         let token_program_info = next_account_info(account_info_iter)?;
 
         let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if token_swap.is_paused() {
+            return Err(SwapError::SwapPaused.into());
+        }
+        Self::check_oracle_price(token_swap.as_ref(), swap_info)?;
         let source_account =
             Self::unpack_token_account(source_info, &token_swap.token_program_id())?;
         let swap_token_a =
             Self::unpack_token_account(swap_token_a_info, &token_swap.token_program_id())?;
         let swap_token_b =
             Self::unpack_token_account(swap_token_b_info, &token_swap.token_program_id())?;
+        Self::observe_prices(swap_info, swap_token_a.amount, swap_token_b.amount)?;
+        // the dynamic trade fee depends on the price just observed
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
 
         let trade_direction = if source_account.mint == swap_token_a.mint {
             TradeDirection::AtoB
@@ -774,28 +1173,17 @@ impl Processor {
         )?;
 
         let pool_mint = Self::unpack_mint(pool_mint_info, &token_swap.token_program_id())?;
-        let pool_mint_supply = to_u128(pool_mint.supply)?;
-
-        let pool_token_amount = token_swap
-            .swap_curve()
-            .trading_tokens_to_pool_tokens(
-                to_u128(source_token_amount)?,
-                to_u128(swap_token_a.amount)?,
-                to_u128(swap_token_b.amount)?,
-                pool_mint_supply,
-                trade_direction,
-                RoundDirection::Floor,
-                token_swap.fees(),
-            )
-            .ok_or(SwapError::ZeroTradingTokens)?;
 
-        let pool_token_amount = to_u64(pool_token_amount)?;
-        if pool_token_amount < minimum_pool_token_amount {
+        let quote = Quoter::new(
+            token_swap.as_ref(),
+            swap_token_a.amount,
+            swap_token_b.amount,
+            pool_mint.supply,
+        )
+        .deposit_single_token_type_exact_amount_in(trade_direction, source_token_amount)?;
+        if quote.pool_token_amount < minimum_pool_token_amount {
             return Err(SwapError::ExceededSlippage.into());
         }
-        if pool_token_amount == 0 {
-            return Err(SwapError::ZeroTradingTokens.into());
-        }
 
         match trade_direction {
             TradeDirection::AtoB => {
@@ -828,10 +1216,10 @@ impl Processor {
             destination_info.clone(),
             authority_info.clone(),
             token_swap.nonce(),
-            pool_token_amount,
+            quote.pool_token_amount,
         )?;
 
-        Ok(())
+        Self::record_settled_amounts(swap_info, swap_token_a_info, swap_token_b_info)
     }
 
     /// Processes a [WithdrawSingleTokenTypeExactAmountOut](enum.Instruction.html).
@@ -854,12 +1242,19 @@ impl Processor {
         let token_program_info = next_account_info(account_info_iter)?;
 
         let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if token_swap.swap_curve().curve_type == CurveType::ConcentratedLiquidity {
+            return Err(SwapError::UnsupportedCurveOperation.into());
+        }
+        Self::check_oracle_price(token_swap.as_ref(), swap_info)?;
         let destination_account =
             Self::unpack_token_account(destination_info, &token_swap.token_program_id())?;
         let swap_token_a =
             Self::unpack_token_account(swap_token_a_info, &token_swap.token_program_id())?;
         let swap_token_b =
             Self::unpack_token_account(swap_token_b_info, &token_swap.token_program_id())?;
+        Self::observe_prices(swap_info, swap_token_a.amount, swap_token_b.amount)?;
+        // the dynamic trade fee depends on the price just observed
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
 
         let trade_direction = if destination_account.mint == swap_token_a.mint {
             TradeDirection::AtoB
@@ -888,62 +1283,25 @@ impl Processor {
         )?;
 
         let pool_mint = Self::unpack_mint(pool_mint_info, &token_swap.token_program_id())?;
-        let pool_mint_supply = to_u128(pool_mint.supply)?;
-        let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
-            TradeDirection::AtoB => (
-                to_u128(
-                    swap_token_a
-                        .amount
-                        .checked_sub(destination_token_amount)
-                        .ok_or(SwapError::CalculationFailure)?,
-                )?,
-                to_u128(swap_token_b.amount)?,
-            ),
-            TradeDirection::BtoA => (
-                to_u128(swap_token_a.amount)?,
-                to_u128(
-                    swap_token_b
-                        .amount
-                        .checked_sub(destination_token_amount)
-                        .ok_or(SwapError::CalculationFailure)?,
-                )?,
-            ),
-        };
-
-        let burn_pool_token_amount = token_swap
-            .swap_curve()
-            .trading_tokens_to_pool_tokens(
-                to_u128(destination_token_amount)?,
-                swap_token_a_amount,
-                swap_token_b_amount,
-                pool_mint_supply,
-                trade_direction,
-                RoundDirection::Ceiling,
-                token_swap.fees(),
-            )
-            .ok_or(SwapError::ZeroTradingTokens)?;
-
-        let withdraw_fee: u128 = if *pool_fee_account_info.key == *source_info.key {
-            // withdrawing from the fee account, don't assess withdraw fee
-            0
-        } else {
-            token_swap
-                .fees()
-                .owner_withdraw_fee(burn_pool_token_amount)
-                .ok_or(SwapError::FeeCalculationFailure)?
-        };
-        let pool_token_amount = burn_pool_token_amount
-            .checked_add(withdraw_fee)
-            .ok_or(SwapError::CalculationFailure)?;
 
-        if to_u64(pool_token_amount)? > maximum_pool_token_amount {
+        // withdrawing from the fee account, don't assess withdraw fee
+        let with_withdraw_fee = *pool_fee_account_info.key != *source_info.key;
+        let quote = Quoter::new(
+            token_swap.as_ref(),
+            swap_token_a.amount,
+            swap_token_b.amount,
+            pool_mint.supply,
+        )
+        .withdraw_single_token_type_exact_amount_out(
+            trade_direction,
+            destination_token_amount,
+            with_withdraw_fee,
+        )?;
+        if quote.pool_token_amount > maximum_pool_token_amount {
             return Err(SwapError::ExceededSlippage.into());
         }
-        if pool_token_amount == 0 {
-            return Err(SwapError::ZeroTradingTokens.into());
-        }
 
-        if withdraw_fee > 0 {
+        if quote.fees.owner_fee > 0 {
             Self::token_transfer(
                 swap_info.key,
                 token_program_info.clone(),
@@ -951,7 +1309,7 @@ impl Processor {
                 pool_fee_account_info.clone(),
                 user_transfer_authority_info.clone(),
                 token_swap.nonce(),
-                to_u64(withdraw_fee)?,
+                quote.fees.owner_fee,
             )?;
         }
         Self::token_burn(
@@ -961,7 +1319,10 @@ impl Processor {
             pool_mint_info.clone(),
             user_transfer_authority_info.clone(),
             token_swap.nonce(),
-            to_u64(burn_pool_token_amount)?,
+            quote
+                .pool_token_amount
+                .checked_sub(quote.fees.owner_fee)
+                .ok_or(SwapError::CalculationFailure)?,
         )?;
 
         match trade_direction {
@@ -989,970 +1350,2121 @@ impl Processor {
             }
         }
 
-        Ok(())
+        Self::record_settled_amounts(swap_info, swap_token_a_info, swap_token_b_info)
     }
 
-    /// Processes an [Instruction](enum.Instruction.html).
-    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
-        Self::process_with_constraints(program_id, accounts, input, &SWAP_CONSTRAINTS)
+    /// Unpacks a swap which can be administered by the given admin authority
+    fn unpack_admin_swap(
+        program_id: &Pubkey,
+        swap_info: &AccountInfo,
+        admin_authority_info: &AccountInfo,
+    ) -> Result<SwapV2, ProgramError> {
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_swap = {
+            let data = swap_info.data.borrow();
+            let (&version, rest) = data.split_first().ok_or(ProgramError::InvalidAccountData)?;
+            if version != 2 {
+                return Err(SwapError::MissingAdminAuthority.into());
+            }
+            SwapV2::unpack(
+                rest.get(..SwapV2::LEN)
+                    .ok_or(ProgramError::InvalidAccountData)?,
+            )?
+        };
+        match token_swap.admin_authority {
+            COption::Some(admin_authority) if admin_authority == *admin_authority_info.key => {}
+            COption::Some(_) => return Err(SwapError::InvalidAdminAuthority.into()),
+            COption::None => return Err(SwapError::MissingAdminAuthority.into()),
+        }
+        if !admin_authority_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(token_swap)
     }
 
-    /// Processes an instruction given extra constraint
-    pub fn process_with_constraints(
+    /// Processes a [SetFees](enum.Instruction.html).
+    pub fn process_set_fees(
         program_id: &Pubkey,
+        fees: Fees,
         accounts: &[AccountInfo],
-        input: &[u8],
         swap_constraints: &Option<SwapConstraints>,
     ) -> ProgramResult {
-        let instruction = SwapInstruction::unpack(input)?;
-        match instruction {
-            SwapInstruction::Initialize(Initialize {
-                nonce,
-                fees,
-                swap_curve,
-            }) => {
-                msg!("Instruction: Init");
-                Self::process_initialize(
-                    program_id,
-                    nonce,
-                    fees,
-                    swap_curve,
-                    accounts,
-                    swap_constraints,
-                )
-            }
-            SwapInstruction::Swap(Swap {
-                amount_in,
-                minimum_amount_out,
-            }) => {
-                msg!("Instruction: Swap");
-                Self::process_swap(program_id, amount_in, minimum_amount_out, accounts)
-            }
-            SwapInstruction::DepositAllTokenTypes(DepositAllTokenTypes {
-                pool_token_amount,
-                maximum_token_a_amount,
-                maximum_token_b_amount,
-            }) => {
-                msg!("Instruction: DepositAllTokenTypes");
-                Self::process_deposit_all_token_types(
-                    program_id,
-                    pool_token_amount,
-                    maximum_token_a_amount,
-                    maximum_token_b_amount,
-                    accounts,
-                )
-            }
-            SwapInstruction::WithdrawAllTokenTypes(WithdrawAllTokenTypes {
-                pool_token_amount,
-                minimum_token_a_amount,
-                minimum_token_b_amount,
-            }) => {
-                msg!("Instruction: WithdrawAllTokenTypes");
-                Self::process_withdraw_all_token_types(
-                    program_id,
-                    pool_token_amount,
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                    accounts,
-                )
-            }
-            SwapInstruction::DepositSingleTokenTypeExactAmountIn(
-                DepositSingleTokenTypeExactAmountIn {
-                    source_token_amount,
-                    minimum_pool_token_amount,
-                },
-            ) => {
-                msg!("Instruction: DepositSingleTokenTypeExactAmountIn");
-                Self::process_deposit_single_token_type_exact_amount_in(
-                    program_id,
-                    source_token_amount,
-                    minimum_pool_token_amount,
-                    accounts,
-                )
-            }
-            SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(
-                WithdrawSingleTokenTypeExactAmountOut {
-                    destination_token_amount,
-                    maximum_pool_token_amount,
-                },
-            ) => {
-                msg!("Instruction: WithdrawSingleTokenTypeExactAmountOut");
-                Self::process_withdraw_single_token_type_exact_amount_out(
-                    program_id,
-                    destination_token_amount,
-                    maximum_pool_token_amount,
-                    accounts,
-                )
-            }
-        }
-    }
-}
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_authority_info = next_account_info(account_info_iter)?;
 
-impl PrintProgramError for SwapError {
-    fn print<E>(&self)
-    where
-        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
-    {
-        match self {
-            SwapError::AlreadyInUse => msg!("Error: Swap account already in use"),
-            SwapError::InvalidProgramAddress => {
-                msg!("Error: Invalid program address generated from nonce and key")
-            }
-            SwapError::InvalidOwner => {
-                msg!("Error: The input account owner is not the program address")
-            }
-            SwapError::InvalidOutputOwner => {
-                msg!("Error: Output pool account owner cannot be the program address")
-            }
-            SwapError::ExpectedMint => msg!("Error: Deserialized account is not an SPL Token mint"),
-            SwapError::ExpectedAccount => {
-                msg!("Error: Deserialized account is not an SPL Token account")
-            }
-            SwapError::EmptySupply => msg!("Error: Input token account empty"),
-            SwapError::InvalidSupply => msg!("Error: Pool token mint has a non-zero supply"),
-            SwapError::RepeatedMint => msg!("Error: Swap input token accounts have the same mint"),
-            SwapError::InvalidDelegate => msg!("Error: Token account has a delegate"),
-            SwapError::InvalidInput => msg!("Error: InvalidInput"),
-            SwapError::IncorrectSwapAccount => {
-                msg!("Error: Address of the provided swap token account is incorrect")
-            }
-            SwapError::IncorrectPoolMint => {
-                msg!("Error: Address of the provided pool token mint is incorrect")
-            }
-            SwapError::InvalidOutput => msg!("Error: InvalidOutput"),
-            SwapError::CalculationFailure => msg!("Error: CalculationFailure"),
-            SwapError::InvalidInstruction => msg!("Error: InvalidInstruction"),
-            SwapError::ExceededSlippage => {
-                msg!("Error: Swap instruction exceeds desired slippage limit")
-            }
-            SwapError::InvalidCloseAuthority => msg!("Error: Token account has a close authority"),
-            SwapError::InvalidFreezeAuthority => {
-                msg!("Error: Pool token mint has a freeze authority")
-            }
-            SwapError::IncorrectFeeAccount => msg!("Error: Pool fee token account incorrect"),
-            SwapError::ZeroTradingTokens => {
-                msg!("Error: Given pool token amount results in zero trading tokens")
-            }
-            SwapError::FeeCalculationFailure => msg!(
-                "Error: The fee calculation failed due to overflow, underflow, or unexpected 0"
-            ),
-            SwapError::ConversionFailure => msg!("Error: Conversion to or from u64 failed."),
-            SwapError::InvalidFee => {
-                msg!("Error: The provided fee does not match the program owner's constraints")
-            }
-            SwapError::IncorrectTokenProgramId => {
-                msg!("Error: The provided token program does not match the token program expected by the swap")
-            }
-            SwapError::UnsupportedCurveType => {
-                msg!("Error: The provided curve type is not supported by the program owner")
-            }
-            SwapError::InvalidCurve => {
-                msg!("Error: The provided curve parameters are invalid")
-            }
-            SwapError::UnsupportedCurveOperation => {
-                msg!("Error: The operation cannot be performed on the given curve")
-            }
+        let mut token_swap = Self::unpack_admin_swap(program_id, swap_info, admin_authority_info)?;
+        if let Some(swap_constraints) = swap_constraints {
+            swap_constraints.validate_fees(&fees)?;
+        }
+        fees.validate()?;
+        if token_swap.swap_curve.curve_type == CurveType::ConcentratedLiquidity {
+            concentrated_liquidity::validate_fees(&fees)?;
         }
-    }
-}
 
-fn to_u128(val: u64) -> Result<u128, SwapError> {
-    val.try_into().map_err(|_| SwapError::ConversionFailure)
-}
+        token_swap.fees = fees;
+        SwapVersion::pack(
+            SwapVersion::SwapV2(token_swap),
+            &mut swap_info.data.borrow_mut(),
+        )?;
+        Ok(())
+    }
 
-fn to_u64(val: u128) -> Result<u64, SwapError> {
-    val.try_into().map_err(|_| SwapError::ConversionFailure)
-}
+    /// Processes a [SetPaused](enum.Instruction.html).
+    pub fn process_set_paused(
+        program_id: &Pubkey,
+        paused: bool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_authority_info = next_account_info(account_info_iter)?;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{
-        curve::calculator::{CurveCalculator, INITIAL_SWAP_POOL_AMOUNT},
-        curve::{
-            base::CurveType, constant_price::ConstantPriceCurve,
-            constant_product::ConstantProductCurve, offset::OffsetCurve,
-        },
-        instruction::{
-            deposit_all_token_types, deposit_single_token_type_exact_amount_in, initialize, swap,
-            withdraw_all_token_types, withdraw_single_token_type_exact_amount_out,
-        },
-    };
-    use solana_program::{instruction::Instruction, program_stubs, rent::Rent};
-    use solana_sdk::account::{create_account_for_test, create_is_signer_account_infos, Account};
-    use spl_token::{
-        error::TokenError,
-        instruction::{
-            approve, initialize_account, initialize_mint, mint_to, revoke, set_authority,
-            AuthorityType,
-        },
-    };
+        let mut token_swap = Self::unpack_admin_swap(program_id, swap_info, admin_authority_info)?;
+        token_swap.is_paused = paused;
+        SwapVersion::pack(
+            SwapVersion::SwapV2(token_swap),
+            &mut swap_info.data.borrow_mut(),
+        )?;
+        Ok(())
+    }
 
-    // Test program id for the swap program.
-    const SWAP_PROGRAM_ID: Pubkey = Pubkey::new_from_array([2u8; 32]);
-    // Test program id for the token program.
-    const TOKEN_PROGRAM_ID: Pubkey = Pubkey::new_from_array([1u8; 32]);
+    /// Processes a [SetDynamicFee](enum.Instruction.html).
+    pub fn process_set_dynamic_fee(
+        program_id: &Pubkey,
+        parameters: DynamicFeeParameters,
+        accounts: &[AccountInfo],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_authority_info = next_account_info(account_info_iter)?;
 
-    struct TestSyscallStubs {}
-    impl program_stubs::SyscallStubs for TestSyscallStubs {
-        fn sol_invoke_signed(
-            &self,
-            instruction: &Instruction,
-            account_infos: &[AccountInfo],
-            signers_seeds: &[&[&[u8]]],
-        ) -> ProgramResult {
-            msg!("TestSyscallStubs::sol_invoke_signed()");
+        let mut token_swap = Self::unpack_admin_swap(program_id, swap_info, admin_authority_info)?;
+        if let Some(swap_constraints) = swap_constraints {
+            swap_constraints.validate_dynamic_fee(&parameters)?;
+        }
+        parameters.validate()?;
 
-            let mut new_account_infos = vec![];
+        token_swap.dynamic_fee = DynamicFee::new(parameters);
+        SwapVersion::pack(
+            SwapVersion::SwapV2(token_swap),
+            &mut swap_info.data.borrow_mut(),
+        )?;
+        Ok(())
+    }
 
-            // mimic check for token program in accounts
-            if !account_infos.iter().any(|x| *x.key == TOKEN_PROGRAM_ID) {
-                return Err(ProgramError::InvalidAccountData);
-            }
+    /// Processes a [RefreshOraclePrice](enum.Instruction.html).
+    pub fn process_refresh_oracle_price(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let oracle_feed_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
 
-            for meta in instruction.accounts.iter() {
-                for account_info in account_infos.iter() {
-                    if meta.pubkey == *account_info.key {
-                        let mut new_account_info = account_info.clone();
-                        for seeds in signers_seeds.iter() {
-                            let signer =
-                                Pubkey::create_program_address(&seeds, &SWAP_PROGRAM_ID).unwrap();
-                            if *account_info.key == signer {
-                                new_account_info.is_signer = true;
-                            }
-                        }
-                        new_account_infos.push(new_account_info);
-                    }
-                }
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut token_swap = {
+            let data = swap_info.data.borrow();
+            match data.split_first() {
+                Some((&2, rest)) => SwapV2::unpack(
+                    rest.get(..SwapV2::LEN)
+                        .ok_or(ProgramError::InvalidAccountData)?,
+                )?,
+                _ => return Err(SwapError::UnsupportedCurveOperation.into()),
             }
-
-            spl_token::processor::Processor::process(
-                &instruction.program_id,
-                &new_account_infos,
-                &instruction.data,
-            )
+        };
+        if token_swap.swap_curve.curve_type != CurveType::ProactiveMarketMaker {
+            return Err(SwapError::UnsupportedCurveOperation.into());
+        }
+        let mut state = Self::unpack_proactive_market_maker(swap_info)?;
+        if *oracle_feed_info.key != state.oracle_feed {
+            return Err(SwapError::InvalidOracle.into());
         }
+        if *token_a_info.key != token_swap.token_a {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if *token_b_info.key != token_swap.token_b {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        Self::unpack_token_account(token_a_info, &token_swap.token_program_id)?;
+        Self::unpack_token_account(token_b_info, &token_swap.token_program_id)?;
+
+        let slot = Clock::get()?.slot;
+        let oracle_price = Self::read_oracle_price(oracle_feed_info, &token_swap.swap_curve, slot)?;
+        // the previous price prevailed until now, and the new oracle price
+        // applies to the amounts the last instruction settled with
+        token_swap.price_accumulator.update(
+            slot,
+            token_swap.swap_curve.calculator.as_ref(),
+            to_u128(token_swap.last_token_a_amount)?,
+            to_u128(token_swap.last_token_b_amount)?,
+        );
+        token_swap.swap_curve = Self::with_oracle_price(&token_swap.swap_curve, oracle_price)?;
+        state.last_refresh_slot = slot;
+        SwapVersion::pack(
+            SwapVersion::SwapV2(token_swap),
+            &mut swap_info.data.borrow_mut(),
+        )?;
+        Self::pack_proactive_market_maker(state, swap_info)
     }
 
-    fn test_syscall_stubs() {
-        use std::sync::Once;
-        static ONCE: Once = Once::new();
-
-        ONCE.call_once(|| {
-            program_stubs::set_syscall_stubs(Box::new(TestSyscallStubs {}));
-        });
+    /// Unpacks the parameters of a concentrated liquidity pool
+    fn unpack_concentrated_liquidity_curve(
+        token_swap: &dyn SwapState,
+    ) -> Result<ConcentratedLiquidityCurve, ProgramError> {
+        let mut packed_curve = [0u8; ConcentratedLiquidityCurve::LEN];
+        token_swap
+            .swap_curve()
+            .calculator
+            .pack_into_slice(&mut packed_curve);
+        ConcentratedLiquidityCurve::unpack_from_slice(&packed_curve)
     }
 
-    struct SwapAccountInfo {
-        nonce: u8,
-        authority_key: Pubkey,
-        fees: Fees,
-        swap_curve: SwapCurve,
-        swap_key: Pubkey,
-        swap_account: Account,
-        pool_mint_key: Pubkey,
-        pool_mint_account: Account,
-        pool_fee_key: Pubkey,
-        pool_fee_account: Account,
-        pool_token_key: Pubkey,
-        pool_token_account: Account,
-        token_a_key: Pubkey,
-        token_a_account: Account,
-        token_a_mint_key: Pubkey,
-        token_a_mint_account: Account,
-        token_b_key: Pubkey,
-        token_b_account: Account,
-        token_b_mint_key: Pubkey,
-        token_b_mint_account: Account,
+    /// Checks the accounts of a concentrated liquidity position instruction,
+    /// returning the state of the pool and the position
+    #[allow(clippy::too_many_arguments)]
+    fn unpack_position(
+        token_swap: &dyn SwapState,
+        program_id: &Pubkey,
+        swap_info: &AccountInfo,
+        authority_info: &AccountInfo,
+        position_info: &AccountInfo,
+        owner_info: &AccountInfo,
+        token_a_info: &AccountInfo,
+        token_b_info: &AccountInfo,
+        token_program_info: &AccountInfo,
+    ) -> Result<(ConcentratedLiquidityState, Position), ProgramError> {
+        if swap_info.owner != program_id || position_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if token_swap.swap_curve().curve_type != CurveType::ConcentratedLiquidity {
+            return Err(SwapError::UnsupportedCurveOperation.into());
+        }
+        if *authority_info.key != Self::authority_id(program_id, swap_info.key, token_swap.nonce())?
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if *token_a_info.key != *token_swap.token_a_account() {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if *token_b_info.key != *token_swap.token_b_account() {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if *token_program_info.key != *token_swap.token_program_id() {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+        let position = Position::unpack(&position_info.data.borrow())?;
+        if position.swap != *swap_info.key || position.owner != *owner_info.key {
+            return Err(SwapError::IncorrectPosition.into());
+        }
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let state = Self::unpack_concentrated_liquidity(swap_info)?;
+        Ok((state, position))
     }
 
-    impl SwapAccountInfo {
-        pub fn new(
-            user_key: &Pubkey,
-            fees: Fees,
-            swap_curve: SwapCurve,
-            token_a_amount: u64,
-            token_b_amount: u64,
-        ) -> Self {
-            let swap_key = Pubkey::new_unique();
-            let swap_account = Account::new(0, SwapVersion::LATEST_LEN, &SWAP_PROGRAM_ID);
-            let (authority_key, nonce) =
-                Pubkey::find_program_address(&[&swap_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
-
-            let (pool_mint_key, mut pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &authority_key, None);
-            let (pool_token_key, pool_token_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &pool_mint_key,
-                &mut pool_mint_account,
-                &authority_key,
-                &user_key,
-                0,
-            );
-            let (pool_fee_key, pool_fee_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &pool_mint_key,
-                &mut pool_mint_account,
-                &authority_key,
-                &user_key,
-                0,
-            );
-            let (token_a_mint_key, mut token_a_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
-            let (token_a_key, token_a_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &token_a_mint_key,
-                &mut token_a_mint_account,
-                &user_key,
-                &authority_key,
-                token_a_amount,
-            );
-            let (token_b_mint_key, mut token_b_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
-            let (token_b_key, token_b_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &token_b_mint_key,
-                &mut token_b_mint_account,
-                &user_key,
-                &authority_key,
-                token_b_amount,
-            );
+    /// Processes an [OpenPosition](enum.Instruction.html).
+    pub fn process_open_position(
+        program_id: &Pubkey,
+        tick_lower: i32,
+        tick_upper: i32,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let position_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
 
-            SwapAccountInfo {
-                nonce,
-                authority_key,
-                fees,
-                swap_curve,
-                swap_key,
-                swap_account,
-                pool_mint_key,
-                pool_mint_account,
-                pool_fee_key,
-                pool_fee_account,
-                pool_token_key,
-                pool_token_account,
-                token_a_key,
-                token_a_account,
-                token_a_mint_key,
-                token_a_mint_account,
-                token_b_key,
-                token_b_account,
-                token_b_mint_key,
-                token_b_mint_account,
-            }
+        if swap_info.owner != program_id || position_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
         }
-
-        pub fn initialize_swap(&mut self) -> ProgramResult {
-            do_process_instruction(
-                initialize(
-                    &SWAP_PROGRAM_ID,
-                    &TOKEN_PROGRAM_ID,
-                    &self.swap_key,
-                    &self.authority_key,
-                    &self.token_a_key,
-                    &self.token_b_key,
-                    &self.pool_mint_key,
-                    &self.pool_fee_key,
-                    &self.pool_token_key,
-                    self.nonce,
-                    self.fees.clone(),
-                    self.swap_curve.clone(),
-                )
-                .unwrap(),
-                vec![
-                    &mut self.swap_account,
-                    &mut Account::default(),
-                    &mut self.token_a_account,
-                    &mut self.token_b_account,
-                    &mut self.pool_mint_account,
-                    &mut self.pool_fee_account,
-                    &mut self.pool_token_account,
-                    &mut Account::default(),
-                ],
-            )
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if !token_swap.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
         }
-
-        pub fn setup_token_accounts(
-            &mut self,
-            mint_owner: &Pubkey,
-            account_owner: &Pubkey,
-            a_amount: u64,
-            b_amount: u64,
-            pool_amount: u64,
-        ) -> (Pubkey, Account, Pubkey, Account, Pubkey, Account) {
-            let (token_a_key, token_a_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &self.token_a_mint_key,
-                &mut self.token_a_mint_account,
-                &mint_owner,
-                &account_owner,
-                a_amount,
-            );
-            let (token_b_key, token_b_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &self.token_b_mint_key,
-                &mut self.token_b_mint_account,
-                &mint_owner,
-                &account_owner,
-                b_amount,
-            );
-            let (pool_key, pool_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &self.pool_mint_key,
-                &mut self.pool_mint_account,
-                &self.authority_key,
-                &account_owner,
-                pool_amount,
-            );
-            (
-                token_a_key,
-                token_a_account,
-                token_b_key,
-                token_b_account,
-                pool_key,
-                pool_account,
-            )
+        if token_swap.swap_curve().curve_type != CurveType::ConcentratedLiquidity {
+            return Err(SwapError::UnsupportedCurveOperation.into());
         }
+        let curve = Self::unpack_concentrated_liquidity_curve(token_swap.as_ref())?;
+        curve.validate_tick_range(tick_lower, tick_upper)?;
 
-        fn get_token_account(&self, account_key: &Pubkey) -> &Account {
-            if *account_key == self.token_a_key {
-                return &self.token_a_account;
-            } else if *account_key == self.token_b_key {
-                return &self.token_b_account;
-            }
-            panic!("Could not find matching swap token account");
+        let position = Position::unpack_unchecked(&position_info.data.borrow())?;
+        if position.is_initialized {
+            return Err(SwapError::AlreadyInUse.into());
         }
-
-        fn set_token_account(&mut self, account_key: &Pubkey, account: Account) {
-            if *account_key == self.token_a_key {
-                self.token_a_account = account;
-                return;
-            } else if *account_key == self.token_b_key {
-                self.token_b_account = account;
-                return;
-            }
-            panic!("Could not find matching swap token account");
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
         }
 
-        #[allow(clippy::too_many_arguments)]
-        pub fn swap(
-            &mut self,
-            user_key: &Pubkey,
-            user_source_key: &Pubkey,
-            mut user_source_account: &mut Account,
-            swap_source_key: &Pubkey,
-            swap_destination_key: &Pubkey,
-            user_destination_key: &Pubkey,
-            mut user_destination_account: &mut Account,
-            amount_in: u64,
-            minimum_amount_out: u64,
-        ) -> ProgramResult {
-            let user_transfer_key = Pubkey::new_unique();
-            // approve moving from user source account
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &user_source_key,
-                    &user_transfer_key,
-                    &user_key,
-                    &[],
-                    amount_in,
-                )
-                .unwrap(),
-                vec![
-                    &mut user_source_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
-
-            let mut swap_source_account = self.get_token_account(swap_source_key).clone();
-            let mut swap_destination_account = self.get_token_account(swap_destination_key).clone();
+        let position = Position {
+            is_initialized: true,
+            swap: *swap_info.key,
+            owner: *owner_info.key,
+            tick_lower,
+            tick_upper,
+            ..Position::default()
+        };
+        Position::pack(position, &mut position_info.data.borrow_mut())?;
+        Ok(())
+    }
 
-            // perform the swap
-            do_process_instruction(
-                swap(
-                    &SWAP_PROGRAM_ID,
-                    &TOKEN_PROGRAM_ID,
-                    &self.swap_key,
-                    &self.authority_key,
-                    &user_transfer_key,
-                    &user_source_key,
-                    &swap_source_key,
-                    &swap_destination_key,
-                    &user_destination_key,
-                    &self.pool_mint_key,
-                    &self.pool_fee_key,
-                    None,
-                    Swap {
-                        amount_in,
-                        minimum_amount_out,
-                    },
-                )
-                .unwrap(),
-                vec![
-                    &mut self.swap_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                    &mut user_source_account,
-                    &mut swap_source_account,
-                    &mut swap_destination_account,
-                    &mut user_destination_account,
-                    &mut self.pool_mint_account,
-                    &mut self.pool_fee_account,
-                    &mut Account::default(),
-                ],
-            )?;
+    /// Processes an [IncreaseLiquidity](enum.Instruction.html).
+    pub fn process_increase_liquidity(
+        program_id: &Pubkey,
+        liquidity: u128,
+        maximum_token_a_amount: u64,
+        maximum_token_b_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let position_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let source_a_info = next_account_info(account_info_iter)?;
+        let source_b_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
 
-            self.set_token_account(swap_source_key, swap_source_account);
-            self.set_token_account(swap_destination_key, swap_destination_account);
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if token_swap.is_paused() {
+            return Err(SwapError::SwapPaused.into());
+        }
+        if liquidity == 0 {
+            return Err(SwapError::InvalidInput.into());
+        }
+        let (mut state, mut position) = Self::unpack_position(
+            token_swap.as_ref(),
+            program_id,
+            swap_info,
+            authority_info,
+            position_info,
+            owner_info,
+            token_a_info,
+            token_b_info,
+            token_program_info,
+        )?;
+        let token_a = Self::unpack_token_account(token_a_info, token_swap.token_program_id())?;
+        let token_b = Self::unpack_token_account(token_b_info, token_swap.token_program_id())?;
+        Self::observe_prices(swap_info, token_a.amount, token_b.amount)?;
+
+        let liquidity_delta =
+            i128::try_from(liquidity).map_err(|_| SwapError::ConversionFailure)?;
+        let (token_a_amount, token_b_amount) =
+            state.update_position(&mut position, liquidity_delta)?;
+        Self::unpack_concentrated_liquidity_curve(token_swap.as_ref())?
+            .validate_position_liquidity(position.liquidity)?;
+        let token_a_amount = to_u64(token_a_amount)?;
+        if token_a_amount > maximum_token_a_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        let token_b_amount = to_u64(token_b_amount)?;
+        if token_b_amount > maximum_token_b_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        Self::pack_concentrated_liquidity(state, swap_info)?;
+        Position::pack(position, &mut position_info.data.borrow_mut())?;
 
-            Ok(())
+        if token_a_amount > 0 {
+            Self::token_transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                source_a_info.clone(),
+                token_a_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_swap.nonce(),
+                token_a_amount,
+            )?;
+        }
+        if token_b_amount > 0 {
+            Self::token_transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                source_b_info.clone(),
+                token_b_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_swap.nonce(),
+                token_b_amount,
+            )?;
         }
+        Self::record_settled_amounts(swap_info, token_a_info, token_b_info)
+    }
 
-        #[allow(clippy::too_many_arguments)]
-        pub fn deposit_all_token_types(
-            &mut self,
-            depositor_key: &Pubkey,
-            depositor_token_a_key: &Pubkey,
-            mut depositor_token_a_account: &mut Account,
-            depositor_token_b_key: &Pubkey,
-            mut depositor_token_b_account: &mut Account,
-            depositor_pool_key: &Pubkey,
-            mut depositor_pool_account: &mut Account,
-            pool_token_amount: u64,
-            maximum_token_a_amount: u64,
-            maximum_token_b_amount: u64,
-        ) -> ProgramResult {
-            let user_transfer_authority = Pubkey::new_unique();
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &depositor_token_a_key,
-                    &user_transfer_authority,
-                    &depositor_key,
-                    &[],
-                    maximum_token_a_amount,
-                )
-                .unwrap(),
-                vec![
-                    &mut depositor_token_a_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
+    /// Processes a [DecreaseLiquidity](enum.Instruction.html).
+    pub fn process_decrease_liquidity(
+        program_id: &Pubkey,
+        liquidity: u128,
+        minimum_token_a_amount: u64,
+        minimum_token_b_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let position_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+        let dest_token_a_info = next_account_info(account_info_iter)?;
+        let dest_token_b_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
 
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &depositor_token_b_key,
-                    &user_transfer_authority,
-                    &depositor_key,
-                    &[],
-                    maximum_token_b_amount,
-                )
-                .unwrap(),
-                vec![
-                    &mut depositor_token_b_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if token_a_info.key == dest_token_a_info.key || token_b_info.key == dest_token_b_info.key {
+            return Err(SwapError::InvalidInput.into());
+        }
+        let (mut state, mut position) = Self::unpack_position(
+            token_swap.as_ref(),
+            program_id,
+            swap_info,
+            authority_info,
+            position_info,
+            owner_info,
+            token_a_info,
+            token_b_info,
+            token_program_info,
+        )?;
+        let token_a = Self::unpack_token_account(token_a_info, token_swap.token_program_id())?;
+        let token_b = Self::unpack_token_account(token_b_info, token_swap.token_program_id())?;
+        Self::observe_prices(swap_info, token_a.amount, token_b.amount)?;
+
+        let liquidity_delta = i128::try_from(liquidity)
+            .map_err(|_| SwapError::InsufficientLiquidity)?
+            .checked_neg()
+            .ok_or(SwapError::InsufficientLiquidity)?;
+        let (token_a_amount, token_b_amount) =
+            state.update_position(&mut position, liquidity_delta)?;
+        Self::unpack_concentrated_liquidity_curve(token_swap.as_ref())?
+            .validate_position_liquidity(position.liquidity)?;
+        // all fees earned by the position are paid out with the liquidity
+        let token_a_amount = to_u64(token_a_amount)?
+            .checked_add(position.fees_owed_a)
+            .ok_or(SwapError::CalculationFailure)?;
+        let token_b_amount = to_u64(token_b_amount)?
+            .checked_add(position.fees_owed_b)
+            .ok_or(SwapError::CalculationFailure)?;
+        if token_a_amount < minimum_token_a_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        if token_b_amount < minimum_token_b_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        position.fees_owed_a = 0;
+        position.fees_owed_b = 0;
+        Self::pack_concentrated_liquidity(state, swap_info)?;
+        Position::pack(position, &mut position_info.data.borrow_mut())?;
 
-            do_process_instruction(
-                deposit_all_token_types(
-                    &SWAP_PROGRAM_ID,
-                    &TOKEN_PROGRAM_ID,
-                    &self.swap_key,
-                    &self.authority_key,
-                    &user_transfer_authority,
-                    &depositor_token_a_key,
-                    &depositor_token_b_key,
-                    &self.token_a_key,
-                    &self.token_b_key,
-                    &self.pool_mint_key,
-                    &depositor_pool_key,
-                    DepositAllTokenTypes {
-                        pool_token_amount,
-                        maximum_token_a_amount,
-                        maximum_token_b_amount,
-                    },
-                )
-                .unwrap(),
-                vec![
-                    &mut self.swap_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                    &mut depositor_token_a_account,
-                    &mut depositor_token_b_account,
-                    &mut self.token_a_account,
-                    &mut self.token_b_account,
-                    &mut self.pool_mint_account,
-                    &mut depositor_pool_account,
-                    &mut Account::default(),
-                ],
-            )
+        if token_a_amount > 0 {
+            Self::token_transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                token_a_info.clone(),
+                dest_token_a_info.clone(),
+                authority_info.clone(),
+                token_swap.nonce(),
+                token_a_amount,
+            )?;
+        }
+        if token_b_amount > 0 {
+            Self::token_transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                token_b_info.clone(),
+                dest_token_b_info.clone(),
+                authority_info.clone(),
+                token_swap.nonce(),
+                token_b_amount,
+            )?;
         }
+        Self::record_settled_amounts(swap_info, token_a_info, token_b_info)
+    }
 
-        #[allow(clippy::too_many_arguments)]
-        pub fn withdraw_all_token_types(
-            &mut self,
-            user_key: &Pubkey,
-            pool_key: &Pubkey,
-            mut pool_account: &mut Account,
-            token_a_key: &Pubkey,
-            mut token_a_account: &mut Account,
-            token_b_key: &Pubkey,
-            mut token_b_account: &mut Account,
-            pool_token_amount: u64,
-            minimum_token_a_amount: u64,
-            minimum_token_b_amount: u64,
-        ) -> ProgramResult {
-            let user_transfer_authority_key = Pubkey::new_unique();
-            // approve user transfer authority to take out pool tokens
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &pool_key,
-                    &user_transfer_authority_key,
-                    &user_key,
-                    &[],
-                    pool_token_amount,
-                )
-                .unwrap(),
-                vec![
-                    &mut pool_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
+    /// Checks the accounts shared by all weighted pool instructions,
+    /// returning the state of the pool
+    fn unpack_weighted_pool(
+        program_id: &Pubkey,
+        pool_info: &AccountInfo,
+        authority_info: &AccountInfo,
+        pool_mint_info: &AccountInfo,
+        token_program_info: &AccountInfo,
+    ) -> Result<WeightedPool, ProgramError> {
+        if pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let pool = WeightedPool::unpack(&pool_info.data.borrow())?;
+        if *authority_info.key != Self::authority_id(program_id, pool_info.key, pool.nonce)? {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if *pool_mint_info.key != pool.pool_mint {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+        if *token_program_info.key != pool.token_program_id {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+        Ok(pool)
+    }
 
-            // withdraw token a and b correctly
-            do_process_instruction(
-                withdraw_all_token_types(
-                    &SWAP_PROGRAM_ID,
-                    &TOKEN_PROGRAM_ID,
-                    &self.swap_key,
-                    &self.authority_key,
-                    &user_transfer_authority_key,
-                    &self.pool_mint_key,
-                    &self.pool_fee_key,
-                    &pool_key,
-                    &self.token_a_key,
-                    &self.token_b_key,
-                    &token_a_key,
-                    &token_b_key,
-                    WithdrawAllTokenTypes {
-                        pool_token_amount,
-                        minimum_token_a_amount,
-                        minimum_token_b_amount,
-                    },
-                )
-                .unwrap(),
-                vec![
-                    &mut self.swap_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                    &mut self.pool_mint_account,
-                    &mut pool_account,
-                    &mut self.token_a_account,
-                    &mut self.token_b_account,
-                    &mut token_a_account,
-                    &mut token_b_account,
-                    &mut self.pool_fee_account,
-                    &mut Account::default(),
-                ],
-            )
+    /// Processes an [InitializeWeightedPool](enum.Instruction.html).
+    pub fn process_initialize_weighted_pool(
+        program_id: &Pubkey,
+        nonce: u8,
+        fees: Fees,
+        weights: Vec<u64>,
+        accounts: &[AccountInfo],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let fee_account_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let token_infos = account_info_iter.as_slice();
+
+        if token_infos.len() < MIN_WEIGHTED_POOL_TOKENS
+            || token_infos.len() > MAX_WEIGHTED_POOL_TOKENS
+            || token_infos.len() != weights.len()
+        {
+            return Err(SwapError::InvalidInput.into());
+        }
+        let token_program_id = *token_program_info.key;
+        if WeightedPool::unpack_unchecked(&pool_info.data.borrow())?.is_initialized {
+            return Err(SwapError::AlreadyInUse.into());
         }
 
-        #[allow(clippy::too_many_arguments)]
-        pub fn deposit_single_token_type_exact_amount_in(
-            &mut self,
-            depositor_key: &Pubkey,
-            deposit_account_key: &Pubkey,
-            mut deposit_token_account: &mut Account,
-            deposit_pool_key: &Pubkey,
-            mut deposit_pool_account: &mut Account,
-            source_token_amount: u64,
-            minimum_pool_token_amount: u64,
-        ) -> ProgramResult {
-            let user_transfer_authority_key = Pubkey::new_unique();
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &deposit_account_key,
-                    &user_transfer_authority_key,
-                    &depositor_key,
-                    &[],
-                    source_token_amount,
-                )
-                .unwrap(),
-                vec![
-                    &mut deposit_token_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
+        if *authority_info.key != Self::authority_id(program_id, pool_info.key, nonce)? {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        let fee_account = Self::unpack_token_account(fee_account_info, &token_program_id)?;
+        let destination = Self::unpack_token_account(destination_info, &token_program_id)?;
+        let pool_mint = Self::unpack_mint(pool_mint_info, &token_program_id)?;
+        if *authority_info.key == destination.owner {
+            return Err(SwapError::InvalidOutputOwner.into());
+        }
+        if *authority_info.key == fee_account.owner {
+            return Err(SwapError::InvalidOutputOwner.into());
+        }
+        if COption::Some(*authority_info.key) != pool_mint.mint_authority {
+            return Err(SwapError::InvalidOwner.into());
+        }
 
-            do_process_instruction(
-                deposit_single_token_type_exact_amount_in(
-                    &SWAP_PROGRAM_ID,
-                    &TOKEN_PROGRAM_ID,
-                    &self.swap_key,
-                    &self.authority_key,
-                    &user_transfer_authority_key,
-                    &deposit_account_key,
-                    &self.token_a_key,
-                    &self.token_b_key,
-                    &self.pool_mint_key,
-                    &deposit_pool_key,
-                    DepositSingleTokenTypeExactAmountIn {
-                        source_token_amount,
-                        minimum_pool_token_amount,
-                    },
-                )
-                .unwrap(),
-                vec![
-                    &mut self.swap_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                    &mut deposit_token_account,
-                    &mut self.token_a_account,
-                    &mut self.token_b_account,
-                    &mut self.pool_mint_account,
-                    &mut deposit_pool_account,
-                    &mut Account::default(),
-                ],
-            )
+        let mut tokens = Vec::with_capacity(token_infos.len());
+        for (token_info, weight) in token_infos.iter().zip(weights.iter()) {
+            let token = Self::unpack_token_account(token_info, &token_program_id)?;
+            if *authority_info.key != token.owner {
+                return Err(SwapError::InvalidOwner.into());
+            }
+            if token.amount == 0 {
+                return Err(SwapError::EmptySupply.into());
+            }
+            if token.delegate.is_some() {
+                return Err(SwapError::InvalidDelegate.into());
+            }
+            if token.close_authority.is_some() {
+                return Err(SwapError::InvalidCloseAuthority.into());
+            }
+            if tokens
+                .iter()
+                .any(|other: &WeightedPoolToken| other.mint == token.mint)
+            {
+                return Err(SwapError::RepeatedMint.into());
+            }
+            tokens.push(WeightedPoolToken {
+                account: *token_info.key,
+                mint: token.mint,
+                weight: *weight,
+            });
         }
 
-        #[allow(clippy::too_many_arguments)]
-        pub fn withdraw_single_token_type_exact_amount_out(
-            &mut self,
-            user_key: &Pubkey,
-            pool_key: &Pubkey,
-            mut pool_account: &mut Account,
-            destination_key: &Pubkey,
-            mut destination_account: &mut Account,
-            destination_token_amount: u64,
-            maximum_pool_token_amount: u64,
-        ) -> ProgramResult {
-            let user_transfer_authority_key = Pubkey::new_unique();
-            // approve user transfer authority to take out pool tokens
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &pool_key,
-                    &user_transfer_authority_key,
-                    &user_key,
-                    &[],
-                    maximum_pool_token_amount,
-                )
-                .unwrap(),
-                vec![
-                    &mut pool_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
+        if pool_mint.supply != 0 {
+            return Err(SwapError::InvalidSupply.into());
+        }
+        if pool_mint.freeze_authority.is_some() {
+            return Err(SwapError::InvalidFreezeAuthority.into());
+        }
+        if *pool_mint_info.key != fee_account.mint {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
 
-            do_process_instruction(
-                withdraw_single_token_type_exact_amount_out(
-                    &SWAP_PROGRAM_ID,
-                    &TOKEN_PROGRAM_ID,
-                    &self.swap_key,
-                    &self.authority_key,
-                    &user_transfer_authority_key,
-                    &self.pool_mint_key,
-                    &self.pool_fee_key,
-                    &pool_key,
-                    &self.token_a_key,
-                    &self.token_b_key,
-                    &destination_key,
-                    WithdrawSingleTokenTypeExactAmountOut {
-                        destination_token_amount,
-                        maximum_pool_token_amount,
-                    },
-                )
-                .unwrap(),
-                vec![
-                    &mut self.swap_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                    &mut self.pool_mint_account,
-                    &mut pool_account,
-                    &mut self.token_a_account,
-                    &mut self.token_b_account,
-                    &mut destination_account,
-                    &mut self.pool_fee_account,
-                    &mut Account::default(),
-                ],
-            )
+        if let Some(swap_constraints) = swap_constraints {
+            let owner_key = swap_constraints
+                .owner_key
+                .parse::<Pubkey>()
+                .map_err(|_| SwapError::InvalidOwner)?;
+            if fee_account.owner != owner_key {
+                return Err(SwapError::InvalidOwner.into());
+            }
+            swap_constraints.validate_curve_type(CurveType::Weighted)?;
+            swap_constraints.validate_fees(&fees)?;
         }
-    }
+        fees.validate()?;
+        weighted::validate_weights(&weights)?;
 
-    fn mint_minimum_balance() -> u64 {
-        Rent::default().minimum_balance(spl_token::state::Mint::get_packed_len())
-    }
+        Self::token_mint_to(
+            pool_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            nonce,
+            to_u64(INITIAL_SWAP_POOL_AMOUNT)?,
+        )?;
 
-    fn account_minimum_balance() -> u64 {
-        Rent::default().minimum_balance(spl_token::state::Account::get_packed_len())
+        let obj = WeightedPool {
+            is_initialized: true,
+            nonce,
+            token_program_id,
+            pool_mint: *pool_mint_info.key,
+            pool_fee_account: *fee_account_info.key,
+            fees,
+            tokens,
+        };
+        WeightedPool::pack(obj, &mut pool_info.data.borrow_mut())?;
+        Ok(())
     }
 
-    fn do_process_instruction_with_fee_constraints(
-        instruction: Instruction,
-        accounts: Vec<&mut Account>,
-        swap_constraints: &Option<SwapConstraints>,
+    /// Processes a [WeightedSwap](enum.Instruction.html).
+    pub fn process_weighted_swap(
+        program_id: &Pubkey,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        accounts: &[AccountInfo],
     ) -> ProgramResult {
-        test_syscall_stubs();
+        let account_info_iter = &mut accounts.iter();
+        let pool_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let swap_source_info = next_account_info(account_info_iter)?;
+        let swap_destination_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
 
-        // approximate the logic in the actual runtime which runs the instruction
-        // and only updates accounts if the instruction is successful
-        let mut account_clones = accounts.iter().map(|x| (*x).clone()).collect::<Vec<_>>();
-        let mut meta = instruction
-            .accounts
-            .iter()
-            .zip(account_clones.iter_mut())
-            .map(|(account_meta, account)| (&account_meta.pubkey, account_meta.is_signer, account))
-            .collect::<Vec<_>>();
-        let mut account_infos = create_is_signer_account_infos(&mut meta);
-        let res = if instruction.program_id == SWAP_PROGRAM_ID {
-            Processor::process_with_constraints(
-                &instruction.program_id,
-                &account_infos,
-                &instruction.data,
-                swap_constraints,
-            )
-        } else {
-            spl_token::processor::Processor::process(
-                &instruction.program_id,
-                &account_infos,
-                &instruction.data,
-            )
-        };
+        let pool = Self::unpack_weighted_pool(
+            program_id,
+            pool_info,
+            authority_info,
+            pool_mint_info,
+            token_program_info,
+        )?;
+        let source_token = pool
+            .find_token(swap_source_info.key)
+            .ok_or(SwapError::IncorrectSwapAccount)?;
+        let destination_token = pool
+            .find_token(swap_destination_info.key)
+            .ok_or(SwapError::IncorrectSwapAccount)?;
+        if swap_source_info.key == swap_destination_info.key {
+            return Err(SwapError::InvalidInput.into());
+        }
+        if swap_source_info.key == source_info.key {
+            return Err(SwapError::InvalidInput.into());
+        }
+        if swap_destination_info.key == destination_info.key {
+            return Err(SwapError::InvalidInput.into());
+        }
+        if *pool_fee_account_info.key != pool.pool_fee_account {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
 
-        if res.is_ok() {
-            let mut account_metas = instruction
-                .accounts
-                .iter()
-                .zip(accounts)
-                .map(|(account_meta, account)| (&account_meta.pubkey, account))
-                .collect::<Vec<_>>();
-            for account_info in account_infos.iter_mut() {
-                for account_meta in account_metas.iter_mut() {
-                    if account_info.key == account_meta.0 {
-                        let account = &mut account_meta.1;
-                        account.owner = *account_info.owner;
-                        account.lamports = **account_info.lamports.borrow();
-                        account.data = account_info.data.borrow().to_vec();
-                    }
-                }
-            }
+        let swap_source = Self::unpack_token_account(swap_source_info, &pool.token_program_id)?;
+        let swap_destination =
+            Self::unpack_token_account(swap_destination_info, &pool.token_program_id)?;
+        let pool_mint = Self::unpack_mint(pool_mint_info, &pool.token_program_id)?;
+
+        let quote = WeightedPoolQuoter::new(&pool, pool_mint.supply).swap(
+            source_token,
+            swap_source.amount,
+            destination_token,
+            swap_destination.amount,
+            amount_in,
+        )?;
+        if quote.amount_out < minimum_amount_out {
+            return Err(SwapError::ExceededSlippage.into());
         }
-        res
-    }
 
-    fn do_process_instruction(
-        instruction: Instruction,
-        accounts: Vec<&mut Account>,
-    ) -> ProgramResult {
-        do_process_instruction_with_fee_constraints(instruction, accounts, &SWAP_CONSTRAINTS)
+        Self::token_transfer(
+            pool_info.key,
+            token_program_info.clone(),
+            source_info.clone(),
+            swap_source_info.clone(),
+            user_transfer_authority_info.clone(),
+            pool.nonce,
+            quote.amount_in,
+        )?;
+        if quote.fees.owner_fee > 0 {
+            Self::token_mint_to(
+                pool_info.key,
+                token_program_info.clone(),
+                pool_mint_info.clone(),
+                pool_fee_account_info.clone(),
+                authority_info.clone(),
+                pool.nonce,
+                quote.fees.owner_fee,
+            )?;
+        }
+
+        Self::token_transfer(
+            pool_info.key,
+            token_program_info.clone(),
+            swap_destination_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            pool.nonce,
+            quote.amount_out,
+        )?;
+        Ok(())
     }
 
-    fn mint_token(
+    /// Processes a [WeightedDepositSingleTokenTypeExactAmountIn](enum.Instruction.html).
+    pub fn process_weighted_deposit_single_token_type_exact_amount_in(
         program_id: &Pubkey,
-        mint_key: &Pubkey,
-        mut mint_account: &mut Account,
-        mint_authority_key: &Pubkey,
-        account_owner_key: &Pubkey,
-        amount: u64,
-    ) -> (Pubkey, Account) {
-        let account_key = Pubkey::new_unique();
-        let mut account_account = Account::new(
-            account_minimum_balance(),
-            spl_token::state::Account::get_packed_len(),
-            &program_id,
-        );
-        let mut mint_authority_account = Account::default();
-        let mut rent_sysvar_account = create_account_for_test(&Rent::free());
+        source_token_amount: u64,
+        minimum_pool_token_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let swap_token_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
 
-        do_process_instruction(
-            initialize_account(&program_id, &account_key, &mint_key, account_owner_key).unwrap(),
-            vec![
-                &mut account_account,
-                &mut mint_account,
-                &mut mint_authority_account,
-                &mut rent_sysvar_account,
-            ],
-        )
-        .unwrap();
+        let pool = Self::unpack_weighted_pool(
+            program_id,
+            pool_info,
+            authority_info,
+            pool_mint_info,
+            token_program_info,
+        )?;
+        let token = pool
+            .find_token(swap_token_info.key)
+            .ok_or(SwapError::IncorrectSwapAccount)?;
+        if swap_token_info.key == source_info.key {
+            return Err(SwapError::InvalidInput.into());
+        }
+        let swap_token = Self::unpack_token_account(swap_token_info, &pool.token_program_id)?;
+        let pool_mint = Self::unpack_mint(pool_mint_info, &pool.token_program_id)?;
 
-        if amount > 0 {
-            do_process_instruction(
-                mint_to(
-                    &program_id,
-                    &mint_key,
-                    &account_key,
-                    &mint_authority_key,
-                    &[],
-                    amount,
-                )
-                .unwrap(),
-                vec![
-                    &mut mint_account,
-                    &mut account_account,
-                    &mut mint_authority_account,
-                ],
-            )
-            .unwrap();
+        let quote = WeightedPoolQuoter::new(&pool, pool_mint.supply)
+            .deposit_single_token_type_exact_amount_in(
+                token,
+                swap_token.amount,
+                source_token_amount,
+            )?;
+        if quote.pool_token_amount < minimum_pool_token_amount {
+            return Err(SwapError::ExceededSlippage.into());
         }
 
-        (account_key, account_account)
+        Self::token_transfer(
+            pool_info.key,
+            token_program_info.clone(),
+            source_info.clone(),
+            swap_token_info.clone(),
+            user_transfer_authority_info.clone(),
+            pool.nonce,
+            source_token_amount,
+        )?;
+        Self::token_mint_to(
+            pool_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            pool.nonce,
+            quote.pool_token_amount,
+        )?;
+        Ok(())
     }
 
-    fn create_mint(
+    /// Processes a [WeightedWithdrawSingleTokenTypeExactAmountOut](enum.Instruction.html).
+    pub fn process_weighted_withdraw_single_token_type_exact_amount_out(
         program_id: &Pubkey,
-        authority_key: &Pubkey,
-        freeze_authority: Option<&Pubkey>,
-    ) -> (Pubkey, Account) {
-        let mint_key = Pubkey::new_unique();
-        let mut mint_account = Account::new(
-            mint_minimum_balance(),
-            spl_token::state::Mint::get_packed_len(),
-            &program_id,
-        );
-        let mut rent_sysvar_account = create_account_for_test(&Rent::free());
+        destination_token_amount: u64,
+        maximum_pool_token_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let swap_token_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
 
-        do_process_instruction(
-            initialize_mint(&program_id, &mint_key, authority_key, freeze_authority, 2).unwrap(),
-            vec![&mut mint_account, &mut rent_sysvar_account],
-        )
-        .unwrap();
+        let pool = Self::unpack_weighted_pool(
+            program_id,
+            pool_info,
+            authority_info,
+            pool_mint_info,
+            token_program_info,
+        )?;
+        let token = pool
+            .find_token(swap_token_info.key)
+            .ok_or(SwapError::IncorrectSwapAccount)?;
+        if swap_token_info.key == destination_info.key {
+            return Err(SwapError::InvalidInput.into());
+        }
+        if *pool_fee_account_info.key != pool.pool_fee_account {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+        let swap_token = Self::unpack_token_account(swap_token_info, &pool.token_program_id)?;
+        let pool_mint = Self::unpack_mint(pool_mint_info, &pool.token_program_id)?;
+
+        // withdrawing from the fee account, don't assess withdraw fee
+        let with_withdraw_fee = *pool_fee_account_info.key != *source_info.key;
+        let quote = WeightedPoolQuoter::new(&pool, pool_mint.supply)
+            .withdraw_single_token_type_exact_amount_out(
+                token,
+                swap_token.amount,
+                destination_token_amount,
+                with_withdraw_fee,
+            )?;
+        if quote.pool_token_amount > maximum_pool_token_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
 
-        (mint_key, mint_account)
+        if quote.fees.owner_fee > 0 {
+            Self::token_transfer(
+                pool_info.key,
+                token_program_info.clone(),
+                source_info.clone(),
+                pool_fee_account_info.clone(),
+                user_transfer_authority_info.clone(),
+                pool.nonce,
+                quote.fees.owner_fee,
+            )?;
+        }
+        Self::token_burn(
+            pool_info.key,
+            token_program_info.clone(),
+            source_info.clone(),
+            pool_mint_info.clone(),
+            user_transfer_authority_info.clone(),
+            pool.nonce,
+            quote
+                .pool_token_amount
+                .checked_sub(quote.fees.owner_fee)
+                .ok_or(SwapError::CalculationFailure)?,
+        )?;
+        Self::token_transfer(
+            pool_info.key,
+            token_program_info.clone(),
+            swap_token_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            pool.nonce,
+            destination_token_amount,
+        )?;
+        Ok(())
     }
 
-    #[test]
-    fn test_token_program_id_error() {
-        test_syscall_stubs();
-        let swap_key = Pubkey::new_unique();
-        let mut mint = (Pubkey::new_unique(), Account::default());
-        let mut destination = (Pubkey::new_unique(), Account::default());
-        let token_program = (TOKEN_PROGRAM_ID, Account::default());
-        let (authority_key, nonce) =
-            Pubkey::find_program_address(&[&swap_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
-        let mut authority = (authority_key, Account::default());
-        let swap_bytes = swap_key.to_bytes();
-        let authority_signature_seeds = [&swap_bytes[..32], &[nonce]];
-        let signers = &[&authority_signature_seeds[..]];
-        let ix = mint_to(
-            &token_program.0,
+    /// Processes an [Instruction](enum.Instruction.html).
+    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+        Self::process_with_constraints(program_id, accounts, input, &SWAP_CONSTRAINTS)
+    }
+
+    /// Processes an instruction given extra constraint
+    pub fn process_with_constraints(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: &[u8],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let instruction = SwapInstruction::unpack(input)?;
+        match instruction {
+            SwapInstruction::Initialize(Initialize {
+                nonce,
+                fees,
+                swap_curve,
+            }) => {
+                msg!("Instruction: Init");
+                Self::process_initialize(
+                    program_id,
+                    nonce,
+                    fees,
+                    swap_curve,
+                    accounts,
+                    swap_constraints,
+                )
+            }
+            SwapInstruction::Swap(Swap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                msg!("Instruction: Swap");
+                Self::process_swap(program_id, amount_in, minimum_amount_out, accounts)
+            }
+            SwapInstruction::DepositAllTokenTypes(DepositAllTokenTypes {
+                pool_token_amount,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+            }) => {
+                msg!("Instruction: DepositAllTokenTypes");
+                Self::process_deposit_all_token_types(
+                    program_id,
+                    pool_token_amount,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::WithdrawAllTokenTypes(WithdrawAllTokenTypes {
+                pool_token_amount,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                msg!("Instruction: WithdrawAllTokenTypes");
+                Self::process_withdraw_all_token_types(
+                    program_id,
+                    pool_token_amount,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::DepositSingleTokenTypeExactAmountIn(
+                DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                },
+            ) => {
+                msg!("Instruction: DepositSingleTokenTypeExactAmountIn");
+                Self::process_deposit_single_token_type_exact_amount_in(
+                    program_id,
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(
+                WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                },
+            ) => {
+                msg!("Instruction: WithdrawSingleTokenTypeExactAmountOut");
+                Self::process_withdraw_single_token_type_exact_amount_out(
+                    program_id,
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::SetFees(SetFees { fees }) => {
+                msg!("Instruction: SetFees");
+                Self::process_set_fees(program_id, fees, accounts, swap_constraints)
+            }
+            SwapInstruction::SetPaused(SetPaused { paused }) => {
+                msg!("Instruction: SetPaused");
+                Self::process_set_paused(program_id, paused, accounts)
+            }
+            SwapInstruction::RouteSwap(RouteSwap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                msg!("Instruction: RouteSwap");
+                Self::process_route_swap(program_id, amount_in, minimum_amount_out, accounts)
+            }
+            SwapInstruction::OpenPosition(OpenPosition {
+                tick_lower,
+                tick_upper,
+            }) => {
+                msg!("Instruction: OpenPosition");
+                Self::process_open_position(program_id, tick_lower, tick_upper, accounts)
+            }
+            SwapInstruction::IncreaseLiquidity(IncreaseLiquidity {
+                liquidity,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+            }) => {
+                msg!("Instruction: IncreaseLiquidity");
+                Self::process_increase_liquidity(
+                    program_id,
+                    liquidity,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::DecreaseLiquidity(DecreaseLiquidity {
+                liquidity,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                msg!("Instruction: DecreaseLiquidity");
+                Self::process_decrease_liquidity(
+                    program_id,
+                    liquidity,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::InitializeWeightedPool(InitializeWeightedPool {
+                nonce,
+                fees,
+                weights,
+            }) => {
+                msg!("Instruction: InitializeWeightedPool");
+                Self::process_initialize_weighted_pool(
+                    program_id,
+                    nonce,
+                    fees,
+                    weights,
+                    accounts,
+                    swap_constraints,
+                )
+            }
+            SwapInstruction::WeightedSwap(Swap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                msg!("Instruction: WeightedSwap");
+                Self::process_weighted_swap(program_id, amount_in, minimum_amount_out, accounts)
+            }
+            SwapInstruction::WeightedDepositSingleTokenTypeExactAmountIn(
+                DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                },
+            ) => {
+                msg!("Instruction: WeightedDepositSingleTokenTypeExactAmountIn");
+                Self::process_weighted_deposit_single_token_type_exact_amount_in(
+                    program_id,
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::WeightedWithdrawSingleTokenTypeExactAmountOut(
+                WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                },
+            ) => {
+                msg!("Instruction: WeightedWithdrawSingleTokenTypeExactAmountOut");
+                Self::process_weighted_withdraw_single_token_type_exact_amount_out(
+                    program_id,
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::RefreshOraclePrice => {
+                msg!("Instruction: RefreshOraclePrice");
+                Self::process_refresh_oracle_price(program_id, accounts)
+            }
+            SwapInstruction::SetDynamicFee(SetDynamicFee { parameters }) => {
+                msg!("Instruction: SetDynamicFee");
+                Self::process_set_dynamic_fee(program_id, parameters, accounts, swap_constraints)
+            }
+        }
+    }
+}
+
+impl PrintProgramError for SwapError {
+    fn print<E>(&self)
+    where
+        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
+    {
+        match self {
+            SwapError::AlreadyInUse => msg!("Error: Swap account already in use"),
+            SwapError::InvalidProgramAddress => {
+                msg!("Error: Invalid program address generated from nonce and key")
+            }
+            SwapError::InvalidOwner => {
+                msg!("Error: The input account owner is not the program address")
+            }
+            SwapError::InvalidOutputOwner => {
+                msg!("Error: Output pool account owner cannot be the program address")
+            }
+            SwapError::ExpectedMint => msg!("Error: Deserialized account is not an SPL Token mint"),
+            SwapError::ExpectedAccount => {
+                msg!("Error: Deserialized account is not an SPL Token account")
+            }
+            SwapError::EmptySupply => msg!("Error: Input token account empty"),
+            SwapError::InvalidSupply => msg!("Error: Pool token mint has a non-zero supply"),
+            SwapError::RepeatedMint => msg!("Error: Swap input token accounts have the same mint"),
+            SwapError::InvalidDelegate => msg!("Error: Token account has a delegate"),
+            SwapError::InvalidInput => msg!("Error: InvalidInput"),
+            SwapError::IncorrectSwapAccount => {
+                msg!("Error: Address of the provided swap token account is incorrect")
+            }
+            SwapError::IncorrectPoolMint => {
+                msg!("Error: Address of the provided pool token mint is incorrect")
+            }
+            SwapError::InvalidOutput => msg!("Error: InvalidOutput"),
+            SwapError::CalculationFailure => msg!("Error: CalculationFailure"),
+            SwapError::InvalidInstruction => msg!("Error: InvalidInstruction"),
+            SwapError::ExceededSlippage => {
+                msg!("Error: Swap instruction exceeds desired slippage limit")
+            }
+            SwapError::InvalidCloseAuthority => msg!("Error: Token account has a close authority"),
+            SwapError::InvalidFreezeAuthority => {
+                msg!("Error: Pool token mint has a freeze authority")
+            }
+            SwapError::IncorrectFeeAccount => msg!("Error: Pool fee token account incorrect"),
+            SwapError::ZeroTradingTokens => {
+                msg!("Error: Given pool token amount results in zero trading tokens")
+            }
+            SwapError::FeeCalculationFailure => msg!(
+                "Error: The fee calculation failed due to overflow, underflow, or unexpected 0"
+            ),
+            SwapError::ConversionFailure => msg!("Error: Conversion to or from u64 failed."),
+            SwapError::InvalidFee => {
+                msg!("Error: The provided fee does not match the program owner's constraints")
+            }
+            SwapError::IncorrectTokenProgramId => {
+                msg!("Error: The provided token program does not match the token program expected by the swap")
+            }
+            SwapError::UnsupportedCurveType => {
+                msg!("Error: The provided curve type is not supported by the program owner")
+            }
+            SwapError::InvalidCurve => {
+                msg!("Error: The provided curve parameters are invalid")
+            }
+            SwapError::UnsupportedCurveOperation => {
+                msg!("Error: The operation cannot be performed on the given curve")
+            }
+            SwapError::SwapPaused => msg!("Error: Swaps and deposits are paused"),
+            SwapError::MissingAdminAuthority => {
+                msg!("Error: The swap does not have an admin authority")
+            }
+            SwapError::InvalidAdminAuthority => {
+                msg!("Error: Admin authority does not match the swap admin authority")
+            }
+            SwapError::InvalidRoute => {
+                msg!("Error: Route swap accounts do not describe one or more pools")
+            }
+            SwapError::InvalidTickRange => msg!("Error: Invalid tick range"),
+            SwapError::TooManyTicks => msg!("Error: Too many initialized ticks in the pool"),
+            SwapError::IncorrectPosition => {
+                msg!("Error: Position does not match the swap or owner")
+            }
+            SwapError::InsufficientLiquidity => {
+                msg!("Error: Insufficient liquidity in the position")
+            }
+            SwapError::InvalidOracle => msg!("Error: Invalid oracle price feed"),
+            SwapError::StaleOraclePrice => msg!("Error: Oracle price is stale"),
+            SwapError::PositionLiquidityTooLow => {
+                msg!("Error: Position liquidity is below the pool minimum")
+            }
+            SwapError::UncertainOraclePrice => {
+                msg!("Error: Oracle price confidence interval is too wide")
+            }
+        }
+    }
+}
+
+fn to_u128(val: u64) -> Result<u128, SwapError> {
+    val.try_into().map_err(|_| SwapError::ConversionFailure)
+}
+
+fn to_u64(val: u128) -> Result<u64, SwapError> {
+    val.try_into().map_err(|_| SwapError::ConversionFailure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        curve::calculator::{CurveCalculator, RoundDirection, INITIAL_SWAP_POOL_AMOUNT},
+        curve::{
+            base::CurveType, constant_price::ConstantPriceCurve,
+            constant_product::ConstantProductCurve, offset::OffsetCurve, stable::StableCurve,
+        },
+        instruction::{
+            decrease_liquidity, deposit_all_token_types, deposit_single_token_type_exact_amount_in,
+            increase_liquidity, initialize, initialize_proactive_market_maker,
+            initialize_weighted_pool, open_position, refresh_oracle_price, route_swap,
+            set_dynamic_fee, set_fees, set_paused, swap,
+            weighted_deposit_single_token_type_exact_amount_in, weighted_swap,
+            weighted_withdraw_single_token_type_exact_amount_out, withdraw_all_token_types,
+            withdraw_single_token_type_exact_amount_out, RouteSwapHop,
+        },
+    };
+    use solana_program::{
+        clock::Slot, entrypoint::SUCCESS, instruction::Instruction, program_stubs, rent::Rent,
+    };
+    use solana_sdk::account::{create_account_for_test, create_is_signer_account_infos, Account};
+    use spl_math::precise_number::PreciseNumber;
+    use spl_token::{
+        error::TokenError,
+        instruction::{
+            approve, initialize_account, initialize_mint, mint_to, revoke, set_authority,
+            AuthorityType,
+        },
+    };
+
+    // Test program id for the swap program.
+    const SWAP_PROGRAM_ID: Pubkey = Pubkey::new_from_array([2u8; 32]);
+    // Test program id for the token program.
+    const TOKEN_PROGRAM_ID: Pubkey = Pubkey::new_from_array([1u8; 32]);
+
+    thread_local! {
+        // Slot returned by the clock sysvar to the current test
+        static TEST_SLOT: std::cell::Cell<Slot> = std::cell::Cell::new(0);
+    }
+
+    fn set_test_slot(slot: Slot) {
+        TEST_SLOT.with(|test_slot| test_slot.set(slot));
+    }
+
+    struct TestSyscallStubs {}
+    impl program_stubs::SyscallStubs for TestSyscallStubs {
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock {
+                slot: TEST_SLOT.with(|test_slot| test_slot.get()),
+                ..Clock::default()
+            };
+            unsafe {
+                *(var_addr as *mut Clock) = clock;
+            }
+            SUCCESS
+        }
+
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            msg!("TestSyscallStubs::sol_invoke_signed()");
+
+            let mut new_account_infos = vec![];
+
+            // mimic check for token program in accounts
+            if !account_infos.iter().any(|x| *x.key == TOKEN_PROGRAM_ID) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            for meta in instruction.accounts.iter() {
+                for account_info in account_infos.iter() {
+                    if meta.pubkey == *account_info.key {
+                        let mut new_account_info = account_info.clone();
+                        for seeds in signers_seeds.iter() {
+                            let signer =
+                                Pubkey::create_program_address(&seeds, &SWAP_PROGRAM_ID).unwrap();
+                            if *account_info.key == signer {
+                                new_account_info.is_signer = true;
+                            }
+                        }
+                        new_account_infos.push(new_account_info);
+                    }
+                }
+            }
+
+            spl_token::processor::Processor::process(
+                &instruction.program_id,
+                &new_account_infos,
+                &instruction.data,
+            )
+        }
+    }
+
+    fn test_syscall_stubs() {
+        use std::sync::Once;
+        static ONCE: Once = Once::new();
+
+        ONCE.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(TestSyscallStubs {}));
+        });
+    }
+
+    struct SwapAccountInfo {
+        nonce: u8,
+        authority_key: Pubkey,
+        admin_key: Pubkey,
+        fees: Fees,
+        swap_curve: SwapCurve,
+        swap_key: Pubkey,
+        swap_account: Account,
+        pool_mint_key: Pubkey,
+        pool_mint_account: Account,
+        pool_fee_key: Pubkey,
+        pool_fee_account: Account,
+        pool_token_key: Pubkey,
+        pool_token_account: Account,
+        token_a_key: Pubkey,
+        token_a_account: Account,
+        token_a_mint_key: Pubkey,
+        token_a_mint_account: Account,
+        token_b_key: Pubkey,
+        token_b_account: Account,
+        token_b_mint_key: Pubkey,
+        token_b_mint_account: Account,
+    }
+
+    impl SwapAccountInfo {
+        pub fn new(
+            user_key: &Pubkey,
+            fees: Fees,
+            swap_curve: SwapCurve,
+            token_a_amount: u64,
+            token_b_amount: u64,
+        ) -> Self {
+            let swap_key = Pubkey::new_unique();
+            let swap_account = Account::new(0, SwapVersion::LATEST_LEN, &SWAP_PROGRAM_ID);
+            let (authority_key, nonce) =
+                Pubkey::find_program_address(&[&swap_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
+
+            let (pool_mint_key, mut pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &authority_key, None);
+            let (pool_token_key, pool_token_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &pool_mint_key,
+                &mut pool_mint_account,
+                &authority_key,
+                &user_key,
+                0,
+            );
+            let (pool_fee_key, pool_fee_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &pool_mint_key,
+                &mut pool_mint_account,
+                &authority_key,
+                &user_key,
+                0,
+            );
+            let (token_a_mint_key, mut token_a_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
+            let (token_a_key, token_a_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &token_a_mint_key,
+                &mut token_a_mint_account,
+                &user_key,
+                &authority_key,
+                token_a_amount,
+            );
+            let (token_b_mint_key, mut token_b_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
+            let (token_b_key, token_b_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &token_b_mint_key,
+                &mut token_b_mint_account,
+                &user_key,
+                &authority_key,
+                token_b_amount,
+            );
+
+            SwapAccountInfo {
+                nonce,
+                authority_key,
+                admin_key: Pubkey::new_unique(),
+                fees,
+                swap_curve,
+                swap_key,
+                swap_account,
+                pool_mint_key,
+                pool_mint_account,
+                pool_fee_key,
+                pool_fee_account,
+                pool_token_key,
+                pool_token_account,
+                token_a_key,
+                token_a_account,
+                token_a_mint_key,
+                token_a_mint_account,
+                token_b_key,
+                token_b_account,
+                token_b_mint_key,
+                token_b_mint_account,
+            }
+        }
+
+        pub fn initialize_swap(&mut self) -> ProgramResult {
+            do_process_instruction(
+                initialize(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    &self.pool_token_key,
+                    self.nonce,
+                    self.fees.clone(),
+                    self.swap_curve.clone(),
+                    Some(&self.admin_key),
+                )
+                .unwrap(),
+                vec![
+                    &mut self.swap_account,
+                    &mut Account::default(),
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut self.pool_mint_account,
+                    &mut self.pool_fee_account,
+                    &mut self.pool_token_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+        }
+
+        pub fn setup_token_accounts(
+            &mut self,
+            mint_owner: &Pubkey,
+            account_owner: &Pubkey,
+            a_amount: u64,
+            b_amount: u64,
+            pool_amount: u64,
+        ) -> (Pubkey, Account, Pubkey, Account, Pubkey, Account) {
+            let (token_a_key, token_a_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &self.token_a_mint_key,
+                &mut self.token_a_mint_account,
+                &mint_owner,
+                &account_owner,
+                a_amount,
+            );
+            let (token_b_key, token_b_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &self.token_b_mint_key,
+                &mut self.token_b_mint_account,
+                &mint_owner,
+                &account_owner,
+                b_amount,
+            );
+            let (pool_key, pool_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &self.pool_mint_key,
+                &mut self.pool_mint_account,
+                &self.authority_key,
+                &account_owner,
+                pool_amount,
+            );
+            (
+                token_a_key,
+                token_a_account,
+                token_b_key,
+                token_b_account,
+                pool_key,
+                pool_account,
+            )
+        }
+
+        fn get_token_account(&self, account_key: &Pubkey) -> &Account {
+            if *account_key == self.token_a_key {
+                return &self.token_a_account;
+            } else if *account_key == self.token_b_key {
+                return &self.token_b_account;
+            }
+            panic!("Could not find matching swap token account");
+        }
+
+        fn set_token_account(&mut self, account_key: &Pubkey, account: Account) {
+            if *account_key == self.token_a_key {
+                self.token_a_account = account;
+                return;
+            } else if *account_key == self.token_b_key {
+                self.token_b_account = account;
+                return;
+            }
+            panic!("Could not find matching swap token account");
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn swap(
+            &mut self,
+            user_key: &Pubkey,
+            user_source_key: &Pubkey,
+            mut user_source_account: &mut Account,
+            swap_source_key: &Pubkey,
+            swap_destination_key: &Pubkey,
+            user_destination_key: &Pubkey,
+            mut user_destination_account: &mut Account,
+            amount_in: u64,
+            minimum_amount_out: u64,
+        ) -> ProgramResult {
+            let user_transfer_key = Pubkey::new_unique();
+            // approve moving from user source account
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &user_source_key,
+                    &user_transfer_key,
+                    &user_key,
+                    &[],
+                    amount_in,
+                )
+                .unwrap(),
+                vec![
+                    &mut user_source_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+
+            let mut swap_source_account = self.get_token_account(swap_source_key).clone();
+            let mut swap_destination_account = self.get_token_account(swap_destination_key).clone();
+
+            // perform the swap
+            do_process_instruction(
+                swap(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_key,
+                    &user_source_key,
+                    &swap_source_key,
+                    &swap_destination_key,
+                    &user_destination_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    None,
+                    Swap {
+                        amount_in,
+                        minimum_amount_out,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.swap_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut user_source_account,
+                    &mut swap_source_account,
+                    &mut swap_destination_account,
+                    &mut user_destination_account,
+                    &mut self.pool_mint_account,
+                    &mut self.pool_fee_account,
+                    &mut Account::default(),
+                ],
+            )?;
+
+            self.set_token_account(swap_source_key, swap_source_account);
+            self.set_token_account(swap_destination_key, swap_destination_account);
+
+            Ok(())
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn deposit_all_token_types(
+            &mut self,
+            depositor_key: &Pubkey,
+            depositor_token_a_key: &Pubkey,
+            mut depositor_token_a_account: &mut Account,
+            depositor_token_b_key: &Pubkey,
+            mut depositor_token_b_account: &mut Account,
+            depositor_pool_key: &Pubkey,
+            mut depositor_pool_account: &mut Account,
+            pool_token_amount: u64,
+            maximum_token_a_amount: u64,
+            maximum_token_b_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_authority = Pubkey::new_unique();
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &depositor_token_a_key,
+                    &user_transfer_authority,
+                    &depositor_key,
+                    &[],
+                    maximum_token_a_amount,
+                )
+                .unwrap(),
+                vec![
+                    &mut depositor_token_a_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &depositor_token_b_key,
+                    &user_transfer_authority,
+                    &depositor_key,
+                    &[],
+                    maximum_token_b_amount,
+                )
+                .unwrap(),
+                vec![
+                    &mut depositor_token_b_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+
+            do_process_instruction(
+                deposit_all_token_types(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority,
+                    &depositor_token_a_key,
+                    &depositor_token_b_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &self.pool_mint_key,
+                    &depositor_pool_key,
+                    DepositAllTokenTypes {
+                        pool_token_amount,
+                        maximum_token_a_amount,
+                        maximum_token_b_amount,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.swap_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut depositor_token_a_account,
+                    &mut depositor_token_b_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut self.pool_mint_account,
+                    &mut depositor_pool_account,
+                    &mut Account::default(),
+                ],
+            )
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn withdraw_all_token_types(
+            &mut self,
+            user_key: &Pubkey,
+            pool_key: &Pubkey,
+            mut pool_account: &mut Account,
+            token_a_key: &Pubkey,
+            mut token_a_account: &mut Account,
+            token_b_key: &Pubkey,
+            mut token_b_account: &mut Account,
+            pool_token_amount: u64,
+            minimum_token_a_amount: u64,
+            minimum_token_b_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_authority_key = Pubkey::new_unique();
+            // approve user transfer authority to take out pool tokens
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &pool_key,
+                    &user_transfer_authority_key,
+                    &user_key,
+                    &[],
+                    pool_token_amount,
+                )
+                .unwrap(),
+                vec![
+                    &mut pool_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+
+            // withdraw token a and b correctly
+            do_process_instruction(
+                withdraw_all_token_types(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    &pool_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &token_a_key,
+                    &token_b_key,
+                    WithdrawAllTokenTypes {
+                        pool_token_amount,
+                        minimum_token_a_amount,
+                        minimum_token_b_amount,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.swap_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut self.pool_mint_account,
+                    &mut pool_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut token_a_account,
+                    &mut token_b_account,
+                    &mut self.pool_fee_account,
+                    &mut Account::default(),
+                ],
+            )
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn deposit_single_token_type_exact_amount_in(
+            &mut self,
+            depositor_key: &Pubkey,
+            deposit_account_key: &Pubkey,
+            mut deposit_token_account: &mut Account,
+            deposit_pool_key: &Pubkey,
+            mut deposit_pool_account: &mut Account,
+            source_token_amount: u64,
+            minimum_pool_token_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_authority_key = Pubkey::new_unique();
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &deposit_account_key,
+                    &user_transfer_authority_key,
+                    &depositor_key,
+                    &[],
+                    source_token_amount,
+                )
+                .unwrap(),
+                vec![
+                    &mut deposit_token_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+
+            do_process_instruction(
+                deposit_single_token_type_exact_amount_in(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority_key,
+                    &deposit_account_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &self.pool_mint_key,
+                    &deposit_pool_key,
+                    DepositSingleTokenTypeExactAmountIn {
+                        source_token_amount,
+                        minimum_pool_token_amount,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.swap_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut deposit_token_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut self.pool_mint_account,
+                    &mut deposit_pool_account,
+                    &mut Account::default(),
+                ],
+            )
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn withdraw_single_token_type_exact_amount_out(
+            &mut self,
+            user_key: &Pubkey,
+            pool_key: &Pubkey,
+            mut pool_account: &mut Account,
+            destination_key: &Pubkey,
+            mut destination_account: &mut Account,
+            destination_token_amount: u64,
+            maximum_pool_token_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_authority_key = Pubkey::new_unique();
+            // approve user transfer authority to take out pool tokens
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &pool_key,
+                    &user_transfer_authority_key,
+                    &user_key,
+                    &[],
+                    maximum_pool_token_amount,
+                )
+                .unwrap(),
+                vec![
+                    &mut pool_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+
+            do_process_instruction(
+                withdraw_single_token_type_exact_amount_out(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    &pool_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &destination_key,
+                    WithdrawSingleTokenTypeExactAmountOut {
+                        destination_token_amount,
+                        maximum_pool_token_amount,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.swap_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut self.pool_mint_account,
+                    &mut pool_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut destination_account,
+                    &mut self.pool_fee_account,
+                    &mut Account::default(),
+                ],
+            )
+        }
+
+        pub fn open_position(
+            &mut self,
+            owner_key: &Pubkey,
+            position_key: &Pubkey,
+            position_account: &mut Account,
+            tick_lower: i32,
+            tick_upper: i32,
+        ) -> ProgramResult {
+            do_process_instruction(
+                open_position(
+                    &SWAP_PROGRAM_ID,
+                    &self.swap_key,
+                    position_key,
+                    owner_key,
+                    OpenPosition {
+                        tick_lower,
+                        tick_upper,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.swap_account,
+                    position_account,
+                    &mut Account::default(),
+                ],
+            )
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn increase_liquidity(
+            &mut self,
+            owner_key: &Pubkey,
+            position_key: &Pubkey,
+            position_account: &mut Account,
+            depositor_token_a_key: &Pubkey,
+            mut depositor_token_a_account: &mut Account,
+            depositor_token_b_key: &Pubkey,
+            mut depositor_token_b_account: &mut Account,
+            liquidity: u128,
+            maximum_token_a_amount: u64,
+            maximum_token_b_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_authority = Pubkey::new_unique();
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &depositor_token_a_key,
+                    &user_transfer_authority,
+                    &owner_key,
+                    &[],
+                    maximum_token_a_amount,
+                )
+                .unwrap(),
+                vec![
+                    &mut depositor_token_a_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &depositor_token_b_key,
+                    &user_transfer_authority,
+                    &owner_key,
+                    &[],
+                    maximum_token_b_amount,
+                )
+                .unwrap(),
+                vec![
+                    &mut depositor_token_b_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+
+            do_process_instruction(
+                increase_liquidity(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority,
+                    position_key,
+                    owner_key,
+                    depositor_token_a_key,
+                    depositor_token_b_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    IncreaseLiquidity {
+                        liquidity,
+                        maximum_token_a_amount,
+                        maximum_token_b_amount,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.swap_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    position_account,
+                    &mut Account::default(),
+                    &mut depositor_token_a_account,
+                    &mut depositor_token_b_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut Account::default(),
+                ],
+            )
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn decrease_liquidity(
+            &mut self,
+            owner_key: &Pubkey,
+            position_key: &Pubkey,
+            position_account: &mut Account,
+            token_a_key: &Pubkey,
+            token_a_account: &mut Account,
+            token_b_key: &Pubkey,
+            token_b_account: &mut Account,
+            liquidity: u128,
+            minimum_token_a_amount: u64,
+            minimum_token_b_amount: u64,
+        ) -> ProgramResult {
+            do_process_instruction(
+                decrease_liquidity(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &self.swap_key,
+                    &self.authority_key,
+                    position_key,
+                    owner_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    token_a_key,
+                    token_b_key,
+                    DecreaseLiquidity {
+                        liquidity,
+                        minimum_token_a_amount,
+                        minimum_token_b_amount,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.swap_account,
+                    &mut Account::default(),
+                    position_account,
+                    &mut Account::default(),
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    token_a_account,
+                    token_b_account,
+                    &mut Account::default(),
+                ],
+            )
+        }
+    }
+
+    fn mint_minimum_balance() -> u64 {
+        Rent::default().minimum_balance(spl_token::state::Mint::get_packed_len())
+    }
+
+    fn account_minimum_balance() -> u64 {
+        Rent::default().minimum_balance(spl_token::state::Account::get_packed_len())
+    }
+
+    fn do_process_instruction_with_fee_constraints(
+        instruction: Instruction,
+        accounts: Vec<&mut Account>,
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        test_syscall_stubs();
+
+        // approximate the logic in the actual runtime which runs the instruction
+        // and only updates accounts if the instruction is successful
+        let mut account_clones = accounts.iter().map(|x| (*x).clone()).collect::<Vec<_>>();
+        let mut meta = instruction
+            .accounts
+            .iter()
+            .zip(account_clones.iter_mut())
+            .map(|(account_meta, account)| (&account_meta.pubkey, account_meta.is_signer, account))
+            .collect::<Vec<_>>();
+        let mut account_infos = create_is_signer_account_infos(&mut meta);
+        let res = if instruction.program_id == SWAP_PROGRAM_ID {
+            Processor::process_with_constraints(
+                &instruction.program_id,
+                &account_infos,
+                &instruction.data,
+                swap_constraints,
+            )
+        } else {
+            spl_token::processor::Processor::process(
+                &instruction.program_id,
+                &account_infos,
+                &instruction.data,
+            )
+        };
+
+        if res.is_ok() {
+            let mut account_metas = instruction
+                .accounts
+                .iter()
+                .zip(accounts)
+                .map(|(account_meta, account)| (&account_meta.pubkey, account))
+                .collect::<Vec<_>>();
+            for account_info in account_infos.iter_mut() {
+                for account_meta in account_metas.iter_mut() {
+                    if account_info.key == account_meta.0 {
+                        let account = &mut account_meta.1;
+                        account.owner = *account_info.owner;
+                        account.lamports = **account_info.lamports.borrow();
+                        account.data = account_info.data.borrow().to_vec();
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    fn do_process_instruction(
+        instruction: Instruction,
+        accounts: Vec<&mut Account>,
+    ) -> ProgramResult {
+        do_process_instruction_with_fee_constraints(instruction, accounts, &SWAP_CONSTRAINTS)
+    }
+
+    fn mint_token(
+        program_id: &Pubkey,
+        mint_key: &Pubkey,
+        mut mint_account: &mut Account,
+        mint_authority_key: &Pubkey,
+        account_owner_key: &Pubkey,
+        amount: u64,
+    ) -> (Pubkey, Account) {
+        let account_key = Pubkey::new_unique();
+        let mut account_account = Account::new(
+            account_minimum_balance(),
+            spl_token::state::Account::get_packed_len(),
+            &program_id,
+        );
+        let mut mint_authority_account = Account::default();
+        let mut rent_sysvar_account = create_account_for_test(&Rent::free());
+
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, account_owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut mint_authority_account,
+                &mut rent_sysvar_account,
+            ],
+        )
+        .unwrap();
+
+        if amount > 0 {
+            do_process_instruction(
+                mint_to(
+                    &program_id,
+                    &mint_key,
+                    &account_key,
+                    &mint_authority_key,
+                    &[],
+                    amount,
+                )
+                .unwrap(),
+                vec![
+                    &mut mint_account,
+                    &mut account_account,
+                    &mut mint_authority_account,
+                ],
+            )
+            .unwrap();
+        }
+
+        (account_key, account_account)
+    }
+
+    fn create_mint(
+        program_id: &Pubkey,
+        authority_key: &Pubkey,
+        freeze_authority: Option<&Pubkey>,
+    ) -> (Pubkey, Account) {
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account = Account::new(
+            mint_minimum_balance(),
+            spl_token::state::Mint::get_packed_len(),
+            &program_id,
+        );
+        let mut rent_sysvar_account = create_account_for_test(&Rent::free());
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, authority_key, freeze_authority, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar_account],
+        )
+        .unwrap();
+
+        (mint_key, mint_account)
+    }
+
+    #[test]
+    fn test_token_program_id_error() {
+        test_syscall_stubs();
+        let swap_key = Pubkey::new_unique();
+        let mut mint = (Pubkey::new_unique(), Account::default());
+        let mut destination = (Pubkey::new_unique(), Account::default());
+        let token_program = (TOKEN_PROGRAM_ID, Account::default());
+        let (authority_key, nonce) =
+            Pubkey::find_program_address(&[&swap_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
+        let mut authority = (authority_key, Account::default());
+        let swap_bytes = swap_key.to_bytes();
+        let authority_signature_seeds = [&swap_bytes[..32], &[nonce]];
+        let signers = &[&authority_signature_seeds[..]];
+        let ix = mint_to(
+            &token_program.0,
             &mint.0,
             &destination.0,
             &authority.0,
@@ -1964,13 +3476,876 @@ mod tests {
         let destination = (&mut destination).into();
         let authority = (&mut authority).into();
 
-        let err = invoke_signed(&ix, &[mint, destination, authority], signers).unwrap_err();
-        assert_eq!(err, ProgramError::InvalidAccountData);
+        let err = invoke_signed(&ix, &[mint, destination, authority], signers).unwrap_err();
+        assert_eq!(err, ProgramError::InvalidAccountData);
+    }
+
+    #[test]
+    fn test_initialize() {
+        let user_key = Pubkey::new_unique();
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 2;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 10;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 5;
+        let host_fee_numerator = 20;
+        let host_fee_denominator = 100;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let token_a_amount = 1000;
+        let token_b_amount = 2000;
+        let pool_token_amount = 10;
+        let curve_type = CurveType::ConstantProduct;
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+
+        // wrong nonce for authority_key
+        {
+            let old_nonce = accounts.nonce;
+            accounts.nonce = old_nonce - 1;
+            assert_eq!(
+                Err(SwapError::InvalidProgramAddress.into()),
+                accounts.initialize_swap()
+            );
+            accounts.nonce = old_nonce;
+        }
+
+        // uninitialized token a account
+        {
+            let old_account = accounts.token_a_account;
+            accounts.token_a_account = Account::new(0, 0, &TOKEN_PROGRAM_ID);
+            assert_eq!(
+                Err(SwapError::ExpectedAccount.into()),
+                accounts.initialize_swap()
+            );
+            accounts.token_a_account = old_account;
+        }
+
+        // uninitialized token b account
+        {
+            let old_account = accounts.token_b_account;
+            accounts.token_b_account = Account::new(0, 0, &TOKEN_PROGRAM_ID);
+            assert_eq!(
+                Err(SwapError::ExpectedAccount.into()),
+                accounts.initialize_swap()
+            );
+            accounts.token_b_account = old_account;
+        }
+
+        // uninitialized pool mint
+        {
+            let old_account = accounts.pool_mint_account;
+            accounts.pool_mint_account = Account::new(0, 0, &TOKEN_PROGRAM_ID);
+            assert_eq!(
+                Err(SwapError::ExpectedMint.into()),
+                accounts.initialize_swap()
+            );
+            accounts.pool_mint_account = old_account;
+        }
+
+        // token A account owner is not swap authority
+        {
+            let (_token_a_key, token_a_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
+                &user_key,
+                &user_key,
+                0,
+            );
+            let old_account = accounts.token_a_account;
+            accounts.token_a_account = token_a_account;
+            assert_eq!(
+                Err(SwapError::InvalidOwner.into()),
+                accounts.initialize_swap()
+            );
+            accounts.token_a_account = old_account;
+        }
+
+        // token B account owner is not swap authority
+        {
+            let (_token_b_key, token_b_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.token_b_mint_key,
+                &mut accounts.token_b_mint_account,
+                &user_key,
+                &user_key,
+                0,
+            );
+            let old_account = accounts.token_b_account;
+            accounts.token_b_account = token_b_account;
+            assert_eq!(
+                Err(SwapError::InvalidOwner.into()),
+                accounts.initialize_swap()
+            );
+            accounts.token_b_account = old_account;
+        }
+
+        // pool token account owner is swap authority
+        {
+            let (_pool_token_key, pool_token_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.pool_mint_key,
+                &mut accounts.pool_mint_account,
+                &accounts.authority_key,
+                &accounts.authority_key,
+                0,
+            );
+            let old_account = accounts.pool_token_account;
+            accounts.pool_token_account = pool_token_account;
+            assert_eq!(
+                Err(SwapError::InvalidOutputOwner.into()),
+                accounts.initialize_swap()
+            );
+            accounts.pool_token_account = old_account;
+        }
+
+        // pool fee account owner is swap authority
+        {
+            let (_pool_fee_key, pool_fee_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.pool_mint_key,
+                &mut accounts.pool_mint_account,
+                &accounts.authority_key,
+                &accounts.authority_key,
+                0,
+            );
+            let old_account = accounts.pool_fee_account;
+            accounts.pool_fee_account = pool_fee_account;
+            assert_eq!(
+                Err(SwapError::InvalidOutputOwner.into()),
+                accounts.initialize_swap()
+            );
+            accounts.pool_fee_account = old_account;
+        }
+
+        // pool mint authority is not swap authority
+        {
+            let (_pool_mint_key, pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
+            let old_mint = accounts.pool_mint_account;
+            accounts.pool_mint_account = pool_mint_account;
+            assert_eq!(
+                Err(SwapError::InvalidOwner.into()),
+                accounts.initialize_swap()
+            );
+            accounts.pool_mint_account = old_mint;
+        }
+
+        // pool mint token has freeze authority
+        {
+            let (_pool_mint_key, pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, Some(&user_key));
+            let old_mint = accounts.pool_mint_account;
+            accounts.pool_mint_account = pool_mint_account;
+            assert_eq!(
+                Err(SwapError::InvalidFreezeAuthority.into()),
+                accounts.initialize_swap()
+            );
+            accounts.pool_mint_account = old_mint;
+        }
+
+        // token A account owned by wrong program
+        {
+            let (_token_a_key, mut token_a_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
+                &user_key,
+                &accounts.authority_key,
+                token_a_amount,
+            );
+            token_a_account.owner = SWAP_PROGRAM_ID;
+            let old_account = accounts.token_a_account;
+            accounts.token_a_account = token_a_account;
+            assert_eq!(
+                Err(SwapError::IncorrectTokenProgramId.into()),
+                accounts.initialize_swap()
+            );
+            accounts.token_a_account = old_account;
+        }
+
+        // token B account owned by wrong program
+        {
+            let (_token_b_key, mut token_b_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.token_b_mint_key,
+                &mut accounts.token_b_mint_account,
+                &user_key,
+                &accounts.authority_key,
+                token_b_amount,
+            );
+            token_b_account.owner = SWAP_PROGRAM_ID;
+            let old_account = accounts.token_b_account;
+            accounts.token_b_account = token_b_account;
+            assert_eq!(
+                Err(SwapError::IncorrectTokenProgramId.into()),
+                accounts.initialize_swap()
+            );
+            accounts.token_b_account = old_account;
+        }
+
+        // empty token A account
+        {
+            let (_token_a_key, token_a_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
+                &user_key,
+                &accounts.authority_key,
+                0,
+            );
+            let old_account = accounts.token_a_account;
+            accounts.token_a_account = token_a_account;
+            assert_eq!(
+                Err(SwapError::EmptySupply.into()),
+                accounts.initialize_swap()
+            );
+            accounts.token_a_account = old_account;
+        }
+
+        // empty token B account
+        {
+            let (_token_b_key, token_b_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.token_b_mint_key,
+                &mut accounts.token_b_mint_account,
+                &user_key,
+                &accounts.authority_key,
+                0,
+            );
+            let old_account = accounts.token_b_account;
+            accounts.token_b_account = token_b_account;
+            assert_eq!(
+                Err(SwapError::EmptySupply.into()),
+                accounts.initialize_swap()
+            );
+            accounts.token_b_account = old_account;
+        }
+
+        // invalid pool tokens
+        {
+            let old_mint = accounts.pool_mint_account;
+            let old_pool_account = accounts.pool_token_account;
+
+            let (_pool_mint_key, pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
+            accounts.pool_mint_account = pool_mint_account;
+
+            let (_empty_pool_token_key, empty_pool_token_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.pool_mint_key,
+                &mut accounts.pool_mint_account,
+                &accounts.authority_key,
+                &user_key,
+                0,
+            );
+
+            let (_pool_token_key, pool_token_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.pool_mint_key,
+                &mut accounts.pool_mint_account,
+                &accounts.authority_key,
+                &user_key,
+                pool_token_amount,
+            );
+
+            // non-empty pool token account
+            accounts.pool_token_account = pool_token_account;
+            assert_eq!(
+                Err(SwapError::InvalidSupply.into()),
+                accounts.initialize_swap()
+            );
+
+            // pool tokens already in circulation
+            accounts.pool_token_account = empty_pool_token_account;
+            assert_eq!(
+                Err(SwapError::InvalidSupply.into()),
+                accounts.initialize_swap()
+            );
+
+            accounts.pool_mint_account = old_mint;
+            accounts.pool_token_account = old_pool_account;
+        }
+
+        // pool fee account has wrong mint
+        {
+            let (_pool_fee_key, pool_fee_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
+                &user_key,
+                &user_key,
+                0,
+            );
+            let old_account = accounts.pool_fee_account;
+            accounts.pool_fee_account = pool_fee_account;
+            assert_eq!(
+                Err(SwapError::IncorrectPoolMint.into()),
+                accounts.initialize_swap()
+            );
+            accounts.pool_fee_account = old_account;
+        }
+
+        // token A account is delegated
+        {
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_a_key,
+                    &user_key,
+                    &accounts.authority_key,
+                    &[],
+                    1,
+                )
+                .unwrap(),
+                vec![
+                    &mut accounts.token_a_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+            assert_eq!(
+                Err(SwapError::InvalidDelegate.into()),
+                accounts.initialize_swap()
+            );
+
+            do_process_instruction(
+                revoke(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_a_key,
+                    &accounts.authority_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_a_account, &mut Account::default()],
+            )
+            .unwrap();
+        }
+
+        // token B account is delegated
+        {
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_b_key,
+                    &user_key,
+                    &accounts.authority_key,
+                    &[],
+                    1,
+                )
+                .unwrap(),
+                vec![
+                    &mut accounts.token_b_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+            assert_eq!(
+                Err(SwapError::InvalidDelegate.into()),
+                accounts.initialize_swap()
+            );
+
+            do_process_instruction(
+                revoke(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_b_key,
+                    &accounts.authority_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_b_account, &mut Account::default()],
+            )
+            .unwrap();
+        }
+
+        // token A account has close authority
+        {
+            do_process_instruction(
+                set_authority(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_a_key,
+                    Some(&user_key),
+                    AuthorityType::CloseAccount,
+                    &accounts.authority_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_a_account, &mut Account::default()],
+            )
+            .unwrap();
+            assert_eq!(
+                Err(SwapError::InvalidCloseAuthority.into()),
+                accounts.initialize_swap()
+            );
+
+            do_process_instruction(
+                set_authority(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_a_key,
+                    None,
+                    AuthorityType::CloseAccount,
+                    &user_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_a_account, &mut Account::default()],
+            )
+            .unwrap();
+        }
+
+        // token B account has close authority
+        {
+            do_process_instruction(
+                set_authority(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_b_key,
+                    Some(&user_key),
+                    AuthorityType::CloseAccount,
+                    &accounts.authority_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_b_account, &mut Account::default()],
+            )
+            .unwrap();
+            assert_eq!(
+                Err(SwapError::InvalidCloseAuthority.into()),
+                accounts.initialize_swap()
+            );
+
+            do_process_instruction(
+                set_authority(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_b_key,
+                    None,
+                    AuthorityType::CloseAccount,
+                    &user_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_b_account, &mut Account::default()],
+            )
+            .unwrap();
+        }
+
+        // wrong token program id
+        {
+            let wrong_program_id = Pubkey::new_unique();
+            assert_eq!(
+                Err(SwapError::IncorrectTokenProgramId.into()),
+                do_process_instruction(
+                    initialize(
+                        &SWAP_PROGRAM_ID,
+                        &wrong_program_id,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &accounts.pool_token_key,
+                        accounts.nonce,
+                        accounts.fees.clone(),
+                        accounts.swap_curve.clone(),
+                        None,
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.pool_token_account,
+                        &mut Account::default(),
+                    ],
+                )
+            );
+        }
+
+        // create swap with same token A and B
+        {
+            let (_token_a_repeat_key, token_a_repeat_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
+                &user_key,
+                &accounts.authority_key,
+                10,
+            );
+            let old_account = accounts.token_b_account;
+            accounts.token_b_account = token_a_repeat_account;
+            assert_eq!(
+                Err(SwapError::RepeatedMint.into()),
+                accounts.initialize_swap()
+            );
+            accounts.token_b_account = old_account;
+        }
+
+        // create valid swap
+        accounts.initialize_swap().unwrap();
+
+        // create invalid flat swap
+        {
+            let token_b_price = 0;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantPrice,
+                calculator: Box::new(ConstantPriceCurve { token_b_price }),
+            };
+            let mut accounts =
+                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+            assert_eq!(
+                Err(SwapError::InvalidCurve.into()),
+                accounts.initialize_swap()
+            );
+        }
+
+        // create valid flat swap
+        {
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let token_b_price = 10_000;
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantPrice,
+                calculator: Box::new(ConstantPriceCurve { token_b_price }),
+            };
+            let mut accounts =
+                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+            accounts.initialize_swap().unwrap();
+        }
+
+        // create invalid offset swap
+        {
+            let token_b_offset = 0;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::Offset,
+                calculator: Box::new(OffsetCurve { token_b_offset }),
+            };
+            let mut accounts =
+                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+            assert_eq!(
+                Err(SwapError::InvalidCurve.into()),
+                accounts.initialize_swap()
+            );
+        }
+
+        // create valid offset swap
+        {
+            let token_b_offset = 10;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::Offset,
+                calculator: Box::new(OffsetCurve { token_b_offset }),
+            };
+            let mut accounts =
+                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+            accounts.initialize_swap().unwrap();
+        }
+
+        // wrong owner key in constraint
+        {
+            let new_key = Pubkey::new_unique();
+            let trade_fee_numerator = 25;
+            let trade_fee_denominator = 10000;
+            let owner_trade_fee_numerator = 5;
+            let owner_trade_fee_denominator = 10000;
+            let host_fee_numerator = 20;
+            let host_fee_denominator = 100;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let curve = ConstantProductCurve {};
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(curve),
+            };
+            let owner_key = &new_key.to_string();
+            let valid_curve_types = &[CurveType::ConstantProduct];
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types,
+                fees: &fees,
+            });
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                fees.clone(),
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
+            );
+            assert_eq!(
+                Err(SwapError::InvalidOwner.into()),
+                do_process_instruction_with_fee_constraints(
+                    initialize(
+                        &SWAP_PROGRAM_ID,
+                        &TOKEN_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &accounts.pool_token_key,
+                        accounts.nonce,
+                        accounts.fees.clone(),
+                        accounts.swap_curve.clone(),
+                        None,
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.pool_token_account,
+                        &mut Account::default(),
+                    ],
+                    &constraints,
+                )
+            );
+        }
+
+        // wrong fee in constraint
+        {
+            let trade_fee_numerator = 25;
+            let trade_fee_denominator = 10000;
+            let owner_trade_fee_numerator = 5;
+            let owner_trade_fee_denominator = 10000;
+            let host_fee_numerator = 20;
+            let host_fee_denominator = 100;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let curve = ConstantProductCurve {};
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(curve),
+            };
+            let owner_key = &user_key.to_string();
+            let valid_curve_types = &[CurveType::ConstantProduct];
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types,
+                fees: &fees,
+            });
+            let mut bad_fees = fees.clone();
+            bad_fees.trade_fee_numerator = trade_fee_numerator - 1;
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                bad_fees,
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
+            );
+            assert_eq!(
+                Err(SwapError::InvalidFee.into()),
+                do_process_instruction_with_fee_constraints(
+                    initialize(
+                        &SWAP_PROGRAM_ID,
+                        &TOKEN_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &accounts.pool_token_key,
+                        accounts.nonce,
+                        accounts.fees.clone(),
+                        accounts.swap_curve.clone(),
+                        None,
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.pool_token_account,
+                        &mut Account::default(),
+                    ],
+                    &constraints,
+                )
+            );
+        }
+
+        // create valid swap with constraints
+        {
+            let trade_fee_numerator = 25;
+            let trade_fee_denominator = 10000;
+            let owner_trade_fee_numerator = 5;
+            let owner_trade_fee_denominator = 10000;
+            let host_fee_numerator = 20;
+            let host_fee_denominator = 100;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let curve = ConstantProductCurve {};
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(curve),
+            };
+            let owner_key = &user_key.to_string();
+            let valid_curve_types = &[CurveType::ConstantProduct];
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types,
+                fees: &fees,
+            });
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                fees.clone(),
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
+            );
+            do_process_instruction_with_fee_constraints(
+                initialize(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.swap_key,
+                    &accounts.authority_key,
+                    &accounts.token_a_key,
+                    &accounts.token_b_key,
+                    &accounts.pool_mint_key,
+                    &accounts.pool_fee_key,
+                    &accounts.pool_token_key,
+                    accounts.nonce,
+                    accounts.fees,
+                    accounts.swap_curve.clone(),
+                    None,
+                )
+                .unwrap(),
+                vec![
+                    &mut accounts.swap_account,
+                    &mut Account::default(),
+                    &mut accounts.token_a_account,
+                    &mut accounts.token_b_account,
+                    &mut accounts.pool_mint_account,
+                    &mut accounts.pool_fee_account,
+                    &mut accounts.pool_token_account,
+                    &mut Account::default(),
+                ],
+                &constraints,
+            )
+            .unwrap();
+        }
+
+        // create again
+        {
+            assert_eq!(
+                Err(SwapError::AlreadyInUse.into()),
+                accounts.initialize_swap()
+            );
+        }
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert_eq!(swap_state.is_initialized(), true);
+        assert_eq!(swap_state.nonce(), accounts.nonce);
+        assert_eq!(
+            swap_state.swap_curve().curve_type,
+            accounts.swap_curve.curve_type
+        );
+        assert_eq!(*swap_state.token_a_account(), accounts.token_a_key);
+        assert_eq!(*swap_state.token_b_account(), accounts.token_b_key);
+        assert_eq!(*swap_state.pool_mint(), accounts.pool_mint_key);
+        assert_eq!(*swap_state.token_a_mint(), accounts.token_a_mint_key);
+        assert_eq!(*swap_state.token_b_mint(), accounts.token_b_mint_key);
+        assert_eq!(*swap_state.pool_fee_account(), accounts.pool_fee_key);
+        let token_a = spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+        assert_eq!(token_a.amount, token_a_amount);
+        let token_b = spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+        assert_eq!(token_b.amount, token_b_amount);
+        let pool_account =
+            spl_token::state::Account::unpack(&accounts.pool_token_account.data).unwrap();
+        let pool_mint = spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+        assert_eq!(pool_mint.supply, pool_account.amount);
     }
 
     #[test]
-    fn test_initialize() {
+    fn test_deposit() {
         let user_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
         let trade_fee_numerator = 1;
         let trade_fee_denominator = 2;
         let owner_trade_fee_numerator = 1;
@@ -1979,6 +4354,7 @@ mod tests {
         let owner_withdraw_fee_denominator = 5;
         let host_fee_numerator = 20;
         let host_fee_denominator = 100;
+
         let fees = Fees {
             trade_fee_numerator,
             trade_fee_denominator,
@@ -1991,8 +4367,7 @@ mod tests {
         };
 
         let token_a_amount = 1000;
-        let token_b_amount = 2000;
-        let pool_token_amount = 10;
+        let token_b_amount = 9000;
         let curve_type = CurveType::ConstantProduct;
         let swap_curve = SwapCurve {
             curve_type,
@@ -2002,832 +4377,1393 @@ mod tests {
         let mut accounts =
             SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
 
+        // depositing 10% of the current pool amount in token A and B means
+        // that our pool tokens will be worth 1 / 10 of the current pool amount
+        let pool_amount = INITIAL_SWAP_POOL_AMOUNT / 10;
+        let deposit_a = token_a_amount / 10;
+        let deposit_b = token_b_amount / 10;
+
+        // swap not initialized
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            assert_eq!(
+                Err(ProgramError::UninitializedAccount),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
+            );
+        }
+
+        accounts.initialize_swap().unwrap();
+
+        // wrong owner for swap account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let old_swap_account = accounts.swap_account;
+            let mut wrong_swap_account = old_swap_account.clone();
+            wrong_swap_account.owner = TOKEN_PROGRAM_ID;
+            accounts.swap_account = wrong_swap_account;
+            assert_eq!(
+                Err(ProgramError::IncorrectProgramId),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
+            );
+            accounts.swap_account = old_swap_account;
+        }
+
         // wrong nonce for authority_key
         {
-            let old_nonce = accounts.nonce;
-            accounts.nonce = old_nonce - 1;
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let old_authority = accounts.authority_key;
+            let (bad_authority_key, _nonce) = Pubkey::find_program_address(
+                &[&accounts.swap_key.to_bytes()[..]],
+                &TOKEN_PROGRAM_ID,
+            );
+            accounts.authority_key = bad_authority_key;
             assert_eq!(
                 Err(SwapError::InvalidProgramAddress.into()),
-                accounts.initialize_swap()
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
+            );
+            accounts.authority_key = old_authority;
+        }
+
+        // not enough token A
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &depositor_key,
+                deposit_a / 2,
+                deposit_b,
+                0,
+            );
+            assert_eq!(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
             );
-            accounts.nonce = old_nonce;
         }
 
-        // uninitialized token a account
+        // not enough token B
         {
-            let old_account = accounts.token_a_account;
-            accounts.token_a_account = Account::new(0, 0, &TOKEN_PROGRAM_ID);
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &depositor_key,
+                deposit_a,
+                deposit_b / 2,
+                0,
+            );
             assert_eq!(
-                Err(SwapError::ExpectedAccount.into()),
-                accounts.initialize_swap()
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
             );
-            accounts.token_a_account = old_account;
         }
 
-        // uninitialized token b account
+        // wrong swap token accounts
         {
-            let old_account = accounts.token_b_account;
-            accounts.token_b_account = Account::new(0, 0, &TOKEN_PROGRAM_ID);
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             assert_eq!(
-                Err(SwapError::ExpectedAccount.into()),
-                accounts.initialize_swap()
+                Err(TokenError::MintMismatch.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
             );
-            accounts.token_b_account = old_account;
         }
 
-        // uninitialized pool mint
+        // wrong pool token account
         {
-            let old_account = accounts.pool_mint_account;
-            accounts.pool_mint_account = Account::new(0, 0, &TOKEN_PROGRAM_ID);
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                mut _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let (
+                wrong_token_key,
+                mut wrong_token_account,
+                _token_b_key,
+                mut _token_b_account,
+                _pool_key,
+                mut _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             assert_eq!(
-                Err(SwapError::ExpectedMint.into()),
-                accounts.initialize_swap()
+                Err(TokenError::MintMismatch.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &wrong_token_key,
+                    &mut wrong_token_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
             );
-            accounts.pool_mint_account = old_account;
         }
 
-        // token A account owner is not swap authority
+        // no approval
         {
-            let (_token_a_key, token_a_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.token_a_mint_key,
-                &mut accounts.token_a_mint_account,
-                &user_key,
-                &user_key,
-                0,
-            );
-            let old_account = accounts.token_a_account;
-            accounts.token_a_account = token_a_account;
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let user_transfer_authority_key = Pubkey::new_unique();
             assert_eq!(
-                Err(SwapError::InvalidOwner.into()),
-                accounts.initialize_swap()
+                Err(TokenError::OwnerMismatch.into()),
+                do_process_instruction(
+                    deposit_all_token_types(
+                        &SWAP_PROGRAM_ID,
+                        &TOKEN_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &user_transfer_authority_key,
+                        &token_a_key,
+                        &token_b_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &accounts.pool_mint_key,
+                        &pool_key,
+                        DepositAllTokenTypes {
+                            pool_token_amount: pool_amount.try_into().unwrap(),
+                            maximum_token_a_amount: deposit_a,
+                            maximum_token_b_amount: deposit_b,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut Account::default(),
+                        &mut token_a_account,
+                        &mut token_b_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
+                        &mut Account::default(),
+                    ],
+                )
             );
-            accounts.token_a_account = old_account;
         }
 
-        // token B account owner is not swap authority
+        // wrong token program id
         {
-            let (_token_b_key, token_b_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.token_b_mint_key,
-                &mut accounts.token_b_mint_account,
-                &user_key,
-                &user_key,
-                0,
-            );
-            let old_account = accounts.token_b_account;
-            accounts.token_b_account = token_b_account;
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let wrong_key = Pubkey::new_unique();
             assert_eq!(
-                Err(SwapError::InvalidOwner.into()),
-                accounts.initialize_swap()
+                Err(SwapError::IncorrectTokenProgramId.into()),
+                do_process_instruction(
+                    deposit_all_token_types(
+                        &SWAP_PROGRAM_ID,
+                        &wrong_key,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.authority_key,
+                        &token_a_key,
+                        &token_b_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &accounts.pool_mint_key,
+                        &pool_key,
+                        DepositAllTokenTypes {
+                            pool_token_amount: pool_amount.try_into().unwrap(),
+                            maximum_token_a_amount: deposit_a,
+                            maximum_token_b_amount: deposit_b,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut Account::default(),
+                        &mut token_a_account,
+                        &mut token_b_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
+                        &mut Account::default(),
+                    ],
+                )
             );
-            accounts.token_b_account = old_account;
         }
 
-        // pool token account owner is swap authority
+        // wrong swap token accounts
         {
-            let (_pool_token_key, pool_token_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.pool_mint_key,
-                &mut accounts.pool_mint_account,
-                &accounts.authority_key,
-                &accounts.authority_key,
-                0,
-            );
-            let old_account = accounts.pool_token_account;
-            accounts.pool_token_account = pool_token_account;
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+
+            let old_a_key = accounts.token_a_key;
+            let old_a_account = accounts.token_a_account;
+
+            accounts.token_a_key = token_a_key;
+            accounts.token_a_account = token_a_account.clone();
+
+            // wrong swap token a account
             assert_eq!(
-                Err(SwapError::InvalidOutputOwner.into()),
-                accounts.initialize_swap()
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
             );
-            accounts.pool_token_account = old_account;
-        }
 
-        // pool fee account owner is swap authority
-        {
-            let (_pool_fee_key, pool_fee_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.pool_mint_key,
-                &mut accounts.pool_mint_account,
-                &accounts.authority_key,
-                &accounts.authority_key,
-                0,
-            );
-            let old_account = accounts.pool_fee_account;
-            accounts.pool_fee_account = pool_fee_account;
+            accounts.token_a_key = old_a_key;
+            accounts.token_a_account = old_a_account;
+
+            let old_b_key = accounts.token_b_key;
+            let old_b_account = accounts.token_b_account;
+
+            accounts.token_b_key = token_b_key;
+            accounts.token_b_account = token_b_account.clone();
+
+            // wrong swap token b account
             assert_eq!(
-                Err(SwapError::InvalidOutputOwner.into()),
-                accounts.initialize_swap()
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
             );
-            accounts.pool_fee_account = old_account;
+
+            accounts.token_b_key = old_b_key;
+            accounts.token_b_account = old_b_account;
         }
 
-        // pool mint authority is not swap authority
+        // wrong mint
         {
-            let (_pool_mint_key, pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
-            let old_mint = accounts.pool_mint_account;
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let (pool_mint_key, pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
+            let old_pool_key = accounts.pool_mint_key;
+            let old_pool_account = accounts.pool_mint_account;
+            accounts.pool_mint_key = pool_mint_key;
             accounts.pool_mint_account = pool_mint_account;
+
             assert_eq!(
-                Err(SwapError::InvalidOwner.into()),
-                accounts.initialize_swap()
+                Err(SwapError::IncorrectPoolMint.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
             );
-            accounts.pool_mint_account = old_mint;
+
+            accounts.pool_mint_key = old_pool_key;
+            accounts.pool_mint_account = old_pool_account;
         }
 
-        // pool mint token has freeze authority
+        // deposit 1 pool token fails beacuse it equates to 0 swap tokens
         {
-            let (_pool_mint_key, pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, Some(&user_key));
-            let old_mint = accounts.pool_mint_account;
-            accounts.pool_mint_account = pool_mint_account;
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             assert_eq!(
-                Err(SwapError::InvalidFreezeAuthority.into()),
-                accounts.initialize_swap()
+                Err(SwapError::ZeroTradingTokens.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    1,
+                    deposit_a,
+                    deposit_b,
+                )
             );
-            accounts.pool_mint_account = old_mint;
         }
 
-        // token A account owned by wrong program
+        // slippage exceeded
         {
-            let (_token_a_key, mut token_a_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.token_a_mint_key,
-                &mut accounts.token_a_mint_account,
-                &user_key,
-                &accounts.authority_key,
-                token_a_amount,
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            // maximum A amount in too low
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a / 10,
+                    deposit_b,
+                )
             );
-            token_a_account.owner = SWAP_PROGRAM_ID;
-            let old_account = accounts.token_a_account;
-            accounts.token_a_account = token_a_account;
+            // maximum B amount in too low
             assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
-                accounts.initialize_swap()
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b / 10,
+                )
             );
-            accounts.token_a_account = old_account;
         }
 
-        // token B account owned by wrong program
+        // invalid input: can't use swap pool tokens as source
         {
-            let (_token_b_key, mut token_b_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.token_b_mint_key,
-                &mut accounts.token_b_mint_account,
-                &user_key,
-                &accounts.authority_key,
-                token_b_amount,
-            );
-            token_b_account.owner = SWAP_PROGRAM_ID;
-            let old_account = accounts.token_b_account;
-            accounts.token_b_account = token_b_account;
+            let (
+                _token_a_key,
+                _token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let swap_token_a_key = accounts.token_a_key;
+            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
+            let swap_token_b_key = accounts.token_b_key;
+            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
+            let authority_key = accounts.authority_key;
             assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
-                accounts.initialize_swap()
+                Err(SwapError::InvalidInput.into()),
+                accounts.deposit_all_token_types(
+                    &authority_key,
+                    &swap_token_a_key,
+                    &mut swap_token_a_account,
+                    &swap_token_b_key,
+                    &mut swap_token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
             );
-            accounts.token_b_account = old_account;
         }
 
-        // empty token A account
+        // correctly deposit
         {
-            let (_token_a_key, token_a_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.token_a_mint_key,
-                &mut accounts.token_a_mint_account,
-                &user_key,
-                &accounts.authority_key,
-                0,
-            );
-            let old_account = accounts.token_a_account;
-            accounts.token_a_account = token_a_account;
-            assert_eq!(
-                Err(SwapError::EmptySupply.into()),
-                accounts.initialize_swap()
-            );
-            accounts.token_a_account = old_account;
-        }
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            accounts
+                .deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
+                .unwrap();
 
-        // empty token B account
-        {
-            let (_token_b_key, token_b_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.token_b_mint_key,
-                &mut accounts.token_b_mint_account,
-                &user_key,
-                &accounts.authority_key,
-                0,
-            );
-            let old_account = accounts.token_b_account;
-            accounts.token_b_account = token_b_account;
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+            assert_eq!(swap_token_a.amount, deposit_a + token_a_amount);
+            let swap_token_b =
+                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+            assert_eq!(swap_token_b.amount, deposit_b + token_b_amount);
+            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+            assert_eq!(token_a.amount, 0);
+            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+            assert_eq!(token_b.amount, 0);
+            let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
+            let swap_pool_account =
+                spl_token::state::Account::unpack(&accounts.pool_token_account.data).unwrap();
+            let pool_mint =
+                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
             assert_eq!(
-                Err(SwapError::EmptySupply.into()),
-                accounts.initialize_swap()
+                pool_mint.supply,
+                pool_account.amount + swap_pool_account.amount
             );
-            accounts.token_b_account = old_account;
         }
+    }
 
-        // invalid pool tokens
-        {
-            let old_mint = accounts.pool_mint_account;
-            let old_pool_account = accounts.pool_token_account;
+    #[test]
+    fn test_withdraw() {
+        let user_key = Pubkey::new_unique();
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 2;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 10;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 5;
+        let host_fee_numerator = 7;
+        let host_fee_denominator = 100;
 
-            let (_pool_mint_key, pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
-            accounts.pool_mint_account = pool_mint_account;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
 
-            let (_empty_pool_token_key, empty_pool_token_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.pool_mint_key,
-                &mut accounts.pool_mint_account,
-                &accounts.authority_key,
-                &user_key,
-                0,
-            );
+        let token_a_amount = 1000;
+        let token_b_amount = 2000;
+        let curve_type = CurveType::ConstantProduct;
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
 
-            let (_pool_token_key, pool_token_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.pool_mint_key,
-                &mut accounts.pool_mint_account,
-                &accounts.authority_key,
-                &user_key,
-                pool_token_amount,
-            );
+        let withdrawer_key = Pubkey::new_unique();
+        let initial_a = token_a_amount / 10;
+        let initial_b = token_b_amount / 10;
+        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
+        let withdraw_amount = initial_pool / 4;
+        let minimum_token_a_amount = initial_a / 40;
+        let minimum_token_b_amount = initial_b / 40;
 
-            // non-empty pool token account
-            accounts.pool_token_account = pool_token_account;
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+
+        // swap not initialized
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
             assert_eq!(
-                Err(SwapError::InvalidSupply.into()),
-                accounts.initialize_swap()
+                Err(ProgramError::UninitializedAccount),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
             );
+        }
 
-            // pool tokens already in circulation
-            accounts.pool_token_account = empty_pool_token_account;
+        accounts.initialize_swap().unwrap();
+
+        // wrong owner for swap account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+            let old_swap_account = accounts.swap_account;
+            let mut wrong_swap_account = old_swap_account.clone();
+            wrong_swap_account.owner = TOKEN_PROGRAM_ID;
+            accounts.swap_account = wrong_swap_account;
             assert_eq!(
-                Err(SwapError::InvalidSupply.into()),
-                accounts.initialize_swap()
+                Err(ProgramError::IncorrectProgramId),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
             );
-
-            accounts.pool_mint_account = old_mint;
-            accounts.pool_token_account = old_pool_account;
+            accounts.swap_account = old_swap_account;
         }
 
-        // pool fee account has wrong mint
+        // wrong nonce for authority_key
         {
-            let (_pool_fee_key, pool_fee_account) = mint_token(
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+            let old_authority = accounts.authority_key;
+            let (bad_authority_key, _nonce) = Pubkey::find_program_address(
+                &[&accounts.swap_key.to_bytes()[..]],
                 &TOKEN_PROGRAM_ID,
-                &accounts.token_a_mint_key,
-                &mut accounts.token_a_mint_account,
-                &user_key,
-                &user_key,
-                0,
             );
-            let old_account = accounts.pool_fee_account;
-            accounts.pool_fee_account = pool_fee_account;
+            accounts.authority_key = bad_authority_key;
             assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                accounts.initialize_swap()
+                Err(SwapError::InvalidProgramAddress.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
             );
-            accounts.pool_fee_account = old_account;
+            accounts.authority_key = old_authority;
         }
 
-        // token A account is delegated
+        // not enough pool tokens
         {
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.token_a_key,
-                    &user_key,
-                    &accounts.authority_key,
-                    &[],
-                    1,
-                )
-                .unwrap(),
-                vec![
-                    &mut accounts.token_a_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
-            assert_eq!(
-                Err(SwapError::InvalidDelegate.into()),
-                accounts.initialize_swap()
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                to_u64(withdraw_amount).unwrap() / 2u64,
+            );
+            assert_eq!(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount / 2,
+                    minimum_token_b_amount / 2,
+                )
             );
-
-            do_process_instruction(
-                revoke(
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.token_a_key,
-                    &accounts.authority_key,
-                    &[],
-                )
-                .unwrap(),
-                vec![&mut accounts.token_a_account, &mut Account::default()],
-            )
-            .unwrap();
         }
 
-        // token B account is delegated
+        // wrong token a / b accounts
         {
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.token_b_key,
-                    &user_key,
-                    &accounts.authority_key,
-                    &[],
-                    1,
-                )
-                .unwrap(),
-                vec![
-                    &mut accounts.token_b_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
-            assert_eq!(
-                Err(SwapError::InvalidDelegate.into()),
-                accounts.initialize_swap()
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
             );
-
-            do_process_instruction(
-                revoke(
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.token_b_key,
-                    &accounts.authority_key,
-                    &[],
+            assert_eq!(
+                Err(TokenError::MintMismatch.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
-                .unwrap(),
-                vec![&mut accounts.token_b_account, &mut Account::default()],
-            )
-            .unwrap();
+            );
         }
 
-        // token A account has close authority
+        // wrong pool token account
         {
-            do_process_instruction(
-                set_authority(
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.token_a_key,
-                    Some(&user_key),
-                    AuthorityType::CloseAccount,
-                    &accounts.authority_key,
-                    &[],
-                )
-                .unwrap(),
-                vec![&mut accounts.token_a_account, &mut Account::default()],
-            )
-            .unwrap();
-            assert_eq!(
-                Err(SwapError::InvalidCloseAuthority.into()),
-                accounts.initialize_swap()
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
             );
-
-            do_process_instruction(
-                set_authority(
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.token_a_key,
-                    None,
-                    AuthorityType::CloseAccount,
-                    &user_key,
-                    &[],
+            let (
+                wrong_token_a_key,
+                mut wrong_token_a_account,
+                _token_b_key,
+                _token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                withdraw_amount.try_into().unwrap(),
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
+            assert_eq!(
+                Err(TokenError::MintMismatch.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &wrong_token_a_key,
+                    &mut wrong_token_a_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
-                .unwrap(),
-                vec![&mut accounts.token_a_account, &mut Account::default()],
-            )
-            .unwrap();
+            );
         }
 
-        // token B account has close authority
+        // wrong pool fee account
         {
-            do_process_instruction(
-                set_authority(
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.token_b_key,
-                    Some(&user_key),
-                    AuthorityType::CloseAccount,
-                    &accounts.authority_key,
-                    &[],
-                )
-                .unwrap(),
-                vec![&mut accounts.token_b_account, &mut Account::default()],
-            )
-            .unwrap();
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                wrong_pool_key,
+                wrong_pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
+            let (
+                _token_a_key,
+                _token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
+            let old_pool_fee_account = accounts.pool_fee_account;
+            let old_pool_fee_key = accounts.pool_fee_key;
+            accounts.pool_fee_account = wrong_pool_account;
+            accounts.pool_fee_key = wrong_pool_key;
             assert_eq!(
-                Err(SwapError::InvalidCloseAuthority.into()),
-                accounts.initialize_swap()
+                Err(SwapError::IncorrectFeeAccount.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                ),
             );
+            accounts.pool_fee_account = old_pool_fee_account;
+            accounts.pool_fee_key = old_pool_fee_key;
+        }
 
-            do_process_instruction(
-                set_authority(
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.token_b_key,
-                    None,
-                    AuthorityType::CloseAccount,
-                    &user_key,
-                    &[],
+        // no approval
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                0,
+                0,
+                withdraw_amount.try_into().unwrap(),
+            );
+            let user_transfer_authority_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(TokenError::OwnerMismatch.into()),
+                do_process_instruction(
+                    withdraw_all_token_types(
+                        &SWAP_PROGRAM_ID,
+                        &TOKEN_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &user_transfer_authority_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &pool_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_a_key,
+                        &token_b_key,
+                        WithdrawAllTokenTypes {
+                            pool_token_amount: withdraw_amount.try_into().unwrap(),
+                            minimum_token_a_amount,
+                            minimum_token_b_amount,
+                        }
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut Account::default(),
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_a_account,
+                        &mut token_b_account,
+                        &mut accounts.pool_fee_account,
+                        &mut Account::default(),
+                    ],
                 )
-                .unwrap(),
-                vec![&mut accounts.token_b_account, &mut Account::default()],
-            )
-            .unwrap();
+            );
         }
 
         // wrong token program id
         {
-            let wrong_program_id = Pubkey::new_unique();
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
+            let wrong_key = Pubkey::new_unique();
             assert_eq!(
                 Err(SwapError::IncorrectTokenProgramId.into()),
                 do_process_instruction(
-                    initialize(
+                    withdraw_all_token_types(
                         &SWAP_PROGRAM_ID,
-                        &wrong_program_id,
+                        &wrong_key,
                         &accounts.swap_key,
                         &accounts.authority_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
+                        &accounts.authority_key,
                         &accounts.pool_mint_key,
                         &accounts.pool_fee_key,
-                        &accounts.pool_token_key,
-                        accounts.nonce,
-                        accounts.fees.clone(),
-                        accounts.swap_curve.clone(),
+                        &pool_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_a_key,
+                        &token_b_key,
+                        WithdrawAllTokenTypes {
+                            pool_token_amount: withdraw_amount.try_into().unwrap(),
+                            minimum_token_a_amount,
+                            minimum_token_b_amount,
+                        },
                     )
                     .unwrap(),
                     vec![
                         &mut accounts.swap_account,
                         &mut Account::default(),
+                        &mut Account::default(),
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
                         &mut accounts.token_a_account,
                         &mut accounts.token_b_account,
-                        &mut accounts.pool_mint_account,
+                        &mut token_a_account,
+                        &mut token_b_account,
                         &mut accounts.pool_fee_account,
-                        &mut accounts.pool_token_account,
                         &mut Account::default(),
                     ],
                 )
             );
         }
 
-        // create swap with same token A and B
+        // wrong swap token accounts
         {
-            let (_token_a_repeat_key, token_a_repeat_account) = mint_token(
-                &TOKEN_PROGRAM_ID,
-                &accounts.token_a_mint_key,
-                &mut accounts.token_a_mint_account,
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
                 &user_key,
-                &accounts.authority_key,
-                10,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
             );
-            let old_account = accounts.token_b_account;
-            accounts.token_b_account = token_a_repeat_account;
+
+            let old_a_key = accounts.token_a_key;
+            let old_a_account = accounts.token_a_account;
+
+            accounts.token_a_key = token_a_key;
+            accounts.token_a_account = token_a_account.clone();
+
+            // wrong swap token a account
             assert_eq!(
-                Err(SwapError::RepeatedMint.into()),
-                accounts.initialize_swap()
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
             );
-            accounts.token_b_account = old_account;
-        }
 
-        // create valid swap
-        accounts.initialize_swap().unwrap();
+            accounts.token_a_key = old_a_key;
+            accounts.token_a_account = old_a_account;
 
-        // create invalid flat swap
-        {
-            let token_b_price = 0;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantPrice,
-                calculator: Box::new(ConstantPriceCurve { token_b_price }),
-            };
-            let mut accounts =
-                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+            let old_b_key = accounts.token_b_key;
+            let old_b_account = accounts.token_b_account;
+
+            accounts.token_b_key = token_b_key;
+            accounts.token_b_account = token_b_account.clone();
+
+            // wrong swap token b account
             assert_eq!(
-                Err(SwapError::InvalidCurve.into()),
-                accounts.initialize_swap()
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
             );
+
+            accounts.token_b_key = old_b_key;
+            accounts.token_b_account = old_b_account;
         }
 
-        // create valid flat swap
+        // wrong mint
         {
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let token_b_price = 10_000;
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantPrice,
-                calculator: Box::new(ConstantPriceCurve { token_b_price }),
-            };
-            let mut accounts =
-                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
-            accounts.initialize_swap().unwrap();
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+            let (pool_mint_key, pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
+            let old_pool_key = accounts.pool_mint_key;
+            let old_pool_account = accounts.pool_mint_account;
+            accounts.pool_mint_key = pool_mint_key;
+            accounts.pool_mint_account = pool_mint_account;
+
+            assert_eq!(
+                Err(SwapError::IncorrectPoolMint.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
+            );
+
+            accounts.pool_mint_key = old_pool_key;
+            accounts.pool_mint_account = old_pool_account;
         }
 
-        // create invalid offset swap
+        // withdrawing 1 pool token fails because it equates to 0 output tokens
         {
-            let token_b_offset = 0;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::Offset,
-                calculator: Box::new(OffsetCurve { token_b_offset }),
-            };
-            let mut accounts =
-                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
             assert_eq!(
-                Err(SwapError::InvalidCurve.into()),
-                accounts.initialize_swap()
+                Err(SwapError::ZeroTradingTokens.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    1,
+                    0,
+                    0,
+                )
             );
         }
 
-        // create valid offset swap
+        // slippage exceeded
         {
-            let token_b_offset = 10;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::Offset,
-                calculator: Box::new(OffsetCurve { token_b_offset }),
-            };
-            let mut accounts =
-                SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
-            accounts.initialize_swap().unwrap();
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+            // minimum A amount out too high
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount * 10,
+                    minimum_token_b_amount,
+                )
+            );
+            // minimum B amount out too high
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount * 10,
+                )
+            );
         }
 
-        // wrong owner key in constraint
+        // invalid input: can't use swap pool tokens as destination
         {
-            let new_key = Pubkey::new_unique();
-            let trade_fee_numerator = 25;
-            let trade_fee_denominator = 10000;
-            let owner_trade_fee_numerator = 5;
-            let owner_trade_fee_denominator = 10000;
-            let host_fee_numerator = 20;
-            let host_fee_denominator = 100;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let curve = ConstantProductCurve {};
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantProduct,
-                calculator: Box::new(curve),
-            };
-            let owner_key = &new_key.to_string();
-            let valid_curve_types = &[CurveType::ConstantProduct];
-            let constraints = Some(SwapConstraints {
-                owner_key,
-                valid_curve_types,
-                fees: &fees,
-            });
-            let mut accounts = SwapAccountInfo::new(
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
                 &user_key,
-                fees.clone(),
-                swap_curve,
-                token_a_amount,
-                token_b_amount,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
             );
+            let swap_token_a_key = accounts.token_a_key;
+            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
             assert_eq!(
-                Err(SwapError::InvalidOwner.into()),
-                do_process_instruction_with_fee_constraints(
-                    initialize(
-                        &SWAP_PROGRAM_ID,
-                        &TOKEN_PROGRAM_ID,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &accounts.pool_token_key,
-                        accounts.nonce,
-                        accounts.fees.clone(),
-                        accounts.swap_curve.clone(),
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut accounts.pool_token_account,
-                        &mut Account::default(),
-                    ],
-                    &constraints,
+                Err(SwapError::InvalidInput.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &swap_token_a_key,
+                    &mut swap_token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
+            );
+            let swap_token_b_key = accounts.token_b_key;
+            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_b_key,
+                    &mut swap_token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
             );
         }
 
-        // wrong fee in constraint
+        // correct withdrawal
         {
-            let trade_fee_numerator = 25;
-            let trade_fee_denominator = 10000;
-            let owner_trade_fee_numerator = 5;
-            let owner_trade_fee_denominator = 10000;
-            let host_fee_numerator = 20;
-            let host_fee_denominator = 100;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let curve = ConstantProductCurve {};
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantProduct,
-                calculator: Box::new(curve),
-            };
-            let owner_key = &user_key.to_string();
-            let valid_curve_types = &[CurveType::ConstantProduct];
-            let constraints = Some(SwapConstraints {
-                owner_key,
-                valid_curve_types,
-                fees: &fees,
-            });
-            let mut bad_fees = fees.clone();
-            bad_fees.trade_fee_numerator = trade_fee_numerator - 1;
-            let mut accounts = SwapAccountInfo::new(
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
                 &user_key,
-                bad_fees,
-                swap_curve,
-                token_a_amount,
-                token_b_amount,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+
+            accounts
+                .withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
+                .unwrap();
+
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+            let swap_token_b =
+                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+            let pool_mint =
+                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+            let withdraw_fee = accounts.fees.owner_withdraw_fee(withdraw_amount).unwrap();
+            let results = accounts
+                .swap_curve
+                .calculator
+                .pool_tokens_to_trading_tokens(
+                    withdraw_amount - withdraw_fee,
+                    pool_mint.supply.try_into().unwrap(),
+                    swap_token_a.amount.try_into().unwrap(),
+                    swap_token_b.amount.try_into().unwrap(),
+                    RoundDirection::Floor,
+                )
+                .unwrap();
+            assert_eq!(
+                swap_token_a.amount,
+                token_a_amount - to_u64(results.token_a_amount).unwrap()
             );
             assert_eq!(
-                Err(SwapError::InvalidFee.into()),
-                do_process_instruction_with_fee_constraints(
-                    initialize(
-                        &SWAP_PROGRAM_ID,
-                        &TOKEN_PROGRAM_ID,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &accounts.pool_token_key,
-                        accounts.nonce,
-                        accounts.fees.clone(),
-                        accounts.swap_curve.clone(),
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut accounts.pool_token_account,
-                        &mut Account::default(),
-                    ],
-                    &constraints,
-                )
+                swap_token_b.amount,
+                token_b_amount - to_u64(results.token_b_amount).unwrap()
+            );
+            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+            assert_eq!(
+                token_a.amount,
+                initial_a + to_u64(results.token_a_amount).unwrap()
+            );
+            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+            assert_eq!(
+                token_b.amount,
+                initial_b + to_u64(results.token_b_amount).unwrap()
+            );
+            let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
+            assert_eq!(
+                pool_account.amount,
+                to_u64(initial_pool - withdraw_amount).unwrap()
+            );
+            let fee_account =
+                spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+            assert_eq!(
+                fee_account.amount,
+                TryInto::<u64>::try_into(withdraw_fee).unwrap()
             );
         }
 
-        // create valid swap with constraints
+        // correct withdrawal from fee account
         {
-            let trade_fee_numerator = 25;
-            let trade_fee_denominator = 10000;
-            let owner_trade_fee_numerator = 5;
-            let owner_trade_fee_denominator = 10000;
-            let host_fee_numerator = 20;
-            let host_fee_denominator = 100;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let curve = ConstantProductCurve {};
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantProduct,
-                calculator: Box::new(curve),
-            };
-            let owner_key = &user_key.to_string();
-            let valid_curve_types = &[CurveType::ConstantProduct];
-            let constraints = Some(SwapConstraints {
-                owner_key,
-                valid_curve_types,
-                fees: &fees,
-            });
-            let mut accounts = SwapAccountInfo::new(
-                &user_key,
-                fees.clone(),
-                swap_curve,
-                token_a_amount,
-                token_b_amount,
-            );
-            do_process_instruction_with_fee_constraints(
-                initialize(
-                    &SWAP_PROGRAM_ID,
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.swap_key,
-                    &accounts.authority_key,
-                    &accounts.token_a_key,
-                    &accounts.token_b_key,
-                    &accounts.pool_mint_key,
-                    &accounts.pool_fee_key,
-                    &accounts.pool_token_key,
-                    accounts.nonce,
-                    accounts.fees,
-                    accounts.swap_curve.clone(),
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                mut _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
+
+            let pool_fee_key = accounts.pool_fee_key;
+            let mut pool_fee_account = accounts.pool_fee_account.clone();
+            let fee_account = spl_token::state::Account::unpack(&pool_fee_account.data).unwrap();
+            let pool_fee_amount = fee_account.amount;
+
+            accounts
+                .withdraw_all_token_types(
+                    &user_key,
+                    &pool_fee_key,
+                    &mut pool_fee_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    pool_fee_amount,
+                    0,
+                    0,
                 )
-                .unwrap(),
-                vec![
-                    &mut accounts.swap_account,
-                    &mut Account::default(),
-                    &mut accounts.token_a_account,
-                    &mut accounts.token_b_account,
-                    &mut accounts.pool_mint_account,
-                    &mut accounts.pool_fee_account,
-                    &mut accounts.pool_token_account,
-                    &mut Account::default(),
-                ],
-                &constraints,
-            )
-            .unwrap();
-        }
+                .unwrap();
 
-        // create again
-        {
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+            let swap_token_b =
+                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+            let pool_mint =
+                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+            let results = accounts
+                .swap_curve
+                .calculator
+                .pool_tokens_to_trading_tokens(
+                    pool_fee_amount.try_into().unwrap(),
+                    pool_mint.supply.try_into().unwrap(),
+                    swap_token_a.amount.try_into().unwrap(),
+                    swap_token_b.amount.try_into().unwrap(),
+                    RoundDirection::Floor,
+                )
+                .unwrap();
+            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
             assert_eq!(
-                Err(SwapError::AlreadyInUse.into()),
-                accounts.initialize_swap()
+                token_a.amount,
+                TryInto::<u64>::try_into(results.token_a_amount).unwrap()
+            );
+            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+            assert_eq!(
+                token_b.amount,
+                TryInto::<u64>::try_into(results.token_b_amount).unwrap()
             );
         }
-        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
-        assert_eq!(swap_state.is_initialized(), true);
-        assert_eq!(swap_state.nonce(), accounts.nonce);
-        assert_eq!(
-            swap_state.swap_curve().curve_type,
-            accounts.swap_curve.curve_type
-        );
-        assert_eq!(*swap_state.token_a_account(), accounts.token_a_key);
-        assert_eq!(*swap_state.token_b_account(), accounts.token_b_key);
-        assert_eq!(*swap_state.pool_mint(), accounts.pool_mint_key);
-        assert_eq!(*swap_state.token_a_mint(), accounts.token_a_mint_key);
-        assert_eq!(*swap_state.token_b_mint(), accounts.token_b_mint_key);
-        assert_eq!(*swap_state.pool_fee_account(), accounts.pool_fee_key);
-        let token_a = spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-        assert_eq!(token_a.amount, token_a_amount);
-        let token_b = spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-        assert_eq!(token_b.amount, token_b_amount);
-        let pool_account =
-            spl_token::state::Account::unpack(&accounts.pool_token_account.data).unwrap();
-        let pool_mint = spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-        assert_eq!(pool_mint.supply, pool_account.amount);
     }
 
     #[test]
-    fn test_deposit() {
+    fn test_deposit_one_exact_in() {
         let user_key = Pubkey::new_unique();
         let depositor_key = Pubkey::new_unique();
         let trade_fee_numerator = 1;
@@ -2861,35 +5797,30 @@ mod tests {
         let mut accounts =
             SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
 
-        // depositing 10% of the current pool amount in token A and B means
-        // that our pool tokens will be worth 1 / 10 of the current pool amount
-        let pool_amount = INITIAL_SWAP_POOL_AMOUNT / 10;
         let deposit_a = token_a_amount / 10;
         let deposit_b = token_b_amount / 10;
+        let pool_amount = to_u64(INITIAL_SWAP_POOL_AMOUNT / 100).unwrap();
 
         // swap not initialized
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             assert_eq!(
                 Err(ProgramError::UninitializedAccount),
-                accounts.deposit_all_token_types(
+                accounts.deposit_single_token_type_exact_amount_in(
                     &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount.try_into().unwrap(),
                     deposit_a,
-                    deposit_b,
+                    pool_amount,
                 )
             );
         }
@@ -2901,8 +5832,8 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
@@ -2912,90 +5843,51 @@ mod tests {
             accounts.swap_account = wrong_swap_account;
             assert_eq!(
                 Err(ProgramError::IncorrectProgramId),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
-                )
-            );
-            accounts.swap_account = old_swap_account;
-        }
-
-        // wrong nonce for authority_key
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let old_authority = accounts.authority_key;
-            let (bad_authority_key, _nonce) = Pubkey::find_program_address(
-                &[&accounts.swap_key.to_bytes()[..]],
-                &TOKEN_PROGRAM_ID,
-            );
-            accounts.authority_key = bad_authority_key;
-            assert_eq!(
-                Err(SwapError::InvalidProgramAddress.into()),
-                accounts.deposit_all_token_types(
+                accounts.deposit_single_token_type_exact_amount_in(
                     &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount.try_into().unwrap(),
                     deposit_a,
-                    deposit_b,
+                    pool_amount,
                 )
             );
-            accounts.authority_key = old_authority;
+            accounts.swap_account = old_swap_account;
         }
 
-        // not enough token A
+        // wrong nonce for authority_key
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &depositor_key,
-                deposit_a / 2,
-                deposit_b,
-                0,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let old_authority = accounts.authority_key;
+            let (bad_authority_key, _nonce) = Pubkey::find_program_address(
+                &[&accounts.swap_key.to_bytes()[..]],
+                &TOKEN_PROGRAM_ID,
             );
+            accounts.authority_key = bad_authority_key;
             assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.deposit_all_token_types(
+                Err(SwapError::InvalidProgramAddress.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
                     &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount.try_into().unwrap(),
                     deposit_a,
-                    deposit_b,
+                    pool_amount,
                 )
             );
+            accounts.authority_key = old_authority;
         }
 
-        // not enough token B
+        // not enough token A / B
         {
             let (
                 token_a_key,
@@ -3007,50 +5899,32 @@ mod tests {
             ) = accounts.setup_token_accounts(
                 &user_key,
                 &depositor_key,
-                deposit_a,
+                deposit_a / 2,
                 deposit_b / 2,
                 0,
             );
             assert_eq!(
                 Err(TokenError::InsufficientFunds.into()),
-                accounts.deposit_all_token_types(
+                accounts.deposit_single_token_type_exact_amount_in(
                     &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount.try_into().unwrap(),
                     deposit_a,
-                    deposit_b,
+                    0,
                 )
             );
-        }
-
-        // wrong swap token accounts
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             assert_eq!(
-                Err(TokenError::MintMismatch.into()),
-                accounts.deposit_all_token_types(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
                     &depositor_key,
                     &token_b_key,
                     &mut token_b_account,
-                    &token_a_key,
-                    &mut token_a_account,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
                     deposit_b,
+                    0,
                 )
             );
         }
@@ -3065,27 +5939,16 @@ mod tests {
                 _pool_key,
                 mut _pool_account,
             ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let (
-                wrong_token_key,
-                mut wrong_token_account,
-                _token_b_key,
-                mut _token_b_account,
-                _pool_key,
-                mut _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             assert_eq!(
                 Err(TokenError::MintMismatch.into()),
-                accounts.deposit_all_token_types(
+                accounts.deposit_single_token_type_exact_amount_in(
                     &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    &wrong_token_key,
-                    &mut wrong_token_account,
-                    pool_amount.try_into().unwrap(),
                     deposit_a,
-                    deposit_b,
+                    pool_amount,
                 )
             );
         }
@@ -3095,8 +5958,8 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
@@ -3104,22 +5967,20 @@ mod tests {
             assert_eq!(
                 Err(TokenError::OwnerMismatch.into()),
                 do_process_instruction(
-                    deposit_all_token_types(
+                    deposit_single_token_type_exact_amount_in(
                         &SWAP_PROGRAM_ID,
                         &TOKEN_PROGRAM_ID,
                         &accounts.swap_key,
                         &accounts.authority_key,
                         &user_transfer_authority_key,
                         &token_a_key,
-                        &token_b_key,
                         &accounts.token_a_key,
                         &accounts.token_b_key,
                         &accounts.pool_mint_key,
                         &pool_key,
-                        DepositAllTokenTypes {
-                            pool_token_amount: pool_amount.try_into().unwrap(),
-                            maximum_token_a_amount: deposit_a,
-                            maximum_token_b_amount: deposit_b,
+                        DepositSingleTokenTypeExactAmountIn {
+                            source_token_amount: deposit_a,
+                            minimum_pool_token_amount: pool_amount,
                         },
                     )
                     .unwrap(),
@@ -3128,7 +5989,6 @@ mod tests {
                         &mut Account::default(),
                         &mut Account::default(),
                         &mut token_a_account,
-                        &mut token_b_account,
                         &mut accounts.token_a_account,
                         &mut accounts.token_b_account,
                         &mut accounts.pool_mint_account,
@@ -3144,8 +6004,8 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
@@ -3153,22 +6013,20 @@ mod tests {
             assert_eq!(
                 Err(SwapError::IncorrectTokenProgramId.into()),
                 do_process_instruction(
-                    deposit_all_token_types(
+                    deposit_single_token_type_exact_amount_in(
                         &SWAP_PROGRAM_ID,
                         &wrong_key,
                         &accounts.swap_key,
                         &accounts.authority_key,
                         &accounts.authority_key,
                         &token_a_key,
-                        &token_b_key,
                         &accounts.token_a_key,
                         &accounts.token_b_key,
                         &accounts.pool_mint_key,
                         &pool_key,
-                        DepositAllTokenTypes {
-                            pool_token_amount: pool_amount.try_into().unwrap(),
-                            maximum_token_a_amount: deposit_a,
-                            maximum_token_b_amount: deposit_b,
+                        DepositSingleTokenTypeExactAmountIn {
+                            source_token_amount: deposit_a,
+                            minimum_pool_token_amount: pool_amount,
                         },
                     )
                     .unwrap(),
@@ -3177,7 +6035,6 @@ mod tests {
                         &mut Account::default(),
                         &mut Account::default(),
                         &mut token_a_account,
-                        &mut token_b_account,
                         &mut accounts.token_a_account,
                         &mut accounts.token_b_account,
                         &mut accounts.pool_mint_account,
@@ -3194,7 +6051,7 @@ mod tests {
                 token_a_key,
                 mut token_a_account,
                 token_b_key,
-                mut token_b_account,
+                token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
@@ -3208,17 +6065,14 @@ mod tests {
             // wrong swap token a account
             assert_eq!(
                 Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.deposit_all_token_types(
+                accounts.deposit_single_token_type_exact_amount_in(
                     &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount.try_into().unwrap(),
                     deposit_a,
-                    deposit_b,
+                    pool_amount,
                 )
             );
 
@@ -3229,22 +6083,19 @@ mod tests {
             let old_b_account = accounts.token_b_account;
 
             accounts.token_b_key = token_b_key;
-            accounts.token_b_account = token_b_account.clone();
+            accounts.token_b_account = token_b_account;
 
             // wrong swap token b account
             assert_eq!(
                 Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.deposit_all_token_types(
+                accounts.deposit_single_token_type_exact_amount_in(
                     &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount.try_into().unwrap(),
                     deposit_a,
-                    deposit_b,
+                    pool_amount,
                 )
             );
 
@@ -3257,8 +6108,8 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
@@ -3271,17 +6122,14 @@ mod tests {
 
             assert_eq!(
                 Err(SwapError::IncorrectPoolMint.into()),
-                accounts.deposit_all_token_types(
+                accounts.deposit_single_token_type_exact_amount_in(
                     &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount.try_into().unwrap(),
                     deposit_a,
-                    deposit_b,
+                    pool_amount,
                 )
             );
 
@@ -3289,33 +6137,6 @@ mod tests {
             accounts.pool_mint_account = old_pool_account;
         }
 
-        // deposit 1 pool token fails beacuse it equates to 0 swap tokens
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            assert_eq!(
-                Err(SwapError::ZeroTradingTokens.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    1,
-                    deposit_a,
-                    deposit_b,
-                )
-            );
-        }
-
         // slippage exceeded
         {
             let (
@@ -3326,36 +6147,30 @@ mod tests {
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            // maximum A amount in too low
+            // minimum pool amount too high
             assert_eq!(
                 Err(SwapError::ExceededSlippage.into()),
-                accounts.deposit_all_token_types(
+                accounts.deposit_single_token_type_exact_amount_in(
                     &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount.try_into().unwrap(),
                     deposit_a / 10,
-                    deposit_b,
+                    pool_amount,
                 )
             );
-            // maximum B amount in too low
+            // minimum pool amount too high
             assert_eq!(
                 Err(SwapError::ExceededSlippage.into()),
-                accounts.deposit_all_token_types(
+                accounts.deposit_single_token_type_exact_amount_in(
                     &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
                     deposit_b / 10,
+                    pool_amount,
                 )
             );
         }
@@ -3377,17 +6192,26 @@ mod tests {
             let authority_key = accounts.authority_key;
             assert_eq!(
                 Err(SwapError::InvalidInput.into()),
-                accounts.deposit_all_token_types(
+                accounts.deposit_single_token_type_exact_amount_in(
                     &authority_key,
                     &swap_token_a_key,
                     &mut swap_token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
+                )
+            );
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &authority_key,
                     &swap_token_b_key,
                     &mut swap_token_b_account,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
                     deposit_b,
+                    pool_amount,
                 )
             );
         }
@@ -3403,30 +6227,42 @@ mod tests {
                 mut pool_account,
             ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             accounts
-                .deposit_all_token_types(
+                .deposit_single_token_type_exact_amount_in(
                     &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount.try_into().unwrap(),
                     deposit_a,
-                    deposit_b,
+                    pool_amount,
                 )
                 .unwrap();
 
             let swap_token_a =
                 spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
             assert_eq!(swap_token_a.amount, deposit_a + token_a_amount);
+
+            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+            assert_eq!(token_a.amount, 0);
+
+            accounts
+                .deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_b,
+                    pool_amount,
+                )
+                .unwrap();
             let swap_token_b =
                 spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
             assert_eq!(swap_token_b.amount, deposit_b + token_b_amount);
-            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-            assert_eq!(token_a.amount, 0);
+
             let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
             assert_eq!(token_b.amount, 0);
+
             let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
             let swap_pool_account =
                 spl_token::state::Account::unpack(&accounts.pool_token_account.data).unwrap();
@@ -3440,7 +6276,7 @@ mod tests {
     }
 
     #[test]
-    fn test_withdraw() {
+    fn test_withdraw_one_exact_out() {
         let user_key = Pubkey::new_unique();
         let trade_fee_numerator = 1;
         let trade_fee_denominator = 2;
@@ -3462,8 +6298,8 @@ mod tests {
             host_fee_denominator,
         };
 
-        let token_a_amount = 1000;
-        let token_b_amount = 2000;
+        let token_a_amount = 100_000;
+        let token_b_amount = 200_000;
         let curve_type = CurveType::ConstantProduct;
         let swap_curve = SwapCurve {
             curve_type,
@@ -3474,9 +6310,9 @@ mod tests {
         let initial_a = token_a_amount / 10;
         let initial_b = token_b_amount / 10;
         let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
-        let withdraw_amount = initial_pool / 4;
-        let minimum_token_a_amount = initial_a / 40;
-        let minimum_token_b_amount = initial_b / 40;
+        let maximum_pool_token_amount = to_u64(initial_pool / 4).unwrap();
+        let destination_a_amount = initial_a / 40;
+        let destination_b_amount = initial_b / 40;
 
         let mut accounts =
             SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
@@ -3486,24 +6322,21 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
             assert_eq!(
                 Err(ProgramError::UninitializedAccount),
-                accounts.withdraw_all_token_types(
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
                 )
             );
         }
@@ -3515,8 +6348,8 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
@@ -3526,17 +6359,14 @@ mod tests {
             accounts.swap_account = wrong_swap_account;
             assert_eq!(
                 Err(ProgramError::IncorrectProgramId),
-                accounts.withdraw_all_token_types(
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
                 )
             );
             accounts.swap_account = old_swap_account;
@@ -3545,8 +6375,8 @@ mod tests {
         // wrong nonce for authority_key
         {
             let (
-                token_a_key,
-                mut token_a_account,
+                _token_a_key,
+                _token_a_account,
                 token_b_key,
                 mut token_b_account,
                 pool_key,
@@ -3560,17 +6390,14 @@ mod tests {
             accounts.authority_key = bad_authority_key;
             assert_eq!(
                 Err(SwapError::InvalidProgramAddress.into()),
-                accounts.withdraw_all_token_types(
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
                 )
             );
             accounts.authority_key = old_authority;
@@ -3579,8 +6406,8 @@ mod tests {
         // not enough pool tokens
         {
             let (
-                token_a_key,
-                mut token_a_account,
+                _token_a_key,
+                _token_a_account,
                 token_b_key,
                 mut token_b_account,
                 pool_key,
@@ -3590,54 +6417,18 @@ mod tests {
                 &withdrawer_key,
                 initial_a,
                 initial_b,
-                to_u64(withdraw_amount).unwrap() / 2u64,
+                maximum_pool_token_amount / 1000,
             );
             assert_eq!(
                 Err(TokenError::InsufficientFunds.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount / 2,
-                    minimum_token_b_amount / 2,
-                )
-            );
-        }
-
-        // wrong token a / b accounts
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                withdraw_amount.try_into().unwrap(),
-            );
-            assert_eq!(
-                Err(TokenError::MintMismatch.into()),
-                accounts.withdraw_all_token_types(
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
                     &token_b_key,
                     &mut token_b_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
                 )
             );
         }
@@ -3654,37 +6445,20 @@ mod tests {
             ) = accounts.setup_token_accounts(
                 &user_key,
                 &withdrawer_key,
-                initial_a,
-                initial_b,
-                withdraw_amount.try_into().unwrap(),
-            );
-            let (
-                wrong_token_a_key,
-                mut wrong_token_a_account,
-                _token_b_key,
-                _token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                withdraw_amount.try_into().unwrap(),
+                maximum_pool_token_amount,
                 initial_b,
-                withdraw_amount.try_into().unwrap(),
+                maximum_pool_token_amount,
             );
             assert_eq!(
                 Err(TokenError::MintMismatch.into()),
-                accounts.withdraw_all_token_types(
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
-                    &wrong_token_a_key,
-                    &mut wrong_token_a_account,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
                 )
             );
         }
@@ -3694,8 +6468,8 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 wrong_pool_key,
                 wrong_pool_account,
             ) = accounts.setup_token_accounts(
@@ -3703,7 +6477,7 @@ mod tests {
                 &withdrawer_key,
                 initial_a,
                 initial_b,
-                withdraw_amount.try_into().unwrap(),
+                maximum_pool_token_amount,
             );
             let (
                 _token_a_key,
@@ -3717,7 +6491,7 @@ mod tests {
                 &withdrawer_key,
                 initial_a,
                 initial_b,
-                withdraw_amount.try_into().unwrap(),
+                maximum_pool_token_amount,
             );
             let old_pool_fee_account = accounts.pool_fee_account;
             let old_pool_fee_key = accounts.pool_fee_key;
@@ -3725,18 +6499,15 @@ mod tests {
             accounts.pool_fee_key = wrong_pool_key;
             assert_eq!(
                 Err(SwapError::IncorrectFeeAccount.into()),
-                accounts.withdraw_all_token_types(
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                ),
+                    destination_a_amount,
+                    maximum_pool_token_amount,
+                )
             );
             accounts.pool_fee_account = old_pool_fee_account;
             accounts.pool_fee_key = old_pool_fee_key;
@@ -3747,8 +6518,8 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(
@@ -3756,13 +6527,13 @@ mod tests {
                 &withdrawer_key,
                 0,
                 0,
-                withdraw_amount.try_into().unwrap(),
+                maximum_pool_token_amount,
             );
             let user_transfer_authority_key = Pubkey::new_unique();
             assert_eq!(
                 Err(TokenError::OwnerMismatch.into()),
                 do_process_instruction(
-                    withdraw_all_token_types(
+                    withdraw_single_token_type_exact_amount_out(
                         &SWAP_PROGRAM_ID,
                         &TOKEN_PROGRAM_ID,
                         &accounts.swap_key,
@@ -3774,11 +6545,9 @@ mod tests {
                         &accounts.token_a_key,
                         &accounts.token_b_key,
                         &token_a_key,
-                        &token_b_key,
-                        WithdrawAllTokenTypes {
-                            pool_token_amount: withdraw_amount.try_into().unwrap(),
-                            minimum_token_a_amount,
-                            minimum_token_b_amount,
+                        WithdrawSingleTokenTypeExactAmountOut {
+                            destination_token_amount: destination_a_amount,
+                            maximum_pool_token_amount,
                         }
                     )
                     .unwrap(),
@@ -3791,7 +6560,6 @@ mod tests {
                         &mut accounts.token_a_account,
                         &mut accounts.token_b_account,
                         &mut token_a_account,
-                        &mut token_b_account,
                         &mut accounts.pool_fee_account,
                         &mut Account::default(),
                     ],
@@ -3804,8 +6572,8 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(
@@ -3813,13 +6581,13 @@ mod tests {
                 &withdrawer_key,
                 initial_a,
                 initial_b,
-                withdraw_amount.try_into().unwrap(),
+                maximum_pool_token_amount,
             );
             let wrong_key = Pubkey::new_unique();
             assert_eq!(
                 Err(SwapError::IncorrectTokenProgramId.into()),
                 do_process_instruction(
-                    withdraw_all_token_types(
+                    withdraw_single_token_type_exact_amount_out(
                         &SWAP_PROGRAM_ID,
                         &wrong_key,
                         &accounts.swap_key,
@@ -3831,12 +6599,10 @@ mod tests {
                         &accounts.token_a_key,
                         &accounts.token_b_key,
                         &token_a_key,
-                        &token_b_key,
-                        WithdrawAllTokenTypes {
-                            pool_token_amount: withdraw_amount.try_into().unwrap(),
-                            minimum_token_a_amount,
-                            minimum_token_b_amount,
-                        },
+                        WithdrawSingleTokenTypeExactAmountOut {
+                            destination_token_amount: destination_a_amount,
+                            maximum_pool_token_amount,
+                        }
                     )
                     .unwrap(),
                     vec![
@@ -3848,7 +6614,6 @@ mod tests {
                         &mut accounts.token_a_account,
                         &mut accounts.token_b_account,
                         &mut token_a_account,
-                        &mut token_b_account,
                         &mut accounts.pool_fee_account,
                         &mut Account::default(),
                     ],
@@ -3882,17 +6647,14 @@ mod tests {
             // wrong swap token a account
             assert_eq!(
                 Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.withdraw_all_token_types(
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
                 )
             );
 
@@ -3908,17 +6670,14 @@ mod tests {
             // wrong swap token b account
             assert_eq!(
                 Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.withdraw_all_token_types(
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
                 )
             );
 
@@ -3931,8 +6690,8 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(
@@ -3951,17 +6710,14 @@ mod tests {
 
             assert_eq!(
                 Err(SwapError::IncorrectPoolMint.into()),
-                accounts.withdraw_all_token_types(
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
                 )
             );
 
@@ -3969,7 +6725,7 @@ mod tests {
             accounts.pool_mint_account = old_pool_account;
         }
 
-        // withdrawing 1 pool token fails because it equates to 0 output tokens
+        // slippage exceeded
         {
             let (
                 token_a_key,
@@ -3983,32 +6739,43 @@ mod tests {
                 &withdrawer_key,
                 initial_a,
                 initial_b,
-                initial_pool.try_into().unwrap(),
+                maximum_pool_token_amount,
             );
+
+            // maximum pool token amount too low
             assert_eq!(
-                Err(SwapError::ZeroTradingTokens.into()),
-                accounts.withdraw_all_token_types(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
+                    destination_a_amount,
+                    maximum_pool_token_amount / 1000,
+                )
+            );
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_b_key,
                     &mut token_b_account,
-                    1,
-                    0,
-                    0,
+                    destination_b_amount,
+                    maximum_pool_token_amount / 1000,
                 )
             );
         }
 
-        // slippage exceeded
+        // invalid input: can't use swap pool tokens as destination
         {
             let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_a_key,
+                _token_a_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(
@@ -4016,49 +6783,45 @@ mod tests {
                 &withdrawer_key,
                 initial_a,
                 initial_b,
-                initial_pool.try_into().unwrap(),
+                maximum_pool_token_amount,
             );
-            // minimum A amount out too high
+            let swap_token_a_key = accounts.token_a_key;
+            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
             assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.withdraw_all_token_types(
+                Err(SwapError::InvalidInput.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount * 10,
-                    minimum_token_b_amount,
+                    &swap_token_a_key,
+                    &mut swap_token_a_account,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
                 )
             );
-            // minimum B amount out too high
+            let swap_token_b_key = accounts.token_b_key;
+            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
             assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount * 10,
+                Err(SwapError::InvalidInput.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &swap_token_b_key,
+                    &mut swap_token_b_account,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
                 )
             );
         }
 
-        // invalid input: can't use swap pool tokens as destination
+        // correct withdrawal
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                token_b_key,
-                mut token_b_account,
+                _token_b_key,
+                _token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(
@@ -4068,197 +6831,398 @@ mod tests {
                 initial_b,
                 initial_pool.try_into().unwrap(),
             );
-            let swap_token_a_key = accounts.token_a_key;
-            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &swap_token_a_key,
-                    &mut swap_token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+            let swap_token_b =
+                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+            let pool_mint =
+                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+
+            let pool_token_amount = accounts
+                .swap_curve
+                .trading_tokens_to_pool_tokens(
+                    destination_a_amount.try_into().unwrap(),
+                    (swap_token_a.amount - destination_a_amount)
+                        .try_into()
+                        .unwrap(),
+                    swap_token_b.amount.try_into().unwrap(),
+                    pool_mint.supply.try_into().unwrap(),
+                    TradeDirection::AtoB,
+                    RoundDirection::Ceiling,
+                    &accounts.fees,
                 )
-            );
-            let swap_token_b_key = accounts.token_b_key;
-            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.withdraw_all_token_types(
+                .unwrap();
+            let withdraw_fee = accounts.fees.owner_withdraw_fee(pool_token_amount).unwrap();
+
+            accounts
+                .withdraw_single_token_type_exact_amount_out(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
-                    &swap_token_b_key,
-                    &mut swap_token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
                 )
+                .unwrap();
+
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+
+            assert_eq!(swap_token_a.amount, token_a_amount - destination_a_amount);
+            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+            assert_eq!(token_a.amount, initial_a + destination_a_amount);
+
+            let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
+            assert_eq!(
+                pool_account.amount,
+                to_u64(initial_pool - pool_token_amount - withdraw_fee).unwrap()
             );
+            let fee_account =
+                spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+            assert_eq!(fee_account.amount, to_u64(withdraw_fee).unwrap());
+        }
+
+        // correct withdrawal from fee account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+
+            let fee_a_amount = 2;
+            let pool_fee_key = accounts.pool_fee_key;
+            let mut pool_fee_account = accounts.pool_fee_account.clone();
+            let fee_account = spl_token::state::Account::unpack(&pool_fee_account.data).unwrap();
+            let pool_fee_amount = fee_account.amount;
+
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+
+            let token_a_amount = swap_token_a.amount;
+            accounts
+                .withdraw_single_token_type_exact_amount_out(
+                    &user_key,
+                    &pool_fee_key,
+                    &mut pool_fee_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    fee_a_amount,
+                    pool_fee_amount,
+                )
+                .unwrap();
+
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+
+            assert_eq!(swap_token_a.amount, token_a_amount - fee_a_amount);
+            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+            assert_eq!(token_a.amount, initial_a + fee_a_amount);
         }
+    }
+
+    fn check_valid_swap_curve(
+        fees: Fees,
+        curve_type: CurveType,
+        calculator: Box<dyn CurveCalculator>,
+        token_a_amount: u64,
+        token_b_amount: u64,
+    ) {
+        let user_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
+
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator,
+        };
+
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            fees.clone(),
+            swap_curve.clone(),
+            token_a_amount,
+            token_b_amount,
+        );
+        let initial_a = token_a_amount / 5;
+        let initial_b = token_b_amount / 5;
+        accounts.initialize_swap().unwrap();
+
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            _pool_key,
+            _pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+        // swap one way
+        let a_to_b_amount = initial_a / 10;
+        let minimum_token_b_amount = 0;
+        let pool_mint = spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+        let initial_supply = pool_mint.supply;
+        accounts
+            .swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+            .unwrap();
+
+        let results = swap_curve
+            .swap(
+                a_to_b_amount.try_into().unwrap(),
+                token_a_amount.try_into().unwrap(),
+                token_b_amount.try_into().unwrap(),
+                TradeDirection::AtoB,
+                &fees,
+            )
+            .unwrap();
+
+        let swap_token_a =
+            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+        let token_a_amount = swap_token_a.amount;
+        assert_eq!(
+            token_a_amount,
+            TryInto::<u64>::try_into(results.new_swap_source_amount).unwrap()
+        );
+        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+        assert_eq!(token_a.amount, initial_a - a_to_b_amount);
+
+        let swap_token_b =
+            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+        let token_b_amount = swap_token_b.amount;
+        assert_eq!(
+            token_b_amount,
+            TryInto::<u64>::try_into(results.new_swap_destination_amount).unwrap()
+        );
+        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+        assert_eq!(
+            token_b.amount,
+            initial_b + to_u64(results.destination_amount_swapped).unwrap()
+        );
+
+        let first_fee = swap_curve
+            .trading_tokens_to_pool_tokens(
+                results.owner_fee,
+                token_a_amount.try_into().unwrap(),
+                token_b_amount.try_into().unwrap(),
+                initial_supply.try_into().unwrap(),
+                TradeDirection::AtoB,
+                RoundDirection::Ceiling,
+                &fees,
+            )
+            .unwrap();
+        let fee_account =
+            spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+        assert_eq!(
+            fee_account.amount,
+            TryInto::<u64>::try_into(first_fee).unwrap()
+        );
+
+        let first_swap_amount = results.destination_amount_swapped;
+
+        // swap the other way
+        let pool_mint = spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+        let initial_supply = pool_mint.supply;
+
+        let b_to_a_amount = initial_b / 10;
+        let minimum_a_amount = 0;
+        accounts
+            .swap(
+                &swapper_key,
+                &token_b_key,
+                &mut token_b_account,
+                &swap_token_b_key,
+                &swap_token_a_key,
+                &token_a_key,
+                &mut token_a_account,
+                b_to_a_amount,
+                minimum_a_amount,
+            )
+            .unwrap();
+
+        let results = swap_curve
+            .swap(
+                b_to_a_amount.try_into().unwrap(),
+                token_b_amount.try_into().unwrap(),
+                token_a_amount.try_into().unwrap(),
+                TradeDirection::BtoA,
+                &fees,
+            )
+            .unwrap();
 
-        // correct withdrawal
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
+        let swap_token_a =
+            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+        let token_a_amount = swap_token_a.amount;
+        assert_eq!(
+            token_a_amount,
+            TryInto::<u64>::try_into(results.new_swap_destination_amount).unwrap()
+        );
+        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+        assert_eq!(
+            token_a.amount,
+            initial_a - a_to_b_amount + to_u64(results.destination_amount_swapped).unwrap()
+        );
 
-            accounts
-                .withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                )
-                .unwrap();
+        let swap_token_b =
+            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+        let token_b_amount = swap_token_b.amount;
+        assert_eq!(
+            token_b_amount,
+            TryInto::<u64>::try_into(results.new_swap_source_amount).unwrap()
+        );
+        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+        assert_eq!(
+            token_b.amount,
+            initial_b + to_u64(first_swap_amount).unwrap()
+                - to_u64(results.source_amount_swapped).unwrap()
+        );
 
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-            let swap_token_b =
-                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-            let pool_mint =
-                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-            let withdraw_fee = accounts.fees.owner_withdraw_fee(withdraw_amount).unwrap();
-            let results = accounts
-                .swap_curve
-                .calculator
-                .pool_tokens_to_trading_tokens(
-                    withdraw_amount - withdraw_fee,
-                    pool_mint.supply.try_into().unwrap(),
-                    swap_token_a.amount.try_into().unwrap(),
-                    swap_token_b.amount.try_into().unwrap(),
-                    RoundDirection::Floor,
-                )
-                .unwrap();
-            assert_eq!(
-                swap_token_a.amount,
-                token_a_amount - to_u64(results.token_a_amount).unwrap()
-            );
-            assert_eq!(
-                swap_token_b.amount,
-                token_b_amount - to_u64(results.token_b_amount).unwrap()
-            );
-            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-            assert_eq!(
-                token_a.amount,
-                initial_a + to_u64(results.token_a_amount).unwrap()
-            );
-            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
-            assert_eq!(
-                token_b.amount,
-                initial_b + to_u64(results.token_b_amount).unwrap()
-            );
-            let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
-            assert_eq!(
-                pool_account.amount,
-                to_u64(initial_pool - withdraw_amount).unwrap()
-            );
-            let fee_account =
-                spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
-            assert_eq!(
-                fee_account.amount,
-                TryInto::<u64>::try_into(withdraw_fee).unwrap()
-            );
-        }
+        let second_fee = swap_curve
+            .trading_tokens_to_pool_tokens(
+                results.owner_fee,
+                token_a_amount.try_into().unwrap(),
+                token_b_amount.try_into().unwrap(),
+                initial_supply.try_into().unwrap(),
+                TradeDirection::BtoA,
+                RoundDirection::Ceiling,
+                &fees,
+            )
+            .unwrap();
+        let fee_account =
+            spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+        assert_eq!(fee_account.amount, to_u64(first_fee + second_fee).unwrap());
+    }
 
-        // correct withdrawal from fee account
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                mut _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
+    #[test]
+    fn test_valid_swap_curves_all_fees() {
+        // All fees
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 10;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 30;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 30;
+        let host_fee_numerator = 20;
+        let host_fee_denominator = 100;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
 
-            let pool_fee_key = accounts.pool_fee_key;
-            let mut pool_fee_account = accounts.pool_fee_account.clone();
-            let fee_account = spl_token::state::Account::unpack(&pool_fee_account.data).unwrap();
-            let pool_fee_amount = fee_account.amount;
+        let token_a_amount = 10_000_000_000;
+        let token_b_amount = 50_000_000_000;
 
-            accounts
-                .withdraw_all_token_types(
-                    &user_key,
-                    &pool_fee_key,
-                    &mut pool_fee_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    pool_fee_amount,
-                    0,
-                    0,
-                )
-                .unwrap();
+        check_valid_swap_curve(
+            fees.clone(),
+            CurveType::ConstantProduct,
+            Box::new(ConstantProductCurve {}),
+            token_a_amount,
+            token_b_amount,
+        );
+        let token_b_price = 1;
+        check_valid_swap_curve(
+            fees.clone(),
+            CurveType::ConstantPrice,
+            Box::new(ConstantPriceCurve { token_b_price }),
+            token_a_amount,
+            token_b_amount,
+        );
+        let token_b_offset = 10_000_000_000;
+        check_valid_swap_curve(
+            fees,
+            CurveType::Offset,
+            Box::new(OffsetCurve { token_b_offset }),
+            token_a_amount,
+            token_b_amount,
+        );
+    }
 
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-            let swap_token_b =
-                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-            let pool_mint =
-                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-            let results = accounts
-                .swap_curve
-                .calculator
-                .pool_tokens_to_trading_tokens(
-                    pool_fee_amount.try_into().unwrap(),
-                    pool_mint.supply.try_into().unwrap(),
-                    swap_token_a.amount.try_into().unwrap(),
-                    swap_token_b.amount.try_into().unwrap(),
-                    RoundDirection::Floor,
-                )
-                .unwrap();
-            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-            assert_eq!(
-                token_a.amount,
-                TryInto::<u64>::try_into(results.token_a_amount).unwrap()
-            );
-            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
-            assert_eq!(
-                token_b.amount,
-                TryInto::<u64>::try_into(results.token_b_amount).unwrap()
-            );
-        }
+    #[test]
+    fn test_valid_swap_curves_trade_fee_only() {
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 10;
+        let owner_trade_fee_numerator = 0;
+        let owner_trade_fee_denominator = 0;
+        let owner_withdraw_fee_numerator = 0;
+        let owner_withdraw_fee_denominator = 0;
+        let host_fee_numerator = 0;
+        let host_fee_denominator = 0;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let token_a_amount = 10_000_000_000;
+        let token_b_amount = 50_000_000_000;
+
+        check_valid_swap_curve(
+            fees.clone(),
+            CurveType::ConstantProduct,
+            Box::new(ConstantProductCurve {}),
+            token_a_amount,
+            token_b_amount,
+        );
+        let token_b_price = 10_000;
+        check_valid_swap_curve(
+            fees.clone(),
+            CurveType::ConstantPrice,
+            Box::new(ConstantPriceCurve { token_b_price }),
+            token_a_amount,
+            token_b_amount / token_b_price,
+        );
+        let token_b_offset = 1;
+        check_valid_swap_curve(
+            fees,
+            CurveType::Offset,
+            Box::new(OffsetCurve { token_b_offset }),
+            token_a_amount,
+            token_b_amount,
+        );
     }
 
     #[test]
-    fn test_deposit_one_exact_in() {
-        let user_key = Pubkey::new_unique();
-        let depositor_key = Pubkey::new_unique();
+    fn test_valid_swap_with_fee_constraints() {
+        let owner_key = Pubkey::new_unique();
+
         let trade_fee_numerator = 1;
-        let trade_fee_denominator = 2;
+        let trade_fee_denominator = 10;
         let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 10;
+        let owner_trade_fee_denominator = 30;
         let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 5;
-        let host_fee_numerator = 20;
+        let owner_withdraw_fee_denominator = 30;
+        let host_fee_numerator = 10;
         let host_fee_denominator = 100;
 
+        let token_a_amount = 1_000_000;
+        let token_b_amount = 5_000_000;
+
         let fees = Fees {
             trade_fee_numerator,
             trade_fee_denominator,
@@ -4270,150 +7234,198 @@ mod tests {
             host_fee_denominator,
         };
 
-        let token_a_amount = 1000;
-        let token_b_amount = 9000;
-        let curve_type = CurveType::ConstantProduct;
+        let curve = ConstantProductCurve {};
         let swap_curve = SwapCurve {
-            curve_type,
-            calculator: Box::new(ConstantProductCurve {}),
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(curve),
         };
 
-        let mut accounts =
-            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+        let owner_key_str = &owner_key.to_string();
+        let valid_curve_types = &[CurveType::ConstantProduct];
+        let constraints = Some(SwapConstraints {
+            owner_key: owner_key_str,
+            valid_curve_types,
+            fees: &fees,
+        });
+        let mut accounts = SwapAccountInfo::new(
+            &owner_key,
+            fees.clone(),
+            swap_curve,
+            token_a_amount,
+            token_b_amount,
+        );
 
-        let deposit_a = token_a_amount / 10;
-        let deposit_b = token_b_amount / 10;
-        let pool_amount = to_u64(INITIAL_SWAP_POOL_AMOUNT / 100).unwrap();
+        // initialize swap
+        do_process_instruction_with_fee_constraints(
+            initialize(
+                &SWAP_PROGRAM_ID,
+                &TOKEN_PROGRAM_ID,
+                &accounts.swap_key,
+                &accounts.authority_key,
+                &accounts.token_a_key,
+                &accounts.token_b_key,
+                &accounts.pool_mint_key,
+                &accounts.pool_fee_key,
+                &accounts.pool_token_key,
+                accounts.nonce,
+                accounts.fees.clone(),
+                accounts.swap_curve.clone(),
+                None,
+            )
+            .unwrap(),
+            vec![
+                &mut accounts.swap_account,
+                &mut Account::default(),
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
+                &mut accounts.pool_mint_account,
+                &mut accounts.pool_fee_account,
+                &mut accounts.pool_token_account,
+                &mut Account::default(),
+            ],
+            &constraints,
+        )
+        .unwrap();
 
-        // swap not initialized
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            assert_eq!(
-                Err(ProgramError::UninitializedAccount),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-            );
-        }
+        let authority_key = accounts.authority_key;
 
-        accounts.initialize_swap().unwrap();
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(
+            &owner_key,
+            &authority_key,
+            token_a_amount,
+            token_b_amount,
+            0,
+        );
 
-        // wrong owner for swap account
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let old_swap_account = accounts.swap_account;
-            let mut wrong_swap_account = old_swap_account.clone();
-            wrong_swap_account.owner = TOKEN_PROGRAM_ID;
-            accounts.swap_account = wrong_swap_account;
-            assert_eq!(
-                Err(ProgramError::IncorrectProgramId),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-            );
-            accounts.swap_account = old_swap_account;
-        }
+        let amount_in = token_a_amount / 2;
+        let minimum_amount_out = 0;
 
-        // wrong nonce for authority_key
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let old_authority = accounts.authority_key;
-            let (bad_authority_key, _nonce) = Pubkey::find_program_address(
-                &[&accounts.swap_key.to_bytes()[..]],
+        // perform the swap
+        do_process_instruction_with_fee_constraints(
+            swap(
+                &SWAP_PROGRAM_ID,
                 &TOKEN_PROGRAM_ID,
-            );
-            accounts.authority_key = bad_authority_key;
-            assert_eq!(
-                Err(SwapError::InvalidProgramAddress.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-            );
-            accounts.authority_key = old_authority;
-        }
+                &accounts.swap_key,
+                &accounts.authority_key,
+                &accounts.authority_key,
+                &token_a_key,
+                &accounts.token_a_key,
+                &accounts.token_b_key,
+                &token_b_key,
+                &accounts.pool_mint_key,
+                &accounts.pool_fee_key,
+                Some(&pool_key),
+                Swap {
+                    amount_in,
+                    minimum_amount_out,
+                },
+            )
+            .unwrap(),
+            vec![
+                &mut accounts.swap_account,
+                &mut Account::default(),
+                &mut Account::default(),
+                &mut token_a_account,
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
+                &mut token_b_account,
+                &mut accounts.pool_mint_account,
+                &mut accounts.pool_fee_account,
+                &mut Account::default(),
+                &mut pool_account,
+            ],
+            &constraints,
+        )
+        .unwrap();
+
+        // check that fees were taken in the host fee account
+        let host_fee_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
+        let owner_fee_account =
+            spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+        let total_fee = owner_fee_account.amount * host_fee_denominator
+            / (host_fee_denominator - host_fee_numerator);
+        assert_eq!(
+            total_fee,
+            host_fee_account.amount + owner_fee_account.amount
+        );
+    }
+
+    #[test]
+    fn test_invalid_swap() {
+        let user_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 4;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 10;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 5;
+        let host_fee_numerator = 9;
+        let host_fee_denominator = 100;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
 
-        // not enough token A / B
+        let token_a_amount = 1000;
+        let token_b_amount = 5000;
+        let curve_type = CurveType::ConstantProduct;
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+
+        let initial_a = token_a_amount / 5;
+        let initial_b = token_b_amount / 5;
+        let minimum_token_b_amount = initial_b / 2;
+
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+
+        // swap not initialized
         {
             let (
                 token_a_key,
                 mut token_a_account,
                 token_b_key,
                 mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &depositor_key,
-                deposit_a / 2,
-                deposit_b / 2,
-                0,
-            );
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
+                Err(ProgramError::UninitializedAccount),
+                accounts.swap(
+                    &swapper_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    0,
-                )
-            );
-            assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
                     &token_b_key,
                     &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_b,
-                    0,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
             );
         }
 
-        // wrong pool token account
+        accounts.initialize_swap().unwrap();
+
+        // wrong swap account program id
         {
             let (
                 token_a_key,
@@ -4421,66 +7433,60 @@ mod tests {
                 token_b_key,
                 mut token_b_account,
                 _pool_key,
-                mut _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let old_swap_account = accounts.swap_account;
+            let mut wrong_swap_account = old_swap_account.clone();
+            wrong_swap_account.owner = TOKEN_PROGRAM_ID;
+            accounts.swap_account = wrong_swap_account;
             assert_eq!(
-                Err(TokenError::MintMismatch.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
+                Err(ProgramError::IncorrectProgramId),
+                accounts.swap(
+                    &swapper_key,
                     &token_a_key,
                     &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
                     &token_b_key,
                     &mut token_b_account,
-                    deposit_a,
-                    pool_amount,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
             );
+            accounts.swap_account = old_swap_account;
         }
 
-        // no approval
+        // wrong nonce
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let user_transfer_authority_key = Pubkey::new_unique();
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let old_authority = accounts.authority_key;
+            let (bad_authority_key, _nonce) = Pubkey::find_program_address(
+                &[&accounts.swap_key.to_bytes()[..]],
+                &TOKEN_PROGRAM_ID,
+            );
+            accounts.authority_key = bad_authority_key;
             assert_eq!(
-                Err(TokenError::OwnerMismatch.into()),
-                do_process_instruction(
-                    deposit_single_token_type_exact_amount_in(
-                        &SWAP_PROGRAM_ID,
-                        &TOKEN_PROGRAM_ID,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &user_transfer_authority_key,
-                        &token_a_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &accounts.pool_mint_key,
-                        &pool_key,
-                        DepositSingleTokenTypeExactAmountIn {
-                            source_token_amount: deposit_a,
-                            minimum_pool_token_amount: pool_amount,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut Account::default(),
-                        &mut token_a_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
-                        &mut Account::default(),
-                    ],
+                Err(SwapError::InvalidProgramAddress.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
             );
+            accounts.authority_key = old_authority;
         }
 
         // wrong token program id
@@ -4488,29 +7494,31 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let wrong_key = Pubkey::new_unique();
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let wrong_program_id = Pubkey::new_unique();
             assert_eq!(
                 Err(SwapError::IncorrectTokenProgramId.into()),
                 do_process_instruction(
-                    deposit_single_token_type_exact_amount_in(
+                    swap(
                         &SWAP_PROGRAM_ID,
-                        &wrong_key,
+                        &wrong_program_id,
                         &accounts.swap_key,
                         &accounts.authority_key,
                         &accounts.authority_key,
                         &token_a_key,
                         &accounts.token_a_key,
                         &accounts.token_b_key,
+                        &token_b_key,
                         &accounts.pool_mint_key,
-                        &pool_key,
-                        DepositSingleTokenTypeExactAmountIn {
-                            source_token_amount: deposit_a,
-                            minimum_pool_token_amount: pool_amount,
+                        &accounts.pool_fee_key,
+                        None,
+                        Swap {
+                            amount_in: initial_a,
+                            minimum_amount_out: minimum_token_b_amount,
                         },
                     )
                     .unwrap(),
@@ -4521,404 +7529,421 @@ mod tests {
                         &mut token_a_account,
                         &mut accounts.token_a_account,
                         &mut accounts.token_b_account,
+                        &mut token_b_account,
                         &mut accounts.pool_mint_account,
-                        &mut pool_account,
+                        &mut accounts.pool_fee_account,
                         &mut Account::default(),
                     ],
-                )
+                ),
             );
         }
 
-        // wrong swap token accounts
+        // not enough token a to swap
         {
             let (
                 token_a_key,
                 mut token_a_account,
                 token_b_key,
-                token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-
-            let old_a_key = accounts.token_a_key;
-            let old_a_account = accounts.token_a_account;
-
-            accounts.token_a_key = token_a_key;
-            accounts.token_a_account = token_a_account.clone();
-
-            // wrong swap token a account
-            assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-            );
-
-            accounts.token_a_key = old_a_key;
-            accounts.token_a_account = old_a_account;
-
-            let old_b_key = accounts.token_b_key;
-            let old_b_account = accounts.token_b_account;
-
-            accounts.token_b_key = token_b_key;
-            accounts.token_b_account = token_b_account;
-
-            // wrong swap token b account
-            assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-            );
-
-            accounts.token_b_key = old_b_key;
-            accounts.token_b_account = old_b_account;
-        }
-
-        // wrong mint
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let (pool_mint_key, pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
-            let old_pool_key = accounts.pool_mint_key;
-            let old_pool_account = accounts.pool_mint_account;
-            accounts.pool_mint_key = pool_mint_key;
-            accounts.pool_mint_account = pool_mint_account;
-
-            assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            assert_eq!(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.swap(
+                    &swapper_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a * 2,
+                    minimum_token_b_amount * 2,
                 )
             );
-
-            accounts.pool_mint_key = old_pool_key;
-            accounts.pool_mint_account = old_pool_account;
         }
 
-        // slippage exceeded
+        // wrong swap token A / B accounts
         {
             let (
                 token_a_key,
                 mut token_a_account,
                 token_b_key,
                 mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            // minimum pool amount too high
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let user_transfer_key = Pubkey::new_unique();
             assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a / 10,
-                    pool_amount,
-                )
+                Err(SwapError::IncorrectSwapAccount.into()),
+                do_process_instruction(
+                    swap(
+                        &SWAP_PROGRAM_ID,
+                        &TOKEN_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &user_transfer_key,
+                        &token_a_key,
+                        &token_a_key,
+                        &token_b_key,
+                        &token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        None,
+                        Swap {
+                            amount_in: initial_a,
+                            minimum_amount_out: minimum_token_b_amount,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut Account::default(),
+                        &mut token_a_account.clone(),
+                        &mut token_a_account,
+                        &mut token_b_account.clone(),
+                        &mut token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut Account::default(),
+                    ],
+                ),
             );
-            // minimum pool amount too high
+        }
+
+        // wrong user token A / B accounts
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
+                Err(TokenError::MintMismatch.into()),
+                accounts.swap(
+                    &swapper_key,
                     &token_b_key,
                     &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_b / 10,
-                    pool_amount,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
             );
         }
 
-        // invalid input: can't use swap pool tokens as source
+        // swap from a to a
         {
             let (
-                _token_a_key,
-                _token_a_account,
+                token_a_key,
+                mut token_a_account,
                 _token_b_key,
                 _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let swap_token_a_key = accounts.token_a_key;
-            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
-            let swap_token_b_key = accounts.token_b_key;
-            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
-            let authority_key = accounts.authority_key;
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             assert_eq!(
                 Err(SwapError::InvalidInput.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &authority_key,
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account.clone(),
                     &swap_token_a_key,
-                    &mut swap_token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-            );
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &authority_key,
-                    &swap_token_b_key,
-                    &mut swap_token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_b,
-                    pool_amount,
+                    &swap_token_a_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
             );
         }
 
-        // correctly deposit
+        // incorrect mint provided
         {
             let (
                 token_a_key,
                 mut token_a_account,
                 token_b_key,
                 mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            accounts
-                .deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let (pool_mint_key, pool_mint_account) =
+                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
+            let old_pool_key = accounts.pool_mint_key;
+            let old_pool_account = accounts.pool_mint_account;
+            accounts.pool_mint_key = pool_mint_key;
+            accounts.pool_mint_account = pool_mint_account;
+
+            assert_eq!(
+                Err(SwapError::IncorrectPoolMint.into()),
+                accounts.swap(
+                    &swapper_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-                .unwrap();
-
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-            assert_eq!(swap_token_a.amount, deposit_a + token_a_amount);
-
-            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-            assert_eq!(token_a.amount, 0);
-
-            accounts
-                .deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
                     &token_b_key,
                     &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_b,
-                    pool_amount,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
-                .unwrap();
-            let swap_token_b =
-                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-            assert_eq!(swap_token_b.amount, deposit_b + token_b_amount);
-
-            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
-            assert_eq!(token_b.amount, 0);
-
-            let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
-            let swap_pool_account =
-                spl_token::state::Account::unpack(&accounts.pool_token_account.data).unwrap();
-            let pool_mint =
-                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-            assert_eq!(
-                pool_mint.supply,
-                pool_account.amount + swap_pool_account.amount
             );
-        }
-    }
-
-    #[test]
-    fn test_withdraw_one_exact_out() {
-        let user_key = Pubkey::new_unique();
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 2;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 10;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 5;
-        let host_fee_numerator = 7;
-        let host_fee_denominator = 100;
-
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
-
-        let token_a_amount = 100_000;
-        let token_b_amount = 200_000;
-        let curve_type = CurveType::ConstantProduct;
-        let swap_curve = SwapCurve {
-            curve_type,
-            calculator: Box::new(ConstantProductCurve {}),
-        };
-
-        let withdrawer_key = Pubkey::new_unique();
-        let initial_a = token_a_amount / 10;
-        let initial_b = token_b_amount / 10;
-        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
-        let maximum_pool_token_amount = to_u64(initial_pool / 4).unwrap();
-        let destination_a_amount = initial_a / 40;
-        let destination_b_amount = initial_b / 40;
 
-        let mut accounts =
-            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+            accounts.pool_mint_key = old_pool_key;
+            accounts.pool_mint_account = old_pool_account;
+        }
 
-        // swap not initialized
+        // incorrect fee account provided
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+                token_b_key,
+                mut token_b_account,
+                wrong_pool_key,
+                wrong_pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let old_pool_fee_account = accounts.pool_fee_account;
+            let old_pool_fee_key = accounts.pool_fee_key;
+            accounts.pool_fee_account = wrong_pool_account;
+            accounts.pool_fee_key = wrong_pool_key;
             assert_eq!(
-                Err(ProgramError::UninitializedAccount),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                Err(SwapError::IncorrectFeeAccount.into()),
+                accounts.swap(
+                    &swapper_key,
                     &token_a_key,
                     &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
             );
+            accounts.pool_fee_account = old_pool_fee_account;
+            accounts.pool_fee_key = old_pool_fee_key;
         }
 
-        accounts.initialize_swap().unwrap();
+        // no approval
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let user_transfer_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(TokenError::OwnerMismatch.into()),
+                do_process_instruction(
+                    swap(
+                        &SWAP_PROGRAM_ID,
+                        &TOKEN_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &user_transfer_key,
+                        &token_a_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        None,
+                        Swap {
+                            amount_in: initial_a,
+                            minimum_amount_out: minimum_token_b_amount,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut Account::default(),
+                        &mut token_a_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut Account::default(),
+                    ],
+                ),
+            );
+        }
 
-        // wrong owner for swap account
+        // output token value 0
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
-            let old_swap_account = accounts.swap_account;
-            let mut wrong_swap_account = old_swap_account.clone();
-            wrong_swap_account.owner = TOKEN_PROGRAM_ID;
-            accounts.swap_account = wrong_swap_account;
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             assert_eq!(
-                Err(ProgramError::IncorrectProgramId),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                Err(SwapError::ZeroTradingTokens.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &swap_token_b_key,
+                    &swap_token_a_key,
                     &token_a_key,
                     &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                    1,
+                    1,
                 )
             );
-            accounts.swap_account = old_swap_account;
         }
 
-        // wrong nonce for authority_key
+        // slippage exceeded: minimum out amount too high
         {
             let (
-                _token_a_key,
-                _token_a_account,
+                token_a_key,
+                mut token_a_account,
                 token_b_key,
                 mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
-            let old_authority = accounts.authority_key;
-            let (bad_authority_key, _nonce) = Pubkey::find_program_address(
-                &[&accounts.swap_key.to_bytes()[..]],
-                &TOKEN_PROGRAM_ID,
-            );
-            accounts.authority_key = bad_authority_key;
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             assert_eq!(
-                Err(SwapError::InvalidProgramAddress.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
                     &token_b_key,
                     &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
+                    initial_a,
+                    minimum_token_b_amount * 2,
                 )
             );
-            accounts.authority_key = old_authority;
         }
 
-        // not enough pool tokens
+        // invalid input: can't use swap pool as user source / dest
         {
             let (
-                _token_a_key,
-                _token_a_account,
+                token_a_key,
+                mut token_a_account,
                 token_b_key,
                 mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount / 1000,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
+            let authority_key = accounts.authority_key;
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.swap(
+                    &authority_key,
+                    &swap_token_a_key,
+                    &mut swap_token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
+                )
             );
+            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
             assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                Err(SwapError::InvalidInput.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &swap_token_b_key,
+                    &mut swap_token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
+                )
+            );
+        }
+
+        // still correct: constraint specified, no host fee account
+        {
+            let authority_key = accounts.authority_key;
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
+            let owner_key = &swapper_key.to_string();
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types: &[],
+                fees: &fees,
+            });
+            do_process_instruction_with_fee_constraints(
+                swap(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.swap_key,
+                    &accounts.authority_key,
+                    &accounts.authority_key,
+                    &token_a_key,
+                    &accounts.token_a_key,
+                    &accounts.token_b_key,
                     &token_b_key,
+                    &accounts.pool_mint_key,
+                    &accounts.pool_fee_key,
+                    None,
+                    Swap {
+                        amount_in: initial_a,
+                        minimum_amount_out: minimum_token_b_amount,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut accounts.swap_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut token_a_account,
+                    &mut accounts.token_a_account,
+                    &mut accounts.token_b_account,
                     &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
-                )
-            );
+                    &mut accounts.pool_mint_account,
+                    &mut accounts.pool_fee_account,
+                    &mut Account::default(),
+                ],
+                &constraints,
+            )
+            .unwrap();
         }
 
-        // wrong pool token account
+        // invalid mint for host fee account
         {
+            let authority_key = accounts.authority_key;
             let (
                 token_a_key,
                 mut token_a_account,
@@ -4926,1704 +7951,3214 @@ mod tests {
                 mut token_b_account,
                 _pool_key,
                 _pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                maximum_pool_token_amount,
-                initial_b,
-                maximum_pool_token_amount,
-            );
+            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
+            let (
+                bad_token_a_key,
+                mut bad_token_a_account,
+                _token_b_key,
+                mut _token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
+            let owner_key = &swapper_key.to_string();
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types: &[],
+                fees: &fees,
+            });
             assert_eq!(
-                Err(TokenError::MintMismatch.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
-                )
+                Err(SwapError::IncorrectPoolMint.into()),
+                do_process_instruction_with_fee_constraints(
+                    swap(
+                        &SWAP_PROGRAM_ID,
+                        &TOKEN_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.authority_key,
+                        &token_a_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        Some(&bad_token_a_key),
+                        Swap {
+                            amount_in: initial_a,
+                            minimum_amount_out: 0,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut Account::default(),
+                        &mut token_a_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut Account::default(),
+                        &mut bad_token_a_account,
+                    ],
+                    &constraints,
+                ),
             );
         }
+    }
 
-        // wrong pool fee account
+    #[test]
+    fn test_overdraw_offset_curve() {
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 10;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 30;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 30;
+        let host_fee_numerator = 10;
+        let host_fee_denominator = 100;
+
+        let token_a_amount = 1_000_000_000;
+        let token_b_amount = 0;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let token_b_offset = 2_000_000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::Offset,
+            calculator: Box::new(OffsetCurve { token_b_offset }),
+        };
+        let user_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
+
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+
+        accounts.initialize_swap().unwrap();
+
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+        let initial_a = 500_000;
+        let initial_b = 1_000;
+
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            _pool_key,
+            _pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+
+        // swap a to b way, fails, there's no liquidity
+        let a_to_b_amount = initial_a;
+        let minimum_token_b_amount = 0;
+
+        assert_eq!(
+            Err(SwapError::ZeroTradingTokens.into()),
+            accounts.swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+        );
+
+        // swap b to a, succeeds at offset price
+        let b_to_a_amount = initial_b;
+        let minimum_token_a_amount = 0;
+        accounts
+            .swap(
+                &swapper_key,
+                &token_b_key,
+                &mut token_b_account,
+                &swap_token_b_key,
+                &swap_token_a_key,
+                &token_a_key,
+                &mut token_a_account,
+                b_to_a_amount,
+                minimum_token_a_amount,
+            )
+            .unwrap();
+
+        // try a to b again, succeeds due to new liquidity
+        accounts
+            .swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+            .unwrap();
+
+        // try a to b again, fails due to no more liquidity
+        assert_eq!(
+            Err(SwapError::ZeroTradingTokens.into()),
+            accounts.swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+        );
+
+        // Try to deposit, fails because deposits are not allowed for offset
+        // curve swaps
         {
+            let initial_a = 100;
+            let initial_b = 100;
+            let pool_amount = 100;
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                wrong_pool_key,
-                wrong_pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
-            );
-            let (
-                _token_a_key,
-                _token_a_account,
-                _token_b_key,
-                _token_b_account,
+                token_b_key,
+                mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
-            );
-            let old_pool_fee_account = accounts.pool_fee_account;
-            let old_pool_fee_key = accounts.pool_fee_key;
-            accounts.pool_fee_account = wrong_pool_account;
-            accounts.pool_fee_key = wrong_pool_key;
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
             assert_eq!(
-                Err(SwapError::IncorrectFeeAccount.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
+                Err(SwapError::UnsupportedCurveOperation.into()),
+                accounts.deposit_all_token_types(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                    pool_amount,
+                    initial_a,
+                    initial_b,
                 )
             );
-            accounts.pool_fee_account = old_pool_fee_account;
-            accounts.pool_fee_key = old_pool_fee_key;
         }
+    }
 
-        // no approval
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
+    #[test]
+    fn test_withdraw_all_offset_curve() {
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 10;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 30;
+        let owner_withdraw_fee_numerator = 0;
+        let owner_withdraw_fee_denominator = 30;
+        let host_fee_numerator = 10;
+        let host_fee_denominator = 100;
+
+        let token_a_amount = 1_000_000_000;
+        let token_b_amount = 10;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let token_b_offset = 2_000_000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::Offset,
+            calculator: Box::new(OffsetCurve { token_b_offset }),
+        };
+        let total_pool = swap_curve.calculator.new_pool_supply();
+        let user_key = Pubkey::new_unique();
+        let withdrawer_key = Pubkey::new_unique();
+
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+
+        accounts.initialize_swap().unwrap();
+
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            _pool_key,
+            _pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
+
+        let pool_key = accounts.pool_token_key;
+        let mut pool_account = accounts.pool_token_account.clone();
+
+        // WithdrawAllTokenTypes takes all tokens for A and B.
+        // The curve's calculation for token B will say to transfer
+        // `token_b_offset + token_b_amount`, but only `token_b_amount` will be
+        // moved.
+        accounts
+            .withdraw_all_token_types(
                 &user_key,
-                &withdrawer_key,
+                &pool_key,
+                &mut pool_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                total_pool.try_into().unwrap(),
                 0,
                 0,
-                maximum_pool_token_amount,
-            );
-            let user_transfer_authority_key = Pubkey::new_unique();
+            )
+            .unwrap();
+
+        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+        assert_eq!(token_a.amount, token_a_amount);
+        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+        assert_eq!(token_b.amount, token_b_amount);
+        let swap_token_a =
+            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+        assert_eq!(swap_token_a.amount, 0);
+        let swap_token_b =
+            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+        assert_eq!(swap_token_b.amount, 0);
+    }
+
+    #[test]
+    fn test_initialize_swap_v1_account() {
+        let user_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 10,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 30,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 30,
+            host_fee_numerator: 10,
+            host_fee_denominator: 100,
+        };
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+        let mut accounts = SwapAccountInfo::new(&user_key, fees, swap_curve, 1000, 2000);
+        accounts.swap_account = Account::new(0, 1 + SwapV1::LEN, &SWAP_PROGRAM_ID);
+
+        // admin authority needs the latest swap version
+        assert_eq!(
+            Err(ProgramError::AccountDataTooSmall),
+            accounts.initialize_swap()
+        );
+
+        do_process_instruction(
+            initialize(
+                &SWAP_PROGRAM_ID,
+                &TOKEN_PROGRAM_ID,
+                &accounts.swap_key,
+                &accounts.authority_key,
+                &accounts.token_a_key,
+                &accounts.token_b_key,
+                &accounts.pool_mint_key,
+                &accounts.pool_fee_key,
+                &accounts.pool_token_key,
+                accounts.nonce,
+                accounts.fees.clone(),
+                accounts.swap_curve.clone(),
+                None,
+            )
+            .unwrap(),
+            vec![
+                &mut accounts.swap_account,
+                &mut Account::default(),
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
+                &mut accounts.pool_mint_account,
+                &mut accounts.pool_fee_account,
+                &mut accounts.pool_token_account,
+                &mut Account::default(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(accounts.swap_account.data[0], 1);
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert_eq!(swap_state.is_initialized(), true);
+        assert_eq!(*swap_state.fees(), accounts.fees);
+        assert_eq!(swap_state.admin_authority(), None);
+        assert_eq!(swap_state.price_accumulator(), None);
+
+        assert_eq!(
+            Err(SwapError::MissingAdminAuthority.into()),
+            do_process_instruction(
+                set_paused(
+                    &SWAP_PROGRAM_ID,
+                    &accounts.swap_key,
+                    &accounts.admin_key,
+                    true
+                )
+                .unwrap(),
+                vec![&mut accounts.swap_account, &mut Account::default()],
+            )
+        );
+    }
+
+    #[test]
+    fn test_set_fees() {
+        let user_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 10,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 30,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 30,
+            host_fee_numerator: 10,
+            host_fee_denominator: 100,
+        };
+        let new_fees = Fees {
+            trade_fee_numerator: 3,
+            trade_fee_denominator: 1000,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 1000,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 20,
+            host_fee_denominator: 100,
+        };
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+        let mut accounts = SwapAccountInfo::new(&user_key, fees.clone(), swap_curve, 1000, 2000);
+
+        // swap not initialized
+        {
+            let admin_key = accounts.admin_key;
             assert_eq!(
-                Err(TokenError::OwnerMismatch.into()),
+                Err(SwapError::MissingAdminAuthority.into()),
                 do_process_instruction(
-                    withdraw_single_token_type_exact_amount_out(
+                    set_fees(
                         &SWAP_PROGRAM_ID,
-                        &TOKEN_PROGRAM_ID,
                         &accounts.swap_key,
-                        &accounts.authority_key,
-                        &user_transfer_authority_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &pool_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_a_key,
-                        WithdrawSingleTokenTypeExactAmountOut {
-                            destination_token_amount: destination_a_amount,
-                            maximum_pool_token_amount,
-                        }
+                        &admin_key,
+                        new_fees.clone(),
                     )
                     .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut Account::default(),
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_a_account,
-                        &mut accounts.pool_fee_account,
-                        &mut Account::default(),
-                    ],
+                    vec![&mut accounts.swap_account, &mut Account::default()],
                 )
             );
         }
 
-        // wrong token program id
+        // admin authority did not sign the initialization
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
-            );
-            let wrong_key = Pubkey::new_unique();
+            let mut instruction = initialize(
+                &SWAP_PROGRAM_ID,
+                &TOKEN_PROGRAM_ID,
+                &accounts.swap_key,
+                &accounts.authority_key,
+                &accounts.token_a_key,
+                &accounts.token_b_key,
+                &accounts.pool_mint_key,
+                &accounts.pool_fee_key,
+                &accounts.pool_token_key,
+                accounts.nonce,
+                accounts.fees.clone(),
+                accounts.swap_curve.clone(),
+                Some(&accounts.admin_key),
+            )
+            .unwrap();
+            instruction.accounts[8].is_signer = false;
             assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
+                Err(ProgramError::MissingRequiredSignature),
                 do_process_instruction(
-                    withdraw_single_token_type_exact_amount_out(
-                        &SWAP_PROGRAM_ID,
-                        &wrong_key,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.authority_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &pool_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_a_key,
-                        WithdrawSingleTokenTypeExactAmountOut {
-                            destination_token_amount: destination_a_amount,
-                            maximum_pool_token_amount,
-                        }
-                    )
-                    .unwrap(),
+                    instruction,
                     vec![
                         &mut accounts.swap_account,
                         &mut Account::default(),
-                        &mut Account::default(),
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
                         &mut accounts.token_a_account,
                         &mut accounts.token_b_account,
-                        &mut token_a_account,
+                        &mut accounts.pool_mint_account,
                         &mut accounts.pool_fee_account,
+                        &mut accounts.pool_token_account,
+                        &mut Account::default(),
                         &mut Account::default(),
                     ],
                 )
             );
         }
 
-        // wrong swap token accounts
+        accounts.initialize_swap().unwrap();
+
+        // wrong admin authority
+        {
+            let wrong_admin_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(SwapError::InvalidAdminAuthority.into()),
+                do_process_instruction(
+                    set_fees(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &wrong_admin_key,
+                        new_fees.clone(),
+                    )
+                    .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
+                )
+            );
+        }
+
+        // admin authority did not sign
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
-
-            let old_a_key = accounts.token_a_key;
-            let old_a_account = accounts.token_a_account;
-
-            accounts.token_a_key = token_a_key;
-            accounts.token_a_account = token_a_account.clone();
-
-            // wrong swap token a account
+            let mut instruction = set_fees(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &accounts.admin_key,
+                new_fees.clone(),
+            )
+            .unwrap();
+            instruction.accounts[1].is_signer = false;
             assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                Err(ProgramError::MissingRequiredSignature),
+                do_process_instruction(
+                    instruction,
+                    vec![&mut accounts.swap_account, &mut Account::default()],
                 )
             );
+        }
 
-            accounts.token_a_key = old_a_key;
-            accounts.token_a_account = old_a_account;
-
-            let old_b_key = accounts.token_b_key;
-            let old_b_account = accounts.token_b_account;
-
-            accounts.token_b_key = token_b_key;
-            accounts.token_b_account = token_b_account.clone();
-
-            // wrong swap token b account
+        // swap not owned by the program
+        {
+            let mut wrong_swap_account = accounts.swap_account.clone();
+            wrong_swap_account.owner = TOKEN_PROGRAM_ID;
             assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
+                Err(ProgramError::IncorrectProgramId),
+                do_process_instruction(
+                    set_fees(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.admin_key,
+                        new_fees.clone(),
+                    )
+                    .unwrap(),
+                    vec![&mut wrong_swap_account, &mut Account::default()],
                 )
             );
-
-            accounts.token_b_key = old_b_key;
-            accounts.token_b_account = old_b_account;
         }
 
-        // wrong mint
+        // invalid fees
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
+            let invalid_fees = Fees {
+                trade_fee_numerator: 11,
+                trade_fee_denominator: 10,
+                ..new_fees.clone()
+            };
+            assert_eq!(
+                Err(SwapError::InvalidFee.into()),
+                do_process_instruction(
+                    set_fees(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.admin_key,
+                        invalid_fees,
+                    )
+                    .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
+                )
             );
-            let (pool_mint_key, pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
-            let old_pool_key = accounts.pool_mint_key;
-            let old_pool_account = accounts.pool_mint_account;
-            accounts.pool_mint_key = pool_mint_key;
-            accounts.pool_mint_account = pool_mint_account;
+        }
 
+        // fees outside of the program owner's constraints
+        {
+            let owner_key = &user_key.to_string();
+            let valid_curve_types = &[CurveType::ConstantProduct];
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types,
+                fees: &fees,
+            });
             assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                Err(SwapError::InvalidFee.into()),
+                do_process_instruction_with_fee_constraints(
+                    set_fees(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.admin_key,
+                        new_fees.clone(),
+                    )
+                    .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
+                    &constraints,
                 )
             );
-
-            accounts.pool_mint_key = old_pool_key;
-            accounts.pool_mint_account = old_pool_account;
         }
 
-        // slippage exceeded
+        // correctly update fees
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
-            );
+            do_process_instruction(
+                set_fees(
+                    &SWAP_PROGRAM_ID,
+                    &accounts.swap_key,
+                    &accounts.admin_key,
+                    new_fees.clone(),
+                )
+                .unwrap(),
+                vec![&mut accounts.swap_account, &mut Account::default()],
+            )
+            .unwrap();
+            let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+            assert_eq!(*swap_state.fees(), new_fees);
+            assert_eq!(swap_state.admin_authority(), Some(&accounts.admin_key));
+            assert_eq!(swap_state.is_paused(), false);
+        }
 
-            // maximum pool token amount too low
-            assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount / 1000,
+        // swap initialized without an admin authority
+        {
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(ConstantProductCurve {}),
+            };
+            let mut accounts =
+                SwapAccountInfo::new(&user_key, fees.clone(), swap_curve, 1000, 2000);
+            do_process_instruction(
+                initialize(
+                    &SWAP_PROGRAM_ID,
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.swap_key,
+                    &accounts.authority_key,
+                    &accounts.token_a_key,
+                    &accounts.token_b_key,
+                    &accounts.pool_mint_key,
+                    &accounts.pool_fee_key,
+                    &accounts.pool_token_key,
+                    accounts.nonce,
+                    accounts.fees.clone(),
+                    accounts.swap_curve.clone(),
+                    None,
                 )
-            );
+                .unwrap(),
+                vec![
+                    &mut accounts.swap_account,
+                    &mut Account::default(),
+                    &mut accounts.token_a_account,
+                    &mut accounts.token_b_account,
+                    &mut accounts.pool_mint_account,
+                    &mut accounts.pool_fee_account,
+                    &mut accounts.pool_token_account,
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+            let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+            assert_eq!(swap_state.admin_authority(), None);
+
             assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount / 1000,
+                Err(SwapError::MissingAdminAuthority.into()),
+                do_process_instruction(
+                    set_fees(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.admin_key,
+                        new_fees,
+                    )
+                    .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
                 )
             );
         }
+    }
 
-        // invalid input: can't use swap pool tokens as destination
-        {
-            let (
-                _token_a_key,
-                _token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
-            );
-            let swap_token_a_key = accounts.token_a_key;
-            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
+    #[test]
+    fn test_set_paused() {
+        let user_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 10,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 30,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
+        };
+        let token_a_amount = 1000;
+        let token_b_amount = 5000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+        accounts.initialize_swap().unwrap();
+
+        let deposit_a = token_a_amount / 10;
+        let deposit_b = token_b_amount / 10;
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+
+        // wrong admin authority
+        {
+            let wrong_admin_key = Pubkey::new_unique();
             assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &swap_token_a_key,
-                    &mut swap_token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                Err(SwapError::InvalidAdminAuthority.into()),
+                do_process_instruction(
+                    set_paused(&SWAP_PROGRAM_ID, &accounts.swap_key, &wrong_admin_key, true)
+                        .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
                 )
             );
-            let swap_token_b_key = accounts.token_b_key;
-            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
+        }
+
+        // admin authority did not sign
+        {
+            let mut instruction = set_paused(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &accounts.admin_key,
+                true,
+            )
+            .unwrap();
+            instruction.accounts[1].is_signer = false;
             assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &swap_token_b_key,
-                    &mut swap_token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
+                Err(ProgramError::MissingRequiredSignature),
+                do_process_instruction(
+                    instruction,
+                    vec![&mut accounts.swap_account, &mut Account::default()],
                 )
             );
         }
 
-        // correct withdrawal
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
+        do_process_instruction(
+            set_paused(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &accounts.admin_key,
+                true,
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert_eq!(swap_state.is_paused(), true);
 
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-            let swap_token_b =
-                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-            let pool_mint =
-                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+        // swaps are paused
+        assert_eq!(
+            Err(SwapError::SwapPaused.into()),
+            accounts.swap(
+                &depositor_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                deposit_a / 2,
+                0,
+            )
+        );
 
-            let pool_token_amount = accounts
-                .swap_curve
-                .trading_tokens_to_pool_tokens(
-                    destination_a_amount.try_into().unwrap(),
-                    (swap_token_a.amount - destination_a_amount)
-                        .try_into()
-                        .unwrap(),
-                    swap_token_b.amount.try_into().unwrap(),
-                    pool_mint.supply.try_into().unwrap(),
-                    TradeDirection::AtoB,
-                    RoundDirection::Ceiling,
-                    &accounts.fees,
-                )
-                .unwrap();
-            let withdraw_fee = accounts.fees.owner_withdraw_fee(pool_token_amount).unwrap();
+        // deposits are paused
+        assert_eq!(
+            Err(SwapError::SwapPaused.into()),
+            accounts.deposit_all_token_types(
+                &depositor_key,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                &pool_key,
+                &mut pool_account,
+                1,
+                deposit_a,
+                deposit_b,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::SwapPaused.into()),
+            accounts.deposit_single_token_type_exact_amount_in(
+                &depositor_key,
+                &token_a_key,
+                &mut token_a_account,
+                &pool_key,
+                &mut pool_account,
+                deposit_a,
+                0,
+            )
+        );
 
+        // withdrawals stay open
+        {
+            let pool_token_key = accounts.pool_token_key;
+            let mut pool_token_account = accounts.pool_token_account.clone();
+            let withdraw_amount = INITIAL_SWAP_POOL_AMOUNT / 10;
             accounts
-                .withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                .withdraw_all_token_types(
+                    &user_key,
+                    &pool_token_key,
+                    &mut pool_token_account,
                     &token_a_key,
                     &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    0,
+                    0,
                 )
                 .unwrap();
-
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-
-            assert_eq!(swap_token_a.amount, token_a_amount - destination_a_amount);
-            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-            assert_eq!(token_a.amount, initial_a + destination_a_amount);
-
-            let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
-            assert_eq!(
-                pool_account.amount,
-                to_u64(initial_pool - pool_token_amount - withdraw_fee).unwrap()
-            );
-            let fee_account =
-                spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
-            assert_eq!(fee_account.amount, to_u64(withdraw_fee).unwrap());
-        }
-
-        // correct withdrawal from fee account
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
-
-            let fee_a_amount = 2;
-            let pool_fee_key = accounts.pool_fee_key;
-            let mut pool_fee_account = accounts.pool_fee_account.clone();
-            let fee_account = spl_token::state::Account::unpack(&pool_fee_account.data).unwrap();
-            let pool_fee_amount = fee_account.amount;
-
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-
-            let token_a_amount = swap_token_a.amount;
             accounts
                 .withdraw_single_token_type_exact_amount_out(
                     &user_key,
-                    &pool_fee_key,
-                    &mut pool_fee_account,
+                    &pool_token_key,
+                    &mut pool_token_account,
                     &token_a_key,
                     &mut token_a_account,
-                    fee_a_amount,
-                    pool_fee_amount,
+                    deposit_a / 2,
+                    withdraw_amount.try_into().unwrap(),
                 )
                 .unwrap();
+        }
 
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+        // unpause and swap again
+        do_process_instruction(
+            set_paused(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &accounts.admin_key,
+                false,
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
+        accounts
+            .swap(
+                &depositor_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                deposit_a / 2,
+                0,
+            )
+            .unwrap();
+    }
 
-            assert_eq!(swap_token_a.amount, token_a_amount - fee_a_amount);
-            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-            assert_eq!(token_a.amount, initial_a + fee_a_amount);
-        }
+    fn unpack_price_accumulator(swap_account: &Account) -> PriceAccumulator {
+        *SwapVersion::unpack(&swap_account.data)
+            .unwrap()
+            .price_accumulator()
+            .unwrap()
     }
 
-    fn check_valid_swap_curve(
-        fees: Fees,
-        curve_type: CurveType,
-        calculator: Box<dyn CurveCalculator>,
-        token_a_amount: u64,
-        token_b_amount: u64,
-    ) {
-        let user_key = Pubkey::new_unique();
-        let swapper_key = Pubkey::new_unique();
+    fn swap_token_amounts(accounts: &SwapAccountInfo) -> (u128, u128) {
+        let swap_token_a =
+            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+        let swap_token_b =
+            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+        (swap_token_a.amount.into(), swap_token_b.amount.into())
+    }
 
+    #[test]
+    fn test_price_accumulator() {
+        let user_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 10,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 30,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
+        };
+        let token_a_amount = 1000;
+        let token_b_amount = 5000;
         let swap_curve = SwapCurve {
-            curve_type,
-            calculator,
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve {}),
         };
-
-        let mut accounts = SwapAccountInfo::new(
-            &user_key,
-            fees.clone(),
-            swap_curve.clone(),
-            token_a_amount,
-            token_b_amount,
-        );
-        let initial_a = token_a_amount / 5;
-        let initial_b = token_b_amount / 5;
+        let calculator = ConstantProductCurve {};
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+        set_test_slot(10);
         accounts.initialize_swap().unwrap();
+        let initial = unpack_price_accumulator(&accounts.swap_account);
+        assert_eq!(initial, PriceAccumulator::new(10));
 
-        let swap_token_a_key = accounts.token_a_key;
-        let swap_token_b_key = accounts.token_b_key;
-
+        let deposit_a = token_a_amount / 10;
+        let deposit_b = token_b_amount / 10;
         let (
-            token_a_key,
-            mut token_a_account,
-            token_b_key,
-            mut token_b_account,
-            _pool_key,
-            _pool_account,
-        ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-        // swap one way
-        let a_to_b_amount = initial_a / 10;
-        let minimum_token_b_amount = 0;
-        let pool_mint = spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-        let initial_supply = pool_mint.supply;
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+
+        // deposit accumulates the price before the deposit, 5 B per A
+        set_test_slot(20);
         accounts
-            .swap(
-                &swapper_key,
+            .deposit_all_token_types(
+                &depositor_key,
                 &token_a_key,
                 &mut token_a_account,
-                &swap_token_a_key,
-                &swap_token_b_key,
                 &token_b_key,
                 &mut token_b_account,
-                a_to_b_amount,
-                minimum_token_b_amount,
+                &pool_key,
+                &mut pool_account,
+                (INITIAL_SWAP_POOL_AMOUNT / 100).try_into().unwrap(),
+                deposit_a,
+                deposit_b,
             )
             .unwrap();
-
-        let results = swap_curve
+        let accumulator = unpack_price_accumulator(&accounts.swap_account);
+        assert_eq!(accumulator.last_update_slot, 20);
+        assert_eq!(accumulator.price_a_cumulative, 10 * (5 << 64));
+        assert_eq!(accumulator.price_b_cumulative, 10 * ((1 << 64) / 5));
+
+        // swap accumulates the price before the swap
+        set_test_slot(30);
+        let (swap_token_a_amount, swap_token_b_amount) = swap_token_amounts(&accounts);
+        let mut expected = accumulator;
+        expected.update(30, &calculator, swap_token_a_amount, swap_token_b_amount);
+        accounts
             .swap(
-                a_to_b_amount.try_into().unwrap(),
-                token_a_amount.try_into().unwrap(),
-                token_b_amount.try_into().unwrap(),
-                TradeDirection::AtoB,
-                &fees,
-            )
-            .unwrap();
-
-        let swap_token_a =
-            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-        let token_a_amount = swap_token_a.amount;
-        assert_eq!(
-            token_a_amount,
-            TryInto::<u64>::try_into(results.new_swap_source_amount).unwrap()
-        );
-        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-        assert_eq!(token_a.amount, initial_a - a_to_b_amount);
-
-        let swap_token_b =
-            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-        let token_b_amount = swap_token_b.amount;
-        assert_eq!(
-            token_b_amount,
-            TryInto::<u64>::try_into(results.new_swap_destination_amount).unwrap()
-        );
-        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
-        assert_eq!(
-            token_b.amount,
-            initial_b + to_u64(results.destination_amount_swapped).unwrap()
-        );
-
-        let first_fee = swap_curve
-            .trading_tokens_to_pool_tokens(
-                results.owner_fee,
-                token_a_amount.try_into().unwrap(),
-                token_b_amount.try_into().unwrap(),
-                initial_supply.try_into().unwrap(),
-                TradeDirection::AtoB,
-                RoundDirection::Ceiling,
-                &fees,
+                &depositor_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                deposit_a / 2,
+                0,
             )
             .unwrap();
-        let fee_account =
-            spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
-        assert_eq!(
-            fee_account.amount,
-            TryInto::<u64>::try_into(first_fee).unwrap()
-        );
-
-        let first_swap_amount = results.destination_amount_swapped;
+        assert_eq!(unpack_price_accumulator(&accounts.swap_account), expected);
 
-        // swap the other way
-        let pool_mint = spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-        let initial_supply = pool_mint.supply;
-
-        let b_to_a_amount = initial_b / 10;
-        let minimum_a_amount = 0;
+        // nothing to accumulate within the same slot
         accounts
             .swap(
-                &swapper_key,
+                &depositor_key,
                 &token_b_key,
                 &mut token_b_account,
                 &swap_token_b_key,
                 &swap_token_a_key,
                 &token_a_key,
                 &mut token_a_account,
-                b_to_a_amount,
-                minimum_a_amount,
-            )
-            .unwrap();
-
-        let results = swap_curve
-            .swap(
-                b_to_a_amount.try_into().unwrap(),
-                token_b_amount.try_into().unwrap(),
-                token_a_amount.try_into().unwrap(),
-                TradeDirection::BtoA,
-                &fees,
+                deposit_b / 10,
+                0,
             )
             .unwrap();
-
-        let swap_token_a =
-            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-        let token_a_amount = swap_token_a.amount;
-        assert_eq!(
-            token_a_amount,
-            TryInto::<u64>::try_into(results.new_swap_destination_amount).unwrap()
-        );
-        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-        assert_eq!(
-            token_a.amount,
-            initial_a - a_to_b_amount + to_u64(results.destination_amount_swapped).unwrap()
-        );
-
-        let swap_token_b =
-            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-        let token_b_amount = swap_token_b.amount;
-        assert_eq!(
-            token_b_amount,
-            TryInto::<u64>::try_into(results.new_swap_source_amount).unwrap()
-        );
-        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
-        assert_eq!(
-            token_b.amount,
-            initial_b + to_u64(first_swap_amount).unwrap()
-                - to_u64(results.source_amount_swapped).unwrap()
-        );
-
-        let second_fee = swap_curve
-            .trading_tokens_to_pool_tokens(
-                results.owner_fee,
-                token_a_amount.try_into().unwrap(),
-                token_b_amount.try_into().unwrap(),
-                initial_supply.try_into().unwrap(),
-                TradeDirection::BtoA,
-                RoundDirection::Ceiling,
-                &fees,
+        assert_eq!(unpack_price_accumulator(&accounts.swap_account), expected);
+
+        // withdrawals accumulate as well
+        set_test_slot(45);
+        let (swap_token_a_amount, swap_token_b_amount) = swap_token_amounts(&accounts);
+        expected.update(45, &calculator, swap_token_a_amount, swap_token_b_amount);
+        let pool_token_key = accounts.pool_token_key;
+        let mut pool_token_account = accounts.pool_token_account.clone();
+        accounts
+            .withdraw_all_token_types(
+                &user_key,
+                &pool_token_key,
+                &mut pool_token_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                (INITIAL_SWAP_POOL_AMOUNT / 10).try_into().unwrap(),
+                0,
+                0,
             )
             .unwrap();
-        let fee_account =
-            spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
-        assert_eq!(fee_account.amount, to_u64(first_fee + second_fee).unwrap());
+        let accumulator = unpack_price_accumulator(&accounts.swap_account);
+        assert_eq!(accumulator, expected);
+
+        // the average lies between the initial price and the lower prices
+        // after selling token A
+        let average = accumulator.time_weighted_average(&initial).unwrap();
+        let average_price = average.precise_price_a().unwrap();
+        assert!(average_price.less_than_or_equal(&PreciseNumber::new(5).unwrap()));
+        assert!(average_price.greater_than(&PreciseNumber::new(4).unwrap()));
     }
 
     #[test]
-    fn test_valid_swap_curves_all_fees() {
-        // All fees
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 20;
-        let host_fee_denominator = 100;
+    fn test_price_accumulator_ignores_donations() {
+        let user_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
         let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 10,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 30,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
         };
+        let token_a_amount = 1000;
+        let token_b_amount = 5000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+        let calculator = ConstantProductCurve {};
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+        set_test_slot(10);
+        accounts.initialize_swap().unwrap();
 
-        let token_a_amount = 10_000_000_000;
-        let token_b_amount = 50_000_000_000;
-
-        check_valid_swap_curve(
-            fees.clone(),
-            CurveType::ConstantProduct,
-            Box::new(ConstantProductCurve {}),
-            token_a_amount,
-            token_b_amount,
-        );
-        let token_b_price = 1;
-        check_valid_swap_curve(
-            fees.clone(),
-            CurveType::ConstantPrice,
-            Box::new(ConstantPriceCurve { token_b_price }),
-            token_a_amount,
-            token_b_amount,
-        );
-        let token_b_offset = 10_000_000_000;
-        check_valid_swap_curve(
-            fees,
-            CurveType::Offset,
-            Box::new(OffsetCurve { token_b_offset }),
-            token_a_amount,
-            token_b_amount,
-        );
-    }
+        let deposit_a = token_a_amount / 10;
+        let deposit_b = token_b_amount / 10;
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
 
-    #[test]
-    fn test_valid_swap_curves_trade_fee_only() {
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 0;
-        let owner_trade_fee_denominator = 0;
-        let owner_withdraw_fee_numerator = 0;
-        let owner_withdraw_fee_denominator = 0;
-        let host_fee_numerator = 0;
-        let host_fee_denominator = 0;
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
+        // token A sent straight to the swap halves its spot price
+        let mut swap_token_a =
+            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+        swap_token_a.amount += token_a_amount;
+        spl_token::state::Account::pack(swap_token_a, &mut accounts.token_a_account.data).unwrap();
 
-        let token_a_amount = 10_000_000_000;
-        let token_b_amount = 50_000_000_000;
+        // but the price the swap was initialized with, 5 B per A, prevailed
+        // until the next instruction
+        set_test_slot(20);
+        accounts
+            .deposit_all_token_types(
+                &depositor_key,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                &pool_key,
+                &mut pool_account,
+                (INITIAL_SWAP_POOL_AMOUNT / 100).try_into().unwrap(),
+                deposit_a,
+                deposit_b,
+            )
+            .unwrap();
+        let accumulator = unpack_price_accumulator(&accounts.swap_account);
+        assert_eq!(accumulator.last_update_slot, 20);
+        assert_eq!(accumulator.price_a_cumulative, 10 * (5 << 64));
+        assert_eq!(accumulator.price_b_cumulative, 10 * ((1 << 64) / 5));
+
+        // the donation only counts once an instruction settled with it
+        set_test_slot(30);
+        let (swap_token_a_amount, swap_token_b_amount) = swap_token_amounts(&accounts);
+        let mut expected = accumulator;
+        expected.update(30, &calculator, swap_token_a_amount, swap_token_b_amount);
+        let pool_token_key = accounts.pool_token_key;
+        let mut pool_token_account = accounts.pool_token_account.clone();
+        accounts
+            .withdraw_all_token_types(
+                &user_key,
+                &pool_token_key,
+                &mut pool_token_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                (INITIAL_SWAP_POOL_AMOUNT / 10).try_into().unwrap(),
+                0,
+                0,
+            )
+            .unwrap();
+        assert_eq!(unpack_price_accumulator(&accounts.swap_account), expected);
+    }
 
-        check_valid_swap_curve(
-            fees.clone(),
-            CurveType::ConstantProduct,
-            Box::new(ConstantProductCurve {}),
-            token_a_amount,
-            token_b_amount,
-        );
-        let token_b_price = 10_000;
-        check_valid_swap_curve(
+    #[test]
+    fn test_dynamic_fee() {
+        let user_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 100,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 1000,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
+        };
+        let token_a_amount = 100_000;
+        let token_b_amount = 500_000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+        let calculator = ConstantProductCurve {};
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
             fees.clone(),
-            CurveType::ConstantPrice,
-            Box::new(ConstantPriceCurve { token_b_price }),
-            token_a_amount,
-            token_b_amount / token_b_price,
-        );
-        let token_b_offset = 1;
-        check_valid_swap_curve(
-            fees,
-            CurveType::Offset,
-            Box::new(OffsetCurve { token_b_offset }),
+            swap_curve.clone(),
             token_a_amount,
             token_b_amount,
         );
-    }
+        set_test_slot(10);
+        accounts.initialize_swap().unwrap();
+        let parameters = DynamicFeeParameters {
+            min_fee_numerator: 25,
+            max_fee_numerator: 500,
+            fee_denominator: 10_000,
+            volatility_multiplier: 10_000,
+            decay_period: 10,
+        };
 
-    #[test]
-    fn test_valid_swap_with_fee_constraints() {
-        let owner_key = Pubkey::new_unique();
+        // wrong admin authority
+        {
+            let wrong_admin_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(SwapError::InvalidAdminAuthority.into()),
+                do_process_instruction(
+                    set_dynamic_fee(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &wrong_admin_key,
+                        parameters,
+                    )
+                    .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
+                )
+            );
+        }
 
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 10;
-        let host_fee_denominator = 100;
+        // maximum fee above the denominator
+        {
+            assert_eq!(
+                Err(SwapError::InvalidFee.into()),
+                do_process_instruction(
+                    set_dynamic_fee(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.admin_key,
+                        DynamicFeeParameters {
+                            max_fee_numerator: 10_000,
+                            ..parameters
+                        },
+                    )
+                    .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
+                )
+            );
+        }
 
-        let token_a_amount = 1_000_000;
-        let token_b_amount = 5_000_000;
+        // minimum fee below the constrained trade fee
+        {
+            let owner_key = &user_key.to_string();
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types: &[CurveType::ConstantProduct],
+                fees: &fees,
+            });
+            assert_eq!(
+                Err(SwapError::InvalidFee.into()),
+                do_process_instruction_with_fee_constraints(
+                    set_dynamic_fee(
+                        &SWAP_PROGRAM_ID,
+                        &accounts.swap_key,
+                        &accounts.admin_key,
+                        parameters,
+                    )
+                    .unwrap(),
+                    vec![&mut accounts.swap_account, &mut Account::default()],
+                    &constraints,
+                )
+            );
+        }
+
+        do_process_instruction(
+            set_dynamic_fee(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &accounts.admin_key,
+                parameters,
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
+        let token_swap = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        let mut expected = DynamicFee::new(parameters);
+        assert_eq!(token_swap.dynamic_fee(), Some(&expected));
+        assert_eq!(token_swap.current_fees().trade_fee_numerator, 25);
+        assert_eq!(token_swap.current_fees().trade_fee_denominator, 10_000);
+
+        let amount_in = token_a_amount / 10;
+        let (token_a_key, mut token_a_account, token_b_key, mut token_b_account, _, _) =
+            accounts.setup_token_accounts(&user_key, &depositor_key, 2 * amount_in, 0, 0);
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+
+        // the first trade sets the reference price and pays for the price it
+        // moves itself, well above the minimum fee, the second one pays for
+        // the price moved by the first one, and the volatility decays over
+        // the slots since the price moved
+        for (slot, amount_in, fee_numerator) in [
+            (20, amount_in, Some(500)),
+            (20, amount_in / 100, Some(500)),
+            (1_000, amount_in / 100, None),
+        ]
+        .iter()
+        {
+            set_test_slot(*slot);
+            let (swap_token_a_amount, swap_token_b_amount) = swap_token_amounts(&accounts);
+            expected.observe(
+                *slot,
+                calculator
+                    .spot_price(swap_token_a_amount, swap_token_b_amount)
+                    .as_ref(),
+            );
+            let trial_result = swap_curve
+                .swap(
+                    u128::from(*amount_in),
+                    swap_token_a_amount,
+                    swap_token_b_amount,
+                    TradeDirection::AtoB,
+                    &expected.apply(&fees),
+                )
+                .unwrap();
+            let expected_fees = expected
+                .after_trade(
+                    calculator
+                        .spot_price(
+                            trial_result.new_swap_source_amount,
+                            trial_result.new_swap_destination_amount,
+                        )
+                        .as_ref(),
+                )
+                .apply(&fees);
+            let expected_result = swap_curve
+                .swap(
+                    u128::from(*amount_in),
+                    swap_token_a_amount,
+                    swap_token_b_amount,
+                    TradeDirection::AtoB,
+                    &expected_fees,
+                )
+                .unwrap();
+            expected.observe(
+                *slot,
+                calculator
+                    .spot_price(
+                        expected_result.new_swap_source_amount,
+                        expected_result.new_swap_destination_amount,
+                    )
+                    .as_ref(),
+            );
+            let initial_b = spl_token::state::Account::unpack(&token_b_account.data)
+                .unwrap()
+                .amount;
+            accounts
+                .swap(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    *amount_in,
+                    0,
+                )
+                .unwrap();
+            let token_swap = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+            assert_eq!(token_swap.dynamic_fee(), Some(&expected));
+            let final_b = spl_token::state::Account::unpack(&token_b_account.data)
+                .unwrap()
+                .amount;
+            assert_eq!(
+                u128::from(final_b - initial_b),
+                expected_result.destination_amount_swapped
+            );
+            match fee_numerator {
+                Some(fee_numerator) => {
+                    assert_eq!(expected_fees.trade_fee_numerator, *fee_numerator)
+                }
+                None => {
+                    assert!(expected_fees.trade_fee_numerator > 25);
+                    assert!(expected_fees.trade_fee_numerator < 500);
+                }
+            }
+        }
+    }
 
+    #[test]
+    fn test_route_swap() {
+        let user_key = Pubkey::new_unique();
+        let trader_key = Pubkey::new_unique();
         let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 100,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 200,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 20,
+            host_fee_denominator: 100,
         };
-
-        let curve = ConstantProductCurve {};
         let swap_curve = SwapCurve {
             curve_type: CurveType::ConstantProduct,
-            calculator: Box::new(curve),
+            calculator: Box::new(ConstantProductCurve {}),
         };
-
-        let owner_key_str = &owner_key.to_string();
-        let valid_curve_types = &[CurveType::ConstantProduct];
-        let constraints = Some(SwapConstraints {
-            owner_key: owner_key_str,
-            valid_curve_types,
-            fees: &fees,
-        });
-        let mut accounts = SwapAccountInfo::new(
-            &owner_key,
+        let mut pool_ab = SwapAccountInfo::new(
+            &user_key,
             fees.clone(),
-            swap_curve,
-            token_a_amount,
-            token_b_amount,
+            swap_curve.clone(),
+            100_000,
+            200_000,
+        );
+        pool_ab.initialize_swap().unwrap();
+
+        // the second pool trades token B of the first pool against token C
+        let pool_bc_b_amount = 300_000;
+        let mut pool_bc =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, pool_bc_b_amount, 50_000);
+        let (token_b_key, token_b_account) = mint_token(
+            &TOKEN_PROGRAM_ID,
+            &pool_ab.token_b_mint_key,
+            &mut pool_ab.token_b_mint_account,
+            &user_key,
+            &pool_bc.authority_key,
+            pool_bc_b_amount,
         );
+        pool_bc.token_a_key = token_b_key;
+        pool_bc.token_a_account = token_b_account;
+        pool_bc.token_a_mint_key = pool_ab.token_b_mint_key;
+        pool_bc.token_a_mint_account = pool_ab.token_b_mint_account.clone();
+        pool_bc.initialize_swap().unwrap();
+
+        let amount_in = 10_000;
+        let (source_key, mut source_account, intermediate_key, mut intermediate_account, _, _) =
+            pool_ab.setup_token_accounts(&user_key, &trader_key, amount_in, 0, 0);
+        let (_, _, destination_key, mut destination_account, _, _) =
+            pool_bc.setup_token_accounts(&user_key, &trader_key, 0, 0, 0);
+
+        let first_hop = pool_ab
+            .swap_curve
+            .swap(
+                amount_in.into(),
+                100_000,
+                200_000,
+                TradeDirection::AtoB,
+                &pool_ab.fees,
+            )
+            .unwrap();
+        let second_hop = pool_bc
+            .swap_curve
+            .swap(
+                first_hop.destination_amount_swapped,
+                pool_bc_b_amount.into(),
+                50_000,
+                TradeDirection::AtoB,
+                &pool_bc.fees,
+            )
+            .unwrap();
+        let amount_out = to_u64(second_hop.destination_amount_swapped).unwrap();
+
+        let hops = [
+            RouteSwapHop {
+                swap_pubkey: pool_ab.swap_key,
+                authority_pubkey: pool_ab.authority_key,
+                swap_source_pubkey: pool_ab.token_a_key,
+                swap_destination_pubkey: pool_ab.token_b_key,
+                destination_pubkey: intermediate_key,
+                pool_mint_pubkey: pool_ab.pool_mint_key,
+                pool_fee_pubkey: pool_ab.pool_fee_key,
+            },
+            RouteSwapHop {
+                swap_pubkey: pool_bc.swap_key,
+                authority_pubkey: pool_bc.authority_key,
+                swap_source_pubkey: pool_bc.token_a_key,
+                swap_destination_pubkey: pool_bc.token_b_key,
+                destination_pubkey: destination_key,
+                pool_mint_pubkey: pool_bc.pool_mint_key,
+                pool_fee_pubkey: pool_bc.pool_fee_key,
+            },
+        ];
+        let route_swap_instruction = |minimum_amount_out| {
+            route_swap(
+                &SWAP_PROGRAM_ID,
+                &TOKEN_PROGRAM_ID,
+                &trader_key,
+                &source_key,
+                &hops,
+                None,
+                RouteSwap {
+                    amount_in,
+                    minimum_amount_out,
+                },
+            )
+            .unwrap()
+        };
 
-        // initialize swap
-        do_process_instruction_with_fee_constraints(
-            initialize(
+        // no pools to route through
+        assert_eq!(
+            Err(SwapError::InvalidRoute.into()),
+            route_swap(
                 &SWAP_PROGRAM_ID,
                 &TOKEN_PROGRAM_ID,
-                &accounts.swap_key,
-                &accounts.authority_key,
-                &accounts.token_a_key,
-                &accounts.token_b_key,
-                &accounts.pool_mint_key,
-                &accounts.pool_fee_key,
-                &accounts.pool_token_key,
-                accounts.nonce,
-                accounts.fees.clone(),
-                accounts.swap_curve.clone(),
+                &trader_key,
+                &source_key,
+                &[],
+                None,
+                RouteSwap {
+                    amount_in,
+                    minimum_amount_out: 0,
+                },
             )
-            .unwrap(),
+        );
+
+        // missing accounts for the last pool
+        {
+            let mut instruction = route_swap_instruction(0);
+            instruction.accounts.pop();
+            assert_eq!(
+                Err(SwapError::InvalidRoute.into()),
+                do_process_instruction(
+                    instruction,
+                    vec![
+                        &mut Account::default(),
+                        &mut source_account,
+                        &mut Account::default(),
+                        &mut pool_ab.swap_account,
+                        &mut Account::default(),
+                        &mut pool_ab.token_a_account,
+                        &mut pool_ab.token_b_account,
+                        &mut intermediate_account,
+                        &mut pool_ab.pool_mint_account,
+                        &mut pool_ab.pool_fee_account,
+                        &mut pool_bc.swap_account,
+                        &mut Account::default(),
+                        &mut pool_bc.token_a_account,
+                        &mut pool_bc.token_b_account,
+                        &mut destination_account,
+                        &mut pool_bc.pool_mint_account,
+                    ],
+                )
+            );
+        }
+
+        // slippage is checked on the final output only
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            do_process_instruction(
+                route_swap_instruction(amount_out + 1),
+                vec![
+                    &mut Account::default(),
+                    &mut source_account,
+                    &mut Account::default(),
+                    &mut pool_ab.swap_account,
+                    &mut Account::default(),
+                    &mut pool_ab.token_a_account,
+                    &mut pool_ab.token_b_account,
+                    &mut intermediate_account,
+                    &mut pool_ab.pool_mint_account,
+                    &mut pool_ab.pool_fee_account,
+                    &mut pool_bc.swap_account,
+                    &mut Account::default(),
+                    &mut pool_bc.token_a_account,
+                    &mut pool_bc.token_b_account,
+                    &mut destination_account,
+                    &mut pool_bc.pool_mint_account,
+                    &mut pool_bc.pool_fee_account,
+                ],
+            )
+        );
+
+        do_process_instruction(
+            route_swap_instruction(amount_out),
             vec![
-                &mut accounts.swap_account,
                 &mut Account::default(),
-                &mut accounts.token_a_account,
-                &mut accounts.token_b_account,
-                &mut accounts.pool_mint_account,
-                &mut accounts.pool_fee_account,
-                &mut accounts.pool_token_account,
+                &mut source_account,
+                &mut Account::default(),
+                &mut pool_ab.swap_account,
                 &mut Account::default(),
+                &mut pool_ab.token_a_account,
+                &mut pool_ab.token_b_account,
+                &mut intermediate_account,
+                &mut pool_ab.pool_mint_account,
+                &mut pool_ab.pool_fee_account,
+                &mut pool_bc.swap_account,
+                &mut Account::default(),
+                &mut pool_bc.token_a_account,
+                &mut pool_bc.token_b_account,
+                &mut destination_account,
+                &mut pool_bc.pool_mint_account,
+                &mut pool_bc.pool_fee_account,
             ],
-            &constraints,
         )
         .unwrap();
 
-        let authority_key = accounts.authority_key;
-
-        let (
-            token_a_key,
-            mut token_a_account,
-            token_b_key,
-            mut token_b_account,
-            pool_key,
-            mut pool_account,
-        ) = accounts.setup_token_accounts(
-            &owner_key,
-            &authority_key,
-            token_a_amount,
-            token_b_amount,
-            0,
+        // rounding may leave dust in the source and intermediate accounts
+        let source = spl_token::state::Account::unpack(&source_account.data).unwrap();
+        assert_eq!(
+            u128::from(amount_in - source.amount),
+            first_hop.source_amount_swapped
         );
+        let intermediate = spl_token::state::Account::unpack(&intermediate_account.data).unwrap();
+        assert_eq!(
+            u128::from(intermediate.amount),
+            first_hop.destination_amount_swapped - second_hop.source_amount_swapped
+        );
+        let destination = spl_token::state::Account::unpack(&destination_account.data).unwrap();
+        assert_eq!(destination.amount, amount_out);
 
-        let amount_in = token_a_amount / 2;
-        let minimum_amount_out = 0;
+        let swap_token_b =
+            spl_token::state::Account::unpack(&pool_ab.token_b_account.data).unwrap();
+        assert_eq!(
+            u128::from(swap_token_b.amount),
+            first_hop.new_swap_destination_amount
+        );
+        let swap_token_b =
+            spl_token::state::Account::unpack(&pool_bc.token_a_account.data).unwrap();
+        assert_eq!(
+            u128::from(swap_token_b.amount),
+            second_hop.new_swap_source_amount
+        );
 
-        // perform the swap
-        do_process_instruction_with_fee_constraints(
-            swap(
+        // a host fee account after the pools takes part of the first pool's owner fee
+        let (source_key, mut source_account, _, _, host_fee_key, mut host_fee_account) =
+            pool_ab.setup_token_accounts(&user_key, &trader_key, amount_in, 0, 0);
+        let (_, _, _, _, wrong_host_fee_key, mut wrong_host_fee_account) =
+            pool_bc.setup_token_accounts(&user_key, &trader_key, 0, 0, 0);
+        let host_fee_route_swap_instruction = |host_fee_key| {
+            route_swap(
                 &SWAP_PROGRAM_ID,
                 &TOKEN_PROGRAM_ID,
-                &accounts.swap_key,
-                &accounts.authority_key,
-                &accounts.authority_key,
-                &token_a_key,
-                &accounts.token_a_key,
-                &accounts.token_b_key,
-                &token_b_key,
-                &accounts.pool_mint_key,
-                &accounts.pool_fee_key,
-                Some(&pool_key),
-                Swap {
+                &trader_key,
+                &source_key,
+                &hops,
+                Some(host_fee_key),
+                RouteSwap {
                     amount_in,
-                    minimum_amount_out,
+                    minimum_amount_out: 0,
                 },
             )
-            .unwrap(),
+            .unwrap()
+        };
+
+        // the host fee account must hold pool tokens of the first pool
+        assert_eq!(
+            Err(SwapError::IncorrectPoolMint.into()),
+            do_process_instruction(
+                host_fee_route_swap_instruction(&wrong_host_fee_key),
+                vec![
+                    &mut Account::default(),
+                    &mut source_account,
+                    &mut Account::default(),
+                    &mut pool_ab.swap_account,
+                    &mut Account::default(),
+                    &mut pool_ab.token_a_account,
+                    &mut pool_ab.token_b_account,
+                    &mut intermediate_account,
+                    &mut pool_ab.pool_mint_account,
+                    &mut pool_ab.pool_fee_account,
+                    &mut pool_bc.swap_account,
+                    &mut Account::default(),
+                    &mut pool_bc.token_a_account,
+                    &mut pool_bc.token_b_account,
+                    &mut destination_account,
+                    &mut pool_bc.pool_mint_account,
+                    &mut pool_bc.pool_fee_account,
+                    &mut wrong_host_fee_account,
+                ],
+            )
+        );
+
+        let owner_fee_before = token_amount(&pool_ab.pool_fee_account);
+        do_process_instruction(
+            host_fee_route_swap_instruction(&host_fee_key),
             vec![
-                &mut accounts.swap_account,
                 &mut Account::default(),
+                &mut source_account,
                 &mut Account::default(),
-                &mut token_a_account,
-                &mut accounts.token_a_account,
-                &mut accounts.token_b_account,
-                &mut token_b_account,
-                &mut accounts.pool_mint_account,
-                &mut accounts.pool_fee_account,
+                &mut pool_ab.swap_account,
                 &mut Account::default(),
-                &mut pool_account,
+                &mut pool_ab.token_a_account,
+                &mut pool_ab.token_b_account,
+                &mut intermediate_account,
+                &mut pool_ab.pool_mint_account,
+                &mut pool_ab.pool_fee_account,
+                &mut pool_bc.swap_account,
+                &mut Account::default(),
+                &mut pool_bc.token_a_account,
+                &mut pool_bc.token_b_account,
+                &mut destination_account,
+                &mut pool_bc.pool_mint_account,
+                &mut pool_bc.pool_fee_account,
+                &mut host_fee_account,
             ],
-            &constraints,
         )
         .unwrap();
 
-        // check that fees were taken in the host fee account
-        let host_fee_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
-        let owner_fee_account =
-            spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
-        let total_fee = owner_fee_account.amount * host_fee_denominator
-            / (host_fee_denominator - host_fee_numerator);
+        let host_fee = token_amount(&host_fee_account);
+        let owner_fee = token_amount(&pool_ab.pool_fee_account) - owner_fee_before;
+        assert!(host_fee > 0);
         assert_eq!(
-            total_fee,
-            host_fee_account.amount + owner_fee_account.amount
+            u128::from(host_fee),
+            pool_ab
+                .fees
+                .host_fee(u128::from(host_fee + owner_fee))
+                .unwrap()
         );
     }
+    fn unpack_concentrated_liquidity_state(swap_account: &Account) -> ConcentratedLiquidityState {
+        ConcentratedLiquidityState::unpack(&swap_account.data[SwapVersion::LATEST_LEN..]).unwrap()
+    }
+
+    fn token_amount(account: &Account) -> u64 {
+        spl_token::state::Account::unpack(&account.data)
+            .unwrap()
+            .amount
+    }
 
     #[test]
-    fn test_invalid_swap() {
+    fn test_concentrated_liquidity() {
         let user_key = Pubkey::new_unique();
-        let swapper_key = Pubkey::new_unique();
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 4;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 10;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 5;
-        let host_fee_numerator = 9;
-        let host_fee_denominator = 100;
+        let wide_owner_key = Pubkey::new_unique();
+        let narrow_owner_key = Pubkey::new_unique();
+        let trader_key = Pubkey::new_unique();
         let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
+            trade_fee_numerator: 3,
+            trade_fee_denominator: 1000,
+            owner_trade_fee_numerator: 0,
+            owner_trade_fee_denominator: 0,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
         };
-
-        let token_a_amount = 1000;
-        let token_b_amount = 5000;
-        let curve_type = CurveType::ConstantProduct;
+        let token_a_amount = 1_000_000;
+        let token_b_amount = 1_000_000;
         let swap_curve = SwapCurve {
-            curve_type,
-            calculator: Box::new(ConstantProductCurve {}),
+            curve_type: CurveType::ConcentratedLiquidity,
+            calculator: Box::new(ConcentratedLiquidityCurve {
+                tick_spacing: 10,
+                min_position_liquidity: 1_000,
+            }),
         };
         let mut accounts =
             SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
 
-        let initial_a = token_a_amount / 5;
-        let initial_b = token_b_amount / 5;
-        let minimum_token_b_amount = initial_b / 2;
-
-        let swap_token_a_key = accounts.token_a_key;
-        let swap_token_b_key = accounts.token_b_key;
+        // no room for the ticks
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            accounts.initialize_swap()
+        );
+        accounts.swap_account = Account::new(
+            0,
+            SwapVersion::LATEST_LEN + ConcentratedLiquidityState::LEN,
+            &SWAP_PROGRAM_ID,
+        );
 
-        // swap not initialized
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(ProgramError::UninitializedAccount),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
-        }
+        // owner trade fees are paid in pool tokens
+        accounts.fees.owner_trade_fee_numerator = 1;
+        accounts.fees.owner_trade_fee_denominator = 100;
+        assert_eq!(
+            Err(SwapError::InvalidFee.into()),
+            accounts.initialize_swap()
+        );
+        accounts.fees.owner_trade_fee_numerator = 0;
+        accounts.fees.owner_trade_fee_denominator = 0;
 
+        set_test_slot(10);
         accounts.initialize_swap().unwrap();
-
-        // wrong swap account program id
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let old_swap_account = accounts.swap_account;
-            let mut wrong_swap_account = old_swap_account.clone();
-            wrong_swap_account.owner = TOKEN_PROGRAM_ID;
-            accounts.swap_account = wrong_swap_account;
-            assert_eq!(
-                Err(ProgramError::IncorrectProgramId),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
+        let state = unpack_concentrated_liquidity_state(&accounts.swap_account);
+        assert_eq!(state.sqrt_price, 1 << 64);
+        assert_eq!(state.tick_current, 0);
+        assert_eq!(state.liquidity, 0);
+
+        // the admin cannot add owner trade fees later either
+        let mut owner_fees = accounts.fees.clone();
+        owner_fees.owner_trade_fee_numerator = 1;
+        owner_fees.owner_trade_fee_denominator = 100;
+        assert_eq!(
+            Err(SwapError::InvalidFee.into()),
+            do_process_instruction(
+                set_fees(
+                    &SWAP_PROGRAM_ID,
+                    &accounts.swap_key,
+                    &accounts.admin_key,
+                    owner_fees,
                 )
-            );
-            accounts.swap_account = old_swap_account;
-        }
+                .unwrap(),
+                vec![&mut accounts.swap_account, &mut Account::default()],
+            )
+        );
 
-        // wrong nonce
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let old_authority = accounts.authority_key;
-            let (bad_authority_key, _nonce) = Pubkey::find_program_address(
-                &[&accounts.swap_key.to_bytes()[..]],
-                &TOKEN_PROGRAM_ID,
-            );
-            accounts.authority_key = bad_authority_key;
-            assert_eq!(
-                Err(SwapError::InvalidProgramAddress.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
-            accounts.authority_key = old_authority;
-        }
+        // positions replace pool tokens
+        let (
+            wide_token_a_key,
+            mut wide_token_a_account,
+            wide_token_b_key,
+            mut wide_token_b_account,
+            wide_pool_key,
+            mut wide_pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &wide_owner_key, 1_000_000, 1_000_000, 0);
+        assert_eq!(
+            Err(SwapError::UnsupportedCurveOperation.into()),
+            accounts.deposit_all_token_types(
+                &wide_owner_key,
+                &wide_token_a_key,
+                &mut wide_token_a_account,
+                &wide_token_b_key,
+                &mut wide_token_b_account,
+                &wide_pool_key,
+                &mut wide_pool_account,
+                1,
+                1_000,
+                1_000,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::UnsupportedCurveOperation.into()),
+            accounts.withdraw_all_token_types(
+                &wide_owner_key,
+                &wide_pool_key,
+                &mut wide_pool_account,
+                &wide_token_a_key,
+                &mut wide_token_a_account,
+                &wide_token_b_key,
+                &mut wide_token_b_account,
+                1,
+                0,
+                0,
+            )
+        );
 
-        // wrong token program id
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let wrong_program_id = Pubkey::new_unique();
-            assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
-                do_process_instruction(
-                    swap(
-                        &SWAP_PROGRAM_ID,
-                        &wrong_program_id,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.authority_key,
-                        &token_a_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        None,
-                        Swap {
-                            amount_in: initial_a,
-                            minimum_amount_out: minimum_token_b_amount,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut Account::default(),
-                        &mut token_a_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut Account::default(),
-                    ],
-                ),
-            );
-        }
+        let wide_position_key = Pubkey::new_unique();
+        let mut wide_position_account = Account::new(0, Position::LEN, &SWAP_PROGRAM_ID);
+        assert_eq!(
+            Err(SwapError::InvalidTickRange.into()),
+            accounts.open_position(
+                &wide_owner_key,
+                &wide_position_key,
+                &mut wide_position_account,
+                -1_005,
+                1_000,
+            )
+        );
+        accounts
+            .open_position(
+                &wide_owner_key,
+                &wide_position_key,
+                &mut wide_position_account,
+                -1_000,
+                1_000,
+            )
+            .unwrap();
+        assert_eq!(
+            Err(SwapError::AlreadyInUse.into()),
+            accounts.open_position(
+                &wide_owner_key,
+                &wide_position_key,
+                &mut wide_position_account,
+                -1_000,
+                1_000,
+            )
+        );
+
+        let (
+            narrow_token_a_key,
+            mut narrow_token_a_account,
+            narrow_token_b_key,
+            mut narrow_token_b_account,
+            _,
+            _,
+        ) = accounts.setup_token_accounts(&user_key, &narrow_owner_key, 1_000_000, 1_000_000, 0);
+        let narrow_position_key = Pubkey::new_unique();
+        let mut narrow_position_account = Account::new(0, Position::LEN, &SWAP_PROGRAM_ID);
+        accounts
+            .open_position(
+                &narrow_owner_key,
+                &narrow_position_key,
+                &mut narrow_position_account,
+                -10,
+                10,
+            )
+            .unwrap();
 
-        // not enough token a to swap
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a * 2,
-                    minimum_token_b_amount * 2,
-                )
-            );
-        }
+        // deposits follow the price and range of the position
+        let liquidity = 10_000_000;
+        let mut expected_state = unpack_concentrated_liquidity_state(&accounts.swap_account);
+        let mut expected_position = Position::unpack(&wide_position_account.data).unwrap();
+        let (deposit_a, deposit_b) = expected_state
+            .update_position(&mut expected_position, liquidity as i128)
+            .unwrap();
+        let (deposit_a, deposit_b) = (deposit_a as u64, deposit_b as u64);
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            accounts.increase_liquidity(
+                &wide_owner_key,
+                &wide_position_key,
+                &mut wide_position_account,
+                &wide_token_a_key,
+                &mut wide_token_a_account,
+                &wide_token_b_key,
+                &mut wide_token_b_account,
+                liquidity,
+                deposit_a - 1,
+                deposit_b,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::IncorrectPosition.into()),
+            accounts.increase_liquidity(
+                &narrow_owner_key,
+                &wide_position_key,
+                &mut wide_position_account,
+                &narrow_token_a_key,
+                &mut narrow_token_a_account,
+                &narrow_token_b_key,
+                &mut narrow_token_b_account,
+                liquidity,
+                deposit_a,
+                deposit_b,
+            )
+        );
+        do_process_instruction(
+            set_paused(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &accounts.admin_key,
+                true,
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(SwapError::SwapPaused.into()),
+            accounts.increase_liquidity(
+                &wide_owner_key,
+                &wide_position_key,
+                &mut wide_position_account,
+                &wide_token_a_key,
+                &mut wide_token_a_account,
+                &wide_token_b_key,
+                &mut wide_token_b_account,
+                liquidity,
+                deposit_a,
+                deposit_b,
+            )
+        );
+        do_process_instruction(
+            set_paused(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &accounts.admin_key,
+                false,
+            )
+            .unwrap(),
+            vec![&mut accounts.swap_account, &mut Account::default()],
+        )
+        .unwrap();
 
-        // wrong swap token A / B accounts
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let user_transfer_key = Pubkey::new_unique();
-            assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                do_process_instruction(
-                    swap(
-                        &SWAP_PROGRAM_ID,
-                        &TOKEN_PROGRAM_ID,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &user_transfer_key,
-                        &token_a_key,
-                        &token_a_key,
-                        &token_b_key,
-                        &token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        None,
-                        Swap {
-                            amount_in: initial_a,
-                            minimum_amount_out: minimum_token_b_amount,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut Account::default(),
-                        &mut token_a_account.clone(),
-                        &mut token_a_account,
-                        &mut token_b_account.clone(),
-                        &mut token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut Account::default(),
-                    ],
-                ),
-            );
-        }
+        set_test_slot(20);
+        accounts
+            .increase_liquidity(
+                &wide_owner_key,
+                &wide_position_key,
+                &mut wide_position_account,
+                &wide_token_a_key,
+                &mut wide_token_a_account,
+                &wide_token_b_key,
+                &mut wide_token_b_account,
+                liquidity,
+                deposit_a,
+                deposit_b,
+            )
+            .unwrap();
+        assert_eq!(token_amount(&wide_token_a_account), 1_000_000 - deposit_a);
+        assert_eq!(token_amount(&wide_token_b_account), 1_000_000 - deposit_b);
+        assert_eq!(
+            token_amount(&accounts.token_a_account),
+            token_a_amount + deposit_a
+        );
+        assert_eq!(
+            token_amount(&accounts.token_b_account),
+            token_b_amount + deposit_b
+        );
+        assert_eq!(
+            unpack_concentrated_liquidity_state(&accounts.swap_account),
+            expected_state
+        );
+        assert_eq!(
+            Position::unpack(&wide_position_account.data).unwrap(),
+            expected_position
+        );
+        // the price accumulator follows the pool price, not the vault amounts
+        assert_eq!(
+            unpack_price_accumulator(&accounts.swap_account).price_a_cumulative,
+            10 << 64
+        );
 
-        // wrong user token A / B accounts
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(TokenError::MintMismatch.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
-        }
+        accounts
+            .increase_liquidity(
+                &narrow_owner_key,
+                &narrow_position_key,
+                &mut narrow_position_account,
+                &narrow_token_a_key,
+                &mut narrow_token_a_account,
+                &narrow_token_b_key,
+                &mut narrow_token_b_account,
+                liquidity,
+                1_000_000,
+                1_000_000,
+            )
+            .unwrap();
+        let state = unpack_concentrated_liquidity_state(&accounts.swap_account);
+        assert_eq!(state.liquidity, 2 * liquidity);
+        assert_eq!(state.ticks.len(), 4);
 
-        // swap from a to a
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account.clone(),
-                    &swap_token_a_key,
-                    &swap_token_a_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
-        }
+        // swap A to B, leaving the narrow range
+        let (trader_a_key, mut trader_a_account, trader_b_key, mut trader_b_account, _, _) =
+            accounts.setup_token_accounts(&user_key, &trader_key, 100_000, 100_000, 0);
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+        let mut expected_state = unpack_concentrated_liquidity_state(&accounts.swap_account);
+        let (swap_a, swap_b) = swap_token_amounts(&accounts);
+        let expected = expected_state
+            .swap(20_000, swap_a, swap_b, TradeDirection::AtoB, &accounts.fees)
+            .unwrap();
+        accounts
+            .swap(
+                &trader_key,
+                &trader_a_key,
+                &mut trader_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &trader_b_key,
+                &mut trader_b_account,
+                20_000,
+                expected.destination_amount_swapped as u64,
+            )
+            .unwrap();
+        assert_eq!(token_amount(&trader_a_account), 80_000);
+        assert_eq!(
+            token_amount(&trader_b_account),
+            100_000 + expected.destination_amount_swapped as u64
+        );
+        let state = unpack_concentrated_liquidity_state(&accounts.swap_account);
+        assert_eq!(state, expected_state);
+        assert!(state.tick_current < -10);
+        assert_eq!(state.liquidity, liquidity);
+
+        // swap B to A, crossing the narrow range back up
+        let (swap_a, swap_b) = swap_token_amounts(&accounts);
+        let expected = expected_state
+            .swap(40_000, swap_b, swap_a, TradeDirection::BtoA, &accounts.fees)
+            .unwrap();
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            accounts.swap(
+                &trader_key,
+                &trader_b_key,
+                &mut trader_b_account,
+                &swap_token_b_key,
+                &swap_token_a_key,
+                &trader_a_key,
+                &mut trader_a_account,
+                40_000,
+                expected.destination_amount_swapped as u64 + 1,
+            )
+        );
+        accounts
+            .swap(
+                &trader_key,
+                &trader_b_key,
+                &mut trader_b_account,
+                &swap_token_b_key,
+                &swap_token_a_key,
+                &trader_a_key,
+                &mut trader_a_account,
+                40_000,
+                expected.destination_amount_swapped as u64,
+            )
+            .unwrap();
+        let state = unpack_concentrated_liquidity_state(&accounts.swap_account);
+        assert_eq!(state, expected_state);
+        assert!(state.tick_current >= 10);
+        assert_eq!(state.liquidity, liquidity);
+
+        // removing all the liquidity pays out the fees earned in both tokens
+        let mut expected_position = Position::unpack(&narrow_position_account.data).unwrap();
+        let (withdraw_a, withdraw_b) = expected_state
+            .update_position(&mut expected_position, -(liquidity as i128))
+            .unwrap();
+        assert!(expected_position.fees_owed_a > 0);
+        assert!(expected_position.fees_owed_b > 0);
+        let withdraw_a = withdraw_a as u64 + expected_position.fees_owed_a;
+        let withdraw_b = withdraw_b as u64 + expected_position.fees_owed_b;
+        let narrow_a_before = token_amount(&narrow_token_a_account);
+        let narrow_b_before = token_amount(&narrow_token_b_account);
+        assert_eq!(
+            Err(SwapError::InsufficientLiquidity.into()),
+            accounts.decrease_liquidity(
+                &narrow_owner_key,
+                &narrow_position_key,
+                &mut narrow_position_account,
+                &narrow_token_a_key,
+                &mut narrow_token_a_account,
+                &narrow_token_b_key,
+                &mut narrow_token_b_account,
+                liquidity + 1,
+                0,
+                0,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            accounts.decrease_liquidity(
+                &narrow_owner_key,
+                &narrow_position_key,
+                &mut narrow_position_account,
+                &narrow_token_a_key,
+                &mut narrow_token_a_account,
+                &narrow_token_b_key,
+                &mut narrow_token_b_account,
+                liquidity,
+                withdraw_a + 1,
+                withdraw_b,
+            )
+        );
+        accounts
+            .decrease_liquidity(
+                &narrow_owner_key,
+                &narrow_position_key,
+                &mut narrow_position_account,
+                &narrow_token_a_key,
+                &mut narrow_token_a_account,
+                &narrow_token_b_key,
+                &mut narrow_token_b_account,
+                liquidity,
+                withdraw_a,
+                withdraw_b,
+            )
+            .unwrap();
+        assert_eq!(
+            token_amount(&narrow_token_a_account),
+            narrow_a_before + withdraw_a
+        );
+        assert_eq!(
+            token_amount(&narrow_token_b_account),
+            narrow_b_before + withdraw_b
+        );
+        let position = Position::unpack(&narrow_position_account.data).unwrap();
+        assert_eq!(position.liquidity, 0);
+        assert_eq!(position.fees_owed_a, 0);
+        assert_eq!(position.fees_owed_b, 0);
+        let state = unpack_concentrated_liquidity_state(&accounts.swap_account);
+        assert_eq!(state.ticks.len(), 2);
+
+        // no liquidity only collects the fees
+        let wide_a_before = token_amount(&wide_token_a_account);
+        let wide_b_before = token_amount(&wide_token_b_account);
+        accounts
+            .decrease_liquidity(
+                &wide_owner_key,
+                &wide_position_key,
+                &mut wide_position_account,
+                &wide_token_a_key,
+                &mut wide_token_a_account,
+                &wide_token_b_key,
+                &mut wide_token_b_account,
+                0,
+                1,
+                1,
+            )
+            .unwrap();
+        assert!(token_amount(&wide_token_a_account) > wide_a_before);
+        assert!(token_amount(&wide_token_b_account) > wide_b_before);
+        let position = Position::unpack(&wide_position_account.data).unwrap();
+        assert_eq!(position.liquidity, liquidity);
+        assert_eq!(position.fees_owed_a, 0);
+        assert_eq!(position.fees_owed_b, 0);
+    }
 
-        // incorrect mint provided
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let (pool_mint_key, pool_mint_account) =
-                create_mint(&TOKEN_PROGRAM_ID, &accounts.authority_key, None);
-            let old_pool_key = accounts.pool_mint_key;
-            let old_pool_account = accounts.pool_mint_account;
-            accounts.pool_mint_key = pool_mint_key;
-            accounts.pool_mint_account = pool_mint_account;
+    #[test]
+    fn test_concentrated_liquidity_tick_table() {
+        let user_key = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 3,
+            trade_fee_denominator: 1000,
+            owner_trade_fee_numerator: 0,
+            owner_trade_fee_denominator: 0,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
+        };
+        let min_position_liquidity = 1_000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConcentratedLiquidity,
+            calculator: Box::new(ConcentratedLiquidityCurve {
+                tick_spacing: 10,
+                min_position_liquidity,
+            }),
+        };
+        let mut accounts = SwapAccountInfo::new(&user_key, fees, swap_curve, 1_000_000, 1_000_000);
+        accounts.swap_account = Account::new(
+            0,
+            SwapVersion::LATEST_LEN + ConcentratedLiquidityState::LEN,
+            &SWAP_PROGRAM_ID,
+        );
+        accounts.initialize_swap().unwrap();
+        let (token_a_key, mut token_a_account, token_b_key, mut token_b_account, _, _) =
+            accounts.setup_token_accounts(&user_key, &owner_key, 1_000_000, 1_000_000, 0);
 
-            assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
+        let open = |accounts: &mut SwapAccountInfo, tick_lower: i32, tick_upper: i32| {
+            let position_key = Pubkey::new_unique();
+            let mut position_account = Account::new(0, Position::LEN, &SWAP_PROGRAM_ID);
+            accounts
+                .open_position(
+                    &owner_key,
+                    &position_key,
+                    &mut position_account,
+                    tick_lower,
+                    tick_upper,
                 )
-            );
+                .unwrap();
+            (position_key, position_account)
+        };
 
-            accounts.pool_mint_key = old_pool_key;
-            accounts.pool_mint_account = old_pool_account;
-        }
+        // positions hold no liquidity or at least the minimum
+        let (position_key, mut position_account) = open(&mut accounts, -10, 10);
+        assert_eq!(
+            Err(SwapError::PositionLiquidityTooLow.into()),
+            accounts.increase_liquidity(
+                &owner_key,
+                &position_key,
+                &mut position_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                min_position_liquidity - 1,
+                1_000_000,
+                1_000_000,
+            )
+        );
+        accounts
+            .increase_liquidity(
+                &owner_key,
+                &position_key,
+                &mut position_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                min_position_liquidity,
+                1_000_000,
+                1_000_000,
+            )
+            .unwrap();
+        assert_eq!(
+            Err(SwapError::PositionLiquidityTooLow.into()),
+            accounts.decrease_liquidity(
+                &owner_key,
+                &position_key,
+                &mut position_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                1,
+                0,
+                0,
+            )
+        );
 
-        // incorrect fee account provided
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                wrong_pool_key,
-                wrong_pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let old_pool_fee_account = accounts.pool_fee_account;
-            let old_pool_fee_key = accounts.pool_fee_key;
-            accounts.pool_fee_account = wrong_pool_account;
-            accounts.pool_fee_key = wrong_pool_key;
-            assert_eq!(
-                Err(SwapError::IncorrectFeeAccount.into()),
-                accounts.swap(
-                    &swapper_key,
+        // fill the tick table with disjoint ranges above the price
+        let mut positions = vec![];
+        for i in 1..concentrated_liquidity::MAX_TICKS as i32 / 2 {
+            let (position_key, mut position_account) = open(&mut accounts, 20 * i, 20 * i + 10);
+            accounts
+                .increase_liquidity(
+                    &owner_key,
+                    &position_key,
+                    &mut position_account,
                     &token_a_key,
                     &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
                     &token_b_key,
                     &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
+                    min_position_liquidity,
+                    1_000_000,
+                    1_000_000,
                 )
-            );
-            accounts.pool_fee_account = old_pool_fee_account;
-            accounts.pool_fee_key = old_pool_fee_key;
+                .unwrap();
+            positions.push((position_key, position_account));
         }
+        let state = unpack_concentrated_liquidity_state(&accounts.swap_account);
+        assert_eq!(state.ticks.len(), concentrated_liquidity::MAX_TICKS);
 
-        // no approval
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let user_transfer_key = Pubkey::new_unique();
-            assert_eq!(
-                Err(TokenError::OwnerMismatch.into()),
-                do_process_instruction(
-                    swap(
-                        &SWAP_PROGRAM_ID,
-                        &TOKEN_PROGRAM_ID,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &user_transfer_key,
-                        &token_a_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        None,
-                        Swap {
-                            amount_in: initial_a,
-                            minimum_amount_out: minimum_token_b_amount,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut Account::default(),
-                        &mut token_a_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut Account::default(),
-                    ],
-                ),
-            );
-        }
+        // new ticks are rejected, leaving the pool untouched
+        let (blocked_key, mut blocked_account) = open(&mut accounts, -30, -20);
+        assert_eq!(
+            Err(SwapError::TooManyTicks.into()),
+            accounts.increase_liquidity(
+                &owner_key,
+                &blocked_key,
+                &mut blocked_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                min_position_liquidity,
+                1_000_000,
+                1_000_000,
+            )
+        );
+        assert_eq!(
+            unpack_concentrated_liquidity_state(&accounts.swap_account),
+            state
+        );
 
-        // output token value 0
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(SwapError::ZeroTradingTokens.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &swap_token_b_key,
-                    &swap_token_a_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    1,
-                    1,
-                )
-            );
-        }
+        // ranges bounded by existing ticks can still be provided
+        let (shared_key, mut shared_account) = open(&mut accounts, -10, 30);
+        accounts
+            .increase_liquidity(
+                &owner_key,
+                &shared_key,
+                &mut shared_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                min_position_liquidity,
+                1_000_000,
+                1_000_000,
+            )
+            .unwrap();
+        let state = unpack_concentrated_liquidity_state(&accounts.swap_account);
+        assert_eq!(state.ticks.len(), concentrated_liquidity::MAX_TICKS);
 
-        // slippage exceeded: minimum out amount too high
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount * 2,
-                )
-            );
-        }
+        // closing a position frees its ticks for new ranges
+        let (position_key, position_account) = &mut positions[1];
+        accounts
+            .decrease_liquidity(
+                &owner_key,
+                position_key,
+                position_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                min_position_liquidity,
+                0,
+                0,
+            )
+            .unwrap();
+        let state = unpack_concentrated_liquidity_state(&accounts.swap_account);
+        assert_eq!(state.ticks.len(), concentrated_liquidity::MAX_TICKS - 2);
+        accounts
+            .increase_liquidity(
+                &owner_key,
+                &blocked_key,
+                &mut blocked_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                min_position_liquidity,
+                1_000_000,
+                1_000_000,
+            )
+            .unwrap();
+        let state = unpack_concentrated_liquidity_state(&accounts.swap_account);
+        assert_eq!(state.ticks.len(), concentrated_liquidity::MAX_TICKS);
+    }
 
-        // invalid input: can't use swap pool as user source / dest
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
-            let authority_key = accounts.authority_key;
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.swap(
-                    &authority_key,
-                    &swap_token_a_key,
-                    &mut swap_token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
-            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &swap_token_b_key,
-                    &mut swap_token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
+    #[test]
+    fn test_weighted_pool() {
+        let user_key = Pubkey::new_unique();
+        let trader_key = Pubkey::new_unique();
+        let pool_key = Pubkey::new_unique();
+        let (authority_key, nonce) =
+            Pubkey::find_program_address(&[&pool_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
+        let fees = Fees {
+            trade_fee_numerator: 25,
+            trade_fee_denominator: 10_000,
+            owner_trade_fee_numerator: 5,
+            owner_trade_fee_denominator: 10_000,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 100,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
+        };
+        let weights = vec![50, 30, 20];
+        let total_weight = 100;
+        let (token_a_amount, token_b_amount, token_c_amount) = (1_000_000, 2_000_000, 500_000);
+
+        let (token_a_mint_key, mut token_a_mint_account) =
+            create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
+        let (token_b_mint_key, mut token_b_mint_account) =
+            create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
+        let (token_c_mint_key, mut token_c_mint_account) =
+            create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
+        let (token_a_key, mut token_a_account) = mint_token(
+            &TOKEN_PROGRAM_ID,
+            &token_a_mint_key,
+            &mut token_a_mint_account,
+            &user_key,
+            &authority_key,
+            token_a_amount,
+        );
+        let (token_b_key, mut token_b_account) = mint_token(
+            &TOKEN_PROGRAM_ID,
+            &token_b_mint_key,
+            &mut token_b_mint_account,
+            &user_key,
+            &authority_key,
+            token_b_amount,
+        );
+        let (token_c_key, mut token_c_account) = mint_token(
+            &TOKEN_PROGRAM_ID,
+            &token_c_mint_key,
+            &mut token_c_mint_account,
+            &user_key,
+            &authority_key,
+            token_c_amount,
+        );
+        let (pool_mint_key, mut pool_mint_account) =
+            create_mint(&TOKEN_PROGRAM_ID, &authority_key, None);
+        let (pool_fee_key, mut pool_fee_account) = mint_token(
+            &TOKEN_PROGRAM_ID,
+            &pool_mint_key,
+            &mut pool_mint_account,
+            &authority_key,
+            &user_key,
+            0,
+        );
+        let (pool_token_key, mut pool_token_account) = mint_token(
+            &TOKEN_PROGRAM_ID,
+            &pool_mint_key,
+            &mut pool_mint_account,
+            &authority_key,
+            &user_key,
+            0,
+        );
+        let mut pool_account = Account::new(0, WeightedPool::get_packed_len(), &SWAP_PROGRAM_ID);
+        let token_keys = [token_a_key, token_b_key, token_c_key];
+        let initialize_instruction = |weights: Vec<u64>| {
+            initialize_weighted_pool(
+                &SWAP_PROGRAM_ID,
+                &TOKEN_PROGRAM_ID,
+                &pool_key,
+                &authority_key,
+                &pool_mint_key,
+                &pool_fee_key,
+                &pool_token_key,
+                &token_keys,
+                nonce,
+                fees.clone(),
+                weights,
+            )
+            .unwrap()
+        };
+
+        // one token short
+        {
+            let mut instruction = initialize_instruction(weights.clone());
+            instruction.accounts.pop();
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                do_process_instruction(
+                    instruction,
+                    vec![
+                        &mut pool_account,
+                        &mut Account::default(),
+                        &mut pool_mint_account,
+                        &mut pool_fee_account,
+                        &mut pool_token_account,
+                        &mut Account::default(),
+                        &mut token_a_account,
+                        &mut token_b_account,
+                    ],
                 )
             );
         }
 
-        // still correct: constraint specified, no host fee account
-        {
-            let authority_key = accounts.authority_key;
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
-            let owner_key = &swapper_key.to_string();
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let constraints = Some(SwapConstraints {
-                owner_key,
-                valid_curve_types: &[],
-                fees: &fees,
-            });
-            do_process_instruction_with_fee_constraints(
-                swap(
-                    &SWAP_PROGRAM_ID,
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.swap_key,
-                    &accounts.authority_key,
-                    &accounts.authority_key,
-                    &token_a_key,
-                    &accounts.token_a_key,
-                    &accounts.token_b_key,
-                    &token_b_key,
-                    &accounts.pool_mint_key,
-                    &accounts.pool_fee_key,
-                    None,
-                    Swap {
-                        amount_in: initial_a,
-                        minimum_amount_out: minimum_token_b_amount,
-                    },
-                )
-                .unwrap(),
+        // token weight too small compared to the others
+        assert_eq!(
+            Err(SwapError::InvalidCurve.into()),
+            do_process_instruction(
+                initialize_instruction(vec![1, 150, 100]),
                 vec![
-                    &mut accounts.swap_account,
+                    &mut pool_account,
                     &mut Account::default(),
+                    &mut pool_mint_account,
+                    &mut pool_fee_account,
+                    &mut pool_token_account,
                     &mut Account::default(),
                     &mut token_a_account,
-                    &mut accounts.token_a_account,
-                    &mut accounts.token_b_account,
                     &mut token_b_account,
-                    &mut accounts.pool_mint_account,
-                    &mut accounts.pool_fee_account,
+                    &mut token_c_account,
+                ],
+            )
+        );
+
+        do_process_instruction(
+            initialize_instruction(weights.clone()),
+            vec![
+                &mut pool_account,
+                &mut Account::default(),
+                &mut pool_mint_account,
+                &mut pool_fee_account,
+                &mut pool_token_account,
+                &mut Account::default(),
+                &mut token_a_account,
+                &mut token_b_account,
+                &mut token_c_account,
+            ],
+        )
+        .unwrap();
+        let pool = WeightedPool::unpack(&pool_account.data).unwrap();
+        assert_eq!(pool.tokens.len(), 3);
+        assert_eq!(pool.tokens[2].mint, token_c_mint_key);
+        assert_eq!(pool.tokens[2].weight, 20);
+        assert_eq!(
+            u128::from(token_amount(&pool_token_account)),
+            INITIAL_SWAP_POOL_AMOUNT
+        );
+
+        // already initialized
+        assert_eq!(
+            Err(SwapError::AlreadyInUse.into()),
+            do_process_instruction(
+                initialize_instruction(weights.clone()),
+                vec![
+                    &mut pool_account,
                     &mut Account::default(),
+                    &mut pool_mint_account,
+                    &mut pool_fee_account,
+                    &mut pool_token_account,
+                    &mut Account::default(),
+                    &mut token_a_account,
+                    &mut token_b_account,
+                    &mut token_c_account,
                 ],
-                &constraints,
+            )
+        );
+
+        // swap A for C, which are not next to each other in the pool
+        let amount_in = 100_000;
+        let (source_key, mut source_account) = mint_token(
+            &TOKEN_PROGRAM_ID,
+            &token_a_mint_key,
+            &mut token_a_mint_account,
+            &user_key,
+            &trader_key,
+            amount_in,
+        );
+        let (destination_key, mut destination_account) = mint_token(
+            &TOKEN_PROGRAM_ID,
+            &token_c_mint_key,
+            &mut token_c_mint_account,
+            &user_key,
+            &trader_key,
+            0,
+        );
+        let total_fees = fees.trading_fee(amount_in.into()).unwrap()
+            + fees.owner_trading_fee(amount_in.into()).unwrap();
+        let amount_out = to_u64(
+            weighted::swap(
+                u128::from(amount_in) - total_fees,
+                token_a_amount.into(),
+                token_c_amount.into(),
+                50,
+                20,
+            )
+            .unwrap()
+            .destination_amount_swapped,
+        )
+        .unwrap();
+        let pool_supply = spl_token::state::Mint::unpack(&pool_mint_account.data)
+            .unwrap()
+            .supply;
+        let swap_quote = WeightedPoolQuoter::new(&pool, pool_supply)
+            .swap(
+                &pool.tokens[0],
+                token_a_amount,
+                &pool.tokens[2],
+                token_c_amount,
+                amount_in,
             )
             .unwrap();
-        }
+        assert_eq!(swap_quote.amount_out, amount_out);
+        assert!(swap_quote
+            .price_impact
+            .unwrap()
+            .greater_than(&PreciseNumber::new(0).unwrap()));
+        let swap_instruction = |pool_source_key: &Pubkey, minimum_amount_out| {
+            weighted_swap(
+                &SWAP_PROGRAM_ID,
+                &TOKEN_PROGRAM_ID,
+                &pool_key,
+                &authority_key,
+                &trader_key,
+                &source_key,
+                pool_source_key,
+                &token_c_key,
+                &destination_key,
+                &pool_mint_key,
+                &pool_fee_key,
+                Swap {
+                    amount_in,
+                    minimum_amount_out,
+                },
+            )
+            .unwrap()
+        };
+
+        // pool source account not held by the pool
+        assert_eq!(
+            Err(SwapError::IncorrectSwapAccount.into()),
+            do_process_instruction(
+                swap_instruction(&destination_key, 0),
+                vec![
+                    &mut pool_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut source_account,
+                    &mut destination_account.clone(),
+                    &mut token_c_account,
+                    &mut destination_account,
+                    &mut pool_mint_account,
+                    &mut pool_fee_account,
+                    &mut Account::default(),
+                ],
+            )
+        );
+
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            do_process_instruction(
+                swap_instruction(&token_a_key, amount_out + 1),
+                vec![
+                    &mut pool_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut source_account,
+                    &mut token_a_account,
+                    &mut token_c_account,
+                    &mut destination_account,
+                    &mut pool_mint_account,
+                    &mut pool_fee_account,
+                    &mut Account::default(),
+                ],
+            )
+        );
+
+        do_process_instruction(
+            swap_instruction(&token_a_key, amount_out),
+            vec![
+                &mut pool_account,
+                &mut Account::default(),
+                &mut Account::default(),
+                &mut source_account,
+                &mut token_a_account,
+                &mut token_c_account,
+                &mut destination_account,
+                &mut pool_mint_account,
+                &mut pool_fee_account,
+                &mut Account::default(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(token_amount(&source_account), 0);
+        assert_eq!(token_amount(&destination_account), amount_out);
+        assert_eq!(token_amount(&token_a_account), token_a_amount + amount_in);
+        assert_eq!(token_amount(&token_c_account), token_c_amount - amount_out);
+        assert_eq!(token_amount(&token_b_account), token_b_amount);
+        assert!(swap_quote.fees.owner_fee > 0);
+        assert_eq!(token_amount(&pool_fee_account), swap_quote.fees.owner_fee);
+
+        // deposit B only
+        let deposit_amount = 200_000;
+        let (deposit_source_key, mut deposit_source_account) = mint_token(
+            &TOKEN_PROGRAM_ID,
+            &token_b_mint_key,
+            &mut token_b_mint_account,
+            &user_key,
+            &trader_key,
+            deposit_amount,
+        );
+        let (trader_pool_key, mut trader_pool_account) = mint_token(
+            &TOKEN_PROGRAM_ID,
+            &pool_mint_key,
+            &mut pool_mint_account,
+            &authority_key,
+            &trader_key,
+            0,
+        );
+        let pool_supply = spl_token::state::Mint::unpack(&pool_mint_account.data)
+            .unwrap()
+            .supply;
+        let deposit_fee = fees
+            .trading_fee(u128::from(deposit_amount) * (total_weight - 30) / total_weight)
+            .unwrap();
+        let deposit_pool_tokens = weighted::trading_tokens_to_pool_tokens(
+            u128::from(deposit_amount) - deposit_fee,
+            token_b_amount.into(),
+            30,
+            total_weight,
+            pool_supply.into(),
+            RoundDirection::Floor,
+        )
+        .unwrap();
+        let deposit_pool_tokens = to_u64(deposit_pool_tokens).unwrap();
+        let deposit_quote = WeightedPoolQuoter::new(&pool, pool_supply)
+            .deposit_single_token_type_exact_amount_in(
+                &pool.tokens[1],
+                token_b_amount,
+                deposit_amount,
+            )
+            .unwrap();
+        assert_eq!(deposit_quote.pool_token_amount, deposit_pool_tokens);
+        assert_eq!(u128::from(deposit_quote.fees.trade_fee), deposit_fee);
+        let deposit_instruction = |minimum_pool_token_amount| {
+            weighted_deposit_single_token_type_exact_amount_in(
+                &SWAP_PROGRAM_ID,
+                &TOKEN_PROGRAM_ID,
+                &pool_key,
+                &authority_key,
+                &trader_key,
+                &deposit_source_key,
+                &token_b_key,
+                &pool_mint_key,
+                &trader_pool_key,
+                DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount: deposit_amount,
+                    minimum_pool_token_amount,
+                },
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            do_process_instruction(
+                deposit_instruction(deposit_pool_tokens + 1),
+                vec![
+                    &mut pool_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut deposit_source_account,
+                    &mut token_b_account,
+                    &mut pool_mint_account,
+                    &mut trader_pool_account,
+                    &mut Account::default(),
+                ],
+            )
+        );
+        do_process_instruction(
+            deposit_instruction(deposit_pool_tokens),
+            vec![
+                &mut pool_account,
+                &mut Account::default(),
+                &mut Account::default(),
+                &mut deposit_source_account,
+                &mut token_b_account,
+                &mut pool_mint_account,
+                &mut trader_pool_account,
+                &mut Account::default(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            token_amount(&trader_pool_account),
+            deposit_quote.pool_token_amount
+        );
+        assert_eq!(
+            token_amount(&token_b_account),
+            token_b_amount + deposit_amount
+        );
+
+        // withdraw some of the deposit back as B, paying the withdrawal fee
+        let withdraw_amount = 100_000;
+        let pool_supply = spl_token::state::Mint::unpack(&pool_mint_account.data)
+            .unwrap()
+            .supply;
+        let withdraw_fee = fees
+            .trading_fee(u128::from(withdraw_amount) * (total_weight - 30) / total_weight)
+            .unwrap();
+        let burn_pool_tokens = weighted::trading_tokens_to_pool_tokens(
+            u128::from(withdraw_amount) + withdraw_fee,
+            u128::from(token_b_amount + deposit_amount - withdraw_amount) - withdraw_fee,
+            30,
+            total_weight,
+            pool_supply.into(),
+            RoundDirection::Ceiling,
+        )
+        .unwrap();
+        let withdraw_pool_tokens =
+            to_u64(burn_pool_tokens + fees.owner_withdraw_fee(burn_pool_tokens).unwrap()).unwrap();
+        let withdraw_quote = WeightedPoolQuoter::new(&pool, pool_supply)
+            .withdraw_single_token_type_exact_amount_out(
+                &pool.tokens[1],
+                token_b_amount + deposit_amount,
+                withdraw_amount,
+                true,
+            )
+            .unwrap();
+        assert_eq!(withdraw_quote.pool_token_amount, withdraw_pool_tokens);
+        assert_eq!(u128::from(withdraw_quote.fees.trade_fee), withdraw_fee);
+        let (withdraw_destination_key, mut withdraw_destination_account) = mint_token(
+            &TOKEN_PROGRAM_ID,
+            &token_b_mint_key,
+            &mut token_b_mint_account,
+            &user_key,
+            &trader_key,
+            0,
+        );
+        let withdraw_instruction = |maximum_pool_token_amount| {
+            weighted_withdraw_single_token_type_exact_amount_out(
+                &SWAP_PROGRAM_ID,
+                &TOKEN_PROGRAM_ID,
+                &pool_key,
+                &authority_key,
+                &trader_key,
+                &pool_mint_key,
+                &pool_fee_key,
+                &trader_pool_key,
+                &token_b_key,
+                &withdraw_destination_key,
+                WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount: withdraw_amount,
+                    maximum_pool_token_amount,
+                },
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            do_process_instruction(
+                withdraw_instruction(withdraw_pool_tokens - 1),
+                vec![
+                    &mut pool_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                    &mut pool_mint_account,
+                    &mut trader_pool_account,
+                    &mut token_b_account,
+                    &mut withdraw_destination_account,
+                    &mut pool_fee_account,
+                    &mut Account::default(),
+                ],
+            )
+        );
+        let pool_fee_before = token_amount(&pool_fee_account);
+        do_process_instruction(
+            withdraw_instruction(withdraw_pool_tokens),
+            vec![
+                &mut pool_account,
+                &mut Account::default(),
+                &mut Account::default(),
+                &mut pool_mint_account,
+                &mut trader_pool_account,
+                &mut token_b_account,
+                &mut withdraw_destination_account,
+                &mut pool_fee_account,
+                &mut Account::default(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(token_amount(&withdraw_destination_account), withdraw_amount);
+        assert_eq!(
+            token_amount(&trader_pool_account),
+            deposit_pool_tokens - withdraw_pool_tokens
+        );
+        assert_eq!(
+            token_amount(&pool_fee_account) - pool_fee_before,
+            withdraw_quote.fees.owner_fee
+        );
+        assert_eq!(
+            withdraw_quote.fees.owner_fee,
+            withdraw_pool_tokens - to_u64(burn_pool_tokens).unwrap()
+        );
+        assert_eq!(
+            token_amount(&token_b_account),
+            token_b_amount + deposit_amount - withdraw_amount
+        );
+    }
 
-        // invalid mint for host fee account
-        {
-            let authority_key = accounts.authority_key;
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
-            let (
-                bad_token_a_key,
-                mut bad_token_a_account,
-                _token_b_key,
-                mut _token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
-            let owner_key = &swapper_key.to_string();
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let constraints = Some(SwapConstraints {
-                owner_key,
-                valid_curve_types: &[],
-                fees: &fees,
-            });
-            assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                do_process_instruction_with_fee_constraints(
-                    swap(
-                        &SWAP_PROGRAM_ID,
-                        &TOKEN_PROGRAM_ID,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.authority_key,
-                        &token_a_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        Some(&bad_token_a_key),
-                        Swap {
-                            amount_in: initial_a,
-                            minimum_amount_out: 0,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut Account::default(),
-                        &mut Account::default(),
-                        &mut token_a_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut Account::default(),
-                        &mut bad_token_a_account,
-                    ],
-                    &constraints,
-                ),
-            );
-        }
+    fn unpack_proactive_market_maker_curve(swap_account: &Account) -> ProactiveMarketMakerCurve {
+        let token_swap = SwapVersion::unpack(&swap_account.data).unwrap();
+        let mut packed_curve = [0u8; ProactiveMarketMakerCurve::LEN];
+        token_swap
+            .swap_curve()
+            .calculator
+            .pack_into_slice(&mut packed_curve);
+        ProactiveMarketMakerCurve::unpack_from_slice(&packed_curve).unwrap()
     }
 
     #[test]
-    fn test_overdraw_offset_curve() {
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 10;
-        let host_fee_denominator = 100;
-
-        let token_a_amount = 1_000_000_000;
-        let token_b_amount = 0;
+    fn test_proactive_market_maker() {
+        let user_key = Pubkey::new_unique();
+        let trader_key = Pubkey::new_unique();
         let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 1000,
+            owner_trade_fee_numerator: 0,
+            owner_trade_fee_denominator: 0,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
         };
-
-        let token_b_offset = 2_000_000;
+        let token_a_amount = 1_000_000;
+        let token_b_amount = 2_000_000;
+        let k = 500_000;
+        let max_oracle_staleness_slots = 25;
+        // the oracle price is only read from the feed
         let swap_curve = SwapCurve {
-            curve_type: CurveType::Offset,
-            calculator: Box::new(OffsetCurve { token_b_offset }),
+            curve_type: CurveType::ProactiveMarketMaker,
+            calculator: Box::new(ProactiveMarketMakerCurve {
+                k,
+                oracle_price: 0,
+                max_oracle_staleness_slots: max_oracle_staleness_slots as u32,
+                max_oracle_confidence_bps: 100,
+            }),
         };
-        let user_key = Pubkey::new_unique();
-        let swapper_key = Pubkey::new_unique();
-
         let mut accounts =
             SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
 
-        accounts.initialize_swap().unwrap();
+        let oracle_feed_key = Pubkey::new_unique();
+        // 2 token B per token A, give or take 1%
+        let mut oracle_feed = OracleFeed {
+            version: 1,
+            publish_slot: 100,
+            price: 2_000,
+            confidence: 20,
+            exponent: -3,
+        };
+        let mut oracle_feed_account = Account::new(0, OracleFeed::LEN, &Pubkey::new_unique());
+        OracleFeed::pack(oracle_feed.clone(), &mut oracle_feed_account.data).unwrap();
+        set_test_slot(100);
 
-        let swap_token_a_key = accounts.token_a_key;
-        let swap_token_b_key = accounts.token_b_key;
-        let initial_a = 500_000;
-        let initial_b = 1_000;
+        // no room for the oracle feed
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            accounts.initialize_swap()
+        );
+        accounts.swap_account = Account::new(
+            0,
+            SwapVersion::LATEST_LEN + ProactiveMarketMakerState::LEN,
+            &SWAP_PROGRAM_ID,
+        );
+
+        let initialize_instruction = initialize_proactive_market_maker(
+            &SWAP_PROGRAM_ID,
+            &TOKEN_PROGRAM_ID,
+            &accounts.swap_key,
+            &accounts.authority_key,
+            &accounts.token_a_key,
+            &accounts.token_b_key,
+            &accounts.pool_mint_key,
+            &accounts.pool_fee_key,
+            &accounts.pool_token_key,
+            &oracle_feed_key,
+            accounts.nonce,
+            accounts.fees.clone(),
+            accounts.swap_curve.clone(),
+            Some(&accounts.admin_key),
+        )
+        .unwrap();
+
+        // the feed must be initialized and recent
+        let mut uninitialized_feed_account =
+            Account::new(0, OracleFeed::LEN, &Pubkey::new_unique());
+        assert_eq!(
+            Err(SwapError::InvalidOracle.into()),
+            do_process_instruction(
+                initialize_instruction.clone(),
+                vec![
+                    &mut accounts.swap_account,
+                    &mut Account::default(),
+                    &mut accounts.token_a_account,
+                    &mut accounts.token_b_account,
+                    &mut accounts.pool_mint_account,
+                    &mut accounts.pool_fee_account,
+                    &mut accounts.pool_token_account,
+                    &mut Account::default(),
+                    &mut uninitialized_feed_account,
+                    &mut Account::default(),
+                ],
+            )
+        );
+        set_test_slot(100 + max_oracle_staleness_slots + 1);
+        assert_eq!(
+            Err(SwapError::StaleOraclePrice.into()),
+            do_process_instruction(
+                initialize_instruction.clone(),
+                vec![
+                    &mut accounts.swap_account,
+                    &mut Account::default(),
+                    &mut accounts.token_a_account,
+                    &mut accounts.token_b_account,
+                    &mut accounts.pool_mint_account,
+                    &mut accounts.pool_fee_account,
+                    &mut accounts.pool_token_account,
+                    &mut Account::default(),
+                    &mut oracle_feed_account,
+                    &mut Account::default(),
+                ],
+            )
+        );
+        set_test_slot(100 + max_oracle_staleness_slots);
+
+        // and its confidence interval within the limit of the curve
+        let mut uncertain_feed_account = oracle_feed_account.clone();
+        OracleFeed::pack(
+            OracleFeed {
+                confidence: 21,
+                ..oracle_feed.clone()
+            },
+            &mut uncertain_feed_account.data,
+        )
+        .unwrap();
+        assert_eq!(
+            Err(SwapError::UncertainOraclePrice.into()),
+            do_process_instruction(
+                initialize_instruction.clone(),
+                vec![
+                    &mut accounts.swap_account,
+                    &mut Account::default(),
+                    &mut accounts.token_a_account,
+                    &mut accounts.token_b_account,
+                    &mut accounts.pool_mint_account,
+                    &mut accounts.pool_fee_account,
+                    &mut accounts.pool_token_account,
+                    &mut Account::default(),
+                    &mut uncertain_feed_account,
+                    &mut Account::default(),
+                ],
+            )
+        );
+
+        set_test_slot(100);
+        do_process_instruction(
+            initialize_instruction,
+            vec![
+                &mut accounts.swap_account,
+                &mut Account::default(),
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
+                &mut accounts.pool_mint_account,
+                &mut accounts.pool_fee_account,
+                &mut accounts.pool_token_account,
+                &mut Account::default(),
+                &mut oracle_feed_account,
+                &mut Account::default(),
+            ],
+        )
+        .unwrap();
+        let curve = unpack_proactive_market_maker_curve(&accounts.swap_account);
+        assert_eq!(curve.k, k);
+        assert_eq!(curve.oracle_price, 2 << 64);
+        let state = ProactiveMarketMakerState::unpack(
+            &accounts.swap_account.data[SwapVersion::LATEST_LEN..],
+        )
+        .unwrap();
+        assert_eq!(
+            state,
+            ProactiveMarketMakerState {
+                oracle_feed: oracle_feed_key,
+                last_refresh_slot: 100,
+            }
+        );
 
+        // swap at the oracle price read in the same slot
         let (
             token_a_key,
             mut token_a_account,
             token_b_key,
             mut token_b_account,
-            _pool_key,
-            _pool_account,
-        ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-
-        // swap a to b way, fails, there's no liquidity
-        let a_to_b_amount = initial_a;
-        let minimum_token_b_amount = 0;
-
-        assert_eq!(
-            Err(SwapError::ZeroTradingTokens.into()),
-            accounts.swap(
-                &swapper_key,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &trader_key, 200_000, 200_000, 0);
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+        let amount_in = 100_000;
+        let expected = SwapCurve {
+            curve_type: CurveType::ProactiveMarketMaker,
+            calculator: Box::new(curve),
+        }
+        .swap(
+            amount_in.into(),
+            token_a_amount.into(),
+            token_b_amount.into(),
+            TradeDirection::AtoB,
+            &accounts.fees,
+        )
+        .unwrap();
+        accounts
+            .swap(
+                &trader_key,
                 &token_a_key,
                 &mut token_a_account,
                 &swap_token_a_key,
                 &swap_token_b_key,
                 &token_b_key,
                 &mut token_b_account,
-                a_to_b_amount,
-                minimum_token_b_amount,
+                amount_in,
+                0,
+            )
+            .unwrap();
+        let amount_out = expected.destination_amount_swapped as u64;
+        assert_eq!(token_amount(&token_b_account), 200_000 + amount_out);
+        assert_eq!(
+            token_amount(&accounts.token_a_account),
+            token_a_amount + amount_in
+        );
+
+        // the price must be refreshed in every slot
+        set_test_slot(101);
+        assert_eq!(
+            Err(SwapError::StaleOraclePrice.into()),
+            accounts.swap(
+                &trader_key,
+                &token_b_key,
+                &mut token_b_account,
+                &swap_token_b_key,
+                &swap_token_a_key,
+                &token_a_key,
+                &mut token_a_account,
+                amount_out,
+                0,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::StaleOraclePrice.into()),
+            accounts.deposit_single_token_type_exact_amount_in(
+                &trader_key,
+                &token_a_key,
+                &mut token_a_account,
+                &pool_key,
+                &mut pool_account,
+                10_000,
+                0,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::StaleOraclePrice.into()),
+            accounts.withdraw_single_token_type_exact_amount_out(
+                &user_key,
+                &accounts.pool_token_key.clone(),
+                &mut accounts.pool_token_account.clone(),
+                &token_a_key,
+                &mut token_a_account,
+                10_000,
+                u64::MAX,
+            )
+        );
+
+        // only the feed of the swap can refresh it
+        let wrong_feed_key = Pubkey::new_unique();
+        assert_eq!(
+            Err(SwapError::InvalidOracle.into()),
+            do_process_instruction(
+                refresh_oracle_price(
+                    &SWAP_PROGRAM_ID,
+                    &accounts.swap_key,
+                    &wrong_feed_key,
+                    &accounts.token_a_key,
+                    &accounts.token_b_key,
+                )
+                .unwrap(),
+                vec![
+                    &mut accounts.swap_account,
+                    &mut oracle_feed_account.clone(),
+                    &mut accounts.token_a_account,
+                    &mut accounts.token_b_account,
+                ],
             )
         );
 
-        // swap b to a, succeeds at offset price
-        let b_to_a_amount = initial_b;
-        let minimum_token_a_amount = 0;
-        accounts
-            .swap(
-                &swapper_key,
-                &token_b_key,
-                &mut token_b_account,
-                &swap_token_b_key,
-                &swap_token_a_key,
-                &token_a_key,
-                &mut token_a_account,
-                b_to_a_amount,
-                minimum_token_a_amount,
+        // a price too uncertain cannot refresh the curve
+        oracle_feed.publish_slot = 101;
+        oracle_feed.price = 3_000;
+        oracle_feed.confidence = 31;
+        OracleFeed::pack(oracle_feed.clone(), &mut oracle_feed_account.data).unwrap();
+        assert_eq!(
+            Err(SwapError::UncertainOraclePrice.into()),
+            do_process_instruction(
+                refresh_oracle_price(
+                    &SWAP_PROGRAM_ID,
+                    &accounts.swap_key,
+                    &oracle_feed_key,
+                    &accounts.token_a_key,
+                    &accounts.token_b_key,
+                )
+                .unwrap(),
+                vec![
+                    &mut accounts.swap_account,
+                    &mut oracle_feed_account,
+                    &mut accounts.token_a_account,
+                    &mut accounts.token_b_account,
+                ],
             )
-            .unwrap();
+        );
 
-        // try a to b again, succeeds due to new liquidity
+        // the price of token A moves to 3 token B
+        oracle_feed.confidence = 30;
+        OracleFeed::pack(oracle_feed, &mut oracle_feed_account.data).unwrap();
+        do_process_instruction(
+            refresh_oracle_price(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &oracle_feed_key,
+                &accounts.token_a_key,
+                &accounts.token_b_key,
+            )
+            .unwrap(),
+            vec![
+                &mut accounts.swap_account,
+                &mut oracle_feed_account,
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
+            ],
+        )
+        .unwrap();
+        let curve = unpack_proactive_market_maker_curve(&accounts.swap_account);
+        assert_eq!(curve.oracle_price, 3 << 64);
+        let state = ProactiveMarketMakerState::unpack(
+            &accounts.swap_account.data[SwapVersion::LATEST_LEN..],
+        )
+        .unwrap();
+        assert_eq!(state.last_refresh_slot, 101);
+
+        // token A is now worth more, so the same trade gets more token B
         accounts
             .swap(
-                &swapper_key,
+                &trader_key,
                 &token_a_key,
                 &mut token_a_account,
                 &swap_token_a_key,
                 &swap_token_b_key,
                 &token_b_key,
                 &mut token_b_account,
-                a_to_b_amount,
-                minimum_token_b_amount,
+                amount_in,
+                0,
             )
             .unwrap();
+        assert!(token_amount(&token_b_account) - 200_000 - amount_out > amount_out);
 
-        // try a to b again, fails due to no more liquidity
+        // an outdated feed cannot refresh the price
+        set_test_slot(101 + max_oracle_staleness_slots + 1);
         assert_eq!(
-            Err(SwapError::ZeroTradingTokens.into()),
-            accounts.swap(
-                &swapper_key,
-                &token_a_key,
-                &mut token_a_account,
-                &swap_token_a_key,
-                &swap_token_b_key,
-                &token_b_key,
-                &mut token_b_account,
-                a_to_b_amount,
-                minimum_token_b_amount,
+            Err(SwapError::StaleOraclePrice.into()),
+            do_process_instruction(
+                refresh_oracle_price(
+                    &SWAP_PROGRAM_ID,
+                    &accounts.swap_key,
+                    &oracle_feed_key,
+                    &accounts.token_a_key,
+                    &accounts.token_b_key,
+                )
+                .unwrap(),
+                vec![
+                    &mut accounts.swap_account,
+                    &mut oracle_feed_account,
+                    &mut accounts.token_a_account,
+                    &mut accounts.token_b_account,
+                ],
             )
         );
+    }
 
-        // Try to deposit, fails because deposits are not allowed for offset
-        // curve swaps
+    fn quoter_snapshot(accounts: &SwapAccountInfo) -> (Box<dyn SwapState>, u64, u64, u64) {
+        let token_swap = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        let pool_mint = spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+        (
+            token_swap,
+            token_amount(&accounts.token_a_account),
+            token_amount(&accounts.token_b_account),
+            pool_mint.supply,
+        )
+    }
+
+    #[test]
+    fn test_quote_matches_execution() {
+        let user_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 25,
+            trade_fee_denominator: 10_000,
+            owner_trade_fee_numerator: 5,
+            owner_trade_fee_denominator: 10_000,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 100,
+            host_fee_numerator: 20,
+            host_fee_denominator: 100,
+        };
+        for swap_curve in [
+            SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(ConstantProductCurve {}),
+            },
+            SwapCurve {
+                curve_type: CurveType::Stable,
+                calculator: Box::new(StableCurve { amp: 100 }),
+            },
+        ]
+        .iter()
         {
-            let initial_a = 100;
-            let initial_b = 100;
-            let pool_amount = 100;
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                fees.clone(),
+                swap_curve.clone(),
+                1_000_000,
+                4_000_000,
+            );
+            accounts.initialize_swap().unwrap();
+            let swap_token_a_key = accounts.token_a_key;
+            let swap_token_b_key = accounts.token_b_key;
             let (
                 token_a_key,
                 mut token_a_account,
@@ -6631,103 +11166,159 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, 100_000, 400_000, 0);
+
+            // swap
+            let (token_swap, a, b, supply) = quoter_snapshot(&accounts);
+            let quote = Quoter::new(token_swap.as_ref(), a, b, supply)
+                .swap(TradeDirection::AtoB, 50_000, false)
+                .unwrap();
+            accounts
+                .swap(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    50_000,
+                    quote.amount_out,
+                )
+                .unwrap();
+            assert_eq!(token_amount(&token_a_account), 100_000 - quote.amount_in);
+            assert_eq!(token_amount(&token_b_account), 400_000 + quote.amount_out);
             assert_eq!(
-                Err(SwapError::UnsupportedCurveOperation.into()),
-                accounts.deposit_all_token_types(
-                    &swapper_key,
+                token_amount(&accounts.pool_fee_account),
+                quote.fees.owner_fee
+            );
+            assert!(quote.fees.trade_fee > 0);
+            let fee_balance = token_amount(&accounts.pool_fee_account);
+
+            // deposit both token types
+            let (token_swap, a, b, supply) = quoter_snapshot(&accounts);
+            let token_a_balance = token_amount(&token_a_account);
+            let token_b_balance = token_amount(&token_b_account);
+            let quote = Quoter::new(token_swap.as_ref(), a, b, supply)
+                .deposit_all_token_types(100_000)
+                .unwrap();
+            accounts
+                .deposit_all_token_types(
+                    &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
-                    pool_amount,
-                    initial_a,
-                    initial_b,
+                    100_000,
+                    quote.token_a_amount,
+                    quote.token_b_amount,
                 )
+                .unwrap();
+            assert_eq!(
+                token_amount(&token_a_account),
+                token_a_balance - quote.token_a_amount
             );
-        }
-    }
-
-    #[test]
-    fn test_withdraw_all_offset_curve() {
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 0;
-        let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 10;
-        let host_fee_denominator = 100;
-
-        let token_a_amount = 1_000_000_000;
-        let token_b_amount = 10;
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
-
-        let token_b_offset = 2_000_000;
-        let swap_curve = SwapCurve {
-            curve_type: CurveType::Offset,
-            calculator: Box::new(OffsetCurve { token_b_offset }),
-        };
-        let total_pool = swap_curve.calculator.new_pool_supply();
-        let user_key = Pubkey::new_unique();
-        let withdrawer_key = Pubkey::new_unique();
-
-        let mut accounts =
-            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
-
-        accounts.initialize_swap().unwrap();
-
-        let (
-            token_a_key,
-            mut token_a_account,
-            token_b_key,
-            mut token_b_account,
-            _pool_key,
-            _pool_account,
-        ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
-
-        let pool_key = accounts.pool_token_key;
-        let mut pool_account = accounts.pool_token_account.clone();
-
-        // WithdrawAllTokenTypes takes all tokens for A and B.
-        // The curve's calculation for token B will say to transfer
-        // `token_b_offset + token_b_amount`, but only `token_b_amount` will be
-        // moved.
-        accounts
-            .withdraw_all_token_types(
-                &user_key,
-                &pool_key,
-                &mut pool_account,
-                &token_a_key,
-                &mut token_a_account,
-                &token_b_key,
-                &mut token_b_account,
-                total_pool.try_into().unwrap(),
-                0,
-                0,
-            )
-            .unwrap();
+            assert_eq!(
+                token_amount(&token_b_account),
+                token_b_balance - quote.token_b_amount
+            );
+            assert_eq!(token_amount(&pool_account), quote.pool_token_amount);
 
-        let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-        assert_eq!(token_a.amount, token_a_amount);
-        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
-        assert_eq!(token_b.amount, token_b_amount);
-        let swap_token_a =
-            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-        assert_eq!(swap_token_a.amount, 0);
-        let swap_token_b =
-            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-        assert_eq!(swap_token_b.amount, 0);
+            // deposit one token type
+            let (token_swap, a, b, supply) = quoter_snapshot(&accounts);
+            let pool_balance = token_amount(&pool_account);
+            let token_b_balance = token_amount(&token_b_account);
+            let quote = Quoter::new(token_swap.as_ref(), a, b, supply)
+                .deposit_single_token_type_exact_amount_in(TradeDirection::BtoA, 100_000)
+                .unwrap();
+            accounts
+                .deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    100_000,
+                    quote.pool_token_amount,
+                )
+                .unwrap();
+            assert_eq!(token_amount(&token_b_account), token_b_balance - 100_000);
+            assert_eq!(
+                token_amount(&pool_account),
+                pool_balance + quote.pool_token_amount
+            );
+            assert!(quote.fees.trade_fee > 0);
+            assert!(quote
+                .price_impact
+                .unwrap()
+                .greater_than(&PreciseNumber::new(0).unwrap()));
+
+            // withdraw one token type
+            let (token_swap, a, b, supply) = quoter_snapshot(&accounts);
+            let pool_balance = token_amount(&pool_account);
+            let token_a_balance = token_amount(&token_a_account);
+            let quote = Quoter::new(token_swap.as_ref(), a, b, supply)
+                .withdraw_single_token_type_exact_amount_out(TradeDirection::AtoB, 10_000, true)
+                .unwrap();
+            accounts
+                .withdraw_single_token_type_exact_amount_out(
+                    &depositor_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    10_000,
+                    quote.pool_token_amount,
+                )
+                .unwrap();
+            assert_eq!(token_amount(&token_a_account), token_a_balance + 10_000);
+            assert_eq!(
+                token_amount(&pool_account),
+                pool_balance - quote.pool_token_amount
+            );
+            assert_eq!(
+                token_amount(&accounts.pool_fee_account),
+                fee_balance + quote.fees.owner_fee
+            );
+            let fee_balance = token_amount(&accounts.pool_fee_account);
+
+            // withdraw both token types
+            let (token_swap, a, b, supply) = quoter_snapshot(&accounts);
+            let pool_balance = token_amount(&pool_account);
+            let token_a_balance = token_amount(&token_a_account);
+            let token_b_balance = token_amount(&token_b_account);
+            let quote = Quoter::new(token_swap.as_ref(), a, b, supply)
+                .withdraw_all_token_types(pool_balance, true)
+                .unwrap();
+            accounts
+                .withdraw_all_token_types(
+                    &depositor_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    pool_balance,
+                    quote.token_a_amount,
+                    quote.token_b_amount,
+                )
+                .unwrap();
+            assert_eq!(
+                token_amount(&token_a_account),
+                token_a_balance + quote.token_a_amount
+            );
+            assert_eq!(
+                token_amount(&token_b_account),
+                token_b_balance + quote.token_b_amount
+            );
+            assert_eq!(token_amount(&pool_account), 0);
+            assert_eq!(
+                token_amount(&accounts.pool_fee_account),
+                fee_balance + quote.fees.owner_fee
+            );
+        }
     }
 }
@@ -0,0 +1,970 @@
+//! Quotes for swaps, deposits and withdrawals.
+//!
+//! The processor executes every instruction through these functions, so a
+//! quote computed off-chain from a deserialized swap, the vault balances and
+//! the pool token supply matches the on-chain result to the token.  The module
+//! only relies on `core` and the unpacked swap state, which keeps it usable
+//! from clients that cannot pull in the runtime.
+//!
+//! Weighted pools are quoted through `WeightedPoolQuoter`, given the pool
+//! tokens involved and the balances of their token accounts.
+//!
+//! Swaps with a dynamic trade fee observe the current price before trading,
+//! so clients quoting them should `observe` it on a copy of the swap's
+//! `DynamicFee` and quote with that copy and the fees it applies.  Swaps then
+//! also pay for their own price movement.
+
+use crate::{
+    curve::{
+        base::{CurveType, SwapResult},
+        calculator::{RoundDirection, TradeDirection},
+        dynamic_fee::DynamicFee,
+        fees::Fees,
+        weighted,
+    },
+    error::SwapError,
+    state::{SwapState, WeightedPool, WeightedPoolToken},
+};
+use core::{cmp, convert::TryFrom};
+use spl_math::precise_number::PreciseNumber;
+
+/// Fees charged by an operation
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QuoteFees {
+    /// Trading fee kept by the pool for liquidity providers, in the traded
+    /// tokens: source tokens for swaps and deposits, destination tokens for
+    /// withdrawals
+    pub trade_fee: u64,
+    /// Fee paid to the pool fee account, in pool tokens
+    pub owner_fee: u64,
+    /// Share of the owner trading fee paid to the host fee account, in pool
+    /// tokens
+    pub host_fee: u64,
+}
+
+/// Quote for a swap
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwapQuote {
+    /// Source tokens taken from the user, fees included
+    pub amount_in: u64,
+    /// Destination tokens sent to the user
+    pub amount_out: u64,
+    /// Fees charged by the swap
+    pub fees: QuoteFees,
+    /// Relative shortfall of the output against trading the amount left
+    /// after fees at the spot price, `None` if the curve has no spot price
+    pub price_impact: Option<PreciseNumber>,
+}
+
+/// Quote for a deposit of both token types
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepositAllTokenTypesQuote {
+    /// Token A taken from the user
+    pub token_a_amount: u64,
+    /// Token B taken from the user
+    pub token_b_amount: u64,
+    /// Pool tokens minted to the user
+    pub pool_token_amount: u64,
+    /// Fees charged by the deposit, always zero for proportional deposits
+    pub fees: QuoteFees,
+    /// Price impact of the deposit, always zero for proportional deposits
+    pub price_impact: Option<PreciseNumber>,
+}
+
+/// Quote for a withdrawal of both token types
+#[derive(Clone, Debug, PartialEq)]
+pub struct WithdrawAllTokenTypesQuote {
+    /// Token A sent to the user
+    pub token_a_amount: u64,
+    /// Token B sent to the user
+    pub token_b_amount: u64,
+    /// Pool tokens taken from the user, withdraw fee included
+    pub pool_token_amount: u64,
+    /// Fees charged by the withdrawal
+    pub fees: QuoteFees,
+    /// Price impact of the withdrawal, always zero for proportional
+    /// withdrawals
+    pub price_impact: Option<PreciseNumber>,
+}
+
+/// Quote for a deposit of one token type
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepositSingleTokenTypeQuote {
+    /// Source tokens taken from the user
+    pub source_token_amount: u64,
+    /// Pool tokens minted to the user
+    pub pool_token_amount: u64,
+    /// Fees charged by the deposit
+    pub fees: QuoteFees,
+    /// Relative shortfall of the pool tokens minted against depositing the
+    /// amount left after fees at the spot price, `None` if the curve has no
+    /// spot price
+    pub price_impact: Option<PreciseNumber>,
+}
+
+/// Quote for a withdrawal of one token type
+#[derive(Clone, Debug, PartialEq)]
+pub struct WithdrawSingleTokenTypeQuote {
+    /// Destination tokens sent to the user
+    pub destination_token_amount: u64,
+    /// Pool tokens taken from the user, withdraw fee included
+    pub pool_token_amount: u64,
+    /// Fees charged by the withdrawal
+    pub fees: QuoteFees,
+    /// Relative excess of the pool tokens burned against withdrawing the
+    /// amount left after fees at the spot price, `None` if the curve has no
+    /// spot price
+    pub price_impact: Option<PreciseNumber>,
+}
+
+/// Quotes operations against a pool snapshot
+pub struct Quoter<'a> {
+    /// Unpacked swap state
+    pub token_swap: &'a dyn SwapState,
+    /// Balance of the swap's token A account
+    pub token_a_amount: u64,
+    /// Balance of the swap's token B account
+    pub token_b_amount: u64,
+    /// Supply of the pool mint
+    pub pool_supply: u64,
+    /// Fees charged by the swap, including its current dynamic trade fee
+    pub fees: Fees,
+    /// Dynamic fee of the swap, which charges swaps for the price movement
+    /// they cause, `None` if the swap charges fixed fees
+    pub dynamic_fee: Option<DynamicFee>,
+}
+
+impl<'a> Quoter<'a> {
+    /// Creates a quoter for the given swap, vault balances and pool supply
+    pub fn new(
+        token_swap: &'a dyn SwapState,
+        token_a_amount: u64,
+        token_b_amount: u64,
+        pool_supply: u64,
+    ) -> Self {
+        Self {
+            token_swap,
+            token_a_amount,
+            token_b_amount,
+            pool_supply,
+            fees: token_swap.current_fees(),
+            dynamic_fee: token_swap
+                .dynamic_fee()
+                .copied()
+                .filter(|dynamic_fee| dynamic_fee.parameters.is_enabled()),
+        }
+    }
+
+    /// Quoter for a swap leaving the pool at `post_trade_price`, charging the
+    /// dynamic trade fee including the price movement of the swap itself
+    pub fn after_trade(&self, post_trade_price: Option<&PreciseNumber>) -> Self {
+        let fees = match &self.dynamic_fee {
+            Some(dynamic_fee) => dynamic_fee.after_trade(post_trade_price).apply(&self.fees),
+            None => self.fees.clone(),
+        };
+        Self {
+            fees,
+            dynamic_fee: None,
+            ..*self
+        }
+    }
+
+    /// Quotes a swap of `amount_in` source tokens. `with_host_fee` splits the
+    /// owner fee with a host fee account.
+    ///
+    /// Concentrated liquidity pools keep their liquidity outside of the swap
+    /// state, so their swaps must be computed by the pool state and quoted
+    /// through `swap_result`.
+    pub fn swap(
+        &self,
+        trade_direction: TradeDirection,
+        amount_in: u64,
+        with_host_fee: bool,
+    ) -> Result<SwapQuote, SwapError> {
+        let swap_curve = self.token_swap.swap_curve();
+        if swap_curve.curve_type == CurveType::ConcentratedLiquidity {
+            return Err(SwapError::UnsupportedCurveOperation);
+        }
+        let (swap_source_amount, swap_destination_amount) = self.directed_amounts(trade_direction);
+        let result = swap_curve
+            .swap(
+                u128::from(amount_in),
+                swap_source_amount,
+                swap_destination_amount,
+                trade_direction,
+                &self.fees,
+            )
+            .ok_or(SwapError::ZeroTradingTokens)?;
+        if self.dynamic_fee.is_some() {
+            // the swap pays for the price movement it causes
+            let (swap_token_a_amount, swap_token_b_amount) =
+                swapped_amounts(trade_direction, &result);
+            let post_trade_price = swap_curve
+                .calculator
+                .spot_price(swap_token_a_amount, swap_token_b_amount);
+            return self.after_trade(post_trade_price.as_ref()).swap(
+                trade_direction,
+                amount_in,
+                with_host_fee,
+            );
+        }
+        self.swap_result(trade_direction, &result, with_host_fee)
+    }
+
+    /// Quotes the fees and price impact of an already computed swap result
+    pub fn swap_result(
+        &self,
+        trade_direction: TradeDirection,
+        result: &SwapResult,
+        with_host_fee: bool,
+    ) -> Result<SwapQuote, SwapError> {
+        let (swap_token_a_amount, swap_token_b_amount) = swapped_amounts(trade_direction, result);
+        let fees = &self.fees;
+        let pool_token_amount = self
+            .token_swap
+            .swap_curve()
+            .trading_tokens_to_pool_tokens(
+                result.owner_fee,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                u128::from(self.pool_supply),
+                trade_direction,
+                RoundDirection::Ceiling,
+                fees,
+            )
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let host_fee = if with_host_fee {
+            fees.host_fee(pool_token_amount)
+                .ok_or(SwapError::FeeCalculationFailure)?
+        } else {
+            0
+        };
+        let owner_fee = pool_token_amount
+            .checked_sub(host_fee)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+
+        let price_impact = self.spot_price().and_then(|spot_price| {
+            let traded_amount = result
+                .source_amount_swapped
+                .checked_sub(result.trade_fee)?
+                .checked_sub(result.owner_fee)?;
+            let traded_amount = PreciseNumber::new(traded_amount)?;
+            let expected_amount = match trade_direction {
+                TradeDirection::AtoB => traded_amount.checked_mul(&spot_price)?,
+                TradeDirection::BtoA => traded_amount.checked_div(&spot_price)?,
+            };
+            shortfall(&expected_amount, result.destination_amount_swapped)
+        });
+
+        Ok(SwapQuote {
+            amount_in: to_u64(result.source_amount_swapped)?,
+            amount_out: to_u64(result.destination_amount_swapped)?,
+            fees: QuoteFees {
+                trade_fee: to_u64(result.trade_fee)?,
+                owner_fee: to_u64(owner_fee)?,
+                host_fee: to_u64(host_fee)?,
+            },
+            price_impact,
+        })
+    }
+
+    /// Quotes the token amounts needed to mint exactly `pool_token_amount`
+    pub fn deposit_all_token_types(
+        &self,
+        pool_token_amount: u64,
+    ) -> Result<DepositAllTokenTypesQuote, SwapError> {
+        let calculator = &self.token_swap.swap_curve().calculator;
+        if !calculator.allows_deposits() {
+            return Err(SwapError::UnsupportedCurveOperation);
+        }
+        let results = calculator
+            .pool_tokens_to_trading_tokens(
+                u128::from(pool_token_amount),
+                u128::from(self.pool_supply),
+                u128::from(self.token_a_amount),
+                u128::from(self.token_b_amount),
+                RoundDirection::Ceiling,
+            )
+            .ok_or(SwapError::ZeroTradingTokens)?;
+        let token_a_amount = to_u64(results.token_a_amount)?;
+        let token_b_amount = to_u64(results.token_b_amount)?;
+        if token_a_amount == 0 || token_b_amount == 0 {
+            return Err(SwapError::ZeroTradingTokens);
+        }
+        Ok(DepositAllTokenTypesQuote {
+            token_a_amount,
+            token_b_amount,
+            pool_token_amount,
+            fees: QuoteFees::default(),
+            price_impact: PreciseNumber::new(0),
+        })
+    }
+
+    /// Quotes the token amounts received for exactly `pool_token_amount`.
+    /// `with_withdraw_fee` is false when withdrawing from the pool fee
+    /// account, which is not charged a withdraw fee.
+    pub fn withdraw_all_token_types(
+        &self,
+        pool_token_amount: u64,
+        with_withdraw_fee: bool,
+    ) -> Result<WithdrawAllTokenTypesQuote, SwapError> {
+        let swap_curve = self.token_swap.swap_curve();
+        if swap_curve.curve_type == CurveType::ConcentratedLiquidity {
+            return Err(SwapError::UnsupportedCurveOperation);
+        }
+        let withdraw_fee = self.withdraw_fee(u128::from(pool_token_amount), with_withdraw_fee)?;
+        let burn_pool_token_amount = u128::from(pool_token_amount)
+            .checked_sub(withdraw_fee)
+            .ok_or(SwapError::CalculationFailure)?;
+
+        let results = swap_curve
+            .calculator
+            .pool_tokens_to_trading_tokens(
+                burn_pool_token_amount,
+                u128::from(self.pool_supply),
+                u128::from(self.token_a_amount),
+                u128::from(self.token_b_amount),
+                RoundDirection::Floor,
+            )
+            .ok_or(SwapError::ZeroTradingTokens)?;
+        let token_a_amount = to_u64(results.token_a_amount)?;
+        if token_a_amount == 0 && self.token_a_amount != 0 {
+            return Err(SwapError::ZeroTradingTokens);
+        }
+        let token_b_amount = to_u64(results.token_b_amount)?;
+        if token_b_amount == 0 && self.token_b_amount != 0 {
+            return Err(SwapError::ZeroTradingTokens);
+        }
+        Ok(WithdrawAllTokenTypesQuote {
+            token_a_amount: cmp::min(self.token_a_amount, token_a_amount),
+            token_b_amount: cmp::min(self.token_b_amount, token_b_amount),
+            pool_token_amount,
+            fees: QuoteFees {
+                owner_fee: to_u64(withdraw_fee)?,
+                ..QuoteFees::default()
+            },
+            price_impact: PreciseNumber::new(0),
+        })
+    }
+
+    /// Quotes the pool tokens minted for depositing exactly
+    /// `source_token_amount`, of token A for `AtoB` and of token B for `BtoA`
+    pub fn deposit_single_token_type_exact_amount_in(
+        &self,
+        trade_direction: TradeDirection,
+        source_token_amount: u64,
+    ) -> Result<DepositSingleTokenTypeQuote, SwapError> {
+        let fees = &self.fees;
+        let pool_token_amount = self
+            .token_swap
+            .swap_curve()
+            .trading_tokens_to_pool_tokens(
+                u128::from(source_token_amount),
+                u128::from(self.token_a_amount),
+                u128::from(self.token_b_amount),
+                u128::from(self.pool_supply),
+                trade_direction,
+                RoundDirection::Floor,
+                fees,
+            )
+            .ok_or(SwapError::ZeroTradingTokens)?;
+        let pool_token_amount = to_u64(pool_token_amount)?;
+        if pool_token_amount == 0 {
+            return Err(SwapError::ZeroTradingTokens);
+        }
+        let trade_fee = fees
+            .single_token_trading_fee(u128::from(source_token_amount))
+            .ok_or(SwapError::FeeCalculationFailure)?;
+
+        let price_impact = u128::from(source_token_amount)
+            .checked_sub(trade_fee)
+            .and_then(|traded_amount| self.expected_pool_tokens(trade_direction, traded_amount))
+            .and_then(|expected_amount| shortfall(&expected_amount, u128::from(pool_token_amount)));
+
+        Ok(DepositSingleTokenTypeQuote {
+            source_token_amount,
+            pool_token_amount,
+            fees: QuoteFees {
+                trade_fee: to_u64(trade_fee)?,
+                ..QuoteFees::default()
+            },
+            price_impact,
+        })
+    }
+
+    /// Quotes the pool tokens needed to withdraw exactly
+    /// `destination_token_amount`, of token A for `AtoB` and of token B for
+    /// `BtoA`.  `with_withdraw_fee` is false when withdrawing from the pool
+    /// fee account, which is not charged a withdraw fee.
+    pub fn withdraw_single_token_type_exact_amount_out(
+        &self,
+        trade_direction: TradeDirection,
+        destination_token_amount: u64,
+        with_withdraw_fee: bool,
+    ) -> Result<WithdrawSingleTokenTypeQuote, SwapError> {
+        let swap_curve = self.token_swap.swap_curve();
+        if swap_curve.curve_type == CurveType::ConcentratedLiquidity {
+            return Err(SwapError::UnsupportedCurveOperation);
+        }
+        let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
+            TradeDirection::AtoB => (
+                self.token_a_amount
+                    .checked_sub(destination_token_amount)
+                    .ok_or(SwapError::CalculationFailure)?,
+                self.token_b_amount,
+            ),
+            TradeDirection::BtoA => (
+                self.token_a_amount,
+                self.token_b_amount
+                    .checked_sub(destination_token_amount)
+                    .ok_or(SwapError::CalculationFailure)?,
+            ),
+        };
+
+        let fees = &self.fees;
+        let burn_pool_token_amount = swap_curve
+            .trading_tokens_to_pool_tokens(
+                u128::from(destination_token_amount),
+                u128::from(swap_token_a_amount),
+                u128::from(swap_token_b_amount),
+                u128::from(self.pool_supply),
+                trade_direction,
+                RoundDirection::Ceiling,
+                fees,
+            )
+            .ok_or(SwapError::ZeroTradingTokens)?;
+        let withdraw_fee = self.withdraw_fee(burn_pool_token_amount, with_withdraw_fee)?;
+        let pool_token_amount = burn_pool_token_amount
+            .checked_add(withdraw_fee)
+            .ok_or(SwapError::CalculationFailure)?;
+        let pool_token_amount = to_u64(pool_token_amount)?;
+        if pool_token_amount == 0 {
+            return Err(SwapError::ZeroTradingTokens);
+        }
+        let trade_fee = fees
+            .single_token_trading_fee(u128::from(destination_token_amount))
+            .ok_or(SwapError::FeeCalculationFailure)?;
+
+        let price_impact = u128::from(destination_token_amount)
+            .checked_sub(trade_fee)
+            .and_then(|traded_amount| self.expected_pool_tokens(trade_direction, traded_amount))
+            .and_then(|expected_amount| excess(&expected_amount, burn_pool_token_amount));
+
+        Ok(WithdrawSingleTokenTypeQuote {
+            destination_token_amount,
+            pool_token_amount,
+            fees: QuoteFees {
+                trade_fee: to_u64(trade_fee)?,
+                owner_fee: to_u64(withdraw_fee)?,
+                ..QuoteFees::default()
+            },
+            price_impact,
+        })
+    }
+
+    /// Vault balances ordered as (source, destination) for the direction
+    fn directed_amounts(&self, trade_direction: TradeDirection) -> (u128, u128) {
+        match trade_direction {
+            TradeDirection::AtoB => (
+                u128::from(self.token_a_amount),
+                u128::from(self.token_b_amount),
+            ),
+            TradeDirection::BtoA => (
+                u128::from(self.token_b_amount),
+                u128::from(self.token_a_amount),
+            ),
+        }
+    }
+
+    fn withdraw_fee(
+        &self,
+        pool_token_amount: u128,
+        with_withdraw_fee: bool,
+    ) -> Result<u128, SwapError> {
+        if with_withdraw_fee {
+            self.fees
+                .owner_withdraw_fee(pool_token_amount)
+                .ok_or(SwapError::FeeCalculationFailure)
+        } else {
+            Ok(0)
+        }
+    }
+
+    fn spot_price(&self) -> Option<PreciseNumber> {
+        let zero = PreciseNumber::new(0)?;
+        self.token_swap
+            .swap_curve()
+            .calculator
+            .spot_price(
+                u128::from(self.token_a_amount),
+                u128::from(self.token_b_amount),
+            )
+            .filter(|spot_price| spot_price.greater_than(&zero))
+    }
+
+    /// Pool tokens worth `trading_tokens` of the deposited or withdrawn side
+    /// when the whole pool is valued at the spot price
+    fn expected_pool_tokens(
+        &self,
+        trade_direction: TradeDirection,
+        trading_tokens: u128,
+    ) -> Option<PreciseNumber> {
+        let spot_price = self.spot_price()?;
+        let token_a_amount = PreciseNumber::new(u128::from(self.token_a_amount))?;
+        let token_b_amount = PreciseNumber::new(u128::from(self.token_b_amount))?;
+        let pool_value = match trade_direction {
+            TradeDirection::AtoB => {
+                token_a_amount.checked_add(&token_b_amount.checked_div(&spot_price)?)?
+            }
+            TradeDirection::BtoA => {
+                token_b_amount.checked_add(&token_a_amount.checked_mul(&spot_price)?)?
+            }
+        };
+        PreciseNumber::new(trading_tokens)?
+            .checked_mul(&PreciseNumber::new(u128::from(self.pool_supply))?)?
+            .checked_div(&pool_value)
+    }
+}
+
+/// Quotes operations against a weighted pool snapshot.  Weighted pools hold
+/// up to eight tokens, so every quote takes the pool tokens it involves along
+/// with the balances of their token accounts.
+pub struct WeightedPoolQuoter<'a> {
+    /// Unpacked pool state
+    pub pool: &'a WeightedPool,
+    /// Supply of the pool mint
+    pub pool_supply: u64,
+}
+
+impl<'a> WeightedPoolQuoter<'a> {
+    /// Creates a quoter for the given pool and pool supply
+    pub fn new(pool: &'a WeightedPool, pool_supply: u64) -> Self {
+        Self { pool, pool_supply }
+    }
+
+    /// Quotes a swap of `amount_in` tokens of `source_token`, whose account
+    /// holds `swap_source_amount`, for `destination_token`, whose account
+    /// holds `swap_destination_amount`
+    pub fn swap(
+        &self,
+        source_token: &WeightedPoolToken,
+        swap_source_amount: u64,
+        destination_token: &WeightedPoolToken,
+        swap_destination_amount: u64,
+        amount_in: u64,
+    ) -> Result<SwapQuote, SwapError> {
+        let fees = &self.pool.fees;
+        let amount_in = u128::from(amount_in);
+        let trade_fee = fees
+            .trading_fee(amount_in)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let owner_fee = fees
+            .owner_trading_fee(amount_in)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let amount_in_less_fees = trade_fee
+            .checked_add(owner_fee)
+            .and_then(|total_fees| amount_in.checked_sub(total_fees))
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let result = weighted::swap(
+            amount_in_less_fees,
+            u128::from(swap_source_amount),
+            u128::from(swap_destination_amount),
+            source_token.weight,
+            destination_token.weight,
+        )
+        .ok_or(SwapError::ZeroTradingTokens)?;
+
+        let new_swap_source_amount = u128::from(swap_source_amount)
+            .checked_add(amount_in)
+            .ok_or(SwapError::CalculationFailure)?;
+        let pool_token_amount = weighted::trading_tokens_to_pool_tokens(
+            owner_fee,
+            new_swap_source_amount,
+            source_token.weight,
+            self.total_weight()?,
+            u128::from(self.pool_supply),
+            RoundDirection::Ceiling,
+        )
+        .ok_or(SwapError::FeeCalculationFailure)?;
+
+        let price_impact = weighted::spot_price(
+            u128::from(swap_source_amount),
+            u128::from(swap_destination_amount),
+            source_token.weight,
+            destination_token.weight,
+        )
+        .and_then(|spot_price| {
+            let expected_amount =
+                PreciseNumber::new(amount_in_less_fees)?.checked_mul(&spot_price)?;
+            shortfall(&expected_amount, result.destination_amount_swapped)
+        });
+
+        Ok(SwapQuote {
+            amount_in: to_u64(amount_in)?,
+            amount_out: to_u64(result.destination_amount_swapped)?,
+            fees: QuoteFees {
+                trade_fee: to_u64(trade_fee)?,
+                owner_fee: to_u64(pool_token_amount)?,
+                host_fee: 0,
+            },
+            price_impact,
+        })
+    }
+
+    /// Quotes the pool tokens minted for depositing exactly
+    /// `source_token_amount` of `token`, whose account holds
+    /// `swap_token_amount`
+    pub fn deposit_single_token_type_exact_amount_in(
+        &self,
+        token: &WeightedPoolToken,
+        swap_token_amount: u64,
+        source_token_amount: u64,
+    ) -> Result<DepositSingleTokenTypeQuote, SwapError> {
+        let trade_fee = self.single_token_trading_fee(source_token_amount, token.weight)?;
+        let source_amount_less_fee = u128::from(source_token_amount)
+            .checked_sub(trade_fee)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let pool_token_amount = weighted::trading_tokens_to_pool_tokens(
+            source_amount_less_fee,
+            u128::from(swap_token_amount),
+            token.weight,
+            self.total_weight()?,
+            u128::from(self.pool_supply),
+            RoundDirection::Floor,
+        )
+        .ok_or(SwapError::ZeroTradingTokens)?;
+        let pool_token_amount = to_u64(pool_token_amount)?;
+        if pool_token_amount == 0 {
+            return Err(SwapError::ZeroTradingTokens);
+        }
+
+        let price_impact = self
+            .expected_pool_tokens(token, swap_token_amount, source_amount_less_fee)
+            .and_then(|expected_amount| shortfall(&expected_amount, u128::from(pool_token_amount)));
+
+        Ok(DepositSingleTokenTypeQuote {
+            source_token_amount,
+            pool_token_amount,
+            fees: QuoteFees {
+                trade_fee: to_u64(trade_fee)?,
+                ..QuoteFees::default()
+            },
+            price_impact,
+        })
+    }
+
+    /// Quotes the pool tokens needed to withdraw exactly
+    /// `destination_token_amount` of `token`, whose account holds
+    /// `swap_token_amount`.  The trading fee is left in the pool on top of
+    /// the withdrawn amount.  `with_withdraw_fee` is false when withdrawing
+    /// from the pool fee account, which is not charged a withdraw fee.
+    pub fn withdraw_single_token_type_exact_amount_out(
+        &self,
+        token: &WeightedPoolToken,
+        swap_token_amount: u64,
+        destination_token_amount: u64,
+        with_withdraw_fee: bool,
+    ) -> Result<WithdrawSingleTokenTypeQuote, SwapError> {
+        let trade_fee = self.single_token_trading_fee(destination_token_amount, token.weight)?;
+        let destination_amount_with_fee = u128::from(destination_token_amount)
+            .checked_add(trade_fee)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let new_swap_token_amount = u128::from(swap_token_amount)
+            .checked_sub(destination_amount_with_fee)
+            .ok_or(SwapError::CalculationFailure)?;
+        let burn_pool_token_amount = weighted::trading_tokens_to_pool_tokens(
+            destination_amount_with_fee,
+            new_swap_token_amount,
+            token.weight,
+            self.total_weight()?,
+            u128::from(self.pool_supply),
+            RoundDirection::Ceiling,
+        )
+        .ok_or(SwapError::ZeroTradingTokens)?;
+        let withdraw_fee = if with_withdraw_fee {
+            self.pool
+                .fees
+                .owner_withdraw_fee(burn_pool_token_amount)
+                .ok_or(SwapError::FeeCalculationFailure)?
+        } else {
+            0
+        };
+        let pool_token_amount = burn_pool_token_amount
+            .checked_add(withdraw_fee)
+            .ok_or(SwapError::CalculationFailure)?;
+        let pool_token_amount = to_u64(pool_token_amount)?;
+        if pool_token_amount == 0 {
+            return Err(SwapError::ZeroTradingTokens);
+        }
+
+        let price_impact = self
+            .expected_pool_tokens(token, swap_token_amount, destination_amount_with_fee)
+            .and_then(|expected_amount| excess(&expected_amount, burn_pool_token_amount));
+
+        Ok(WithdrawSingleTokenTypeQuote {
+            destination_token_amount,
+            pool_token_amount,
+            fees: QuoteFees {
+                trade_fee: to_u64(trade_fee)?,
+                owner_fee: to_u64(withdraw_fee)?,
+                ..QuoteFees::default()
+            },
+            price_impact,
+        })
+    }
+
+    fn total_weight(&self) -> Result<u128, SwapError> {
+        self.pool
+            .total_weight()
+            .ok_or(SwapError::CalculationFailure)
+    }
+
+    /// Trading fee charged on a single-sided deposit or withdrawal.  Only the
+    /// share of the amount which would need to be traded for the other
+    /// tokens of the pool is charged.
+    fn single_token_trading_fee(&self, amount: u64, token_weight: u64) -> Result<u128, SwapError> {
+        let total_weight = self.total_weight()?;
+        let traded_amount = u128::from(amount)
+            .checked_mul(
+                total_weight
+                    .checked_sub(u128::from(token_weight))
+                    .ok_or(SwapError::CalculationFailure)?,
+            )
+            .ok_or(SwapError::CalculationFailure)?
+            .checked_div(total_weight)
+            .ok_or(SwapError::CalculationFailure)?;
+        self.pool
+            .fees
+            .trading_fee(traded_amount)
+            .ok_or(SwapError::FeeCalculationFailure)
+    }
+
+    /// Pool tokens worth `trading_tokens` of `token` when the whole pool is
+    /// valued at the spot price, where `token` makes up its weight's share of
+    /// the pool value
+    fn expected_pool_tokens(
+        &self,
+        token: &WeightedPoolToken,
+        swap_token_amount: u64,
+        trading_tokens: u128,
+    ) -> Option<PreciseNumber> {
+        let pool_value = u128::from(swap_token_amount).checked_mul(self.total_weight().ok()?)?;
+        PreciseNumber::new(trading_tokens)?
+            .checked_mul(&PreciseNumber::new(u128::from(self.pool_supply))?)?
+            .checked_mul(&PreciseNumber::new(u128::from(token.weight))?)?
+            .checked_div(&PreciseNumber::new(pool_value)?)
+    }
+}
+
+/// Swap token amounts after a swap, ordered as (token A, token B)
+fn swapped_amounts(trade_direction: TradeDirection, result: &SwapResult) -> (u128, u128) {
+    match trade_direction {
+        TradeDirection::AtoB => (
+            result.new_swap_source_amount,
+            result.new_swap_destination_amount,
+        ),
+        TradeDirection::BtoA => (
+            result.new_swap_destination_amount,
+            result.new_swap_source_amount,
+        ),
+    }
+}
+
+/// Relative amount by which `actual` falls short of `expected`, zero if it
+/// does not
+fn shortfall(expected: &PreciseNumber, actual: u128) -> Option<PreciseNumber> {
+    let actual = PreciseNumber::new(actual)?;
+    if !actual.less_than(expected) {
+        return PreciseNumber::new(0);
+    }
+    expected.checked_sub(&actual)?.checked_div(expected)
+}
+
+/// Relative amount by which `actual` exceeds `expected`, zero if it does not
+fn excess(expected: &PreciseNumber, actual: u128) -> Option<PreciseNumber> {
+    let actual = PreciseNumber::new(actual)?;
+    if !actual.greater_than(expected) {
+        return PreciseNumber::new(0);
+    }
+    actual.checked_sub(expected)?.checked_div(expected)
+}
+
+fn to_u64(val: u128) -> Result<u64, SwapError> {
+    u64::try_from(val).map_err(|_| SwapError::ConversionFailure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        curve::{
+            base::SwapCurve, concentrated_liquidity::ConcentratedLiquidityCurve,
+            constant_product::ConstantProductCurve, fees::Fees,
+        },
+        state::SwapV1,
+    };
+
+    fn constant_product_swap() -> SwapV1 {
+        SwapV1 {
+            is_initialized: true,
+            fees: Fees {
+                trade_fee_numerator: 25,
+                trade_fee_denominator: 10_000,
+                owner_trade_fee_numerator: 5,
+                owner_trade_fee_denominator: 10_000,
+                owner_withdraw_fee_numerator: 1,
+                owner_withdraw_fee_denominator: 100,
+                host_fee_numerator: 20,
+                host_fee_denominator: 100,
+            },
+            swap_curve: SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(ConstantProductCurve {}),
+            },
+            ..SwapV1::default()
+        }
+    }
+
+    #[test]
+    fn swap_fee_split() {
+        let token_swap = constant_product_swap();
+        let quoter = Quoter::new(&token_swap, 1_000_000, 4_000_000, 1_000_000);
+        let quote = quoter.swap(TradeDirection::AtoB, 10_000, false).unwrap();
+        assert_eq!(quote.amount_in, 10_000);
+        assert_eq!(quote.fees.trade_fee, 25);
+        assert_eq!(quote.fees.host_fee, 0);
+
+        let hosted_quote = quoter.swap(TradeDirection::AtoB, 10_000, true).unwrap();
+        assert_eq!(hosted_quote.amount_out, quote.amount_out);
+        assert_eq!(
+            hosted_quote.fees.owner_fee + hosted_quote.fees.host_fee,
+            quote.fees.owner_fee
+        );
+        assert_eq!(
+            u128::from(hosted_quote.fees.host_fee),
+            token_swap
+                .fees
+                .host_fee(u128::from(quote.fees.owner_fee))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn price_impact_grows_with_size() {
+        let token_swap = constant_product_swap();
+        let quoter = Quoter::new(&token_swap, 1_000_000, 4_000_000, 1_000_000);
+        let mut last_impact = PreciseNumber::new(0).unwrap();
+        for amount_in in &[1_000, 10_000, 100_000, 1_000_000] {
+            let impact = quoter
+                .swap(TradeDirection::BtoA, *amount_in, false)
+                .unwrap()
+                .price_impact
+                .unwrap();
+            assert!(impact.greater_than(&last_impact));
+            last_impact = impact;
+        }
+        // trading x into a constant product pool of a moves the output by
+        // x / (a + x), with x about half the pool after fees
+        let impact = quoter
+            .swap(TradeDirection::AtoB, 500_000, false)
+            .unwrap()
+            .price_impact
+            .unwrap();
+        let fraction = |numerator, denominator| {
+            PreciseNumber::new(numerator)
+                .unwrap()
+                .checked_div(&PreciseNumber::new(denominator).unwrap())
+                .unwrap()
+        };
+        assert!(impact.greater_than(&fraction(33, 100)));
+        assert!(impact.less_than(&fraction(1, 3)));
+
+        let deposit_impact = |amount| {
+            quoter
+                .deposit_single_token_type_exact_amount_in(TradeDirection::AtoB, amount)
+                .unwrap()
+                .price_impact
+                .unwrap()
+        };
+        assert!(deposit_impact(100_000).greater_than(&deposit_impact(1_000)));
+        let withdraw_impact = |amount| {
+            quoter
+                .withdraw_single_token_type_exact_amount_out(TradeDirection::BtoA, amount, true)
+                .unwrap()
+                .price_impact
+                .unwrap()
+        };
+        assert!(withdraw_impact(400_000).greater_than(&withdraw_impact(4_000)));
+    }
+
+    #[test]
+    fn withdraw_fee() {
+        let token_swap = constant_product_swap();
+        let quoter = Quoter::new(&token_swap, 1_000_000, 4_000_000, 1_000_000);
+        let quote = quoter.withdraw_all_token_types(10_000, true).unwrap();
+        assert_eq!(quote.fees.owner_fee, 100);
+        assert_eq!(quote.token_a_amount, 9_900);
+        assert_eq!(quote.token_b_amount, 39_600);
+        let quote = quoter.withdraw_all_token_types(10_000, false).unwrap();
+        assert_eq!(quote.fees, QuoteFees::default());
+        assert_eq!(quote.token_a_amount, 10_000);
+
+        let quote = quoter
+            .withdraw_single_token_type_exact_amount_out(TradeDirection::AtoB, 10_000, true)
+            .unwrap();
+        let fee_free_quote = quoter
+            .withdraw_single_token_type_exact_amount_out(TradeDirection::AtoB, 10_000, false)
+            .unwrap();
+        assert_eq!(fee_free_quote.fees.owner_fee, 0);
+        assert_eq!(
+            quote.pool_token_amount,
+            fee_free_quote.pool_token_amount + quote.fees.owner_fee
+        );
+    }
+
+    #[test]
+    fn zero_amounts() {
+        let token_swap = constant_product_swap();
+        let quoter = Quoter::new(&token_swap, 1_000_000, 4_000_000, 1_000_000);
+        assert_eq!(
+            quoter.deposit_all_token_types(0),
+            Err(SwapError::ZeroTradingTokens)
+        );
+        assert_eq!(
+            quoter.withdraw_all_token_types(0, true),
+            Err(SwapError::ZeroTradingTokens)
+        );
+        assert_eq!(
+            quoter.deposit_single_token_type_exact_amount_in(TradeDirection::AtoB, 1),
+            Err(SwapError::ZeroTradingTokens)
+        );
+    }
+
+    #[test]
+    fn concentrated_liquidity_unsupported() {
+        let token_swap = SwapV1 {
+            swap_curve: SwapCurve {
+                curve_type: CurveType::ConcentratedLiquidity,
+                calculator: Box::new(ConcentratedLiquidityCurve::default()),
+            },
+            ..constant_product_swap()
+        };
+        let quoter = Quoter::new(&token_swap, 1_000_000, 4_000_000, 1_000_000);
+        assert_eq!(
+            quoter.swap(TradeDirection::AtoB, 1_000, false),
+            Err(SwapError::UnsupportedCurveOperation)
+        );
+        assert_eq!(
+            quoter.deposit_all_token_types(1_000),
+            Err(SwapError::UnsupportedCurveOperation)
+        );
+        assert_eq!(
+            quoter.withdraw_all_token_types(1_000, true),
+            Err(SwapError::UnsupportedCurveOperation)
+        );
+        assert_eq!(
+            quoter.withdraw_single_token_type_exact_amount_out(TradeDirection::AtoB, 1_000, true),
+            Err(SwapError::UnsupportedCurveOperation)
+        );
+    }
+}
@@ -1,10 +1,14 @@
 //! State transition types
 
-use crate::curve::{base::SwapCurve, fees::Fees};
+use crate::{
+    curve::{base::SwapCurve, dynamic_fee::DynamicFee, fees::Fees, weighted},
+    oracle::PriceAccumulator,
+};
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use enum_dispatch::enum_dispatch;
 use solana_program::{
     program_error::ProgramError,
+    program_option::COption,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
@@ -37,13 +41,26 @@ pub trait SwapState {
     fn fees(&self) -> &Fees;
     /// Curve associated with swap
     fn swap_curve(&self) -> &SwapCurve;
+
+    /// Authority which can update the fees and pause the swap, if any
+    fn admin_authority(&self) -> Option<&Pubkey>;
+    /// Are swaps and deposits paused
+    fn is_paused(&self) -> bool;
+    /// Cumulative prices of the swap, if they are tracked
+    fn price_accumulator(&self) -> Option<&PriceAccumulator>;
+    /// Volatility-driven trade fee of the swap, if it is tracked
+    fn dynamic_fee(&self) -> Option<&DynamicFee>;
+    /// Fees charged by the next trade, with the dynamic trade fee if enabled
+    fn current_fees(&self) -> Fees;
 }
 
 /// All versions of SwapState
 #[enum_dispatch(SwapState)]
 pub enum SwapVersion {
-    /// Latest version, used for all new swaps
+    /// Original version, without an admin authority
     SwapV1,
+    /// Latest version, used for all new swaps
+    SwapV2,
 }
 
 /// SwapVersion does not implement program_pack::Pack because there are size
@@ -51,7 +68,7 @@ pub enum SwapVersion {
 /// special implementations are provided here
 impl SwapVersion {
     /// Size of the latest version of the SwapState
-    pub const LATEST_LEN: usize = 1 + SwapV1::LEN; // add one for the version enum
+    pub const LATEST_LEN: usize = 1 + SwapV2::LEN; // add one for the version enum
 
     /// Pack a swap into a byte array, based on its version
     pub fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
@@ -60,6 +77,14 @@ impl SwapVersion {
                 dst[0] = 1;
                 SwapV1::pack(swap_info, &mut dst[1..])
             }
+            Self::SwapV2(swap_info) => {
+                // concentrated liquidity pools keep their state after the swap
+                let dst = dst
+                    .get_mut(..Self::LATEST_LEN)
+                    .ok_or(ProgramError::InvalidAccountData)?;
+                dst[0] = 2;
+                SwapV2::pack(swap_info, &mut dst[1..])
+            }
         }
     }
 
@@ -71,6 +96,10 @@ impl SwapVersion {
             .ok_or(ProgramError::InvalidAccountData)?;
         match version {
             1 => Ok(Box::new(SwapV1::unpack(rest)?)),
+            2 => Ok(Box::new(SwapV2::unpack(
+                rest.get(..SwapV2::LEN)
+                    .ok_or(ProgramError::InvalidAccountData)?,
+            )?)),
             _ => Err(ProgramError::UninitializedAccount),
         }
     }
@@ -170,6 +199,26 @@ impl SwapState for SwapV1 {
     fn swap_curve(&self) -> &SwapCurve {
         &self.swap_curve
     }
+
+    fn admin_authority(&self) -> Option<&Pubkey> {
+        None
+    }
+
+    fn is_paused(&self) -> bool {
+        false
+    }
+
+    fn price_accumulator(&self) -> Option<&PriceAccumulator> {
+        None
+    }
+
+    fn dynamic_fee(&self) -> Option<&DynamicFee> {
+        None
+    }
+
+    fn current_fees(&self) -> Fees {
+        self.fees.clone()
+    }
 }
 
 impl Sealed for SwapV1 {}
@@ -247,10 +296,436 @@ impl Pack for SwapV1 {
     }
 }
 
+/// Program states, with an admin authority which can update the fees and
+/// pause swaps and deposits.
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct SwapV2 {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Nonce used in program address.
+    /// The program address is created deterministically with the nonce,
+    /// swap program id, and swap account pubkey.  This program address has
+    /// authority over the swap's token A account, token B account, and pool
+    /// token mint.
+    pub nonce: u8,
+
+    /// Program ID of the tokens being exchanged.
+    pub token_program_id: Pubkey,
+
+    /// Token A
+    pub token_a: Pubkey,
+    /// Token B
+    pub token_b: Pubkey,
+
+    /// Pool tokens are issued when A or B tokens are deposited.
+    /// Pool tokens can be withdrawn back to the original A or B token.
+    pub pool_mint: Pubkey,
+
+    /// Mint information for token A
+    pub token_a_mint: Pubkey,
+    /// Mint information for token B
+    pub token_b_mint: Pubkey,
+
+    /// Pool token account to receive trading and / or withdrawal fees
+    pub pool_fee_account: Pubkey,
+
+    /// All fee information
+    pub fees: Fees,
+
+    /// Swap curve parameters, to be unpacked and used by the SwapCurve, which
+    /// calculates swaps, deposits, and withdrawals
+    pub swap_curve: SwapCurve,
+
+    /// Authority which can update the fees and pause the swap.  Without an
+    /// admin authority the fees are fixed and the swap can never be paused.
+    pub admin_authority: COption<Pubkey>,
+
+    /// Swaps and deposits are rejected while paused, withdrawals stay open
+    pub is_paused: bool,
+
+    /// Cumulative prices, updated by swaps, deposits and withdrawals
+    pub price_accumulator: PriceAccumulator,
+
+    /// Trade fee rising with the volatility of the pool price, disabled
+    /// unless configured by the admin authority
+    pub dynamic_fee: DynamicFee,
+
+    /// Amount of token A held by the swap when the last instruction settled
+    pub last_token_a_amount: u64,
+    /// Amount of token B held by the swap when the last instruction settled.
+    /// Together with the token A amount, it sets the price accumulated until
+    /// the next instruction, so tokens sent to the swap in between do not
+    /// move the cumulative prices.
+    pub last_token_b_amount: u64,
+}
+
+impl SwapState for SwapV2 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+
+    fn nonce(&self) -> u8 {
+        self.nonce
+    }
+
+    fn token_program_id(&self) -> &Pubkey {
+        &self.token_program_id
+    }
+
+    fn token_a_account(&self) -> &Pubkey {
+        &self.token_a
+    }
+
+    fn token_b_account(&self) -> &Pubkey {
+        &self.token_b
+    }
+
+    fn pool_mint(&self) -> &Pubkey {
+        &self.pool_mint
+    }
+
+    fn token_a_mint(&self) -> &Pubkey {
+        &self.token_a_mint
+    }
+
+    fn token_b_mint(&self) -> &Pubkey {
+        &self.token_b_mint
+    }
+
+    fn pool_fee_account(&self) -> &Pubkey {
+        &self.pool_fee_account
+    }
+
+    fn fees(&self) -> &Fees {
+        &self.fees
+    }
+
+    fn swap_curve(&self) -> &SwapCurve {
+        &self.swap_curve
+    }
+
+    fn admin_authority(&self) -> Option<&Pubkey> {
+        match &self.admin_authority {
+            COption::Some(admin_authority) => Some(admin_authority),
+            COption::None => None,
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    fn price_accumulator(&self) -> Option<&PriceAccumulator> {
+        Some(&self.price_accumulator)
+    }
+
+    fn dynamic_fee(&self) -> Option<&DynamicFee> {
+        Some(&self.dynamic_fee)
+    }
+
+    fn current_fees(&self) -> Fees {
+        self.dynamic_fee.apply(&self.fees)
+    }
+}
+
+impl Sealed for SwapV2 {}
+impl IsInitialized for SwapV2 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for SwapV2 {
+    const LEN: usize = 488;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 488];
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            pool_fee_account,
+            fees,
+            swap_curve,
+            admin_authority,
+            is_paused,
+            price_accumulator,
+            dynamic_fee,
+            last_token_a_amount,
+            last_token_b_amount,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 32, 32, 32, 32, 32, 64, 33, 36, 1, 40, 72, 8, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        nonce[0] = self.nonce;
+        token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        token_a.copy_from_slice(self.token_a.as_ref());
+        token_b.copy_from_slice(self.token_b.as_ref());
+        pool_mint.copy_from_slice(self.pool_mint.as_ref());
+        token_a_mint.copy_from_slice(self.token_a_mint.as_ref());
+        token_b_mint.copy_from_slice(self.token_b_mint.as_ref());
+        pool_fee_account.copy_from_slice(self.pool_fee_account.as_ref());
+        self.fees.pack_into_slice(&mut fees[..]);
+        self.swap_curve.pack_into_slice(&mut swap_curve[..]);
+        pack_coption_key(&self.admin_authority, admin_authority);
+        is_paused[0] = self.is_paused as u8;
+        self.price_accumulator
+            .pack_into_slice(&mut price_accumulator[..]);
+        self.dynamic_fee.pack_into_slice(&mut dynamic_fee[..]);
+        *last_token_a_amount = self.last_token_a_amount.to_le_bytes();
+        *last_token_b_amount = self.last_token_b_amount.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [SwapV2](struct.SwapV2.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 488];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            pool_fee_account,
+            fees,
+            swap_curve,
+            admin_authority,
+            is_paused,
+            price_accumulator,
+            dynamic_fee,
+            last_token_a_amount,
+            last_token_b_amount,
+        ) = array_refs![input, 1, 1, 32, 32, 32, 32, 32, 32, 32, 64, 33, 36, 1, 40, 72, 8, 8];
+        Ok(Self {
+            is_initialized: unpack_bool(is_initialized)?,
+            nonce: nonce[0],
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            token_a: Pubkey::new_from_array(*token_a),
+            token_b: Pubkey::new_from_array(*token_b),
+            pool_mint: Pubkey::new_from_array(*pool_mint),
+            token_a_mint: Pubkey::new_from_array(*token_a_mint),
+            token_b_mint: Pubkey::new_from_array(*token_b_mint),
+            pool_fee_account: Pubkey::new_from_array(*pool_fee_account),
+            fees: Fees::unpack_from_slice(fees)?,
+            swap_curve: SwapCurve::unpack_from_slice(swap_curve)?,
+            admin_authority: unpack_coption_key(admin_authority)?,
+            is_paused: unpack_bool(is_paused)?,
+            price_accumulator: PriceAccumulator::unpack_from_slice(price_accumulator)?,
+            dynamic_fee: DynamicFee::unpack_from_slice(dynamic_fee)?,
+            last_token_a_amount: u64::from_le_bytes(*last_token_a_amount),
+            last_token_b_amount: u64::from_le_bytes(*last_token_b_amount),
+        })
+    }
+}
+
+/// Minimum number of tokens held by a weighted pool
+pub const MIN_WEIGHTED_POOL_TOKENS: usize = 2;
+/// Maximum number of tokens held by a weighted pool
+pub const MAX_WEIGHTED_POOL_TOKENS: usize = 8;
+
+/// A token held by a weighted pool
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WeightedPoolToken {
+    /// Address of the token account holding the pool liquidity
+    pub account: Pubkey,
+    /// Address of the token mint
+    pub mint: Pubkey,
+    /// Weight of the token, relative to the other tokens of the pool
+    pub weight: u64,
+}
+
+impl WeightedPoolToken {
+    const LEN: usize = 72;
+}
+
+/// Weighted pool state, for pools of two to eight tokens, where each token
+/// makes up a fixed share of the pool value.
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct WeightedPool {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Nonce used in program address.
+    /// The program address is created deterministically with the nonce,
+    /// weighted pool program id, and weighted pool account pubkey.  This
+    /// program address has authority over the pool's token accounts and pool
+    /// token mint.
+    pub nonce: u8,
+
+    /// Program ID of the tokens being exchanged.
+    pub token_program_id: Pubkey,
+
+    /// Pool tokens are issued when tokens are deposited.
+    /// Pool tokens can be withdrawn back to the original tokens.
+    pub pool_mint: Pubkey,
+
+    /// Pool token account to receive trading and / or withdrawal fees
+    pub pool_fee_account: Pubkey,
+
+    /// All fee information
+    pub fees: Fees,
+
+    /// Tokens held by the pool, along with their weights
+    pub tokens: Vec<WeightedPoolToken>,
+}
+
+impl WeightedPool {
+    /// Sum of the weights of all tokens in the pool
+    pub fn total_weight(&self) -> Option<u128> {
+        let weights: Vec<u64> = self.tokens.iter().map(|token| token.weight).collect();
+        weighted::total_weight(&weights)
+    }
+
+    /// Find the pool token held in the given token account
+    pub fn find_token(&self, account: &Pubkey) -> Option<&WeightedPoolToken> {
+        self.tokens.iter().find(|token| token.account == *account)
+    }
+}
+
+impl Sealed for WeightedPool {}
+impl IsInitialized for WeightedPool {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for WeightedPool {
+    const LEN: usize = 163 + MAX_WEIGHTED_POOL_TOKENS * WeightedPoolToken::LEN;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, WeightedPool::LEN];
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            pool_mint,
+            pool_fee_account,
+            fees,
+            token_count,
+            tokens,
+        ) = mut_array_refs![
+            output,
+            1,
+            1,
+            32,
+            32,
+            32,
+            64,
+            1,
+            MAX_WEIGHTED_POOL_TOKENS * WeightedPoolToken::LEN
+        ];
+        is_initialized[0] = self.is_initialized as u8;
+        nonce[0] = self.nonce;
+        token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        pool_mint.copy_from_slice(self.pool_mint.as_ref());
+        pool_fee_account.copy_from_slice(self.pool_fee_account.as_ref());
+        self.fees.pack_into_slice(&mut fees[..]);
+        token_count[0] = self.tokens.len() as u8;
+        let default_token = WeightedPoolToken::default();
+        let tokens_or_default = self.tokens.iter().chain(std::iter::repeat(&default_token));
+        for (token, output) in tokens_or_default.zip(tokens.chunks_mut(WeightedPoolToken::LEN)) {
+            let output = array_mut_ref![output, 0, WeightedPoolToken::LEN];
+            let (account, mint, weight) = mut_array_refs![output, 32, 32, 8];
+            account.copy_from_slice(token.account.as_ref());
+            mint.copy_from_slice(token.mint.as_ref());
+            *weight = token.weight.to_le_bytes();
+        }
+    }
+
+    /// Unpacks a byte buffer into a [WeightedPool](struct.WeightedPool.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, WeightedPool::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            pool_mint,
+            pool_fee_account,
+            fees,
+            token_count,
+            tokens,
+        ) = array_refs![
+            input,
+            1,
+            1,
+            32,
+            32,
+            32,
+            64,
+            1,
+            MAX_WEIGHTED_POOL_TOKENS * WeightedPoolToken::LEN
+        ];
+        let token_count = token_count[0] as usize;
+        if token_count > MAX_WEIGHTED_POOL_TOKENS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self {
+            is_initialized: unpack_bool(is_initialized)?,
+            nonce: nonce[0],
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            pool_mint: Pubkey::new_from_array(*pool_mint),
+            pool_fee_account: Pubkey::new_from_array(*pool_fee_account),
+            fees: Fees::unpack_from_slice(fees)?,
+            tokens: tokens
+                .chunks(WeightedPoolToken::LEN)
+                .take(token_count)
+                .map(|input| {
+                    let input = array_ref![input, 0, WeightedPoolToken::LEN];
+                    let (account, mint, weight) = array_refs![input, 32, 32, 8];
+                    WeightedPoolToken {
+                        account: Pubkey::new_from_array(*account),
+                        mint: Pubkey::new_from_array(*mint),
+                        weight: u64::from_le_bytes(*weight),
+                    }
+                })
+                .collect(),
+        })
+    }
+}
+
+fn unpack_bool(src: &[u8; 1]) -> Result<bool, ProgramError> {
+    match src {
+        [0] => Ok(false),
+        [1] => Ok(true),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+fn pack_coption_key(src: &COption<Pubkey>, dst: &mut [u8; 36]) {
+    let (tag, body) = mut_array_refs![dst, 4, 32];
+    match src {
+        COption::Some(key) => {
+            *tag = [1, 0, 0, 0];
+            body.copy_from_slice(key.as_ref());
+        }
+        COption::None => {
+            *tag = [0; 4];
+        }
+    }
+}
+
+fn unpack_coption_key(src: &[u8; 36]) -> Result<COption<Pubkey>, ProgramError> {
+    let (tag, body) = array_refs![src, 4, 32];
+    match *tag {
+        [0, 0, 0, 0] => Ok(COption::None),
+        [1, 0, 0, 0] => Ok(COption::Some(Pubkey::new_from_array(*body))),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::curve::stable::StableCurve;
+    use crate::curve::{dynamic_fee::DynamicFeeParameters, stable::StableCurve};
 
     use std::convert::TryInto;
 
@@ -273,6 +748,24 @@ mod tests {
     const TEST_TOKEN_A_MINT: Pubkey = Pubkey::new_from_array([5u8; 32]);
     const TEST_TOKEN_B_MINT: Pubkey = Pubkey::new_from_array([6u8; 32]);
     const TEST_POOL_FEE_ACCOUNT: Pubkey = Pubkey::new_from_array([7u8; 32]);
+    const TEST_ADMIN_AUTHORITY: Pubkey = Pubkey::new_from_array([8u8; 32]);
+    const TEST_PRICE_ACCUMULATOR: PriceAccumulator = PriceAccumulator {
+        last_update_slot: 100,
+        price_a_cumulative: 1 << 70,
+        price_b_cumulative: 1 << 58,
+    };
+    const TEST_DYNAMIC_FEE: DynamicFee = DynamicFee {
+        parameters: DynamicFeeParameters {
+            min_fee_numerator: 25,
+            max_fee_numerator: 100,
+            fee_denominator: 10_000,
+            volatility_multiplier: 5_000,
+            decay_period: 50,
+        },
+        volatility: 1_000,
+        reference_price: 1 << 64,
+        last_update_slot: 90,
+    };
 
     const TEST_CURVE_TYPE: u8 = 2;
     const TEST_AMP: u64 = 1;
@@ -300,7 +793,7 @@ mod tests {
             swap_curve: swap_curve.clone(),
         });
 
-        let mut packed = [0u8; SwapVersion::LATEST_LEN];
+        let mut packed = [0u8; 1 + SwapV1::LEN];
         SwapVersion::pack(swap_info, &mut packed).unwrap();
         let unpacked = SwapVersion::unpack(&packed).unwrap();
 
@@ -315,6 +808,183 @@ mod tests {
         assert_eq!(*unpacked.pool_fee_account(), TEST_POOL_FEE_ACCOUNT);
         assert_eq!(*unpacked.fees(), TEST_FEES);
         assert_eq!(*unpacked.swap_curve(), swap_curve);
+        assert_eq!(unpacked.admin_authority(), None);
+        assert_eq!(unpacked.is_paused(), false);
+        assert_eq!(unpacked.price_accumulator(), None);
+        assert_eq!(unpacked.dynamic_fee(), None);
+        assert_eq!(unpacked.current_fees(), TEST_FEES);
+
+        let swap_info = SwapVersion::SwapV2(SwapV2 {
+            is_initialized: true,
+            nonce: TEST_NONCE,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            token_a: TEST_TOKEN_A,
+            token_b: TEST_TOKEN_B,
+            pool_mint: TEST_POOL_MINT,
+            token_a_mint: TEST_TOKEN_A_MINT,
+            token_b_mint: TEST_TOKEN_B_MINT,
+            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
+            fees: TEST_FEES,
+            swap_curve: swap_curve.clone(),
+            admin_authority: COption::Some(TEST_ADMIN_AUTHORITY),
+            is_paused: true,
+            price_accumulator: TEST_PRICE_ACCUMULATOR,
+            dynamic_fee: TEST_DYNAMIC_FEE,
+            last_token_a_amount: 1_000,
+            last_token_b_amount: 2_000,
+        });
+
+        let mut packed = [0u8; SwapVersion::LATEST_LEN];
+        SwapVersion::pack(swap_info, &mut packed).unwrap();
+        assert_eq!(packed[0], 2);
+        let unpacked = SwapVersion::unpack(&packed).unwrap();
+
+        assert_eq!(unpacked.is_initialized(), true);
+        assert_eq!(*unpacked.pool_mint(), TEST_POOL_MINT);
+        assert_eq!(*unpacked.fees(), TEST_FEES);
+        assert_eq!(*unpacked.swap_curve(), swap_curve);
+        assert_eq!(unpacked.admin_authority(), Some(&TEST_ADMIN_AUTHORITY));
+        assert_eq!(unpacked.is_paused(), true);
+        assert_eq!(unpacked.price_accumulator(), Some(&TEST_PRICE_ACCUMULATOR));
+        assert_eq!(unpacked.dynamic_fee(), Some(&TEST_DYNAMIC_FEE));
+        assert_eq!(unpacked.current_fees(), TEST_DYNAMIC_FEE.apply(&TEST_FEES));
+    }
+
+    #[test]
+    fn swap_v2_pack() {
+        let curve_type = TEST_CURVE_TYPE.try_into().unwrap();
+        let calculator = Box::new(TEST_CURVE);
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator,
+        };
+        let swap_info = SwapV2 {
+            is_initialized: true,
+            nonce: TEST_NONCE,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            token_a: TEST_TOKEN_A,
+            token_b: TEST_TOKEN_B,
+            pool_mint: TEST_POOL_MINT,
+            token_a_mint: TEST_TOKEN_A_MINT,
+            token_b_mint: TEST_TOKEN_B_MINT,
+            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
+            fees: TEST_FEES,
+            swap_curve,
+            admin_authority: COption::Some(TEST_ADMIN_AUTHORITY),
+            is_paused: false,
+            price_accumulator: TEST_PRICE_ACCUMULATOR,
+            dynamic_fee: TEST_DYNAMIC_FEE,
+            last_token_a_amount: 1_000,
+            last_token_b_amount: 2_000,
+        };
+
+        let mut packed = [0u8; SwapV2::LEN];
+        SwapV2::pack_into_slice(&swap_info, &mut packed);
+        let unpacked = SwapV2::unpack(&packed).unwrap();
+        assert_eq!(swap_info, unpacked);
+
+        // the admin authority, pause flag, price accumulator, dynamic fee and
+        // last swap token amounts are appended to the SwapV1 layout
+        let v1_info = SwapV1 {
+            is_initialized: true,
+            nonce: TEST_NONCE,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            token_a: TEST_TOKEN_A,
+            token_b: TEST_TOKEN_B,
+            pool_mint: TEST_POOL_MINT,
+            token_a_mint: TEST_TOKEN_A_MINT,
+            token_b_mint: TEST_TOKEN_B_MINT,
+            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
+            fees: TEST_FEES,
+            swap_curve: swap_info.swap_curve.clone(),
+        };
+        let mut expected = vec![0u8; SwapV1::LEN];
+        SwapV1::pack_into_slice(&v1_info, &mut expected);
+        expected.extend_from_slice(&[1, 0, 0, 0]);
+        expected.extend_from_slice(&TEST_ADMIN_AUTHORITY.to_bytes());
+        expected.push(0);
+        expected.extend_from_slice(&TEST_PRICE_ACCUMULATOR.last_update_slot.to_le_bytes());
+        expected.extend_from_slice(&TEST_PRICE_ACCUMULATOR.price_a_cumulative.to_le_bytes());
+        expected.extend_from_slice(&TEST_PRICE_ACCUMULATOR.price_b_cumulative.to_le_bytes());
+        let mut dynamic_fee = [0u8; DynamicFee::LEN];
+        TEST_DYNAMIC_FEE.pack_into_slice(&mut dynamic_fee);
+        expected.extend_from_slice(&dynamic_fee);
+        expected.extend_from_slice(&1_000u64.to_le_bytes());
+        expected.extend_from_slice(&2_000u64.to_le_bytes());
+        assert_eq!(packed.to_vec(), expected);
+
+        let mut invalid = packed;
+        invalid[SwapV2::LEN - 16 - DynamicFee::LEN - PriceAccumulator::LEN - 1] = 2;
+        let err = SwapV2::unpack(&invalid).unwrap_err();
+        assert_eq!(err, ProgramError::InvalidAccountData);
+
+        let packed = [0u8; SwapV2::LEN];
+        let swap_info: SwapV2 = Default::default();
+        let unpack_unchecked = SwapV2::unpack_unchecked(&packed).unwrap();
+        assert_eq!(unpack_unchecked, swap_info);
+        let err = SwapV2::unpack(&packed).unwrap_err();
+        assert_eq!(err, ProgramError::UninitializedAccount);
+    }
+
+    #[test]
+    fn weighted_pool_pack() {
+        let pool_info = WeightedPool {
+            is_initialized: true,
+            nonce: TEST_NONCE,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            pool_mint: TEST_POOL_MINT,
+            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
+            fees: TEST_FEES,
+            tokens: vec![
+                WeightedPoolToken {
+                    account: TEST_TOKEN_A,
+                    mint: TEST_TOKEN_A_MINT,
+                    weight: 80,
+                },
+                WeightedPoolToken {
+                    account: TEST_TOKEN_B,
+                    mint: TEST_TOKEN_B_MINT,
+                    weight: 20,
+                },
+            ],
+        };
+
+        let mut packed = [0u8; WeightedPool::LEN];
+        WeightedPool::pack_into_slice(&pool_info, &mut packed);
+        let unpacked = WeightedPool::unpack(&packed).unwrap();
+        assert_eq!(pool_info, unpacked);
+        assert_eq!(unpacked.total_weight(), Some(100));
+        assert_eq!(unpacked.find_token(&TEST_TOKEN_B).unwrap().weight, 20);
+        assert_eq!(unpacked.find_token(&TEST_POOL_MINT), None);
+
+        let mut expected = vec![1u8, TEST_NONCE];
+        expected.extend_from_slice(&TEST_TOKEN_PROGRAM_ID.to_bytes());
+        expected.extend_from_slice(&TEST_POOL_MINT.to_bytes());
+        expected.extend_from_slice(&TEST_POOL_FEE_ACCOUNT.to_bytes());
+        let mut fees = [0u8; Fees::LEN];
+        TEST_FEES.pack_into_slice(&mut fees);
+        expected.extend_from_slice(&fees);
+        expected.push(2);
+        expected.extend_from_slice(&TEST_TOKEN_A.to_bytes());
+        expected.extend_from_slice(&TEST_TOKEN_A_MINT.to_bytes());
+        expected.extend_from_slice(&80u64.to_le_bytes());
+        expected.extend_from_slice(&TEST_TOKEN_B.to_bytes());
+        expected.extend_from_slice(&TEST_TOKEN_B_MINT.to_bytes());
+        expected.extend_from_slice(&20u64.to_le_bytes());
+        expected.resize(WeightedPool::LEN, 0);
+        assert_eq!(packed.to_vec(), expected);
+
+        let mut invalid = packed;
+        invalid[163 - 1] = MAX_WEIGHTED_POOL_TOKENS as u8 + 1;
+        let err = WeightedPool::unpack(&invalid).unwrap_err();
+        assert_eq!(err, ProgramError::InvalidAccountData);
+
+        let packed = [0u8; WeightedPool::LEN];
+        let pool_info: WeightedPool = Default::default();
+        let unpack_unchecked = WeightedPool::unpack_unchecked(&packed).unwrap();
+        assert_eq!(unpack_unchecked, pool_info);
+        let err = WeightedPool::unpack(&packed).unwrap_err();
+        assert_eq!(err, ProgramError::UninitializedAccount);
     }
 
     #[test]